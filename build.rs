@@ -0,0 +1,43 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    ffi_header();
+}
+
+/// Generate the C header for the `ffi` module at `$OUT_DIR/tilr.h`.
+#[cfg(feature = "ffi")]
+fn ffi_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    // the FFI tests need the target triple to find a C compiler
+    println!(
+        "cargo:rustc-env=TILR_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Unable to read cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate C header")
+        .write_to_file(format!("{}/tilr.h", out_dir));
+}
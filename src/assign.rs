@@ -0,0 +1,92 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// Assign each of `rows` rows to a distinct one of `cols` columns, so that
+/// the total cost of the chosen entries is as small as possible.
+///
+/// `costs` holds the cost of assigning each row to each column, in
+/// row-major order. Returns the column assigned to each row.
+///
+/// This is the Hungarian algorithm (with potentials), which takes
+/// `O(rows² · cols)` time, so it is only practical for a few thousand rows.
+///
+/// # Panics
+/// This function panics if there are more rows than columns, or if
+/// `costs` does not hold `rows * cols` entries.
+pub(crate) fn assign(costs: &[f32], rows: usize, cols: usize) -> Vec<usize> {
+    assert!(rows <= cols, "Every row must have a column of its own");
+    assert_eq!(costs.len(), rows * cols);
+    let cost = |i: usize, j: usize| costs[(i - 1) * cols + (j - 1)] as f64;
+
+    // rows and columns are numbered from 1 below, with column 0 standing
+    // in for the row being added
+    let mut u = vec![0.0; rows + 1];
+    let mut v = vec![0.0; cols + 1];
+    // the row assigned to each column (0 if none)
+    let mut owner = vec![0; cols + 1];
+    // the previous column on the augmenting path to each column
+    let mut way = vec![0; cols + 1];
+    for i in 1..=rows {
+        owner[0] = i;
+        let mut j0 = 0;
+        let mut min_slack = vec![f64::INFINITY; cols + 1];
+        let mut used = vec![false; cols + 1];
+        loop {
+            used[j0] = true;
+            let i0 = owner[j0];
+            let (mut delta, mut j1) = (f64::INFINITY, 0);
+            for j in 1..=cols {
+                if used[j] {
+                    continue;
+                }
+                let slack = cost(i0, j) - u[i0] - v[j];
+                if slack < min_slack[j] {
+                    min_slack[j] = slack;
+                    way[j] = j0;
+                }
+                if min_slack[j] < delta {
+                    delta = min_slack[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=cols {
+                if used[j] {
+                    u[owner[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_slack[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if owner[j0] == 0 {
+                break;
+            }
+        }
+        // follow the augmenting path back to the new row
+        while j0 != 0 {
+            let j1 = way[j0];
+            owner[j0] = owner[j1];
+            j0 = j1;
+        }
+    }
+
+    let mut assignment = vec![0; rows];
+    for (j, &i) in owner.iter().enumerate().skip(1) {
+        if i != 0 {
+            assignment[i - 1] = j - 1;
+        }
+    }
+    assignment
+}
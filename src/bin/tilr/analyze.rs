@@ -0,0 +1,101 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::{info, warn};
+use std::path::PathBuf;
+use tilr::{ColorHistogram, DiversityCheck, Metric, TileSet};
+
+use crate::{print_diversity_warnings, print_load_summary, TileLoadArgs};
+
+// The arguments for the `analyze` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// The number of divisions along each axis of the RGB color cube
+    /// when measuring coverage.
+    #[clap(long, default_value = "16")]
+    divisions: u32,
+
+    /// The number of poorly-covered regions to list.
+    #[clap(long, default_value = "10")]
+    gaps: usize,
+
+    /// Path at which to save a false-color image of the coverage
+    /// (one square per slice of the RGB cube along the blue axis;
+    /// red regions are poorly covered).
+    #[clap(long, value_parser)]
+    coverage_image: Option<PathBuf>,
+
+    /// Path to a source image, to check whether the tiles' colors
+    /// cover its colors.
+    #[clap(long, value_parser)]
+    source: Option<PathBuf>,
+
+    /// Warn if the tiles' average colors fall short of the source's colors
+    /// by more than this (0-255) at either end of any channel.
+    #[clap(long, value_name = "0..255", default_value_t = DiversityCheck::default().margin)]
+    diversity_margin: u8,
+}
+
+/// Analyze a tile set
+pub fn run(args: Args) {
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    print_load_summary(&report, &args.tile_load.tile_dir);
+    if report.tiles.is_empty() {
+        warn!("No tiles to analyze.");
+        return;
+    }
+
+    let tiles = crate::flatten_tiles(report.tiles, args.tile_load.tile_background);
+    let tiles = TileSet::from(&tiles);
+    let summary = tiles.summary();
+    println!("{}", summary);
+
+    let histogram = args.source.map(|path| {
+        let img = tilr::load_oriented(&path).expect("Unable to read source image.");
+        ColorHistogram::new(&img.to_rgb8())
+    });
+    let check = DiversityCheck {
+        margin: args.diversity_margin,
+        ..Default::default()
+    };
+    print_diversity_warnings(&check.check(&summary, histogram.as_ref()));
+
+    let coverage = tiles.coverage_report(args.divisions, Metric::default());
+
+    println!("Color coverage (distance to the nearest tile):");
+    for p in [50.0, 90.0, 99.0, 100.0] {
+        println!("  p{:<3}  {:7.2}", p, coverage.percentile(p));
+    }
+    println!("Least covered colors:");
+    for gap in coverage.emptiest(args.gaps) {
+        let [r, g, b] = gap.center;
+        println!("  #{:02x}{:02x}{:02x}  {:7.2}", r, g, b, gap.distance);
+    }
+
+    if let Some(path) = args.coverage_image {
+        info!("Saving coverage image to {}...", path.display());
+        coverage
+            .to_image(4)
+            .save(path)
+            .expect("Error saving coverage image.");
+    }
+}
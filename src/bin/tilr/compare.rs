@@ -0,0 +1,75 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use tilr::MosaicPlan;
+
+// The arguments for the `compare` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// The first mosaic plan (as saved with --sidecar) or image.
+    #[clap(value_parser)]
+    a: PathBuf,
+
+    /// The second mosaic plan or image, to compare against the first.
+    #[clap(value_parser)]
+    b: PathBuf,
+
+    /// Path at which to save an image highlighting the differences
+    /// (changed cells or pixels in red, unchanged ones in blue).
+    #[clap(long, value_parser)]
+    diff_image: Option<PathBuf>,
+
+    /// With plans, the side length of each cell in the --diff-image
+    /// (in pixels).
+    #[clap(long, default_value = "8")]
+    cell_px: u32,
+}
+
+/// Compare two mosaic plans or images
+pub fn run(args: Args) {
+    let diff_image = if is_plan(&args.a) && is_plan(&args.b) {
+        let a = MosaicPlan::load(&args.a).expect("Error loading plan");
+        let b = MosaicPlan::load(&args.b).expect("Error loading plan");
+        let diff = a.compare(&b).unwrap_or_else(|e| exit(e));
+        println!("{}", diff);
+        args.diff_image
+            .map(|path| (path, diff.to_image(args.cell_px)))
+    } else {
+        let a = tilr::load_oriented(&args.a).expect("Unable to read image file.");
+        let b = tilr::load_oriented(&args.b).expect("Unable to read image file.");
+        let diff = tilr::compare_images(&a.to_rgb8(), &b.to_rgb8()).unwrap_or_else(|e| exit(e));
+        println!("{}", diff);
+        args.diff_image.map(|path| (path, diff.to_image()))
+    };
+
+    if let Some((path, img)) = diff_image {
+        info!("Saving diff image to {}...", path.display());
+        img.save(path).expect("Error saving diff image.");
+    }
+}
+
+/// Check whether a path refers to a mosaic plan (rather than an image)
+fn is_plan(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// Report an error comparing the inputs and exit
+fn exit(e: Box<dyn std::error::Error>) -> ! {
+    error!("{}", e);
+    std::process::exit(1);
+}
@@ -0,0 +1,125 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// The config file read from the current directory when --config isn't given.
+pub const DEFAULT_PATH: &str = "tilr.toml";
+
+/// Why a config file couldn't be used
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Unreadable(std::io::Error),
+    /// The file isn't valid TOML.
+    Invalid(toml::de::Error),
+    /// The file sets an option which isn't a flag of `tilr`.
+    UnknownOption(String),
+    /// The file sets an option to a value it can't take (e.g., a table),
+    /// with a description of the values it can.
+    BadValue(String, &'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable(e) => write!(f, "unable to read the file ({})", e),
+            Self::Invalid(e) => write!(f, "invalid TOML ({})", e.message()),
+            Self::UnknownOption(key) => write!(f, "unknown option '{}'", key),
+            Self::BadValue(key, expected) => write!(f, "'{}' must be {}", key, expected),
+        }
+    }
+}
+
+/// Get the config file to read: the one given with --config, or
+/// `tilr.toml` in the current directory if there is one
+pub fn path(matches: &ArgMatches) -> Option<PathBuf> {
+    match matches.get_one::<PathBuf>("config") {
+        Some(path) => Some(path.clone()),
+        None => Some(PathBuf::from(DEFAULT_PATH)).filter(|p| p.is_file()),
+    }
+}
+
+/// Read the config file at `path` into the flags it sets for `cmd`, leaving
+/// out any which were already given on the command line (in `matches`), so
+/// they can be parsed along with the command line
+///
+/// Options are named like the long flags (e.g., `tile-size`, or
+/// `tile_size`). Flags are set with `true`, counted flags (like `verbose`)
+/// with a number, and flags which may be given more than once (like
+/// `tile-dir`) with an array.
+pub fn read(
+    path: &Path,
+    cmd: &Command,
+    matches: &ArgMatches,
+) -> Result<Vec<OsString>, ConfigError> {
+    let text = fs::read_to_string(path).map_err(ConfigError::Unreadable)?;
+    let table: Table = text.parse().map_err(ConfigError::Invalid)?;
+
+    let mut flags = Vec::new();
+    for (key, value) in table {
+        let long = key.replace('_', "-");
+        let arg = cmd
+            .get_arguments()
+            .find(|a| a.get_long() == Some(&long) && long != "config")
+            .ok_or_else(|| ConfigError::UnknownOption(key.clone()))?;
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+
+        let flag = format!("--{}", long);
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue, Value::Boolean(set)) => {
+                if set {
+                    flags.push(flag.into());
+                }
+            }
+            (ArgAction::Count, Value::Integer(n)) if n >= 0 => {
+                flags.extend(std::iter::repeat_n(OsString::from(&flag), n as usize));
+            }
+            (ArgAction::SetTrue, _) => return Err(ConfigError::BadValue(key, "true or false")),
+            (ArgAction::Count, _) => return Err(ConfigError::BadValue(key, "a count")),
+            (_, Value::Array(values)) => {
+                for value in values {
+                    let value = scalar(&key, value)?;
+                    flags.push(format!("{}={}", flag, value).into());
+                }
+            }
+            (_, value) => flags.push(format!("{}={}", flag, scalar(&key, value)?).into()),
+        }
+    }
+    Ok(flags)
+}
+
+/// Format the value of the option `key` as it would be given on the
+/// command line
+fn scalar(key: &str, value: Value) -> Result<String, ConfigError> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Float(x) => Ok(x.to_string()),
+        Value::Boolean(b) => Ok(b.to_string()),
+        _ => Err(ConfigError::BadValue(
+            key.to_string(),
+            "a string, number, boolean, or an array of them",
+        )),
+    }
+}
@@ -0,0 +1,634 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+use log::{error, info};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use tilr::{Metric, Mosaic, MosaicOptions, TileSet};
+
+use crate::{print_load_summary, TileLoadArgs};
+
+/// The largest size of the preview (in pixels)
+const PREVIEW_SIZE: (u32, u32) = (800, 600);
+
+/// The height of each control (slider or button) below the preview
+const CONTROL_HEIGHT: u32 = 24;
+
+/// The margin around each control (in pixels)
+const MARGIN: u32 = 6;
+
+// The arguments for the `gui` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Path to the original image.
+    #[clap(value_parser)]
+    src_image: PathBuf,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// The initial scaling to apply to the image before building the mosaic.
+    #[clap(short, long, default_value = "1.0")]
+    scale: f32,
+
+    /// The initial side length to use for the tiles (in pixels).
+    #[clap(long, default_value = "8")]
+    tile_size: u8,
+
+    /// Path at which to save the mosaic when rendering it in full.
+    #[clap(short, long, default_value = "mosaic.png", value_parser)]
+    output: PathBuf,
+}
+
+/// The metrics to choose between with the metric slider
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetricChoice {
+    /// Plain RGB distance (the default).
+    Rgb,
+    /// RGB distance weighted towards green and red, roughly following
+    /// their contributions to perceived brightness.
+    Luma,
+}
+
+impl MetricChoice {
+    /// Get the weights passed to --metric-weights for this metric, if any
+    fn weights(&self) -> Option<[f32; 3]> {
+        match self {
+            MetricChoice::Rgb => None,
+            MetricChoice::Luma => Some([3.0, 6.0, 1.0]),
+        }
+    }
+
+    /// Get the name of this metric
+    fn name(&self) -> &'static str {
+        match self {
+            MetricChoice::Rgb => "rgb",
+            MetricChoice::Luma => "luma",
+        }
+    }
+}
+
+/// The settings adjusted with the sliders
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Settings {
+    scale: f32,
+    tile_size: u8,
+    metric: MetricChoice,
+}
+
+impl Settings {
+    /// Get the options to build a mosaic with these settings
+    fn options(&self) -> MosaicOptions {
+        let mut options = MosaicOptions::default();
+        if let Some(weights) = self.metric.weights() {
+            options.metric = Metric::weighted_rgb(weights);
+        }
+        options
+    }
+
+    /// Get the arguments to `tilr` which build the mosaic with these settings
+    fn command_args(&self, args: &Args) -> Vec<String> {
+        let [r, g, b] = args.tile_load.tile_background.0;
+        let mut cmd = vec![
+            args.src_image.display().to_string(),
+            "--output".into(),
+            args.output.display().to_string(),
+            "--scale".into(),
+            self.scale.to_string(),
+            "--tile-size".into(),
+            self.tile_size.to_string(),
+            "--tile-background".into(),
+            format!("#{:02x}{:02x}{:02x}", r, g, b),
+        ];
+        for dir in &args.tile_load.tile_dir {
+            cmd.extend(["--tile-dir".into(), dir.display().to_string()]);
+        }
+        let load = &args.tile_load;
+        if load.follow_symlinks {
+            cmd.push("--follow-symlinks".into());
+        }
+        if load.keep_duplicates {
+            cmd.push("--keep-duplicates".into());
+        }
+        if let Some(dim) = load.min_tile_dim {
+            cmd.extend(["--min-tile-dim".into(), dim.to_string()]);
+        }
+        if load.recursive {
+            cmd.push("--recursive".into());
+        }
+        for pattern in &load.tile_include {
+            cmd.extend(["--tile-include".into(), pattern.to_string()]);
+        }
+        for pattern in &load.tile_exclude {
+            cmd.extend(["--tile-exclude".into(), pattern.to_string()]);
+        }
+        if let Some([r, g, b]) = self.metric.weights() {
+            cmd.extend(["--metric-weights".into(), format!("{},{},{}", r, g, b)]);
+        }
+        cmd
+    }
+
+    /// Get the value of the setting controlled by the slider with the given index
+    fn get(&self, slider: usize) -> f32 {
+        match slider {
+            0 => self.scale,
+            1 => self.tile_size as f32,
+            _ => (self.metric == MetricChoice::Luma) as u8 as f32,
+        }
+    }
+
+    /// Set the value of the setting controlled by the slider with the given index
+    fn set(&mut self, slider: usize, value: f32) {
+        match slider {
+            0 => self.scale = value,
+            1 => self.tile_size = value as u8,
+            _ => {
+                self.metric = match value >= 1.0 {
+                    true => MetricChoice::Luma,
+                    false => MetricChoice::Rgb,
+                }
+            }
+        }
+    }
+}
+
+/// A slider controlling one of the [`Settings`]
+#[derive(Debug)]
+struct Slider {
+    min: f32,
+    max: f32,
+    step: f32,
+    color: u32,
+}
+
+impl Slider {
+    /// Get the value at the given fraction of the way along the slider,
+    /// rounded to the nearest step
+    fn value_at(&self, fraction: f32) -> f32 {
+        let value = self.min + fraction.clamp(0.0, 1.0) * (self.max - self.min);
+        let steps = ((value - self.min) / self.step).round();
+        (self.min + steps * self.step).clamp(self.min, self.max)
+    }
+
+    /// Get the fraction of the way along the slider of the given value
+    fn fraction(&self, value: f32) -> f32 {
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+    }
+}
+
+/// The sliders for scale, tile size, and metric (in that order)
+const SLIDERS: [Slider; 3] = [
+    Slider {
+        min: 0.1,
+        max: 2.0,
+        step: 0.05,
+        color: 0x4a90d9,
+    },
+    Slider {
+        min: 1.0,
+        max: 64.0,
+        step: 1.0,
+        color: 0xd98c4a,
+    },
+    Slider {
+        min: 0.0,
+        max: 1.0,
+        step: 1.0,
+        color: 0x9b59b6,
+    },
+];
+
+/// A control below the preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    /// The slider with the given index.
+    Slider(usize),
+    /// The button to render the full mosaic.
+    Render,
+}
+
+/// Find the control at the given position in the window, if any
+fn control_at(x: u32, y: u32) -> Option<Control> {
+    let row = y.checked_sub(PREVIEW_SIZE.1)? / CONTROL_HEIGHT;
+    if x >= PREVIEW_SIZE.0 {
+        return None;
+    }
+    match row as usize {
+        i if i < SLIDERS.len() => Some(Control::Slider(i)),
+        i if i == SLIDERS.len() => Some(Control::Render),
+        _ => None,
+    }
+}
+
+/// Get the fraction of the way along a slider of the given x position
+fn slider_fraction(x: u32) -> f32 {
+    let width = PREVIEW_SIZE.0 - 2 * MARGIN;
+    x.saturating_sub(MARGIN) as f32 / width as f32
+}
+
+/// Build a preview of the mosaic with the given settings: the average
+/// color of the tile chosen for each cell, scaled to fit in [`PREVIEW_SIZE`]
+fn preview(src: &DynamicImage, tiles: &TileSet, settings: &Settings) -> RgbImage {
+    let mosaic = Mosaic::with_tile_set(
+        src.clone(),
+        tiles.clone(),
+        settings.scale,
+        settings.tile_size,
+        settings.options(),
+    );
+    let averages = mosaic.preview(PREVIEW_SIZE.0.max(PREVIEW_SIZE.1));
+
+    let (w, h) = averages.dimensions();
+    let fit = (PREVIEW_SIZE.0 as f32 / w as f32).min(PREVIEW_SIZE.1 as f32 / h as f32);
+    let (w, h) = (
+        ((w as f32 * fit) as u32).max(1),
+        ((h as f32 * fit) as u32).max(1),
+    );
+    image::imageops::resize(&averages, w, h, FilterType::Nearest)
+}
+
+/// Builds previews in a background thread, skipping any settings which
+/// are superseded before it gets to them
+struct Previewer {
+    requests: Sender<(u64, Settings)>,
+    results: Receiver<(u64, RgbImage)>,
+    /// The generation of the most recent request.
+    latest: u64,
+    /// The generation of the most recent preview received.
+    shown: u64,
+}
+
+impl Previewer {
+    /// Start building previews of a mosaic of `src` with `tiles`
+    fn spawn(src: DynamicImage, tiles: TileSet) -> Self {
+        let (requests, rx) = mpsc::channel::<(u64, Settings)>();
+        let (tx, results) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(mut request) = rx.recv() {
+                // skip to the most recent settings
+                while let Ok(newer) = rx.try_recv() {
+                    request = newer;
+                }
+                let (generation, settings) = request;
+                if tx
+                    .send((generation, preview(&src, &tiles, &settings)))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            results,
+            latest: 0,
+            shown: 0,
+        }
+    }
+
+    /// Request a preview with the given settings
+    fn request(&mut self, settings: Settings) {
+        self.latest += 1;
+        self.requests
+            .send((self.latest, settings))
+            .expect("Preview thread stopped");
+    }
+
+    /// Get the most recent preview built since the last call, if any
+    fn poll(&mut self) -> Option<RgbImage> {
+        let (generation, img) = self.results.try_iter().last()?;
+        self.shown = generation;
+        Some(img)
+    }
+
+    /// Check whether the last preview received reflects the latest settings
+    fn is_current(&self) -> bool {
+        self.shown == self.latest
+    }
+}
+
+/// Draw the preview and controls into a window's frame buffer
+fn draw(frame: &mut [u32], preview: &RgbImage, settings: &Settings) {
+    let width = PREVIEW_SIZE.0;
+    frame.fill(0x202020);
+
+    // center the preview in its area
+    let (w, h) = preview.dimensions();
+    let (x0, y0) = ((PREVIEW_SIZE.0 - w) / 2, (PREVIEW_SIZE.1 - h) / 2);
+    for (x, y, px) in preview.enumerate_pixels() {
+        let [r, g, b] = px.0;
+        frame[((y0 + y) * width + x0 + x) as usize] = u32::from_be_bytes([0, r, g, b]);
+    }
+
+    let mut fill = |(x, y, w, h): (u32, u32, u32, u32), color: u32| {
+        for row in y..y + h {
+            let start = (row * width + x) as usize;
+            frame[start..start + w as usize].fill(color);
+        }
+    };
+    let track = PREVIEW_SIZE.0 - 2 * MARGIN;
+    for (i, slider) in SLIDERS.iter().enumerate() {
+        let y = PREVIEW_SIZE.1 + i as u32 * CONTROL_HEIGHT + MARGIN;
+        let h = CONTROL_HEIGHT - 2 * MARGIN;
+        let filled = (slider.fraction(settings.get(i)) * track as f32) as u32;
+        fill((MARGIN, y, track, h), 0x505050);
+        fill((MARGIN, y, filled, h), slider.color);
+    }
+    let y = PREVIEW_SIZE.1 + SLIDERS.len() as u32 * CONTROL_HEIGHT + MARGIN;
+    fill((MARGIN, y, track, CONTROL_HEIGHT - 2 * MARGIN), 0x3c8c3c);
+}
+
+/// Start rendering the full mosaic with the given settings, by running
+/// `tilr` with the equivalent arguments
+fn render_full(args: &Args, settings: &Settings) -> std::io::Result<Child> {
+    let cmd_args = settings.command_args(args);
+    info!("Running: tilr {}", cmd_args.join(" "));
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(cmd_args)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    // confirm the size of the mosaic
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(b"y\n")?;
+    }
+    Ok(child)
+}
+
+/// Open a window with a live preview of a mosaic
+pub fn run(args: Args) {
+    info!("Loading input image...");
+    let img = tilr::load_oriented(&args.src_image).expect("Unable to read image file.");
+
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    print_load_summary(&report, &args.tile_load.tile_dir);
+    if report.tiles.is_empty() {
+        error!("No tiles to build a mosaic from.");
+        std::process::exit(1);
+    }
+    let tiles = crate::flatten_tiles(report.tiles, args.tile_load.tile_background);
+    let tiles = TileSet::from(&tiles);
+
+    let mut settings = Settings {
+        scale: SLIDERS[0].value_at(SLIDERS[0].fraction(args.scale)),
+        tile_size: args.tile_size.max(1),
+        metric: MetricChoice::Rgb,
+    };
+    let mut previewer = Previewer::spawn(img, tiles);
+    previewer.request(settings);
+
+    let height = PREVIEW_SIZE.1 + (SLIDERS.len() as u32 + 1) * CONTROL_HEIGHT;
+    let mut window = Window::new(
+        "tilr",
+        PREVIEW_SIZE.0 as usize,
+        height as usize,
+        WindowOptions::default(),
+    )
+    .expect("Unable to open a window.");
+    window.set_target_fps(30);
+
+    let mut frame = vec![0; (PREVIEW_SIZE.0 * height) as usize];
+    let mut shown = RgbImage::new(1, 1);
+    let mut render: Option<Child> = None;
+    let mut was_down = false;
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if let Some(img) = previewer.poll() {
+            shown = img;
+        }
+
+        // adjust the sliders (and press the button) with the mouse
+        let down = window.get_mouse_down(MouseButton::Left);
+        let control = window
+            .get_mouse_pos(MouseMode::Discard)
+            .and_then(|(x, y)| Some((x as u32, control_at(x as u32, y as u32)?)));
+        let mut start_render = window.is_key_pressed(Key::R, KeyRepeat::No);
+        match control {
+            Some((x, Control::Slider(i))) if down => {
+                let value = SLIDERS[i].value_at(slider_fraction(x));
+                if value != settings.get(i) {
+                    settings.set(i, value);
+                    previewer.request(settings);
+                }
+            }
+            Some((_, Control::Render)) if down && !was_down => start_render = true,
+            _ => (),
+        }
+        was_down = down;
+
+        if start_render && render.is_none() {
+            match render_full(&args, &settings) {
+                Ok(child) => render = Some(child),
+                Err(e) => error!("Unable to start rendering: {}", e),
+            }
+        }
+        let status = match render.as_mut().map(Child::try_wait) {
+            Some(Ok(None)) => "rendering...".to_string(),
+            Some(Ok(Some(status))) => {
+                render = None;
+                match status.success() {
+                    true => info!("Saved the mosaic to {}.", args.output.display()),
+                    false => error!("Rendering failed ({}).", status),
+                }
+                String::new()
+            }
+            _ => String::new(),
+        };
+
+        window.set_title(&format!(
+            "tilr - scale {:.2} | tile size {} | metric {}{} - R or the green button to render {}",
+            settings.scale,
+            settings.tile_size,
+            settings.metric.name(),
+            match previewer.is_current() {
+                true => "",
+                false => " (updating...)",
+            },
+            match status.is_empty() {
+                true => args.output.display().to_string(),
+                false => status,
+            },
+        ));
+        draw(&mut frame, &shown, &settings);
+        window
+            .update_with_buffer(&frame, PREVIEW_SIZE.0 as usize, height as usize)
+            .expect("Unable to update the window.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use image::Rgb;
+    use std::time::{Duration, Instant};
+
+    fn args() -> Args {
+        Args {
+            src_image: "in.png".into(),
+            tile_load: TileLoadArgs {
+                tile_dir: vec!["tiles/a".into(), "tiles/b".into()],
+                follow_symlinks: true,
+                keep_duplicates: false,
+                min_tile_dim: Some(16),
+                recursive: true,
+                tile_include: vec![glob::Pattern::new("*.png").unwrap()],
+                tile_exclude: vec![glob::Pattern::new("beach/*").unwrap()],
+                tile_background: Rgb([0, 128, 255]),
+            },
+            scale: 1.0,
+            tile_size: 8,
+            output: "out.png".into(),
+        }
+    }
+
+    fn source() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(16, 12, |x, y| {
+            Rgb([x as u8 * 16, y as u8 * 20, 128])
+        }))
+    }
+
+    fn tiles() -> TileSet {
+        let imgs: Vec<DynamicImage> = (0..8)
+            .map(|i| {
+                let c = [(i & 1) * 255, (i >> 1 & 1) * 255, (i >> 2 & 1) * 255];
+                DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb(c)))
+            })
+            .collect();
+        TileSet::from(&imgs)
+    }
+
+    #[test]
+    fn command_round_trip() {
+        let args = args();
+        for settings in [
+            Settings {
+                scale: 0.35,
+                tile_size: 12,
+                metric: MetricChoice::Luma,
+            },
+            Settings {
+                scale: SLIDERS[0].value_at(0.77),
+                tile_size: 1,
+                metric: MetricChoice::Rgb,
+            },
+        ] {
+            let cmd = settings.command_args(&args);
+            let cli =
+                crate::Cli::try_parse_from(std::iter::once("tilr".into()).chain(cmd)).unwrap();
+            let build = cli.build;
+            assert!(cli.command.is_none());
+            assert_eq!(build.src_image, std::slice::from_ref(&args.src_image));
+            assert_eq!(build.output, args.output);
+            assert_eq!(build.tile_load.tile_dir, args.tile_load.tile_dir);
+            assert_eq!(
+                build.tile_load.load_options(),
+                args.tile_load.load_options()
+            );
+            assert_eq!(
+                build.tile_load.tile_background,
+                args.tile_load.tile_background
+            );
+            assert_eq!(build.scale, settings.scale);
+            assert_eq!(build.tile_size, Some(settings.tile_size));
+            assert_eq!(build.metric_weights, settings.metric.weights());
+        }
+    }
+
+    #[test]
+    fn sliders() {
+        let slider = &SLIDERS[0];
+        assert_eq!(slider.value_at(-1.0), 0.1);
+        assert_eq!(slider.value_at(2.0), 2.0);
+        assert!((slider.value_at(0.5) - 1.05).abs() < 1e-6);
+
+        let mut settings = Settings {
+            scale: 1.0,
+            tile_size: 8,
+            metric: MetricChoice::Rgb,
+        };
+        for (i, slider) in SLIDERS.iter().enumerate() {
+            let value = slider.value_at(0.9);
+            settings.set(i, value);
+            assert_eq!(settings.get(i), value);
+            let step = slider.step / (slider.max - slider.min);
+            assert!((slider.fraction(value) - 0.9).abs() <= step / 2.0 + 1e-6);
+        }
+        assert_eq!(settings.metric, MetricChoice::Luma);
+    }
+
+    #[test]
+    fn controls() {
+        assert_eq!(control_at(10, 10), None);
+        assert_eq!(control_at(10, PREVIEW_SIZE.1 + 1), Some(Control::Slider(0)));
+        let button = PREVIEW_SIZE.1 + 3 * CONTROL_HEIGHT + 1;
+        assert_eq!(control_at(10, button), Some(Control::Render));
+        assert_eq!(control_at(10, button + CONTROL_HEIGHT), None);
+        assert_eq!(slider_fraction(0), 0.0);
+        assert_eq!(slider_fraction(PREVIEW_SIZE.0 - MARGIN), 1.0);
+    }
+
+    #[test]
+    fn preview_matches_plan() {
+        let settings = Settings {
+            scale: 1.0,
+            tile_size: 4,
+            metric: MetricChoice::Rgb,
+        };
+        let img = preview(&source(), &tiles(), &settings);
+        // 16x12 cells, scaled by 50 to fit in 800x600
+        assert_eq!(img.dimensions(), (800, 600));
+
+        let mosaic = Mosaic::with_tile_set(source(), tiles(), 1.0, 4, settings.options());
+        let averages = mosaic.plan().render_averages(mosaic.tiles());
+        for (x, y, px) in averages.enumerate_pixels() {
+            assert_eq!(img.get_pixel(x * 50 + 25, y * 50 + 25), px);
+        }
+    }
+
+    #[test]
+    fn previewer_shows_latest() {
+        let mut previewer = Previewer::spawn(source(), tiles());
+        let first = Settings {
+            scale: 1.0,
+            tile_size: 4,
+            metric: MetricChoice::Rgb,
+        };
+        let last = Settings {
+            scale: 0.5,
+            ..first
+        };
+        previewer.request(first);
+        previewer.request(last);
+        assert!(!previewer.is_current());
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut shown = None;
+        while !previewer.is_current() {
+            assert!(Instant::now() < deadline, "Timed out waiting for a preview");
+            shown = previewer.poll().or(shown);
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(shown, Some(preview(&source(), &tiles(), &last)));
+    }
+}
@@ -0,0 +1,62 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Prints the messages logged by `tilr` (and the library) to stderr, one
+/// per line, with warnings marked as such.
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // leave out the messages logged by dependencies (e.g., ureq)
+        metadata.level() <= log::max_level() && metadata.target().starts_with("tilr")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+/// Print messages logged at the level chosen by -v and -q.
+pub fn init(verbose: u8, quiet: u8) {
+    // only fails if a logger was already set, which would be this one
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level(verbose, quiet));
+}
+
+/// Get the most detailed level to print, after `verbose` -v flags and
+/// `quiet` -q flags: info by default, then debug and trace with -v, or
+/// warnings, errors, and nothing at all with -q.
+pub fn level(verbose: u8, quiet: u8) -> LevelFilter {
+    match 3 + verbose as i16 - quiet as i16 {
+        ..=0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
@@ -0,0 +1,2643 @@
+//! Build a mosaic of a particular image out of a set of smaller images.
+
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+mod analyze;
+mod compare;
+mod config;
+#[cfg(feature = "gui")]
+mod gui;
+mod logger;
+mod normalize;
+mod preview;
+#[cfg(feature = "serve")]
+mod serve;
+mod stats;
+mod verify;
+#[cfg(feature = "video")]
+mod video;
+#[cfg(feature = "watch")]
+mod watch;
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use glob::Pattern;
+use image::{
+    DynamicImage, GenericImageView, ImageBuffer, ImageFormat, PixelWithColorType, Rgb, RgbImage,
+};
+use log::{debug, error, info, log_enabled, warn, Level};
+use std::borrow::Cow;
+use std::io::{stderr, stdin, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[cfg(feature = "video")]
+use tilr::FrameSampling;
+#[cfg(feature = "remote")]
+use tilr::RemoteOptions;
+use tilr::{
+    CellColor, ColorHistogram, DecodeCache, Descriptor, DiversityCheck, DiversityWarning, GapFill,
+    LoadOptions, LoadReport, LoadWarningReason, MapCache, Metric, Montage, Mosaic, MosaicOptions,
+    MosaicPlan, OutputOptions, PngCompression, PostProcess, Posterize, Preprocess, PrintSize, Rect,
+    ResizeFilter, Rotation, RunReport, Tile, TileFit, TileSet, Timings, Transform,
+};
+
+// Struct to describe our command-line arguments
+// and generate a parser for them.
+#[derive(Debug, Parser)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = r#"A program to build a mosaic of an image from a set of smaller image 'tiles'
+
+Copyright (C) 2023 Charles German <5donuts@pm.me>
+This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY.
+See the GNU General Public License for more details. You should have received a copy of the
+GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>."#,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    /// Build a mosaic (the default when no subcommand is given).
+    #[clap(flatten)]
+    build: Args,
+
+    /// Print more detailed information (e.g., which tiles were skipped);
+    /// give twice to print everything.
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Print less: only warnings and errors, or (given twice) only errors.
+    #[clap(short, long, action = clap::ArgAction::Count, global = true)]
+    quiet: u8,
+}
+
+// The subcommands other than building a mosaic.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Report how well a tile set covers the range of possible colors.
+    Analyze(analyze::Args),
+    /// Compare two mosaic plans (or images) and report what changed.
+    Compare(compare::Args),
+    /// Open a window with a live preview of a mosaic.
+    #[cfg(feature = "gui")]
+    Gui(gui::Args),
+    /// Crop and scale a set of tiles to squares of the same size.
+    Normalize(normalize::Args),
+    /// Display a quick, flat-color preview of a mosaic.
+    Preview(preview::Args),
+    /// Serve a DeepZoom (DZI) pyramid with a zoomable viewer.
+    #[cfg(feature = "serve")]
+    Serve(serve::Args),
+    /// Report how a saved mosaic plan uses its tiles.
+    Stats(stats::Args),
+    /// Check that a mosaic matches the plan it was built from.
+    Verify(verify::Args),
+    /// Build a mosaic of each frame of a video (requires ffmpeg).
+    #[cfg(feature = "video")]
+    Video(video::Args),
+}
+
+// The arguments used to build a mosaic.
+#[derive(Debug, Clone, clap::Args)]
+struct Args {
+    /// Path to the original image (or, with --montage, to each of the
+    /// images to combine), or `-` to read it from standard input.
+    #[clap(value_parser, required = true)]
+    src_image: Vec<PathBuf>,
+
+    /// Combine the source images into a single mosaic, laid out in a grid
+    /// of COLSxROWS (e.g., `3x1` for three side by side), filled across
+    /// each row. Each image is scaled to fit a slot the size of the largest
+    /// of them, and the rest of its slot is left as background.
+    #[clap(
+        long,
+        value_name = "COLSxROWS",
+        value_parser = parse_grid,
+        conflicts_with_all = ["self_tiles", "label_mask"]
+    )]
+    montage: Option<(u32, u32)>,
+
+    /// With --montage, the number of background cells between the images.
+    #[clap(long, value_name = "CELLS", default_value = "0", requires = "montage")]
+    montage_gap: u32,
+
+    /// The format in which to save the mosaic. An `image` is saved in the
+    /// format given by the extension of the output path, unless one is
+    /// given (e.g., `jpeg`).
+    #[clap(long, value_enum, default_value = "image")]
+    format: Format,
+
+    /// The quality of JPEG output, from 1 (the smallest files) to 100 (the
+    /// best quality).
+    #[clap(long, value_name = "QUALITY", default_value = "75", value_parser = clap::value_parser!(u8).range(1..=100))]
+    jpeg_quality: u8,
+
+    /// How hard to compress PNG output: `best` makes the smallest files,
+    /// but can take a while for large mosaics.
+    #[clap(long, value_enum, default_value = "default")]
+    png_compression: CompressionName,
+
+    /// With --format blocks, the side length of the block for each cell
+    /// (in pixels).
+    #[clap(long, value_name = "PX", default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    block_size: u32,
+
+    /// Path at which to save a CSV of the index and average color of the
+    /// tile chosen for each cell (e.g., with --format blocks).
+    #[clap(long, value_name = "PATH")]
+    cells_csv: Option<PathBuf>,
+
+    /// Path at which to save a JSON report of the run: the inputs which were
+    /// skipped (and why), how the tiles were augmented, the warnings given,
+    /// and the statistics of the mosaic.
+    #[clap(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// With --format pdf, the width of the printed mosaic (in millimeters).
+    /// [default: the width of the mosaic at --dpi, or 300 DPI]
+    #[clap(long, value_name = "MM")]
+    print_width_mm: Option<f32>,
+
+    /// The resolution at which the mosaic will be printed. This is recorded
+    /// in the metadata of PNG and JPEG output, and used to report the
+    /// printed size of the mosaic.
+    #[clap(long, value_parser = parse_positive)]
+    dpi: Option<f32>,
+
+    /// Suggest the --scale and --tile-size needed to print the mosaic
+    /// this wide (in centimeters) at --dpi.
+    #[clap(long, value_name = "CM", requires = "dpi", value_parser = parse_positive)]
+    print_width_cm: Option<f32>,
+
+    /// Load the tiles and choose the tile for each cell, then report the size
+    /// of the mosaic, the tiles it uses, and the memory and file size it
+    /// needs, without rendering or saving it.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Read default values for any of these options from this TOML file,
+    /// by their long names (e.g., `tile_size = 16`, `tile_dir = ["a", "b"]`,
+    /// `yes = true`); options given on the command line take precedence. By
+    /// default, `tilr.toml` is read from the current directory, if it exists.
+    #[clap(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Build the mosaic without asking for confirmation, however large it
+    /// is (up to --max-output-pixels), e.g., to run unattended from a script.
+    #[clap(short, long)]
+    yes: bool,
+
+    /// Ask for confirmation before building a mosaic larger than this many
+    /// megapixels; smaller mosaics are built without asking. When the input
+    /// is not a terminal, larger mosaics are refused unless --yes is given.
+    #[clap(long, value_name = "MEGAPIXELS", default_value = "100")]
+    confirm_above: f64,
+
+    /// Refuse to build a mosaic larger than this many pixels, even with --yes.
+    #[clap(long, value_name = "PIXELS")]
+    max_output_pixels: Option<u64>,
+
+    /// After building the mosaic, keep watching the source image and the
+    /// tile directories, and rebuild the mosaic whenever they change
+    /// (until interrupted with Ctrl-C).
+    #[cfg(feature = "watch")]
+    #[clap(long, conflicts_with = "dry_run")]
+    watch: bool,
+
+    /// Render only this rectangle of the mosaic (in pixels of the full
+    /// mosaic), e.g., to inspect part of a very large mosaic at full
+    /// resolution. Tiles are still chosen for the whole mosaic.
+    #[clap(long, value_name = "X,Y,WxH", value_parser = parse_window, conflicts_with = "report_quality")]
+    window: Option<Rect>,
+
+    /// Give the --window in cells rather than pixels.
+    #[clap(long, requires = "window")]
+    window_cells: bool,
+
+    /// Load exactly the tiles listed in this file (one path per line), or
+    /// in standard input if it is `-`, rather than the images in --tile-dir
+    /// (e.g., `find photos -name '*.jpg' | tilr in.png --tile-list -`).
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tile_dir", "self_tiles", "region", "categories", "recursive", "tile_include", "tile_exclude"]
+    )]
+    tile_list: Option<PathBuf>,
+
+    /// With --tile-list, the paths are separated by NUL characters rather
+    /// than newlines (e.g., from `find -print0`), so they may contain newlines.
+    #[clap(short = '0', long, requires = "tile_list")]
+    null: bool,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// How to make tiles which are not square into squares: scale them
+    /// (`stretch`), crop the center of them (`center`), or crop the most
+    /// detailed part of them (`smart`).
+    #[clap(long, default_value = "stretch")]
+    tile_fit: TileFit,
+
+    /// Path at which to save the resulting image, or `-` to write it to
+    /// standard output (which needs a --format, e.g., `png`).
+    #[clap(short, long, default_value = "mosaic.png", value_parser)]
+    output: PathBuf,
+
+    /// Scaling to apply to the image before building the mosaic.
+    #[clap(short, long, default_value = "1.0")]
+    scale: f32,
+
+    /// Choose the --scale for the largest mosaic no larger than WIDTHxHEIGHT
+    /// (in pixels).
+    #[clap(long, value_name = "WIDTHxHEIGHT", value_parser = parse_grid, conflicts_with_all = ["scale", "montage"])]
+    fit_within: Option<(u32, u32)>,
+
+    /// Divide the (scaled) source image into cells of NxN pixels, and place
+    /// one tile per cell, rather than one tile per pixel.
+    #[clap(long, value_name = "N", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    cell_size: u32,
+
+    /// With --cell-size, the color of each cell to match to a tile: the
+    /// average of its pixels (`average`), or its most common color
+    /// (`dominant`, which keeps the edges of flat regions crisp).
+    #[clap(long, default_value = "average")]
+    cell_color: CellColor,
+
+    /// The side length to use for the tiles (in pixels). Any tiles which
+    /// are not squares with this side length will be resized; this may
+    /// introduce some distortion in the resulting mosaic.
+    /// [default: 8, or the size of the slices with --self-tiles]
+    #[clap(long)]
+    tile_size: Option<u8>,
+
+    /// Build the mosaic out of pieces of the source image itself, rather
+    /// than tiles from --tile-dir, by slicing it into a grid of COLSxROWS
+    /// (or NxN) tiles.
+    #[clap(long, value_name = "COLSxROWS", value_parser = parse_grid, conflicts_with = "tile_dir")]
+    self_tiles: Option<(u32, u32)>,
+
+    /// With --self-tiles, also use horizontally and vertically flipped
+    /// copies of each slice as tiles.
+    #[clap(long, requires = "self_tiles")]
+    self_tiles_flip: bool,
+
+    /// Take the tiles from frames of this video (requires ffmpeg) rather
+    /// than from --tile-dir, e.g., to build a movie's poster out of the
+    /// movie's own frames. (See --tile-fit to crop rather than stretch them.)
+    #[cfg(feature = "video")]
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tile_dir", "tile_list", "self_tiles", "region", "categories"]
+    )]
+    tile_video: Option<PathBuf>,
+
+    /// With --tile-video, take every Nth frame as a tile.
+    #[cfg(feature = "video")]
+    #[clap(long, value_name = "N", requires = "tile_video", value_parser = clap::value_parser!(u32).range(1..))]
+    frame_every: Option<u32>,
+
+    /// With --tile-video, take N frames spread evenly through the video as
+    /// tiles. [default: 500]
+    #[cfg(feature = "video")]
+    #[clap(
+        long,
+        value_name = "N",
+        requires = "tile_video",
+        conflicts_with = "frame_every",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    frame_count: Option<u32>,
+
+    /// Download the tiles from the URLs listed in this file (one per line;
+    /// blank lines and lines starting with `#` are skipped), rather than
+    /// loading them from --tile-dir.
+    #[cfg(feature = "remote")]
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["tile_dir", "tile_list", "self_tiles", "region", "categories"]
+    )]
+    tile_urls: Option<PathBuf>,
+
+    /// With --tile-urls, the directory to keep downloaded tiles in, so
+    /// they aren't downloaded again. [default: `tilr/urls` in the user's
+    /// cache directory]
+    #[cfg(feature = "remote")]
+    #[clap(long, value_name = "DIR", requires = "tile_urls")]
+    url_cache: Option<PathBuf>,
+
+    /// Path to a label mask: an image (the same shape as the source image)
+    /// whose gray levels divide the source image into regions, each built
+    /// from its own tiles (see --region).
+    #[clap(long, value_parser, requires = "region")]
+    label_mask: Option<PathBuf>,
+
+    /// Build the region with the given gray level in the --label-mask out
+    /// of the tiles in DIR (e.g., `0=tiles/sky`), rather than the tiles
+    /// from --tile-dir. May be given more than once.
+    #[clap(
+        long,
+        value_name = "LABEL=DIR",
+        value_parser = parse_region,
+        action = clap::ArgAction::Append,
+        requires = "label_mask",
+        conflicts_with_all = ["self_tiles", "fill_gaps"]
+    )]
+    region: Vec<(u8, PathBuf)>,
+
+    /// Treat each subdirectory of the --tile-dir directories as a category
+    /// of tiles named after it (e.g., `tiles/sky` holds the `sky` tiles),
+    /// to limit with --category-limit. Images directly in the directories
+    /// have no category.
+    #[clap(long, conflicts_with_all = ["self_tiles", "region", "recursive"])]
+    categories: bool,
+
+    /// Fill at most this share of the cells with tiles from the given
+    /// category (e.g., `sky=15%`). May be given more than once.
+    #[clap(
+        long,
+        value_name = "NAME=PERCENT",
+        value_parser = parse_category_limit,
+        action = clap::ArgAction::Append,
+        requires = "categories",
+        conflicts_with = "unique"
+    )]
+    category_limit: Vec<(String, f32)>,
+
+    /// Let a category go over its --category-limit when every tile outside
+    /// of a full category scores more than this many times worse than the
+    /// best tile for a cell. By default, limits are never exceeded.
+    #[clap(
+        long,
+        value_name = "FACTOR",
+        default_value = "0.0",
+        requires = "category_limit"
+    )]
+    category_overflow: f32,
+
+    /// Path at which to save a flat-color preview of the mosaic (one pixel
+    /// per cell) as soon as the tiles are chosen, before the (much slower)
+    /// full render.
+    #[clap(long, value_name = "PATH", value_parser)]
+    preview_first: Option<PathBuf>,
+
+    /// Path at which to save a map of the tile each cell uses (one pixel
+    /// per cell, with a hue for each tile), e.g., to see how often tiles
+    /// are repeated.
+    #[clap(long, value_name = "PATH", value_parser)]
+    usage_map: Option<PathBuf>,
+
+    /// Make the --usage-map a mask of the cells which use the tile with
+    /// this index (in the order the tiles are loaded, as listed by
+    /// `tilr stats`) instead.
+    #[clap(long, value_name = "TILE", requires = "usage_map")]
+    usage_map_for: Option<usize>,
+
+    /// Path at which to save the mosaic plan (the tile assigned to each
+    /// cell, plus the options used) as JSON.
+    #[clap(long, value_parser)]
+    sidecar: Option<PathBuf>,
+
+    /// Path to a cache of the closest tile to each color. The cache is
+    /// loaded (if it exists and matches the tile set) before building the
+    /// mosaic and saved afterwards, so repeated runs with the same tiles
+    /// can skip searching for colors they've already seen.
+    #[clap(long, value_parser)]
+    map_cache: Option<PathBuf>,
+
+    /// Adjust the source image after scaling it and before matching it to
+    /// tiles, so low-contrast images use more of the tile set: stretch its
+    /// contrast (`stretch`), or equalize the histogram of each channel
+    /// (`equalize`) or of its luminance (`equalize-luminance`).
+    #[clap(long, value_enum)]
+    preprocess: Option<PreprocessName>,
+
+    /// With --preprocess stretch, the percentage of the darkest and of the
+    /// brightest pixels to clip to black and white.
+    #[clap(long, value_name = "PERCENT", default_value = "1.0", value_parser = parse_clip)]
+    stretch_clip: f32,
+
+    /// Scale the saturation (chroma) of the source image by this factor
+    /// before matching it to tiles (after --preprocess), e.g., `1.3` to
+    /// make up for tiles which are less vivid than the source, or `0.7`
+    /// for a muted mosaic.
+    #[clap(long, value_name = "FACTOR", default_value = "1.0", value_parser = parse_non_negative)]
+    saturation: f32,
+
+    /// Posterize the source image before matching it to tiles (after
+    /// --saturation), rounding each channel to this many levels, so areas
+    /// of similar color use a single tile. [default: 4 when given without
+    /// a value]
+    #[clap(long, value_name = "LEVELS", num_args = 0..=1, default_missing_value = "4", value_parser = clap::value_parser!(u8).range(2..))]
+    posterize: Option<u8>,
+
+    /// Like --posterize, but reduce the source image to a palette of this
+    /// many colors chosen to suit it. [default: 8 when given without a
+    /// value]
+    #[clap(long, value_name = "K", num_args = 0..=1, default_missing_value = "8", value_parser = clap::value_parser!(u16).range(1..), conflicts_with = "posterize")]
+    posterize_colors: Option<u16>,
+
+    /// How to compare colors: by the distance between them in RGB (`rgb`),
+    /// or in the perceptual OKLab (`oklab`) or CIELAB (`lab`) color spaces,
+    /// which track how different colors look more closely, or by the
+    /// CIEDE2000 difference (`ciede2000`), which refines CIELAB distances
+    /// further. These are on other scales (black and white are 1.0 apart in
+    /// OKLab, and 100.0 in CIELAB), so scale --fill-gaps and --usage-penalty
+    /// to match. [default: rgb]
+    #[clap(long, value_enum, conflicts_with = "metric_weights")]
+    metric: Option<MetricName>,
+
+    /// Weights for the red, green, and blue channels when comparing
+    /// colors (e.g., `2.0,1.0,0.5` to prioritize matching red).
+    #[clap(long, value_name = "R,G,B", value_parser = parse_metric_weights)]
+    metric_weights: Option<[f32; 3]>,
+
+    /// How to compare cells to tiles: by their average colors (`mean`), or
+    /// by the average colors of their quadrants (`quadrants`) or of a 3x3
+    /// grid over them (`grid3`), which keep more of the structure within
+    /// each cell, or by the average colors of tiles weighted towards their
+    /// centers (`center`), for tiles whose subject is in the middle.
+    #[clap(long, default_value = "mean")]
+    descriptor: Descriptor,
+
+    /// The spread of the weighting used by `--descriptor center`, as a
+    /// fraction of the tile size; smaller values focus more tightly on the
+    /// middle of each tile.
+    #[clap(long, value_name = "SIGMA", value_parser = parse_center_sigma)]
+    center_sigma: Option<f32>,
+
+    /// Perturb the color of each cell by up to this much (per channel)
+    /// before matching it to a tile, to break up banding in gradients.
+    #[clap(long, value_name = "AMPLITUDE", default_value = "0")]
+    dither: u8,
+
+    /// Carry this fraction (from 0 to 1) of the difference between each
+    /// cell and its tile on to the cells after it (Floyd-Steinberg error
+    /// diffusion), to blend gradients between tiles with small tile sets.
+    #[clap(long, value_name = "STRENGTH", default_value = "0.0", value_parser = parse_fraction, conflicts_with = "unique")]
+    error_diffusion: f32,
+
+    /// Accept tiles whose distance to a cell is up to this factor of the
+    /// best match's (e.g., `1.05`), to search large tile sets faster.
+    #[clap(long, value_name = "FACTOR", default_value = "1.0", value_parser = parse_approx)]
+    approx: f32,
+
+    /// Add this much to a tile's distance to each cell for every time it
+    /// has already been used, to spread cells between similar tiles.
+    #[clap(long, value_name = "STRENGTH", default_value = "0.0", value_parser = parse_non_negative)]
+    usage_penalty: f32,
+
+    /// Add this much to a tile's distance to each cell for every cell
+    /// within --repeat-radius which already uses it, to rotate between
+    /// similar tiles in flat regions rather than repeating one in patches.
+    #[clap(long, value_name = "STRENGTH", default_value = "0.0", value_parser = parse_non_negative)]
+    repeat_penalty: f32,
+
+    /// The distance (in cells) within which uses of a tile count towards
+    /// --repeat-penalty.
+    #[clap(long, value_name = "CELLS", default_value = "2")]
+    repeat_radius: u32,
+
+    /// Place one of the K closest tiles to each cell, picked at random
+    /// (seeded by --seed), rather than always the closest one.
+    #[clap(long, value_name = "K", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    top_k: u32,
+
+    /// With --top-k, only pick tiles at most this much further from a cell
+    /// than the closest one.
+    #[clap(long, value_name = "DISTANCE", requires = "top_k", value_parser = parse_non_negative)]
+    top_k_tolerance: Option<f32>,
+
+    /// Use each tile at most once (e.g., to use every photo exactly once),
+    /// finding the best assignment over all cells at once. Requires at
+    /// least as many tiles as cells.
+    #[clap(long, conflicts_with_all = ["usage_penalty", "repeat_penalty", "top_k", "label_mask", "region", "recurse"])]
+    unique: bool,
+
+    /// Rearrange the tiles within regions of nearly constant color (adjacent
+    /// cells within this distance of each other) following blue noise, so
+    /// repeated tiles are spread evenly rather than in stripes (e.g., with
+    /// --usage-penalty).
+    #[clap(long, value_name = "DISTANCE", default_value = "0.0", value_parser = parse_non_negative)]
+    flat_shuffle: f32,
+
+    /// Seed for randomized features such as --dither, --usage-penalty, and
+    /// --top-k.
+    #[clap(long, default_value = "0")]
+    seed: u64,
+
+    /// For source images with transparency, leave cells less opaque than
+    /// this (0-255) as background instead of placing tiles in them.
+    #[clap(long, value_name = "0..255", default_value = "0")]
+    alpha_threshold: u8,
+
+    /// Color to composite a transparent source image over before matching
+    /// tiles, and to fill background cells with (e.g., `#ffffff`).
+    #[clap(long, value_name = "HEX", default_value = "#ffffff", value_parser = parse_hex_color)]
+    matte: Rgb<u8>,
+
+    /// Save a transparent mosaic: tiles keep their transparency (and are
+    /// matched by the color of their opaque parts), and background cells
+    /// are left transparent rather than filled with --matte. The output
+    /// must be an image format with transparency (e.g., PNG).
+    #[clap(
+        long,
+        conflicts_with_all = ["tile_background", "label_mask", "categories", "recurse", "window", "grayscale", "sharpen", "contrast", "gamma"]
+    )]
+    transparent: bool,
+
+    /// Shift the colors of each tile by this fraction (from 0 to 1) of the
+    /// difference between its cell and its average color as it is placed,
+    /// so that small tile sets can reproduce any color.
+    #[clap(long, value_name = "STRENGTH", default_value = "0.0", value_parser = parse_fraction)]
+    color_adjust: f32,
+
+    /// Blend the scaled source image over the mosaic with this opacity
+    /// (from 0 to 1), to make its subject easier to recognize.
+    #[clap(long, value_name = "OPACITY", default_value = "0.0", value_parser = parse_fraction)]
+    overlay_strength: f32,
+
+    /// Add solid-color tiles to the tile set until every color is within
+    /// this distance of some tile's average color.
+    #[clap(long, value_name = "MAX_DISTANCE", value_parser = parse_non_negative)]
+    fill_gaps: Option<f32>,
+
+    /// With --fill-gaps, the colors to fill gaps for: every color (`all`),
+    /// or only the colors of the (scaled) source image (`source`), which
+    /// adds just the tiles this mosaic needs.
+    #[clap(
+        long,
+        value_name = "COLORS",
+        default_value = "all",
+        requires = "fill_gaps"
+    )]
+    fill_gaps_for: GapColors,
+
+    /// With --fill-gaps, add subtle noise (seeded by --seed) to the tiles
+    /// added, so they look less flat among the other tiles.
+    #[clap(long, requires = "fill_gaps")]
+    fill_gaps_noise: bool,
+
+    /// Path to a directory in which to save the tiles generated
+    /// by --fill-gaps (for inspection).
+    #[clap(long, value_parser, requires = "fill_gaps")]
+    synthetic_tile_dir: Option<PathBuf>,
+
+    /// Warn if the tiles' average colors fall short of the source's colors
+    /// by more than this (0-255) at either end of any channel.
+    #[clap(long, value_name = "0..255", default_value_t = DiversityCheck::default().margin)]
+    diversity_margin: u8,
+
+    /// Build a recursive mosaic, in which every tile is itself a mosaic
+    /// built from the tile set, this many levels deep (1 is a normal mosaic).
+    #[clap(long, value_name = "DEPTH", default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    recurse: u32,
+
+    /// The side length (in pixels) of the tiles used inside each tile
+    /// with --recurse.
+    #[clap(long, default_value = "4", value_parser = clap::value_parser!(u32).range(1..))]
+    recurse_tile_size: u32,
+
+    /// The filter used to scale both the source image and the tiles:
+    /// `nearest`, `triangle`, `catmull-rom`, `gaussian`, or `lanczos3`.
+    #[clap(long, default_value = "triangle")]
+    filter: ResizeFilter,
+
+    /// The filter used to scale the source image (e.g., `lanczos3` for
+    /// smooth gradients). [default: --filter]
+    #[clap(long)]
+    source_filter: Option<ResizeFilter>,
+
+    /// The filter used to scale the tiles (e.g., `nearest` to keep the
+    /// hard edges of pixel art). [default: --filter]
+    #[clap(long)]
+    tile_filter: Option<ResizeFilter>,
+
+    /// Sharpen each tile with an unsharp mask of this strength (e.g., 0.5)
+    /// after scaling it to --tile-size, and before computing its average
+    /// color, since shrinking tiles to a few pixels blurs them.
+    #[clap(long, value_name = "AMOUNT", default_value = "0.0", value_parser = parse_non_negative)]
+    tile_sharpen: f32,
+
+    /// Sharpen the mosaic with an unsharp mask of this strength
+    /// (e.g., 0.5) after building it.
+    #[clap(long, value_name = "AMOUNT", default_value = "0.0", value_parser = parse_non_negative)]
+    sharpen: f32,
+
+    /// Adjust the contrast of the mosaic after building it, from -100
+    /// (flat gray) to 100 (double contrast).
+    #[clap(long, default_value = "0.0", allow_negative_numbers = true)]
+    contrast: f32,
+
+    /// Apply gamma correction to the mosaic after building it; values
+    /// above 1 brighten it and values below 1 darken it.
+    #[clap(long, default_value = "1.0", value_parser = parse_positive)]
+    gamma: f32,
+
+    /// Print measures of how closely the mosaic resembles the source
+    /// image (PSNR, SSIM, and mean tile distance) after building it.
+    #[clap(long)]
+    report_quality: bool,
+
+    /// Print how long each phase of building the mosaic took.
+    #[clap(long)]
+    time: bool,
+
+    /// Rotate and/or flip the source image.
+    #[clap(flatten)]
+    transform: TransformArgs,
+
+    /// Convert the source image and tiles to grayscale and save a grayscale
+    /// image. (This happens automatically when they are all gray already.)
+    #[clap(long)]
+    grayscale: bool,
+}
+
+// The arguments used to rotate and/or flip the source image. These are
+// applied after the image's EXIF orientation.
+#[derive(Debug, Clone, clap::Args)]
+pub(crate) struct TransformArgs {
+    /// Rotate the source image clockwise by 90, 180, or 270 degrees.
+    #[clap(long, value_name = "DEGREES", value_parser = parse_rotation)]
+    rotate: Option<Rotation>,
+
+    /// Mirror the source image left-to-right (after rotating it).
+    #[clap(long)]
+    flip_h: bool,
+
+    /// Mirror the source image top-to-bottom (after rotating it).
+    #[clap(long)]
+    flip_v: bool,
+}
+
+impl From<&TransformArgs> for Transform {
+    fn from(args: &TransformArgs) -> Self {
+        Self {
+            rotate: args.rotate.unwrap_or_default(),
+            flip_h: args.flip_h,
+            flip_v: args.flip_v,
+        }
+    }
+}
+
+// The arguments used to load a tile set, shared by every subcommand which
+// loads one.
+#[derive(Debug, Clone, clap::Args)]
+pub(crate) struct TileLoadArgs {
+    /// Path to the directory containing the tile set. Each image in this
+    /// directory should be squares of the same size for optimal results.
+    /// May be given more than once to combine several directories.
+    #[clap(short, long, default_value = "tiles/", value_parser, action = clap::ArgAction::Append)]
+    tile_dir: Vec<PathBuf>,
+
+    /// Load tiles through symbolic links (which are skipped otherwise).
+    #[clap(long)]
+    follow_symlinks: bool,
+
+    /// Load files with the same contents as another tile (which are
+    /// skipped otherwise), e.g., to weight tiles by duplicating them.
+    #[clap(long)]
+    keep_duplicates: bool,
+
+    /// Skip tile images whose width or height is below this many pixels
+    /// (e.g., thumbnails and icons, which look poor when scaled up).
+    #[clap(long, value_name = "PX")]
+    min_tile_dim: Option<u32>,
+
+    /// Load tiles from the subdirectories of the tile directories too
+    /// (and from their subdirectories, and so on).
+    #[clap(long)]
+    recursive: bool,
+
+    /// Only load tile files matching this glob pattern (e.g., `*.png`);
+    /// may be given more than once. Patterns containing a `/` (e.g.,
+    /// `beach/*.jpg`) match the path within the tile directory, and others
+    /// the file name.
+    #[clap(long, value_name = "PATTERN", value_parser = parse_glob, action = clap::ArgAction::Append)]
+    tile_include: Vec<Pattern>,
+
+    /// Skip tile files matching this glob pattern (like --tile-include),
+    /// even if they match --tile-include; may be given more than once.
+    #[clap(long, value_name = "PATTERN", value_parser = parse_glob, action = clap::ArgAction::Append)]
+    tile_exclude: Vec<Pattern>,
+
+    /// Color to composite transparent tiles over (e.g., `#ffffff`).
+    #[clap(long, value_name = "HEX", default_value = "#ffffff", value_parser = parse_hex_color)]
+    tile_background: Rgb<u8>,
+}
+
+impl TileLoadArgs {
+    /// Get the options to load the tiles with
+    pub(crate) fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            follow_symlinks: self.follow_symlinks,
+            keep_duplicates: self.keep_duplicates,
+            min_dim: self.min_tile_dim,
+            recursive: self.recursive,
+            include: self.tile_include.clone(),
+            exclude: self.tile_exclude.clone(),
+        }
+    }
+}
+
+/// The colors to fill gaps for with --fill-gaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum GapColors {
+    /// Every color.
+    All,
+    /// The colors of the (scaled) source image.
+    Source,
+}
+
+/// The format in which to save a mosaic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    /// An image, in the format given by the extension of the output path.
+    Image,
+    /// A PDF for printing (requires the `pdf` feature).
+    Pdf,
+    /// Flat blocks of the average color of the tile chosen for each cell
+    /// (see --block-size), e.g., to plan a physical mosaic.
+    Blocks,
+    /// A PNG image, whatever the extension of the output path.
+    Png,
+    /// A JPEG image (see --jpeg-quality), whatever the extension of the
+    /// output path.
+    Jpeg,
+    /// A TIFF image, whatever the extension of the output path.
+    Tiff,
+    /// A BMP image, whatever the extension of the output path.
+    Bmp,
+}
+
+/// The levels of PNG compression which can be chosen with --png-compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CompressionName {
+    /// Compress quickly, making larger files.
+    Fast,
+    /// Balance speed and size.
+    Default,
+    /// Make the smallest files, slowly.
+    Best,
+}
+
+/// The color distance metrics which can be chosen with --metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum MetricName {
+    /// Euclidean distance in RGB.
+    Rgb,
+    /// Euclidean distance in OKLab.
+    Oklab,
+    /// Euclidean distance in CIELAB (CIE76 ΔE).
+    Lab,
+    /// The CIEDE2000 color difference (ΔE00), in CIELAB.
+    Ciede2000,
+}
+
+/// The adjustments which can be chosen with --preprocess
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum PreprocessName {
+    /// A percentile-based contrast stretch.
+    Stretch,
+    /// Per-channel histogram equalization.
+    Equalize,
+    /// Luminance histogram equalization.
+    EqualizeLuminance,
+}
+
+/// The resolution used to size printed mosaics when no width is given
+#[cfg(feature = "pdf")]
+const DEFAULT_PRINT_DPI: f32 = 300.0;
+
+fn main() {
+    // fetch the CLI args, with any defaults from the config file
+    let cmd = Cli::command();
+    let matches = cmd.clone().get_matches();
+    let cli = match config::path(&matches).filter(|_| matches.subcommand().is_none()) {
+        Some(path) => {
+            let flags = config::read(&path, &cmd, &matches).unwrap_or_else(|e| {
+                eprintln!("Error reading config {}: {}.", path.display(), e);
+                std::process::exit(1);
+            });
+            // the config comes first, so the command line is parsed as given
+            let mut args = std::env::args_os();
+            Cli::parse_from(args.next().into_iter().chain(flags).chain(args))
+        }
+        None => Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()),
+    };
+    logger::init(cli.verbose, cli.quiet);
+    match cli.command {
+        Some(Command::Analyze(args)) => analyze::run(args),
+        Some(Command::Compare(args)) => compare::run(args),
+        #[cfg(feature = "gui")]
+        Some(Command::Gui(args)) => gui::run(args),
+        Some(Command::Normalize(args)) => normalize::run(args),
+        Some(Command::Preview(args)) => preview::run(args),
+        #[cfg(feature = "serve")]
+        Some(Command::Serve(args)) => serve::run(args),
+        Some(Command::Stats(args)) => stats::run(args),
+        Some(Command::Verify(args)) => verify::run(args),
+        #[cfg(feature = "video")]
+        Some(Command::Video(args)) => video::run(args),
+        #[cfg(feature = "watch")]
+        None if cli.build.watch => watch::run(cli.build),
+        None => {
+            build(cli.build, &mut Session::default());
+        }
+    }
+}
+
+/// State kept between builds of the same mosaic (with --watch)
+#[derive(Debug, Default)]
+pub(crate) struct Session {
+    /// Whether to keep decoded images for the next build.
+    watching: bool,
+    /// The tiles decoded by the last build (for each --region, if any).
+    caches: Vec<DecodeCache>,
+    /// The source image, if it is unchanged since the last build.
+    source: Option<DynamicImage>,
+    /// The size of the mosaic the user last agreed to build, if any.
+    confirmed: Option<String>,
+}
+
+impl Session {
+    /// Start a session in which images are kept for the next build
+    #[cfg(feature = "watch")]
+    pub(crate) fn watching() -> Self {
+        Self {
+            watching: true,
+            ..Default::default()
+        }
+    }
+
+    /// Forget the source image, so it is loaded again by the next build
+    #[cfg(feature = "watch")]
+    pub(crate) fn source_changed(&mut self) {
+        self.source = None;
+    }
+
+    /// Get the number of tiles decoded (rather than reused) by the last build
+    #[cfg(feature = "watch")]
+    pub(crate) fn decoded(&self) -> usize {
+        self.caches.iter().map(DecodeCache::decoded).sum()
+    }
+
+    /// Load the tiles in `dirs`, reusing any decoded by the last build when
+    /// watching (`cache` identifies the tiles' --region, if any)
+    fn load_tiles(
+        &mut self,
+        dirs: &[PathBuf],
+        options: &LoadOptions,
+        cache: usize,
+    ) -> Result<LoadReport, tilr::Error> {
+        if !self.watching {
+            return tilr::load_tiles_multi(dirs, options);
+        }
+        if self.caches.len() <= cache {
+            self.caches.resize_with(cache + 1, DecodeCache::default);
+        }
+        tilr::load_tiles_cached(dirs, options, &mut self.caches[cache])
+    }
+}
+
+/// Build a mosaic
+///
+/// Returns whether the mosaic was built (rather than declined or a dry run)
+pub(crate) fn build(args: Args, session: &mut Session) -> bool {
+    let src_images = args.src_image;
+    let montage = args.montage.map(|(columns, rows)| Montage {
+        columns,
+        rows,
+        gap: args.montage_gap,
+    });
+    let load_options = args.tile_load.load_options();
+    let tile_background = args.tile_load.tile_background;
+    let tile_dir = args.tile_load.tile_dir;
+    let tile_list = args.tile_list;
+    let null = args.null;
+    let tile_fit = args.tile_fit;
+    let scale = args.scale;
+    let fit_within = args.fit_within;
+    let cell_size = args.cell_size;
+    let cell_color = args.cell_color;
+    let tile_size = args.tile_size;
+    let output = args.output;
+    let preview_first = args.preview_first;
+    let block_size = args.block_size;
+    let cells_csv = args.cells_csv;
+    let report_path = args.report;
+    let usage_map = args.usage_map;
+    let usage_map_for = args.usage_map_for;
+    let sidecar = args.sidecar;
+    let map_cache = args.map_cache;
+    // images in a given format are otherwise saved like any other image
+    let (format, image_format) = match args.format {
+        Format::Png => (Format::Image, Some(ImageFormat::Png)),
+        Format::Jpeg => (Format::Image, Some(ImageFormat::Jpeg)),
+        Format::Tiff => (Format::Image, Some(ImageFormat::Tiff)),
+        Format::Bmp => (Format::Image, Some(ImageFormat::Bmp)),
+        format => (format, None),
+    };
+    #[cfg(feature = "pdf")]
+    let print_width_mm = args.print_width_mm;
+    let dpi = args.dpi;
+    let output_options = OutputOptions {
+        format: image_format,
+        jpeg_quality: args.jpeg_quality,
+        png_compression: match args.png_compression {
+            CompressionName::Fast => PngCompression::Fast,
+            CompressionName::Default => PngCompression::Default,
+            CompressionName::Best => PngCompression::Best,
+        },
+        dpi,
+    };
+    let print_width_cm = args.print_width_cm;
+    let dry_run = args.dry_run;
+    let confirm = ConfirmOptions {
+        yes: args.yes,
+        above: args.confirm_above,
+        max_pixels: args.max_output_pixels,
+    };
+    let window = args.window;
+    let window_cells = args.window_cells;
+    let report_quality = args.report_quality;
+    let fill_gaps = args.fill_gaps;
+    let fill_gaps_for = args.fill_gaps_for;
+    let gap_fill = match args.fill_gaps_noise {
+        true => GapFill::Noise(args.seed),
+        false => GapFill::Solid,
+    };
+    let preprocess = match args.preprocess {
+        None => Preprocess::None,
+        Some(PreprocessName::Stretch) => Preprocess::Stretch {
+            clip: args.stretch_clip,
+        },
+        Some(PreprocessName::Equalize) => Preprocess::Equalize,
+        Some(PreprocessName::EqualizeLuminance) => Preprocess::EqualizeLuminance,
+    };
+    let saturation = args.saturation;
+    let tile_sharpen = args.tile_sharpen;
+    let source_filter = args.source_filter.unwrap_or(args.filter);
+    let tile_filter = args.tile_filter.unwrap_or(args.filter);
+    let posterize = match (args.posterize, args.posterize_colors) {
+        (Some(levels), _) => Posterize::Levels(levels),
+        (_, Some(k)) => Posterize::Colors(k),
+        (None, None) => Posterize::None,
+    };
+    let metric = args.metric;
+    let metric_weights = args.metric_weights;
+    let descriptor = args.descriptor;
+    let center_sigma = args.center_sigma;
+    let dither = args.dither;
+    let error_diffusion = args.error_diffusion;
+    let approx = args.approx;
+    let usage_penalty = args.usage_penalty;
+    let repeat_penalty = args.repeat_penalty;
+    let repeat_radius = args.repeat_radius;
+    let top_k = args.top_k;
+    let top_k_tolerance = args.top_k_tolerance;
+    let unique = args.unique;
+    let flat_shuffle = args.flat_shuffle;
+    let seed = args.seed;
+    let transform = Transform::from(&args.transform);
+    let grayscale = args.grayscale;
+    // leave the gaps of a montage as background
+    let alpha_threshold = match montage {
+        Some(_) => args.alpha_threshold.max(1),
+        None => args.alpha_threshold,
+    };
+    let matte = args.matte;
+    let transparent = args.transparent;
+    let color_adjust = args.color_adjust;
+    let overlay_strength = args.overlay_strength;
+    let synthetic_tile_dir = args.synthetic_tile_dir;
+    let diversity_margin = args.diversity_margin;
+    let self_tiles = args.self_tiles;
+    let self_tiles_flip = args.self_tiles_flip;
+    #[cfg(feature = "video")]
+    let tile_video = args.tile_video.map(|path| {
+        let sampling = match args.frame_every {
+            Some(n) => FrameSampling::Every(n),
+            None => FrameSampling::Count(args.frame_count.unwrap_or(500)),
+        };
+        (path, sampling)
+    });
+    #[cfg(feature = "remote")]
+    let tile_urls = args.tile_urls.map(|list| (list, args.url_cache));
+    let label_mask = args.label_mask;
+    let regions = args.region;
+    let categories = args.categories;
+    let category_limits = args.category_limit;
+    let category_overflow = args.category_overflow;
+    let recurse = args.recurse;
+    let recurse_tile_size = args.recurse_tile_size;
+    let time = args.time;
+    let post = PostProcess {
+        sharpen: args.sharpen,
+        contrast: args.contrast,
+        gamma: args.gamma,
+    };
+    let mut timings = Timings::default();
+    let mut run_report = RunReport::default();
+
+    if transparent && format != Format::Image {
+        error!("--transparent requires an image output (see --format).");
+        std::process::exit(1);
+    }
+    if format == Format::Pdf && !cfg!(feature = "pdf") {
+        error!("tilr was built without PDF support; rebuild it with `--features pdf`.");
+        std::process::exit(1);
+    }
+    if !(-100.0..=100.0).contains(&post.contrast) {
+        error!("--contrast must be between -100 and 100.");
+        std::process::exit(1);
+    }
+    #[cfg(all(feature = "video", feature = "remote"))]
+    if tile_video.is_some() && tile_urls.is_some() {
+        error!("Tiles can't be taken from both a --tile-video and --tile-urls.");
+        std::process::exit(1);
+    }
+    if tile_list.as_deref() == Some(Path::new("-"))
+        && src_images.iter().any(|p| p == Path::new("-"))
+    {
+        error!("The source image and the --tile-list can't both be read from standard input.");
+        std::process::exit(1);
+    }
+    if src_images.iter().filter(|p| *p == Path::new("-")).count() > 1 {
+        error!("Only one source image can be read from standard input.");
+        std::process::exit(1);
+    }
+    if output == Path::new("-") && format != Format::Pdf && image_format.is_none() {
+        error!(
+            "Use --format (e.g., --format png) to choose the format to write to standard output."
+        );
+        std::process::exit(1);
+    }
+    match montage {
+        None if src_images.len() > 1 => {
+            error!("Use --montage to build a mosaic of more than one image.");
+            std::process::exit(1);
+        }
+        Some(m) if src_images.len() as u64 > m.columns as u64 * m.rows as u64 => {
+            error!(
+                "A {}x{} montage has room for {} images, not {}.",
+                m.columns,
+                m.rows,
+                m.columns * m.rows,
+                src_images.len()
+            );
+            std::process::exit(1);
+        }
+        _ => (),
+    }
+    if format == Format::Pdf && !post.is_identity() {
+        warn!("--sharpen, --contrast, and --gamma do not apply to PDFs.");
+    }
+    if format == Format::Blocks && !post.is_identity() {
+        warn!("--sharpen, --contrast, and --gamma do not apply to blocks.");
+    }
+    if format == Format::Pdf && recurse > 1 {
+        error!("Recursive mosaics cannot be saved as PDFs.");
+        std::process::exit(1);
+    }
+    if format == Format::Blocks && recurse > 1 {
+        error!("Recursive mosaics cannot be saved as blocks.");
+        std::process::exit(1);
+    }
+    if window.is_some() && (format != Format::Image || recurse > 1) {
+        error!("--window cannot be used with --format pdf, --format blocks, or --recurse.");
+        std::process::exit(1);
+    }
+
+    // load the image to build a mosaic from
+    let img = match session.source.take() {
+        Some(img) => img,
+        None => {
+            info!("Loading input image...");
+            let mut loaded = Vec::new();
+            let mut converted = Vec::new();
+            for src_image in &src_images {
+                let (img, cmyk) = Timings::measure(&mut timings.load, || {
+                    load_source(src_image).expect("Unable to read image file.")
+                });
+                if cmyk {
+                    converted.push(src_image);
+                }
+                loaded.push(img);
+            }
+            let img = match montage {
+                Some(montage) => {
+                    for (img, path) in loaded.iter().zip(&src_images) {
+                        check_scale(img.dimensions(), scale, Some(path));
+                    }
+                    let canvas = montage.compose(&loaded, scale, source_filter);
+                    DynamicImage::ImageRgba8(canvas)
+                }
+                None => loaded.pop().expect("Source image is required"),
+            };
+            match converted[..] {
+                [] => (),
+                [_] if montage.is_none() => {
+                    info!("Converted the input image from CMYK to RGB.")
+                }
+                _ => {
+                    for path in converted {
+                        info!("Converted {} from CMYK to RGB.", path.display());
+                    }
+                }
+            }
+            img
+        }
+    };
+    // a montage is already scaled
+    let scale = match montage {
+        Some(_) => 1.0,
+        None => scale,
+    };
+    if session.watching {
+        session.source = Some(img.clone());
+    }
+
+    // load the images to use as tiles (with the label of the region
+    // each may be used in, if any)
+    let mut tile_labels = Vec::new();
+    let mut tile_categories = Vec::new();
+    #[cfg(feature = "video")]
+    let video_frames = tile_video.map(|(path, sampling)| {
+        Timings::measure(&mut timings.load, || {
+            video::load_frames(&path, sampling, tile_size.unwrap_or(8))
+        })
+    });
+    #[cfg(not(feature = "video"))]
+    let video_frames: Option<Vec<DynamicImage>> = None;
+    #[cfg(feature = "remote")]
+    let url_report = tile_urls.map(|(list, cache)| {
+        let (urls, remote) = read_url_list(&list, cache);
+        info!("Loading {} tiles from URLs...", fmt_count(urls.len()));
+        Timings::measure(&mut timings.load, || {
+            tilr::load_tile_urls(&urls, &load_options, &remote)
+        })
+        .unwrap_or_else(|e| {
+            error!(
+                "Error caching tiles in {}: {}",
+                remote.cache_dir.display(),
+                e
+            );
+            std::process::exit(1);
+        })
+    });
+    #[cfg(not(feature = "remote"))]
+    let url_report: Option<LoadReport> = None;
+    let (tiles, tile_size) = if let Some((columns, rows)) = self_tiles {
+        info!("Slicing input image into tiles...");
+        let mut tiles = tilr::slice_image(&img, columns, rows);
+        if self_tiles_flip {
+            let flipped: Vec<DynamicImage> =
+                tiles.iter().flat_map(|t| [t.fliph(), t.flipv()]).collect();
+            tiles.extend(flipped);
+            run_report
+                .augmentations
+                .push("Added flipped copies of the slices of the input image".to_string());
+        }
+
+        // default to the size of the slices
+        let slice_size = tiles.iter().map(|t| t.width().min(t.height())).min();
+        let slice_size = slice_size.unwrap_or(8).min(u8::MAX as u32) as u8;
+        (tiles, tile_size.unwrap_or(slice_size))
+    } else if let Some(frames) = video_frames {
+        (frames, tile_size.unwrap_or(8))
+    } else if let Some(report) = url_report {
+        print_load_summary(&report, &[]);
+        run_report.add_load(&report);
+        (report.tiles, tile_size.unwrap_or(8))
+    } else if !regions.is_empty() {
+        let mut tiles = Vec::new();
+        for (i, (label, dir)) in regions.iter().enumerate() {
+            info!("Loading tiles for region {}...", label);
+            let report = Timings::measure(&mut timings.load, || {
+                let dirs = std::slice::from_ref(dir);
+                session
+                    .load_tiles(dirs, &load_options, i)
+                    .expect("Error loading tiles")
+            });
+            print_load_summary(&report, std::slice::from_ref(dir));
+            run_report.add_load(&report);
+            tile_labels.extend(std::iter::repeat_n(*label, report.tiles.len()));
+            tiles.extend(report.tiles);
+        }
+        (tiles, tile_size.unwrap_or(8))
+    } else if categories {
+        let mut groups = vec![(None, tile_dir.clone())];
+        for dir in &tile_dir {
+            groups.extend(
+                category_dirs(dir)
+                    .into_iter()
+                    .map(|(n, d)| (Some(n), vec![d])),
+            );
+        }
+        let mut tiles = Vec::new();
+        for (i, (category, dirs)) in groups.iter().enumerate() {
+            match category {
+                Some(category) => info!("Loading tiles for category {}...", category),
+                None => info!("Loading uncategorized tiles..."),
+            }
+            let mut report = Timings::measure(&mut timings.load, || {
+                session
+                    .load_tiles(dirs, &load_options, i)
+                    .expect("Error loading tiles")
+            });
+            // the subdirectories are loaded as categories
+            report
+                .warnings
+                .retain(|w| w.reason != LoadWarningReason::IsDirectory);
+            print_load_summary(&report, dirs);
+            run_report.add_load(&report);
+            tile_categories.extend(std::iter::repeat_n(category.clone(), report.tiles.len()));
+            tiles.extend(report.tiles);
+        }
+        (tiles, tile_size.unwrap_or(8))
+    } else if let Some(list) = &tile_list {
+        let paths = match read_tile_list(list, null) {
+            Ok(paths) => paths,
+            Err(e) => {
+                error!("Error reading the tile list {}: {}", list.display(), e);
+                std::process::exit(1);
+            }
+        };
+        info!("Loading {} listed tiles...", fmt_count(paths.len()));
+        let report = Timings::measure(&mut timings.load, || {
+            tilr::load_tile_files(&paths, &load_options)
+        });
+        print_load_summary(&report, &[]);
+        run_report.add_load(&report);
+        (report.tiles, tile_size.unwrap_or(8))
+    } else {
+        info!("Loading tiles...");
+        let report = Timings::measure(&mut timings.load, || {
+            session
+                .load_tiles(&tile_dir, &load_options, 0)
+                .expect("Error loading tiles")
+        });
+        print_load_summary(&report, &tile_dir);
+        run_report.add_load(&report);
+        (report.tiles, tile_size.unwrap_or(8))
+    };
+
+    // composite transparent tiles over the chosen backdrop (unless the
+    // mosaic keeps their transparency) and crop them
+    let tiles = match transparent {
+        true => tiles,
+        false => flatten_tiles(tiles, tile_background),
+    };
+    let tiles: Vec<DynamicImage> = tiles.into_iter().map(|t| tile_fit.apply(t)).collect();
+    warn_upscaled(&tiles, tile_size as u32);
+    let scale = match fit_within {
+        Some(max_out) => {
+            let src_dims = transform.output_size(img.dimensions());
+            // fit the pixels of the scaled source, so it divides into
+            // exactly as many cells as fit
+            let tile_size = tile_size as u32;
+            let max_px = (
+                max_out.0 / tile_size * cell_size,
+                max_out.1 / tile_size * cell_size,
+            );
+            let scale = Mosaic::scale_to_fit(src_dims, 1, max_px);
+            if scale == 0.0 {
+                error!(
+                    "A mosaic of this image does not fit within {}x{} with {}px tiles.",
+                    max_out.0, max_out.1, tile_size
+                );
+                std::process::exit(1);
+            }
+            info!("Scaling the input image by {:.3} to fit.", scale);
+            scale
+        }
+        None => scale,
+    };
+    if unique {
+        // fail before scaling anything
+        let size = transform.output_size(img.dimensions());
+        check_unique(size, scale, cell_size, tiles.len());
+    }
+    let (img, tiles) = if grayscale {
+        let tiles = tiles.iter().map(|t| t.grayscale()).collect();
+        (img.grayscale(), tiles)
+    } else {
+        (img, tiles)
+    };
+
+    // build the mosaic
+    let (src_width, _) = transform.output_size(img.dimensions());
+    let mut options = MosaicOptions {
+        alpha_threshold,
+        matte: matte.0,
+        color_adjust,
+        overlay_strength,
+        transform,
+        cell_size,
+        cell_color,
+        descriptor,
+        dither,
+        error_diffusion,
+        approx,
+        usage_penalty,
+        repeat_penalty,
+        repeat_radius,
+        top_k,
+        top_k_tolerance,
+        unique,
+        category_overflow,
+        flat_shuffle,
+        seed,
+        preprocess,
+        saturation,
+        posterize,
+        tile_sharpen,
+        source_filter,
+        tile_filter,
+        ..Default::default()
+    };
+    if let Some(weights) = metric_weights {
+        options.metric = Metric::weighted_rgb(weights);
+    }
+    match metric {
+        Some(MetricName::Oklab) => options.metric = Metric::Oklab,
+        Some(MetricName::Lab) => options.metric = Metric::Lab,
+        Some(MetricName::Ciede2000) => options.metric = Metric::CieDe2000,
+        Some(MetricName::Rgb) | None => {}
+    }
+    let img_dims = options.transform.output_size(img.dimensions());
+    check_scale(img_dims, scale, None);
+
+    // get user confirmation to proceed (so we don't start making hilariously huge images
+    // w/o asking first), before the source and tiles are scaled.
+    let (columns, rows) = options
+        .validate(img.dimensions(), scale)
+        .expect("The scale has already been checked");
+    let (mos_x, mos_y) = match format {
+        Format::Blocks => (columns * block_size, rows * block_size),
+        // the grid of cells is already scaled
+        _ => tilr::estimate_output_size((columns, rows), 1.0, tile_size as u32),
+    };
+    let (mos_x, mos_y) = recursive_size((mos_x, mos_y), recurse, recurse_tile_size)
+        .expect("Recursive mosaic is too large.");
+    let print_size = match dpi {
+        Some(dpi) => format!(", {} at {} DPI", PrintSize::new((mos_x, mos_y), dpi), dpi),
+        None => String::new(),
+    };
+    if let (Some(width_cm), Some(dpi)) = (print_width_cm, dpi) {
+        // the suggested scales are for one pixel of the source per cell
+        let src_columns = src_width.div_ceil(cell_size);
+        print_suggestions(src_columns, tile_size as u32, width_cm, dpi);
+    }
+    let window = window.map(|w| match window_cells {
+        true => {
+            let s = tile_size as u32;
+            Rect::new(w.x * s, w.y * s, w.width * s, w.height * s)
+        }
+        false => w,
+    });
+    let pixels = match window {
+        Some(w) => w.width as u64 * w.height as u64,
+        None => mos_x as u64 * mos_y as u64,
+    };
+    let size = match window {
+        Some(w) if Rect::new(0, 0, mos_x, mos_y).intersect(&w) != Some(w) => {
+            error!(
+                "The window {}px x {}px at ({}, {}) does not fit in the {}px x {}px mosaic.",
+                w.width, w.height, w.x, w.y, mos_x, mos_y
+            );
+            std::process::exit(1);
+        }
+        Some(w) => format!(
+            "{}px x {}px window of a {}px x {}px mosaic",
+            w.width, w.height, mos_x, mos_y
+        ),
+        None => format!("{}px x {}px image{}", mos_x, mos_y, print_size),
+    };
+    if dry_run {
+        info!("Resulting mosaic would be a {}.", size);
+        run_report.notes.push(format!("Dry run of a {}", size));
+    }
+    let records_dpi = matches!(
+        output_options.format_for(&output),
+        Ok(ImageFormat::Png | ImageFormat::Jpeg)
+    );
+    if let Some(dpi) = dpi.filter(|_| format == Format::Image && !records_dpi) {
+        warn!(
+            "{} DPI will not be recorded in {} (only PNG and JPEG support it).",
+            dpi,
+            output.display()
+        );
+    }
+
+    // only ask again (e.g., with --watch) if the size changed
+    // a dry run doesn't build the mosaic, however large it would be
+    let confirmed = dry_run
+        || session.confirmed.as_ref() == Some(&size)
+        || match confirm.decide(pixels, stdin().is_terminal()) {
+            Confirmation::Proceed => {
+                info!("Resulting mosaic will be a {}.", size);
+                true
+            }
+            Confirmation::Ask => user_confirm(&format!(
+                "Resulting mosaic will be a {}. Continue y/N? ",
+                size
+            )),
+            Confirmation::TooLarge => {
+                let note = format!(
+                    "Resulting mosaic would be a {}, which is more than --max-output-pixels ({}).",
+                    size,
+                    fmt_count(confirm.max_pixels.unwrap_or(0) as usize)
+                );
+                error!("{}", note);
+                run_report.notes.push(note);
+                save_run_report(&run_report, report_path.as_deref());
+                std::process::exit(1);
+            }
+            Confirmation::NotInteractive => {
+                let note = format!(
+                    "Resulting mosaic would be a {}, which is more than --confirm-above ({} MP); use --yes to build it without a terminal.",
+                    size, confirm.above
+                );
+                error!("{}", note);
+                run_report.notes.push(note);
+                save_run_report(&run_report, report_path.as_deref());
+                std::process::exit(1);
+            }
+        };
+    if !confirmed {
+        run_report
+            .notes
+            .push(format!("Building a {} was not confirmed", size));
+        save_run_report(&run_report, report_path.as_deref());
+        return false;
+    }
+    session.confirmed = Some(size);
+
+    info!("Initializing mosaic canvas...");
+    let mut mosaic = if let Some(path) = label_mask {
+        let mask = tilr::load_oriented(&path).expect("Unable to read label mask.");
+        let mut groups: Vec<(u8, Vec<DynamicImage>)> = Vec::new();
+        for (label, tile) in tile_labels.into_iter().zip(tiles) {
+            match groups.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, group)) => group.push(tile),
+                None => groups.push((label, vec![tile])),
+            }
+        }
+        let tiles = Timings::measure(&mut timings.averages, || {
+            TileSet::with_labels(&groups, tile_background)
+        });
+        let mut mosaic = Mosaic::with_tile_set(img, tiles, scale, tile_size, options);
+        timings.averages += mosaic.timings().averages;
+        if let Err(e) = mosaic.set_label_mask(&mask) {
+            error!("{} (use --region LABEL=DIR to add them).", e);
+            std::process::exit(1);
+        }
+        mosaic
+    } else if categories {
+        let mut groups: Vec<(Option<String>, Vec<DynamicImage>)> = Vec::new();
+        for (category, tile) in tile_categories.into_iter().zip(tiles) {
+            match groups.iter_mut().find(|(c, _)| *c == category) {
+                Some((_, group)) => group.push(tile),
+                None => groups.push((category, vec![tile])),
+            }
+        }
+        let tiles = Timings::measure(&mut timings.averages, || {
+            TileSet::with_categories(&groups, tile_background)
+        });
+        let mut mosaic = Mosaic::with_tile_set(img, tiles, scale, tile_size, options);
+        timings.averages += mosaic.timings().averages;
+        for (name, share) in &category_limits {
+            if !mosaic.tiles().categories().contains(&name.as_str()) {
+                error!(
+                    "No tiles in category {} (there are: {}).",
+                    name,
+                    mosaic.tiles().categories().join(", ")
+                );
+                std::process::exit(1);
+            }
+            mosaic.tiles_mut().set_category_limit(name, *share);
+        }
+        mosaic
+    } else if transparent {
+        let tiles = Timings::measure(&mut timings.averages, || TileSet::with_alpha(&tiles));
+        let mosaic = Mosaic::with_tile_set(img, tiles, scale, tile_size, options);
+        timings.averages += mosaic.timings().averages;
+        mosaic
+    } else {
+        let mosaic = Mosaic::with_options(img, &tiles, scale, tile_size, options);
+        timings.averages = mosaic.timings().averages;
+        mosaic
+    };
+    // the progress line is redrawn in place, so it isn't logged
+    if log_enabled!(Level::Info) {
+        mosaic.on_progress(|progress| {
+            eprint!("\r{}...          ", progress);
+            if progress.is_done() {
+                eprintln!();
+            }
+        });
+    }
+    if let Some(sigma) = center_sigma {
+        mosaic.tiles_mut().set_center_sigma(sigma);
+    }
+
+    // fill gaps in the colors covered by the tiles
+    if let Some(max_distance) = fill_gaps {
+        let metric = mosaic.options().metric;
+        let added = Timings::measure(&mut timings.averages, || match fill_gaps_for {
+            GapColors::All => mosaic
+                .tiles_mut()
+                .fill_gaps_with(max_distance, metric, gap_fill),
+            GapColors::Source => {
+                let source = mosaic.source().clone();
+                mosaic
+                    .tiles_mut()
+                    .fill_source_gaps(&source, max_distance, metric, gap_fill)
+            }
+        });
+        info!("Added {} synthetic tiles to fill gaps.", fmt_count(added));
+        run_report
+            .augmentations
+            .push(format!("Added {} synthetic tiles to fill gaps", added));
+        let synthetic: Vec<&Tile> = mosaic.tiles().iter().filter(|t| t.is_synthetic()).collect();
+        for tile in &synthetic {
+            let [r, g, b] = tile.avg().0;
+            debug!("  #{:02x}{:02x}{:02x}", r, g, b);
+        }
+        if let Some(dir) = synthetic_tile_dir {
+            info!("Saving synthetic tiles to {}...", dir.display());
+            std::fs::create_dir_all(&dir).expect("Error creating synthetic tile dir.");
+            for tile in synthetic {
+                let [r, g, b] = tile.avg().0;
+                let path = dir.join(format!("synthetic-{:02x}{:02x}{:02x}.png", r, g, b));
+                tile.img().save(path).expect("Error saving synthetic tile.");
+            }
+        }
+    }
+
+    // warn about muddy mosaics before making the user wait for one
+    let check = DiversityCheck {
+        margin: diversity_margin,
+        ..Default::default()
+    };
+    let histogram = ColorHistogram::new(mosaic.source());
+    let warnings = check.check(&mosaic.tiles().summary(), Some(&histogram));
+    print_diversity_warnings(&warnings);
+    run_report
+        .notes
+        .extend(warnings.iter().map(|w| w.to_string()));
+
+    let start = Instant::now();
+    let mut plan = if let Some(path) = &map_cache {
+        let metric = mosaic.options().metric;
+        let mut cache = if path.exists() {
+            MapCache::load(path, mosaic.tiles(), metric).unwrap_or_else(|e| {
+                warn!("ignoring map cache {}: {}", path.display(), e);
+                MapCache::new(mosaic.tiles(), metric)
+            })
+        } else {
+            MapCache::new(mosaic.tiles(), metric)
+        };
+        let cached = cache.len();
+        let plan = mosaic.plan_cached(&mut cache);
+        debug!(
+            "Map cache: {} colors cached, {} searched.",
+            fmt_count(cached),
+            fmt_count(cache.searches())
+        );
+        if !dry_run {
+            info!("Saving map cache to {}...", path.display());
+            cache.save(path).expect("Error saving map cache.");
+        }
+        plan
+    } else {
+        mosaic.plan()
+    };
+    timings.mapping += start.elapsed();
+    run_report.stats = Some(plan.stats(10));
+    if unique {
+        mosaic.options_mut().unique = false;
+        let repeated = mosaic.plan();
+        mosaic.options_mut().unique = true;
+        print_unique_tradeoff(&plan, &repeated);
+    }
+    if dry_run {
+        let gray = mosaic.is_grayscale() && !transparent;
+        let channels = match (transparent, gray) {
+            (true, _) => 4,
+            (false, true) => 1,
+            (false, false) => 3,
+        };
+        let file_size = match format {
+            Format::Pdf => None,
+            Format::Blocks => output_options
+                .format_for(&output)
+                .and_then(|f| {
+                    let img = plan.render_blocks(mosaic.tiles(), block_size);
+                    tilr::encoded_size(&img, f, &output_options)
+                })
+                .ok(),
+            _ => estimate_file_size(
+                &plan,
+                mosaic.tiles(),
+                gray,
+                pixels,
+                &output,
+                &output_options,
+            ),
+        };
+        print_dry_run(&plan, mosaic.tiles(), pixels * channels, file_size);
+        save_run_report(&run_report, report_path.as_deref());
+        return false;
+    }
+    if let Some(path) = preview_first {
+        info!("Saving preview to {}...", path.display());
+        plan.save_preview(mosaic.tiles(), &path)
+            .expect("Error saving preview.");
+    }
+    if let Some(path) = cells_csv {
+        info!("Saving cells to {}...", path.display());
+        plan.save_csv(mosaic.tiles(), &path)
+            .expect("Error saving cells.");
+    }
+    if let Some(path) = usage_map {
+        info!("Saving usage map to {}...", path.display());
+        let saved = match usage_map_for {
+            Some(tile) if tile >= plan.tiles().len() => {
+                error!(
+                    "There is no tile {} (there are {}).",
+                    tile,
+                    plan.tiles().len()
+                );
+                std::process::exit(1);
+            }
+            Some(tile) => tilr::save_image(&plan.usage_mask(tile), &path, None),
+            None => tilr::save_image(&plan.usage_map(), &path, None),
+        };
+        saved.expect("Error saving usage map.");
+    }
+
+    let mut output_sha256 = None;
+    if format == Format::Pdf {
+        info!("Saving PDF to {}...", &output.display());
+        #[cfg(feature = "pdf")]
+        Timings::measure(&mut timings.encoding, || {
+            let dpi = dpi.unwrap_or(DEFAULT_PRINT_DPI);
+            let width_mm = print_width_mm.unwrap_or(mos_x as f32 / dpi * 25.4);
+            let file: Box<dyn Write> = match output == Path::new("-") {
+                true => Box::new(std::io::stdout().lock()),
+                false => Box::new(std::fs::File::create(&output).expect("Error saving PDF.")),
+            };
+            let mut writer = std::io::BufWriter::new(tilr::HashingWriter::new(file));
+            plan.write_pdf(mosaic.tiles(), width_mm, &mut writer)
+                .expect("Error saving PDF.");
+            let (_, hash) = writer
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .expect("Error saving PDF.")
+                .finish();
+            output_sha256 = Some(hash);
+        });
+    } else if format == Format::Blocks {
+        let img = Timings::measure(&mut timings.placement, || {
+            plan.render_blocks(mosaic.tiles(), block_size)
+        });
+        info!("Saving blocks to {}...", &output.display());
+        let hash = Timings::measure(&mut timings.encoding, || {
+            save_output(&img, &output, &output_options)
+        })
+        .expect("Error saving blocks.");
+        output_sha256 = Some(hash);
+    } else {
+        // render grayscale mosaics w/o expanding them to RGB
+        let gray = mosaic.is_grayscale() && !transparent;
+        if gray {
+            debug!("Rendering a grayscale mosaic.");
+        }
+        let img = Timings::measure(&mut timings.placement, || {
+            if recurse > 1 {
+                let img = mosaic
+                    .render_recursive(recurse, recurse_tile_size)
+                    .expect("Error building recursive mosaic.");
+                DynamicImage::ImageRgb8(img)
+            } else if let Some(window) = window {
+                // converted to grayscale below, if need be
+                DynamicImage::ImageRgb8(plan.render_window(mosaic.tiles(), window))
+            } else if transparent {
+                DynamicImage::ImageRgba8(plan.render_rgba(mosaic.tiles()))
+            } else if gray {
+                DynamicImage::ImageLuma8(plan.render_gray(mosaic.tiles()))
+            } else {
+                DynamicImage::ImageRgb8(plan.render(mosaic.tiles()))
+            }
+        });
+        let img = if post.is_identity() {
+            img
+        } else {
+            info!("Post-processing mosaic...");
+            let img = Timings::measure(&mut timings.post_process, || {
+                DynamicImage::ImageRgb8(post.apply(&as_rgb(&img)))
+            });
+            img
+        };
+        let img = match gray {
+            true => DynamicImage::ImageLuma8(img.into_luma8()),
+            false => img,
+        };
+        info!("Saving image to {}...", &output.display());
+        let hash = Timings::measure(&mut timings.encoding, || match &img {
+            DynamicImage::ImageLuma8(img) => save_output(img, &output, &output_options),
+            DynamicImage::ImageRgba8(img) => save_output(img, &output, &output_options),
+            img => save_output(&*as_rgb(img), &output, &output_options),
+        })
+        .expect("Error saving mosaic.");
+        output_sha256 = Some(hash);
+
+        if report_quality && recurse > 1 {
+            warn!("Quality is not reported for recursive mosaics.");
+        } else if report_quality {
+            eprintln!("Quality: {}", mosaic.quality(&as_rgb(&img)));
+        }
+    }
+
+    // record the plan with the hash of the file it was rendered to
+    if let Some(hash) = &output_sha256 {
+        plan.set_output_sha256(hash.as_str());
+    }
+    if let Some(sidecar) = sidecar {
+        info!("Saving plan to {}...", sidecar.display());
+        plan.save(&sidecar).expect("Error saving plan.");
+    }
+
+    if time {
+        eprintln!("Timings:\n{}", timings);
+    }
+    if let Some(hash) = output_sha256 {
+        info!("sha256({}) = {}", output.display(), hash);
+        run_report.output_sha256 = Some(hash);
+    }
+    save_run_report(&run_report, report_path.as_deref());
+    true
+}
+
+/// Load a source image from `path`, or from standard input for `-`
+fn load_source(path: &Path) -> Result<(DynamicImage, bool), tilr::Error> {
+    if path != Path::new("-") {
+        return tilr::load_oriented_cmyk(path);
+    }
+    let mut bytes = Vec::new();
+    stdin().lock().read_to_end(&mut bytes)?;
+    tilr::decode_oriented_cmyk(&bytes)
+}
+
+/// Save the mosaic at `output`, or write it to standard output for `-`,
+/// and get the SHA-256 hash of what was written
+fn save_output<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    output: &Path,
+    options: &OutputOptions,
+) -> Result<String, Box<dyn std::error::Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    if output != Path::new("-") {
+        return tilr::save_image_hashed_with(img, output, options);
+    }
+    let mut writer = std::io::BufWriter::new(tilr::HashingWriter::new(std::io::stdout().lock()));
+    tilr::write_image_with(img, &mut writer, options)?;
+    let (_, hash) = writer.into_inner().map_err(|e| e.into_error())?.finish();
+    Ok(hash)
+}
+
+/// Save the report of a run to `path` (see --report), if given
+fn save_run_report(report: &RunReport, path: Option<&Path>) {
+    if let Some(path) = path {
+        info!("Saving report to {}...", path.display());
+        report.save(path).expect("Error saving report.");
+    }
+}
+
+/// The most pixels of the mosaic which are rendered to estimate the size
+/// of the file it's saved as
+const FILE_SIZE_SAMPLE: u32 = 512;
+
+/// Estimate the size of the file a mosaic of `pixels` pixels (rendered in
+/// grayscale, if `gray`) would be saved as, by rendering and encoding a
+/// sample from the middle of it
+fn estimate_file_size(
+    plan: &MosaicPlan,
+    tiles: &TileSet,
+    gray: bool,
+    pixels: u64,
+    output: &Path,
+    options: &OutputOptions,
+) -> Option<u64> {
+    let format = options.format_for(output).ok()?;
+    let (w, h) = plan.output_size();
+    let (sample_w, sample_h) = (w.min(FILE_SIZE_SAMPLE), h.min(FILE_SIZE_SAMPLE));
+    let sample = Rect::new((w - sample_w) / 2, (h - sample_h) / 2, sample_w, sample_h);
+    let img = plan.render_window(tiles, sample);
+    let bytes = match gray {
+        true => {
+            let gray = DynamicImage::ImageRgb8(img).into_luma8();
+            tilr::encoded_size(&gray, format, options)
+        }
+        false => tilr::encoded_size(&img, format, options),
+    }
+    .ok()?;
+    let sampled = sample_w as u64 * sample_h as u64;
+    Some((bytes as f64 * pixels as f64 / sampled.max(1) as f64).round() as u64)
+}
+
+/// Print what building a mosaic from `plan` would take (see --dry-run):
+/// the tiles it uses, the memory needed for the `output_bytes` of the
+/// rendered mosaic and the tiles, and the size of the file, if known
+fn print_dry_run(plan: &MosaicPlan, tiles: &TileSet, output_bytes: u64, file_size: Option<u64>) {
+    let stats = plan.stats(0);
+    let side = tiles.tile_side_len() as u64;
+    let tile_bytes = tiles.len() as u64 * side * side * 3;
+    eprintln!(
+        "Cells: {} ({} background)",
+        fmt_count(stats.cells),
+        fmt_count(stats.background)
+    );
+    eprintln!(
+        "Tiles: {} unique tiles used, of {}",
+        fmt_count(stats.tiles - stats.unused),
+        fmt_count(stats.tiles)
+    );
+    eprintln!(
+        "Estimated memory: {} ({} for the mosaic, {} for the tiles)",
+        fmt_bytes(output_bytes + tile_bytes),
+        fmt_bytes(output_bytes),
+        fmt_bytes(tile_bytes)
+    );
+    match file_size {
+        Some(bytes) => eprintln!("Estimated file size: {}", fmt_bytes(bytes)),
+        None => eprintln!("Estimated file size: unknown"),
+    }
+}
+
+/// Print the combinations of scale and tile size needed to print a mosaic
+/// `width_cm` wide at `dpi`
+fn print_suggestions(src_width: u32, tile_size: u32, width_cm: f32, dpi: f32) {
+    eprintln!(
+        "To print {}cm wide at {} DPI, the mosaic must be {}px wide:",
+        width_cm,
+        dpi,
+        fmt_count(tilr::print_width_px(width_cm, dpi) as usize)
+    );
+    let mut sizes = vec![tile_size, 8, 16, 32, 64];
+    sizes.sort_unstable();
+    sizes.dedup();
+    for size in sizes {
+        let scale = tilr::scale_for_print(src_width, size, width_cm, dpi);
+        // smaller scales are allowed, but unlikely to be what's wanted
+        if scale >= MosaicOptions::SMALL_SCALE {
+            eprintln!("  --tile-size {:<3} --scale {:.3}", size, scale);
+        }
+    }
+}
+
+/// Print a summary of the tiles which were loaded (and skipped); the files
+/// which were skipped, and why, are logged by the library with -v
+pub(crate) fn print_load_summary(report: &LoadReport, dirs: &[PathBuf]) {
+    let loaded = report.tiles.len();
+    let skipped = report.warnings.len();
+    let duplicates = report
+        .warnings
+        .iter()
+        .filter(|w| matches!(w.reason, LoadWarningReason::Duplicate(_)))
+        .count();
+    let skipped = match duplicates {
+        0 => fmt_count(skipped),
+        n if n == skipped => format!("{} exact duplicates", fmt_count(n)),
+        n => format!("{} ({} exact duplicates)", fmt_count(skipped), fmt_count(n)),
+    };
+    if report.warnings.is_empty() {
+        info!("Loaded {} tiles.", fmt_count(loaded));
+    } else if !log_enabled!(Level::Debug) {
+        info!(
+            "Loaded {} tiles, skipped {} — run with -v for details.",
+            fmt_count(loaded),
+            skipped
+        );
+    } else {
+        info!("Loaded {} tiles, skipped {}.", fmt_count(loaded), skipped);
+    }
+
+    if !report.cmyk.is_empty() {
+        let converted = fmt_count(report.cmyk.len());
+        info!("Converted {} tiles from CMYK to RGB.", converted);
+    }
+
+    if dirs.len() > 1 {
+        // tiles found in more than one directory are only loaded from the first
+        let mut counts = vec![0; dirs.len()];
+        for path in &report.paths {
+            if let Some(i) = dirs.iter().position(|d| path.starts_with(d)) {
+                counts[i] += 1;
+            }
+        }
+        for (dir, count) in dirs.iter().zip(counts) {
+            debug!("  {}: {} tiles", dir.display(), fmt_count(count));
+        }
+    }
+}
+
+/// Parse a grid size given as `COLSxROWS` (e.g., `16x9`) or `N` (for NxN)
+fn parse_grid(s: &str) -> Result<(u32, u32), String> {
+    let parse = |n: &str| match n.trim().parse::<u32>() {
+        Ok(0) => Err("grid dimensions must be non-zero".to_string()),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    };
+    match s.split_once(['x', 'X']) {
+        Some((columns, rows)) => Ok((parse(columns)?, parse(rows)?)),
+        None => parse(s).map(|n| (n, n)),
+    }
+}
+
+/// Parse the limit on the share of cells for a category of tiles (e.g.,
+/// `sky=15%`), as a fraction
+fn parse_category_limit(s: &str) -> Result<(String, f32), String> {
+    let (name, percent) = s
+        .split_once('=')
+        .ok_or("expected NAME=PERCENT (e.g., `sky=15%`)")?;
+    let percent = percent.trim();
+    let percent: f32 = percent
+        .strip_suffix('%')
+        .unwrap_or(percent)
+        .parse()
+        .map_err(|_| format!("invalid percentage `{}`", percent))?;
+    if !(percent > 0.0 && percent <= 100.0) {
+        return Err("the percentage must be more than 0 and at most 100".into());
+    }
+    Ok((name.trim().to_string(), percent / 100.0))
+}
+
+/// Find the subdirectories of a tile directory, each holding a category of
+/// tiles named after it (sorted by name)
+fn category_dirs(dir: &Path) -> Vec<(String, PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<(String, PathBuf)> = entries
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .filter_map(|p| Some((p.file_name()?.to_str()?.to_string(), p)))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Read the URLs in a --tile-urls file, and where to keep the tiles
+/// downloaded from them (`cache`, or the user's cache directory)
+#[cfg(feature = "remote")]
+fn read_url_list(list: &Path, cache: Option<PathBuf>) -> (Vec<String>, RemoteOptions) {
+    let urls = match std::fs::read_to_string(list) {
+        Ok(text) => tilr::parse_url_list(&text),
+        Err(e) => {
+            error!("Error reading the URL list {}: {}", list.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let cache = cache.unwrap_or_else(|| {
+        let home = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache"));
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or(home)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("tilr")
+            .join("urls")
+    });
+    (urls, RemoteOptions::new(cache))
+}
+
+/// Read the paths in a --tile-list file (or standard input, for `-`)
+fn read_tile_list(list: &Path, null: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut bytes = Vec::new();
+    if list == Path::new("-") {
+        stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        bytes = std::fs::read(list)?;
+    }
+    Ok(parse_tile_list(&bytes, null))
+}
+
+/// Split a --tile-list into paths, separated by newlines (or NULs),
+/// skipping empty lines
+fn parse_tile_list(bytes: &[u8], null: bool) -> Vec<PathBuf> {
+    let separator = if null { b'\0' } else { b'\n' };
+    bytes
+        .split(|&b| b == separator)
+        .map(|path| match null {
+            true => path,
+            false => path.strip_suffix(b"\r").unwrap_or(path),
+        })
+        .filter(|path| !path.is_empty())
+        .map(path_from_bytes)
+        .collect()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Parse a region of the label mask and its tile directory (e.g., `0=tiles/sky`)
+fn parse_region(s: &str) -> Result<(u8, PathBuf), String> {
+    let (label, dir) = s
+        .split_once('=')
+        .ok_or("expected LABEL=DIR (e.g., `0=tiles/sky`)")?;
+    let label = label
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid label `{}` (expected 0-255)", label))?;
+    Ok((label, PathBuf::from(dir)))
+}
+
+/// Parse a glob pattern selecting tile files (e.g., `*.png`)
+fn parse_glob(s: &str) -> Result<Pattern, String> {
+    Pattern::new(s).map_err(|e| format!("invalid pattern: {}", e))
+}
+
+/// Parse a rectangle of the mosaic (e.g., `100,200,640x480`)
+fn parse_window(s: &str) -> Result<Rect, String> {
+    let err = || "expected X,Y,WxH (e.g., `100,200,640x480`)".to_string();
+    let mut parts = s.splitn(3, ',');
+    let (x, y, size) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), Some(size)) => (x, y, size),
+        _ => return Err(err()),
+    };
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(err)?;
+    let parse = |n: &str| n.trim().parse::<u32>().map_err(|e| e.to_string());
+    let window = Rect::new(parse(x)?, parse(y)?, parse(width)?, parse(height)?);
+    if window.is_empty() {
+        return Err("the window must not be empty".into());
+    }
+    Ok(window)
+}
+
+/// Get the size of a mosaic of the given `size` once each cell is made
+/// recursive to `depth` with tiles of `inner_tile_size` (see --recurse), or
+/// `None` if it doesn't fit in a `u32`
+fn recursive_size((x, y): (u32, u32), depth: u32, inner_tile_size: u32) -> Option<(u32, u32)> {
+    let factor = inner_tile_size.checked_pow(depth.saturating_sub(1))?;
+    Some((x.checked_mul(factor)?, y.checked_mul(factor)?))
+}
+
+/// Check that a source image with the given (transformed) dimensions can
+/// be scaled by `scale`, exiting with an error if not, and warn if the
+/// scale is so small the mosaic is unlikely to resemble it
+fn check_scale(dims: (u32, u32), scale: f32, path: Option<&PathBuf>) {
+    let name = path.map_or(String::new(), |p| format!("{}: ", p.display()));
+    // the dimensions are already transformed
+    match MosaicOptions::default().validate(dims, scale) {
+        Ok((columns, rows)) if scale < MosaicOptions::SMALL_SCALE => warn!(
+            "{}scaling by {} leaves only {}x{} cells.",
+            name, scale, columns, rows
+        ),
+        Ok(_) => (),
+        Err(e) => {
+            error!("{}{}.", name, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Parse an approximation factor for tile matching (at least `1.0`)
+fn parse_approx(s: &str) -> Result<f32, String> {
+    let factor: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(factor.is_finite() && factor >= 1.0) {
+        return Err("the factor must be a finite number of at least 1.0".into());
+    }
+    Ok(factor)
+}
+
+/// Parse the spread of the weighting used by --descriptor center
+fn parse_center_sigma(s: &str) -> Result<f32, String> {
+    let sigma: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(sigma.is_finite() && sigma > 0.0) {
+        return Err("the sigma must be a positive, finite number".into());
+    }
+    Ok(sigma)
+}
+
+/// Parse per-channel metric weights (e.g., `2.0,1.0,0.5`)
+/// Parse the percentage clipped by --preprocess stretch
+fn parse_clip(s: &str) -> Result<f32, String> {
+    let clip: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(0.0..50.0).contains(&clip) {
+        return Err("must be at least 0 and less than 50".into());
+    }
+    Ok(clip)
+}
+
+/// Parse a fraction from 0 to 1 (e.g., for --error-diffusion)
+fn parse_fraction(s: &str) -> Result<f32, String> {
+    let fraction: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(0.0..=1.0).contains(&fraction) {
+        return Err("must be from 0 to 1".into());
+    }
+    Ok(fraction)
+}
+
+/// Parse a non-negative number (e.g., for --saturation)
+fn parse_non_negative(s: &str) -> Result<f32, String> {
+    let factor: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(factor.is_finite() && factor >= 0.0) {
+        return Err("must not be negative".into());
+    }
+    Ok(factor)
+}
+
+/// Parse a positive number (e.g., for --dpi)
+fn parse_positive(s: &str) -> Result<f32, String> {
+    let n: f32 = s
+        .parse()
+        .map_err(|e: std::num::ParseFloatError| e.to_string())?;
+    if !(n.is_finite() && n > 0.0) {
+        return Err("must be a positive number".into());
+    }
+    Ok(n)
+}
+
+fn parse_metric_weights(s: &str) -> Result<[f32; 3], String> {
+    let weights = s
+        .split(',')
+        .map(|w| w.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let weights: [f32; 3] = weights
+        .try_into()
+        .map_err(|_| "expected three comma-separated weights".to_string())?;
+    if !weights.iter().all(|w| w.is_finite() && *w > 0.0) {
+        return Err("weights must be positive".into());
+    }
+    Ok(weights)
+}
+
+/// Borrow an RGB image as-is, or convert any other image to RGB
+fn as_rgb(img: &DynamicImage) -> Cow<'_, RgbImage> {
+    match img.as_rgb8() {
+        Some(img) => Cow::Borrowed(img),
+        None => Cow::Owned(img.to_rgb8()),
+    }
+}
+
+/// Parse a clockwise rotation in degrees (`90`, `180`, or `270`)
+fn parse_rotation(s: &str) -> Result<Rotation, String> {
+    match s.parse::<u32>() {
+        Ok(degrees @ (90 | 180 | 270)) => Ok(Rotation::from_degrees(degrees).unwrap()),
+        _ => Err("expected 90, 180, or 270".into()),
+    }
+}
+
+/// Parse a hex color (e.g., `#ff8800` or `f80`)
+pub(crate) fn parse_hex_color(s: &str) -> Result<Rgb<u8>, String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    let digits = hex
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| format!("invalid hex color '{}'", s))?;
+    match digits[..] {
+        [r, g, b] => Ok(Rgb([r * 17, g * 17, b * 17])),
+        [r1, r2, g1, g2, b1, b2] => Ok(Rgb([r1 << 4 | r2, g1 << 4 | g2, b1 << 4 | b2])),
+        _ => Err("expected a color like '#rrggbb' or '#rgb'".into()),
+    }
+}
+
+/// Print the reasons the tiles' colors may be too alike, if any
+pub(crate) fn print_diversity_warnings(warnings: &[DiversityWarning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    let mut message =
+        "the tile set may be too uniform in color, so the mosaic may look muddy:".to_string();
+    for warning in warnings {
+        message.push_str(&format!("\n  {}", warning));
+    }
+    message.push_str(
+        "\n  Consider adding more varied tiles, or --fill-gaps to add the missing colors.",
+    );
+    warn!("{}", message);
+}
+
+/// Exit if a mosaic of a source of the given (transformed) size, scaled
+/// by `scale` and divided into cells of `cell_size`, has more cells than
+/// there are tiles to use once each
+fn check_unique(size: (u32, u32), scale: f32, cell_size: u32, tiles: usize) {
+    let (columns, rows) = tilr::grid_size(size, scale);
+    let (columns, rows) = (columns.div_ceil(cell_size), rows.div_ceil(cell_size));
+    let cells = columns as usize * rows as usize;
+    if cells <= tiles {
+        return;
+    }
+    // the largest scale giving no more cells than tiles
+    let max_scale = scale * (tiles as f32 / cells as f32).sqrt();
+    error!(
+        "--unique needs at least as many tiles as cells, but the mosaic has {} cells \
+         ({} x {}) and there are only {} tiles. Add {} more tiles, or use a --scale \
+         of at most {:.3}.",
+        fmt_count(cells),
+        columns,
+        rows,
+        fmt_count(tiles),
+        fmt_count(cells - tiles),
+        max_scale
+    );
+    std::process::exit(1);
+}
+
+/// Report how much worse the cells match with unique tiles than when
+/// tiles may repeat
+fn print_unique_tradeoff(unique: &MosaicPlan, repeated: &MosaicPlan) {
+    let total = |plan: &MosaicPlan| plan.distances().iter().map(|&d| d as f64).sum::<f64>();
+    let cells = unique.cells().len().max(1) as f64;
+    let (unique, repeated) = (total(unique), total(repeated));
+    let increase = match repeated {
+        0.0 => String::new(),
+        r => format!(" (+{:.1}%)", 100.0 * (unique - r) / r),
+    };
+    info!(
+        "Unique tiles: total distance {:.1} (mean {:.2} per cell), versus {:.1} \
+         (mean {:.2}) if tiles could repeat{}.",
+        unique,
+        unique / cells,
+        repeated,
+        repeated / cells,
+        increase
+    );
+}
+
+/// The most a tile may be scaled up to the tile size without a warning
+const MAX_UPSCALE: u32 = 2;
+
+/// Warn if any tiles would need scaling up by more than [`MAX_UPSCALE`]
+/// to reach the tile size
+fn warn_upscaled(tiles: &[DynamicImage], tile_size: u32) {
+    let small = tiles
+        .iter()
+        .filter(|t| t.width().min(t.height()) * MAX_UPSCALE < tile_size)
+        .count();
+    if small > 0 {
+        warn!(
+            "{} tiles are less than 1/{} of the tile size ({}px) and will be \
+             scaled up; consider --min-tile-dim to skip them.",
+            fmt_count(small),
+            MAX_UPSCALE,
+            tile_size
+        );
+    }
+}
+
+/// Composite any transparent tiles over the given backdrop color
+pub(crate) fn flatten_tiles(tiles: Vec<DynamicImage>, background: Rgb<u8>) -> Vec<DynamicImage> {
+    tiles
+        .into_iter()
+        .map(|t| match t.color().has_alpha() {
+            true => DynamicImage::ImageRgb8(tilr::flatten_alpha(&t, background)),
+            false => t,
+        })
+        .collect()
+}
+
+/// Format a count with thousands separators (e.g., `4,812`)
+pub(crate) fn fmt_count(n: usize) -> String {
+    let digits = n.to_string();
+    let mut s = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            s.push(',');
+        }
+        s.push(c);
+    }
+    s
+}
+
+/// Format a number of bytes in the largest unit it's at least one of
+/// (e.g., `12.3 MB`)
+fn fmt_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// When to ask before building a mosaic (see --yes, --confirm-above, and
+/// --max-output-pixels)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ConfirmOptions {
+    /// Whether to build mosaics of any size (up to `max_pixels`) without asking.
+    yes: bool,
+    /// The size (in megapixels) above which to ask.
+    above: f64,
+    /// The size (in pixels) above which to refuse.
+    max_pixels: Option<u64>,
+}
+
+/// What to do before building a mosaic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Confirmation {
+    /// Build it without asking.
+    Proceed,
+    /// Ask the user whether to build it.
+    Ask,
+    /// Refuse to build it, since it's larger than --max-output-pixels.
+    TooLarge,
+    /// Refuse to build it, since it's large enough to ask about but there
+    /// is no terminal to ask on.
+    NotInteractive,
+}
+
+impl ConfirmOptions {
+    /// Decide what to do before building a mosaic of `pixels` pixels, where
+    /// `is_tty` is whether the input is a terminal
+    fn decide(&self, pixels: u64, is_tty: bool) -> Confirmation {
+        if self.max_pixels.is_some_and(|max| pixels > max) {
+            Confirmation::TooLarge
+        } else if self.yes || pixels as f64 <= self.above * 1e6 {
+            Confirmation::Proceed
+        } else if is_tty {
+            Confirmation::Ask
+        } else {
+            Confirmation::NotInteractive
+        }
+    }
+}
+
+/// Get user confirmation for the given prompt
+///
+/// The prompt is printed to stderr, like the rest of the messages. Anything
+/// but 'y' (including an empty answer, or a closed input) is taken as 'no'.
+fn user_confirm(prompt: &str) -> bool {
+    eprint!("{}", prompt);
+    let _ = stderr().flush();
+
+    let mut s = String::new();
+    if !matches!(stdin().read_line(&mut s), Ok(n) if n > 0) {
+        eprintln!();
+        return false;
+    }
+    parse_answer(&s).unwrap_or_else(|| {
+        eprintln!("Unrecognized input; expected 'y' or 'n'.");
+        false
+    })
+}
+
+/// Parse the answer to a y/N prompt (where no answer means 'no'), or `None`
+/// if it's neither
+fn parse_answer(s: &str) -> Option<bool> {
+    // we only care about the first character
+    match s.trim().to_lowercase().chars().next() {
+        Some('y') => Some(true),
+        Some('n') | None => Some(false),
+        Some(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        use clap::CommandFactory;
+        Cli::command().debug_assert()
+    }
+
+    #[test]
+    fn answers() {
+        for (answer, expected) in [
+            ("y\n", Some(true)),
+            ("Yes\n", Some(true)),
+            ("  y", Some(true)),
+            ("n\n", Some(false)),
+            ("NO\n", Some(false)),
+            ("\n", Some(false)),
+            ("", Some(false)),
+            ("maybe\n", None),
+        ] {
+            assert_eq!(parse_answer(answer), expected, "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn verbosity() {
+        use log::LevelFilter::*;
+        for ((verbose, quiet), expected) in [
+            ((0, 0), Info),
+            ((1, 0), Debug),
+            ((2, 0), Trace),
+            ((5, 0), Trace),
+            ((0, 1), Warn),
+            ((0, 2), Error),
+            ((0, 3), Off),
+            ((1, 1), Info),
+        ] {
+            assert_eq!(logger::level(verbose, quiet), expected);
+        }
+    }
+
+    #[test]
+    fn confirmation() {
+        use Confirmation::*;
+        let options = |yes, max_pixels| ConfirmOptions {
+            yes,
+            above: 100.0,
+            max_pixels,
+        };
+        let small = 50_000_000;
+        let large = 200_000_000;
+        let huge = 400_000_000;
+        let max = Some(300_000_000);
+        // (pixels, --yes, --max-output-pixels, is_tty, expected)
+        let cases = [
+            (small, false, None, true, Proceed),
+            (small, false, None, false, Proceed),
+            (small, true, None, true, Proceed),
+            (small, true, None, false, Proceed),
+            (100_000_000, false, None, false, Proceed),
+            (large, false, None, true, Ask),
+            (large, false, None, false, NotInteractive),
+            (large, true, None, true, Proceed),
+            (large, true, None, false, Proceed),
+            (small, false, max, false, Proceed),
+            (large, false, max, true, Ask),
+            (large, false, max, false, NotInteractive),
+            (large, true, max, false, Proceed),
+            (huge, false, max, true, TooLarge),
+            (huge, false, max, false, TooLarge),
+            (huge, true, max, true, TooLarge),
+            (huge, true, max, false, TooLarge),
+            (huge, true, None, false, Proceed),
+        ];
+        for (pixels, yes, max_pixels, is_tty, expected) in cases {
+            let decided = options(yes, max_pixels).decide(pixels, is_tty);
+            assert_eq!(
+                decided, expected,
+                "{} px, --yes {}, max {:?}, tty {}",
+                pixels, yes, max_pixels, is_tty
+            );
+        }
+    }
+
+    #[test]
+    fn grid() {
+        assert_eq!(parse_grid("16x9"), Ok((16, 9)));
+        assert_eq!(parse_grid("4X3"), Ok((4, 3)));
+        assert_eq!(parse_grid("10"), Ok((10, 10)));
+        assert!(parse_grid("0x4").is_err());
+        assert!(parse_grid("4x").is_err());
+        assert!(parse_grid("ax4").is_err());
+    }
+
+    #[test]
+    fn positive() {
+        assert_eq!(parse_positive("300"), Ok(300.0));
+        assert_eq!(parse_positive("0.5"), Ok(0.5));
+        for s in ["0", "-72", "inf", "NaN", "dpi"] {
+            assert!(parse_positive(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn post_process() {
+        use clap::Parser;
+        let parse = |args: &[&str]| Cli::try_parse_from([&["tilr", "in.png"], args].concat());
+        assert!(parse(&["--gamma", "2.2", "--sharpen", "0.5"]).is_ok());
+        for args in [
+            ["--gamma", "0"],
+            ["--gamma", "inf"],
+            ["--gamma", "NaN"],
+            ["--sharpen", "-1"],
+            ["--sharpen", "inf"],
+        ] {
+            assert!(parse(&args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn metric_weights() {
+        assert_eq!(parse_metric_weights("2.0,1,0.5"), Ok([2.0, 1.0, 0.5]));
+        assert!(parse_metric_weights("1,1").is_err());
+        assert!(parse_metric_weights("1,1,1,1").is_err());
+        assert!(parse_metric_weights("1,0,1").is_err());
+        assert!(parse_metric_weights("1,-1,1").is_err());
+        assert!(parse_metric_weights("1,x,1").is_err());
+    }
+
+    #[test]
+    fn window() {
+        assert_eq!(
+            parse_window("100,200,640x480"),
+            Ok(Rect::new(100, 200, 640, 480))
+        );
+        assert_eq!(parse_window("0, 0, 8X4"), Ok(Rect::new(0, 0, 8, 4)));
+        assert!(parse_window("0,0,0x4").is_err());
+        assert!(parse_window("0,0,8").is_err());
+        assert!(parse_window("0,8x4").is_err());
+        assert!(parse_window("-1,0,8x4").is_err());
+    }
+
+    #[test]
+    fn region() {
+        assert_eq!(parse_region("0=tiles/sky"), Ok((0, "tiles/sky".into())));
+        assert_eq!(parse_region("255=a=b"), Ok((255, "a=b".into())));
+        assert!(parse_region("256=tiles").is_err());
+        assert!(parse_region("tiles/sky").is_err());
+        assert!(parse_region("x=tiles").is_err());
+    }
+
+    #[test]
+    fn tile_list() {
+        let paths = |s: &[u8], null| parse_tile_list(s, null);
+        assert_eq!(
+            paths(b"a.png\ntiles/b c.jpg\n\n", false),
+            [PathBuf::from("a.png"), "tiles/b c.jpg".into()]
+        );
+        assert_eq!(
+            paths(b"a.png\r\nb.png", false),
+            [PathBuf::from("a.png"), "b.png".into()]
+        );
+        assert_eq!(
+            paths(b"line\nbreak.png\0b.png\0", true),
+            [PathBuf::from("line\nbreak.png"), "b.png".into()]
+        );
+        assert!(paths(b"", false).is_empty());
+    }
+
+    #[test]
+    fn rotation() {
+        assert_eq!(parse_rotation("90"), Ok(Rotation::Cw90));
+        assert_eq!(parse_rotation("180"), Ok(Rotation::Cw180));
+        assert_eq!(parse_rotation("270"), Ok(Rotation::Cw270));
+        assert!(parse_rotation("0").is_err());
+        assert!(parse_rotation("45").is_err());
+        assert!(parse_rotation("-90").is_err());
+    }
+
+    #[test]
+    fn hex_color() {
+        assert_eq!(parse_hex_color("#ff8800"), Ok(Rgb([255, 136, 0])));
+        assert_eq!(parse_hex_color("0a0B0c"), Ok(Rgb([10, 11, 12])));
+        assert_eq!(parse_hex_color("#fff"), Ok(Rgb([255, 255, 255])));
+        assert!(parse_hex_color("#ff88").is_err());
+        assert!(parse_hex_color("#gg8800").is_err());
+        assert!(parse_hex_color("").is_err());
+    }
+
+    #[test]
+    fn count_formatting() {
+        assert_eq!(fmt_count(0), "0");
+        assert_eq!(fmt_count(999), "999");
+        assert_eq!(fmt_count(4812), "4,812");
+        assert_eq!(fmt_count(1234567), "1,234,567");
+    }
+}
@@ -0,0 +1,86 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::imageops::FilterType;
+use log::{info, warn};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use tilr::TileFit;
+
+use crate::{fmt_count, print_load_summary, TileLoadArgs};
+
+// The arguments for the `normalize` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Path to the directory in which to save the normalized tiles (as
+    /// PNGs named after the original files). Transparent tiles are
+    /// composited over --tile-background.
+    #[clap(short, long, value_parser)]
+    output: PathBuf,
+
+    /// The side length to scale the tiles to (in pixels).
+    /// [default: the smallest side of any tile]
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    tile_size: Option<u32>,
+
+    /// How to make tiles which are not square into squares: scale them
+    /// (`stretch`), crop the center of them (`center`), or crop the most
+    /// detailed part of them (`smart`).
+    #[clap(long, default_value = "center")]
+    tile_fit: TileFit,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+}
+
+/// Crop and scale a tile set to uniform squares
+pub fn run(args: Args) {
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    print_load_summary(&report, &args.tile_load.tile_dir);
+    if report.tiles.is_empty() {
+        warn!("No tiles to normalize.");
+        return;
+    }
+
+    let size = args.tile_size.unwrap_or_else(|| {
+        let sides = report.tiles.iter().map(|t| t.width().min(t.height()));
+        sides.min().unwrap()
+    });
+    std::fs::create_dir_all(&args.output).expect("Error creating output directory.");
+
+    info!("Saving tiles to {}...", args.output.display());
+    let mut names = HashSet::new();
+    let tiles = crate::flatten_tiles(report.tiles, args.tile_load.tile_background);
+    for (tile, path) in tiles.into_iter().zip(&report.paths) {
+        // name each tile after its file, numbering any repeated names
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{}.png", stem);
+        let mut n = 1;
+        while !names.insert(name.clone()) {
+            n += 1;
+            name = format!("{}-{}.png", stem, n);
+        }
+
+        let tile = args.tile_fit.apply(tile);
+        tile.resize_exact(size, size, FilterType::Triangle)
+            .save(args.output.join(name))
+            .expect("Error saving tile.");
+    }
+    info!("Saved {} {}px tiles.", fmt_count(names.len()), size);
+}
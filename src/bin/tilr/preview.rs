@@ -0,0 +1,313 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::ImageFormat;
+use log::info;
+use std::io::{stdout, Cursor, Write};
+use std::path::PathBuf;
+use tilr::{Mosaic, MosaicOptions, Transform};
+
+use crate::{TileLoadArgs, TransformArgs};
+
+// The arguments for the `preview` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Path to the original image.
+    #[clap(value_parser)]
+    src_image: PathBuf,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// Scaling to apply to the image before building the mosaic.
+    #[clap(short, long, default_value = "1.0")]
+    scale: f32,
+
+    /// The side length to use for the tiles (in pixels). This affects
+    /// which tiles are chosen, since tiles are compared by their average
+    /// color after resizing.
+    #[clap(long, default_value = "8")]
+    tile_size: u8,
+
+    /// Display the preview in the terminal, using the kitty or iTerm2
+    /// inline image protocol. If neither is supported, the preview is
+    /// saved to --output instead.
+    #[clap(long)]
+    inline: bool,
+
+    /// Path at which to save the preview.
+    #[clap(short, long, default_value = "preview.png", value_parser)]
+    output: PathBuf,
+
+    /// Rotate and/or flip the source image.
+    #[clap(flatten)]
+    transform: TransformArgs,
+}
+
+/// A protocol for displaying images inline in a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    /// The kitty graphics protocol.
+    Kitty,
+    /// The iTerm2 inline images protocol (OSC 1337).
+    Iterm2,
+}
+
+/// The largest chunk of base64 data kitty accepts in a single escape sequence
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+/// Preview a mosaic
+pub fn run(args: Args) {
+    info!("Loading input image...");
+    let img = tilr::load_oriented(&args.src_image).expect("Unable to read image file.");
+
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    crate::print_load_summary(&report, &args.tile_load.tile_dir);
+
+    let tiles = crate::flatten_tiles(report.tiles, args.tile_load.tile_background);
+    let options = MosaicOptions {
+        transform: Transform::from(&args.transform),
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(img, &tiles, args.scale, args.tile_size, options);
+    let preview = mosaic.plan().render_averages(mosaic.tiles());
+
+    let protocol = if args.inline {
+        let protocol = detect_protocol(|var| std::env::var(var).ok());
+        if protocol.is_none() {
+            info!("Terminal does not support inline images; saving the preview instead.");
+        }
+        protocol
+    } else {
+        None
+    };
+
+    match protocol {
+        Some(protocol) => {
+            let mut png = Vec::new();
+            preview
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .expect("Error encoding preview.");
+            let columns = std::env::var("COLUMNS")
+                .ok()
+                .and_then(|c| c.parse().ok())
+                .map(|c: u32| c.min(preview.width()));
+            let mut out = stdout().lock();
+            out.write_all(&escape(protocol, &png, columns))
+                .and_then(|_| writeln!(out))
+                .expect("Error writing preview.");
+        }
+        None => {
+            info!("Saving preview to {}...", args.output.display());
+            preview.save(&args.output).expect("Error saving preview.");
+        }
+    }
+}
+
+/// Work out which inline image protocol the terminal supports (if any)
+/// from its environment variables
+fn detect_protocol(var: impl Fn(&str) -> Option<String>) -> Option<Protocol> {
+    let term = var("TERM").unwrap_or_default();
+    let program = var("TERM_PROGRAM").unwrap_or_default();
+    if var("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" || program == "ghostty" {
+        Some(Protocol::Kitty)
+    } else if program == "iTerm.app"
+        || program == "WezTerm"
+        || var("LC_TERMINAL").is_some_and(|t| t == "iTerm2")
+    {
+        Some(Protocol::Iterm2)
+    } else {
+        None
+    }
+}
+
+/// Build the escape sequence(s) to display a PNG image inline, at most
+/// `columns` terminal cells wide (if given)
+fn escape(protocol: Protocol, png: &[u8], columns: Option<u32>) -> Vec<u8> {
+    let data = base64(png);
+    let mut out = Vec::new();
+    match protocol {
+        Protocol::Kitty => {
+            let size = columns.map(|c| format!(",c={}", c)).unwrap_or_default();
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK_SIZE).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let more = (i + 1 < chunks.len()) as u8;
+                if i == 0 {
+                    out.extend(format!("\x1b_Ga=T,f=100{},m={};", size, more).as_bytes());
+                } else {
+                    out.extend(format!("\x1b_Gm={};", more).as_bytes());
+                }
+                out.extend(*chunk);
+                out.extend(b"\x1b\\");
+            }
+        }
+        Protocol::Iterm2 => {
+            let size = columns.map(|c| format!(";width={}", c)).unwrap_or_default();
+            out.extend(
+                format!(
+                    "\x1b]1337;File=inline=1;size={}{};preserveAspectRatio=1:",
+                    png.len(),
+                    size
+                )
+                .as_bytes(),
+            );
+            out.extend(data.as_bytes());
+            out.push(b'\x07');
+        }
+    }
+
+    out
+}
+
+/// Encode bytes as (padded) base64
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// Decode (padded) base64
+    fn decode(s: &str) -> Vec<u8> {
+        let value = |c: u8| match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => panic!("invalid base64 character {:?}", c as char),
+        };
+        assert_eq!(s.len() % 4, 0, "base64 data is not padded");
+        let mut out = Vec::new();
+        for chunk in s.as_bytes().chunks(4) {
+            let pad = chunk.iter().filter(|&&c| c == b'=').count();
+            let n = chunk
+                .iter()
+                .map(|&c| if c == b'=' { 0 } else { value(c) as u32 })
+                .fold(0, |n, v| n << 6 | v);
+            out.extend(&n.to_be_bytes()[1..4 - pad]);
+        }
+        out
+    }
+
+    fn tiny_png() -> Vec<u8> {
+        let img = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8 * 80, y as u8 * 80, 7]));
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn assert_png(data: &[u8]) {
+        let img = image::load_from_memory(&decode(std::str::from_utf8(data).unwrap())).unwrap();
+        assert_eq!(img.to_rgb8().get_pixel(2, 1), &Rgb([160, 80, 7]));
+    }
+
+    #[test]
+    fn base64_encoding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(decode(&base64(b"tilr!")), b"tilr!");
+    }
+
+    #[test]
+    fn kitty() {
+        let out = escape(Protocol::Kitty, &tiny_png(), Some(40));
+        let prefix = b"\x1b_Ga=T,f=100,c=40,m=0;";
+        assert!(out.starts_with(prefix));
+        assert!(out.ends_with(b"\x1b\\"));
+        assert_png(&out[prefix.len()..out.len() - 2]);
+    }
+
+    #[test]
+    fn kitty_chunks() {
+        let png = vec![0; KITTY_CHUNK_SIZE];
+        let out = escape(Protocol::Kitty, &png, None);
+        assert!(out.starts_with(b"\x1b_Ga=T,f=100,m=1;"));
+        let chunks = out
+            .split(|&b| b == b'\x1b')
+            .filter(|c| c.starts_with(b"_G"));
+        assert_eq!(chunks.count(), 2);
+        assert!(out.ends_with(b"\x1b\\"));
+    }
+
+    #[test]
+    fn iterm2() {
+        let png = tiny_png();
+        let out = escape(Protocol::Iterm2, &png, None);
+        let prefix = format!(
+            "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:",
+            png.len()
+        );
+        assert!(out.starts_with(prefix.as_bytes()));
+        assert_eq!(out.last(), Some(&b'\x07'));
+        assert_png(&out[prefix.len()..out.len() - 1]);
+    }
+
+    #[test]
+    fn protocol_detection() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |var: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == var)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        assert_eq!(
+            detect_protocol(env(&[("TERM", "xterm-kitty")])),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            detect_protocol(env(&[("KITTY_WINDOW_ID", "1")])),
+            Some(Protocol::Kitty)
+        );
+        assert_eq!(
+            detect_protocol(env(&[("TERM_PROGRAM", "iTerm.app")])),
+            Some(Protocol::Iterm2)
+        );
+        assert_eq!(
+            detect_protocol(env(&[("TERM_PROGRAM", "WezTerm")])),
+            Some(Protocol::Iterm2)
+        );
+        assert_eq!(detect_protocol(env(&[("TERM", "xterm-256color")])), None);
+    }
+}
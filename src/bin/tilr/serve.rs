@@ -0,0 +1,350 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::info;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// The page which displays the pyramid (with `__DZI__` in place of its name)
+const VIEWER: &str = include_str!("viewer.html");
+
+// The arguments for the `serve` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Path to the DeepZoom (.dzi) file to serve, or to the directory
+    /// containing it. Its tiles are expected in the `<name>_files/`
+    /// directory next to it; no other files are served.
+    #[clap(value_parser)]
+    path: PathBuf,
+
+    /// The port on which to listen.
+    #[clap(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// The address on which to listen (e.g., `0.0.0.0` to allow other
+    /// machines to connect).
+    #[clap(long, default_value = "127.0.0.1")]
+    host: String,
+}
+
+/// A DeepZoom pyramid to serve
+#[derive(Debug)]
+struct Site {
+    /// The (canonical) directory containing the .dzi file. Only the .dzi
+    /// file and the files in its `<name>_files/` directory are served
+    /// from it.
+    root: PathBuf,
+    /// The file name of the .dzi file.
+    dzi: String,
+}
+
+impl Site {
+    /// Find the pyramid at `path` (a .dzi file, or a directory containing one)
+    fn new(path: &Path) -> Result<Self, String> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("Unable to open {}: {}", path.display(), e))?;
+        let dzi = if path.is_dir() {
+            let mut found: Vec<PathBuf> = fs::read_dir(&path)
+                .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|p| is_dzi(p) && p.is_file())
+                .collect();
+            found.sort();
+            found
+                .into_iter()
+                .next()
+                .ok_or_else(|| format!("No .dzi file found in {}", path.display()))?
+        } else if is_dzi(&path) {
+            path
+        } else {
+            return Err(format!("{} is not a .dzi file", path.display()));
+        };
+        let name = dzi.file_name().and_then(|n| n.to_str());
+        let parent = dzi.parent();
+        match (parent, name) {
+            (Some(root), Some(name)) => Ok(Self {
+                root: root.to_path_buf(),
+                dzi: name.to_string(),
+            }),
+            _ => Err(format!("Unable to serve {}", dzi.display())),
+        }
+    }
+
+    /// Get the viewer page for the pyramid
+    fn viewer(&self) -> String {
+        let name = serde_json::to_string(&self.dzi).expect("Unable to encode the file name.");
+        VIEWER.replace("__DZI__", &name)
+    }
+
+    /// Map the path of a URL to the .dzi file or one of the pyramid's tiles
+    ///
+    /// Returns `None` for any other file, even within the same directory.
+    fn file(&self, url: &str) -> Option<PathBuf> {
+        let path = resolve(&self.root, url)?;
+        let stem = Path::new(&self.dzi).file_stem()?.to_str()?;
+        let tiles = self.root.join(format!("{}_files", stem));
+        (path == self.root.join(&self.dzi) || path.starts_with(tiles)).then_some(path)
+    }
+
+    /// Respond to a request for the viewer or a file of the pyramid
+    fn respond(&self, request: Request) -> io::Result<()> {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return request.respond(Response::empty(405));
+        }
+        let path = request.url().split(['?', '#']).next().unwrap_or_default();
+        if path == "/" || path == "/index.html" {
+            let response = Response::from_string(self.viewer())
+                .with_header(content_type("text/html; charset=utf-8"));
+            request.respond(response)
+        } else {
+            match self
+                .file(path)
+                .and_then(|p| Some((File::open(&p).ok()?, p)))
+            {
+                Some((file, path)) => {
+                    let mime = content_type(mime_type(&path));
+                    request.respond(Response::from_file(file).with_header(mime))
+                }
+                None => request.respond(Response::from_string("Not found").with_status_code(404)),
+            }
+        }
+    }
+
+    /// Respond to requests until the server is closed
+    fn serve(&self, server: &Server) {
+        for request in server.incoming_requests() {
+            // errors mean the client went away, so there's nobody to tell
+            let _ = self.respond(request);
+        }
+    }
+}
+
+/// Check whether `path` is a DeepZoom descriptor
+fn is_dzi(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("dzi"))
+}
+
+/// Build a `Content-Type` header
+fn content_type(mime: &str) -> Header {
+    Header::from_bytes("Content-Type", mime).expect("Invalid header.")
+}
+
+/// Get the MIME type of a file served with the pyramid
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase);
+    match ext.as_deref() {
+        Some("dzi" | "xml") => "application/xml",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("json") => "application/json",
+        Some("html") => "text/html; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Decode the percent-encoded characters in a URL path
+///
+/// Returns `None` if the encoding is malformed or doesn't decode to UTF-8.
+fn percent_decode(path: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut iter = path.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Map the path of a URL to a file within `root`
+///
+/// Returns `None` if the path is malformed, tries to leave `root` (with `..`,
+/// or through a symbolic link), or doesn't name a file.
+fn resolve(root: &Path, url: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(url)?;
+    let mut path = root.to_path_buf();
+    for part in decoded.split('/').filter(|p| !p.is_empty()) {
+        // e.g., `..`, or an absolute path (`C:` on Windows)
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if !part.contains('\\') => path.push(name),
+            (Some(Component::CurDir), None) => (),
+            _ => return None,
+        }
+    }
+    let path = path.canonicalize().ok()?;
+    (path.starts_with(root) && path.is_file()).then_some(path)
+}
+
+/// Serve a DeepZoom pyramid and a page to view it
+pub fn run(args: Args) {
+    let site = Site::new(&args.path).unwrap_or_else(|e| panic!("{}", e));
+    let server = Server::http((args.host.as_str(), args.port))
+        .unwrap_or_else(|e| panic!("Unable to listen on {}:{}: {}", args.host, args.port, e));
+    let addr = server
+        .server_addr()
+        .to_ip()
+        .expect("Server is not listening on an IP address.");
+    info!(
+        "Serving {} at http://{}/ (press Ctrl-C to stop)...",
+        site.root.join(&site.dzi).display(),
+        addr
+    );
+    site.serve(&server);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// Create a small pyramid (with files next to it and above it which
+    /// mustn't be served)
+    fn pyramid(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tilr-serve-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        let site = dir.join("site");
+        fs::create_dir_all(site.join("mosaic_files/0")).unwrap();
+        fs::write(
+            site.join("mosaic.dzi"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<Image xmlns="http://schemas.microsoft.com/deepzoom/2008" Format="png" Overlap="1" TileSize="254">
+  <Size Width="1" Height="1"/>
+</Image>"#,
+        )
+        .unwrap();
+        image::RgbImage::new(1, 1)
+            .save(site.join("mosaic_files/0/0_0.png"))
+            .unwrap();
+        fs::write(dir.join("secret.txt"), "secret").unwrap();
+        fs::write(site.join("secret.txt"), "secret").unwrap();
+        site
+    }
+
+    /// Send a GET request, returning the status code, content type, and body
+    fn get(addr: std::net::SocketAddr, path: &str) -> (u16, String, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\nHost: {}\r\n\r\n", path, addr).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8_lossy(&response[..split]).to_string();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        let mime = head
+            .lines()
+            .find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.eq_ignore_ascii_case("content-type")
+                    .then(|| value.trim().to_string())
+            })
+            .unwrap_or_default();
+        (status, mime, response[split + 4..].to_vec())
+    }
+
+    #[test]
+    fn serves_pyramid() {
+        let site = Arc::new(Site::new(&pyramid("pyramid")).unwrap());
+        assert_eq!(site.dzi, "mosaic.dzi");
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let addr = server.server_addr().to_ip().unwrap();
+        let handle = {
+            let (site, server) = (Arc::clone(&site), Arc::clone(&server));
+            thread::spawn(move || site.serve(&server))
+        };
+
+        let (status, mime, body) = get(addr, "/");
+        assert_eq!((status, mime.as_str()), (200, "text/html; charset=utf-8"));
+        let body = String::from_utf8(body).unwrap();
+        assert!(body.contains(r#"const DZI = "mosaic.dzi";"#));
+
+        let (status, mime, _) = get(addr, "/mosaic.dzi");
+        assert_eq!((status, mime.as_str()), (200, "application/xml"));
+        let (status, mime, body) = get(addr, "/mosaic_files/0/0_0.png?v=1");
+        assert_eq!((status, mime.as_str()), (200, "image/png"));
+        assert!(body.starts_with(b"\x89PNG"));
+
+        for path in [
+            "/mosaic_files/1/0_0.png",
+            "/../secret.txt",
+            "/%2e%2e/secret.txt",
+            "/mosaic_files/..%2f..%2fsecret.txt",
+            "/mosaic_files",
+            "/secret.txt",
+            "/./secret.txt",
+            "/mosaic_files/../secret.txt",
+        ] {
+            assert_eq!(get(addr, path).0, 404, "{}", path);
+        }
+
+        server.unblock();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn traversal() {
+        let site = pyramid("traversal");
+        let root = site.canonicalize().unwrap();
+        let tile = root.join("mosaic_files/0/0_0.png");
+        assert_eq!(
+            resolve(&root, "/mosaic_files/0/0_0.png"),
+            Some(tile.clone())
+        );
+        assert_eq!(resolve(&root, "//mosaic_files/./0/0%5f0.png"), Some(tile));
+        for url in [
+            "/../secret.txt",
+            "/mosaic_files/../../secret.txt",
+            "/%2E%2E/secret.txt",
+            "/..%5csecret.txt",
+            "/%zz",
+            "/%c3",
+        ] {
+            assert_eq!(resolve(&root, url), None, "{}", url);
+        }
+
+        // symbolic links out of the root aren't followed
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("../secret.txt"), root.join("link.txt")).unwrap();
+            assert_eq!(resolve(&root, "/link.txt"), None);
+        }
+    }
+
+    #[test]
+    fn finds_dzi() {
+        let site = pyramid("finds");
+        assert_eq!(
+            Site::new(&site.join("mosaic.dzi")).unwrap().dzi,
+            "mosaic.dzi"
+        );
+        assert!(Site::new(&site.join("mosaic_files")).is_err());
+        assert!(Site::new(&site.join("mosaic_files/0/0_0.png")).is_err());
+        assert_eq!(mime_type(Path::new("a/0_0.JPEG")), "image/jpeg");
+    }
+}
@@ -0,0 +1,49 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::error;
+use std::path::PathBuf;
+use tilr::MosaicPlan;
+
+// The arguments for the `stats` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// The mosaic plan to summarize (as saved with --sidecar).
+    #[clap(value_parser)]
+    plan: PathBuf,
+
+    /// The number of most used tiles to list.
+    #[clap(long, default_value = "10")]
+    top: usize,
+
+    /// Print the statistics as JSON.
+    #[clap(long)]
+    json: bool,
+}
+
+/// Report how a saved mosaic plan uses its tiles
+pub fn run(args: Args) {
+    let plan = MosaicPlan::load(&args.plan).unwrap_or_else(|e| {
+        error!("Error loading plan {}: {}", args.plan.display(), e);
+        std::process::exit(1);
+    });
+    let stats = plan.stats(args.top);
+    if args.json {
+        let json = serde_json::to_string_pretty(&stats).expect("Unable to encode statistics.");
+        println!("{}", json);
+    } else {
+        println!("{}", stats);
+    }
+}
@@ -0,0 +1,216 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{DynamicImage, ImageFormat, RgbImage};
+use log::{error, info};
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use tilr::{HashingWriter, MosaicPlan, TileFit, TileSet};
+
+use crate::TileLoadArgs;
+
+/// The exit code for a mosaic which does not match its plan
+const MISMATCH: i32 = 1;
+
+/// The exit code for a mosaic without a plan (or recorded hash) to check
+const MISSING_METADATA: i32 = 2;
+
+/// The default --tolerance for JPEG mosaics, whose compression changes
+/// the color of every cell slightly
+const JPEG_TOLERANCE: f32 = 8.0;
+
+// The arguments for the `verify` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// The mosaic to check.
+    #[clap(value_parser)]
+    mosaic: PathBuf,
+
+    /// The plan the mosaic was built from (as saved with --sidecar).
+    /// [default: the path of the mosaic with a `.json` extension]
+    #[clap(long, value_name = "PATH")]
+    sidecar: Option<PathBuf>,
+
+    /// Render the plan again from the tiles in --tile-dir and compare the
+    /// mosaic to it cell by cell, rather than checking the hash of the
+    /// mosaic recorded in the plan. This also works for plans saved
+    /// without a hash, and finds tiles which have since gone missing.
+    #[clap(long)]
+    deep: bool,
+
+    /// With --deep, the largest mean difference (per channel, from 0 to
+    /// 255) allowed between a cell of the mosaic and the same cell rendered
+    /// again. [default: 0, or 8 for JPEG mosaics]
+    #[clap(long, value_name = "DIFF", requires = "deep")]
+    tolerance: Option<f32>,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// How tiles which are not square were made into squares.
+    #[clap(long, default_value = "stretch")]
+    tile_fit: TileFit,
+}
+
+/// Check that a mosaic matches the plan it was built from
+///
+/// Exits with 0 if it does, 1 if it does not, and 2 if there is no plan
+/// (or recorded hash) to check it against.
+pub fn run(args: Args) {
+    let sidecar = args
+        .sidecar
+        .clone()
+        .unwrap_or_else(|| args.mosaic.with_extension("json"));
+    if !sidecar.exists() {
+        error!(
+            "There is no plan for {} at {}; give its path with --sidecar.",
+            args.mosaic.display(),
+            sidecar.display()
+        );
+        std::process::exit(MISSING_METADATA);
+    }
+    let plan = MosaicPlan::load(&sidecar).unwrap_or_else(|e| {
+        error!("Error loading plan {}: {}", sidecar.display(), e);
+        std::process::exit(MISSING_METADATA);
+    });
+
+    let problem = match args.deep {
+        true => check_cells(&args, &plan),
+        false => check_hash(&args.mosaic, &plan),
+    };
+    match problem {
+        None => println!("{}: OK", args.mosaic.display()),
+        Some(problem) => {
+            println!("{}: MISMATCH ({})", args.mosaic.display(), problem);
+            std::process::exit(MISMATCH);
+        }
+    }
+}
+
+/// Compare the hash of the mosaic to the one recorded in its plan,
+/// returning the problem if they differ
+fn check_hash(mosaic: &Path, plan: &MosaicPlan) -> Option<String> {
+    let Some(expected) = plan.output_sha256() else {
+        error!(
+            "The plan for {} does not record its hash; use --deep to render it again instead.",
+            mosaic.display()
+        );
+        std::process::exit(MISSING_METADATA);
+    };
+    match sha256(mosaic) {
+        Ok(hash) if hash == expected => None,
+        Ok(hash) => Some(format!(
+            "sha256 is {}, but the plan records {}",
+            hash, expected
+        )),
+        Err(e) => Some(format!("unable to read it: {}", e)),
+    }
+}
+
+/// Get the SHA-256 hash (in hex) of a file
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut hasher = HashingWriter::new(io::sink());
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finish().1)
+}
+
+/// Render the plan again and compare the mosaic to it cell by cell,
+/// returning the problem if any cells differ
+fn check_cells(args: &Args, plan: &MosaicPlan) -> Option<String> {
+    let mosaic = match image::open(&args.mosaic) {
+        Ok(img) => img.to_rgb8(),
+        Err(e) => return Some(format!("unable to read it: {}", e)),
+    };
+
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    if report.tiles.is_empty() {
+        return Some("there are no tiles to render the plan with".to_string());
+    }
+    let tiles: Vec<DynamicImage> =
+        crate::flatten_tiles(report.tiles, args.tile_load.tile_background)
+            .into_iter()
+            .map(|t| args.tile_fit.apply(t))
+            .collect();
+    let mut tiles = TileSet::from_slice(&tiles);
+    if tiles.tile_side_len() != plan.tile_size() {
+        tiles.scale_tiles_with(plan.tile_size(), plan.options());
+    }
+
+    info!("Rendering plan...");
+    let expected = match plan.render_by_hash(&tiles) {
+        Ok(img) => img,
+        Err(e) => return Some(e.to_string()),
+    };
+    if mosaic.dimensions() != expected.dimensions() {
+        return Some(format!(
+            "it is {}x{}, but the plan renders a {}x{} mosaic",
+            mosaic.width(),
+            mosaic.height(),
+            expected.width(),
+            expected.height()
+        ));
+    }
+
+    let tolerance = args
+        .tolerance
+        .unwrap_or_else(|| match ImageFormat::from_path(&args.mosaic) {
+            Ok(ImageFormat::Jpeg) => JPEG_TOLERANCE,
+            _ => 0.0,
+        });
+    let differing = differing_cells(&mosaic, &expected, plan, tolerance);
+    differing.first().map(|(x, y)| {
+        format!(
+            "{} of {} cells differ from the plan, e.g., the cell at ({}, {})",
+            differing.len(),
+            plan.cells().len(),
+            x,
+            y
+        )
+    })
+}
+
+/// Find the cells (as `(column, row)`) where the mean difference between
+/// two renderings of a plan is more than `tolerance`
+fn differing_cells(
+    a: &RgbImage,
+    b: &RgbImage,
+    plan: &MosaicPlan,
+    tolerance: f32,
+) -> Vec<(u32, u32)> {
+    let (columns, rows) = plan.grid_size();
+    let side = plan.tile_size();
+    let mut differing = Vec::new();
+    for y in 0..rows {
+        for x in 0..columns {
+            let mut total = 0u64;
+            for py in y * side..(y + 1) * side {
+                for px in x * side..(x + 1) * side {
+                    let (pa, pb) = (a.get_pixel(px, py), b.get_pixel(px, py));
+                    total += (0..3).map(|c| pa[c].abs_diff(pb[c]) as u64).sum::<u64>();
+                }
+            }
+            let mean = total as f32 / (side * side * 3) as f32;
+            if mean > tolerance {
+                differing.push((x, y));
+            }
+        }
+    }
+    differing
+}
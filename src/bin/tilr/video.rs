@@ -0,0 +1,120 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::DynamicImage;
+use log::{debug, error, info, log_enabled, warn, Level};
+use std::path::{Path, PathBuf};
+use tilr::{FrameSampling, MapCache, VideoMosaic};
+
+use crate::{flatten_tiles, fmt_count, print_load_summary, TileLoadArgs};
+
+// The arguments for the `video` subcommand.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Path to the original video.
+    #[clap(value_parser)]
+    input: PathBuf,
+
+    /// Path at which to save the resulting video.
+    #[clap(short, long, default_value = "mosaic.mp4", value_parser)]
+    output: PathBuf,
+
+    /// How to load the tiles.
+    #[clap(flatten)]
+    tile_load: TileLoadArgs,
+
+    /// Scaling to apply to each frame before building its mosaic.
+    #[clap(short, long, default_value = "1.0")]
+    scale: f32,
+
+    /// The side length to use for the tiles (in pixels).
+    #[clap(long, default_value = "8")]
+    tile_size: u8,
+
+    /// Path to a cache of the closest tile to each color, which is loaded
+    /// before building the mosaic (if it matches the tile set) and saved
+    /// afterwards.
+    #[clap(long, value_parser)]
+    map_cache: Option<PathBuf>,
+}
+
+/// Build a mosaic of a video
+pub fn run(args: Args) {
+    info!("Loading tiles...");
+    let options = args.tile_load.load_options();
+    let report =
+        tilr::load_tiles_multi(&args.tile_load.tile_dir, &options).expect("Error loading tiles");
+    print_load_summary(&report, &args.tile_load.tile_dir);
+
+    let tiles = flatten_tiles(report.tiles, args.tile_load.tile_background);
+    let mut mosaic = VideoMosaic::new(&tiles, args.scale, args.tile_size);
+    if let Some(path) = args.map_cache.as_ref().filter(|p| p.exists()) {
+        let metric = mosaic.options().metric;
+        match MapCache::load(path, mosaic.tiles(), metric) {
+            Ok(cache) => *mosaic.cache_mut() = cache,
+            Err(e) => warn!("ignoring map cache {}: {}", path.display(), e),
+        }
+    }
+
+    // the progress line is redrawn in place, so it isn't logged
+    let show_progress = log_enabled!(Level::Info);
+    if show_progress {
+        mosaic.on_progress(|progress| eprint!("\r{}...", progress));
+    }
+    let rendered = mosaic.render(&args.input, &args.output);
+    if show_progress {
+        eprintln!(); // end the progress line
+    }
+    match rendered {
+        Ok(frames) => info!(
+            "Saved {} frames to {}.",
+            fmt_count(frames),
+            args.output.display()
+        ),
+        Err(e) => {
+            error!("Error building video mosaic: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let cache = mosaic.cache();
+    debug!(
+        "Map cache: {} colors, {} searched.",
+        fmt_count(cache.len()),
+        fmt_count(cache.searches())
+    );
+    if let Some(path) = args.map_cache {
+        info!("Saving map cache to {}...", path.display());
+        mosaic.cache().save(&path).expect("Error saving map cache.");
+    }
+}
+
+/// Take the tiles for a mosaic from frames of the video at `path` (see
+/// --tile-video)
+pub fn load_frames(path: &Path, sampling: FrameSampling, tile_size: u8) -> Vec<DynamicImage> {
+    info!("Taking tiles from the frames of {}...", path.display());
+    // the tiles are no larger than the shorter side of the frames, so
+    // there's no need to keep more detail than that
+    match tilr::video_frames(path, sampling, Some(tile_size as u32)) {
+        Ok(frames) => {
+            info!("Took {} frames.", fmt_count(frames.len()));
+            frames
+        }
+        Err(e) => {
+            error!("Error reading frames from {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use log::{error, info, warn};
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{build, fmt_count, Args, Session};
+
+/// How long to wait for changes to settle before rebuilding
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// How often to check whether we've been interrupted while waiting
+const POLL: Duration = Duration::from_millis(200);
+
+/// The paths which the build of a mosaic depends on
+#[derive(Debug)]
+struct Watched {
+    /// The source images.
+    sources: Vec<PathBuf>,
+    /// The label mask, if any.
+    label_mask: Option<PathBuf>,
+    /// The directories of tiles.
+    tile_dirs: Vec<PathBuf>,
+    /// Whether tiles are loaded from subdirectories of `tile_dirs` too.
+    recursive: bool,
+    /// The files written by the build (which are ignored).
+    outputs: Vec<PathBuf>,
+}
+
+/// What changed since the last build of a mosaic
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Changes {
+    /// Whether the source image changed.
+    source: bool,
+    /// Whether the label mask changed.
+    label_mask: bool,
+    /// The tiles which were added, changed, or removed.
+    tiles: BTreeSet<PathBuf>,
+}
+
+impl Changes {
+    /// Check whether nothing the mosaic depends on changed
+    fn is_empty(&self) -> bool {
+        !self.source && !self.label_mask && self.tiles.is_empty()
+    }
+}
+
+impl fmt::Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.source {
+            parts.push("the source image".to_string());
+        }
+        if self.label_mask {
+            parts.push("the label mask".to_string());
+        }
+        match self.tiles.len() {
+            0 => (),
+            1 => parts.push("1 tile".to_string()),
+            n => parts.push(format!("{} tiles", fmt_count(n))),
+        }
+        write!(f, "{}", parts.join(" and "))
+    }
+}
+
+impl Watched {
+    /// Get the paths the build described by `args` depends on
+    fn new(args: &Args) -> Self {
+        let absolute = |p: &Path| std::path::absolute(p).unwrap_or_else(|_| p.into());
+        let tile_dirs = if args.self_tiles.is_some() {
+            Vec::new()
+        } else if !args.region.is_empty() {
+            args.region.iter().map(|(_, dir)| absolute(dir)).collect()
+        } else {
+            args.tile_load
+                .tile_dir
+                .iter()
+                .map(|dir| absolute(dir))
+                .collect()
+        };
+        let outputs = [&args.sidecar, &args.preview_first, &args.map_cache]
+            .into_iter()
+            .flatten()
+            .chain([&args.output])
+            .map(|p| absolute(p))
+            .collect();
+        Self {
+            sources: args.src_image.iter().map(|p| absolute(p)).collect(),
+            label_mask: args.label_mask.as_deref().map(absolute),
+            tile_dirs,
+            recursive: args.tile_load.recursive,
+            outputs,
+        }
+    }
+
+    /// Get the directories to watch for changes (see [`mode`](Self::mode))
+    fn dirs(&self) -> Vec<PathBuf> {
+        let mut dirs: Vec<PathBuf> = self
+            .sources
+            .iter()
+            .chain(&self.label_mask)
+            .filter_map(|p| p.parent().map(Path::to_path_buf))
+            .chain(self.tile_dirs.iter().cloned())
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// Get whether to watch `dir` (one of [`dirs`](Self::dirs)) recursively
+    fn mode(&self, dir: &Path) -> RecursiveMode {
+        match self.recursive && self.tile_dirs.iter().any(|d| d == dir) {
+            true => RecursiveMode::Recursive,
+            false => RecursiveMode::NonRecursive,
+        }
+    }
+
+    /// Work out what the mosaic needs to reload after the given events
+    ///
+    /// Events which don't change the contents of files (e.g., reading them
+    /// or changing their permissions) and events for the files written by
+    /// the build are ignored.
+    fn changes(&self, events: &[Event]) -> Changes {
+        let mut changes = Changes::default();
+        for event in events {
+            if matches!(
+                event.kind,
+                EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_))
+            ) {
+                continue;
+            }
+            for path in &event.paths {
+                if self.outputs.contains(path) {
+                    continue;
+                }
+                if self.sources.contains(path) {
+                    changes.source = true;
+                }
+                if self.label_mask.as_ref() == Some(path) {
+                    changes.label_mask = true;
+                }
+                let in_dir = |dir: &PathBuf| match self.recursive {
+                    true => path.starts_with(dir),
+                    false => path == dir || path.parent() == Some(dir),
+                };
+                if self.tile_dirs.iter().any(in_dir) {
+                    changes.tiles.insert(path.clone());
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Build a mosaic, then rebuild it whenever its inputs change
+pub fn run(args: Args) {
+    if args.tile_list.is_some() {
+        error!("--watch can't follow the tiles in a --tile-list; use --tile-dir instead.");
+        std::process::exit(1);
+    }
+    #[cfg(feature = "video")]
+    if args.tile_video.is_some() {
+        error!("--watch can't follow the tiles in a --tile-video; use --tile-dir instead.");
+        std::process::exit(1);
+    }
+    #[cfg(feature = "remote")]
+    if args.tile_urls.is_some() {
+        error!("--watch can't follow the tiles in --tile-urls; use --tile-dir instead.");
+        std::process::exit(1);
+    }
+    if args.src_image.iter().any(|p| p == Path::new("-")) {
+        error!("--watch can't follow a source image read from standard input.");
+        std::process::exit(1);
+    }
+    let mut session = Session::watching();
+    if !build(args.clone(), &mut session) {
+        return;
+    }
+
+    // stop after the current build on Ctrl-C (or right away on a second Ctrl-C)
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&interrupted);
+    ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        info!("\nStopping...");
+    })
+    .expect("Unable to handle Ctrl-C.");
+
+    let watched = Watched::new(&args);
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).expect("Unable to watch for changes.");
+    for dir in watched.dirs() {
+        watcher
+            .watch(&dir, watched.mode(&dir))
+            .unwrap_or_else(|e| panic!("Unable to watch {}: {}", dir.display(), e));
+    }
+    info!("Watching for changes (press Ctrl-C to stop)...");
+
+    let mut events = Vec::new();
+    while !interrupted.load(Ordering::SeqCst) {
+        match rx.recv_timeout(POLL) {
+            Ok(Ok(event)) => events.push(event),
+            Ok(Err(e)) => warn!("{}", e),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // wait until nothing has changed for a while
+        while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+            match event {
+                Ok(event) => events.push(event),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        let changes = watched.changes(&std::mem::take(&mut events));
+        if changes.is_empty() || interrupted.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        if changes.source {
+            session.source_changed();
+        }
+        let start = Instant::now();
+        let built = panic::catch_unwind(AssertUnwindSafe(|| build(args.clone(), &mut session)));
+        match built {
+            Ok(true) => info!(
+                "Rebuilt {} after changes to {} ({} tiles decoded) in {:.1}s.",
+                args.output.display(),
+                changes,
+                fmt_count(session.decoded()),
+                start.elapsed().as_secs_f32()
+            ),
+            Ok(false) => info!("Skipped rebuilding {}.", args.output.display()),
+            Err(_) => error!("Rebuilding failed; waiting for further changes."),
+        }
+    }
+    info!("Stopped watching.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind, RemoveKind};
+
+    fn watched() -> Watched {
+        Watched {
+            sources: vec!["/work/src.png".into()],
+            label_mask: None,
+            tile_dirs: vec!["/work/tiles".into(), "/more/tiles".into()],
+            recursive: false,
+            outputs: vec!["/work/tiles/mosaic.png".into()],
+        }
+    }
+
+    fn event(kind: EventKind, path: &str) -> Event {
+        Event::new(kind).add_path(path.into())
+    }
+
+    fn modified(path: &str) -> Event {
+        event(
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            path,
+        )
+    }
+
+    #[test]
+    fn source_changed() {
+        let changes = watched().changes(&[modified("/work/src.png")]);
+        assert!(changes.source);
+        assert!(changes.tiles.is_empty());
+        assert_eq!(changes.to_string(), "the source image");
+    }
+
+    #[test]
+    fn tiles_changed() {
+        let changes = watched().changes(&[
+            event(EventKind::Create(CreateKind::File), "/work/tiles/a.png"),
+            modified("/work/tiles/a.png"),
+            event(EventKind::Remove(RemoveKind::File), "/more/tiles/b.png"),
+            modified("/more/tiles"),
+        ]);
+        assert!(!changes.source);
+        let tiles: Vec<&str> = changes.tiles.iter().map(|p| p.to_str().unwrap()).collect();
+        assert_eq!(
+            tiles,
+            ["/more/tiles", "/more/tiles/b.png", "/work/tiles/a.png"]
+        );
+        assert_eq!(changes.to_string(), "3 tiles");
+    }
+
+    #[test]
+    fn ignored() {
+        let changes = watched().changes(&[
+            // other files next to the source, or in subdirectories of tiles
+            modified("/work/other.png"),
+            modified("/work/tiles/nested/a.png"),
+            // reading files or changing their metadata
+            event(EventKind::Access(AccessKind::Any), "/work/src.png"),
+            event(
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)),
+                "/work/tiles/a.png",
+            ),
+            // the mosaic itself
+            modified("/work/tiles/mosaic.png"),
+        ]);
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn recursive() {
+        let watched = Watched {
+            recursive: true,
+            ..watched()
+        };
+        let changes = watched.changes(&[
+            modified("/work/tiles/nested/a.png"),
+            modified("/work/other.png"),
+        ]);
+        let tiles: Vec<&str> = changes.tiles.iter().map(|p| p.to_str().unwrap()).collect();
+        assert_eq!(tiles, ["/work/tiles/nested/a.png"]);
+        assert_eq!(
+            watched.mode(Path::new("/work/tiles")),
+            RecursiveMode::Recursive
+        );
+        assert_eq!(
+            watched.mode(Path::new("/work")),
+            RecursiveMode::NonRecursive
+        );
+    }
+
+    #[test]
+    fn renamed() {
+        // e.g., an editor saving the source by renaming a temporary file
+        let rename = Event::new(EventKind::Modify(ModifyKind::Name(
+            notify::event::RenameMode::Both,
+        )))
+        .add_path("/work/.src.png.tmp".into())
+        .add_path("/work/src.png".into());
+        let changes = watched().changes(&[rename, modified("/work/tiles/c.png")]);
+        assert!(changes.source);
+        assert_eq!(changes.to_string(), "the source image and 1 tile");
+    }
+
+    #[test]
+    fn label_mask() {
+        let watched = Watched {
+            label_mask: Some("/work/mask.png".into()),
+            ..watched()
+        };
+        let changes = watched.changes(&[modified("/work/mask.png")]);
+        assert!(changes.label_mask && !changes.source);
+        assert_eq!(
+            watched.dirs(),
+            [
+                PathBuf::from("/more/tiles"),
+                "/work".into(),
+                "/work/tiles".into()
+            ]
+        );
+    }
+}
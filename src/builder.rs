@@ -0,0 +1,135 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use crate::mosaic::Mosaic;
+use crate::options::MosaicOptions;
+use crate::tiles::{IntoTileSet, TileSet};
+use crate::timings::Timings;
+use image::{DynamicImage, GenericImageView};
+use std::time::Duration;
+
+/// Configures and builds a [`Mosaic`], checking its inputs rather than
+/// panicking on them.
+///
+/// # Example
+/// ```
+/// # use image::{DynamicImage, Rgb, RgbImage};
+/// # use tilr::Mosaic;
+/// let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([200, 40, 40])));
+/// let tiles = vec![
+///     DynamicImage::ImageRgb8(RgbImage::from_pixel(16, 16, Rgb([255, 0, 0]))),
+///     DynamicImage::ImageRgb8(RgbImage::from_pixel(16, 16, Rgb([0, 0, 255]))),
+/// ];
+/// let mosaic = Mosaic::builder(img)
+///     .tiles(&tiles)
+///     .scale(0.5)
+///     .tile_size(4)
+///     .build()?;
+/// assert_eq!(mosaic.output_size(), (16, 16));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[allow(missing_debug_implementations)]
+pub struct MosaicBuilder {
+    img: DynamicImage,
+    /// The tiles, once they're given (or why they can't be used).
+    tiles: Option<Result<TileSet, Error>>,
+    img_scaling: f32,
+    tile_size: Option<u8>,
+    options: MosaicOptions,
+    /// The time spent building the [`TileSet`] from images.
+    averages: Duration,
+}
+
+impl MosaicBuilder {
+    /// Start configuring a mosaic of `img` (see [`Mosaic::builder`]).
+    pub(crate) fn new(img: DynamicImage) -> Self {
+        Self {
+            img,
+            tiles: None,
+            img_scaling: 1.0,
+            tile_size: None,
+            options: MosaicOptions::default(),
+            averages: Duration::ZERO,
+        }
+    }
+
+    /// Use the given images (e.g., a `&Vec<DynamicImage>`, a slice, or an
+    /// iterator of owned or borrowed images), or a [`TileSet`], as the
+    /// tiles of the mosaic (see [`IntoTileSet`]). This replaces any tiles
+    /// given before.
+    pub fn tiles<I: IntoTileSet>(mut self, tiles: I) -> Self {
+        let mut averages = Duration::ZERO;
+        let tiles = Timings::measure(&mut averages, || tiles.into_tile_set());
+        self.tiles = Some(tiles);
+        self.averages = averages;
+        self
+    }
+
+    /// Use a [`TileSet`] which has already been built (e.g., with
+    /// [`TileSet::with_labels`]) as the tiles of the mosaic. This replaces
+    /// any tiles given before.
+    pub fn tile_set(mut self, tiles: TileSet) -> Self {
+        self.tiles = Some(Ok(tiles));
+        self.averages = Duration::ZERO;
+        self
+    }
+
+    /// Set the scaling factor applied to the source image (see
+    /// [`Mosaic::new`]). Defaults to `1.0`, i.e., one cell per pixel.
+    pub fn scale(mut self, img_scaling: f32) -> Self {
+        self.img_scaling = img_scaling;
+        self
+    }
+
+    /// Set the side length of the tiles in the mosaic; tiles of any other
+    /// size are scaled to it. Defaults to the size of the tiles.
+    pub fn tile_size(mut self, tile_size: u8) -> Self {
+        self.tile_size = Some(tile_size);
+        self
+    }
+
+    /// Set the options used to build the mosaic (see
+    /// [`Mosaic::with_options`]). Defaults to [`MosaicOptions::default`].
+    pub fn options(mut self, options: MosaicOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Build the mosaic.
+    ///
+    /// # Errors
+    /// This function returns [`Error::NoTiles`] if no tiles (or an empty
+    /// set of tiles) were given, [`Error::TileSize`] if the tile size is
+    /// zero (or, when it isn't set, the tiles are larger than 255 pixels),
+    /// and [`Error::Scale`] if the scaling factor is invalid for the source
+    /// image (see [`MosaicOptions::validate`]).
+    pub fn build(self) -> Result<Mosaic, Error> {
+        let tiles = self.tiles.ok_or(Error::NoTiles)??;
+        let side_len = tiles.tile_side_len();
+        let tile_size = match self.tile_size {
+            Some(0) => return Err(Error::TileSize(0)),
+            Some(tile_size) => tile_size,
+            None => u8::try_from(side_len).map_err(|_| Error::TileSize(side_len))?,
+        };
+        self.options
+            .validate(self.img.dimensions(), self.img_scaling)?;
+
+        let mut mosaic =
+            Mosaic::with_tile_set(self.img, tiles, self.img_scaling, tile_size, self.options);
+        mosaic.timings.averages += self.averages;
+        Ok(mosaic)
+    }
+}
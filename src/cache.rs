@@ -0,0 +1,203 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::descriptor::Descriptor;
+use crate::metric::Metric;
+use crate::tiles::TileSet;
+use crate::utils::fnv1a;
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+/// A persistent map from colors to the index of the closest [`Tile`](crate::Tile)
+/// in a [`TileSet`].
+///
+/// Finding the closest [`Tile`](crate::Tile) to a color is the most expensive
+/// part of planning a mosaic. When many mosaics are built with the same
+/// [`TileSet`] (e.g., the frames of an animation), the same colors come up
+/// again and again; this cache remembers the answers so each color only
+/// has to be searched for once. It can be [saved](MapCache::save) and
+/// [loaded](MapCache::load) to share those answers between runs.
+///
+/// A cache is only valid for the [`TileSet`], [`Metric`], and [`Descriptor`]
+/// it was built for. These are identified by a fingerprint of the number
+/// and size of the [`Tile`](crate::Tile)s, the metric, the descriptor, and
+/// the color (as summarized by the descriptor) and weight of every
+/// [`Tile`](crate::Tile); see
+/// [`Mosaic::plan_cached`](crate::Mosaic::plan_cached). Only descriptors
+/// which summarize each cell with a single color (i.e., with a
+/// [grid size](Descriptor::grid_size) of 1) can be cached.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapCache {
+    /// Identifies the tile set, metric, and descriptor this cache was
+    /// built for.
+    fingerprint: String,
+    /// The descriptor used to compare colors to tiles.
+    #[serde(default)]
+    descriptor: Descriptor,
+    /// The index of the closest tile to each color, keyed by the
+    /// packed (`0xRRGGBB`) color.
+    entries: BTreeMap<u32, usize>,
+    /// A hash of the entries, used to detect corrupt caches.
+    checksum: String,
+    /// The number of closest-tile searches performed by this cache.
+    #[serde(skip)]
+    searches: usize,
+}
+
+impl MapCache {
+    /// Create an empty cache for the given [`TileSet`] and [`Metric`], with
+    /// [`Descriptor::Mean`].
+    pub fn new(tiles: &TileSet, metric: Metric) -> Self {
+        Self::for_descriptor(tiles, metric, Descriptor::Mean)
+    }
+
+    /// Create an empty cache for the given [`TileSet`], [`Metric`], and
+    /// [`Descriptor`].
+    ///
+    /// # Panics
+    /// This function panics if the descriptor compares more than one block
+    /// of each cell (e.g., [`Descriptor::Quadrants`]).
+    pub fn for_descriptor(tiles: &TileSet, metric: Metric, descriptor: Descriptor) -> Self {
+        if descriptor.grid_size() > 1 {
+            panic!("Only single-color descriptors can be cached");
+        }
+        Self {
+            fingerprint: fingerprint(tiles, metric, descriptor),
+            descriptor,
+            entries: BTreeMap::new(),
+            checksum: String::new(),
+            searches: 0,
+        }
+    }
+
+    /// Load a cache previously written with [`save`](MapCache::save).
+    ///
+    /// # Errors
+    /// This function returns an error if the cache cannot be read, if it is
+    /// corrupt, or if it was built for a different [`TileSet`] or [`Metric`].
+    /// (The cache keeps the [`Descriptor`] it was built for; see
+    /// [`descriptor`](MapCache::descriptor).)
+    /// In any of these cases, the cache should be discarded (and a
+    /// [new](MapCache::new) one used instead).
+    pub fn load(path: &Path, tiles: &TileSet, metric: Metric) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let cache: Self = serde_json::from_reader(reader)?;
+
+        if cache.descriptor.grid_size() > 1
+            || cache.fingerprint != fingerprint(tiles, metric, cache.descriptor)
+        {
+            return Err("Cache was built for a different tile set or metric".into());
+        }
+        if cache.checksum != checksum(&cache.entries) {
+            return Err("Cache is corrupt (checksum mismatch)".into());
+        }
+        if let Some((&color, &idx)) = cache
+            .entries
+            .iter()
+            .find(|(&color, &idx)| color > 0xffffff || idx >= tiles.len())
+        {
+            return Err(format!("Cache is corrupt (invalid entry {:#x} -> {})", color, idx).into());
+        }
+
+        Ok(cache)
+    }
+
+    /// Save this cache as JSON at the given `path`.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let cache = Self {
+            checksum: checksum(&self.entries),
+            ..self.clone()
+        };
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, &cache)?;
+        Ok(())
+    }
+
+    /// Get the number of colors in this cache.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether this cache contains no colors.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the number of closest-tile searches this cache has performed
+    /// (i.e., the number of cache misses) since it was created or loaded.
+    pub fn searches(&self) -> usize {
+        self.searches
+    }
+
+    /// Get the [`Descriptor`] this cache was built for.
+    pub fn descriptor(&self) -> Descriptor {
+        self.descriptor
+    }
+
+    /// Check whether this cache was built for the given [`TileSet`] and
+    /// [`Metric`] (with its own [`descriptor`](MapCache::descriptor)).
+    pub fn matches(&self, tiles: &TileSet, metric: Metric) -> bool {
+        self.fingerprint == fingerprint(tiles, metric, self.descriptor)
+    }
+
+    /// Get the index of the closest [`Tile`](crate::Tile) to `px`, searching
+    /// the [`TileSet`] (and remembering the answer) if it isn't cached.
+    pub(crate) fn closest_tile(&mut self, tiles: &TileSet, px: &Rgb<u8>, metric: Metric) -> usize {
+        let [r, g, b] = px.0;
+        let color = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        *self.entries.entry(color).or_insert_with(|| {
+            self.searches += 1;
+            match self.descriptor {
+                Descriptor::Mean => tiles.closest_tile(px, metric),
+                d => tiles.closest_to(std::slice::from_ref(px), d, metric, None),
+            }
+        })
+    }
+}
+
+/// Identify a [`TileSet`], [`Metric`], and [`Descriptor`] by everything
+/// which affects which [`Tile`](crate::Tile) is closest to a color.
+fn fingerprint(tiles: &TileSet, metric: Metric, descriptor: Descriptor) -> String {
+    let mut bytes = Vec::new();
+    bytes.extend((tiles.len() as u64).to_le_bytes());
+    bytes.extend(tiles.tile_side_len().to_le_bytes());
+    bytes.extend(serde_json::to_vec(&metric).expect("Metric is serializable"));
+    // caches built before descriptors were recorded are all for the mean
+    if descriptor != Descriptor::Mean {
+        bytes.extend(serde_json::to_vec(&descriptor).expect("Descriptor is serializable"));
+    }
+    for tile in tiles.iter() {
+        bytes.extend(tile.descriptor(descriptor)[0].0);
+        bytes.extend(tile.weight().to_le_bytes());
+    }
+
+    format!("{:016x}", fnv1a(&bytes))
+}
+
+/// Hash the entries of a cache.
+fn checksum(entries: &BTreeMap<u32, usize>) -> String {
+    let mut bytes = Vec::with_capacity(entries.len() * 12);
+    for (color, idx) in entries {
+        bytes.extend(color.to_le_bytes());
+        bytes.extend((*idx as u64).to_le_bytes());
+    }
+
+    format!("{:016x}", fnv1a(&bytes))
+}
@@ -0,0 +1,165 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{DynamicImage, ImageBuffer, Pixel, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// The number of bits kept from each channel when grouping the pixels of a
+/// cell into colors for [`CellColor::Dominant`].
+const DOMINANT_BITS: u32 = 4;
+
+/// How the color of each cell of the source image is chosen from its
+/// pixels, when cells cover more than one pixel (see
+/// [`MosaicOptions::cell_size`](crate::MosaicOptions::cell_size)).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CellColor {
+    /// The average color of the pixels in the cell.
+    #[default]
+    Average,
+    /// The most common color in the cell: the pixels are grouped into
+    /// colors with 16 levels per channel, and the average of the largest
+    /// group is used (ties go to the group of the lowest red, then green,
+    /// then blue levels). This keeps the edges between
+    /// regions of flat color (e.g., in logos and pixel art) crisp, rather
+    /// than blending them into colors found in neither region.
+    Dominant,
+}
+
+/// Divide an image into cells of `cell_size` x `cell_size` pixels, and get
+/// the color of each cell (as chosen by `color`) as one pixel of a smaller
+/// image. The cells along the right and bottom edges cover what is left of
+/// the image, so they may be smaller.
+///
+/// Every channel (including alpha) is averaged over the same pixels.
+pub(crate) fn cell_colors(img: DynamicImage, cell_size: u32, color: CellColor) -> DynamicImage {
+    if cell_size <= 1 {
+        return img;
+    }
+    match img {
+        DynamicImage::ImageRgb8(img) => DynamicImage::ImageRgb8(divide(&img, cell_size, color)),
+        img if img.color().has_alpha() => {
+            DynamicImage::ImageRgba8(divide(&img.into_rgba8(), cell_size, color))
+        }
+        img => DynamicImage::ImageRgb8(divide(&img.into_rgb8(), cell_size, color)),
+    }
+}
+
+/// Divide an [`RgbImage`] into cells, like [`cell_colors`].
+pub(crate) fn cell_colors_rgb(img: RgbImage, cell_size: u32, color: CellColor) -> RgbImage {
+    match cell_size <= 1 {
+        true => img,
+        false => divide(&img, cell_size, color),
+    }
+}
+
+/// Get the size of the grid of cells of `cell_size` x `cell_size` pixels
+/// over an image of the given dimensions (see [`cell_colors`]).
+pub(crate) fn cell_grid((x, y): (u32, u32), cell_size: u32) -> (u32, u32) {
+    let cell_size = cell_size.max(1);
+    (x.div_ceil(cell_size), y.div_ceil(cell_size))
+}
+
+/// Divide an image into cells; see [`cell_colors`].
+fn divide<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    cell_size: u32,
+    color: CellColor,
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+{
+    let (columns, rows) = cell_grid(img.dimensions(), cell_size);
+    ImageBuffer::from_fn(columns, rows, |cx, cy| {
+        let (x0, y0) = (cx * cell_size, cy * cell_size);
+        let (x1, y1) = (
+            (x0 + cell_size).min(img.width()),
+            (y0 + cell_size).min(img.height()),
+        );
+        let pixels = (y0..y1).flat_map(|y| (x0..x1).map(move |x| img.get_pixel(x, y)));
+        match color {
+            CellColor::Average => average(pixels),
+            CellColor::Dominant => dominant(pixels),
+        }
+    })
+}
+
+/// Get the average of some pixels, channel by channel
+fn average<'a, P, I>(pixels: I) -> P
+where
+    P: Pixel<Subpixel = u8> + 'a,
+    I: Iterator<Item = &'a P>,
+{
+    let mut sums = [0u64; 4];
+    let mut count = 0u64;
+    for px in pixels {
+        for (sum, &c) in sums.iter_mut().zip(px.channels()) {
+            *sum += c as u64;
+        }
+        count += 1;
+    }
+    let mut avg = *P::from_slice(&[0; 4][..P::CHANNEL_COUNT as usize]);
+    for (c, sum) in avg.channels_mut().iter_mut().zip(sums) {
+        *c = ((sum + count / 2) / count) as u8;
+    }
+    avg
+}
+
+/// Get the average of the largest group of similar pixels; see
+/// [`CellColor::Dominant`]
+fn dominant<'a, P, I>(pixels: I) -> P
+where
+    P: Pixel<Subpixel = u8> + 'a,
+    I: Iterator<Item = &'a P>,
+{
+    let shift = 8 - DOMINANT_BITS;
+    let mut groups: HashMap<[u8; 3], Vec<&P>> = HashMap::new();
+    for px in pixels {
+        let rgb = px.to_rgb().0;
+        groups.entry(rgb.map(|c| c >> shift)).or_default().push(px);
+    }
+    let (_, group) = groups
+        .into_iter()
+        .max_by(|(a, a_px), (b, b_px)| a_px.len().cmp(&b_px.len()).then(b.cmp(a)))
+        .expect("Cells are never empty");
+    average(group.into_iter())
+}
+
+impl FromStr for CellColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "average" => Ok(CellColor::Average),
+            "dominant" => Ok(CellColor::Dominant),
+            _ => Err(format!(
+                "unknown cell color '{}' (expected average or dominant)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CellColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CellColor::Average => write!(f, "average"),
+            CellColor::Dominant => write!(f, "dominant"),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::error::{DecodingError, ImageFormatHint};
+use image::{ImageError, ImageFormat, ImageResult, Rgb, RgbImage};
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+/// Convert a CMYK color to RGB.
+///
+/// Each channel is the amount of ink, from `0` for none to `255` for full
+/// coverage. This is the naive transform (e.g., full cyan is `(0, 255, 255)`),
+/// which ignores any ICC profile, so colors can be off from what a printer
+/// would produce, but close enough to pick tiles for them.
+pub(crate) fn cmyk_to_rgb([c, m, y, k]: [u8; 4]) -> Rgb<u8> {
+    let white = 255 - k as u32;
+    Rgb([c, m, y].map(|ink| (((255 - ink as u32) * white + 127) / 255) as u8))
+}
+
+/// Convert a YCbCr color to RGB (as in JFIF).
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+    [
+        y + 1.402 * cr,
+        y - 0.344_136 * cb - 0.714_136 * cr,
+        y + 1.772 * cb,
+    ]
+    .map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Check whether a JPEG has an Adobe (APP14) segment.
+///
+/// Adobe's software writes CMYK JPEGs with inverted values (`255` for no
+/// ink), and marks them with this segment; other CMYK JPEGs aren't
+/// inverted.
+fn is_adobe(bytes: &[u8]) -> bool {
+    let mut i = 2;
+    while i + 4 <= bytes.len() {
+        if bytes[i] != 0xFF {
+            return false;
+        }
+        let marker = bytes[i + 1];
+        match marker {
+            // padding before a marker
+            0xFF => {
+                i += 1;
+                continue;
+            }
+            // the image data starts (or ends) before any Adobe segment
+            0xDA | 0xD9 => return false,
+            _ => (),
+        }
+        let len = u16::from_be_bytes([bytes[i + 2], bytes[i + 3]]) as usize;
+        let segment = &bytes[i + 4..(i + 2 + len).min(bytes.len())];
+        if marker == 0xEE && segment.starts_with(b"Adobe") {
+            return true;
+        }
+        i += 2 + len;
+    }
+    false
+}
+
+/// Decode a JPEG stored as CMYK (or YCCK, i.e., CMYK stored as YCbCr to
+/// compress it better), converting it to RGB with [`cmyk_to_rgb`].
+///
+/// # Returns
+/// The converted image, or `None` if the JPEG isn't stored as CMYK (so it
+/// can be decoded as usual).
+///
+/// # Errors
+/// This function returns an error if the JPEG cannot be decoded.
+pub(crate) fn decode_jpeg(bytes: &[u8]) -> ImageResult<Option<RgbImage>> {
+    let error = |e: zune_jpeg::errors::DecodeErrors| {
+        let hint = ImageFormatHint::Exact(ImageFormat::Jpeg);
+        ImageError::Decoding(DecodingError::new(hint, e.to_string()))
+    };
+    let options = DecoderOptions::default()
+        .set_strict_mode(false)
+        .set_max_width(usize::MAX)
+        .set_max_height(usize::MAX);
+    let mut decoder = JpegDecoder::new_with_options(bytes, options);
+    decoder.decode_headers().map_err(error)?;
+    let colorspace = match decoder.get_input_colorspace() {
+        Some(colorspace @ (ColorSpace::CMYK | ColorSpace::YCCK)) => colorspace,
+        _ => return Ok(None),
+    };
+    // decode the channels as they're stored, to convert them here
+    decoder.set_options(options.jpeg_set_out_colorspace(colorspace));
+    let raw = decoder.decode().map_err(error)?;
+    let (width, height) = decoder.dimensions().expect("Headers were decoded.");
+
+    // YCCK is only written by Adobe's software, so it's always inverted
+    let inverted = colorspace == ColorSpace::YCCK || is_adobe(bytes);
+    let pixels = raw.chunks_exact(4).flat_map(|px| {
+        let cmyk = match colorspace {
+            // YCbCr decodes to the (stored) CMY values inverted
+            ColorSpace::YCCK => {
+                let [r, g, b] = ycc_to_rgb(px[0], px[1], px[2]);
+                [255 - r, 255 - g, 255 - b, px[3]]
+            }
+            _ => [px[0], px[1], px[2], px[3]],
+        };
+        let cmyk = match inverted {
+            true => cmyk.map(|v| 255 - v),
+            false => cmyk,
+        };
+        cmyk_to_rgb(cmyk).0
+    });
+    let img = RgbImage::from_raw(width as u32, height as u32, pixels.collect())
+        .expect("Decoded JPEG has the wrong size.");
+    Ok(Some(img))
+}
@@ -0,0 +1,250 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::Rgb;
+use std::sync::OnceLock;
+
+/// A color in the [OKLab](https://bottosson.github.io/posts/oklab/)
+/// perceptual color space, in which the Euclidean distance between two
+/// colors tracks how different they look more closely than in RGB.
+///
+/// `l` is the perceived lightness (from `0.0` for black to `1.0` for
+/// white), while `a` and `b` are how green/red and blue/yellow the color
+/// is (each roughly within `-0.4..0.4` for colors in sRGB).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Oklab {
+    /// The perceived lightness.
+    pub l: f32,
+    /// How green (negative) or red (positive) the color is.
+    pub a: f32,
+    /// How blue (negative) or yellow (positive) the color is.
+    pub b: f32,
+}
+
+impl Oklab {
+    /// Convert a color from linear sRGB (with each channel in `0.0..=1.0`).
+    pub fn from_linear_srgb([r, g, b]: [f32; 3]) -> Self {
+        let l = 0.412_221_46 * r + 0.536_332_55 * g + 0.051_445_995 * b;
+        let m = 0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b;
+        let s = 0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b;
+        let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+        Self {
+            l: 0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            a: 1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            b: 0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        }
+    }
+
+    /// Convert this color to linear sRGB.
+    ///
+    /// Colors outside of the sRGB gamut have channels outside of
+    /// `0.0..=1.0`.
+    pub fn to_linear_srgb(&self) -> [f32; 3] {
+        let l = self.l + 0.396_337_78 * self.a + 0.215_803_76 * self.b;
+        let m = self.l - 0.105_561_346 * self.a - 0.063_854_17 * self.b;
+        let s = self.l - 0.089_484_18 * self.a - 1.291_485_5 * self.b;
+        let (l, m, s) = (l.powi(3), m.powi(3), s.powi(3));
+
+        [
+            4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+            -1.268_438 * l + 2.609_757_4 * m - 0.341_319_4 * s,
+            -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+        ]
+    }
+
+    /// Convert this color to sRGB, clamping it to the sRGB gamut.
+    pub fn to_rgb(&self) -> Rgb<u8> {
+        Rgb(self.to_linear_srgb().map(linear_to_srgb))
+    }
+
+    /// Compute the Euclidean distance between this color and another.
+    ///
+    /// Black and white are `1.0` apart.
+    pub fn distance(&self, other: &Oklab) -> f32 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2))
+            .sqrt()
+    }
+}
+
+impl From<&Rgb<u8>> for Oklab {
+    /// Convert a color from sRGB.
+    fn from(px: &Rgb<u8>) -> Self {
+        Self::from_linear_srgb(px.0.map(srgb_to_linear))
+    }
+}
+
+impl From<Rgb<u8>> for Oklab {
+    /// Convert a color from sRGB.
+    fn from(px: Rgb<u8>) -> Self {
+        Self::from(&px)
+    }
+}
+
+/// A color in the [CIELAB](https://en.wikipedia.org/wiki/CIELAB_color_space)
+/// color space (relative to the D65 white point of sRGB), in which the
+/// Euclidean distance between two colors (the CIE76 ΔE) tracks how
+/// different they look more closely than in RGB.
+///
+/// `l` is the lightness (from `0.0` for black to `100.0` for white), while
+/// `a` and `b` are how green/red and blue/yellow the color is (each
+/// roughly within `-110.0..130.0` for colors in sRGB).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Lab {
+    /// The lightness.
+    pub l: f32,
+    /// How green (negative) or red (positive) the color is.
+    pub a: f32,
+    /// How blue (negative) or yellow (positive) the color is.
+    pub b: f32,
+}
+
+impl Lab {
+    /// Convert a color from linear sRGB (with each channel in `0.0..=1.0`).
+    pub fn from_linear_srgb([r, g, b]: [f32; 3]) -> Self {
+        // CIE XYZ, relative to the D65 white point
+        let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+        let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+        let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+        let f = |t: f32| {
+            const DELTA: f32 = 6.0 / 29.0;
+            if t > DELTA.powi(3) {
+                t.cbrt()
+            } else {
+                t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+            }
+        };
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+
+        Self {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    /// Compute the Euclidean distance (CIE76 ΔE) between this color and
+    /// another.
+    ///
+    /// Black and white are `100.0` apart.
+    pub fn distance(&self, other: &Lab) -> f32 {
+        ((self.l - other.l).powi(2) + (self.a - other.a).powi(2) + (self.b - other.b).powi(2))
+            .sqrt()
+    }
+
+    /// Compute the CIEDE2000 color difference (ΔE00) between this color and
+    /// another, which corrects the [`distance`](Lab::distance) for how the
+    /// eye judges differences in lightness, chroma, and hue (particularly
+    /// among near-neutral colors and blues).
+    ///
+    /// This follows Sharma, Wu, and Dalal, "The CIEDE2000 Color-Difference
+    /// Formula: Implementation Notes, Supplementary Test Data, and
+    /// Mathematical Observations" (2005), with the weighting factors
+    /// `kL`, `kC`, and `kH` all `1.0`. Like the `distance`, black and white
+    /// are `100.0` apart.
+    pub fn ciede2000(&self, other: &Lab) -> f32 {
+        let (l1, a1, b1) = (self.l as f64, self.a as f64, self.b as f64);
+        let (l2, a2, b2) = (other.l as f64, other.a as f64, other.b as f64);
+        let pow25_7 = 25f64.powi(7);
+
+        // adjust a* so that near-neutral colors have more chroma
+        let c_bar = (a1.hypot(b1) + a2.hypot(b2)) / 2.0;
+        let g = 0.5 * (1.0 - (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt());
+        let (a1, a2) = ((1.0 + g) * a1, (1.0 + g) * a2);
+        let (c1, c2) = (a1.hypot(b1), a2.hypot(b2));
+        let hue = |a: f64, b: f64| match (a, b) {
+            (0.0, 0.0) => 0.0,
+            _ => b.atan2(a).to_degrees().rem_euclid(360.0),
+        };
+        let (h1, h2) = (hue(a1, b1), hue(a2, b2));
+
+        // the differences in lightness, chroma, and hue
+        let d_l = l2 - l1;
+        let d_c = c2 - c1;
+        let d_h = match h2 - h1 {
+            _ if c1 * c2 == 0.0 => 0.0,
+            d if d > 180.0 => d - 360.0,
+            d if d < -180.0 => d + 360.0,
+            d => d,
+        };
+        let d_h = 2.0 * (c1 * c2).sqrt() * (d_h / 2.0).to_radians().sin();
+
+        // the weighting functions, at the mean of the two colors
+        let l_bar = (l1 + l2) / 2.0;
+        let c_bar = (c1 + c2) / 2.0;
+        let h_bar = match (h1 + h2, (h1 - h2).abs()) {
+            (sum, _) if c1 * c2 == 0.0 => sum,
+            (sum, diff) if diff <= 180.0 => sum / 2.0,
+            (sum, _) if sum < 360.0 => (sum + 360.0) / 2.0,
+            (sum, _) => (sum - 360.0) / 2.0,
+        };
+        let cos = |degrees: f64| degrees.to_radians().cos();
+        let t = 1.0 - 0.17 * cos(h_bar - 30.0)
+            + 0.24 * cos(2.0 * h_bar)
+            + 0.32 * cos(3.0 * h_bar + 6.0)
+            - 0.20 * cos(4.0 * h_bar - 63.0);
+        let d_theta = 30.0 * (-((h_bar - 275.0) / 25.0).powi(2)).exp();
+        let r_c = 2.0 * (c_bar.powi(7) / (c_bar.powi(7) + pow25_7)).sqrt();
+        let s_l = 1.0 + 0.015 * (l_bar - 50.0).powi(2) / (20.0 + (l_bar - 50.0).powi(2)).sqrt();
+        let s_c = 1.0 + 0.045 * c_bar;
+        let s_h = 1.0 + 0.015 * c_bar * t;
+        let r_t = -(2.0 * d_theta).to_radians().sin() * r_c;
+
+        let (l, c, h) = (d_l / s_l, d_c / s_c, d_h / s_h);
+        (l * l + c * c + h * h + r_t * c * h).sqrt() as f32
+    }
+}
+
+impl From<&Rgb<u8>> for Lab {
+    /// Convert a color from sRGB.
+    fn from(px: &Rgb<u8>) -> Self {
+        Self::from_linear_srgb(px.0.map(srgb_to_linear))
+    }
+}
+
+impl From<Rgb<u8>> for Lab {
+    /// Convert a color from sRGB.
+    fn from(px: Rgb<u8>) -> Self {
+        Self::from(&px)
+    }
+}
+
+/// Convert an sRGB channel to linear light (in `0.0..=1.0`)
+pub(crate) fn srgb_to_linear(c: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        std::array::from_fn(|i| {
+            let c = i as f64 / 255.0;
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            linear as f32
+        })
+    });
+    table[c as usize]
+}
+
+/// Convert a channel in linear light to sRGB (clamping it to `0..=255`)
+pub(crate) fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (c * 255.0).round() as u8
+}
@@ -0,0 +1,217 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::coverage::heat_color;
+use crate::plan::{MosaicPlan, TileRef};
+use crate::quality;
+use image::{GrayImage, Luma, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// The differences between two [`MosaicPlan`]s of the same size.
+///
+/// See [`MosaicPlan::compare`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanDiff {
+    /// The number of cells in each plan.
+    pub cells: usize,
+    /// The number of cells assigned a different tile in each plan (or
+    /// which are background in only one of them).
+    pub changed: usize,
+    /// Whether each cell changed, as `255` (changed) or `0` (unchanged).
+    #[serde(skip)]
+    mask: GrayImage,
+}
+
+/// The differences between two images of the same size.
+///
+/// See [`compare_images`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageDiff {
+    /// The number of pixels in each image.
+    pub pixels: usize,
+    /// The number of pixels which differ between the images.
+    pub changed: usize,
+    /// The peak signal-to-noise ratio (in dB) between the images; identical
+    /// images have an infinite PSNR.
+    pub psnr: f64,
+    /// Whether each pixel changed, as `255` (changed) or `0` (unchanged).
+    #[serde(skip)]
+    mask: GrayImage,
+}
+
+impl MosaicPlan {
+    /// Find the cells which are assigned different [`Tile`](crate::Tile)s
+    /// in this plan and `other`.
+    ///
+    /// Tiles are compared by their [`TileRef`](crate::TileRef)s (see
+    /// [`same_tile`](crate::TileRef::same_tile)), so the plans may have
+    /// been built from different (e.g., reordered, extended, or moved)
+    /// tile sets.
+    ///
+    /// # Errors
+    /// This function returns an error if the plans' grids are not the
+    /// same size.
+    pub fn compare(&self, other: &MosaicPlan) -> Result<PlanDiff, Box<dyn Error>> {
+        if self.grid_size() != other.grid_size() {
+            return Err(format!(
+                "Plans have different grid sizes ({:?} and {:?})",
+                self.grid_size(),
+                other.grid_size()
+            )
+            .into());
+        }
+
+        let (columns, rows) = self.grid_size();
+        let mask = GrayImage::from_fn(columns, rows, |x, y| {
+            let same = match (self.cell(x, y), other.cell(x, y)) {
+                (Some(a), Some(b)) => a.same_tile(b),
+                (a, b) => a.is_none() && b.is_none(),
+            };
+            Luma([if same { 0 } else { 255 }])
+        });
+
+        Ok(PlanDiff {
+            cells: (columns * rows) as usize,
+            changed: count_changed(&mask),
+            mask,
+        })
+    }
+
+    /// Get the [`TileRef`] for the tile in the cell at `(x, y)`, or `None`
+    /// if it is background.
+    fn cell(&self, x: u32, y: u32) -> Option<&TileRef> {
+        (!self.is_skipped(x, y)).then(|| &self.tiles()[self.tile_at(x, y)])
+    }
+}
+
+impl PlanDiff {
+    /// Get the fraction of cells which changed, in `[0, 1]`.
+    pub fn changed_fraction(&self) -> f64 {
+        fraction(self.changed, self.cells)
+    }
+
+    /// Check whether the cell at `(x, y)` changed.
+    pub fn is_changed(&self, x: u32, y: u32) -> bool {
+        self.mask.get_pixel(x, y).0[0] > 0
+    }
+
+    /// Render the differences as an image for humans, with `cell_px`
+    /// pixels per cell. Changed cells are drawn in red, and unchanged
+    /// cells in blue.
+    pub fn to_image(&self, cell_px: u32) -> RgbImage {
+        diff_image(&self.mask, cell_px)
+    }
+}
+
+impl ImageDiff {
+    /// Get the fraction of pixels which changed, in `[0, 1]`.
+    pub fn changed_fraction(&self) -> f64 {
+        fraction(self.changed, self.pixels)
+    }
+
+    /// Check whether the pixel at `(x, y)` changed.
+    pub fn is_changed(&self, x: u32, y: u32) -> bool {
+        self.mask.get_pixel(x, y).0[0] > 0
+    }
+
+    /// Render the differences as an image for humans, the same size as
+    /// the images compared. Changed pixels are drawn in red, and unchanged
+    /// pixels in blue.
+    pub fn to_image(&self) -> RgbImage {
+        diff_image(&self.mask, 1)
+    }
+}
+
+/// Find the pixels which differ between two images, and measure how
+/// much they differ overall.
+///
+/// # Errors
+/// This function returns an error if the images are not the same size.
+pub fn compare_images(a: &RgbImage, b: &RgbImage) -> Result<ImageDiff, Box<dyn Error>> {
+    if a.dimensions() != b.dimensions() {
+        return Err(format!(
+            "Images have different dimensions ({:?} and {:?})",
+            a.dimensions(),
+            b.dimensions()
+        )
+        .into());
+    }
+
+    let (w, h) = a.dimensions();
+    let mask = GrayImage::from_fn(w, h, |x, y| {
+        Luma([if a.get_pixel(x, y) == b.get_pixel(x, y) {
+            0
+        } else {
+            255
+        }])
+    });
+
+    Ok(ImageDiff {
+        pixels: (w * h) as usize,
+        changed: count_changed(&mask),
+        psnr: quality::psnr(a, b),
+        mask,
+    })
+}
+
+impl fmt::Display for PlanDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} cells changed ({:.2}%)",
+            self.changed,
+            self.cells,
+            self.changed_fraction() * 100.0
+        )
+    }
+}
+
+impl fmt::Display for ImageDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} pixels changed ({:.2}%), PSNR {:.2} dB",
+            self.changed,
+            self.pixels,
+            self.changed_fraction() * 100.0,
+            self.psnr
+        )
+    }
+}
+
+/// Count the changed entries in a mask of changes.
+fn count_changed(mask: &GrayImage) -> usize {
+    mask.pixels().filter(|px| px.0[0] > 0).count()
+}
+
+/// Compute `part / total`, where an empty total has no changes.
+fn fraction(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+/// Draw a mask of changes with `cell_px` pixels per entry, using the
+/// same colors as a [`CoverageReport`](crate::CoverageReport) image.
+fn diff_image(mask: &GrayImage, cell_px: u32) -> RgbImage {
+    let (w, h) = mask.dimensions();
+    RgbImage::from_fn(w * cell_px, h * cell_px, |x, y| {
+        let changed = mask.get_pixel(x / cell_px, y / cell_px).0[0];
+        heat_color(changed as f32 / 255.0)
+    })
+}
@@ -0,0 +1,219 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::metric::Metric;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+/// Describes how well the average colors of the [`Tile`](crate::Tile)s
+/// in a [`TileSet`](crate::TileSet) cover the RGB color cube.
+///
+/// The cube is divided into a grid of `divisions`³ cells; for each cell,
+/// the report records the distance (using the chosen [`Metric`]) from the
+/// color at the center of the cell to the nearest tile average. Large
+/// distances indicate colors the tile set cannot reproduce well.
+///
+/// See [`TileSet::coverage_report`](crate::TileSet::coverage_report).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// The number of cells along each axis of the RGB cube.
+    divisions: u32,
+    /// The metric used to measure distances.
+    metric: Metric,
+    /// The distance from the center of each cell to the nearest tile,
+    /// indexed by [`index`](CoverageReport::index).
+    distances: Vec<f32>,
+}
+
+/// A poorly-covered region of the RGB cube.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CoverageGap {
+    /// The color at the center of the region.
+    pub center: [u8; 3],
+    /// The distance from the center of the region to the nearest tile.
+    pub distance: f32,
+}
+
+impl CoverageReport {
+    /// Build a report from the average colors of a set of tiles.
+    ///
+    /// # Panics
+    /// This function panics if `divisions` is zero or greater than 256.
+    pub(crate) fn new(avgs: &[Rgb<u8>], divisions: u32, metric: Metric) -> Self {
+        if divisions == 0 || divisions > 256 {
+            panic!("Coverage grid must have between 1 and 256 divisions");
+        }
+
+        let mut report = Self {
+            divisions,
+            metric,
+            distances: Vec::with_capacity(divisions.pow(3) as usize),
+        };
+        for r in 0..divisions {
+            for g in 0..divisions {
+                for b in 0..divisions {
+                    let center = Rgb(report.center(r, g, b));
+                    let dist = avgs
+                        .iter()
+                        .map(|avg| metric.distance(&center, avg))
+                        .fold(f32::INFINITY, f32::min);
+                    report.distances.push(dist);
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Update the report to account for a new tile with the given
+    /// average color.
+    pub(crate) fn add_tile(&mut self, avg: &Rgb<u8>) {
+        let d = self.divisions;
+        for r in 0..d {
+            for g in 0..d {
+                for b in 0..d {
+                    let center = Rgb(self.center(r, g, b));
+                    let idx = self.index(r, g, b);
+                    let dist = self.metric.distance(&center, avg);
+                    self.distances[idx] = self.distances[idx].min(dist);
+                }
+            }
+        }
+    }
+
+    /// Get the number of cells along each axis of the RGB cube.
+    pub fn divisions(&self) -> u32 {
+        self.divisions
+    }
+
+    /// Get the metric used to measure distances.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Get the index of the cell at `(r, g, b)` (in cell coordinates)
+    /// in [`distances`](CoverageReport::distances).
+    pub fn index(&self, r: u32, g: u32, b: u32) -> usize {
+        ((r * self.divisions + g) * self.divisions + b) as usize
+    }
+
+    /// Get the distance from the center of every cell to the nearest tile.
+    pub fn distances(&self) -> &[f32] {
+        &self.distances
+    }
+
+    /// Get the color at the center of the cell at `(r, g, b)`
+    /// (in cell coordinates).
+    pub fn center(&self, r: u32, g: u32, b: u32) -> [u8; 3] {
+        let size = 256.0 / self.divisions as f32;
+        [r, g, b].map(|c| ((c as f32 + 0.5) * size) as u8)
+    }
+
+    /// Get the distance from the center of the cell containing the given
+    /// color to the nearest tile.
+    pub fn distance_at(&self, color: &Rgb<u8>) -> f32 {
+        let [r, g, b] = color.0.map(|c| c as u32 * self.divisions / 256);
+        self.distances[self.index(r, g, b)]
+    }
+
+    /// Get the distance below which `p` percent of the cells fall
+    /// (using the nearest-rank method).
+    ///
+    /// # Panics
+    /// This function panics if `p` is not in `[0, 100]`.
+    pub fn percentile(&self, p: f32) -> f32 {
+        if !(0.0..=100.0).contains(&p) {
+            panic!("Percentile must be between 0 and 100");
+        }
+        let mut sorted = self.distances.clone();
+        sorted.sort_by(f32::total_cmp);
+        let rank = ((p / 100.0) * sorted.len() as f32).ceil() as usize;
+        sorted[rank.saturating_sub(1)]
+    }
+
+    /// Get the `n` most poorly-covered cells, furthest from any tile first.
+    pub fn emptiest(&self, n: usize) -> Vec<CoverageGap> {
+        let d = self.divisions;
+        let mut gaps: Vec<CoverageGap> = (0..d)
+            .flat_map(|r| (0..d).flat_map(move |g| (0..d).map(move |b| (r, g, b))))
+            .map(|(r, g, b)| CoverageGap {
+                center: self.center(r, g, b),
+                distance: self.distances[self.index(r, g, b)],
+            })
+            .collect();
+        // sort_by is stable, so ties remain in cube order
+        gaps.sort_by(|a, b| b.distance.total_cmp(&a.distance));
+        gaps.truncate(n);
+        gaps
+    }
+
+    /// Render the report as a false-color image for humans.
+    ///
+    /// The RGB cube is sliced along the blue axis; each slice is drawn as
+    /// a square (red increasing to the right, green increasing downwards)
+    /// of `cell_px` pixels per cell, and the slices are placed side by side
+    /// from least to most blue, separated by a 1px black line. Well-covered
+    /// cells are drawn in blue and poorly-covered cells in red.
+    pub fn to_image(&self, cell_px: u32) -> RgbImage {
+        let d = self.divisions;
+        let side = d * cell_px;
+        let max = self.distances.iter().cloned().fold(0.0, f32::max);
+
+        let mut img = RgbImage::new(d * (side + 1) - 1, side);
+        for b in 0..d {
+            for r in 0..d {
+                for g in 0..d {
+                    let dist = self.distances[self.index(r, g, b)];
+                    let t = if max > 0.0 { dist / max } else { 0.0 };
+                    let color = heat_color(t);
+
+                    let (x0, y0) = (b * (side + 1) + r * cell_px, g * cell_px);
+                    for x in x0..x0 + cell_px {
+                        for y in y0..y0 + cell_px {
+                            img.put_pixel(x, y, color);
+                        }
+                    }
+                }
+            }
+        }
+
+        img
+    }
+}
+
+/// Map a value in `[0, 1]` to a color ranging from blue (`0`) through
+/// green to red (`1`).
+pub(crate) fn heat_color(t: f32) -> Rgb<u8> {
+    let hue = 240.0 * (1.0 - t.clamp(0.0, 1.0));
+    hsv_to_rgb(hue, 1.0, 1.0)
+}
+
+/// Convert a color in HSV (hue in degrees, saturation & value in `[0, 1]`)
+/// to RGB.
+pub(crate) fn hsv_to_rgb(h: f32, s: f32, v: f32) -> Rgb<u8> {
+    let c = v * s;
+    let h = (h.rem_euclid(360.0)) / 60.0;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    Rgb([r, g, b].map(|ch| ((ch + m) * 255.0).round() as u8))
+}
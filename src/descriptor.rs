@@ -0,0 +1,177 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::metric::Metric;
+use image::{imageops, imageops::FilterType, GenericImageView, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// How the pixels of a [`Tile`](crate::Tile) and of the part of the
+/// source image covered by a cell are summarized to compare them.
+///
+/// Each descriptor splits the [`Tile`](crate::Tile) (and the cell) into a
+/// square grid of blocks and compares the average colors of corresponding
+/// blocks, so finer grids preserve more of the structure within each cell
+/// at the cost of more comparisons.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Descriptor {
+    /// The average color of the whole [`Tile`](crate::Tile).
+    #[default]
+    Mean,
+    /// The average colors of the four quadrants of the
+    /// [`Tile`](crate::Tile) (a 2x2 grid).
+    Quadrants,
+    /// The average colors of the blocks of a 3x3 grid over the
+    /// [`Tile`](crate::Tile).
+    Grid3,
+    /// The average color of the whole [`Tile`](crate::Tile), weighted by a
+    /// Gaussian centered on it (see [`TileSet::set_center_sigma`]), so the
+    /// subject of a photo counts for more than its background.
+    ///
+    /// Cells are still compared by their plain average color, and the
+    /// whole tile is drawn in each cell, so a tile chosen this way may
+    /// look slightly further from its cell than its descriptor suggests.
+    /// This is by design: the center of a tile is what the eye picks out.
+    ///
+    /// [`TileSet::set_center_sigma`]: crate::TileSet::set_center_sigma
+    Center,
+}
+
+impl Descriptor {
+    /// Get the number of blocks along each side of the grid used by
+    /// this descriptor.
+    pub fn grid_size(&self) -> u32 {
+        match self {
+            Descriptor::Mean | Descriptor::Center => 1,
+            Descriptor::Quadrants => 2,
+            Descriptor::Grid3 => 3,
+        }
+    }
+
+    /// Compute the distance between two descriptors (as the average colors
+    /// of their blocks, in row-major order).
+    ///
+    /// This is the Euclidean distance between the concatenated block
+    /// colors, so for [`Descriptor::Mean`] it is the same as
+    /// [`Metric::distance`].
+    pub fn distance(metric: Metric, a: &[Rgb<u8>], b: &[Rgb<u8>]) -> f32 {
+        if let ([a], [b]) = (a, b) {
+            return metric.distance(a, b);
+        }
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| metric.distance(a, b).powi(2))
+            .sum::<f32>()
+            .sqrt()
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Descriptor::Mean),
+            "quadrants" | "2x2" => Ok(Descriptor::Quadrants),
+            "grid3" | "3x3" => Ok(Descriptor::Grid3),
+            "center" => Ok(Descriptor::Center),
+            _ => Err(format!(
+                "unknown descriptor '{}' (expected mean, quadrants, grid3, or center)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Descriptor::Mean => write!(f, "mean"),
+            Descriptor::Quadrants => write!(f, "quadrants"),
+            Descriptor::Grid3 => write!(f, "grid3"),
+            Descriptor::Center => write!(f, "center"),
+        }
+    }
+}
+
+/// Compute the average color of each block of an `n` x `n` grid over an
+/// image, in row-major order.
+///
+/// Blocks differ in size by at most one pixel. If the image is smaller
+/// than `n` pixels along a side, blocks along that side share pixels.
+pub(crate) fn block_averages<I>(img: &I, n: u32) -> Vec<Rgb<u8>>
+where
+    I: GenericImageView<Pixel = Rgb<u8>>,
+{
+    let (w, h) = img.dimensions();
+    let bounds = |i: u32, len: u32| {
+        let start = i * len / n;
+        let end = ((i + 1) * len / n).max(start + 1);
+        start..end
+    };
+
+    let mut blocks = Vec::with_capacity((n * n) as usize);
+    for by in 0..n {
+        for bx in 0..n {
+            let mut total = [0u64; 3];
+            let mut count = 0;
+            for y in bounds(by, h) {
+                for x in bounds(bx, w) {
+                    let px = img.get_pixel(x, y);
+                    for (t, c) in total.iter_mut().zip(px.0) {
+                        *t += c as u64;
+                    }
+                    count += 1;
+                }
+            }
+            blocks.push(Rgb(total.map(|t| (t / count) as u8)));
+        }
+    }
+    blocks
+}
+
+/// Compute the average color of an image, weighted by a Gaussian centered
+/// on it with a standard deviation of `sigma` times its width (see
+/// [`Descriptor::Center`]).
+pub(crate) fn center_average<I>(img: &I, sigma: f32) -> Rgb<u8>
+where
+    I: GenericImageView<Pixel = Rgb<u8>>,
+{
+    let (w, h) = img.dimensions();
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    // at least half a pixel, so some pixel always has a usable weight
+    let sigma = (sigma * w as f32).max(0.5);
+    let mut total = [0f32; 3];
+    let mut total_weight = 0.0;
+    for (x, y, px) in img.pixels() {
+        let dx = x as f32 + 0.5 - cx;
+        let dy = y as f32 + 0.5 - cy;
+        let weight = (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp();
+        for (t, c) in total.iter_mut().zip(px.0) {
+            *t += weight * c as f32;
+        }
+        total_weight += weight;
+    }
+    Rgb(total.map(|t| (t / total_weight).round() as u8))
+}
+
+/// Resize an image so that each pixel of `grid` (the dimensions of the
+/// scaled source image) covers an `n` x `n` block of pixels, from which
+/// the descriptor of each cell is computed.
+pub(crate) fn detail_image(img: &RgbImage, (columns, rows): (u32, u32), n: u32) -> RgbImage {
+    imageops::resize(img, columns * n, rows * n, FilterType::Triangle)
+}
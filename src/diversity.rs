@@ -0,0 +1,189 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::preprocess::{percentile, percentile_rev};
+use crate::summary::TileSetSummary;
+use image::RgbImage;
+use std::fmt;
+
+/// The names of the color channels, for messages.
+const CHANNELS: [&str; 3] = ["red", "green", "blue"];
+
+/// Histograms of the red, green, and blue values of an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColorHistogram {
+    /// The number of pixels with each value, per channel.
+    counts: [[u64; 256]; 3],
+    /// The number of pixels counted.
+    total: u64,
+}
+
+impl ColorHistogram {
+    /// The percentage of pixels at each end of a channel ignored by
+    /// [`range`](ColorHistogram::range), so a few stray pixels don't
+    /// widen it.
+    pub const CLIP: f32 = 1.0;
+
+    /// Count the values of each channel of `img`.
+    pub fn new(img: &RgbImage) -> Self {
+        let mut counts = [[0; 256]; 3];
+        for px in img.pixels() {
+            for (c, &v) in px.0.iter().enumerate() {
+                counts[c][v as usize] += 1;
+            }
+        }
+        Self {
+            counts,
+            total: img.pixels().len() as u64,
+        }
+    }
+
+    /// Get the number of pixels with each value of the given channel
+    /// (`0` for red, `1` for green, and `2` for blue).
+    ///
+    /// # Panics
+    /// This function panics if `channel` is greater than `2`.
+    pub fn counts(&self, channel: usize) -> &[u64; 256] {
+        &self.counts[channel]
+    }
+
+    /// Get the smallest and largest values of the given channel, ignoring
+    /// the darkest and brightest [`CLIP`](ColorHistogram::CLIP) percent of
+    /// the pixels.
+    ///
+    /// # Panics
+    /// This function panics if `channel` is greater than `2`.
+    pub fn range(&self, channel: usize) -> (u8, u8) {
+        let skip = (self.total as f32 * Self::CLIP / 100.0) as u64;
+        let hist = &self.counts[channel];
+        (percentile(hist, skip), percentile_rev(hist, skip))
+    }
+}
+
+/// Thresholds for deciding whether the colors of a tile set are too
+/// alike to reproduce a source image (e.g., a folder of night photos,
+/// which would make a muddy mosaic).
+///
+/// See [`DiversityCheck::check`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiversityCheck {
+    /// The smallest acceptable spread of the tiles' average colors: the
+    /// root mean square of their per-channel standard deviations (see
+    /// [`TileSetSummary::std_dev`]).
+    pub min_std_dev: f32,
+    /// How far (in channel values) the range of the tiles' average colors
+    /// may fall short of the range of the source's colors, at either end
+    /// of any channel.
+    pub margin: u8,
+}
+
+impl Default for DiversityCheck {
+    fn default() -> Self {
+        Self {
+            min_std_dev: 12.0,
+            margin: 32,
+        }
+    }
+}
+
+/// A reason the colors of a tile set may be too alike (see
+/// [`DiversityCheck::check`]).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiversityWarning {
+    /// The tiles' average colors barely vary.
+    LowSpread {
+        /// The spread of the tiles' average colors.
+        std_dev: f32,
+        /// The smallest acceptable spread.
+        min: f32,
+    },
+    /// The tiles' average colors don't reach the values of one channel
+    /// of the source.
+    NarrowRange {
+        /// The channel (`0` for red, `1` for green, and `2` for blue).
+        channel: usize,
+        /// The smallest and largest values of the tiles' average colors.
+        tiles: (u8, u8),
+        /// The smallest and largest values of the source (see
+        /// [`ColorHistogram::range`]).
+        source: (u8, u8),
+    },
+}
+
+impl DiversityCheck {
+    /// Check whether the tiles summarized by `summary` vary too little,
+    /// or (given the `source` image's histogram) fail to cover its colors.
+    ///
+    /// Returns every problem found, or nothing if the tiles look diverse
+    /// enough.
+    pub fn check(
+        &self,
+        summary: &TileSetSummary,
+        source: Option<&ColorHistogram>,
+    ) -> Vec<DiversityWarning> {
+        let mut warnings = Vec::new();
+        if summary.count == 0 {
+            return warnings;
+        }
+
+        let variance = summary.std_dev.iter().map(|s| s.powi(2)).sum::<f32>() / 3.0;
+        let std_dev = variance.sqrt();
+        if std_dev < self.min_std_dev {
+            warnings.push(DiversityWarning::LowSpread {
+                std_dev,
+                min: self.min_std_dev,
+            });
+        }
+
+        if let Some(source) = source {
+            for channel in 0..3 {
+                let tiles = (summary.min[channel], summary.max[channel]);
+                let (lo, hi) = source.range(channel);
+                if tiles.0 > lo.saturating_add(self.margin)
+                    || tiles.1 < hi.saturating_sub(self.margin)
+                {
+                    warnings.push(DiversityWarning::NarrowRange {
+                        channel,
+                        tiles,
+                        source: (lo, hi),
+                    });
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+impl fmt::Display for DiversityWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LowSpread { std_dev, min } => write!(
+                f,
+                "tile colors barely vary (standard deviation {:.1}, below {:.1})",
+                std_dev, min
+            ),
+            Self::NarrowRange {
+                channel,
+                tiles,
+                source,
+            } => write!(
+                f,
+                "tiles cover {} {}-{}, but the source spans {}-{}",
+                CHANNELS[*channel], tiles.0, tiles.1, source.0, source.1
+            ),
+        }
+    }
+}
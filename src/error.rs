@@ -0,0 +1,106 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::options::ScaleError;
+use image::ImageError;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Why a [`Mosaic`](crate::Mosaic) (or the images for one) couldn't be
+/// built or loaded.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The scaling factor can't be used for the source image.
+    Scale(ScaleError),
+    /// There are no tiles to build the mosaic from.
+    NoTiles,
+    /// The side length (in pixels) of the tiles is zero, or (when it isn't
+    /// given) the tiles are too large to use at their own size.
+    TileSize(u32),
+    /// The path tiles were to be loaded from is not a directory.
+    NotADirectory(PathBuf),
+    /// An image couldn't be decoded.
+    Decode(ImageError),
+    /// A file or directory couldn't be read.
+    Io(io::Error),
+    /// Building or rendering the mosaic was cancelled before it finished
+    /// (see [`Mosaic::to_image_cancellable`](crate::Mosaic::to_image_cancellable)).
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scale(e) => write!(f, "{}", e),
+            Self::NoTiles => write!(f, "Cannot build a tile set from no images"),
+            Self::TileSize(0) => write!(f, "Tile size must be at least 1"),
+            Self::TileSize(size) => write!(
+                f,
+                "Tiles are {}px, which is too large without setting a tile size",
+                size
+            ),
+            Self::NotADirectory(path) => {
+                write!(f, "Path must be a directory: {}", path.display())
+            }
+            Self::Decode(e) => write!(f, "Unable to decode image: {}", e),
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Cancelled => write!(f, "The mosaic was cancelled before it was finished"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Scale(e) => Some(e),
+            Self::Decode(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ScaleError> for Error {
+    fn from(e: ScaleError) -> Self {
+        Self::Scale(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::IoError(e) => Self::Io(e),
+            e => Self::Decode(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Check whether a long task has been cancelled, i.e., whether `cancel` is
+/// set, returning [`Error::Cancelled`] if so.
+pub(crate) fn check_cancelled(cancel: Option<&AtomicBool>) -> Result<(), Error> {
+    match cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+        true => Err(Error::Cancelled),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,160 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Estimates how quickly a long task (e.g., placing the tiles of a large
+/// mosaic) is progressing, and how long it has left.
+///
+/// The rate is measured over the last few seconds (the
+/// [`window`](Eta::with_window)) rather than since the start, so it follows
+/// changes in speed (e.g., as the tiles being placed change from cached to
+/// uncached ones) without jumping around with every update.
+///
+/// # Example
+/// ```
+/// # use std::time::{Duration, Instant};
+/// # use tilr::Eta;
+/// let start = Instant::now();
+/// let mut eta = Eta::new(1000);
+/// eta.record_at(0, start);
+/// eta.record_at(100, start + Duration::from_secs(1));
+/// assert_eq!(eta.rate(), Some(100.0));
+/// assert_eq!(eta.remaining(), Some(Duration::from_secs(9)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Eta {
+    /// The amount of work in the whole task.
+    total: u64,
+    /// How far back the rate is measured.
+    window: Duration,
+    /// The amount of work done at (roughly) evenly spaced times within
+    /// the window, plus the last point before it, oldest first.
+    samples: VecDeque<(Instant, u64)>,
+    /// The most recent progress, which is only kept as a sample once
+    /// it's far enough from the previous one.
+    latest: Option<(Instant, u64)>,
+}
+
+impl Eta {
+    /// How far back the rate is measured by default.
+    pub const DEFAULT_WINDOW: Duration = Duration::from_secs(5);
+    /// The number of samples kept within the window.
+    const SAMPLES: u32 = 50;
+
+    /// Start estimating a task with `total` units of work (e.g., cells or
+    /// bytes), measuring the rate over the default window.
+    pub fn new(total: u64) -> Self {
+        Self::with_window(total, Self::DEFAULT_WINDOW)
+    }
+
+    /// Start estimating a task with `total` units of work, measuring the
+    /// rate over the given window.
+    pub fn with_window(total: u64, window: Duration) -> Self {
+        Self {
+            total,
+            window,
+            samples: VecDeque::new(),
+            latest: None,
+        }
+    }
+
+    /// Record that `done` units of work have been done by now.
+    pub fn record(&mut self, done: u64) {
+        self.record_at(done, Instant::now());
+    }
+
+    /// Record that `done` units of work had been done by the given time.
+    ///
+    /// Progress must be recorded in order.
+    pub fn record_at(&mut self, done: u64, at: Instant) {
+        let spacing = self.window / Self::SAMPLES;
+        let due = match self.samples.back() {
+            Some(&(last, _)) => at.saturating_duration_since(last) >= spacing,
+            None => true,
+        };
+        if due {
+            self.samples.push_back((at, done));
+            // keep one sample from before the window, so the rate covers
+            // all of it
+            while self.samples.len() > 2
+                && at.saturating_duration_since(self.samples[1].0) >= self.window
+            {
+                self.samples.pop_front();
+            }
+        }
+        self.latest = Some((at, done));
+    }
+
+    /// Get the amount of work done so far.
+    pub fn done(&self) -> u64 {
+        self.latest.map_or(0, |(_, done)| done)
+    }
+
+    /// Get the amount of work in the whole task.
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// Get the rate at which work was done recently (in units per second),
+    /// or `None` if too little progress has been recorded to tell.
+    pub fn rate(&self) -> Option<f64> {
+        let (start, from) = *self.samples.front()?;
+        let (end, to) = self.latest?;
+        let elapsed = end.saturating_duration_since(start).as_secs_f64();
+        (elapsed > 0.0).then(|| to.saturating_sub(from) as f64 / elapsed)
+    }
+
+    /// Get the estimated time until the task is done, or `None` if it
+    /// can't be estimated yet (or no progress is being made).
+    pub fn remaining(&self) -> Option<Duration> {
+        let rate = self.rate().filter(|rate| *rate > 0.0)?;
+        let left = self.total.saturating_sub(self.done());
+        Some(Duration::from_secs_f64(left as f64 / rate))
+    }
+}
+
+impl fmt::Display for Eta {
+    /// Format the rate and the time left (e.g., `1.2k/s, 3m 05s left`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_rate(f, self.rate(), self.remaining())
+    }
+}
+
+/// Format a rate and the time left, as for [`Eta`]'s `Display`.
+pub(crate) fn fmt_rate(
+    f: &mut fmt::Formatter<'_>,
+    rate: Option<f64>,
+    remaining: Option<Duration>,
+) -> fmt::Result {
+    let (rate, remaining) = match (rate, remaining) {
+        (Some(rate), Some(remaining)) => (rate, remaining),
+        _ => return write!(f, "estimating time left"),
+    };
+    if rate >= 1000.0 {
+        write!(f, "{:.1}k/s, ", rate / 1000.0)?;
+    } else {
+        write!(f, "{:.0}/s, ", rate)?;
+    }
+    let secs = remaining.as_secs_f64().round() as u64;
+    match secs {
+        0 => write!(f, "<1s left"),
+        1..60 => write!(f, "{}s left", secs),
+        60..3600 => write!(f, "{}m {:02}s left", secs / 60, secs % 60),
+        _ => write!(f, "{}h {:02}m left", secs / 3600, secs / 60 % 60),
+    }
+}
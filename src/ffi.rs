@@ -0,0 +1,268 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A small C API for embedding tilr in other programs.
+//!
+//! Every function returns a [`TilrStatus`]; on failure, a description of
+//! the error can be fetched with [`tilr_last_error_message`]. Panics are
+//! caught at the boundary and reported as [`TilrStatus::Panic`].
+
+use crate::{load_tiles, Mosaic};
+use image::{DynamicImage, RgbImage};
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::ptr;
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+/// Status codes returned by the functions in the C API.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TilrStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// An argument was invalid (e.g., a non-UTF-8 path).
+    InvalidArgument = 2,
+    /// The operation failed (e.g., the tiles could not be loaded).
+    Failed = 3,
+    /// The library panicked; the panic was caught at the boundary.
+    Panic = 4,
+}
+
+/// An opaque handle to a set of images to use as tiles.
+#[derive(Debug)]
+pub struct TilrTileSet(Vec<DynamicImage>);
+
+/// An opaque handle to an image mosaic.
+#[allow(missing_debug_implementations)]
+pub struct TilrMosaic(Mosaic);
+
+thread_local! {
+    /// The message describing the most recent error on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record the message describing the most recent error on this thread.
+fn set_last_error(msg: &str) {
+    let msg = CString::new(msg.replace('\0', "")).expect("Interior NUL bytes were removed");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Get a description of a caught panic.
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "tilr panicked".into()
+    }
+}
+
+/// Run the body of an API function, converting errors and panics into
+/// status codes.
+fn guard<F>(f: F) -> TilrStatus
+where
+    F: FnOnce() -> Result<(), (TilrStatus, String)>,
+{
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => TilrStatus::Ok,
+        Ok(Err((status, msg))) => {
+            set_last_error(&msg);
+            status
+        }
+        Err(panic) => {
+            set_last_error(&panic_message(panic));
+            TilrStatus::Panic
+        }
+    }
+}
+
+/// Get a description of the most recent error on the calling thread.
+///
+/// Returns null if no error has occurred. The returned string is owned by
+/// the library and remains valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn tilr_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Load every image in the directory at `path` to use as tiles.
+///
+/// On success, `*out` is set to a new handle which must be released
+/// with [`tilr_tileset_free`].
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_tileset_from_dir(
+    path: *const c_char,
+    out: *mut *mut TilrTileSet,
+) -> TilrStatus {
+    guard(|| {
+        if path.is_null() || out.is_null() {
+            return Err((TilrStatus::NullPointer, "Null pointer argument".into()));
+        }
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|e| (TilrStatus::InvalidArgument, e.to_string()))?;
+        let tiles = load_tiles(Path::new(path))
+            .map_err(|e| (TilrStatus::Failed, e.to_string()))?
+            .tiles;
+        if tiles.is_empty() {
+            return Err((TilrStatus::Failed, format!("No tiles found in {}", path)));
+        }
+
+        *out = Box::into_raw(Box::new(TilrTileSet(tiles)));
+        Ok(())
+    })
+}
+
+/// Release a tile set created with [`tilr_tileset_from_dir`].
+///
+/// # Safety
+/// `tiles` must be null or a handle returned by this library which
+/// has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_tileset_free(tiles: *mut TilrTileSet) {
+    if !tiles.is_null() {
+        drop(Box::from_raw(tiles));
+    }
+}
+
+/// Initialize a new image mosaic from a raw RGB8 image buffer.
+///
+/// `rgb` must point to `width * height * 3` bytes of tightly-packed,
+/// row-major RGB pixel data. The buffer is copied, so it may be released
+/// once this call returns. See [`Mosaic::new`] for the meaning of `scale`
+/// and `tile_size`.
+///
+/// On success, `*out` is set to a new handle which must be released
+/// with [`tilr_mosaic_free`].
+///
+/// # Safety
+/// `rgb` must be valid for reads of `width * height * 3` bytes, `tiles`
+/// must be a live handle returned by this library, and `out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_mosaic_new(
+    rgb: *const u8,
+    width: u32,
+    height: u32,
+    tiles: *const TilrTileSet,
+    scale: f32,
+    tile_size: u8,
+    out: *mut *mut TilrMosaic,
+) -> TilrStatus {
+    guard(|| {
+        if rgb.is_null() || tiles.is_null() || out.is_null() {
+            return Err((TilrStatus::NullPointer, "Null pointer argument".into()));
+        }
+        if width == 0 || height == 0 {
+            return Err((
+                TilrStatus::InvalidArgument,
+                "Image dimensions must be non-zero".into(),
+            ));
+        }
+        let len = width as usize * height as usize * 3;
+        let buf = std::slice::from_raw_parts(rgb, len).to_vec();
+        let img = RgbImage::from_raw(width, height, buf).expect("Buffer has the right length");
+
+        let img = DynamicImage::ImageRgb8(img);
+        let mosaic = Mosaic::try_new(img, &(*tiles).0, scale, tile_size)
+            .map_err(|e| (TilrStatus::InvalidArgument, e.to_string()))?;
+
+        *out = Box::into_raw(Box::new(TilrMosaic(mosaic)));
+        Ok(())
+    })
+}
+
+/// Render an image mosaic.
+///
+/// On success, `*out_buf` is set to a buffer allocated with `malloc`
+/// holding `*out_width * *out_height * 3` bytes of tightly-packed,
+/// row-major RGB pixel data. Release it with [`tilr_buffer_free`]
+/// (or `free`).
+///
+/// # Safety
+/// `mosaic` must be a live handle returned by this library, and
+/// `out_buf`, `out_width`, and `out_height` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_mosaic_render(
+    mosaic: *const TilrMosaic,
+    out_buf: *mut *mut u8,
+    out_width: *mut u32,
+    out_height: *mut u32,
+) -> TilrStatus {
+    guard(|| {
+        if mosaic.is_null() || out_buf.is_null() || out_width.is_null() || out_height.is_null() {
+            return Err((TilrStatus::NullPointer, "Null pointer argument".into()));
+        }
+        let mosaic = &(*mosaic).0;
+        let img = mosaic.plan().render(mosaic.tiles());
+
+        let (width, height) = img.dimensions();
+        let raw = img.into_raw();
+        let buf = malloc(raw.len()) as *mut u8;
+        if buf.is_null() {
+            return Err((
+                TilrStatus::Failed,
+                "Unable to allocate output buffer".into(),
+            ));
+        }
+        ptr::copy_nonoverlapping(raw.as_ptr(), buf, raw.len());
+
+        *out_buf = buf;
+        *out_width = width;
+        *out_height = height;
+        Ok(())
+    })
+}
+
+/// Release a mosaic created with [`tilr_mosaic_new`].
+///
+/// # Safety
+/// `mosaic` must be null or a handle returned by this library which
+/// has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_mosaic_free(mosaic: *mut TilrMosaic) {
+    if !mosaic.is_null() {
+        drop(Box::from_raw(mosaic));
+    }
+}
+
+/// Release a buffer returned by [`tilr_mosaic_render`].
+///
+/// # Safety
+/// `buf` must be null or a buffer returned by this library which
+/// has not already been released.
+#[no_mangle]
+pub unsafe extern "C" fn tilr_buffer_free(buf: *mut u8) {
+    if !buf.is_null() {
+        free(buf as *mut c_void);
+    }
+}
@@ -0,0 +1,82 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The filter used to resize the source image or the [`Tile`](crate::Tile)s
+/// of a [`Mosaic`](crate::Mosaic) (see
+/// [`MosaicOptions::source_filter`](crate::MosaicOptions::source_filter) and
+/// [`MosaicOptions::tile_filter`](crate::MosaicOptions::tile_filter)).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+    /// Take the nearest pixel, keeping hard edges (e.g., for pixel art).
+    Nearest,
+    /// Linear interpolation; fast, but a little soft.
+    #[default]
+    Triangle,
+    /// Cubic interpolation.
+    CatmullRom,
+    /// A Gaussian filter; smooth, but blurry.
+    Gaussian,
+    /// Lanczos interpolation with a window of 3; the sharpest and smoothest
+    /// for photographs and gradients, but the slowest.
+    Lanczos3,
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(filter: ResizeFilter) -> Self {
+        match filter {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Gaussian => FilterType::Gaussian,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+impl FromStr for ResizeFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(ResizeFilter::Nearest),
+            "triangle" => Ok(ResizeFilter::Triangle),
+            "catmull-rom" => Ok(ResizeFilter::CatmullRom),
+            "gaussian" => Ok(ResizeFilter::Gaussian),
+            "lanczos3" => Ok(ResizeFilter::Lanczos3),
+            _ => Err(format!(
+                "unknown filter '{}' (expected nearest, triangle, catmull-rom, gaussian, or lanczos3)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ResizeFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResizeFilter::Nearest => write!(f, "nearest"),
+            ResizeFilter::Triangle => write!(f, "triangle"),
+            ResizeFilter::CatmullRom => write!(f, "catmull-rom"),
+            ResizeFilter::Gaussian => write!(f, "gaussian"),
+            ResizeFilter::Lanczos3 => write!(f, "lanczos3"),
+        }
+    }
+}
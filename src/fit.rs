@@ -0,0 +1,167 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{imageops::FilterType, DynamicImage, GrayImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The longest side of the downscaled copy of an image searched by
+/// [`TileFit::Smart`].
+const SMART_CROP_SIZE: u32 = 96;
+
+/// The number of bins in the luminance histograms used by
+/// [`TileFit::Smart`].
+const HISTOGRAM_BINS: usize = 32;
+
+/// How images which are not square are made into square [`Tile`](crate::Tile)s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileFit {
+    /// Scale the whole image to a square, ignoring its aspect ratio.
+    #[default]
+    Stretch,
+    /// Crop the largest square from the center of the image.
+    Center,
+    /// Crop the largest square from wherever along the long side of the
+    /// image has the most detail (the highest luminance entropy), so
+    /// e.g., the heads in portrait photos aren't cropped off. Images
+    /// without any detail are cropped from the center.
+    Smart,
+}
+
+impl TileFit {
+    /// Crop an image to a square as described by this fit.
+    ///
+    /// Images which are already square (and any image, for
+    /// [`TileFit::Stretch`]) are returned as-is.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let (w, h) = (img.width(), img.height());
+        if *self == TileFit::Stretch || w == h {
+            return img;
+        }
+
+        let side = w.min(h);
+        let offset = match self {
+            TileFit::Stretch => unreachable!(),
+            TileFit::Center => (w.max(h) - side) / 2,
+            TileFit::Smart => smart_offset(&img),
+        };
+        match w > h {
+            true => img.crop_imm(offset, 0, side, side),
+            false => img.crop_imm(0, offset, side, side),
+        }
+    }
+}
+
+/// Find the offset along the long side of an image of the square with the
+/// highest luminance entropy.
+///
+/// The search runs over a downscaled copy of the image, sliding the square
+/// one row (or column) at a time and updating its histogram incrementally.
+/// Ties go to the square closest to the center.
+fn smart_offset(img: &DynamicImage) -> u32 {
+    let (w, h) = (img.width(), img.height());
+    let long = w.max(h);
+    let luma: GrayImage = if long > SMART_CROP_SIZE {
+        let s = SMART_CROP_SIZE as f32 / long as f32;
+        let (sw, sh) = (
+            ((w as f32 * s).round() as u32).max(1),
+            ((h as f32 * s).round() as u32).max(1),
+        );
+        img.resize_exact(sw, sh, FilterType::Triangle).to_luma8()
+    } else {
+        img.to_luma8()
+    };
+
+    // treat the image as portrait, so each slice is a row
+    let luma = match w > h {
+        true => image::imageops::rotate90(&luma),
+        false => luma,
+    };
+    let (side, len) = luma.dimensions();
+    let side = side.min(len);
+    let slices: Vec<[u32; HISTOGRAM_BINS]> = luma
+        .rows()
+        .map(|row| {
+            let mut hist = [0; HISTOGRAM_BINS];
+            for px in row {
+                hist[px.0[0] as usize * HISTOGRAM_BINS / 256] += 1;
+            }
+            hist
+        })
+        .collect();
+
+    let mut hist = [0; HISTOGRAM_BINS];
+    for slice in &slices[..side as usize] {
+        hist.iter_mut().zip(slice).for_each(|(a, b)| *a += b);
+    }
+    let center = (len - side) as f32 / 2.0;
+    let mut best = (entropy(&hist), 0);
+    for p in 1..=(len - side) as usize {
+        let (out, inc) = (&slices[p - 1], &slices[p - 1 + side as usize]);
+        for i in 0..HISTOGRAM_BINS {
+            hist[i] = hist[i] - out[i] + inc[i];
+        }
+        let e = entropy(&hist);
+        let closer = (p as f32 - center).abs() < (best.1 as f32 - center).abs();
+        if e > best.0 + 1e-6 || ((e - best.0).abs() <= 1e-6 && closer) {
+            best = (e, p);
+        }
+    }
+
+    // map the offset back onto the full image
+    let max = long - w.min(h);
+    let offset = (best.1 as f32 * long as f32 / len as f32).round() as u32;
+    offset.min(max)
+}
+
+/// Compute the Shannon entropy (in bits) of a histogram.
+fn entropy(hist: &[u32]) -> f32 {
+    let total: u32 = hist.iter().sum();
+    hist.iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f32 / total as f32;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+impl FromStr for TileFit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stretch" => Ok(TileFit::Stretch),
+            "center" => Ok(TileFit::Center),
+            "smart" => Ok(TileFit::Smart),
+            _ => Err(format!(
+                "unknown tile fit '{}' (expected stretch, center, or smart)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TileFit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TileFit::Stretch => write!(f, "stretch"),
+            TileFit::Center => write!(f, "center"),
+            TileFit::Smart => write!(f, "smart"),
+        }
+    }
+}
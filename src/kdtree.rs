@@ -0,0 +1,160 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::color::{Lab, Oklab};
+use crate::metric::Metric;
+use crate::tiles::{Tile, TileSet};
+use image::Rgb;
+
+/// A k-d tree over the average colors of the [`Tile`]s in a [`TileSet`],
+/// to find the closest [`Tile`] to a pixel without comparing it to every
+/// [`Tile`] in the set.
+///
+/// The average colors are points in the space the [`Metric`] measures
+/// Euclidean distances in (e.g., [`Oklab`] for [`Metric::Oklab`]). The
+/// difference along any one axis is a lower bound on the distance between
+/// two points, so the search skips every subtree on the far side of a
+/// split which is further away than the best [`Tile`] so far.
+///
+/// The search is exact: it always finds the same [`Tile`] as an exhaustive
+/// one, including weights and breaking ties in favor of the first [`Tile`]
+/// in the set.
+#[derive(Debug, Clone)]
+pub(crate) struct KdTree {
+    metric: Metric,
+    /// The point of each tile, with its index in the set. The node for each
+    /// subtree is at the middle of its range, with the points below it on
+    /// the subtree's axis before it and those above it after.
+    nodes: Vec<([f32; 3], usize)>,
+    /// An upper bound on the weight of any tile in the set (which scales
+    /// down the lower bound on their scores).
+    max_weight: f32,
+}
+
+impl KdTree {
+    /// The fewest [`Tile`]s in a set for which searching a tree is faster
+    /// than comparing a pixel to every [`Tile`].
+    pub(crate) const MIN_TILES: usize = 64;
+
+    /// Build a tree over the given [`Tile`]s for the given [`Metric`], or
+    /// `None` if the [`Metric`] is not a Euclidean distance (i.e.,
+    /// [`Metric::CieDe2000`]).
+    pub(crate) fn new(tiles: &[Tile], metric: Metric) -> Option<Self> {
+        let mut nodes = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Some((point(metric, t.avg())?, i)))
+            .collect::<Option<Vec<_>>>()?;
+        build(&mut nodes, 0);
+        let max_weight = tiles.iter().map(Tile::weight).fold(0.0, f32::max);
+
+        Some(Self {
+            metric,
+            nodes,
+            max_weight,
+        })
+    }
+
+    /// Account for a [`Tile`] whose weight has changed to `weight`.
+    ///
+    /// A lower weight leaves `max_weight` too high, which only loosens the
+    /// bounds of the search, so the tree needn't be rebuilt.
+    pub(crate) fn reweigh(&mut self, weight: f32) {
+        self.max_weight = self.max_weight.max(weight);
+    }
+
+    /// Get the [`Metric`] this tree was built for.
+    pub(crate) fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// Find the index of the [`Tile`] in `tiles` (the set this tree was
+    /// built over) with the best [score](Tile::score) for the given pixel.
+    pub(crate) fn closest(&self, tiles: &TileSet, px: &Rgb<u8>) -> usize {
+        let target = point(self.metric, px).expect("The tree's metric has points");
+        let mut best = (f32::INFINITY, 0);
+        self.search(&self.nodes, 0, &target, tiles, px, &mut best);
+        best.1
+    }
+
+    /// Search the subtree in `nodes` (at the given depth) for a better
+    /// [`Tile`] than the `best` so far, as `(score, index)`.
+    fn search(
+        &self,
+        nodes: &[([f32; 3], usize)],
+        depth: usize,
+        target: &[f32; 3],
+        tiles: &TileSet,
+        px: &Rgb<u8>,
+        best: &mut (f32, usize),
+    ) {
+        if nodes.is_empty() {
+            return;
+        }
+        let mid = nodes.len() / 2;
+        let (split, idx) = nodes[mid];
+        let score = tiles.get(idx).unwrap().score(px, self.metric);
+        if score < best.0 || (score == best.0 && idx < best.1) {
+            *best = (score, idx);
+        }
+
+        let axis = depth % 3;
+        let gap = target[axis] - split[axis];
+        let (near, far) = match gap < 0.0 {
+            true => (&nodes[..mid], &nodes[mid + 1..]),
+            false => (&nodes[mid + 1..], &nodes[..mid]),
+        };
+        self.search(near, depth + 1, target, tiles, px, best);
+
+        // leave a little slack for rounding in the computed scores
+        let bound = gap.abs() * (1.0 - 1e-5) / self.max_weight;
+        if bound <= best.0 {
+            self.search(far, depth + 1, target, tiles, px, best);
+        }
+    }
+}
+
+/// Arrange `nodes` into the subtree at the given depth, splitting on the
+/// median along one axis per level.
+fn build(nodes: &mut [([f32; 3], usize)], depth: usize) {
+    if nodes.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = nodes.len() / 2;
+    nodes.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    let (below, above) = nodes.split_at_mut(mid);
+    build(below, depth + 1);
+    build(&mut above[1..], depth + 1);
+}
+
+/// Get the point for a color in the space the metric measures Euclidean
+/// distances in, if any.
+fn point(metric: Metric, px: &Rgb<u8>) -> Option<[f32; 3]> {
+    let weights = match metric {
+        Metric::Rgb => [1.0; 3],
+        Metric::WeightedRgb(weights) => weights,
+        Metric::Oklab => {
+            let Oklab { l, a, b } = Oklab::from(px);
+            return Some([l, a, b]);
+        }
+        Metric::Lab => {
+            let Lab { l, a, b } = Lab::from(px);
+            return Some([l, a, b]);
+        }
+        Metric::CieDe2000 => return None,
+    };
+    Some([0, 1, 2].map(|c| weights[c] * px.0[c] as f32))
+}
@@ -1,4 +1,15 @@
 //! Build a mosaic of a particular image out of a set of smaller images.
+//!
+//! # Determinism
+//! Building a mosaic from the same source image, tiles, and options always
+//! produces exactly the same output, on any platform:
+//! * [`load_tiles`] returns tiles sorted by path, rather than in the
+//!   (platform-dependent) order the directory is listed in.
+//! * A [`TileSet`] sorts its [`Tile`]s into a canonical order based on
+//!   their pixels, so the order in which images are given does not matter.
+//! * When several [`Tile`]s match a pixel equally well, the first one in
+//!   the [`TileSet`] is always chosen.
+//! * Any randomized feature is seeded from [`MosaicOptions::seed`].
 
 // tilr - A program to build an image from a set of image 'tiles'.
 // Copyright (C) 2023  Charles German <5donuts@pm.me>
@@ -22,9 +33,96 @@
     broken_intra_doc_links
 )]
 
+mod assign;
+mod builder;
+mod cache;
+mod cells;
+mod cmyk;
+mod color;
+mod compare;
+mod coverage;
+mod descriptor;
+mod diversity;
+mod error;
+mod eta;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod filter;
+mod fit;
+mod kdtree;
+mod metric;
+mod montage;
 mod mosaic;
-mod tiles;
+mod noise;
+mod options;
+mod output;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod plan;
+mod postprocess;
+mod preprocess;
+mod progress;
+mod quality;
+mod quota;
+mod rect;
+#[cfg(feature = "remote")]
+mod remote;
+mod report;
+mod search;
+mod shuffle;
+mod stats;
+#[cfg(feature = "mmap")]
+mod store;
+mod summary;
+pub mod tiles;
+mod timings;
+mod transform;
 mod utils;
+#[cfg(feature = "video")]
+mod video;
 
-pub use mosaic::Mosaic;
-pub use utils::load_tiles;
+pub use builder::MosaicBuilder;
+pub use cache::MapCache;
+pub use cells::CellColor;
+pub use color::{Lab, Oklab};
+pub use compare::{compare_images, ImageDiff, PlanDiff};
+pub use coverage::{CoverageGap, CoverageReport};
+pub use descriptor::Descriptor;
+pub use diversity::{ColorHistogram, DiversityCheck, DiversityWarning};
+pub use error::Error;
+pub use eta::Eta;
+pub use filter::ResizeFilter;
+pub use fit::TileFit;
+pub use metric::Metric;
+pub use montage::Montage;
+pub use mosaic::{estimate_output_size, grid_size, Mosaic, MAX_RECURSIVE_OUTPUT_PIXELS};
+pub use options::{MosaicOptions, ScaleError};
+pub use output::{
+    encoded_size, print_width_px, records_dpi, save_image, save_image_hashed,
+    save_image_hashed_with, save_image_with, scale_for_print, write_image_with, HashingWriter,
+    OutputOptions, PngCompression, PrintSize, CM_PER_INCH,
+};
+pub use plan::{Cell, MosaicPlan, TileRef};
+pub use postprocess::PostProcess;
+pub use preprocess::{saturate, Posterize, Preprocess};
+pub use progress::{Phase, Progress};
+pub use quality::QualityReport;
+pub use rect::Rect;
+#[cfg(feature = "remote")]
+pub use remote::{load_tile_urls, parse_url_list, RemoteOptions};
+pub use report::RunReport;
+pub use search::ApproxSearch;
+pub use stats::{CategoryUsage, Clustering, DistanceStats, PlanStats, TileUsage};
+#[cfg(feature = "mmap")]
+pub use store::TileStore;
+pub use summary::{HueBucket, TileSetSummary};
+pub use tiles::{GapFill, IntoTileSet, Tile, TileSet};
+pub use timings::Timings;
+pub use transform::{decode_oriented_cmyk, load_oriented, load_oriented_cmyk, Rotation, Transform};
+pub use utils::{
+    flatten_alpha, load_tile_files, load_tiles, load_tiles_cached, load_tiles_multi,
+    load_tiles_with, slice_image, DecodeCache, LoadOptions, LoadReport, LoadWarning,
+    LoadWarningReason,
+};
+#[cfg(feature = "video")]
+pub use video::{video_frames, FrameSampling, VideoMosaic};
@@ -0,0 +1,95 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::color::{Lab, Oklab};
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+
+/// The color distance metric used to match pixels to [`Tile`](crate::Tile)s.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Euclidean distance between the RGB values of two colors.
+    #[default]
+    Rgb,
+    /// Euclidean distance between the RGB values of two colors, with the
+    /// difference in each channel multiplied by the corresponding weight
+    /// (red, green, blue) before squaring.
+    ///
+    /// Construct this with [`Metric::weighted_rgb`] to validate the weights.
+    WeightedRgb([f32; 3]),
+    /// Euclidean distance between two colors in the perceptual [`Oklab`]
+    /// color space, which tracks how different colors look (particularly
+    /// in hue) more closely than RGB.
+    ///
+    /// Distances are on a different scale than the RGB metrics: black and
+    /// white are `1.0` apart (rather than about `441.7`), which matters
+    /// for thresholds given as distances.
+    Oklab,
+    /// Euclidean distance between two colors in the [`Lab`] (CIELAB) color
+    /// space (the CIE76 ΔE), which tracks how different colors look more
+    /// closely than RGB, particularly among dark colors and skin tones.
+    ///
+    /// As with [`Metric::Oklab`], distances are on a different scale than
+    /// the RGB metrics: black and white are `100.0` apart (rather than about
+    /// `441.7`).
+    Lab,
+    /// The CIEDE2000 color difference (ΔE00) between two colors in the
+    /// [`Lab`] color space (see [`Lab::ciede2000`]), which corrects the
+    /// distance of [`Metric::Lab`] for how the eye judges differences in
+    /// lightness, chroma, and hue, so that near-neutral and saturated colors
+    /// are matched more accurately. It is on the same scale as
+    /// [`Metric::Lab`], but slower to compute.
+    #[serde(rename = "ciede2000")]
+    CieDe2000,
+}
+
+impl Metric {
+    /// Build a [`Metric::WeightedRgb`] metric with the given weights
+    /// for the red, green, and blue channels.
+    ///
+    /// # Panics
+    /// This function panics if any weight is not a positive, finite number.
+    pub fn weighted_rgb(weights: [f32; 3]) -> Self {
+        if !weights.iter().all(|w| w.is_finite() && *w > 0.0) {
+            panic!("Channel weights must be positive and finite");
+        }
+        Metric::WeightedRgb(weights)
+    }
+
+    /// Compute the distance between two colors using this metric.
+    pub fn distance(&self, p: &Rgb<u8>, q: &Rgb<u8>) -> f32 {
+        match self {
+            Metric::Rgb => {
+                let d_r = p.0[0] as i32 - q.0[0] as i32;
+                let d_g = p.0[1] as i32 - q.0[1] as i32;
+                let d_b = p.0[2] as i32 - q.0[2] as i32;
+
+                ((d_r.pow(2) + d_g.pow(2) + d_b.pow(2)) as f32).sqrt()
+            }
+            Metric::WeightedRgb(weights) => {
+                p.0.iter()
+                    .zip(q.0)
+                    .zip(weights)
+                    .map(|((&p, q), w)| (w * (p as f32 - q as f32)).powi(2))
+                    .sum::<f32>()
+                    .sqrt()
+            }
+            Metric::Oklab => Oklab::from(p).distance(&Oklab::from(q)),
+            Metric::Lab => Lab::from(p).distance(&Lab::from(q)),
+            Metric::CieDe2000 => Lab::from(p).ciede2000(&Lab::from(q)),
+        }
+    }
+}
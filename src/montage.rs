@@ -0,0 +1,122 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::filter::ResizeFilter;
+use crate::mosaic::grid_size;
+use crate::options::check_scale;
+use image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+
+/// A grid of several source images, combined into one source image for a
+/// single [`Mosaic`](crate::Mosaic) (e.g., photos side by side), so they
+/// share a tile set and the tiles are chosen consistently across them.
+///
+/// Every image is given a slot of the same size in the grid: the largest
+/// width and the largest height of the images (once scaled). Each image is
+/// scaled to fit within its slot (keeping its aspect ratio) and centered in
+/// it. The rest of the slot, and the gaps between slots, are transparent,
+/// so they're left as background (with an
+/// [`alpha_threshold`](crate::MosaicOptions::alpha_threshold) of at least
+/// `1`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Montage {
+    /// The number of columns in the grid.
+    pub columns: u32,
+    /// The number of rows in the grid.
+    pub rows: u32,
+    /// The number of cells between neighboring slots.
+    pub gap: u32,
+}
+
+impl Montage {
+    /// Get the size (in cells) of each slot of the grid for images of the
+    /// given sizes, scaled by `img_scaling` (see [`Mosaic::new`](crate::Mosaic::new)).
+    pub fn slot_size(&self, sizes: &[(u32, u32)], img_scaling: f32) -> (u32, u32) {
+        sizes
+            .iter()
+            .map(|&size| grid_size(size, img_scaling))
+            .fold((0, 0), |(w, h), (x, y)| (w.max(x), h.max(y)))
+    }
+
+    /// Get the size (in cells) of the combined image, with slots of the
+    /// given size.
+    pub fn canvas_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        (
+            self.columns * width + (self.columns - 1) * self.gap,
+            self.rows * height + (self.rows - 1) * self.gap,
+        )
+    }
+
+    /// Get the position (in cells) of the top left corner of the given slot
+    /// (counting across each row, then down), with slots of the given size.
+    pub fn slot_position(&self, slot: u32, (width, height): (u32, u32)) -> (u32, u32) {
+        let (column, row) = (slot % self.columns, slot / self.columns);
+        (column * (width + self.gap), row * (height + self.gap))
+    }
+
+    /// Combine the `sources` into one image, filling the slots of the grid
+    /// across each row, then down.
+    ///
+    /// The result is already scaled by `img_scaling` (with the given
+    /// `filter`): each of its pixels is a cell, so build the mosaic of it
+    /// with a scaling factor of `1`.
+    ///
+    /// # Panics
+    /// This function panics if there are no `sources`, if there are more
+    /// `sources` than slots in the grid, or if `img_scaling` is invalid for
+    /// any of them (see [`MosaicOptions::validate`](crate::MosaicOptions::validate)).
+    pub fn compose(
+        &self,
+        sources: &[DynamicImage],
+        img_scaling: f32,
+        filter: ResizeFilter,
+    ) -> RgbaImage {
+        if sources.is_empty() {
+            panic!("A montage needs at least one image.");
+        }
+        if sources.len() as u64 > self.columns as u64 * self.rows as u64 {
+            panic!(
+                "Too many images for a {}x{} montage ({} images).",
+                self.columns,
+                self.rows,
+                sources.len()
+            );
+        }
+        for img in sources {
+            check_scale(img.dimensions(), img_scaling).unwrap_or_else(|e| panic!("{}", e));
+        }
+        let sizes: Vec<(u32, u32)> = sources.iter().map(|img| img.dimensions()).collect();
+        let slot = self.slot_size(&sizes, img_scaling);
+        let (width, height) = self.canvas_size(slot);
+        let mut canvas = RgbaImage::new(width, height);
+
+        for (i, img) in sources.iter().enumerate() {
+            let (x, y) = grid_size(img.dimensions(), img_scaling);
+            // fit the image within the slot, keeping its aspect ratio
+            let fit = (slot.0 as f64 / x as f64).min(slot.1 as f64 / y as f64);
+            let (x, y) = (
+                ((x as f64 * fit).round() as u32).clamp(1, slot.0),
+                ((y as f64 * fit).round() as u32).clamp(1, slot.1),
+            );
+            let scaled = match img.dimensions() == (x, y) {
+                true => img.to_rgba8(),
+                false => imageops::resize(img, x, y, filter.into()),
+            };
+            let (left, top) = self.slot_position(i as u32, slot);
+            let (left, top) = (left + (slot.0 - x) / 2, top + (slot.1 - y) / 2);
+            imageops::replace(&mut canvas, &scaled, left as i64, top as i64);
+        }
+        canvas
+    }
+}
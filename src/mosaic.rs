@@ -13,8 +13,32 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use crate::builder::MosaicBuilder;
+use crate::cache::MapCache;
+use crate::cells::{cell_colors, cell_colors_rgb};
+use crate::descriptor::detail_image;
+use crate::eta::Eta;
+use crate::filter::ResizeFilter;
+use crate::options::{check_scale, MosaicOptions};
+use crate::plan::{Cell, MosaicPlan};
+use crate::preprocess::SourceAdjustment;
+use crate::progress::{Phase, Progress, ProgressHook};
+use crate::quality::{self, QualityReport};
+use crate::rect::Rect;
 use crate::tiles::*;
-use image::{DynamicImage, GenericImage, GenericImageView, Pixel, RgbImage};
+use crate::timings::Timings;
+use crate::utils::{alpha_channel, composite, flatten_alpha, is_gray, majority_labels};
+use image::{
+    imageops, DynamicImage, GenericImageView, GrayImage, ImageBuffer, Pixel, Rgb, RgbImage,
+    RgbaImage,
+};
+use log::debug;
+use std::borrow::Cow;
+use std::error::Error;
+use std::sync::atomic::AtomicBool;
+
+/// The largest mosaic (in pixels) that [`Mosaic::render_recursive`] will build.
+pub const MAX_RECURSIVE_OUTPUT_PIXELS: u64 = 1 << 30;
 
 /// Generates an image 'mosaic' using a set of image Tiles.
 ///
@@ -26,14 +50,27 @@ use image::{DynamicImage, GenericImage, GenericImageView, Pixel, RgbImage};
 pub struct Mosaic {
     /// The original image used to create the mosaic.
     img: RgbImage,
+    /// The alpha channel of the original image, if it has one.
+    alpha: Option<GrayImage>,
+    /// The original image scaled so that each cell covers a block of
+    /// pixels, from which the descriptor of each cell is computed; only
+    /// kept for descriptors with a [grid size](crate::Descriptor::grid_size) above 1.
+    detail: Option<RgbImage>,
+    /// The label of the region each pixel of the (scaled) source image
+    /// belongs to, if a label mask has been set.
+    labels: Option<GrayImage>,
     /// The set of [`Tile`]s to use to build the mosaic.
     ///
     /// Pixels in the original image are mapped to these tiles based
     /// on the Euclidean distance between the RGB pixel values and the
     /// average RGB values in the [`Tile`].
     tiles: TileSet,
-    /// An inner member used to build the resulting image mosaic.
-    inner: Inner,
+    /// The options used to assign [`Tile`]s to pixels.
+    options: MosaicOptions,
+    /// The time spent building this mosaic so far.
+    pub(crate) timings: Timings,
+    /// The callback to report progress to while rendering, if any.
+    progress: ProgressHook,
 }
 
 impl Mosaic {
@@ -41,72 +78,282 @@ impl Mosaic {
     ///
     /// # Arguments
     /// * `img` - The original image used to create the mosaic.
-    /// * `tiles` - The set of Tiles to use to build the mosaic.
+    /// * `tiles` - The images to use as the Tiles of the mosaic (e.g., a
+    ///   `&Vec<DynamicImage>`, a slice, or an iterator of owned or
+    ///   borrowed images), or a [`TileSet`] which has already been built
+    ///   (see [`IntoTileSet`]).
     /// * `img_scaling` - The scaling factor to apply to the original
-    ///                   image for the mosaic. A scaling factor of `1`
-    ///                   means no scaling. The scaling performed does
-    ///                   _not_ preserve aspect ratio.
+    ///   image for the mosaic. A scaling factor of `1` means no scaling.
+    ///   The scaling performed does _not_ preserve aspect ratio.
     /// * `tile_size` - The desired side length for the Tiles to use
-    ///                 to generate this mosaic. If the Tiles are not
-    ///                 already squares with this side length, they will
-    ///                 be resized (without preserving aspect ratio) to
-    ///                 be squares with the given side length.
+    ///   to generate this mosaic. If the Tiles are not already squares
+    ///   with this side length, they will be resized (without preserving
+    ///   aspect ratio) to be squares with the given side length.
     ///
     /// # Returns
-    /// An empty mosaic. To build the mosaic, call [`to_image`](Mosaic::to_image).
+    /// An empty mosaic. To build the mosaic, call [`render`](Mosaic::render).
     /// Note that generating the resulting mosaic is an expensive operation and
     /// could take many seconds (or minutes for especially large mosaics).
     ///
+    /// If `img` has an alpha channel, its (semi-)transparent pixels are
+    /// composited over the [`matte`](MosaicOptions::matte) before they are
+    /// matched to tiles, and pixels less opaque than the
+    /// [`alpha_threshold`](MosaicOptions::alpha_threshold) are left as
+    /// background.
+    ///
     /// # Panics
-    /// This function panics if `img_scaling` is less than `0.1`.
-    /// Additionally, it will panic if the chosen scaling factor would result
-    /// in an image that has zero pixels in any dimension.
-    pub fn new(
+    /// This function panics if the scaling factor is invalid for `img` (see
+    /// [`MosaicOptions::validate`], which reports why without panicking),
+    /// if `tiles` is empty, or if `tile_size` is zero; see
+    /// [`try_new`](Mosaic::try_new) to handle these as errors instead.
+    pub fn new<I>(img: DynamicImage, tiles: I, img_scaling: f32, tile_size: u8) -> Self
+    where
+        I: IntoTileSet,
+    {
+        Self::try_new(img, tiles, img_scaling, tile_size).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Initialize a new image mosaic, like [`new`](Mosaic::new).
+    ///
+    /// # Errors
+    /// See [`MosaicBuilder::build`].
+    pub fn try_new<I>(
         img: DynamicImage,
-        tiles: &Vec<DynamicImage>,
+        tiles: I,
         img_scaling: f32,
         tile_size: u8,
+    ) -> Result<Self, crate::Error>
+    where
+        I: IntoTileSet,
+    {
+        Self::try_with_options(img, tiles, img_scaling, tile_size, MosaicOptions::default())
+    }
+
+    /// Start configuring a new image mosaic of `img` with a
+    /// [`MosaicBuilder`], which checks the tiles, tile size, and scaling
+    /// factor when the mosaic is built rather than panicking on them.
+    pub fn builder(img: DynamicImage) -> MosaicBuilder {
+        MosaicBuilder::new(img)
+    }
+
+    /// Initialize a new image mosaic with the given options.
+    ///
+    /// This is the same as [`new`](Mosaic::new), except that the options
+    /// are set up front, so the [`transform`](MosaicOptions::transform) can
+    /// be applied to `img` before it is scaled, and the
+    /// [`preprocess`](MosaicOptions::preprocess),
+    /// [`saturation`](MosaicOptions::saturation), and
+    /// [`posterize`](MosaicOptions::posterize) adjustments after.
+    /// (Changing any of these later with [`options_mut`](Mosaic::options_mut)
+    /// has no effect.)
+    ///
+    /// Likewise, with a [`descriptor`](MosaicOptions::descriptor) which
+    /// compares blocks of each cell (e.g.,
+    /// [`Descriptor::Quadrants`](crate::Descriptor::Quadrants)), the blocks
+    /// of each cell are computed from the original `img` rather than from
+    /// the scaled source image. (If the descriptor is changed later, they are
+    /// computed from the neighborhood of each pixel of the scaled source
+    /// image instead.)
+    ///
+    /// # Panics
+    /// See [`new`](Mosaic::new), [`Preprocess::apply`](crate::Preprocess::apply),
+    /// [`saturate`](crate::saturate), and [`Posterize::apply`](crate::Posterize::apply).
+    pub fn with_options<I>(
+        img: DynamicImage,
+        tiles: I,
+        img_scaling: f32,
+        tile_size: u8,
+        options: MosaicOptions,
+    ) -> Self
+    where
+        I: IntoTileSet,
+    {
+        Self::try_with_options(img, tiles, img_scaling, tile_size, options)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Initialize a new image mosaic with the given options, like
+    /// [`with_options`](Mosaic::with_options).
+    ///
+    /// # Errors
+    /// See [`MosaicBuilder::build`].
+    ///
+    /// # Panics
+    /// See [`Preprocess::apply`](crate::Preprocess::apply),
+    /// [`saturate`](crate::saturate), and [`Posterize::apply`](crate::Posterize::apply).
+    pub fn try_with_options<I>(
+        img: DynamicImage,
+        tiles: I,
+        img_scaling: f32,
+        tile_size: u8,
+        options: MosaicOptions,
+    ) -> Result<Self, crate::Error>
+    where
+        I: IntoTileSet,
+    {
+        Self::builder(img)
+            .tiles(tiles)
+            .scale(img_scaling)
+            .tile_size(tile_size)
+            .options(options)
+            .build()
+    }
+
+    /// Initialize a new image mosaic of an [`RgbImage`] with the given
+    /// options.
+    ///
+    /// This is the same as [`with_options`](Mosaic::with_options), for a
+    /// source which is already an `RgbImage` (e.g., a decoded video frame),
+    /// which is scaled and matched to tiles as it is, without converting
+    /// it (or copying it) first.
+    ///
+    /// # Panics
+    /// See [`with_options`](Mosaic::with_options).
+    pub fn from_rgb<I>(
+        img: RgbImage,
+        tiles: I,
+        img_scaling: f32,
+        tile_size: u8,
+        options: MosaicOptions,
+    ) -> Self
+    where
+        I: IntoTileSet,
+    {
+        let img = DynamicImage::ImageRgb8(img);
+        Self::with_options(img, tiles, img_scaling, tile_size, options)
+    }
+
+    /// Initialize a new image mosaic from a [`TileSet`] which has already
+    /// been built (e.g., with [`TileSet::with_labels`]), with the given
+    /// options.
+    ///
+    /// This is the same as [`with_options`](Mosaic::with_options), except
+    /// that the tiles are only scaled (if they are not already `tile_size`).
+    ///
+    /// # Panics
+    /// See [`new`](Mosaic::new).
+    pub fn with_tile_set(
+        img: DynamicImage,
+        tiles: TileSet,
+        img_scaling: f32,
+        tile_size: u8,
+        options: MosaicOptions,
     ) -> Self {
-        if img_scaling < 0.1 {
-            panic!("Scaling factor must be at least 0.1.");
-        }
-        // Scale the source image, if specified
-        let img = if img_scaling != 1.0 {
-            let (x, y) = img.dimensions();
-            let x = (x as f32 * img_scaling) as u32;
-            let y = (y as f32 * img_scaling) as u32;
-            if x == 0 || y == 0 {
-                panic!(
-                    "Scaling factor results in an image with at least one dimension with zero px"
-                );
+        let img = match img {
+            DynamicImage::ImageRgb8(img) => {
+                return Self::with_rgb_tile_set(img, tiles, img_scaling, tile_size, options)
             }
-            img.resize_exact(x, y, image::imageops::FilterType::Triangle)
-        } else {
-            img
-        }
-        .to_rgb8();
+            img => options.transform.apply(img),
+        };
+        let original =
+            (options.descriptor.grid_size() > 1).then(|| flatten_alpha(&img, Rgb(options.matte)));
+        let img = scale_source(img, img_scaling, options.source_filter);
+        let img = cell_colors(img, options.cell_size, options.cell_color);
+        let alpha = alpha_channel(&img);
+        let img = img.into_rgb8();
+        Self::from_scaled(img, alpha, original, tiles, tile_size, options)
+    }
+
+    /// Initialize a new image mosaic of an [`RgbImage`] from a [`TileSet`]
+    /// (see [`with_tile_set`](Mosaic::with_tile_set)).
+    fn with_rgb_tile_set(
+        img: RgbImage,
+        tiles: TileSet,
+        img_scaling: f32,
+        tile_size: u8,
+        options: MosaicOptions,
+    ) -> Self {
+        let img = match options.transform.is_identity() {
+            true => img,
+            false => options.transform.apply(img.into()).into_rgb8(),
+        };
+        let original = (options.descriptor.grid_size() > 1).then(|| img.clone());
+        let img = scale_source_rgb(img, img_scaling, options.source_filter);
+        let img = cell_colors_rgb(img, options.cell_size, options.cell_color);
+        Self::from_scaled(img, None, original, tiles, tile_size, options)
+    }
 
-        // Build the tileset
-        let mut tiles = TileSet::from(tiles);
+    /// Initialize a new image mosaic of a source image which has already
+    /// been transformed and scaled (with the `original` image from which
+    /// the blocks of each cell are computed, if they're needed).
+    fn from_scaled(
+        mut img: RgbImage,
+        alpha: Option<GrayImage>,
+        original: Option<RgbImage>,
+        mut tiles: TileSet,
+        tile_size: u8,
+        options: MosaicOptions,
+    ) -> Self {
+        let mut detail = original.map(|original| {
+            detail_image(&original, img.dimensions(), options.descriptor.grid_size())
+        });
 
-        // Scale the tiles if they're not already appropriately
-        // sized.
-        // TODO: just build them the correct size to start with.
-        let tile_size = tile_size as u32;
-        if tiles.tile_side_len() != tile_size {
-            tiles.scale_tiles(tile_size);
+        // fit the adjustments to the colors which are matched to tiles
+        let adjustment = match &alpha {
+            Some(alpha) => {
+                SourceAdjustment::fit(&options, &composite(&img, alpha, Rgb(options.matte)))
+            }
+            None => SourceAdjustment::fit(&options, &img),
+        };
+        if let Some(adjustment) = adjustment {
+            img = adjustment.apply(&img);
+            detail = detail.map(|detail| adjustment.apply(&detail));
         }
 
-        // Initialize the inner image (the output mosaic image)
-        let (img_x, img_y) = img.dimensions();
-        let (mos_x, mos_y) = (img_x * tile_size, img_y * tile_size);
-        let inner = Inner(DynamicImage::new_rgb8(mos_x, mos_y));
+        let mut timings = Timings::default();
+        Timings::measure(&mut timings.averages, || {
+            if tiles.tile_side_len() != tile_size as u32 {
+                tiles.scale_tiles_with(tile_size as u32, &options);
+            }
+        });
+        debug!(
+            "Matching {}x{} cells to {} tiles of {}px.",
+            img.width(),
+            img.height(),
+            tiles.len(),
+            tile_size
+        );
+
+        Self {
+            img,
+            alpha,
+            detail,
+            labels: None,
+            tiles,
+            options,
+            timings,
+            progress: ProgressHook::default(),
+        }
+    }
 
-        Self { img, tiles, inner }
+    /// Get the largest scaling factor for a source image of `src_dims`
+    /// (once transformed) for which a mosaic with tiles of `tile_size`
+    /// is no larger than `max_out` (in pixels).
+    ///
+    /// The result takes the rounding of the [grid of cells](grid_size) into
+    /// account, so the mosaic never exceeds `max_out`, and it's less than
+    /// one tile short of it along at least one side.
+    ///
+    /// If `max_out` is smaller than one tile along either side, no mosaic
+    /// fits, and this returns `0.0` (which [`new`](Mosaic::new) does not
+    /// accept).
+    pub const fn scale_to_fit(src_dims: (u32, u32), tile_size: u32, max_out: (u32, u32)) -> f32 {
+        let (columns, rows) = (max_out.0 / tile_size, max_out.1 / tile_size);
+        if columns == 0 || rows == 0 || src_dims.0 == 0 || src_dims.1 == 0 {
+            return 0.0;
+        }
+        // aim for the middle of the cell so the grid rounds down to
+        // exactly `columns` (or `rows`) cells despite any float error
+        let x = (columns as f32 + 0.5) / src_dims.0 as f32;
+        let y = (rows as f32 + 0.5) / src_dims.1 as f32;
+        x.min(y)
     }
 
     /// Get the size (in pixels) of the resulting mosaic based on the input image size,
     /// scale factor, and tile size.
+    ///
+    /// To get the size before building the mosaic, see
+    /// [`estimate_output_size`].
     pub fn output_size(&self) -> (u32, u32) {
         let (img_x, img_y) = self.img.dimensions();
         let tile_size = self.tiles.tile_side_len();
@@ -115,73 +362,708 @@ impl Mosaic {
         (mos_x, mos_y)
     }
 
-    /// Generate the image mosaic and convert it to an [`RgbImage`].
+    /// Get the [`TileSet`] used to build this mosaic.
+    pub fn tiles(&self) -> &TileSet {
+        &self.tiles
+    }
+
+    /// Get a mutable reference to the [`TileSet`] used to build this
+    /// mosaic (e.g., to [fill gaps](TileSet::fill_gaps) in it).
+    pub fn tiles_mut(&mut self) -> &mut TileSet {
+        &mut self.tiles
+    }
+
+    /// Get the options used to assign [`Tile`]s to pixels.
+    pub fn options(&self) -> &MosaicOptions {
+        &self.options
+    }
+
+    /// Get a mutable reference to the options used to assign [`Tile`]s
+    /// to pixels.
+    pub fn options_mut(&mut self) -> &mut MosaicOptions {
+        &mut self.options
+    }
+
+    /// Get the (scaled) source image used to build this mosaic.
     ///
-    /// Depending on the size of the mosaic to build, this function may
-    /// take some time to run.
-    pub fn to_image(self) -> RgbImage {
-        let map = self.tiles.map_to(&self.img);
+    /// Each pixel of this image corresponds to one cell of the mosaic.
+    pub fn source(&self) -> &RgbImage {
+        &self.img
+    }
+
+    /// Get a mutable reference to the (scaled) source image used to
+    /// build this mosaic (e.g., to paint over part of it before calling
+    /// [`update_region`](Mosaic::update_region)).
+    pub fn source_mut(&mut self) -> &mut RgbImage {
+        &mut self.img
+    }
+
+    /// Get the alpha channel of the (scaled) source image, if it has one.
+    pub fn source_alpha(&self) -> Option<&GrayImage> {
+        self.alpha.as_ref()
+    }
+
+    /// Check whether both the (scaled) source image and every [`Tile`] are
+    /// grayscale, so the mosaic can be rendered with
+    /// [`MosaicPlan::render_gray`] without losing any color.
+    pub fn is_grayscale(&self) -> bool {
+        self.tiles.is_grayscale() && self.matched_source().pixels().all(is_gray)
+    }
+
+    /// Restrict each part of the source image to the [`Tile`]s for its
+    /// region, as given by a label mask.
+    ///
+    /// Each pixel of `mask` is the label (its gray level) of the region the
+    /// corresponding pixel of the source image belongs to; color masks are
+    /// converted to grayscale. The mask is rotated and flipped with the
+    /// [`transform`](MosaicOptions::transform) (like the source image), then
+    /// each cell is given the label of most of the pixels it covers (the
+    /// lowest such label in case of a tie). From then on, each cell is only
+    /// matched against the [`Tile`]s with the same [label](Tile::label)
+    /// (see [`TileSet::with_labels`]).
+    ///
+    /// # Errors
+    /// This function returns an error if any cell is given a label which no
+    /// [`Tile`] in the set has, in which case the mask is not set.
+    pub fn set_label_mask(&mut self, mask: &DynamicImage) -> Result<(), Box<dyn Error>> {
+        let mask = self.options.transform.apply(mask.clone()).to_luma8();
+        let labels = majority_labels(&mask, self.img.dimensions());
+
+        let mut counts = [0usize; 256];
+        for px in labels.pixels() {
+            counts[px.0[0] as usize] += 1;
+        }
+        let missing: Vec<String> = (0..=u8::MAX)
+            .filter(|&l| counts[l as usize] > 0)
+            .filter(|&l| !self.tiles.iter().any(|t| t.label() == Some(l)))
+            .map(|l| format!("{} ({} cells)", l, counts[l as usize]))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("No tiles for labels in the mask: {}", missing.join(", ")).into());
+        }
+
+        self.labels = Some(labels);
+        Ok(())
+    }
+
+    /// Get the label of the region each pixel of the (scaled) source image
+    /// belongs to, if a label mask has been set (see
+    /// [`set_label_mask`](Mosaic::set_label_mask)).
+    pub fn labels(&self) -> Option<&GrayImage> {
+        self.labels.as_ref()
+    }
+
+    /// Get the detail image for the current descriptor, if there is one
+    /// (see [`with_options`](Mosaic::with_options)).
+    fn detail(&self) -> Option<&RgbImage> {
+        let n = self.options.descriptor.grid_size();
+        let (x, y) = self.img.dimensions();
+        self.detail
+            .as_ref()
+            .filter(|d| n > 1 && d.dimensions() == (x * n, y * n))
+    }
+
+    /// Assign a [`Tile`] to each cell of the (scaled) source image within
+    /// `region`, unless `cancel` is set first.
+    fn plan_region(
+        &self,
+        region: Rect,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<MosaicPlan, crate::Error> {
+        let (tiles, options) = (&self.tiles, self.options);
+        let origin = (region.x, region.y);
+        let labels = self.labels.as_ref().map(|l| crop(l, region, 1));
+        let labels = labels.as_deref();
+        let mut plan = match self.detail() {
+            Some(detail) => {
+                let detail = crop(detail, region, options.descriptor.grid_size());
+                MosaicPlan::for_detail(&detail, tiles, options, origin, labels, cancel)?
+            }
+            None => {
+                let src = self.matched_source();
+                let src = crop(&src, region, 1);
+                MosaicPlan::for_region(&src, tiles, options, origin, labels, cancel)?
+            }
+        };
+        if let Some(alpha) = &self.alpha {
+            plan.skip_transparent(&crop(alpha, region, 1));
+        }
+        plan.set_progress(self.progress.clone());
+        Ok(plan)
+    }
+
+    /// Get the (scaled) source image as it is matched to [`Tile`]s, i.e.,
+    /// composited over the [`matte`](MosaicOptions::matte) if it has an
+    /// alpha channel.
+    fn matched_source(&self) -> Cow<'_, RgbImage> {
+        match &self.alpha {
+            Some(alpha) => Cow::Owned(composite(&self.img, alpha, Rgb(self.options.matte))),
+            None => Cow::Borrowed(&self.img),
+        }
+    }
+
+    /// Re-assign [`Tile`]s to the cells within part of the (scaled)
+    /// source image and re-draw only those cells in a rendered mosaic.
+    ///
+    /// Every other pixel in `output` is left untouched, so after changing
+    /// part of the [`source`](Mosaic::source_mut), updating the changed
+    /// region gives the same image as rendering the mosaic from scratch.
+    ///
+    /// # Arguments
+    /// * `output` - A mosaic previously rendered from this [`Mosaic`]
+    ///   (e.g., with [`render`](Mosaic::render)).
+    /// * `region` - The part of the (scaled) source image to update.
+    ///   Parts of the region outside the source image are ignored.
+    ///
+    /// # Panics
+    /// This function panics if `output` is not the size given by
+    /// [`output_size`](Mosaic::output_size).
+    pub fn update_region(&self, output: &mut RgbImage, region: Rect) {
+        if output.dimensions() != self.output_size() {
+            panic!("Output image does not match the size of the mosaic");
+        }
+
         let (img_x, img_y) = self.img.dimensions();
+        let Some(region) = region.intersect(&Rect::new(0, 0, img_x, img_y)) else {
+            return;
+        };
+
+        let plan = self
+            .plan_region(region, None)
+            .expect("Planning without a cancellation flag can't be cancelled");
+
         let tile_size = self.tiles.tile_side_len();
-        let mut mosaic = self.inner;
-
-        // Build the mosaic
-        let mut mos_x = 0;
-        for x in 0..img_x {
-            let mut mos_y = 0;
-            for y in 0..img_y {
-                // print some information about the current source image pixel we're processing
-                let cur_px = y + (x * img_y) + 1;
-                eprint!(
-                    "\rProcessing source px {:04}/{:04}: src loc ({:03}, {:03}) -- dst loc ({:04}, {:04})...          ",
-                    cur_px,
-                    img_x * img_y,
-                    x,
-                    y,
-                    mos_x,
-                    mos_y
-                );
-
-                // Add the tile to the mosaic
-                let tile_for_px = map.get(&self.img.get_pixel(x, y)).expect("No tile for px");
-                mosaic.add_tile(tile_for_px, (mos_x, mos_y));
-
-                // Move to the next pixel in the mosaic
-                mos_y += tile_size;
-            }
+        plan.render_at(
+            &self.tiles,
+            output,
+            (region.x * tile_size, region.y * tile_size),
+        );
+    }
+
+    /// Report the progress of rendering this mosaic (e.g., with
+    /// [`to_image`](Mosaic::to_image)) to `callback`, which is called after
+    /// each cell is placed. This replaces any callback set before.
+    ///
+    /// By default, no progress is reported. The callback is passed on to
+    /// the [`MosaicPlan`]s built by this mosaic (e.g., with
+    /// [`plan`](Mosaic::plan)), so rendering them reports progress too.
+    ///
+    /// # Example
+    /// ```
+    /// # use image::{DynamicImage, Rgb, RgbImage};
+    /// # use std::sync::{Arc, Mutex};
+    /// # use tilr::Mosaic;
+    /// let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 3, Rgb([200, 40, 40])));
+    /// let tiles = vec![DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])))];
+    /// let mut mosaic = Mosaic::builder(img).tiles(&tiles).build()?;
+    ///
+    /// let placed = Arc::new(Mutex::new(0));
+    /// let counter = Arc::clone(&placed);
+    /// mosaic.on_progress(move |progress| *counter.lock().unwrap() = progress.done);
+    /// mosaic.to_image();
+    /// assert_eq!(*placed.lock().unwrap(), 12);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn on_progress(&mut self, callback: impl FnMut(Progress) + Send + 'static) {
+        self.progress = ProgressHook::new(callback);
+    }
 
-            // Move to the next row in the mosaic
-            mos_x += tile_size;
+    /// Get the time spent building this mosaic so far (i.e., preparing
+    /// the [`Tile`]s in [`new`](Mosaic::new)).
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Generate the image mosaic, like [`to_image`](Mosaic::to_image),
+    /// and report how long each phase took.
+    ///
+    /// The returned [`Timings`] include those from [`timings`](Mosaic::timings),
+    /// along with the time spent assigning and placing [`Tile`]s.
+    pub fn render_timed(&self) -> (RgbImage, Timings) {
+        let mut timings = self.timings;
+        let plan = Timings::measure(&mut timings.mapping, || self.plan());
+        let img = Timings::measure(&mut timings.placement, || plan.render(&self.tiles));
+        (img, timings)
+    }
+
+    /// Generate the image mosaic with an alpha channel, like
+    /// [`to_image`](Mosaic::to_image), keeping the transparency of the
+    /// [`Tile`]s and leaving background cells transparent (see
+    /// [`MosaicPlan::render_rgba`]).
+    pub fn render_rgba(&self) -> RgbaImage {
+        self.plan().render_rgba(&self.tiles)
+    }
+
+    /// Generate the image mosaic, like [`to_image`](Mosaic::to_image),
+    /// passing the pixels of each [`Tile`] through `hook` just before it
+    /// is placed (see [`MosaicPlan::render_with`]).
+    ///
+    /// The target color of each cell is the color of the (scaled) source
+    /// image at that cell (composited over the
+    /// [`matte`](MosaicOptions::matte), if it has an alpha channel).
+    ///
+    /// # Panics
+    /// See [`MosaicPlan::render_with`].
+    pub fn render_with<F>(&self, hook: F) -> RgbImage
+    where
+        F: for<'t> Fn(&Cell, &'t RgbImage) -> Cow<'t, RgbImage> + Sync,
+    {
+        self.plan()
+            .render_with(&self.tiles, &self.matched_source(), hook)
+    }
+
+    /// Assign a [`Tile`] to each pixel of the (scaled) source image.
+    ///
+    /// The resulting [`MosaicPlan`] can be rendered with
+    /// [`MosaicPlan::render`] using this mosaic's [`tiles`](Mosaic::tiles).
+    pub fn plan(&self) -> MosaicPlan {
+        let (x, y) = self.img.dimensions();
+        self.plan_region(Rect::new(0, 0, x, y), None)
+            .expect("Planning without a cancellation flag can't be cancelled")
+    }
+
+    /// List the [`Tile`] placed in each cell of the mosaic, as
+    /// `(grid_x, grid_y, tile)` where `tile` is the index of the [`Tile`]
+    /// in this mosaic's [`tiles`](Mosaic::tiles), in row-major order (see
+    /// [`MosaicPlan::placements`]).
+    ///
+    /// Along with the [source](Tile::source) of each [`Tile`], this tells
+    /// which images were used where (e.g., to assemble a physical mosaic).
+    /// The mosaic is planned first; to render it as well, build a single
+    /// [`plan`](Mosaic::plan) and use [`MosaicPlan::placements`].
+    ///
+    /// # Example
+    /// ```
+    /// # use image::{DynamicImage, Rgb, RgbImage};
+    /// # use tilr::tiles::{Tile, TileSet};
+    /// # use tilr::Mosaic;
+    /// let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 1, Rgb([200, 40, 40])));
+    /// let red = Tile::from(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0]))).with_source("red.png");
+    /// let mosaic = Mosaic::new(img, TileSet::new(vec![red]), 1.0, 8);
+    ///
+    /// for (x, y, tile) in mosaic.placements() {
+    ///     let source = mosaic.tiles().get(tile).unwrap().source().unwrap();
+    ///     assert_eq!(source.to_str(), Some("red.png"));
+    ///     assert!(x < 2 && y == 0);
+    /// }
+    /// ```
+    pub fn placements(&self) -> impl Iterator<Item = (u32, u32, usize)> {
+        let placements: Vec<_> = self.plan().placements().collect();
+        placements.into_iter()
+    }
+
+    /// Assign a [`Tile`] to each pixel of the (scaled) source image, like
+    /// [`plan`](Mosaic::plan), stopping early if `cancel` is set (e.g., from
+    /// another thread). It's checked regularly while the cells are matched.
+    ///
+    /// # Errors
+    /// This function returns [`Error::Cancelled`](crate::Error::Cancelled)
+    /// if `cancel` is set before every cell has been matched.
+    pub fn plan_cancellable(&self, cancel: &AtomicBool) -> Result<MosaicPlan, crate::Error> {
+        let (x, y) = self.img.dimensions();
+        self.plan_region(Rect::new(0, 0, x, y), Some(cancel))
+    }
+
+    /// Assign a [`Tile`] to each cell and render a flat-color preview of
+    /// the mosaic, with each cell painted the average color of its
+    /// [`Tile`], downscaled so its longest side is at most `max_dim`.
+    ///
+    /// This is much faster than [`to_image`](Mosaic::to_image), since no
+    /// tiles are drawn. To preview a plan which has already been built,
+    /// see [`MosaicPlan::preview`].
+    ///
+    /// # Panics
+    /// This function panics if `max_dim` is `0`.
+    pub fn preview(&self, max_dim: u32) -> RgbImage {
+        self.plan().preview(&self.tiles, max_dim)
+    }
+
+    /// Assign a [`Tile`] to each pixel of the (scaled) source image, reusing
+    /// the closest [`Tile`]s to colors already recorded in `cache`.
+    ///
+    /// See [`MosaicPlan::for_image_cached`].
+    pub fn plan_cached(&self, cache: &mut MapCache) -> MosaicPlan {
+        if self.options.descriptor.grid_size() > 1 || self.labels.is_some() {
+            return self.plan();
+        }
+        let src = self.matched_source();
+        let mut plan = MosaicPlan::for_image_cached(&src, &self.tiles, self.options, cache);
+        if let Some(alpha) = &self.alpha {
+            plan.skip_transparent(alpha);
+        }
+        plan.set_progress(self.progress.clone());
+        plan
+    }
+
+    /// Measure how closely a rendered mosaic resembles the (scaled)
+    /// source image.
+    ///
+    /// The rendered mosaic is downscaled back to the dimensions of the
+    /// scaled source image (by averaging the pixels in each cell) before
+    /// it is compared to the source. Background cells (see
+    /// [`MosaicOptions::alpha_threshold`]) are not counted in the mean
+    /// distance.
+    ///
+    /// # Panics
+    /// This function panics if `rendered` is not the size given by
+    /// [`output_size`](Mosaic::output_size).
+    pub fn quality(&self, rendered: &RgbImage) -> QualityReport {
+        if rendered.dimensions() != self.output_size() {
+            panic!("Rendered image does not match the size of the mosaic");
         }
+        let downscaled = quality::block_average(rendered, self.tiles.tile_side_len());
 
-        eprintln!(); // so we don't have to add a newline later...
+        let src = self.matched_source();
+        let plan = self.plan();
+        let metric = self.options.metric;
+        let (total_dist, num_cells) = src
+            .pixels()
+            .zip(plan.cells())
+            .enumerate()
+            .filter(|(i, _)| !plan.is_skipped_at(*i))
+            .map(|(_, (px, &idx))| {
+                let tile = self.tiles.get(idx).expect("No tile for cell");
+                metric.distance(px, tile.avg()) as f64
+            })
+            .fold((0.0, 0), |(total, n), d| (total + d, n + 1));
 
-        mosaic.0.into_rgb8()
+        QualityReport {
+            psnr: quality::psnr(&src, &downscaled),
+            ssim: quality::ssim(&src, &downscaled),
+            mean_distance: match num_cells {
+                0 => 0.0,
+                n => total_dist / n as f64,
+            },
+        }
     }
-}
 
-/// A wrapper around a [`DynamicImage`] used to build the resulting
-/// image mosaic.
-struct Inner(DynamicImage);
-
-impl Inner {
-    /// Add a [`Tile`] to the image mosaic.
-    ///
-    /// More specifically, insert the pixels of a given [`Tile`] into
-    /// this image at an offset based on where that [`Tile`] belongs
-    /// in the [`Mosaic`].
-    pub fn add_tile(&mut self, tile: &Tile, start_coords: (u32, u32)) {
-        let s = tile.side_len();
-        let (start_x, start_y) = start_coords;
-        let mut tile_px = tile.img().pixels();
-        for x in start_x..(start_x + s) {
-            for y in start_y..(start_y + s) {
-                let px = tile_px
-                    .next()
-                    .expect("Unable to get next tile px")
-                    .to_rgba();
-                self.0.put_pixel(x, y, px);
+    /// Get the size (in pixels) of the mosaic built by
+    /// [`render_recursive`](Mosaic::render_recursive) with the given
+    /// `depth` and `inner_tile_size`.
+    ///
+    /// Returns `None` if the size does not fit in a `u32`, or if either
+    /// argument is zero.
+    pub fn recursive_output_size(&self, depth: u32, inner_tile_size: u32) -> Option<(u32, u32)> {
+        if depth == 0 || inner_tile_size == 0 {
+            return None;
+        }
+        let factor = (inner_tile_size as u64).checked_pow(depth - 1)?;
+        let (x, y) = self.output_size();
+        let x = (x as u64).checked_mul(factor)?;
+        let y = (y as u64).checked_mul(factor)?;
+
+        Some((x.try_into().ok()?, y.try_into().ok()?))
+    }
+
+    /// Generate a recursive image mosaic, in which each [`Tile`] is itself
+    /// replaced by a mosaic of that [`Tile`].
+    ///
+    /// At depth `1`, this is the same as [`to_image`](Mosaic::to_image).
+    /// At depth `2`, each pixel of every placed [`Tile`] is replaced by a
+    /// [`Tile`] from the same set, scaled to `inner_tile_size`; at depth
+    /// `3`, each pixel of _those_ [`Tile`]s is replaced in turn, and so on.
+    /// So, each cell of the mosaic is `tile_size * inner_tile_size^(depth - 1)`
+    /// pixels square; see [`recursive_output_size`](Mosaic::recursive_output_size).
+    ///
+    /// The mosaic is rendered one cell at a time, so the memory used (beyond
+    /// the output image) does not grow with the depth.
+    ///
+    /// # Errors
+    /// This function returns an error if `depth` or `inner_tile_size` is
+    /// zero, or if the resulting image would be larger than
+    /// [`MAX_RECURSIVE_OUTPUT_PIXELS`].
+    pub fn render_recursive(
+        &self,
+        depth: u32,
+        inner_tile_size: u32,
+    ) -> Result<RgbImage, Box<dyn Error>> {
+        if depth == 0 {
+            return Err("Recursion depth must be at least 1".into());
+        }
+        if inner_tile_size == 0 {
+            return Err("Inner tile size must be at least 1".into());
+        }
+        if depth == 1 {
+            return Ok(self.plan().render(&self.tiles));
+        }
+
+        let (mos_x, mos_y) = self
+            .recursive_output_size(depth, inner_tile_size)
+            .filter(|(x, y)| *x as u64 * *y as u64 <= MAX_RECURSIVE_OUTPUT_PIXELS)
+            .ok_or_else(|| {
+                format!(
+                    "A depth-{} mosaic with {}px inner tiles would be larger than {} pixels",
+                    depth, inner_tile_size, MAX_RECURSIVE_OUTPUT_PIXELS
+                )
+            })?;
+
+        let mut inner = self.tiles.clone();
+        inner.scale_tiles(inner_tile_size);
+
+        let plan = self.plan();
+        let (columns, _) = plan.grid_size();
+        let cell_size = self.tiles.tile_side_len() * inner_tile_size.pow(depth - 1);
+        let num_cells = plan.cells().len();
+        let mut mosaic = RgbImage::new(mos_x, mos_y);
+
+        let mut eta = self.progress.is_set().then(|| Eta::new(num_cells as u64));
+        for (i, &idx) in plan.cells().iter().enumerate() {
+            let x = i as u32 % columns;
+            let y = i as u32 / columns;
+            let offset = (x * cell_size, y * cell_size);
+            if plan.is_skipped_at(i) {
+                let matte = RgbImage::from_pixel(cell_size, cell_size, Rgb(self.options.matte));
+                image::imageops::replace(&mut mosaic, &matte, offset.0 as i64, offset.1 as i64);
+            } else {
+                let tile = self.tiles.get(idx).expect("No tile for cell");
+                let img = plan.adjusted(i, tile.img());
+                self.compose(&img, &inner, depth - 1, &mut mosaic, offset);
             }
+
+            if let Some(eta) = &mut eta {
+                let done = i as u64 + 1;
+                eta.record(done);
+                self.progress
+                    .report(Progress::of(Phase::Placement, done, eta));
+            }
+        }
+
+        Ok(mosaic)
+    }
+
+    /// Render a mosaic of `img` with `levels` levels of recursion into
+    /// `canvas` at the given offset.
+    fn compose(
+        &self,
+        img: &RgbImage,
+        tiles: &TileSet,
+        levels: u32,
+        canvas: &mut RgbImage,
+        offset: (u32, u32),
+    ) {
+        let plan = MosaicPlan::for_image(img, tiles, self.options);
+        if levels == 1 {
+            plan.render_at(tiles, canvas, offset);
+            return;
+        }
+
+        let (columns, _) = plan.grid_size();
+        let cell_size = tiles.tile_side_len().pow(levels);
+        for (i, &idx) in plan.cells().iter().enumerate() {
+            let x = i as u32 % columns;
+            let y = i as u32 / columns;
+            let tile = tiles.get(idx).expect("No tile for cell");
+            let offset = (offset.0 + x * cell_size, offset.1 + y * cell_size);
+            self.compose(&tile.img(), tiles, levels - 1, canvas, offset);
+        }
+    }
+
+    /// Render the single cell at `(cx, cy)` of the mosaic: the image which
+    /// [`to_image`](Mosaic::to_image) places in that cell.
+    ///
+    /// Since the [`Tile`] chosen for a cell may depend on the others (e.g.,
+    /// with a [`usage_penalty`](MosaicOptions::usage_penalty)), the whole
+    /// mosaic is planned first; to render many cells, render them from a
+    /// single [`plan`](Mosaic::plan) with [`MosaicPlan::render_cell`].
+    ///
+    /// # Panics
+    /// See [`MosaicPlan::render_cell`].
+    pub fn render_cell(&self, cx: u32, cy: u32) -> RgbImage {
+        self.plan().render_cell(&self.tiles, cx, cy)
+    }
+
+    /// Generate the image mosaic and convert it to an [`RgbImage`], like
+    /// [`render`](Mosaic::render), consuming the mosaic.
+    ///
+    /// Depending on the size of the mosaic to build, this function may
+    /// take some time to run.
+    pub fn to_image(self) -> RgbImage {
+        self.render()
+    }
+
+    /// Generate the image mosaic as an [`RgbImage`].
+    ///
+    /// Unlike [`to_image`](Mosaic::to_image), this borrows the mosaic, so it
+    /// can be rendered again (e.g., after changing its
+    /// [`options`](Mosaic::options_mut)) without preparing the source image
+    /// and [`Tile`]s again. Depending on the size of the mosaic to build,
+    /// this function may take some time to run.
+    ///
+    /// # Example
+    /// ```
+    /// # use image::{DynamicImage, Rgb, RgbImage};
+    /// # use tilr::Mosaic;
+    /// let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 3, Rgb([200, 40, 40])));
+    /// let tiles = vec![
+    ///     DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0]))),
+    ///     DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([0, 0, 255]))),
+    /// ];
+    /// let mut mosaic = Mosaic::builder(img).tiles(&tiles).build()?;
+    ///
+    /// let plain = mosaic.render();
+    /// mosaic.options_mut().overlay_strength = 0.5;
+    /// let overlaid = mosaic.render();
+    /// assert_eq!(plain.dimensions(), overlaid.dimensions());
+    /// assert_ne!(plain, overlaid);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn render(&self) -> RgbImage {
+        self.plan().render(&self.tiles)
+    }
+
+    /// Generate the image mosaic into `output`, like
+    /// [`render`](Mosaic::render), reusing its buffer rather than allocating
+    /// a new one (see [`MosaicPlan::render_into`]).
+    ///
+    /// # Panics
+    /// This function panics if `output` is not the size given by
+    /// [`output_size`](Mosaic::output_size).
+    pub fn render_into(&self, output: &mut RgbImage) {
+        if output.dimensions() != self.output_size() {
+            panic!("Output image does not match the size of the mosaic");
         }
+        self.plan().render_into(&self.tiles, output);
+    }
+
+    /// Generate the image mosaic, like [`to_image`](Mosaic::to_image),
+    /// stopping early if `cancel` is set (e.g., by another thread, when a
+    /// user aborts a long render). It's checked regularly while
+    /// [`Tile`]s are assigned to cells, and before each cell is placed.
+    ///
+    /// # Errors
+    /// This function returns [`Error::Cancelled`](crate::Error::Cancelled)
+    /// if `cancel` is set before the mosaic is finished.
+    ///
+    /// # Example
+    /// ```
+    /// # use image::{DynamicImage, Rgb, RgbImage};
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// # use tilr::{Error, Mosaic};
+    /// let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 3, Rgb([200, 40, 40])));
+    /// let tiles = vec![DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0])))];
+    /// let mosaic = Mosaic::builder(img).tiles(&tiles).build()?;
+    ///
+    /// let cancel = AtomicBool::new(false);
+    /// assert!(mosaic.to_image_cancellable(&cancel).is_ok());
+    /// cancel.store(true, Ordering::Relaxed);
+    /// assert!(matches!(mosaic.to_image_cancellable(&cancel), Err(Error::Cancelled)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_image_cancellable(&self, cancel: &AtomicBool) -> Result<RgbImage, crate::Error> {
+        self.plan_cancellable(cancel)?
+            .render_cancellable(&self.tiles, cancel)
+    }
+}
+
+/// Get the size of the grid of cells of a mosaic of a source image with
+/// the given (transformed) dimensions, scaled by `img_scaling` (see
+/// [`Mosaic::new`]), without scaling it.
+pub fn grid_size((x, y): (u32, u32), img_scaling: f32) -> (u32, u32) {
+    if img_scaling == 1.0 {
+        return (x, y);
+    }
+    (
+        (x as f32 * img_scaling) as u32,
+        (y as f32 * img_scaling) as u32,
+    )
+}
+
+/// Estimate the size (in pixels) of a mosaic of a source image with the
+/// given (transformed) dimensions, scaled by `img_scaling`, with [`Tile`]s
+/// of `tile_size`, without building the [`Mosaic`] (i.e., the size
+/// [`Mosaic::output_size`] would give), e.g., to check it's not too large
+/// before scaling anything.
+///
+/// This assumes one cell per pixel of the scaled source image. With a
+/// larger [`cell_size`](MosaicOptions::cell_size), pass the grid of cells
+/// given by [`MosaicOptions::validate`] with an `img_scaling` of `1.0`.
+/// The size saturates at [`u32::MAX`] rather than overflowing.
+///
+/// # Example
+/// ```
+/// assert_eq!(tilr::estimate_output_size((400, 300), 0.5, 8), (1600, 1200));
+/// ```
+pub fn estimate_output_size(src_dims: (u32, u32), img_scaling: f32, tile_size: u32) -> (u32, u32) {
+    let (columns, rows) = grid_size(src_dims, img_scaling);
+    (
+        columns.saturating_mul(tile_size),
+        rows.saturating_mul(tile_size),
+    )
+}
+
+/// Get the size of the source image for a mosaic once it's scaled, or
+/// `None` if it isn't scaled.
+///
+/// # Panics
+/// See [`Mosaic::new`].
+fn scaled_size(size: (u32, u32), img_scaling: f32) -> Option<(u32, u32)> {
+    let scaled = check_scale(size, img_scaling).unwrap_or_else(|e| panic!("{}", e));
+    (img_scaling != 1.0).then_some(scaled)
+}
+
+/// Scale the source image for a mosaic.
+///
+/// # Panics
+/// See [`Mosaic::new`].
+pub(crate) fn scale_source(
+    img: DynamicImage,
+    img_scaling: f32,
+    filter: ResizeFilter,
+) -> DynamicImage {
+    match scaled_size(img.dimensions(), img_scaling) {
+        Some((x, y)) => img.resize_exact(x, y, filter.into()),
+        None => img,
+    }
+}
+
+/// Scale the source image for a mosaic, when it's already RGB.
+///
+/// # Panics
+/// See [`Mosaic::new`].
+pub(crate) fn scale_source_rgb(img: RgbImage, img_scaling: f32, filter: ResizeFilter) -> RgbImage {
+    match scaled_size(img.dimensions(), img_scaling) {
+        Some((x, y)) => imageops::resize(&img, x, y, filter.into()),
+        None => img,
+    }
+}
+
+/// Crop the part of an image covered by `region` (in cells), where each
+/// cell covers `n` x `n` pixels of the image.
+fn crop<P: Pixel + 'static>(
+    img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    region: Rect,
+    n: u32,
+) -> Cow<'_, ImageBuffer<P, Vec<P::Subpixel>>> {
+    let (x, y, w, h) = (
+        region.x * n,
+        region.y * n,
+        region.width * n,
+        region.height * n,
+    );
+    if (x, y, w, h) == (0, 0, img.width(), img.height()) {
+        return Cow::Borrowed(img);
+    }
+    Cow::Owned(img.view(x, y, w, h).to_image())
+}
+
+/// Build the [`TileSet`] for a mosaic, with [`Tile`]s of the given side length.
+/// Tiles are scaled as described by the `options` (see
+/// [`TileSet::scale_tiles_with`]).
+#[cfg(feature = "video")]
+pub(crate) fn build_tiles<I>(tiles: I, tile_size: u8, options: &MosaicOptions) -> TileSet
+where
+    I: IntoIterator,
+    I::Item: std::borrow::Borrow<DynamicImage>,
+{
+    // Build the tileset
+    let mut tiles: TileSet = tiles.into_iter().collect();
+
+    // Scale the tiles if they're not already appropriately
+    // sized.
+    // TODO: just build them the correct size to start with.
+    let tile_size = tile_size as u32;
+    if tiles.tile_side_len() != tile_size {
+        tiles.scale_tiles_with(tile_size, options);
     }
+    tiles
 }
@@ -0,0 +1,213 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::utils::fnv1a;
+use image::Rgb;
+
+/// Perturb each channel of a pixel by up to `amplitude`, using seeded noise
+/// that depends only on the position of the pixel.
+///
+/// The noise is interleaved gradient noise, which is spread evenly over
+/// the image (much like blue noise) rather than clumping like white noise.
+/// Each channel uses a different offset into the noise, derived from `seed`.
+pub(crate) fn dither(px: &Rgb<u8>, (x, y): (u32, u32), seed: u64, amplitude: u8) -> Rgb<u8> {
+    if amplitude == 0 {
+        return *px;
+    }
+
+    let mut out = *px;
+    for (c, v) in out.0.iter_mut().enumerate() {
+        let hash = fnv1a(&[&seed.to_le_bytes()[..], &[c as u8]].concat());
+        let (dx, dy) = ((hash & 0xfff) as u32, ((hash >> 12) & 0xfff) as u32);
+        let noise = gradient_noise(x.wrapping_add(dx), y.wrapping_add(dy));
+        let delta = ((noise * 2.0 - 1.0) * amplitude as f32).round() as i32;
+        *v = (*v as i32 + delta).clamp(0, 255) as u8;
+    }
+    out
+}
+
+/// Interleaved gradient noise at the given position, in `[0, 1)`.
+fn gradient_noise(x: u32, y: u32) -> f32 {
+    let fract = |v: f32| v - v.floor();
+    fract(52.982_918 * fract(0.067_110_56 * x as f32 + 0.005_837_15 * y as f32))
+}
+
+/// The largest side length of the matrices made by [`blue_noise`]; larger
+/// grids repeat the matrix.
+pub(crate) const BLUE_NOISE_SIZE: u32 = 64;
+
+/// The spread of the energy each point adds around it when placing points
+/// in [`blue_noise`] (the standard deviation of a Gaussian, in cells).
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// Rank each cell of a `width` x `height` grid (which wraps around at the
+/// edges) so that the cells of every prefix of the ranking are spread evenly
+/// over the grid, without clumps or regular patterns (i.e., blue noise).
+///
+/// This is the void-and-cluster method: points are added one at a time to
+/// the emptiest part of the grid (and removed from the most crowded), where
+/// each point adds a Gaussian of energy around it. The initial points are
+/// chosen pseudo-randomly from `seed`. Returns the rank of each cell, in
+/// row-major order.
+pub(crate) fn blue_noise((width, height): (u32, u32), seed: u64) -> Vec<u32> {
+    let (w, h) = (width as usize, height as usize);
+    let n = w * h;
+    // the energy a point adds at each (wrapped) offset from it
+    let weight: Vec<f32> = (0..n)
+        .map(|i| {
+            let (dx, dy) = (i % w, i / w);
+            let (dx, dy) = (dx.min(w - dx), dy.min(h - dy));
+            let d2 = (dx * dx + dy * dy) as f32;
+            (-d2 / (2.0 * BLUE_NOISE_SIGMA.powi(2))).exp()
+        })
+        .collect();
+    let toggle = |energy: &mut [f32], i: usize, sign: f32| {
+        let (x0, y0) = (i % w, i / w);
+        for (j, e) in energy.iter_mut().enumerate() {
+            let (dx, dy) = ((j % w + w - x0) % w, (j / w + h - y0) % h);
+            *e += sign * weight[dy * w + dx];
+        }
+    };
+    // the most crowded point that is set, or the emptiest one that isn't
+    let extreme = |energy: &[f32], on: &[bool], set: bool| {
+        let mut best: Option<usize> = None;
+        for i in (0..n).filter(|&i| on[i] == set) {
+            let better = match best {
+                None => true,
+                Some(b) if set => energy[i] > energy[b],
+                Some(b) => energy[i] < energy[b],
+            };
+            if better {
+                best = Some(i);
+            }
+        }
+        best.expect("No cells to choose from")
+    };
+
+    // start with a tenth of the cells set, chosen pseudo-randomly
+    let ones = (n / 10).max(1);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&i| fnv1a(&[seed.to_le_bytes(), (i as u64).to_le_bytes()].concat()));
+    let mut on = vec![false; n];
+    let mut energy = vec![0.0; n];
+    for &i in &order[..ones] {
+        on[i] = true;
+        toggle(&mut energy, i, 1.0);
+    }
+
+    // spread the initial points out by moving the most crowded into the
+    // emptiest space until that would move it back
+    for _ in 0..n {
+        let cluster = extreme(&energy, &on, true);
+        on[cluster] = false;
+        toggle(&mut energy, cluster, -1.0);
+        let void = extreme(&energy, &on, false);
+        on[void] = true;
+        toggle(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    // rank the initial points by removing the most crowded first...
+    let mut rank = vec![0; n];
+    let (initial, initial_energy) = (on.clone(), energy.clone());
+    for r in (0..ones).rev() {
+        let cluster = extreme(&energy, &on, true);
+        on[cluster] = false;
+        toggle(&mut energy, cluster, -1.0);
+        rank[cluster] = r as u32;
+    }
+    // ...and rank the rest by filling the emptiest space first
+    let (mut on, mut energy) = (initial, initial_energy);
+    for r in ones..n {
+        let void = extreme(&energy, &on, false);
+        on[void] = true;
+        toggle(&mut energy, void, 1.0);
+        rank[void] = r as u32;
+    }
+    rank
+}
+
+/// The quantization error carried between the cells of a grid which are
+/// matched to tiles one at a time, in row-major order (see
+/// [`MosaicOptions::error_diffusion`](crate::MosaicOptions::error_diffusion)).
+///
+/// The error of each cell (the difference between its color and the color
+/// of its tile) is spread over the cells after it with the Floyd–Steinberg
+/// weights: 7/16 to the right, and 3/16, 5/16, and 1/16 to the cells below
+/// left, below, and below right.
+#[derive(Debug)]
+pub(crate) struct ErrorDiffusion {
+    /// The fraction of the error of each cell which is carried on.
+    strength: f32,
+    /// The error carried to each cell of the current row.
+    current: Vec<[f32; 3]>,
+    /// The error carried to each cell of the next row.
+    next: Vec<[f32; 3]>,
+}
+
+impl ErrorDiffusion {
+    /// Start diffusing the error over a grid `columns` wide.
+    pub(crate) fn new(columns: u32, strength: f32) -> Self {
+        Self {
+            strength,
+            current: vec![[0.0; 3]; columns as usize],
+            next: vec![[0.0; 3]; columns as usize],
+        }
+    }
+
+    /// Add the error carried to the cell in column `x` of the current row
+    /// to each of its blocks, and get the color the cell should have (the
+    /// average of its blocks, plus the error).
+    pub(crate) fn adjust(&self, x: u32, blocks: &mut [Rgb<u8>]) -> [f32; 3] {
+        let error = self.current[x as usize];
+        let n = blocks.len() as f32;
+        let mut wanted = [0.0; 3];
+        for block in blocks.iter_mut() {
+            for (c, v) in block.0.iter_mut().enumerate() {
+                let adjusted = (*v as f32 + error[c]).clamp(0.0, 255.0);
+                wanted[c] += adjusted / n;
+                *v = adjusted.round() as u8;
+            }
+        }
+        wanted
+    }
+
+    /// Spread the error of the cell in column `x` of the current row, which
+    /// should have been `wanted` (see [`adjust`](ErrorDiffusion::adjust)),
+    /// but was matched to a tile of the color `chosen`.
+    pub(crate) fn record(&mut self, x: u32, wanted: [f32; 3], chosen: &Rgb<u8>) {
+        let x = x as usize;
+        let columns = self.current.len();
+        for (c, wanted) in wanted.iter().enumerate() {
+            let error = (wanted - chosen.0[c] as f32) * self.strength;
+            if x + 1 < columns {
+                self.current[x + 1][c] += error * 7.0 / 16.0;
+                self.next[x + 1][c] += error / 16.0;
+            }
+            if x > 0 {
+                self.next[x - 1][c] += error * 3.0 / 16.0;
+            }
+            self.next[x][c] += error * 5.0 / 16.0;
+        }
+    }
+
+    /// Move on to the next row of the grid.
+    pub(crate) fn next_row(&mut self) {
+        std::mem::swap(&mut self.current, &mut self.next);
+        self.next.fill([0.0; 3]);
+    }
+}
@@ -0,0 +1,362 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::cells::{cell_grid, CellColor};
+use crate::descriptor::Descriptor;
+use crate::filter::ResizeFilter;
+use crate::metric::Metric;
+use crate::mosaic::grid_size;
+use crate::preprocess::{Posterize, Preprocess};
+use crate::transform::Transform;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// Options controlling how pixels in the source image are
+/// assigned to [`Tile`](crate::Tile)s.
+///
+/// These are recorded in every [`MosaicPlan`](crate::MosaicPlan) so
+/// that a plan can be re-rendered (or audited) later.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MosaicOptions {
+    /// The color distance metric used to match pixels to tiles.
+    pub metric: Metric,
+    /// How cells of the source image and tiles are summarized to compare
+    /// them (see [`Mosaic::with_options`](crate::Mosaic::with_options)).
+    pub descriptor: Descriptor,
+    /// The seed for any randomized selection feature.
+    pub seed: u64,
+    /// The largest amount (per channel) by which the color of each cell
+    /// is perturbed before it is matched to a tile, so that smooth gradients
+    /// blend between two tiles over a band of cells rather than switching
+    /// from one to the other along a hard line. The perturbation is seeded
+    /// noise based on the [`seed`](MosaicOptions::seed) and the position of
+    /// each cell. With the default of `0`, cells are not perturbed.
+    pub dither: u8,
+    /// The fraction (from `0.0` to `1.0`) of the difference between the
+    /// color of each cell and the average color of the tile matched to it
+    /// which is carried on to the cells after it (to the right and in the
+    /// next row, with Floyd–Steinberg weights) before they are matched.
+    /// Where no tile matches a gradient closely, this alternates between
+    /// the tiles on either side of it in proportion, rather than leaving
+    /// bands of the same tile; it suits small tile sets. Cells are matched
+    /// one at a time (in row-major order), so as with the
+    /// [`usage_penalty`](MosaicOptions::usage_penalty), updating part of a
+    /// mosaic only diffuses the error within that part. This has no effect
+    /// with [`unique`](MosaicOptions::unique) tiles. With the default of
+    /// `0.0`, each cell is matched by its own color.
+    pub error_diffusion: f32,
+    /// How far from the best match a tile may be (as a factor of its
+    /// [score](crate::Tile::score)) to be chosen for a cell, in exchange
+    /// for searching large tile sets faster (see [`ApproxSearch`]). With
+    /// the default of `1.0`, every cell gets its best match. This only
+    /// applies to the [`Descriptor::Mean`] descriptor.
+    ///
+    /// [`ApproxSearch`]: crate::ApproxSearch
+    pub approx: f32,
+    /// An amount added to the score of each tile for every cell it has
+    /// already been placed in, to spread cells between several similar
+    /// tiles rather than repeating the best match. Cells are assigned
+    /// tiles one at a time (in row-major order), so each cell's tile
+    /// depends on those before it: with a usage penalty, updating part of
+    /// a mosaic with [`Mosaic::update_region`](crate::Mosaic::update_region)
+    /// only balances usage within that part. With the default of `0.0`,
+    /// every cell gets its best match.
+    pub usage_penalty: f32,
+    /// An amount added to the score of each tile for every nearby cell
+    /// (within the [`repeat_radius`](MosaicOptions::repeat_radius)) which
+    /// was matched to it before, so that a region of nearly constant color
+    /// rotates between several close matches rather than repeating the same
+    /// tile in a patch. Unlike the [`usage_penalty`](MosaicOptions::usage_penalty),
+    /// only nearby uses count, so a tile is never penalized for being used
+    /// in other parts of the mosaic. As with the usage penalty, cells are
+    /// matched one at a time (in row-major order), and updating part of a
+    /// mosaic only counts the uses within that part. With the default of
+    /// `0.0`, every cell gets its best match.
+    pub repeat_penalty: f32,
+    /// The distance (in cells, along each axis) within which the uses of a
+    /// tile count towards the [`repeat_penalty`](MosaicOptions::repeat_penalty).
+    /// Defaults to `2`, i.e., the cells matched before each cell in the 5x5
+    /// block around it.
+    pub repeat_radius: u32,
+    /// The number of closest tiles to pick from for each cell: rather than
+    /// always placing the best match, one of the `top_k` best matches is
+    /// picked pseudo-randomly (seeded by the [`seed`](MosaicOptions::seed)
+    /// and the position of the cell), for more variety in regions of
+    /// similar color. Penalties and category limits are applied before
+    /// ranking the tiles. With the default of `1`, every cell gets its best
+    /// match.
+    pub top_k: u32,
+    /// How much worse (using the [`metric`](MosaicOptions::metric)) than
+    /// the best match a tile may score and still be picked with a
+    /// [`top_k`](MosaicOptions::top_k) above `1`, so that a tile set with
+    /// only a few close matches for a color doesn't place poor ones. With
+    /// the default of `None`, any of the `top_k` best matches may be picked.
+    pub top_k_tolerance: Option<f32>,
+    /// Whether to use each tile in at most one cell (e.g., to use every
+    /// photo in a collection exactly once). Rather than assigning tiles one
+    /// cell at a time, which would let the first cells take all of the good
+    /// matches, the assignment minimizing the total score over all cells is
+    /// found at once; this takes time growing with the cube of the number of
+    /// cells, so it suits mosaics of up to a few thousand cells. Planning
+    /// panics if there are fewer tiles than cells. As with the
+    /// [`usage_penalty`](MosaicOptions::usage_penalty), updating part of a
+    /// mosaic only keeps tiles unique within that part.
+    pub unique: bool,
+    /// How much worse (as a factor of its [score](crate::Tile::score)) the
+    /// best tile outside of any full category may be than the best tile
+    /// overall before a category goes over its quota (see
+    /// [`TileSet::set_category_limit`](crate::TileSet::set_category_limit)).
+    /// For example, with `2.0`, a tile from a full category is still placed
+    /// if every other tile scores more than twice as badly. With the
+    /// default of `0.0`, quotas are never exceeded while any other tile
+    /// could be placed.
+    pub category_overflow: f32,
+    /// The largest distance (using the [`metric`](MosaicOptions::metric))
+    /// between the colors of cells in the same flat region: a group of
+    /// adjacent cells of nearly the same color. Within each flat region, the
+    /// tiles chosen for its cells are rearranged following a blue-noise
+    /// ranking of the cells (seeded by the [`seed`](MosaicOptions::seed)),
+    /// so that tiles used several times (e.g., with a
+    /// [`usage_penalty`](MosaicOptions::usage_penalty)) are spread evenly
+    /// rather than repeating in stripes. The number of cells using each tile
+    /// is unchanged. As with the usage penalty, updating part of a mosaic
+    /// only rearranges tiles within that part. With the default of `0.0`,
+    /// tiles stay in the cells they were matched to.
+    pub flat_shuffle: f32,
+    /// Pixels of a source image with an alpha channel that are less
+    /// opaque than this are treated as background: no tile is placed in
+    /// their cells, which are filled with the [`matte`](MosaicOptions::matte)
+    /// instead. With the default of `0`, every cell gets a tile.
+    pub alpha_threshold: u8,
+    /// The color (as `[r, g, b]`) that (semi-)transparent pixels of the
+    /// source image are composited over before they are matched to tiles,
+    /// and that background cells are filled with. Defaults to white.
+    pub matte: [u8; 3],
+    /// The fraction (from `0.0` to `1.0`) of the difference between the
+    /// color of each cell and the average color of its tile by which the
+    /// tile's pixels are shifted when it is placed, so that even a small
+    /// tile set can reproduce any color. The texture of each tile is kept,
+    /// but at `1.0`, tiles lose their own colors (up to clipping) and the
+    /// mosaic takes on the colors of the source. The color of each cell is
+    /// recorded in the [`MosaicPlan`](crate::MosaicPlan), so the tiles are
+    /// shifted by every way of rendering it. With the default of `0.0`,
+    /// tiles are placed as-is.
+    pub color_adjust: f32,
+    /// The opacity (from `0.0` to `1.0`) with which the colors of the cells
+    /// (i.e., the scaled source image), scaled up smoothly to the size of
+    /// the mosaic, are blended over the tiles as they are placed. Even a
+    /// little of this makes the subject of a mosaic (e.g., a face) much
+    /// easier to recognize, at the cost of washing out the tiles. As with
+    /// the [`color_adjust`](MosaicOptions::color_adjust), the colors of the
+    /// cells are recorded in the [`MosaicPlan`](crate::MosaicPlan); updating
+    /// part of a mosaic with
+    /// [`Mosaic::update_region`](crate::Mosaic::update_region) only blends
+    /// the colors of the cells within that part. With the default of `0.0`,
+    /// tiles are placed as-is.
+    pub overlay_strength: f32,
+    /// The rotation and/or flips applied to the source image before it
+    /// is scaled (see [`Mosaic::with_options`](crate::Mosaic::with_options)).
+    pub transform: Transform,
+    /// The side length (in pixels of the scaled source image) of the square
+    /// cells the source image is divided into, each of which is matched to
+    /// one tile. This sets the grid of the mosaic without scaling the source
+    /// image down to one pixel per cell first; the color of each cell is
+    /// chosen from its pixels as described by the
+    /// [`cell_color`](MosaicOptions::cell_color). The cells along the right
+    /// and bottom edges cover what is left of the image, so they may be
+    /// smaller. This is applied when the [`Mosaic`](crate::Mosaic) is
+    /// created, after the source image is scaled. With the default of `1`
+    /// (and `0`), each pixel is a cell.
+    pub cell_size: u32,
+    /// How the color of each cell is chosen from its pixels, with a
+    /// [`cell_size`](MosaicOptions::cell_size) above `1`.
+    pub cell_color: CellColor,
+    /// The adjustment applied to the source image after it is scaled and
+    /// before it is matched to tiles (see [`Preprocess`]). Like the
+    /// [`transform`](MosaicOptions::transform), this is applied when the
+    /// [`Mosaic`](crate::Mosaic) is created.
+    pub preprocess: Preprocess,
+    /// The factor by which the chroma of the source image is scaled before
+    /// it is matched to tiles (after the
+    /// [`preprocess`](MosaicOptions::preprocess) adjustment; see
+    /// [`saturate`](crate::saturate)). Mosaics tend to look less vivid than
+    /// their sources, since the average colors of tiles cluster around
+    /// grays, so values above `1.0` can make up for it. Like the
+    /// `preprocess` adjustment, this is applied when the
+    /// [`Mosaic`](crate::Mosaic) is created. With the default of `1.0`,
+    /// colors are unchanged.
+    pub saturation: f32,
+    /// The reduction of the source image to a few colors before it is
+    /// matched to tiles (after the [`saturation`](MosaicOptions::saturation)
+    /// adjustment; see [`Posterize`]). Like the other adjustments to the
+    /// source, this is applied when the [`Mosaic`](crate::Mosaic) is
+    /// created.
+    pub posterize: Posterize,
+    /// The strength of the unsharp mask applied to [`Tile`](crate::Tile)s
+    /// after they are scaled to the tile size (see
+    /// [`TileSet::scale_tiles_with`](crate::TileSet::scale_tiles_with)),
+    /// since shrinking them to a few pixels blurs them. This is applied when
+    /// the [`Mosaic`](crate::Mosaic) is created, and only to tiles which
+    /// need scaling. With the default of `0.0`, tiles are not sharpened.
+    pub tile_sharpen: f32,
+    /// The filter used to scale the source image (see
+    /// [`Mosaic::new`](crate::Mosaic::new)). This is applied when the
+    /// [`Mosaic`](crate::Mosaic) is created.
+    pub source_filter: ResizeFilter,
+    /// The filter used to scale [`Tile`](crate::Tile)s to the tile size
+    /// (see [`TileSet::scale_tiles_with`](crate::TileSet::scale_tiles_with)),
+    /// e.g., [`ResizeFilter::Nearest`] to keep the hard edges of pixel art.
+    /// This is applied when the [`Mosaic`](crate::Mosaic) is created.
+    pub tile_filter: ResizeFilter,
+}
+
+impl Default for MosaicOptions {
+    fn default() -> Self {
+        Self {
+            metric: Metric::default(),
+            descriptor: Descriptor::default(),
+            seed: 0,
+            dither: 0,
+            error_diffusion: 0.0,
+            approx: 1.0,
+            usage_penalty: 0.0,
+            repeat_penalty: 0.0,
+            repeat_radius: 2,
+            top_k: 1,
+            top_k_tolerance: None,
+            unique: false,
+            category_overflow: 0.0,
+            flat_shuffle: 0.0,
+            alpha_threshold: 0,
+            matte: [255, 255, 255],
+            color_adjust: 0.0,
+            overlay_strength: 0.0,
+            transform: Transform::default(),
+            cell_size: 1,
+            cell_color: CellColor::default(),
+            preprocess: Preprocess::default(),
+            saturation: 1.0,
+            posterize: Posterize::default(),
+            tile_sharpen: 0.0,
+            source_filter: ResizeFilter::default(),
+            tile_filter: ResizeFilter::default(),
+        }
+    }
+}
+
+impl MosaicOptions {
+    /// The largest number of cells in a mosaic (so the index of every cell
+    /// fits in a `u32`).
+    pub const MAX_CELLS: u64 = u32::MAX as u64;
+
+    /// Scaling factors below this shrink the source image so much that the
+    /// mosaic is unlikely to resemble it; they're allowed, but the `tilr`
+    /// binary warns about them.
+    pub const SMALL_SCALE: f32 = 0.1;
+
+    /// Check that a source image of the given dimensions (before it is
+    /// [transformed](MosaicOptions::transform)) can be scaled by
+    /// `img_scaling` to build a mosaic with these options (see
+    /// [`Mosaic::new`](crate::Mosaic::new)), and get the size of the grid
+    /// of cells of the mosaic (with the
+    /// [`cell_size`](MosaicOptions::cell_size)).
+    ///
+    /// Any positive scaling factor is allowed, as long as the scaled image
+    /// is at least one pixel wide and high, and has at most
+    /// [`MAX_CELLS`](MosaicOptions::MAX_CELLS) pixels.
+    ///
+    /// # Errors
+    /// This function returns a [`ScaleError`] if `img_scaling` is not a
+    /// positive, finite number, or if the scaled image would be too small
+    /// or too large.
+    pub fn validate(
+        &self,
+        src_dims: (u32, u32),
+        img_scaling: f32,
+    ) -> Result<(u32, u32), ScaleError> {
+        check_scale(self.transform.output_size(src_dims), img_scaling)
+            .map(|scaled| cell_grid(scaled, self.cell_size))
+    }
+}
+
+/// Why a source image can't be scaled to build a mosaic; see
+/// [`MosaicOptions::validate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScaleError {
+    /// The scaling factor is zero, negative, infinite, or NaN.
+    Invalid(f32),
+    /// The scaled image would have no pixels along at least one side.
+    Collapsed {
+        /// The dimensions of the (transformed) source image.
+        src: (u32, u32),
+        /// The scaling factor.
+        scale: f32,
+        /// The dimensions of the scaled image.
+        scaled: (u32, u32),
+    },
+    /// The scaled image would have more than
+    /// [`MAX_CELLS`](MosaicOptions::MAX_CELLS) pixels.
+    TooManyCells {
+        /// The dimensions of the scaled image.
+        scaled: (u32, u32),
+    },
+}
+
+impl fmt::Display for ScaleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScaleError::Invalid(scale) => {
+                write!(f, "Scaling factor must be positive and finite (got {})", scale)
+            }
+            ScaleError::Collapsed { src, scale, scaled } => write!(
+                f,
+                "Scaling a {}x{} image by {} results in a {}x{} image, with no pixels along at least one side",
+                src.0, src.1, scale, scaled.0, scaled.1
+            ),
+            ScaleError::TooManyCells { scaled } => write!(
+                f,
+                "Scaling results in a {}x{} image, which is more than {} cells",
+                scaled.0,
+                scaled.1,
+                MosaicOptions::MAX_CELLS
+            ),
+        }
+    }
+}
+
+impl Error for ScaleError {}
+
+/// Check that a source image with the given (transformed) dimensions can
+/// be scaled by `img_scaling`, and get its dimensions once scaled; see
+/// [`MosaicOptions::validate`].
+pub(crate) fn check_scale(src: (u32, u32), img_scaling: f32) -> Result<(u32, u32), ScaleError> {
+    if !(img_scaling.is_finite() && img_scaling > 0.0) {
+        return Err(ScaleError::Invalid(img_scaling));
+    }
+    let scaled = grid_size(src, img_scaling);
+    if scaled.0 == 0 || scaled.1 == 0 {
+        return Err(ScaleError::Collapsed {
+            src,
+            scale: img_scaling,
+            scaled,
+        });
+    }
+    if scaled.0 as u64 * scaled.1 as u64 > MosaicOptions::MAX_CELLS {
+        return Err(ScaleError::TooManyCells { scaled });
+    }
+    Ok(scaled)
+}
@@ -0,0 +1,405 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ExtendedColorType, ImageBuffer, ImageEncoder, ImageFormat, PixelWithColorType};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Cursor, Write};
+use std::path::Path;
+
+/// The number of centimeters in an inch.
+pub const CM_PER_INCH: f32 = 2.54;
+
+/// The physical size of an image when printed at a particular resolution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintSize {
+    /// The width of the printed image (in inches).
+    pub width_in: f32,
+    /// The height of the printed image (in inches).
+    pub height_in: f32,
+}
+
+impl PrintSize {
+    /// Get the size of an image of `(width, height)` pixels printed at `dpi`.
+    pub fn new((width, height): (u32, u32), dpi: f32) -> Self {
+        Self {
+            width_in: width as f32 / dpi,
+            height_in: height as f32 / dpi,
+        }
+    }
+
+    /// Get the width of the printed image (in centimeters).
+    pub fn width_cm(&self) -> f32 {
+        self.width_in * CM_PER_INCH
+    }
+
+    /// Get the height of the printed image (in centimeters).
+    pub fn height_cm(&self) -> f32 {
+        self.height_in * CM_PER_INCH
+    }
+}
+
+impl fmt::Display for PrintSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}in x {:.1}in ({:.1}cm x {:.1}cm)",
+            self.width_in,
+            self.height_in,
+            self.width_cm(),
+            self.height_cm()
+        )
+    }
+}
+
+/// Get the width (in pixels) an image must be to print `width_cm` wide at `dpi`.
+pub fn print_width_px(width_cm: f32, dpi: f32) -> u32 {
+    (width_cm / CM_PER_INCH * dpi).round() as u32
+}
+
+/// Get the scaling factor for the source image needed to build a mosaic
+/// which prints `width_cm` wide at `dpi` (see [`Mosaic::new`](crate::Mosaic::new)).
+///
+/// # Arguments
+/// * `src_width` - The width of the source image (in pixels).
+/// * `tile_size` - The side length of the tiles (in pixels).
+/// * `width_cm` - The desired width of the printed mosaic.
+/// * `dpi` - The resolution at which the mosaic will be printed.
+pub fn scale_for_print(src_width: u32, tile_size: u32, width_cm: f32, dpi: f32) -> f32 {
+    print_width_px(width_cm, dpi) as f32 / (src_width as f32 * tile_size as f32)
+}
+
+/// How much effort to spend compressing PNGs; see
+/// [`OutputOptions::png_compression`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngCompression {
+    /// Compress quickly, at the cost of larger files.
+    Fast,
+    /// Balance the time spent compressing against the size of the file.
+    #[default]
+    Default,
+    /// Make the smallest files, at the cost of compressing slowly (which
+    /// can take a while for large mosaics).
+    Best,
+}
+
+/// Options controlling how [`save_image_with`] encodes an image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputOptions {
+    /// The format to save the image in, or `None` (the default) for the
+    /// format given by the extension of the path.
+    pub format: Option<ImageFormat>,
+    /// The quality (from `1`, the worst, to `100`, the best) of JPEGs.
+    /// Higher qualities make larger files. The default is `75`.
+    pub jpeg_quality: u8,
+    /// How much effort to spend compressing PNGs (which are lossless, so
+    /// this only changes the size of the file and the time spent saving it).
+    pub png_compression: PngCompression,
+    /// The resolution to record in the metadata of the file, if any (so
+    /// that it prints at the intended size), for the formats which support
+    /// it: PNG (as a `pHYs` chunk) and JPEG (as the JFIF density). Other
+    /// formats are saved without it.
+    pub dpi: Option<f32>,
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: None,
+            jpeg_quality: 75,
+            png_compression: PngCompression::default(),
+            dpi: None,
+        }
+    }
+}
+
+impl OutputOptions {
+    /// Get the format an image saved at `path` with these options is
+    /// encoded in: the [`format`](OutputOptions::format), if one is given,
+    /// or the one given by the extension of `path`.
+    ///
+    /// # Errors
+    /// This function returns an error if no format is given and the
+    /// extension of `path` is not that of a supported format.
+    pub fn format_for(&self, path: &Path) -> Result<ImageFormat, Box<dyn Error>> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => Ok(ImageFormat::from_path(path)?),
+        }
+    }
+}
+
+/// Save an image at the given `path`, in the format given by its extension.
+///
+/// If `dpi` is given, the resolution is recorded in the metadata of the
+/// file (so that it prints at the intended size) for the formats which
+/// support it: PNG (as a `pHYs` chunk) and JPEG (as the JFIF density).
+/// Other formats are saved without it.
+///
+/// Both RGB images (e.g., from [`MosaicPlan::render`](crate::MosaicPlan::render))
+/// and grayscale images (e.g., from
+/// [`MosaicPlan::render_gray`](crate::MosaicPlan::render_gray)) can be saved.
+///
+/// This uses the default [`OutputOptions`] (with the given `dpi`); see
+/// [`save_image_with`].
+pub fn save_image<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    path: &Path,
+    dpi: Option<f32>,
+) -> Result<(), Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    let options = OutputOptions {
+        dpi,
+        ..Default::default()
+    };
+    save_image_with(img, path, &options)
+}
+
+/// Save an image at the given `path`, like [`save_image`], encoded as
+/// described by the given [`OutputOptions`] (e.g., as a JPEG of a certain
+/// quality, whatever the extension of `path`).
+pub fn save_image_with<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    path: &Path,
+    options: &OutputOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    let format = options.format_for(path)?;
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_image(img, format, options, &mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Save an image like [`save_image`], and get the SHA-256 hash (in hex) of
+/// the file, so that two runs can be checked to have produced exactly the
+/// same file without comparing the files themselves.
+///
+/// The hash is computed from the encoded bytes as they are written, rather
+/// than by reading the file back.
+pub fn save_image_hashed<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    path: &Path,
+    dpi: Option<f32>,
+) -> Result<String, Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    let options = OutputOptions {
+        dpi,
+        ..Default::default()
+    };
+    save_image_hashed_with(img, path, &options)
+}
+
+/// Save an image like [`save_image_with`], and get the SHA-256 hash (in
+/// hex) of the file (see [`save_image_hashed`]).
+pub fn save_image_hashed_with<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    path: &Path,
+    options: &OutputOptions,
+) -> Result<String, Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    let format = options.format_for(path)?;
+    let mut writer = BufWriter::new(HashingWriter::new(File::create(path)?));
+    write_image(img, format, options, &mut writer)?;
+    let (_, hash) = writer.into_inner().map_err(|e| e.into_error())?.finish();
+    Ok(hash)
+}
+
+/// Encode an image to `writer` (e.g., standard output), as
+/// [`save_image_with`] saves it to a file. Since there's no path to take the
+/// format from, the [`format`](OutputOptions::format) must be given.
+///
+/// # Errors
+/// This function returns an error if no format is given, or if the image
+/// cannot be encoded or written.
+pub fn write_image_with<P, W>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    writer: W,
+    options: &OutputOptions,
+) -> Result<(), Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+    W: Write,
+{
+    let format = options
+        .format
+        .ok_or("no format was given to encode the image in")?;
+    write_image(img, format, options, writer)
+}
+
+/// Get the size (in bytes) of the file [`save_image_with`] would save an
+/// image as in the given `format`, without saving it.
+///
+/// Encoding a sample of a large image this way gives an estimate of how
+/// large the whole image would be (see `tilr --dry-run`).
+pub fn encoded_size<P>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    format: ImageFormat,
+    options: &OutputOptions,
+) -> Result<u64, Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+{
+    let mut counter = CountingWriter(0);
+    write_image(img, format, options, &mut counter)?;
+    Ok(counter.0)
+}
+
+/// A writer which discards everything written to it, counting the bytes
+struct CountingWriter(u64);
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encode an image in the given `format` to `writer`, as described by the
+/// `options` (see [`save_image_with`]).
+fn write_image<P, W>(
+    img: &ImageBuffer<P, Vec<u8>>,
+    format: ImageFormat,
+    options: &OutputOptions,
+    mut writer: W,
+) -> Result<(), Box<dyn Error>>
+where
+    P: PixelWithColorType<Subpixel = u8>,
+    W: Write,
+{
+    match (format, options.dpi) {
+        (ImageFormat::Png, None) => {
+            let compression = match options.png_compression {
+                PngCompression::Fast => CompressionType::Fast,
+                PngCompression::Default => CompressionType::Default,
+                PngCompression::Best => CompressionType::Best,
+            };
+            PngEncoder::new_with_quality(writer, compression, FilterType::Adaptive).write_image(
+                img.as_raw(),
+                img.width(),
+                img.height(),
+                P::COLOR_TYPE,
+            )?;
+        }
+        (ImageFormat::Png, Some(dpi)) => {
+            let mut encoder = png::Encoder::new(writer, img.width(), img.height());
+            encoder.set_color(match P::COLOR_TYPE {
+                ExtendedColorType::L8 => png::ColorType::Grayscale,
+                ExtendedColorType::La8 => png::ColorType::GrayscaleAlpha,
+                ExtendedColorType::Rgba8 => png::ColorType::Rgba,
+                _ => png::ColorType::Rgb,
+            });
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.set_compression(match options.png_compression {
+                PngCompression::Fast => png::Compression::Fast,
+                PngCompression::Default => png::Compression::Default,
+                PngCompression::Best => png::Compression::Best,
+            });
+            let mut writer = encoder.write_header()?;
+
+            // pixels per meter along each axis, then the unit (1 = meters)
+            let ppm = (dpi / CM_PER_INCH * 100.0).round() as u32;
+            let mut phys = Vec::with_capacity(9);
+            phys.extend(ppm.to_be_bytes());
+            phys.extend(ppm.to_be_bytes());
+            phys.push(1);
+            writer.write_chunk(png::chunk::pHYs, &phys)?;
+
+            writer.write_image_data(img.as_raw())?;
+            writer.finish()?;
+        }
+        (ImageFormat::Jpeg, dpi) => {
+            let quality = options.jpeg_quality.clamp(1, 100);
+            let mut encoder = JpegEncoder::new_with_quality(&mut writer, quality);
+            if let Some(dpi) = dpi {
+                let density = dpi.round().clamp(1.0, u16::MAX as f32) as u16;
+                encoder.set_pixel_density(PixelDensity::dpi(density));
+            }
+            encoder.encode_image(img)?;
+        }
+        (format, _) => {
+            // some encoders (e.g., TIFF) seek back to fill in offsets, so
+            // they can't write to `writer` directly
+            let mut bytes = Cursor::new(Vec::new());
+            img.write_to(&mut bytes, format)?;
+            writer.write_all(bytes.get_ref())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// A writer which computes the SHA-256 hash of everything written through
+/// it (e.g., to hash a file as it is saved; see [`save_image_hashed`]).
+#[derive(Debug)]
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    /// Hash everything written to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Get the wrapped writer and the SHA-256 hash (in hex) of everything
+    /// written to it.
+    pub fn finish(self) -> (W, String) {
+        let hash = self.hasher.finalize();
+        let hex = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        (self.inner, hex)
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Check whether [`save_image`] records the resolution for the format
+/// given by the extension of `path`.
+pub fn records_dpi(path: &Path) -> bool {
+    matches!(
+        ImageFormat::from_path(path),
+        Ok(ImageFormat::Png | ImageFormat::Jpeg)
+    )
+}
@@ -0,0 +1,219 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Export mosaics as PDFs for printing.
+//!
+//! Rather than rasterizing the whole mosaic, each distinct [`Tile`](crate::Tile)
+//! used by a [`MosaicPlan`] is embedded in the PDF once (as an image XObject)
+//! and drawn in every cell it is assigned to. Since tiles repeat many times
+//! in a typical mosaic, this is much smaller than the equivalent raster
+//! image, and it can be printed at any size.
+
+use crate::plan::MosaicPlan;
+use crate::tiles::TileSet;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// The number of PDF points in a millimeter.
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+impl MosaicPlan {
+    /// Get the size (in millimeters) of the page used by
+    /// [`write_pdf`](MosaicPlan::write_pdf) for the given width.
+    ///
+    /// The height of the page is chosen to preserve the aspect
+    /// ratio of the mosaic.
+    pub fn pdf_page_size_mm(&self, width_mm: f32) -> (f32, f32) {
+        let (columns, rows) = self.grid_size();
+        (width_mm, width_mm * rows as f32 / columns as f32)
+    }
+
+    /// Write the mosaic described by this plan as a single-page PDF.
+    ///
+    /// # Arguments
+    /// * `tiles` - The [`TileSet`] used to build the plan.
+    /// * `width_mm` - The width of the page (in millimeters). See
+    ///   [`pdf_page_size_mm`](MosaicPlan::pdf_page_size_mm).
+    /// * `writer` - Where to write the PDF.
+    ///
+    /// # Errors
+    /// This function returns an error if `width_mm` is not positive,
+    /// or if writing the PDF fails.
+    ///
+    /// # Panics
+    /// This function panics if the set does not contain every [`Tile`](crate::Tile)
+    /// the plan refers to.
+    pub fn write_pdf<W: Write>(
+        &self,
+        tiles: &TileSet,
+        width_mm: f32,
+        writer: W,
+    ) -> Result<(), Box<dyn Error>> {
+        if !(width_mm.is_finite() && width_mm > 0.0) {
+            return Err(format!("Invalid page width: {}mm", width_mm).into());
+        }
+
+        let (columns, _) = self.grid_size();
+        let (width_mm, height_mm) = self.pdf_page_size_mm(width_mm);
+        let page_w = width_mm as f64 * POINTS_PER_MM;
+        let page_h = height_mm as f64 * POINTS_PER_MM;
+        let cell = page_w / columns as f64;
+
+        // name each distinct tile used by the plan; objects 1-4 are the
+        // catalog, page tree, page, and content stream
+        let used: BTreeSet<usize> = (self.cells().iter().enumerate())
+            .filter(|(i, _)| !self.is_skipped_at(*i))
+            .map(|(_, &idx)| idx)
+            .collect();
+        let names: BTreeMap<usize, usize> = used.into_iter().zip(0..).collect();
+
+        let mut content = String::new();
+        for (i, &idx) in self.cells().iter().enumerate() {
+            let x = (i as u32 % columns) as f64 * cell;
+            let y = page_h - ((i as u32 / columns) + 1) as f64 * cell;
+            if self.is_skipped_at(i) {
+                let [r, g, b] = self.options().matte.map(|c| c as f64 / 255.0);
+                content += &format!(
+                    "q {:.4} {:.4} {:.4} rg {:.4} {:.4} {:.4} {:.4} re f Q\n",
+                    r, g, b, x, y, cell, cell
+                );
+                continue;
+            }
+            content += &format!(
+                "q {:.4} 0 0 {:.4} {:.4} {:.4} cm /Im{} Do Q\n",
+                cell, cell, x, y, names[&idx]
+            );
+        }
+
+        let mut pdf = PdfWriter::new(writer)?;
+        pdf.object(1, b"<< /Type /Catalog /Pages 2 0 R >>")?;
+        pdf.object(2, b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>")?;
+
+        let xobjects: String = names
+            .values()
+            .map(|n| format!("/Im{} {} 0 R ", n, n + 5))
+            .collect();
+        let page = format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.4} {:.4}] \
+             /Resources << /XObject << {}>> >> /Contents 4 0 R >>",
+            page_w, page_h, xobjects
+        );
+        pdf.object(3, page.as_bytes())?;
+        pdf.stream(4, "", content.as_bytes())?;
+
+        let tile_size = self.tile_size();
+        for (&idx, &n) in &names {
+            let tile = tiles.get(idx).expect("No tile for cell");
+            let dict = format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8 ",
+                tile_size, tile_size
+            );
+            pdf.stream(n + 5, &dict, tile.img().as_raw())?;
+        }
+
+        pdf.finish()
+    }
+
+    /// Save the mosaic described by this plan as a PDF at the given `path`.
+    ///
+    /// See [`write_pdf`](MosaicPlan::write_pdf).
+    pub fn save_pdf(
+        &self,
+        tiles: &TileSet,
+        width_mm: f32,
+        path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        self.write_pdf(tiles, width_mm, writer)
+    }
+}
+
+/// Writes the objects of a PDF, keeping track of where each one starts.
+struct PdfWriter<W: Write> {
+    inner: W,
+    /// The number of bytes written so far.
+    offset: usize,
+    /// The offset of each object, indexed by object number (minus one).
+    objects: Vec<usize>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    /// Start a new PDF by writing its header.
+    fn new(inner: W) -> Result<Self, Box<dyn Error>> {
+        let mut pdf = Self {
+            inner,
+            offset: 0,
+            objects: Vec::new(),
+        };
+        // the binary comment marks the file as containing binary data
+        pdf.write(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n")?;
+        Ok(pdf)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Box<dyn Error>> {
+        self.inner.write_all(bytes)?;
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+    /// Write an object; objects must be written in order.
+    fn object(&mut self, num: usize, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        debug_assert_eq!(num, self.objects.len() + 1);
+        self.objects.push(self.offset);
+        self.write(format!("{} 0 obj\n", num).as_bytes())?;
+        self.write(body)?;
+        self.write(b"\nendobj\n")
+    }
+
+    /// Write a (compressed) stream object with the given dictionary entries.
+    fn stream(&mut self, num: usize, dict: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let data = encoder.finish()?;
+
+        let mut body = format!(
+            "<< {}/Filter /FlateDecode /Length {} >>\nstream\n",
+            dict,
+            data.len()
+        )
+        .into_bytes();
+        body.extend(data);
+        body.extend(b"\nendstream");
+        self.object(num, &body)
+    }
+
+    /// Write the cross-reference table and trailer.
+    fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        let xref = self.offset;
+        let size = self.objects.len() + 1;
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", size);
+        for offset in &self.objects {
+            table += &format!("{:010} 00000 n \n", offset);
+        }
+        table += &format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            size, xref
+        );
+        self.write(table.as_bytes())?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1303 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::cache::MapCache;
+use crate::coverage::heat_color;
+use crate::descriptor::{detail_image, Descriptor};
+use crate::error::check_cancelled;
+use crate::eta::Eta;
+use crate::options::MosaicOptions;
+use crate::progress::{Phase, Progress, ProgressHook};
+use crate::quality::block_average;
+use crate::rect::Rect;
+use crate::shuffle::shuffle_flat;
+use crate::stats::PlanStats;
+use crate::tiles::{Tile, TileSet};
+use image::imageops::{self, FilterType};
+use image::{
+    GenericImage, GenericImageView, GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage, Rgba,
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::{Borrow, Cow};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+/// Everything needed to deterministically render a [`Mosaic`](crate::Mosaic).
+///
+/// A plan records the cell grid of the mosaic, the side length of the
+/// [`Tile`]s, the [`Tile`] assigned to each cell (and how closely it
+/// matches), and the options used to make those assignments. Plans are cheap compared to the rendered
+/// image, so they can be saved alongside the output (see
+/// [`save`](MosaicPlan::save)) and rendered again later with
+/// [`render`](MosaicPlan::render).
+///
+/// Plans are validated when they are deserialized: the number of cells
+/// must match the grid dimensions and every cell must refer to a
+/// [`Tile`] recorded in the plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "RawPlan")]
+pub struct MosaicPlan {
+    /// The number of cells in each row of the mosaic.
+    columns: u32,
+    /// The number of cells in each column of the mosaic.
+    rows: u32,
+    /// The side length (in pixels) of the [`Tile`]s in the mosaic.
+    tile_size: u32,
+    /// The options used to assign [`Tile`]s to cells.
+    options: MosaicOptions,
+    /// Identifiers for each [`Tile`] in the [`TileSet`] used to build
+    /// this plan; cells refer to tiles by their index in this list.
+    tiles: Vec<TileRef>,
+    /// The index of the [`Tile`] assigned to each cell, in row-major order.
+    cells: Vec<usize>,
+    /// Whether each cell is background (i.e., filled with the matte
+    /// color instead of its [`Tile`]), in row-major order. Empty if no
+    /// cells are background.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    skipped: Vec<bool>,
+    /// The distance (using the options' metric) from the color of each
+    /// cell to the average color of its [`Tile`], in row-major order.
+    /// Empty for plans saved before distances were recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    distances: Vec<f32>,
+    /// The color of each cell (as `[r, g, b]`), in row-major order, which
+    /// [`Tile`]s are shifted towards (see
+    /// [`color_adjust`](MosaicOptions::color_adjust)) or blended with (see
+    /// [`overlay_strength`](MosaicOptions::overlay_strength)) when they are
+    /// placed. Empty unless the options do either.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    targets: Vec<[u8; 3]>,
+    /// The SHA-256 hash (in hex) of the file the mosaic was rendered to,
+    /// if it has been recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_sha256: Option<String>,
+    /// The callback to report progress to while rendering, if any.
+    #[serde(skip)]
+    progress: ProgressHook,
+}
+
+/// A cell of a mosaic, as passed to the hook given to
+/// [`MosaicPlan::render_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    /// The column of the cell in the grid.
+    pub x: u32,
+    /// The row of the cell in the grid.
+    pub y: u32,
+    /// The color the cell is meant to show (i.e., the color of the source
+    /// image at the cell).
+    pub target: Rgb<u8>,
+    /// The index of the [`Tile`] assigned to the cell in the [`TileSet`].
+    pub tile: usize,
+}
+
+/// Identifies a single [`Tile`] referenced by a [`MosaicPlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileRef {
+    /// A hash of the [`Tile`]'s pixels (as a hex string).
+    pub hash: String,
+    /// The [category](Tile::category) of the [`Tile`], if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// The name of (or path to) the image the [`Tile`] was loaded from,
+    /// if it's known (see [`Tile::source`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+}
+
+impl TileRef {
+    /// Check whether this refers to the same [`Tile`] as `other`: one with
+    /// the same pixels and category, wherever it was loaded from.
+    pub fn same_tile(&self, other: &TileRef) -> bool {
+        self.hash == other.hash && self.category == other.category
+    }
+}
+
+impl From<&Tile> for TileRef {
+    fn from(tile: &Tile) -> Self {
+        Self {
+            hash: format!("{:016x}", tile.content_hash()),
+            category: tile.category().map(str::to_string),
+            source: tile.source().map(Path::to_path_buf),
+        }
+    }
+}
+
+impl MosaicPlan {
+    /// Build a new plan from a grid of [`Tile`] assignments, where each
+    /// pixel of `colors` is the color of the corresponding cell.
+    pub(crate) fn new(
+        colors: &RgbImage,
+        tiles: &TileSet,
+        options: MosaicOptions,
+        cells: Vec<usize>,
+    ) -> Self {
+        let (columns, rows) = colors.dimensions();
+        debug_assert_eq!(cells.len(), (columns * rows) as usize);
+        let distances = colors
+            .pixels()
+            .zip(&cells)
+            .map(|(px, &idx)| {
+                let tile = tiles.get(idx).expect("No tile for cell");
+                options.metric.distance(px, tile.avg())
+            })
+            .collect();
+        let targets = match options.color_adjust > 0.0 || options.overlay_strength > 0.0 {
+            true => colors.pixels().map(|px| px.0).collect(),
+            false => Vec::new(),
+        };
+        Self {
+            columns,
+            rows,
+            tile_size: tiles.tile_side_len(),
+            options,
+            tiles: tiles.iter().map(TileRef::from).collect(),
+            cells,
+            skipped: Vec::new(),
+            distances,
+            targets,
+            output_sha256: None,
+            progress: ProgressHook::default(),
+        }
+    }
+
+    /// Mark the cells whose pixels in `alpha` are less opaque than the
+    /// [`alpha_threshold`](MosaicOptions::alpha_threshold) as background.
+    pub(crate) fn skip_transparent(&mut self, alpha: &GrayImage) {
+        debug_assert_eq!(alpha.dimensions(), self.grid_size());
+        let threshold = self.options.alpha_threshold;
+        if alpha.pixels().any(|a| a.0[0] < threshold) {
+            self.skipped = alpha.pixels().map(|a| a.0[0] < threshold).collect();
+        }
+    }
+
+    /// Assign a [`Tile`] from the given set to each pixel of an image.
+    ///
+    /// This is the planner used by [`Mosaic::plan`](crate::Mosaic::plan),
+    /// applied to an arbitrary image (e.g., the image of a [`Tile`], to
+    /// build a mosaic of a tile). The image is not scaled; each of its
+    /// pixels becomes one cell in the plan.
+    ///
+    /// With a [`descriptor`](MosaicOptions::descriptor) which compares
+    /// blocks of each cell (e.g., [`Descriptor::Quadrants`]), the blocks
+    /// are taken from the image scaled up by the descriptor's
+    /// [grid size](Descriptor::grid_size) (i.e., from the neighborhood of
+    /// each pixel).
+    pub fn for_image(img: &RgbImage, tiles: &TileSet, options: MosaicOptions) -> Self {
+        Self::for_region(img, tiles, options, (0, 0), None, None)
+            .expect("Planning without a cancellation flag can't be cancelled")
+    }
+
+    /// Assign a [`Tile`] from the given set to each pixel of an image, like
+    /// [`for_image`](MosaicPlan::for_image), where the image is part of a
+    /// larger one with its top left corner at `origin`.
+    ///
+    /// The origin only matters when [dithering](MosaicOptions::dither) or
+    /// [shuffling](MosaicOptions::flat_shuffle) flat regions, so that each
+    /// cell is treated the same way however the image is split.
+    ///
+    /// With `labels` (one per pixel), each pixel is only matched against
+    /// the [`Tile`]s with the same [label](crate::Tile::label).
+    ///
+    /// If `cancel` is set before every pixel is matched, this returns
+    /// [`Error::Cancelled`](crate::Error::Cancelled).
+    pub(crate) fn for_region(
+        img: &RgbImage,
+        tiles: &TileSet,
+        options: MosaicOptions,
+        origin: (u32, u32),
+        labels: Option<&GrayImage>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Self, crate::Error> {
+        let n = options.descriptor.grid_size();
+        if n > 1 {
+            let detail = detail_image(img, img.dimensions(), n);
+            return Self::for_detail(&detail, tiles, options, origin, labels, cancel);
+        }
+        if options.descriptor != Descriptor::Mean
+            || options.dither > 0
+            || options.error_diffusion > 0.0
+            || options.usage_penalty > 0.0
+            || options.repeat_penalty > 0.0
+            || options.top_k > 1
+            || options.unique
+            || options.flat_shuffle > 0.0
+            || labels.is_some()
+            || !tiles.category_limits().is_empty()
+        {
+            return Self::for_detail(img, tiles, options, origin, labels, cancel);
+        }
+
+        let map = tiles.map_to(img, options.metric, options.approx, cancel)?;
+        let cells = img.pixels().map(|px| map[px]).collect();
+
+        Ok(Self::new(img, tiles, options, cells))
+    }
+
+    /// Assign a [`Tile`] from the given set to each cell of an image, where
+    /// each cell is an `n` x `n` block of pixels of `detail` (with `n` the
+    /// [grid size](Descriptor::grid_size) of the options' descriptor), and
+    /// the top left cell is at `origin` in the whole mosaic. With `labels`
+    /// and `cancel`, see [`for_region`](MosaicPlan::for_region).
+    pub(crate) fn for_detail(
+        detail: &RgbImage,
+        tiles: &TileSet,
+        options: MosaicOptions,
+        origin: (u32, u32),
+        labels: Option<&GrayImage>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Self, crate::Error> {
+        let n = options.descriptor.grid_size();
+        let mut cells = tiles.map_descriptors(detail, &options, origin, labels, cancel)?;
+        let colors = match n {
+            1 => Cow::Borrowed(detail),
+            n => Cow::Owned(block_average(detail, n)),
+        };
+        if options.flat_shuffle > 0.0 {
+            shuffle_flat(&mut cells, &colors, &options, origin);
+        }
+
+        Ok(Self::new(&colors, tiles, options, cells))
+    }
+
+    /// Assign a [`Tile`] from the given set to each pixel of an image,
+    /// looking up (and recording) the closest [`Tile`] to each color in
+    /// a [`MapCache`].
+    ///
+    /// The result is the same as [`for_image`](MosaicPlan::for_image). If
+    /// the cache was built for a different [`TileSet`], metric, or
+    /// descriptor, it is cleared before it is used. The cache only records
+    /// single colors, so it is not used with a
+    /// [`descriptor`](MosaicOptions::descriptor) which compares blocks of
+    /// each cell (e.g., [`Descriptor::Quadrants`]), when
+    /// [dithering](MosaicOptions::dither), [diffusing
+    /// error](MosaicOptions::error_diffusion), or penalizing
+    /// [usage](MosaicOptions::usage_penalty) or
+    /// [repetition](MosaicOptions::repeat_penalty), when tiles are
+    /// [unique](MosaicOptions::unique) or
+    /// [shuffled](MosaicOptions::flat_shuffle), when
+    /// [approximating](MosaicOptions::approx) matches, or when categories of
+    /// tiles are [limited](TileSet::set_category_limit).
+    pub fn for_image_cached(
+        img: &RgbImage,
+        tiles: &TileSet,
+        options: MosaicOptions,
+        cache: &mut MapCache,
+    ) -> Self {
+        if options.descriptor.grid_size() > 1
+            || options.dither > 0
+            || options.error_diffusion > 0.0
+            || options.usage_penalty > 0.0
+            || options.repeat_penalty > 0.0
+            || options.top_k > 1
+            || options.unique
+            || options.flat_shuffle > 0.0
+            || options.approx > 1.0
+            || !tiles.category_limits().is_empty()
+        {
+            return Self::for_image(img, tiles, options);
+        }
+        if cache.descriptor() != options.descriptor || !cache.matches(tiles, options.metric) {
+            *cache = MapCache::for_descriptor(tiles, options.metric, options.descriptor);
+        }
+        let cells = img
+            .pixels()
+            .map(|px| cache.closest_tile(tiles, px, options.metric))
+            .collect();
+
+        Self::new(img, tiles, options, cells)
+    }
+
+    /// Get the dimensions of the cell grid as `(columns, rows)`.
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.columns, self.rows)
+    }
+
+    /// Get the side length (in pixels) of the [`Tile`]s in the mosaic.
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    /// Get the options used to build this plan.
+    pub fn options(&self) -> &MosaicOptions {
+        &self.options
+    }
+
+    /// Get the identifiers of the [`Tile`]s referenced by this plan.
+    pub fn tiles(&self) -> &[TileRef] {
+        &self.tiles
+    }
+
+    /// Get the index of the [`Tile`] assigned to each cell, in row-major order.
+    pub fn cells(&self) -> &[usize] {
+        &self.cells
+    }
+
+    /// Get the distance from the color of each cell to the average color
+    /// of its [`Tile`] (using the options' [`metric`](MosaicOptions::metric)),
+    /// in row-major order.
+    ///
+    /// This is empty for plans loaded from files saved before distances
+    /// were recorded.
+    pub fn distances(&self) -> &[f32] {
+        &self.distances
+    }
+
+    /// Get the SHA-256 hash (in hex) of the file the mosaic was rendered
+    /// to, if it has been recorded (see
+    /// [`set_output_sha256`](MosaicPlan::set_output_sha256)).
+    pub fn output_sha256(&self) -> Option<&str> {
+        self.output_sha256.as_deref()
+    }
+
+    /// Record the SHA-256 hash (in hex) of the file the mosaic was rendered
+    /// to (e.g., as returned by [`save_image_hashed`](crate::save_image_hashed)),
+    /// so that anyone rebuilding the mosaic from this plan can check that
+    /// they produced exactly the same file.
+    pub fn set_output_sha256(&mut self, hash: impl Into<String>) {
+        self.output_sha256 = Some(hash.into());
+    }
+
+    /// Summarize how this plan uses its [`Tile`]s (e.g., which are used
+    /// most, and how closely they match), listing the `top` most used.
+    pub fn stats(&self, top: usize) -> PlanStats {
+        PlanStats::new(self, top)
+    }
+
+    /// Get the index of the [`Tile`] assigned to the cell at `(x, y)`.
+    pub fn tile_at(&self, x: u32, y: u32) -> usize {
+        self.cells[(y * self.columns + x) as usize]
+    }
+
+    /// List the [`Tile`] placed in each cell, as `(x, y, tile)` where `tile`
+    /// is the index of the [`Tile`] in the [`TileSet`], in row-major order.
+    /// Background cells have no tile, and are left out.
+    pub fn placements(&self) -> impl Iterator<Item = (u32, u32, usize)> + '_ {
+        (0..self.rows)
+            .flat_map(move |y| (0..self.columns).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.is_skipped(x, y))
+            .map(|(x, y)| (x, y, self.tile_at(x, y)))
+    }
+
+    /// Check whether the cell at `(x, y)` is background, i.e., it is
+    /// filled with the [`matte`](MosaicOptions::matte) color rather than
+    /// its [`Tile`] when the plan is rendered.
+    pub fn is_skipped(&self, x: u32, y: u32) -> bool {
+        self.is_skipped_at((y * self.columns + x) as usize)
+    }
+
+    /// Check whether the cell at the given (row-major) index is background.
+    pub(crate) fn is_skipped_at(&self, i: usize) -> bool {
+        self.skipped.get(i).copied().unwrap_or(false)
+    }
+
+    /// Get the size (in pixels) of the rendered mosaic.
+    pub fn output_size(&self) -> (u32, u32) {
+        (self.columns * self.tile_size, self.rows * self.tile_size)
+    }
+
+    /// Report the progress of rendering this plan to `callback`, which is
+    /// called after each cell is placed by [`render`](MosaicPlan::render)
+    /// (and the other methods which render the whole plan, or a
+    /// [window](MosaicPlan::render_window) of it). This replaces any
+    /// callback set before, including the one set on the
+    /// [`Mosaic`](crate::Mosaic) the plan was built by.
+    ///
+    /// The callback is not saved with the plan, and is shared by its clones.
+    pub fn on_progress(&mut self, callback: impl FnMut(Progress) + Send + 'static) {
+        self.progress = ProgressHook::new(callback);
+    }
+
+    /// Report progress to the given callback (see
+    /// [`on_progress`](MosaicPlan::on_progress)).
+    pub(crate) fn set_progress(&mut self, progress: ProgressHook) {
+        self.progress = progress;
+    }
+
+    /// Render the mosaic described by this plan using the given [`TileSet`].
+    ///
+    /// The [`TileSet`] should be the same one used to build the plan;
+    /// see [`matches`](MosaicPlan::matches).
+    ///
+    /// # Panics
+    /// This function panics if the side length of the [`Tile`]s in the
+    /// set does not match the plan, or if the set does not contain every
+    /// [`Tile`] the plan refers to.
+    pub fn render(&self, tiles: &TileSet) -> RgbImage {
+        self.render_until(tiles, None).0
+    }
+
+    /// Render the mosaic described by this plan into `output`, like
+    /// [`render`](MosaicPlan::render), reusing its buffer rather than
+    /// allocating a new one (e.g., to render many plans of the same size).
+    /// Every pixel of `output` is overwritten.
+    ///
+    /// # Panics
+    /// This function panics for the same reasons as [`render`](MosaicPlan::render),
+    /// or if `output` is not the size given by
+    /// [`output_size`](MosaicPlan::output_size).
+    pub fn render_into(&self, tiles: &TileSet, output: &mut RgbImage) {
+        if output.dimensions() != self.output_size() {
+            panic!("Output image does not match the size of the mosaic");
+        }
+        self.render_into_until(tiles, output, None);
+    }
+
+    /// Render the mosaic described by this plan, like
+    /// [`render`](MosaicPlan::render), stopping early if `cancel` is set
+    /// (e.g., from another thread). It's checked before each cell is placed.
+    ///
+    /// # Errors
+    /// This function returns [`Error::Cancelled`](crate::Error::Cancelled)
+    /// if `cancel` is set before every cell has been placed.
+    ///
+    /// # Panics
+    /// See [`render`](MosaicPlan::render).
+    pub fn render_cancellable(
+        &self,
+        tiles: &TileSet,
+        cancel: &AtomicBool,
+    ) -> Result<RgbImage, crate::Error> {
+        match self.render_until(tiles, Some(cancel)) {
+            (img, true) => Ok(img),
+            (_, false) => Err(crate::Error::Cancelled),
+        }
+    }
+
+    /// Render the mosaic described by this plan until `cancel` is set, and
+    /// check whether every cell was placed.
+    fn render_until(&self, tiles: &TileSet, cancel: Option<&AtomicBool>) -> (RgbImage, bool) {
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = RgbImage::new(mos_x, mos_y);
+        let done = self.render_into_until(tiles, &mut mosaic, cancel);
+        (mosaic, done)
+    }
+
+    /// Render the mosaic described by this plan into `output`, which is
+    /// already the size of the mosaic, until `cancel` is set, and check
+    /// whether every cell was placed.
+    fn render_into_until(
+        &self,
+        tiles: &TileSet,
+        output: &mut RgbImage,
+        cancel: Option<&AtomicBool>,
+    ) -> bool {
+        let mut mosaic = Inner(std::mem::take(output));
+        let tile_img = |idx, _| tiles.get(idx).expect("No tile for cell").pixels();
+        let matte = Rgb(self.options.matte);
+        let done = self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            self.bounds(),
+            true,
+            cancel,
+        );
+        *output = mosaic.0;
+        done
+    }
+
+    /// Render the mosaic described by this plan as a grayscale image,
+    /// like [`render`](MosaicPlan::render).
+    ///
+    /// This uses a third of the memory of [`render`](MosaicPlan::render),
+    /// so it is the better choice when every [`Tile`] is already gray (see
+    /// [`TileSet::is_grayscale`]); in that case, the result holds the same
+    /// values as each channel of [`render`](MosaicPlan::render). Otherwise,
+    /// the [`Tile`]s are converted to grayscale as they are placed.
+    ///
+    /// # Panics
+    /// See [`render`](MosaicPlan::render).
+    pub fn render_gray(&self, tiles: &TileSet) -> GrayImage {
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = Inner(GrayImage::new(mos_x, mos_y));
+        let grays: Vec<GrayImage> = tiles.iter().map(Tile::luma).collect();
+        let tile_img = |idx, _| Cow::Borrowed(grays.get(idx).expect("No tile for cell"));
+        let matte = Rgb(self.options.matte).to_luma();
+        self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            self.bounds(),
+            true,
+            None,
+        );
+
+        mosaic.0
+    }
+
+    /// Render the mosaic described by this plan with an alpha channel,
+    /// like [`render`](MosaicPlan::render).
+    ///
+    /// [`Tile`]s which keep their transparency (see
+    /// [`TileSet::with_alpha`]) are placed with it, and background cells
+    /// (see [`alpha_threshold`](MosaicOptions::alpha_threshold)) are left
+    /// fully transparent rather than filled with the
+    /// [`matte`](MosaicOptions::matte); everything else is opaque.
+    ///
+    /// # Panics
+    /// See [`render`](MosaicPlan::render).
+    pub fn render_rgba(&self, tiles: &TileSet) -> RgbaImage {
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = Inner(RgbaImage::new(mos_x, mos_y));
+        let tile_img = |idx, _| Cow::Owned(tiles.get(idx).expect("No tile for cell").rgba());
+        self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            Rgba([0, 0, 0, 0]),
+            &mut mosaic,
+            (0, 0),
+            self.bounds(),
+            true,
+            None,
+        );
+        mosaic.0
+    }
+
+    /// Render the mosaic described by this plan, like
+    /// [`render`](MosaicPlan::render), passing the pixels of each [`Tile`]
+    /// through `hook` just before it is placed.
+    ///
+    /// The hook is called once for each cell (other than background cells),
+    /// with the [`Cell`] and the pixels of its [`Tile`], and returns the
+    /// pixels to place in the cell: either the same pixels (borrowed) to
+    /// place the [`Tile`] as-is, or a modified copy (e.g., tinted towards
+    /// the cell's target color). `source` gives the target color of each
+    /// cell, e.g., the (scaled) source image the plan was built from (see
+    /// [`Mosaic::render_with`](crate::Mosaic::render_with)).
+    ///
+    /// # Panics
+    /// This function panics for the same reasons as [`render`](MosaicPlan::render),
+    /// if `source` is not the size of the plan's grid, or if the hook
+    /// returns an image which is not the size of a [`Tile`].
+    pub fn render_with<F>(&self, tiles: &TileSet, source: &RgbImage, hook: F) -> RgbImage
+    where
+        F: for<'t> Fn(&Cell, &'t RgbImage) -> Cow<'t, RgbImage> + Sync,
+    {
+        if source.dimensions() != self.grid_size() {
+            panic!("Source image does not match the size of the plan's grid");
+        }
+
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = Inner(RgbImage::new(mos_x, mos_y));
+        let tile_img = |idx, (x, y)| {
+            let cell = Cell {
+                x,
+                y,
+                target: *source.get_pixel(x, y),
+                tile: idx,
+            };
+            let img = match tiles.get(idx).expect("No tile for cell").img() {
+                Cow::Borrowed(img) => hook(&cell, img),
+                // copied out of a TileStore, so the hook can't lend it back
+                Cow::Owned(img) => Cow::Owned(hook(&cell, &img).into_owned()),
+            };
+            if img.dimensions() != (self.tile_size, self.tile_size) {
+                panic!(
+                    "Hook returned a {}x{} image for a {}px tile",
+                    img.width(),
+                    img.height(),
+                    self.tile_size
+                );
+            }
+            img
+        };
+        let matte = Rgb(self.options.matte);
+        self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            self.bounds(),
+            true,
+            None,
+        );
+
+        mosaic.0
+    }
+
+    /// Render a flat-color preview of the mosaic described by this plan.
+    ///
+    /// The preview has one pixel per cell, colored with the average color
+    /// of the [`Tile`] assigned to that cell. This is much faster than
+    /// [`render`](MosaicPlan::render), since no tiles are drawn.
+    ///
+    /// # Panics
+    /// This function panics if the set does not contain every [`Tile`]
+    /// the plan refers to.
+    pub fn render_averages(&self, tiles: &TileSet) -> RgbImage {
+        RgbImage::from_fn(self.columns, self.rows, |x, y| {
+            if self.is_skipped(x, y) {
+                return Rgb(self.options.matte);
+            }
+            let tile = tiles.get(self.tile_at(x, y)).expect("No tile for cell");
+            *tile.avg()
+        })
+    }
+
+    /// Render a flat-color preview of the mosaic described by this plan,
+    /// like [`render_averages`](MosaicPlan::render_averages), downscaled
+    /// (if needed) so that its longest side is at most `max_dim` pixels.
+    ///
+    /// The preview is downscaled by sampling the nearest cell, so every
+    /// pixel is still the average color of a [`Tile`] (or the
+    /// [`matte`](MosaicOptions::matte), for background cells).
+    ///
+    /// # Panics
+    /// This function panics if `max_dim` is `0`, or if the set does not
+    /// contain every [`Tile`] the plan refers to.
+    pub fn preview(&self, tiles: &TileSet, max_dim: u32) -> RgbImage {
+        if max_dim == 0 {
+            panic!("A preview must be at least one pixel wide and high");
+        }
+        let averages = self.render_averages(tiles);
+        let longest = self.columns.max(self.rows);
+        if longest <= max_dim {
+            return averages;
+        }
+        let fit = |len: u32| ((len as u64 * max_dim as u64 / longest as u64) as u32).max(1);
+        imageops::resize(
+            &averages,
+            fit(self.columns),
+            fit(self.rows),
+            FilterType::Nearest,
+        )
+    }
+
+    /// Render the mosaic described by this plan as flat blocks of color:
+    /// each cell becomes a `block_size` x `block_size` square of the
+    /// average color of its [`Tile`] (as in
+    /// [`render_averages`](MosaicPlan::render_averages)).
+    ///
+    /// This is a "paint by numbers" version of the mosaic, e.g., to plan a
+    /// physical mosaic, with the [`Tile`] for each block listed by
+    /// [`write_csv`](MosaicPlan::write_csv).
+    ///
+    /// # Panics
+    /// This function panics if `block_size` is `0`, or if the set does not
+    /// contain every [`Tile`] the plan refers to.
+    pub fn render_blocks(&self, tiles: &TileSet, block_size: u32) -> RgbImage {
+        if block_size == 0 {
+            panic!("Blocks must be at least one pixel wide");
+        }
+        let averages = self.render_averages(tiles);
+        let (w, h) = (self.columns * block_size, self.rows * block_size);
+        imageops::resize(&averages, w, h, FilterType::Nearest)
+    }
+
+    /// Write the [`Tile`] and color of each cell of this plan as CSV, with
+    /// a header and one row per cell (across each row of cells, then
+    /// down): its column, its row, the index of its [`Tile`] in the set,
+    /// and the average color of the [`Tile`] (as `#rrggbb`).
+    ///
+    /// Background cells have no tile, and the color of the
+    /// [`matte`](MosaicOptions::matte).
+    ///
+    /// # Panics
+    /// This function panics if the set does not contain every [`Tile`]
+    /// the plan refers to.
+    pub fn write_csv<W: Write>(&self, tiles: &TileSet, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "column,row,tile,color")?;
+        for y in 0..self.rows {
+            for x in 0..self.columns {
+                let (tile, color) = match self.is_skipped(x, y) {
+                    true => (String::new(), Rgb(self.options.matte)),
+                    false => {
+                        let idx = self.tile_at(x, y);
+                        let tile = tiles.get(idx).expect("No tile for cell");
+                        (idx.to_string(), *tile.avg())
+                    }
+                };
+                let [r, g, b] = color.0;
+                writeln!(writer, "{},{},{},#{:02x}{:02x}{:02x}", x, y, tile, r, g, b)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draw which [`Tile`] each cell uses, with one pixel per cell.
+    ///
+    /// Each tile gets its own hue, from blue for the first tile to red for
+    /// the last (as in a [`CoverageReport`](crate::CoverageReport) image),
+    /// so a tile which is repeated shows up as patches of one color.
+    /// Background cells are black.
+    pub fn usage_map(&self) -> RgbImage {
+        let last = self.tiles.len().saturating_sub(1).max(1) as f32;
+        RgbImage::from_fn(self.columns, self.rows, |x, y| {
+            match self.is_skipped(x, y) {
+                true => Rgb([0, 0, 0]),
+                false => heat_color(self.tile_at(x, y) as f32 / last),
+            }
+        })
+    }
+
+    /// Draw a mask of the cells which use the [`Tile`] with the given
+    /// index (in white), with one pixel per cell.
+    pub fn usage_mask(&self, tile: usize) -> GrayImage {
+        GrayImage::from_fn(self.columns, self.rows, |x, y| {
+            let used = !self.is_skipped(x, y) && self.tile_at(x, y) == tile;
+            Luma([if used { 255 } else { 0 }])
+        })
+    }
+
+    /// Render the mosaic described by this plan into part of a larger image,
+    /// with the top left corner of the mosaic at `offset`.
+    ///
+    /// This can be used to compose several mosaics (e.g., mosaics of
+    /// individual [`Tile`]s) into a single image.
+    ///
+    /// # Panics
+    /// This function panics for the same reasons as [`render`](MosaicPlan::render),
+    /// or if the mosaic does not fit in `canvas` at the given offset.
+    pub fn render_at(&self, tiles: &TileSet, canvas: &mut RgbImage, offset: (u32, u32)) {
+        let mut mosaic = Inner(std::mem::take(canvas));
+        let tile_img = |idx, _| tiles.get(idx).expect("No tile for cell").pixels();
+        let matte = Rgb(self.options.matte);
+        self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            offset,
+            self.bounds(),
+            false,
+            None,
+        );
+        *canvas = mosaic.0;
+    }
+
+    /// Render part of the mosaic described by this plan: only the cells
+    /// which intersect `window` (in the pixel coordinates of the full
+    /// [`render`](MosaicPlan::render)), clipped to it.
+    ///
+    /// The result is exactly the size of `window` and is identical to the
+    /// same crop of the full render, but only the cells in the window are
+    /// placed, so parts of very large mosaics can be inspected without
+    /// rendering the whole thing.
+    ///
+    /// # Panics
+    /// This function panics for the same reasons as [`render`](MosaicPlan::render),
+    /// or if `window` is empty or does not lie within the mosaic (see
+    /// [`output_size`](MosaicPlan::output_size)).
+    pub fn render_window(&self, tiles: &TileSet, window: Rect) -> RgbImage {
+        if window.is_empty() || self.bounds().intersect(&window) != Some(window) {
+            let (mos_x, mos_y) = self.output_size();
+            panic!(
+                "Window {}x{} at ({}, {}) does not lie within the {}x{} mosaic",
+                window.width, window.height, window.x, window.y, mos_x, mos_y
+            );
+        }
+
+        self.render_part(tiles, window, true)
+    }
+
+    /// Render the single cell at `(x, y)` in the plan's grid: the
+    /// `tile_size` x `tile_size` image placed there by
+    /// [`render`](MosaicPlan::render) (i.e., the image of its [`Tile`], or
+    /// the [`matte`](MosaicOptions::matte) for background cells).
+    ///
+    /// # Panics
+    /// This function panics for the same reasons as [`render`](MosaicPlan::render),
+    /// or if the cell lies outside of the grid.
+    pub fn render_cell(&self, tiles: &TileSet, x: u32, y: u32) -> RgbImage {
+        if x >= self.columns || y >= self.rows {
+            panic!(
+                "Cell ({}, {}) lies outside of the {}x{} grid",
+                x, y, self.columns, self.rows
+            );
+        }
+        let s = self.tile_size;
+        self.render_part(tiles, Rect::new(x * s, y * s, s, s), false)
+    }
+
+    /// Render the part of the mosaic within `window`, which must lie
+    /// within the mosaic.
+    fn render_part(&self, tiles: &TileSet, window: Rect, progress: bool) -> RgbImage {
+        let mut mosaic = Inner(RgbImage::new(window.width, window.height));
+        let tile_img = |idx, _| tiles.get(idx).expect("No tile for cell").pixels();
+        let matte = Rgb(self.options.matte);
+        self.place(
+            tiles.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            window,
+            progress,
+            None,
+        );
+        mosaic.0
+    }
+
+    /// Get the rectangle covered by the whole mosaic (in pixels).
+    fn bounds(&self) -> Rect {
+        let (mos_x, mos_y) = self.output_size();
+        Rect::new(0, 0, mos_x, mos_y)
+    }
+
+    /// Add the [`Tile`] for each cell of this plan which intersects
+    /// `window` to a mosaic, using `tile_img` to get the image to place for
+    /// the [`Tile`] with a given index in the cell at a given position.
+    ///
+    /// The part of each cell within the window is placed at its position
+    /// relative to the top left corner of the window, plus `offset`.
+    /// `side_len` is the side length of the [`Tile`]s, which must match
+    /// the plan. Each image is [adjusted](MosaicPlan::adjusted) before it
+    /// is placed. With `progress`, each cell placed is reported to the
+    /// plan's [progress callback](MosaicPlan::on_progress).
+    ///
+    /// If `cancel` is set, the remaining cells are left empty; this returns
+    /// whether every cell was placed.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn place<'a, P, C, T, F>(
+        &self,
+        side_len: u32,
+        tile_img: F,
+        matte: P,
+        mosaic: &mut Inner<P>,
+        offset: (u32, u32),
+        window: Rect,
+        progress: bool,
+        cancel: Option<&AtomicBool>,
+    ) -> bool
+    where
+        P: Pixel<Subpixel = u8> + 'a,
+        C: Deref<Target = [u8]>,
+        T: Borrow<ImageBuffer<P, C>>,
+        F: Fn(usize, (u32, u32)) -> T,
+    {
+        let tile_size = self.tile_size;
+        if side_len != tile_size {
+            panic!(
+                "Tile set has {}px tiles but the plan requires {}px tiles",
+                side_len, tile_size
+            );
+        }
+
+        // the cells which intersect the window
+        let (x0, y0) = (window.x / tile_size, window.y / tile_size);
+        let x1 = (window.x + window.width).div_ceil(tile_size);
+        let y1 = (window.y + window.height).div_ceil(tile_size);
+        let cells = (y0..y1).flat_map(|y| (x0..x1).map(move |x| (x, y)));
+
+        let num_cells = ((x1 - x0) * (y1 - y0)) as u64;
+        let mut eta = (progress && self.progress.is_set()).then(|| Eta::new(num_cells));
+        for (done, (x, y)) in cells.enumerate() {
+            if check_cancelled(cancel).is_err() {
+                return false;
+            }
+            let cell = Rect::new(x * tile_size, y * tile_size, tile_size, tile_size);
+            let part = cell
+                .intersect(&window)
+                .expect("Cell is outside of the window");
+            let (dst_x, dst_y) = (offset.0 + part.x - window.x, offset.1 + part.y - window.y);
+
+            let i = (y * self.columns + x) as usize;
+            if self.is_skipped_at(i) {
+                mosaic.fill((dst_x, dst_y), (part.width, part.height), matte);
+            } else {
+                let part = Rect::new(part.x - cell.x, part.y - cell.y, part.width, part.height);
+                let tile = tile_img(self.cells[i], (x, y));
+                match self.adjust(i, tile.borrow()) {
+                    Some(adjusted) => mosaic.add_tile(&adjusted, part, (dst_x, dst_y)),
+                    None => mosaic.add_tile(tile.borrow(), part, (dst_x, dst_y)),
+                }
+            }
+
+            if let Some(eta) = &mut eta {
+                let done = done as u64 + 1;
+                eta.record(done);
+                self.progress
+                    .report(Progress::of(Phase::Placement, done, eta));
+            }
+        }
+        true
+    }
+
+    /// Adjust the image of the [`Tile`] placed in the `i`th cell (in
+    /// row-major order): shift it towards the color of the cell, if the
+    /// options [adjust colors](MosaicOptions::color_adjust), then blend
+    /// the colors of the cells over it, if the options
+    /// [overlay](MosaicOptions::overlay_strength) them.
+    pub(crate) fn adjusted<'a, P>(
+        &self,
+        i: usize,
+        tile: Cow<'a, ImageBuffer<P, Vec<u8>>>,
+    ) -> Cow<'a, ImageBuffer<P, Vec<u8>>>
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        match self.adjust(i, &*tile) {
+            Some(adjusted) => Cow::Owned(adjusted),
+            None => tile,
+        }
+    }
+
+    /// Adjust the image of the [`Tile`] placed in the `i`th cell, as with
+    /// [`adjusted`](MosaicPlan::adjusted), returning `None` if the options
+    /// leave it unchanged.
+    fn adjust<P, C>(&self, i: usize, tile: &ImageBuffer<P, C>) -> Option<ImageBuffer<P, Vec<u8>>>
+    where
+        P: Pixel<Subpixel = u8>,
+        C: Deref<Target = [u8]>,
+    {
+        let target = self.targets.get(i)?;
+        let mut adjusted = match self.options.color_adjust > 0.0 {
+            true => Some(adjust_colors(tile, target, self.options.color_adjust)),
+            false => None,
+        };
+        if self.options.overlay_strength > 0.0 {
+            let cell = (i as u32 % self.columns, i as u32 / self.columns);
+            let adjusted = adjusted.get_or_insert_with(|| to_owned_image(tile));
+            self.overlay(adjusted, cell);
+        }
+        adjusted
+    }
+
+    /// Blend the colors of the cells, scaled up smoothly (by bilinear
+    /// interpolation between the centers of the cells), over the image of
+    /// the [`Tile`] placed in the cell at `(cx, cy)` (see
+    /// [`overlay_strength`](MosaicOptions::overlay_strength)). Grayscale
+    /// images are blended with the luma of the colors.
+    fn overlay<P>(&self, tile: &mut ImageBuffer<P, Vec<u8>>, (cx, cy): (u32, u32))
+    where
+        P: Pixel<Subpixel = u8>,
+    {
+        let strength = self.options.overlay_strength.min(1.0);
+        let side = tile.width() as f32;
+        let (max_x, max_y) = ((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let at = |x: u32, y: u32| self.targets[(y * self.columns + x) as usize].map(f32::from);
+        for (x, y, px) in tile.enumerate_pixels_mut() {
+            let gx = (cx as f32 + (x as f32 + 0.5) / side - 0.5).clamp(0.0, max_x);
+            let gy = (cy as f32 + (y as f32 + 0.5) / side - 0.5).clamp(0.0, max_y);
+            let (x0, y0) = (gx as u32, gy as u32);
+            let (x1, y1) = ((x0 + 1).min(self.columns - 1), (y0 + 1).min(self.rows - 1));
+            let (fx, fy) = (gx - x0 as f32, gy - y0 as f32);
+            let (a, b, c, d) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+            let rgb = [0, 1, 2].map(|ch| {
+                let top = a[ch] + (b[ch] - a[ch]) * fx;
+                let bottom = c[ch] + (d[ch] - c[ch]) * fx;
+                top + (bottom - top) * fy
+            });
+            let color = match P::CHANNEL_COUNT {
+                1 | 2 => vec![0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]],
+                _ => rgb.to_vec(),
+            };
+            for (ch, target) in px.channels_mut().iter_mut().zip(color) {
+                let blended = *ch as f32 + (target - *ch as f32) * strength;
+                *ch = blended.round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+
+    /// Check whether the given [`TileSet`] contains exactly the [`Tile`]s
+    /// used to build this plan.
+    pub fn matches(&self, tiles: &TileSet) -> bool {
+        tiles.tile_side_len() == self.tile_size
+            && tiles.len() == self.tiles.len()
+            && tiles
+                .iter()
+                .zip(&self.tiles)
+                .all(|(t, r)| TileRef::from(t).same_tile(r))
+    }
+
+    /// Find the [`Tile`] in `tiles` with the same pixels as each [`Tile`]
+    /// referenced by this plan (by their
+    /// [content hashes](Tile::content_hash)), in the order of
+    /// [`tiles`](MosaicPlan::tiles), with `None` for any which are missing.
+    ///
+    /// Unlike [`matches`](MosaicPlan::matches), this allows the set to hold
+    /// other tiles as well (e.g., images added to the tile directory since
+    /// the plan was built).
+    pub fn find_tiles(&self, tiles: &TileSet) -> Vec<Option<usize>> {
+        let by_hash: HashMap<String, usize> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (TileRef::from(t).hash, i))
+            .collect();
+        self.tiles
+            .iter()
+            .map(|r| by_hash.get(&r.hash).copied())
+            .collect()
+    }
+
+    /// Render the mosaic described by this plan like
+    /// [`render`](MosaicPlan::render), taking each [`Tile`] from `tiles` by
+    /// its content hash (see [`find_tiles`](MosaicPlan::find_tiles)), so
+    /// the set need not be exactly the one used to build the plan.
+    ///
+    /// # Errors
+    /// This function returns an error if the side length of the [`Tile`]s
+    /// in the set does not match the plan, or if any [`Tile`] the plan
+    /// refers to is missing from the set.
+    pub fn render_by_hash(&self, tiles: &TileSet) -> Result<RgbImage, Box<dyn Error>> {
+        if tiles.tile_side_len() != self.tile_size {
+            return Err(format!(
+                "Plan uses {}px tiles, but the tiles are {}px",
+                self.tile_size,
+                tiles.tile_side_len()
+            )
+            .into());
+        }
+        let found = self.find_tiles(tiles);
+        let missing: Vec<&str> = found
+            .iter()
+            .zip(&self.tiles)
+            .filter(|(f, _)| f.is_none())
+            .map(|(_, r)| r.hash.as_str())
+            .collect();
+        if let Some(first) = missing.first() {
+            return Err(format!(
+                "{} of the {} tiles used by the plan are missing (e.g., the tile with hash {})",
+                missing.len(),
+                self.tiles.len(),
+                first
+            )
+            .into());
+        }
+
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = Inner(RgbImage::new(mos_x, mos_y));
+        let tile_img = |idx: usize, _| {
+            let tile = found[idx].and_then(|i| tiles.get(i));
+            tile.expect("No tile for cell").pixels()
+        };
+        let matte = Rgb(self.options.matte);
+        let bounds = self.bounds();
+        self.place(
+            self.tile_size,
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            bounds,
+            false,
+            None,
+        );
+        Ok(mosaic.0)
+    }
+
+    /// Save this plan as JSON at the given `path`.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Save a flat-color preview of the mosaic described by this plan (see
+    /// [`render_averages`](MosaicPlan::render_averages)) at the given
+    /// `path`, in the format given by its extension.
+    ///
+    /// This takes a fraction of the time needed to render the mosaic, so
+    /// it can be written before the full render to check the composition.
+    ///
+    /// # Panics
+    /// See [`render_averages`](MosaicPlan::render_averages).
+    pub fn save_preview(&self, tiles: &TileSet, path: &Path) -> Result<(), Box<dyn Error>> {
+        crate::save_image(&self.render_averages(tiles), path, None)
+    }
+
+    /// Save the [`Tile`] and color of each cell of this plan as CSV (see
+    /// [`write_csv`](MosaicPlan::write_csv)) at the given `path`.
+    ///
+    /// # Panics
+    /// See [`write_csv`](MosaicPlan::write_csv).
+    pub fn save_csv(&self, tiles: &TileSet, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_csv(tiles, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a plan previously written with [`save`](MosaicPlan::save).
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// The unvalidated form of a [`MosaicPlan`], used for deserialization.
+#[derive(Deserialize)]
+struct RawPlan {
+    columns: u32,
+    rows: u32,
+    tile_size: u32,
+    #[serde(default)]
+    options: MosaicOptions,
+    tiles: Vec<TileRef>,
+    cells: Vec<usize>,
+    #[serde(default)]
+    skipped: Vec<bool>,
+    #[serde(default)]
+    distances: Vec<f32>,
+    #[serde(default)]
+    targets: Vec<[u8; 3]>,
+    #[serde(default)]
+    output_sha256: Option<String>,
+}
+
+impl TryFrom<RawPlan> for MosaicPlan {
+    type Error = String;
+
+    fn try_from(raw: RawPlan) -> Result<Self, Self::Error> {
+        if raw.columns == 0 || raw.rows == 0 || raw.tile_size == 0 {
+            return Err("Plan dimensions must be non-zero".into());
+        }
+        let expected = raw.columns as usize * raw.rows as usize;
+        if raw.cells.len() != expected {
+            return Err(format!(
+                "Plan has {} cells but a {}x{} grid requires {}",
+                raw.cells.len(),
+                raw.columns,
+                raw.rows,
+                expected
+            ));
+        }
+        if !raw.skipped.is_empty() && raw.skipped.len() != expected {
+            return Err(format!(
+                "Plan marks {} cells as background but has {} cells",
+                raw.skipped.len(),
+                expected
+            ));
+        }
+        if !raw.distances.is_empty() && raw.distances.len() != expected {
+            return Err(format!(
+                "Plan records {} distances but has {} cells",
+                raw.distances.len(),
+                expected
+            ));
+        }
+        if !raw.targets.is_empty() && raw.targets.len() != expected {
+            return Err(format!(
+                "Plan records {} cell colors but has {} cells",
+                raw.targets.len(),
+                expected
+            ));
+        }
+        if let Some(idx) = raw.cells.iter().find(|&&idx| idx >= raw.tiles.len()) {
+            return Err(format!(
+                "Plan refers to tile {} but only has {} tiles",
+                idx,
+                raw.tiles.len()
+            ));
+        }
+
+        Ok(Self {
+            columns: raw.columns,
+            rows: raw.rows,
+            tile_size: raw.tile_size,
+            options: raw.options,
+            tiles: raw.tiles,
+            cells: raw.cells,
+            skipped: raw.skipped,
+            distances: raw.distances,
+            targets: raw.targets,
+            output_sha256: raw.output_sha256,
+            progress: ProgressHook::default(),
+        })
+    }
+}
+
+/// Shift the pixels of the image of a [`Tile`] by `strength` times the
+/// difference between `target` and the image's average color (see
+/// [`MosaicOptions::color_adjust`]). Grayscale images are shifted towards
+/// the luma of `target`.
+fn adjust_colors<P, C>(
+    tile: &ImageBuffer<P, C>,
+    target: &[u8; 3],
+    strength: f32,
+) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+    C: Deref<Target = [u8]>,
+{
+    let target = match P::CHANNEL_COUNT {
+        1 | 2 => Rgb(*target).to_luma().0.to_vec(),
+        _ => target.to_vec(),
+    };
+    let mut sums = vec![0u64; target.len()];
+    for px in tile.pixels() {
+        for (sum, &c) in sums.iter_mut().zip(px.channels()) {
+            *sum += c as u64;
+        }
+    }
+    let count = (tile.width() as u64 * tile.height() as u64).max(1);
+    let shifts: Vec<f32> = sums
+        .iter()
+        .zip(&target)
+        .map(|(&sum, &t)| strength * (t as f32 - sum as f32 / count as f32))
+        .collect();
+
+    let mut adjusted = to_owned_image(tile);
+    for px in adjusted.pixels_mut() {
+        for (c, shift) in px.channels_mut().iter_mut().zip(&shifts) {
+            *c = (*c as f32 + shift).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    adjusted
+}
+
+/// Copy an image (e.g., a view of the pixels of a [`Tile`]) into memory
+fn to_owned_image<P, C>(img: &ImageBuffer<P, C>) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8>,
+    C: Deref<Target = [u8]>,
+{
+    ImageBuffer::from_raw(img.width(), img.height(), img.as_raw().to_vec())
+        .expect("Image is incomplete")
+}
+
+/// A wrapper around an image (e.g., an [`RgbImage`]) used to build the
+/// resulting image mosaic.
+pub(crate) struct Inner<P: Pixel>(pub(crate) ImageBuffer<P, Vec<P::Subpixel>>);
+
+impl<P: Pixel> Inner<P> {
+    /// Add (part of) the image of a [`Tile`] to the image mosaic.
+    ///
+    /// More specifically, insert the pixels within `part` of a given
+    /// [`Tile`] into this image at an offset based on where that [`Tile`]
+    /// belongs in the [`Mosaic`](crate::Mosaic).
+    pub fn add_tile<C>(&mut self, tile: &ImageBuffer<P, C>, part: Rect, start_coords: (u32, u32))
+    where
+        C: Deref<Target = [P::Subpixel]>,
+    {
+        let (start_x, start_y) = start_coords;
+        let part = tile.view(part.x, part.y, part.width, part.height);
+        self.0
+            .copy_from(&*part, start_x, start_y)
+            .expect("Tile does not fit in the mosaic");
+    }
+
+    /// Fill a rectangle of the image mosaic with a solid color.
+    pub fn fill(&mut self, start_coords: (u32, u32), (width, height): (u32, u32), color: P) {
+        let (start_x, start_y) = start_coords;
+        for y in start_y..start_y + height {
+            for x in start_x..start_x + width {
+                self.0.put_pixel(x, y, color);
+            }
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{Rgb, RgbImage};
+
+/// Adjustments applied to a rendered mosaic before it is saved.
+///
+/// The adjustments are applied in a fixed order: first the image is
+/// sharpened, then its contrast is adjusted, and finally its gamma.
+///
+/// Sharpening uses an unsharp mask with a 3x3 box blur, so each output
+/// pixel depends on its immediate neighbors. When an image is processed
+/// in horizontal bands (see [`apply_band`](PostProcess::apply_band)),
+/// each band must include [`OVERLAP`](PostProcess::OVERLAP) extra rows
+/// from its neighbors above and below.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcess {
+    /// The strength of the unsharp mask; `0` leaves the image unchanged,
+    /// and `1` doubles the difference between each pixel and its neighbors.
+    pub sharpen: f32,
+    /// The contrast adjustment, from `-100` (flat gray) to `100` (double
+    /// contrast); `0` leaves the image unchanged.
+    pub contrast: f32,
+    /// The gamma correction; values above `1` brighten the image, and values
+    /// below `1` darken it. `1` leaves the image unchanged.
+    pub gamma: f32,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            sharpen: 0.0,
+            contrast: 0.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl PostProcess {
+    /// The number of rows of context needed above and below a band.
+    pub const OVERLAP: u32 = 1;
+
+    /// Check whether these adjustments leave images unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply the adjustments to a whole image.
+    ///
+    /// # Panics
+    /// See [`apply_band`](PostProcess::apply_band).
+    pub fn apply(&self, img: &RgbImage) -> RgbImage {
+        self.apply_band(img, 0, 0)
+    }
+
+    /// Apply the adjustments to a horizontal band of a larger image.
+    ///
+    /// # Arguments
+    /// * `band` - The rows of the band, along with `top` rows of context
+    ///   above it and `bottom` rows of context below it. The context should
+    ///   be [`OVERLAP`](PostProcess::OVERLAP) rows, except at the top and
+    ///   bottom edges of the full image, where there is none.
+    /// * `top` - The number of rows of context at the top of `band`.
+    /// * `bottom` - The number of rows of context at the bottom of `band`.
+    ///
+    /// # Returns
+    /// The adjusted rows of the band, without the context. Processing an
+    /// image band by band gives the same result as processing it whole.
+    ///
+    /// # Panics
+    /// This function panics if `contrast` is not between `-100` and `100`,
+    /// if `gamma` is not positive, or if `sharpen` is negative. It will also
+    /// panic if `band` has no rows besides the context.
+    pub fn apply_band(&self, band: &RgbImage, top: u32, bottom: u32) -> RgbImage {
+        if !(-100.0..=100.0).contains(&self.contrast) {
+            panic!("Contrast must be between -100 and 100");
+        }
+        if !(self.gamma.is_finite() && self.gamma > 0.0) {
+            panic!("Gamma must be positive");
+        }
+        if !(self.sharpen.is_finite() && self.sharpen >= 0.0) {
+            panic!("Sharpening amount must not be negative");
+        }
+        let (w, h) = band.dimensions();
+        if top + bottom >= h {
+            panic!("Band has no rows besides its context");
+        }
+
+        let lut = self.lut();
+        RgbImage::from_fn(w, h - top - bottom, |x, y| {
+            let y = y + top;
+            let px = if self.sharpen == 0.0 {
+                *band.get_pixel(x, y)
+            } else {
+                let blur = box_blur(band, x, y);
+                let orig = band.get_pixel(x, y);
+                Rgb(std::array::from_fn(|c| {
+                    let v = orig.0[c] as f32;
+                    (v + self.sharpen * (v - blur[c])).round().clamp(0.0, 255.0) as u8
+                }))
+            };
+            Rgb(px.0.map(|v| lut[v as usize]))
+        })
+    }
+
+    /// Build a lookup table for the contrast and gamma adjustments.
+    fn lut(&self) -> [u8; 256] {
+        let factor = 1.0 + self.contrast / 100.0;
+        std::array::from_fn(|v| {
+            let v = ((v as f32 - 128.0) * factor + 128.0).clamp(0.0, 255.0);
+            let v = 255.0 * (v / 255.0).powf(1.0 / self.gamma);
+            v.round().clamp(0.0, 255.0) as u8
+        })
+    }
+}
+
+/// Get the average of the 3x3 block of pixels around `(x, y)`, repeating
+/// the pixels at the edges of the image as needed.
+fn box_blur(img: &RgbImage, x: u32, y: u32) -> [f32; 3] {
+    let (w, h) = img.dimensions();
+    let mut sum = [0.0; 3];
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let nx = (x as i64 + dx).clamp(0, w as i64 - 1) as u32;
+            let ny = (y as i64 + dy).clamp(0, h as i64 - 1) as u32;
+            for (sum, v) in sum.iter_mut().zip(img.get_pixel(nx, ny).0) {
+                *sum += v as f32;
+            }
+        }
+    }
+    sum.map(|s| s / 9.0)
+}
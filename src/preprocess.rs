@@ -0,0 +1,477 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Adjust source images before they are matched to tiles.
+//!
+//! A [`Mosaic`](crate::Mosaic) adjusts its source image after scaling it
+//! and before matching it to [`Tile`](crate::Tile)s, in a fixed order:
+//!
+//! 1. the [`preprocess`](crate::MosaicOptions::preprocess) adjustment
+//!    (a contrast stretch or histogram equalization), fitted to the
+//!    histogram of the scaled source;
+//! 2. the [`saturation`](crate::MosaicOptions::saturation) adjustment
+//!    (see [`saturate`]), so it boosts the colors the stretch produced;
+//! 3. the [`posterize`](crate::MosaicOptions::posterize) quantization,
+//!    fitted to the colors produced by the first two.
+//!
+//! Any other quantization of the source should come last as well.
+
+use crate::color::Oklab;
+use crate::options::MosaicOptions;
+use image::{Pixel, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An adjustment applied to the (scaled) source image of a
+/// [`Mosaic`](crate::Mosaic) before it is matched to [`Tile`](crate::Tile)s,
+/// to spread the colors of low-contrast images over more of the tile set.
+///
+/// The adjustment is fitted to the histogram of the scaled source image,
+/// and only changes which tiles are chosen: the mosaic isn't adjusted
+/// after it is rendered (see [`PostProcess`](crate::PostProcess) for that).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preprocess {
+    /// Leave the source image unchanged.
+    #[default]
+    None,
+    /// Stretch the contrast of the image linearly, so that the darkest and
+    /// brightest `clip` percent of the pixels (by luminance) are clipped to
+    /// black and white, and the rest span the full range. The same stretch
+    /// is applied to every channel, so colors keep their balance.
+    Stretch {
+        /// The percentage of pixels to clip at each end (from `0` up to,
+        /// but not including, `50`).
+        clip: f32,
+    },
+    /// Equalize the histogram of each channel separately, spreading the
+    /// values of each channel evenly over the full range. This can shift
+    /// the colors of the image.
+    Equalize,
+    /// Equalize the histogram of the luminance of the image, shifting every
+    /// channel of each pixel by the change in its luminance.
+    EqualizeLuminance,
+}
+
+/// A reduction of the source image of a [`Mosaic`](crate::Mosaic) to a few
+/// colors before it is matched to [`Tile`](crate::Tile)s, so that areas of
+/// similar color become flat and use a single tile, for a deliberately
+/// graphic look.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Posterize {
+    /// Leave the source image unchanged.
+    #[default]
+    None,
+    /// Round each channel to one of this many evenly spaced levels (at
+    /// least `2`), leaving at most the cube of it colors.
+    Levels(u8),
+    /// Reduce the image to a palette of at most this many colors (at least
+    /// `1`), chosen by median cut to suit the image.
+    Colors(u16),
+}
+
+impl Posterize {
+    /// Check whether this leaves images unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Posterize::None
+    }
+
+    /// Posterize an image (choosing the palette for the image itself).
+    ///
+    /// # Panics
+    /// This function panics if the number of [`Levels`](Posterize::Levels)
+    /// is less than `2`, or the number of [`Colors`](Posterize::Colors) is
+    /// `0`.
+    pub fn apply(&self, img: &RgbImage) -> RgbImage {
+        self.quantizer(img).apply(img)
+    }
+
+    /// Fit this to an image, so the same palette can be applied to other
+    /// images (e.g., versions of it at other sizes).
+    ///
+    /// # Panics
+    /// See [`apply`](Posterize::apply).
+    fn quantizer(&self, img: &RgbImage) -> Quantizer {
+        match *self {
+            Posterize::None => Quantizer::Levels(std::array::from_fn(|v| v as u8)),
+            Posterize::Levels(n) => {
+                if n < 2 {
+                    panic!("Posterizing needs at least 2 levels per channel");
+                }
+                let steps = (n - 1) as f32;
+                Quantizer::Levels(std::array::from_fn(|v| {
+                    let level = (v as f32 / 255.0 * steps).round();
+                    (level / steps * 255.0).round() as u8
+                }))
+            }
+            Posterize::Colors(k) => {
+                if k == 0 {
+                    panic!("Posterizing needs at least 1 color");
+                }
+                Quantizer::Palette(median_cut(img, k as usize))
+            }
+        }
+    }
+}
+
+/// A quantization fitted to an image by [`Posterize`]
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+enum Quantizer {
+    /// A lookup table applied to every channel.
+    Levels([u8; 256]),
+    /// A palette which every pixel is mapped to the closest color of.
+    Palette(Vec<Rgb<u8>>),
+}
+
+impl Quantizer {
+    /// Quantize an image.
+    fn apply(&self, img: &RgbImage) -> RgbImage {
+        let mut out = img.clone();
+        match self {
+            Quantizer::Levels(lut) => {
+                for v in out.iter_mut() {
+                    *v = lut[*v as usize];
+                }
+            }
+            Quantizer::Palette(palette) => {
+                let mut closest = HashMap::new();
+                for px in out.pixels_mut() {
+                    *px = *closest.entry(*px).or_insert_with(|| nearest(palette, px));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Find the color in a palette closest to `px` (the first, if tied)
+fn nearest(palette: &[Rgb<u8>], px: &Rgb<u8>) -> Rgb<u8> {
+    let dist = |c: &Rgb<u8>| -> u32 {
+        c.0.iter()
+            .zip(px.0)
+            .map(|(&a, b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+    *palette
+        .iter()
+        .min_by_key(|c| dist(c))
+        .expect("Palette is empty")
+}
+
+/// Choose a palette of at most `k` colors for an image by median cut
+///
+/// Starting with a box holding every pixel, the box whose pixels span the
+/// widest range in any channel is split at the median of that channel,
+/// until there are `k` boxes (or no box can be split). Each box then
+/// contributes the average color of its pixels to the palette.
+fn median_cut(img: &RgbImage, k: usize) -> Vec<Rgb<u8>> {
+    /// The widest channel of a box of pixels and its range
+    fn widest(pixels: &[Rgb<u8>]) -> (usize, u8) {
+        (0..3)
+            .map(|c| {
+                let values = pixels.iter().map(|px| px.0[c]);
+                let range = values.clone().max().unwrap_or(0) - values.min().unwrap_or(0);
+                (c, range)
+            })
+            .max_by_key(|&(c, range)| (range, std::cmp::Reverse(c)))
+            .unwrap()
+    }
+
+    let mut boxes: Vec<Vec<Rgb<u8>>> = vec![img.pixels().copied().collect()];
+    while boxes.len() < k {
+        let (i, (channel, range)) = boxes
+            .iter()
+            .map(|b| widest(b))
+            .enumerate()
+            .max_by_key(|&(i, (_, range))| (range, std::cmp::Reverse(i)))
+            .expect("No boxes to split");
+        if range == 0 {
+            break; // every box is a single color
+        }
+        let mut pixels = boxes.swap_remove(i);
+        pixels.sort_unstable_by_key(|px| (px.0[channel], px.0));
+        // split between different values, so neither half is empty
+        let mid = pixels.len() / 2;
+        let value = pixels[mid].0[channel];
+        let split = match pixels.partition_point(|px| px.0[channel] < value) {
+            0 => pixels.partition_point(|px| px.0[channel] <= value),
+            split => split,
+        };
+        let upper = pixels.split_off(split);
+        boxes.push(pixels);
+        boxes.push(upper);
+    }
+
+    let mut palette: Vec<Rgb<u8>> = boxes
+        .iter()
+        .filter(|b| !b.is_empty())
+        .map(|b| {
+            let mut total = [0u64; 3];
+            for px in b {
+                for (t, v) in total.iter_mut().zip(px.0) {
+                    *t += v as u64;
+                }
+            }
+            let n = b.len() as u64;
+            Rgb(total.map(|t| ((t + n / 2) / n) as u8))
+        })
+        .collect();
+    palette.sort_unstable_by_key(|c| c.0);
+    palette.dedup();
+    palette
+}
+
+/// A mapping of colors fitted to an image by a [`Preprocess`]
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Levels {
+    /// A lookup table for each channel.
+    Channels([[u8; 256]; 3]),
+    /// A lookup table for the luminance of each pixel.
+    Luminance([u8; 256]),
+}
+
+impl Preprocess {
+    /// The default percentage of channel values clipped by
+    /// [`Preprocess::Stretch`].
+    pub const DEFAULT_CLIP: f32 = 1.0;
+
+    /// Check whether this leaves images unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Preprocess::None
+    }
+
+    /// Apply this adjustment to an image (fitted to the image itself).
+    ///
+    /// # Panics
+    /// This function panics if the `clip` of a [`Preprocess::Stretch`] is
+    /// not between `0` (inclusive) and `50` (exclusive).
+    pub fn apply(&self, img: &RgbImage) -> RgbImage {
+        self.levels(img).apply(img)
+    }
+
+    /// Fit this adjustment to an image, so the same mapping can be applied
+    /// to other images (e.g., versions of it at other sizes).
+    ///
+    /// # Panics
+    /// See [`apply`](Preprocess::apply).
+    pub(crate) fn levels(&self, img: &RgbImage) -> Levels {
+        let identity = std::array::from_fn(|v| v as u8);
+        match *self {
+            Preprocess::None => Levels::Channels([identity; 3]),
+            Preprocess::Stretch { clip } => {
+                if !(0.0..50.0).contains(&clip) {
+                    panic!("Clip percentage must be at least 0 and less than 50");
+                }
+                let hist = luma_histogram(img);
+                let clipped = (img.len() as f64 / 3.0 * clip as f64 / 100.0).floor() as u64;
+                let (lo, hi) = (percentile(&hist, clipped), percentile_rev(&hist, clipped));
+                Levels::Channels([stretch(lo, hi); 3])
+            }
+            Preprocess::Equalize => Levels::Channels(std::array::from_fn(|c| {
+                let mut hist = [0u64; 256];
+                for px in img.pixels() {
+                    hist[px.0[c] as usize] += 1;
+                }
+                equalize(&hist)
+            })),
+            Preprocess::EqualizeLuminance => Levels::Luminance(equalize(&luma_histogram(img))),
+        }
+    }
+}
+
+impl Levels {
+    /// Map the colors of an image.
+    pub(crate) fn apply(&self, img: &RgbImage) -> RgbImage {
+        let mut out = img.clone();
+        for px in out.pixels_mut() {
+            *px = match self {
+                Levels::Channels(luts) => Rgb(std::array::from_fn(|c| luts[c][px.0[c] as usize])),
+                Levels::Luminance(lut) => {
+                    let luma = px.to_luma().0[0];
+                    let shift = lut[luma as usize] as i16 - luma as i16;
+                    Rgb(px.0.map(|v| (v as i16 + shift).clamp(0, 255) as u8))
+                }
+            };
+        }
+        out
+    }
+}
+
+/// The adjustments made to the source image of a
+/// [`Mosaic`](crate::Mosaic), fitted to it (see the module documentation
+/// for their order)
+#[derive(Debug, Clone)]
+pub(crate) struct SourceAdjustment {
+    levels: Levels,
+    saturation: f32,
+    quantizer: Quantizer,
+}
+
+impl SourceAdjustment {
+    /// Fit the adjustments in `options` to an image.
+    ///
+    /// Returns `None` if the options leave images unchanged.
+    ///
+    /// # Panics
+    /// See [`Preprocess::apply`], [`saturate`], and [`Posterize::apply`].
+    pub(crate) fn fit(options: &MosaicOptions, img: &RgbImage) -> Option<Self> {
+        if options.preprocess.is_identity()
+            && options.saturation == 1.0
+            && options.posterize.is_identity()
+        {
+            return None;
+        }
+        check_saturation(options.saturation);
+        let levels = options.preprocess.levels(img);
+        let adjusted = saturate(&levels.apply(img), options.saturation);
+        Some(Self {
+            levels,
+            saturation: options.saturation,
+            quantizer: options.posterize.quantizer(&adjusted),
+        })
+    }
+
+    /// Adjust an image (the one these were fitted to, or a version of it
+    /// at another size).
+    pub(crate) fn apply(&self, img: &RgbImage) -> RgbImage {
+        let adjusted = saturate(&self.levels.apply(img), self.saturation);
+        self.quantizer.apply(&adjusted)
+    }
+}
+
+/// Scale the chroma of every pixel of an image by `factor`, keeping its
+/// lightness and hue (in the [`Oklab`] color space).
+///
+/// A `factor` above `1.0` makes colors more vivid, and one below `1.0`
+/// mutes them (down to grays at `0.0`). Colors which would be pushed
+/// outside of the sRGB gamut are clamped to its edge by scaling their
+/// chroma by as much as fits instead, so their hue doesn't shift.
+///
+/// # Panics
+/// This function panics if `factor` is negative or not finite.
+pub fn saturate(img: &RgbImage, factor: f32) -> RgbImage {
+    check_saturation(factor);
+    let mut out = img.clone();
+    if factor == 1.0 {
+        return out;
+    }
+    for px in out.pixels_mut() {
+        // grays have no chroma to scale
+        if px.0[0] == px.0[1] && px.0[1] == px.0[2] {
+            continue;
+        }
+        let lab = Oklab::from(*px);
+        let scaled = |s: f32| Oklab {
+            a: lab.a * s,
+            b: lab.b * s,
+            ..lab
+        };
+        let mut s = factor;
+        if !in_gamut(&scaled(s)) {
+            // the original color is in the gamut, so search between the two
+            let (mut lo, mut hi) = (1.0, factor);
+            for _ in 0..16 {
+                let mid = (lo + hi) / 2.0;
+                match in_gamut(&scaled(mid)) {
+                    true => lo = mid,
+                    false => hi = mid,
+                }
+            }
+            s = lo;
+        }
+        *px = scaled(s).to_rgb();
+    }
+    out
+}
+
+/// Check whether a color lies within the sRGB gamut (give or take rounding)
+fn in_gamut(lab: &Oklab) -> bool {
+    lab.to_linear_srgb()
+        .iter()
+        .all(|c| (-1e-4..=1.0 + 1e-4).contains(c))
+}
+
+/// Check that a saturation factor is valid
+fn check_saturation(factor: f32) {
+    if !(factor.is_finite() && factor >= 0.0) {
+        panic!("Saturation must be a non-negative, finite number");
+    }
+}
+
+/// Count the pixels of an image with each luminance
+fn luma_histogram(img: &RgbImage) -> [u64; 256] {
+    let mut hist = [0; 256];
+    for px in img.pixels() {
+        hist[px.to_luma().0[0] as usize] += 1;
+    }
+    hist
+}
+
+/// Find the smallest value with more than `skip` values below or at it
+pub(crate) fn percentile(hist: &[u64; 256], skip: u64) -> u8 {
+    let mut seen = 0;
+    for (v, n) in hist.iter().enumerate() {
+        seen += n;
+        if seen > skip {
+            return v as u8;
+        }
+    }
+    255
+}
+
+/// Find the largest value with more than `skip` values above or at it
+pub(crate) fn percentile_rev(hist: &[u64; 256], skip: u64) -> u8 {
+    let mut seen = 0;
+    for (v, n) in hist.iter().enumerate().rev() {
+        seen += n;
+        if seen > skip {
+            return v as u8;
+        }
+    }
+    0
+}
+
+/// Build a lookup table mapping `lo` to black and `hi` to white
+fn stretch(lo: u8, hi: u8) -> [u8; 256] {
+    if lo >= hi {
+        // a flat image has no contrast to stretch
+        return std::array::from_fn(|v| v as u8);
+    }
+    let range = (hi - lo) as f32;
+    std::array::from_fn(|v| {
+        let v = (v as f32 - lo as f32) / range * 255.0;
+        v.round().clamp(0.0, 255.0) as u8
+    })
+}
+
+/// Build a lookup table spreading the values in a histogram evenly over
+/// the full range
+fn equalize(hist: &[u64; 256]) -> [u8; 256] {
+    let total: u64 = hist.iter().sum();
+    let first = hist.iter().copied().find(|&n| n > 0).unwrap_or(0);
+    if total == first {
+        // a single value (or none); leave it be
+        return std::array::from_fn(|v| v as u8);
+    }
+    let mut cdf = 0;
+    std::array::from_fn(|v| {
+        cdf += hist[v];
+        let v = cdf.saturating_sub(first) as f64 / (total - first) as f64 * 255.0;
+        v.round() as u8
+    })
+}
@@ -0,0 +1,133 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::eta::{self, Eta};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The part of a long task a [`Progress`] update refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Placing the [`Tile`](crate::Tile)s of a mosaic, one cell at a time.
+    Placement,
+    /// Building a mosaic of each frame of a video, one frame at a time.
+    Frames,
+}
+
+/// An update on the progress of a long task (e.g., rendering a large
+/// mosaic), as passed to the callback given to
+/// [`Mosaic::on_progress`](crate::Mosaic::on_progress).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The part of the task in progress.
+    pub phase: Phase,
+    /// The number of units of work (e.g., cells or frames) done so far.
+    pub done: u64,
+    /// The number of units of work in the whole phase, if it is known
+    /// in advance.
+    pub total: Option<u64>,
+    /// The recent rate of progress (in units per second), if it can be
+    /// estimated yet (see [`Eta::rate`]).
+    pub rate: Option<f64>,
+    /// The estimated time until the phase is done, if it can be estimated
+    /// yet (see [`Eta::remaining`]).
+    pub remaining: Option<Duration>,
+}
+
+impl Progress {
+    /// Describe the progress of a phase with a known amount of work.
+    pub(crate) fn of(phase: Phase, done: u64, eta: &Eta) -> Self {
+        Self {
+            phase,
+            done,
+            total: Some(eta.total()),
+            rate: eta.rate(),
+            remaining: eta.remaining(),
+        }
+    }
+
+    /// Check whether the phase is done, i.e., all of its (known) work has
+    /// been done.
+    pub fn is_done(&self) -> bool {
+        self.total.is_some_and(|total| self.done >= total)
+    }
+}
+
+impl fmt::Display for Progress {
+    /// Format the progress (e.g., `Placing tile 0042/1000 (1.2k/s, <1s left)`).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.phase {
+            Phase::Placement => write!(f, "Placing tile ")?,
+            Phase::Frames => write!(f, "Processing frame ")?,
+        }
+        match self.total {
+            Some(total) => write!(f, "{:04}/{:04} (", self.done, total)?,
+            None => return write!(f, "{:05}", self.done),
+        }
+        eta::fmt_rate(f, self.rate, self.remaining)?;
+        write!(f, ")")
+    }
+}
+
+/// A callback to which [`Progress`] updates are reported.
+type Callback = dyn FnMut(Progress) + Send;
+
+/// The callback to which [`Progress`] updates are reported, if one has
+/// been set.
+///
+/// The callback is shared by clones (e.g., by a [`Mosaic`](crate::Mosaic)
+/// and the [`MosaicPlan`](crate::MosaicPlan)s it builds), and is ignored
+/// when comparing them.
+#[derive(Clone, Default)]
+pub(crate) struct ProgressHook(Option<Arc<Mutex<Callback>>>);
+
+impl ProgressHook {
+    /// Report updates to `callback`.
+    pub(crate) fn new(callback: impl FnMut(Progress) + Send + 'static) -> Self {
+        Self(Some(Arc::new(Mutex::new(callback))))
+    }
+
+    /// Check whether a callback has been set, i.e., whether it's worth
+    /// tracking progress at all.
+    pub(crate) fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Report an update to the callback, if one has been set.
+    pub(crate) fn report(&self, progress: Progress) {
+        if let Some(callback) = &self.0 {
+            // a callback which panicked before can still be called
+            let mut callback = callback.lock().unwrap_or_else(|e| e.into_inner());
+            callback(progress);
+        }
+    }
+}
+
+impl fmt::Debug for ProgressHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "ProgressHook(Some(..))"),
+            None => write!(f, "ProgressHook(None)"),
+        }
+    }
+}
+
+impl PartialEq for ProgressHook {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
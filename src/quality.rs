@@ -0,0 +1,127 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::{GenericImageView, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Objective measures of how closely a rendered mosaic resembles
+/// its (scaled) source image.
+///
+/// See [`Mosaic::quality`](crate::Mosaic::quality).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QualityReport {
+    /// The peak signal-to-noise ratio (in dB) between the source and the
+    /// downscaled mosaic. Higher is better; identical images have an
+    /// infinite PSNR.
+    pub psnr: f64,
+    /// The mean structural similarity between the luma of the source and
+    /// the downscaled mosaic, in `[-1, 1]`. Higher is better; identical
+    /// images have an SSIM of `1`.
+    pub ssim: f64,
+    /// The mean distance between each cell's color and the average color
+    /// of the tile assigned to it. Lower is better.
+    pub mean_distance: f64,
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PSNR {:.2} dB, SSIM {:.4}, mean distance {:.2}",
+            self.psnr, self.ssim, self.mean_distance
+        )
+    }
+}
+
+/// Downscale an image by averaging each `block` x `block` square of pixels.
+///
+/// The dimensions of `img` must be multiples of `block`.
+pub(crate) fn block_average(img: &RgbImage, block: u32) -> RgbImage {
+    let (w, h) = img.dimensions();
+    let n = block * block;
+    RgbImage::from_fn(w / block, h / block, |x, y| {
+        let mut tot = [0u32; 3];
+        for (_, _, px) in img.view(x * block, y * block, block, block).pixels() {
+            for (t, c) in tot.iter_mut().zip(px.0) {
+                *t += c as u32;
+            }
+        }
+        Rgb(tot.map(|t| ((t + n / 2) / n) as u8))
+    })
+}
+
+/// Compute the peak signal-to-noise ratio (in dB) between two images
+/// of the same size.
+pub(crate) fn psnr(a: &RgbImage, b: &RgbImage) -> f64 {
+    let sq_err: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&p, &q)| (p as f64 - q as f64).powi(2))
+        .sum();
+    let mse = sq_err / a.as_raw().len() as f64;
+    if mse == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0f64.powi(2) / mse).log10()
+}
+
+/// Compute the mean structural similarity between the luma of two images
+/// of the same size.
+///
+/// The SSIM is computed over non-overlapping 8x8 windows (smaller at the
+/// right and bottom edges) and averaged.
+pub(crate) fn ssim(a: &RgbImage, b: &RgbImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let luma =
+        |px: &Rgb<u8>| 0.299 * px.0[0] as f64 + 0.587 * px.0[1] as f64 + 0.114 * px.0[2] as f64;
+
+    let (w, h) = a.dimensions();
+    let mut total = 0.0;
+    let mut windows = 0;
+    for wy in (0..h).step_by(WINDOW as usize) {
+        for wx in (0..w).step_by(WINDOW as usize) {
+            let (ww, wh) = (WINDOW.min(w - wx), WINDOW.min(h - wy));
+            let n = (ww * wh) as f64;
+
+            let mut pairs = Vec::with_capacity((ww * wh) as usize);
+            for y in wy..wy + wh {
+                for x in wx..wx + ww {
+                    pairs.push((luma(a.get_pixel(x, y)), luma(b.get_pixel(x, y))));
+                }
+            }
+
+            let mu_a = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+            let mu_b = pairs.iter().map(|p| p.1).sum::<f64>() / n;
+            let var_a = pairs.iter().map(|p| (p.0 - mu_a).powi(2)).sum::<f64>() / n;
+            let var_b = pairs.iter().map(|p| (p.1 - mu_b).powi(2)).sum::<f64>() / n;
+            let cov = pairs
+                .iter()
+                .map(|p| (p.0 - mu_a) * (p.1 - mu_b))
+                .sum::<f64>()
+                / n;
+
+            total += ((2.0 * mu_a * mu_b + C1) * (2.0 * cov + C2))
+                / ((mu_a.powi(2) + mu_b.powi(2) + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+
+    total / windows as f64
+}
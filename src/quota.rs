@@ -0,0 +1,68 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::tiles::TileSet;
+
+/// The number of cells filled by each limited category of
+/// [`Tile`](crate::Tile)s while assigning tiles to cells, and the number
+/// each may fill (see [`TileSet::set_category_limit`]).
+pub(crate) struct Quotas {
+    /// The index (in `caps`) of the limited category of each tile, if any.
+    category: Vec<Option<usize>>,
+    /// The largest number of cells each limited category may fill.
+    caps: Vec<usize>,
+    /// The number of cells each limited category has filled so far.
+    used: Vec<usize>,
+}
+
+impl Quotas {
+    /// Start counting the cells filled by each limited category of a tile
+    /// set, out of `cells` cells, or `None` if no category is limited.
+    pub(crate) fn new(tiles: &TileSet, cells: usize) -> Option<Self> {
+        let limits = tiles.category_limits();
+        if limits.is_empty() {
+            return None;
+        }
+        let category = tiles
+            .iter()
+            .map(|t| {
+                let name = t.category()?;
+                limits.iter().position(|(limit, _)| limit == name)
+            })
+            .collect();
+        let caps = limits
+            .iter()
+            .map(|(_, share)| (share * cells as f32).round() as usize)
+            .collect();
+        Some(Self {
+            category,
+            caps,
+            used: vec![0; limits.len()],
+        })
+    }
+
+    /// Check whether the category of the tile with the given index has
+    /// filled all of its cells.
+    pub(crate) fn is_full(&self, tile: usize) -> bool {
+        self.category[tile].is_some_and(|c| self.used[c] >= self.caps[c])
+    }
+
+    /// Count a cell filled by the tile with the given index.
+    pub(crate) fn record(&mut self, tile: usize) {
+        if let Some(c) = self.category[tile] {
+            self.used[c] += 1;
+        }
+    }
+}
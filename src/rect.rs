@@ -0,0 +1,66 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+/// An axis-aligned rectangle of pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    /// The x coordinate of the left edge of the rectangle.
+    pub x: u32,
+    /// The y coordinate of the top edge of the rectangle.
+    pub y: u32,
+    /// The width of the rectangle (in pixels).
+    pub width: u32,
+    /// The height of the rectangle (in pixels).
+    pub height: u32,
+}
+
+impl Rect {
+    /// Create a new rectangle with its top left corner at `(x, y)`.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Check whether this rectangle contains no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Get the part of this rectangle which overlaps `other`.
+    ///
+    /// # Returns
+    /// The overlapping rectangle, or `None` if the rectangles
+    /// do not overlap.
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x as u64 + self.width as u64).min(other.x as u64 + other.width as u64);
+        let bottom = (self.y as u64 + self.height as u64).min(other.y as u64 + other.height as u64);
+        if right <= left as u64 || bottom <= top as u64 {
+            return None;
+        }
+
+        Some(Rect::new(
+            left,
+            top,
+            (right - left as u64) as u32,
+            (bottom - top as u64) as u32,
+        ))
+    }
+}
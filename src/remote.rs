@@ -0,0 +1,243 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Download tiles from a list of URLs.
+//!
+//! Images are downloaded over HTTP(S), several at once, into a cache
+//! directory, and loaded from there like any other tile files, so loading
+//! the same URLs again doesn't download them again.
+
+use crate::error::Error;
+use crate::utils::{
+    log_skipped, read_tile_files, LoadOptions, LoadReport, LoadWarning, LoadWarningReason,
+};
+use image::ImageFormat;
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use ureq::Agent;
+use xxhash_rust::xxh3::xxh3_128;
+
+/// Options controlling how [`load_tile_urls`] downloads images.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteOptions {
+    /// The directory to keep downloaded images in (which is created if it
+    /// doesn't exist). Images already in it aren't downloaded again.
+    pub cache_dir: PathBuf,
+    /// The most images to download at once.
+    pub jobs: usize,
+    /// How long to wait for each image to download before giving up.
+    pub timeout: Duration,
+    /// The largest image to download, in bytes.
+    pub max_bytes: u64,
+}
+
+impl RemoteOptions {
+    /// The number of images downloaded at once by default.
+    pub const DEFAULT_JOBS: usize = 8;
+
+    /// How long to wait for each image to download by default.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// The largest image to download by default (50 MB).
+    pub const DEFAULT_MAX_BYTES: u64 = 50_000_000;
+
+    /// Options to keep downloaded images in `cache_dir`, and the defaults
+    /// for everything else.
+    pub fn new(cache_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            cache_dir: cache_dir.into(),
+            jobs: Self::DEFAULT_JOBS,
+            timeout: Self::DEFAULT_TIMEOUT,
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+/// Split a list of URLs into the URLs: one per line, skipping blank lines
+/// and comments (lines starting with `#`).
+pub fn parse_url_list(list: &str) -> Vec<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Download the images at each of the given URLs (or take them from the
+/// [cache](RemoteOptions::cache_dir)), and load them to use as tiles in
+/// the [`Mosaic`][crate::Mosaic], using the given [`LoadOptions`].
+///
+/// The images are loaded as with [`load_tile_files`](crate::load_tile_files),
+/// in the order given, but the report gives each tile's URL as its path.
+/// URLs which can't be downloaded (or used as tiles) are skipped and
+/// recorded in the [`warnings`](LoadReport::warnings) of the returned
+/// report; they are tried again by the next load.
+///
+/// # Errors
+/// This function returns an error if the cache directory can't be created
+/// or read.
+pub fn load_tile_urls(
+    urls: &[String],
+    options: &LoadOptions,
+    remote: &RemoteOptions,
+) -> Result<LoadReport, Error> {
+    fs::create_dir_all(&remote.cache_dir)?;
+    // the cached images, by the names they're saved under (without their
+    // extensions, which depend on their contents)
+    let mut cached = HashMap::new();
+    for entry in fs::read_dir(&remote.cache_dir)? {
+        let path = entry?.path();
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+            if path.extension().is_some_and(|ext| ext != "part") {
+                cached.insert(stem.to_string(), path);
+            }
+        }
+    }
+
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(remote.timeout))
+        .build()
+        .into();
+    let next = AtomicUsize::new(0);
+    let downloaded = AtomicUsize::new(0);
+    let download = || {
+        let mut results = Vec::new();
+        loop {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+            let Some(url) = urls.get(i) else {
+                return results;
+            };
+            let name = cache_name(url);
+            let result = match cached.get(&name) {
+                Some(path) => Ok(path.clone()),
+                None => {
+                    downloaded.fetch_add(1, Ordering::Relaxed);
+                    fetch(&agent, url, &remote.cache_dir.join(name), remote.max_bytes)
+                }
+            };
+            results.push((i, result));
+        }
+    };
+    let mut results: Vec<(usize, Result<PathBuf, String>)> = std::thread::scope(|s| {
+        let workers: Vec<_> = (0..remote.jobs.clamp(1, urls.len().max(1)))
+            .map(|_| s.spawn(download))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("Download thread panicked"))
+            .collect()
+    });
+    results.sort_by_key(|(i, _)| *i);
+    debug!(
+        "Downloaded {} images, and took {} from the cache.",
+        downloaded.load(Ordering::Relaxed),
+        urls.len() - downloaded.load(Ordering::Relaxed)
+    );
+
+    // load the images from the cache, then report them by their URLs
+    let mut paths = Vec::new();
+    let mut warnings = Vec::new();
+    let mut url_of = HashMap::new();
+    for ((_, result), url) in results.into_iter().zip(urls) {
+        match result {
+            Ok(path) => {
+                url_of.insert(path.clone(), PathBuf::from(url));
+                paths.push(path);
+            }
+            Err(e) => warnings.push(LoadWarning {
+                path: PathBuf::from(url),
+                reason: LoadWarningReason::DownloadFailed(e),
+            }),
+        }
+    }
+    let report = read_tile_files(&paths, options);
+    let url = |path: PathBuf| url_of.get(&path).cloned().unwrap_or(path);
+    for warning in report.warnings {
+        let reason = match warning.reason {
+            LoadWarningReason::Duplicate(original) => LoadWarningReason::Duplicate(url(original)),
+            reason => reason,
+        };
+        let path = url(warning.path);
+        warnings.push(LoadWarning { path, reason });
+    }
+    // keep the warnings in the order of the URLs
+    let order: HashMap<&str, usize> = urls
+        .iter()
+        .enumerate()
+        .rev()
+        .map(|(i, url)| (url.as_str(), i))
+        .collect();
+    warnings.sort_by_key(|w| w.path.to_str().and_then(|p| order.get(p)).copied());
+
+    let report = LoadReport {
+        tiles: report.tiles,
+        paths: report.paths.into_iter().map(url).collect(),
+        warnings,
+        cmyk: report.cmyk.into_iter().map(url).collect(),
+    };
+    log_skipped(&report);
+    Ok(report)
+}
+
+/// Get the name to cache the image at `url` under (without an extension).
+fn cache_name(url: &str) -> String {
+    format!("{:032x}", xxh3_128(url.as_bytes()))
+}
+
+/// Download the image at `url`, and save it at `path` (with the extension
+/// of its format).
+///
+/// # Returns
+/// The path the image was saved at, or a description of why it couldn't
+/// be downloaded or saved.
+fn fetch(agent: &Agent, url: &str, path: &Path, max_bytes: u64) -> Result<PathBuf, String> {
+    let bytes = agent
+        .get(url)
+        .call()
+        .and_then(|mut response| {
+            response
+                .body_mut()
+                .with_config()
+                .limit(max_bytes)
+                .read_to_vec()
+        })
+        .map_err(|e| e.to_string())?;
+
+    // judge the format by the contents, since URLs often have no extension
+    // (or the wrong one); the extension is how the format is chosen later
+    let extension = match image::guess_format(&bytes) {
+        Ok(format) => format.extensions_str().first().copied(),
+        Err(_) => None,
+    }
+    .or_else(|| {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let extension = Path::new(path).extension()?.to_str()?;
+        ImageFormat::from_extension(extension).map(|_| extension)
+    })
+    .unwrap_or("bin");
+
+    // write to a temporary file first, so an interrupted download isn't
+    // taken for a whole image
+    let partial = path.with_extension("part");
+    let path = path.with_extension(extension);
+    fs::write(&partial, &bytes)
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|e| format!("unable to save {} ({})", path.display(), e))?;
+    Ok(path)
+}
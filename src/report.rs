@@ -0,0 +1,75 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::stats::PlanStats;
+use crate::utils::{LoadReport, LoadWarning};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// A durable record of what happened while building a mosaic: the inputs
+/// which were skipped (and why), how the tiles were changed, and the
+/// decisions made along the way, with the [`PlanStats`] of the result.
+///
+/// This collects what would otherwise only be printed as warnings, so it
+/// can be [saved](RunReport::save) as JSON and checked after a long run.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RunReport {
+    /// The number of tiles which were loaded.
+    pub loaded: usize,
+    /// The entries which were skipped while loading tiles, and why
+    /// (including exact duplicates).
+    pub skipped: Vec<LoadWarning>,
+    /// The tiles which were converted from CMYK to RGB as they were loaded.
+    pub converted: Vec<PathBuf>,
+    /// The ways in which the loaded tiles were augmented (e.g., flipped,
+    /// or with synthetic tiles added to fill gaps in their colors).
+    pub augmentations: Vec<String>,
+    /// The warnings given and decisions made while building the mosaic
+    /// (e.g., about the diversity of the tiles, or its size).
+    pub notes: Vec<String>,
+    /// The statistics of the mosaic, once it has been planned.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<PlanStats>,
+    /// The SHA-256 hash (in hex) of the file the mosaic was saved to, once
+    /// it has been saved.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_sha256: Option<String>,
+}
+
+impl RunReport {
+    /// Record the tiles which were loaded (and skipped) in a [`LoadReport`].
+    pub fn add_load(&mut self, report: &LoadReport) {
+        self.loaded += report.tiles.len();
+        self.skipped.extend(report.warnings.iter().cloned());
+        self.converted.extend(report.cmyk.iter().cloned());
+    }
+
+    /// Save this report as JSON at the given `path`.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a report previously written with [`save`](RunReport::save).
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
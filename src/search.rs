@@ -0,0 +1,147 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::color::{Lab, Oklab};
+use crate::metric::Metric;
+use crate::tiles::TileSet;
+use image::Rgb;
+
+/// An approximate nearest-[`Tile`](crate::Tile) search over a [`TileSet`],
+/// which trades a bounded loss of accuracy for speed on large sets.
+///
+/// The tiles are sorted by the projection of their average colors onto
+/// the gray axis (scaled by the channel weights of the [`Metric`]). The
+/// difference between the projections of two colors is a lower bound on
+/// the distance between them, so the search scans outward from the
+/// projection of the pixel, and stops as soon as every remaining tile is
+/// known to be no better than the best so far divided by the `factor`.
+///
+/// With a `factor` of `1.0`, this always finds the same
+/// [`Tile`](crate::Tile) as an exhaustive search (including breaking ties
+/// in favor of the first [`Tile`](crate::Tile) in the set). Otherwise, the
+/// [score](crate::Tile::score) of the [`Tile`](crate::Tile) it finds is at
+/// most `factor` times the best score in the set.
+/// The search is deterministic either way.
+#[derive(Debug)]
+pub struct ApproxSearch<'a> {
+    tiles: &'a TileSet,
+    metric: Metric,
+    factor: f32,
+    /// The projection of each tile's average color, with its index in
+    /// the set, sorted by projection.
+    order: Vec<(f64, usize)>,
+    /// The largest weight of any tile in the set (which scales down the
+    /// lower bound on their scores).
+    max_weight: f32,
+}
+
+impl<'a> ApproxSearch<'a> {
+    /// Prepare to search the given set of [`Tile`](crate::Tile)s using
+    /// the given [`Metric`].
+    ///
+    /// # Panics
+    /// This function panics if `factor` is not a finite number of at
+    /// least `1.0`.
+    pub fn new(tiles: &'a TileSet, metric: Metric, factor: f32) -> Self {
+        if !(factor.is_finite() && factor >= 1.0) {
+            panic!("Approximation factor must be a finite number of at least 1.0");
+        }
+
+        let mut order: Vec<(f64, usize)> = tiles
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (project(metric, t.avg()), i))
+            .collect();
+        order.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let max_weight = tiles.iter().map(|t| t.weight()).fold(0.0, f32::max);
+
+        Self {
+            tiles,
+            metric,
+            factor,
+            order,
+            max_weight,
+        }
+    }
+
+    /// Find the index of a [`Tile`](crate::Tile) whose score for the given
+    /// pixel is within the approximation factor of the best in the set.
+    pub fn closest(&self, px: &Rgb<u8>) -> usize {
+        let key = project(self.metric, px);
+        let split = self.order.partition_point(|(k, _)| *k < key);
+        let (mut below, mut above) = (split, split);
+
+        let mut best = (f32::INFINITY, 0);
+        loop {
+            // visit whichever unvisited neighbor is closer in projection
+            let gap_below = below.checked_sub(1).map(|i| key - self.order[i].0);
+            let gap_above = self.order.get(above).map(|(k, _)| k - key);
+            let (gap, pos) = match (gap_below, gap_above) {
+                (Some(b), Some(a)) if b <= a => (b, below - 1),
+                (_, Some(a)) => (a, above),
+                (Some(b), None) => (b, below - 1),
+                (None, None) => break,
+            };
+
+            // leave a little slack for rounding in the computed scores
+            let bound = (gap * (1.0 - 1e-5)) as f32 / self.max_weight;
+            if bound * self.factor > best.0 {
+                break;
+            }
+
+            let idx = self.order[pos].1;
+            let score = self.tiles.get(idx).unwrap().score(px, self.metric);
+            if score < best.0 || (score == best.0 && idx < best.1) {
+                best = (score, idx);
+            }
+            if pos < below {
+                below = pos;
+            } else {
+                above = pos + 1;
+            }
+        }
+
+        best.1
+    }
+}
+
+/// Project a color onto the gray axis, after scaling each channel by
+/// the weights of the metric (if any). With [`Metric::Oklab`] and
+/// [`Metric::Lab`], this is the lightness of the color; with
+/// [`Metric::CieDe2000`], it is the lightness scaled down by the largest
+/// weight the metric gives differences in lightness.
+///
+/// The difference between the projections of two colors is never greater
+/// than the distance between them.
+fn project(metric: Metric, px: &Rgb<u8>) -> f64 {
+    let weights = match metric {
+        Metric::Rgb => [1.0; 3],
+        Metric::WeightedRgb(weights) => weights,
+        Metric::Oklab => return Oklab::from(px).l as f64,
+        Metric::Lab => return Lab::from(px).l as f64,
+        Metric::CieDe2000 => {
+            // the weight (S_L) is largest for the mean lightness furthest
+            // from 50.0, i.e., 0.0 or 100.0
+            let max_s_l = 1.0 + 0.015 * 2500.0 / 2520f64.sqrt();
+            return Lab::from(px).l as f64 / max_s_l;
+        }
+    };
+    let sum: f64 =
+        px.0.iter()
+            .zip(weights)
+            .map(|(&c, w)| c as f64 * w as f64)
+            .sum();
+    sum / 3f64.sqrt()
+}
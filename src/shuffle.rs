@@ -0,0 +1,98 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::metric::Metric;
+use crate::noise::{blue_noise, BLUE_NOISE_SIZE};
+use crate::options::MosaicOptions;
+use image::RgbImage;
+
+/// The fewest cells a flat region needs for its tiles to be rearranged.
+const MIN_FLAT_CELLS: usize = 4;
+
+/// Rearrange the [`Tile`](crate::Tile)s assigned to the cells of each flat
+/// region of a grid (see [`MosaicOptions::flat_shuffle`]), so that each
+/// one is spread evenly over the region rather than in stripes.
+///
+/// `cells` holds the index of the tile assigned to each cell and `colors`
+/// the color of each cell, in row-major order, for a grid with its top left
+/// cell at `origin` in the whole mosaic. The number of cells using each tile
+/// in a region is kept; only their positions change, following a blue-noise
+/// ranking of the cells (seeded by the options' [`seed`](MosaicOptions::seed)).
+pub(crate) fn shuffle_flat(
+    cells: &mut [usize],
+    colors: &RgbImage,
+    options: &MosaicOptions,
+    origin: (u32, u32),
+) {
+    let (w, h) = colors.dimensions();
+    let size = (w.min(BLUE_NOISE_SIZE), h.min(BLUE_NOISE_SIZE));
+    let ranks = blue_noise(size, options.seed);
+    let rank = |i: usize| {
+        let (x, y) = (origin.0 + i as u32 % w, origin.1 + i as u32 / w);
+        ranks[((y % size.1) * size.0 + x % size.0) as usize]
+    };
+
+    for mut region in flat_regions(colors, options.metric, options.flat_shuffle) {
+        if region.len() < MIN_FLAT_CELLS {
+            continue;
+        }
+        // each tile takes a run of consecutive ranks, and so a set of
+        // cells spread evenly over the region
+        let mut tiles: Vec<usize> = region.iter().map(|&i| cells[i]).collect();
+        tiles.sort_unstable();
+        region.sort_by_key(|&i| (rank(i), i));
+        for (i, tile) in region.into_iter().zip(tiles) {
+            cells[i] = tile;
+        }
+    }
+}
+
+/// Find the regions of near-constant color in an image: groups of
+/// (horizontally or vertically) adjacent pixels within `epsilon` of the
+/// first pixel of the group (in row-major order).
+///
+/// Returns the (row-major) indices of the pixels in each region.
+fn flat_regions(colors: &RgbImage, metric: Metric, epsilon: f32) -> Vec<Vec<usize>> {
+    let (w, h) = (colors.width() as usize, colors.height() as usize);
+    let pixels: Vec<_> = colors.pixels().collect();
+    let mut visited = vec![false; w * h];
+    let mut regions = Vec::new();
+    for start in 0..w * h {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut region = vec![start];
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % w, i / w);
+            let neighbors = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for j in neighbors.into_iter().flatten() {
+                if !visited[j] && metric.distance(pixels[start], pixels[j]) <= epsilon {
+                    visited[j] = true;
+                    region.push(j);
+                    stack.push(j);
+                }
+            }
+        }
+        regions.push(region);
+    }
+    regions
+}
@@ -0,0 +1,273 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::plan::MosaicPlan;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Statistics describing how a [`MosaicPlan`] uses its
+/// [`Tile`](crate::Tile)s, computed from the plan alone (without the
+/// source image or the tiles' pixels).
+///
+/// See [`MosaicPlan::stats`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStats {
+    /// The number of cells which show a tile (i.e., which are not background).
+    pub cells: usize,
+    /// The number of background cells.
+    pub background: usize,
+    /// The number of tiles recorded in the plan.
+    pub tiles: usize,
+    /// The number of tiles which no cell uses.
+    pub unused: usize,
+    /// The most used tiles, most used first (ties go to the lower index).
+    pub top: Vec<TileUsage>,
+    /// How closely the cells using the most used tile are clustered, if
+    /// any cells use a tile.
+    pub clustering: Option<Clustering>,
+    /// The distribution of the distances from each cell's color to its
+    /// tile's average color, if the plan records them (see
+    /// [`MosaicPlan::distances`]).
+    pub distances: Option<DistanceStats>,
+    /// How many cells use the tiles in each category, by name, if any
+    /// tiles have a [category](crate::Tile::category).
+    #[serde(default)]
+    pub categories: Vec<CategoryUsage>,
+}
+
+/// How many cells of a [`MosaicPlan`] use a single tile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileUsage {
+    /// The index of the tile in [`MosaicPlan::tiles`].
+    pub index: usize,
+    /// The hash identifying the tile (see [`TileRef`](crate::TileRef)).
+    pub hash: String,
+    /// The number of cells using the tile.
+    pub count: usize,
+}
+
+/// How many cells of a [`MosaicPlan`] use the tiles in one category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryUsage {
+    /// The name of the category.
+    pub name: String,
+    /// The number of cells using a tile in the category.
+    pub count: usize,
+}
+
+/// How closely the cells using a tile are clustered together.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Clustering {
+    /// The index of the tile in [`MosaicPlan::tiles`].
+    pub tile: usize,
+    /// The fraction of the (horizontal and vertical) neighbors of the
+    /// tile's cells which use the same tile. Background cells are ignored.
+    pub same_neighbors: f64,
+    /// The fraction of neighbors expected to use the same tile if the
+    /// tile's cells were scattered at random. Values of
+    /// [`same_neighbors`](Clustering::same_neighbors) well above this
+    /// mean the tile forms visible patches.
+    pub expected: f64,
+}
+
+/// The distribution of a set of distances.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DistanceStats {
+    /// The mean distance.
+    pub mean: f32,
+    /// The smallest distance.
+    pub min: f32,
+    /// The median distance.
+    pub p50: f32,
+    /// The distance below which 90% of the distances fall.
+    pub p90: f32,
+    /// The distance below which 99% of the distances fall.
+    pub p99: f32,
+    /// The largest distance.
+    pub max: f32,
+}
+
+impl PlanStats {
+    /// Compute the statistics of a plan, listing its `top` most used tiles.
+    pub(crate) fn new(plan: &MosaicPlan, top: usize) -> Self {
+        let shown = |i: &usize| !plan.is_skipped_at(*i);
+        let mut usage = vec![0; plan.tiles().len()];
+        for (_, &idx) in plan.cells().iter().enumerate().filter(|(i, _)| shown(i)) {
+            usage[idx] += 1;
+        }
+        let cells: usize = usage.iter().sum();
+
+        let mut ranked: Vec<usize> = (0..usage.len()).filter(|&i| usage[i] > 0).collect();
+        // sort_by is stable, so ties remain in index order
+        ranked.sort_by(|&a, &b| usage[b].cmp(&usage[a]));
+        let clustering = ranked
+            .first()
+            .map(|&tile| clustering(plan, tile, usage[tile], cells));
+
+        let distances: Vec<f32> = (plan.distances().iter().enumerate())
+            .filter(|(i, _)| shown(i))
+            .map(|(_, &d)| d)
+            .collect();
+
+        let mut categories: Vec<CategoryUsage> = Vec::new();
+        for (tile, &count) in plan.tiles().iter().zip(&usage) {
+            let Some(name) = &tile.category else {
+                continue;
+            };
+            match categories.iter_mut().find(|c| c.name == *name) {
+                Some(category) => category.count += count,
+                None => categories.push(CategoryUsage {
+                    name: name.clone(),
+                    count,
+                }),
+            }
+        }
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            cells,
+            background: plan.cells().len() - cells,
+            tiles: usage.len(),
+            unused: usage.iter().filter(|&&n| n == 0).count(),
+            top: ranked
+                .into_iter()
+                .take(top)
+                .map(|index| TileUsage {
+                    index,
+                    hash: plan.tiles()[index].hash.clone(),
+                    count: usage[index],
+                })
+                .collect(),
+            clustering,
+            distances: DistanceStats::new(distances),
+            categories,
+        }
+    }
+}
+
+/// Measure how closely the `count` cells using `tile` (of the plan's
+/// `cells` non-background cells) are clustered.
+fn clustering(plan: &MosaicPlan, tile: usize, count: usize, cells: usize) -> Clustering {
+    let (columns, rows) = plan.grid_size();
+    let (mut same, mut total) = (0usize, 0usize);
+    for y in 0..rows {
+        for x in 0..columns {
+            if plan.tile_at(x, y) != tile || plan.is_skipped(x, y) {
+                continue;
+            }
+            let neighbors = [
+                (x.checked_sub(1), Some(y)),
+                (Some(x + 1).filter(|&x| x < columns), Some(y)),
+                (Some(x), y.checked_sub(1)),
+                (Some(x), Some(y + 1).filter(|&y| y < rows)),
+            ];
+            for (nx, ny) in neighbors {
+                if let (Some(nx), Some(ny)) = (nx, ny) {
+                    if !plan.is_skipped(nx, ny) {
+                        total += 1;
+                        same += (plan.tile_at(nx, ny) == tile) as usize;
+                    }
+                }
+            }
+        }
+    }
+
+    let fraction = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+    Clustering {
+        tile,
+        same_neighbors: fraction(same, total),
+        // every other cell is equally likely to be a neighbor
+        expected: fraction(count - 1, cells - 1),
+    }
+}
+
+impl DistanceStats {
+    /// Summarize a set of distances, if there are any.
+    fn new(mut distances: Vec<f32>) -> Option<Self> {
+        if distances.is_empty() {
+            return None;
+        }
+        distances.sort_by(f32::total_cmp);
+        // the nearest-rank method
+        let percentile = |p: f32| {
+            let rank = ((p / 100.0) * distances.len() as f32).ceil() as usize;
+            distances[rank.saturating_sub(1)]
+        };
+        let sum: f64 = distances.iter().map(|&d| d as f64).sum();
+        Some(Self {
+            mean: (sum / distances.len() as f64) as f32,
+            min: distances[0],
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: distances[distances.len() - 1],
+        })
+    }
+}
+
+impl fmt::Display for PlanStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} cells", self.cells)?;
+        if self.background > 0 {
+            write!(f, " ({} background)", self.background)?;
+        }
+        writeln!(f, ", {} tiles, {} never used", self.tiles, self.unused)?;
+
+        if !self.top.is_empty() {
+            writeln!(f, "Most used tiles:")?;
+            for (rank, usage) in self.top.iter().enumerate() {
+                writeln!(
+                    f,
+                    "  {:>3}. tile {:<5} {}  {:>7} cells ({:.1}%)",
+                    rank + 1,
+                    usage.index,
+                    usage.hash,
+                    usage.count,
+                    100.0 * usage.count as f64 / self.cells as f64
+                )?;
+            }
+        }
+        if !self.categories.is_empty() {
+            writeln!(f, "Cells by category:")?;
+            for category in &self.categories {
+                writeln!(
+                    f,
+                    "  {:<16} {:>7} cells ({:.1}%)",
+                    category.name,
+                    category.count,
+                    100.0 * category.count as f64 / self.cells as f64
+                )?;
+            }
+        }
+        if let Some(c) = &self.clustering {
+            writeln!(
+                f,
+                "Clustering of tile {}: {:.1}% of its neighbors are the same tile \
+                 ({:.1}% if scattered at random)",
+                c.tile,
+                100.0 * c.same_neighbors,
+                100.0 * c.expected
+            )?;
+        }
+        match &self.distances {
+            Some(d) => write!(
+                f,
+                "Match distances: mean {:.2}, min {:.2}, p50 {:.2}, p90 {:.2}, p99 {:.2}, max {:.2}",
+                d.mean, d.min, d.p50, d.p90, d.p99, d.max
+            ),
+            None => write!(f, "Match distances: not recorded in this plan"),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Keep the pixels of tiles in a memory-mapped file.
+//!
+//! The pixels of a large tile library (e.g., 100,000 tiles at 64px) may not
+//! fit in memory, even though only their averages are needed to match them
+//! to cells. A [`TileStore`] writes the pixels of each [`Tile`](crate::Tile)
+//! to a single file, as fixed-size records of raw RGB, and maps it into
+//! memory, so the OS pages tiles in and out as they are placed.
+
+use crate::plan::{Inner, MosaicPlan};
+use crate::rect::Rect;
+use crate::tiles::TileSet;
+use image::{ImageBuffer, Rgb, RgbImage};
+use memmap2::Mmap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// The first bytes of a tile store file (including the format version).
+const MAGIC: &[u8; 8] = b"TILRTS01";
+
+/// The size of the header of a tile store file: the magic bytes, then the
+/// side length of the tiles and the number of tiles (as little-endian `u32`s).
+const HEADER_LEN: usize = 16;
+
+/// The pixels of a set of [`Tile`](crate::Tile)s, kept in a memory-mapped
+/// file (see [`TileSet::store`]).
+#[derive(Debug)]
+pub struct TileStore {
+    /// The mapped file (shared with the [`Tile`](crate::Tile)s whose
+    /// pixels are in it).
+    map: Arc<Mmap>,
+    /// The side length of the tiles in the store.
+    side_len: u32,
+    /// The number of tiles in the store.
+    len: usize,
+}
+
+impl TileStore {
+    /// Write the pixels of every [`Tile`](crate::Tile) in `tiles` (in
+    /// order) to a new file at `path`, and map it.
+    ///
+    /// # Errors
+    /// This function returns an error if the file can't be written, or if
+    /// the pixels of the tiles have already been moved to a store.
+    pub fn create(path: &Path, tiles: &TileSet) -> Result<Self, Box<dyn Error>> {
+        if tiles.iter().any(|t| t.is_stored()) {
+            return Err("Tile pixels have already been moved to a TileStore".into());
+        }
+        let side_len = tiles.tile_side_len();
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&side_len.to_le_bytes())?;
+        writer.write_all(&(tiles.len() as u32).to_le_bytes())?;
+        for tile in tiles.iter() {
+            writer.write_all(tile.pixels().as_raw())?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        Self::open(path)
+    }
+
+    /// Map a file previously written with [`create`](TileStore::create).
+    ///
+    /// # Errors
+    /// This function returns an error if the file can't be read, or if it
+    /// isn't a complete tile store.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(path)?;
+        // SAFETY: the file is only read through the map; as with any
+        // mapped file, it must not be modified while the store is in use.
+        let map = unsafe { Mmap::map(&file)? };
+
+        if map.len() < HEADER_LEN || &map[..MAGIC.len()] != MAGIC {
+            return Err(format!("{} is not a tile store", path.display()).into());
+        }
+        let side_len = u32::from_le_bytes(map[8..12].try_into()?);
+        let len = u32::from_le_bytes(map[12..16].try_into()?) as usize;
+        let expected = record_len(side_len)
+            .and_then(|n| n.checked_mul(len))
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        if expected != Some(map.len()) {
+            return Err(format!(
+                "Tile store {} is {} bytes, which doesn't fit {} {}px tiles",
+                path.display(),
+                map.len(),
+                len,
+                side_len
+            )
+            .into());
+        }
+
+        Ok(Self {
+            map: Arc::new(map),
+            side_len,
+            len,
+        })
+    }
+
+    /// Get the number of tiles in this store.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether this store contains no tiles.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the side length of the tiles in this store.
+    pub fn tile_side_len(&self) -> u32 {
+        self.side_len
+    }
+
+    /// Get a view of the pixels of the tile at the given index, directly
+    /// in the mapped file.
+    pub fn get(&self, idx: usize) -> Option<ImageBuffer<Rgb<u8>, &[u8]>> {
+        if idx >= self.len {
+            return None;
+        }
+        let start = self.start(idx);
+        let end = start + record_len(self.side_len)?;
+        ImageBuffer::from_raw(self.side_len, self.side_len, &self.map[start..end])
+    }
+
+    /// Get the offset of the pixels of the tile at the given index in the
+    /// file (which [`open`](TileStore::open) checked is within it).
+    fn start(&self, idx: usize) -> usize {
+        HEADER_LEN + idx * record_len(self.side_len).unwrap_or_default()
+    }
+}
+
+/// Get the size (in bytes) of the pixels of one tile in a store, if it
+/// doesn't overflow.
+fn record_len(side_len: u32) -> Option<usize> {
+    (side_len as usize)
+        .checked_mul(side_len as usize)?
+        .checked_mul(3)
+}
+
+impl TileSet {
+    /// Move the pixels of every [`Tile`](crate::Tile) in this set to a new
+    /// [`TileStore`] at `path`, keeping only their averages in memory.
+    ///
+    /// The set can still be used to build a [`MosaicPlan`] (e.g., with
+    /// [`MosaicPlan::for_image`]), which is then rendered from the store
+    /// with [`render_stored`](MosaicPlan::render_stored). The pixels of the
+    /// tiles are read from the store by [`Tile::pixels`](crate::Tile::pixels),
+    /// and copied out of it by anything which needs them in memory (e.g.,
+    /// [`Tile::img`](crate::Tile::img), or [`MosaicPlan::render`]).
+    ///
+    /// # Errors
+    /// See [`TileStore::create`]; the pixels are kept if the store can't
+    /// be created.
+    pub fn store(&mut self, path: &Path) -> Result<TileStore, Box<dyn Error>> {
+        let store = TileStore::create(path, self)?;
+        self.map_pixels(&store.map, |i| store.start(i));
+        Ok(store)
+    }
+}
+
+impl MosaicPlan {
+    /// Render the mosaic described by this plan, like
+    /// [`render`](MosaicPlan::render), with the pixels of each
+    /// [`Tile`](crate::Tile) read from a [`TileStore`].
+    ///
+    /// The store should be the one made from the [`TileSet`] used to build
+    /// the plan (see [`TileSet::store`]); the result is then identical to
+    /// rendering the plan with that set before its pixels were stored.
+    ///
+    /// # Panics
+    /// This function panics if the side length of the tiles in the store
+    /// does not match the plan, or if the store does not contain every
+    /// tile the plan refers to.
+    pub fn render_stored(&self, store: &TileStore) -> RgbImage {
+        let (mos_x, mos_y) = self.output_size();
+        let mut mosaic = Inner(RgbImage::new(mos_x, mos_y));
+        let tile_img = |idx, _| store.get(idx).expect("No tile for cell");
+        let matte = Rgb(self.options().matte);
+        let window = Rect::new(0, 0, mos_x, mos_y);
+        self.place(
+            store.tile_side_len(),
+            tile_img,
+            matte,
+            &mut mosaic,
+            (0, 0),
+            window,
+            true,
+            None,
+        );
+
+        mosaic.0
+    }
+}
@@ -0,0 +1,192 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use image::Rgb;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Statistics describing the average colors of the [`Tile`](crate::Tile)s
+/// in a [`TileSet`](crate::TileSet).
+///
+/// See [`TileSet::summary`](crate::TileSet::summary).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TileSetSummary {
+    /// The number of tiles in the set.
+    pub count: usize,
+    /// The side length of each tile (in pixels).
+    pub side_len: u32,
+    /// The smallest average red, green, and blue values of any tile.
+    pub min: [u8; 3],
+    /// The largest average red, green, and blue values of any tile.
+    pub max: [u8; 3],
+    /// The mean of the average red, green, and blue values of the tiles.
+    pub mean: [f32; 3],
+    /// The (population) standard deviation of the average red, green,
+    /// and blue values of the tiles. Low values mean the tiles are all
+    /// much the same color.
+    pub std_dev: [f32; 3],
+    /// The number of tiles whose average color falls in each [`HueBucket`]
+    /// (including those with no tiles).
+    pub hues: BTreeMap<HueBucket, usize>,
+}
+
+/// A coarse range of hues, used to group colors in a [`TileSetSummary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HueBucket {
+    /// Black, white, and grays (colors with little chroma).
+    Neutral,
+    /// Hues within 30° of red (0°).
+    Red,
+    /// Hues within 30° of yellow (60°).
+    Yellow,
+    /// Hues within 30° of green (120°).
+    Green,
+    /// Hues within 30° of cyan (180°).
+    Cyan,
+    /// Hues within 30° of blue (240°).
+    Blue,
+    /// Hues within 30° of magenta (300°).
+    Magenta,
+}
+
+impl HueBucket {
+    /// Every bucket, in order.
+    pub const ALL: [HueBucket; 7] = [
+        Self::Neutral,
+        Self::Red,
+        Self::Yellow,
+        Self::Green,
+        Self::Cyan,
+        Self::Blue,
+        Self::Magenta,
+    ];
+
+    /// Colors whose chroma (the difference between their largest and
+    /// smallest channels) is below this are [`Neutral`](HueBucket::Neutral).
+    const MIN_CHROMA: u8 = 16;
+
+    /// Get the bucket containing the hue of the given color.
+    pub fn of(color: &Rgb<u8>) -> Self {
+        let [r, g, b] = color.0;
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        let chroma = max - min;
+        if chroma < Self::MIN_CHROMA {
+            return Self::Neutral;
+        }
+
+        let (r, g, b, c) = (r as f32, g as f32, b as f32, chroma as f32);
+        let hue = if max == color.0[0] {
+            60.0 * ((g - b) / c)
+        } else if max == color.0[1] {
+            60.0 * (2.0 + (b - r) / c)
+        } else {
+            60.0 * (4.0 + (r - g) / c)
+        };
+        // each bucket is centered on a multiple of 60°
+        match ((hue + 30.0).rem_euclid(360.0) / 60.0) as u32 {
+            0 => Self::Red,
+            1 => Self::Yellow,
+            2 => Self::Green,
+            3 => Self::Cyan,
+            4 => Self::Blue,
+            _ => Self::Magenta,
+        }
+    }
+}
+
+impl TileSetSummary {
+    /// Summarize a set of tiles with the given side length from
+    /// their average colors, in a single pass over them.
+    pub(crate) fn new<'a>(avgs: impl IntoIterator<Item = &'a Rgb<u8>>, side_len: u32) -> Self {
+        let mut count = 0;
+        let (mut min, mut max) = ([u8::MAX; 3], [0; 3]);
+        let (mut sum, mut sum_sq) = ([0.0f64; 3], [0.0f64; 3]);
+        let mut hues: BTreeMap<HueBucket, usize> = HueBucket::ALL.iter().map(|&h| (h, 0)).collect();
+
+        for avg in avgs {
+            count += 1;
+            for c in 0..3 {
+                let v = avg.0[c];
+                min[c] = min[c].min(v);
+                max[c] = max[c].max(v);
+                sum[c] += v as f64;
+                sum_sq[c] += (v as f64).powi(2);
+            }
+            *hues.entry(HueBucket::of(avg)).or_default() += 1;
+        }
+
+        if count == 0 {
+            min = [0; 3];
+        }
+        let n = count.max(1) as f64;
+        let mean = sum.map(|s| s / n);
+        let mut std_dev = [0.0; 3];
+        for c in 0..3 {
+            // clamp tiny negative variances caused by rounding
+            std_dev[c] = (sum_sq[c] / n - mean[c].powi(2)).max(0.0).sqrt() as f32;
+        }
+
+        Self {
+            count,
+            side_len,
+            min,
+            max,
+            mean: mean.map(|m| m as f32),
+            std_dev,
+            hues,
+        }
+    }
+}
+
+impl fmt::Display for TileSetSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} tiles, {}x{}px",
+            self.count, self.side_len, self.side_len
+        )?;
+        writeln!(f, "Average colors:")?;
+        writeln!(f, "          min  max     mean  std dev")?;
+        for (c, name) in ["red", "green", "blue"].iter().enumerate() {
+            writeln!(
+                f,
+                "  {:<6} {:>4} {:>4} {:>8.2} {:>8.2}",
+                name, self.min[c], self.max[c], self.mean[c], self.std_dev[c]
+            )?;
+        }
+        write!(f, "Hues:")?;
+        for (hue, count) in &self.hues {
+            write!(f, " {} {}", hue, count)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HueBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Neutral => "neutral",
+            Self::Red => "red",
+            Self::Yellow => "yellow",
+            Self::Green => "green",
+            Self::Cyan => "cyan",
+            Self::Blue => "blue",
+            Self::Magenta => "magenta",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,3 +1,11 @@
+//! The [`Tile`]s a mosaic is built from, and the [`TileSet`]s which hold
+//! them.
+//!
+//! A [`TileSet`] can be built once (e.g., with [`TileSet::from`] a set of
+//! images, or [`TileSet::new`] from [`Tile`]s) and reused for any number of
+//! [`Mosaic`](crate::Mosaic)s, since anything which implements
+//! [`IntoTileSet`] can be passed to [`Mosaic::new`](crate::Mosaic::new).
+
 // tilr - A program to build an image from a set of image 'tiles'.
 // Copyright (C) 2023  Charles German <5donuts@pm.me>
 //
@@ -13,17 +21,73 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use image::imageops::FilterType;
-use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use crate::assign::assign;
+use crate::color::{Lab, Oklab};
+use crate::coverage::CoverageReport;
+use crate::descriptor::{block_averages, center_average, Descriptor};
+use crate::error::{check_cancelled, Error};
+use crate::fit::TileFit;
+use crate::kdtree::KdTree;
+use crate::metric::Metric;
+use crate::noise::{dither, ErrorDiffusion};
+use crate::options::MosaicOptions;
+use crate::postprocess::PostProcess;
+use crate::quota::Quotas;
+use crate::search::ApproxSearch;
+use crate::summary::TileSetSummary;
+use crate::utils::{composite, flatten_alpha, fnv1a, is_gray, LoadReport};
+use image::imageops::{self, FilterType};
+use image::{
+    DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba, RgbaImage,
+};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
+#[cfg(feature = "rayon")]
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
+
+/// The error when building a [`TileSet`] from no images.
+const EMPTY: &str = "Cannot build a tile set from no images";
+
+/// An image to build a [`Tile`] from, with its label, category and source
+/// (if any).
+type TileImage<'a> = (
+    &'a DynamicImage,
+    Option<u8>,
+    Option<&'a str>,
+    Option<&'a Path>,
+);
+
+/// Where the pixels of a [`Tile`] are kept
+#[derive(Debug, Clone)]
+enum Pixels {
+    /// In memory.
+    Owned(RgbImage),
+    /// In a [`TileStore`](crate::TileStore): its mapped file, and the
+    /// offset of the Tile's pixels in it.
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<Mmap>, usize),
+}
 
 /// Represents a single tile in a set; used to map
 /// between pixels in the original image and images
 /// in the [`TileSet`](super::TileSet).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Tile {
-    /// The underlying image to use for this Tile.
-    img: RgbImage,
+    /// The pixels of the underlying image to use for this Tile.
+    img: Pixels,
+    /// The opacity of each pixel of the underlying image, if this Tile
+    /// keeps its transparency (see [`TileSet::with_alpha`]).
+    alpha: Option<GrayImage>,
+    /// The side length of the underlying image.
+    side_len: u32,
+    /// Whether every pixel of the underlying image is a shade of gray.
+    gray: bool,
     /// The average pixel in the underlying image.
     ///
     /// This is computed only once when the tile is
@@ -31,6 +95,40 @@ pub struct Tile {
     /// images being used as tiles and making the mapping
     /// between image pixels and Tiles very slow.
     avg: Rgb<u8>,
+    /// The average pixel in the underlying image, in the [`Oklab`] color
+    /// space (so [`Metric::Oklab`] needn't convert it for every pixel).
+    oklab: Oklab,
+    /// The average pixel in the underlying image, in the [`Lab`] color
+    /// space (so [`Metric::Lab`] and [`Metric::CieDe2000`] needn't convert
+    /// it for every pixel).
+    lab: Lab,
+    /// The average pixel in each quadrant of the underlying image (see
+    /// [`Descriptor::Quadrants`]).
+    quadrants: Vec<Rgb<u8>>,
+    /// The average pixel in each block of a 3x3 grid over the underlying
+    /// image (see [`Descriptor::Grid3`]).
+    grid3: Vec<Rgb<u8>>,
+    /// The average pixel in the underlying image, weighted towards its
+    /// center (see [`Descriptor::Center`]).
+    center: Rgb<u8>,
+    /// A hash of the pixels in the underlying image, used
+    /// to identify this Tile in a [`MosaicPlan`](crate::MosaicPlan).
+    hash: u64,
+    /// Whether this Tile was generated by tilr (e.g., to fill a gap
+    /// in the colors covered by a [`TileSet`]) rather than loaded
+    /// from an image.
+    synthetic: bool,
+    /// The weight of this Tile when choosing between Tiles; see
+    /// [`TileSet::set_weight`].
+    weight: f32,
+    /// The label of the region of the source image this Tile may be
+    /// placed in, if any; see [`TileSet::with_labels`].
+    label: Option<u8>,
+    /// The category of this Tile, if any; see [`TileSet::with_categories`].
+    category: Option<String>,
+    /// The name of (or path to) the image this Tile was loaded from, if
+    /// known; see [`Tile::with_source`].
+    source: Option<PathBuf>,
 }
 
 impl Tile {
@@ -38,28 +136,269 @@ impl Tile {
     /// of the given pixel and the average pixel color
     /// of this Tile.
     pub fn dist_to(&self, px: &Rgb<u8>) -> f32 {
-        // color values for the given px
-        let p_r = px.0[0] as i32;
-        let p_g = px.0[1] as i32;
-        let p_b = px.0[2] as i32;
+        Metric::Rgb.distance(px, &self.avg)
+    }
 
-        // color values for the avg px color of the tile
-        let q_r = self.avg.0[0] as i32;
-        let q_g = self.avg.0[1] as i32;
-        let q_b = self.avg.0[2] as i32;
+    /// Get the average pixel color of this Tile.
+    pub fn avg(&self) -> &Rgb<u8> {
+        &self.avg
+    }
+
+    /// Get the average pixel color of this Tile in the [`Oklab`] color space.
+    pub fn oklab(&self) -> Oklab {
+        self.oklab
+    }
 
-        // Euclidean distance
-        (((p_r - q_r).pow(2) + (p_g - q_g).pow(2) + (p_b - q_b).pow(2)) as f32).sqrt()
+    /// Get the average pixel color of this Tile in the [`Lab`] color space.
+    pub fn lab(&self) -> Lab {
+        self.lab
+    }
+
+    /// Get the average colors of the blocks of this Tile used by the
+    /// given [`Descriptor`], in row-major order.
+    pub fn descriptor(&self, descriptor: Descriptor) -> &[Rgb<u8>] {
+        match descriptor {
+            Descriptor::Mean => std::slice::from_ref(&self.avg),
+            Descriptor::Quadrants => &self.quadrants,
+            Descriptor::Grid3 => &self.grid3,
+            Descriptor::Center => std::slice::from_ref(&self.center),
+        }
+    }
+
+    /// Get a hash of the pixels in this Tile.
+    ///
+    /// The hash is stable across runs and platforms, so it can be used
+    /// to check that a [`MosaicPlan`](crate::MosaicPlan) is rendered with
+    /// the same Tiles it was built from.
+    pub fn content_hash(&self) -> u64 {
+        self.hash
     }
 
     /// Get the underlying image for this Tile.
-    pub fn img(&self) -> &RgbImage {
-        &self.img
+    ///
+    /// This is borrowed, unless the pixels of this Tile have been moved to
+    /// a `TileStore` (see [`is_stored`](Tile::is_stored)), in which case
+    /// they are copied out of it; use [`pixels`](Tile::pixels) to read
+    /// them without copying.
+    pub fn img(&self) -> Cow<'_, RgbImage> {
+        match &self.img {
+            Pixels::Owned(img) => Cow::Borrowed(img),
+            #[cfg(feature = "mmap")]
+            Pixels::Mapped(..) => {
+                let pixels = self.pixels();
+                let (w, h) = pixels.dimensions();
+                Cow::Owned(RgbImage::from_raw(w, h, pixels.into_raw().to_vec()).unwrap())
+            }
+        }
+    }
+
+    /// Get a view of the pixels of the underlying image for this Tile,
+    /// wherever they are kept (in memory, or in a `TileStore`).
+    pub fn pixels(&self) -> ImageBuffer<Rgb<u8>, &[u8]> {
+        let raw = match &self.img {
+            Pixels::Owned(img) => img.as_raw().as_slice(),
+            #[cfg(feature = "mmap")]
+            Pixels::Mapped(map, start) => {
+                let len = self.side_len as usize * self.side_len as usize * 3;
+                &map[*start..*start + len]
+            }
+        };
+        ImageBuffer::from_raw(self.side_len, self.side_len, raw).expect("Tile is incomplete")
+    }
+
+    /// Get the opacity of each pixel of this Tile, if it keeps its
+    /// transparency (see [`TileSet::with_alpha`]).
+    pub fn alpha(&self) -> Option<&GrayImage> {
+        self.alpha.as_ref()
+    }
+
+    /// Get the underlying image for this Tile with its transparency (or
+    /// fully opaque, if it doesn't keep any), e.g., to place it in a
+    /// transparent mosaic (see [`MosaicPlan::render_rgba`](crate::MosaicPlan::render_rgba)).
+    pub fn rgba(&self) -> RgbaImage {
+        let img = self.pixels();
+        RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+            let [r, g, b] = img.get_pixel(x, y).0;
+            let a = self
+                .alpha
+                .as_ref()
+                .map_or(u8::MAX, |a| a.get_pixel(x, y).0[0]);
+            Rgba([r, g, b, a])
+        })
+    }
+
+    /// Check whether the pixels of this Tile have been moved to a
+    /// `TileStore`, leaving only its averages in memory.
+    pub fn is_stored(&self) -> bool {
+        !matches!(self.img, Pixels::Owned(_))
+    }
+
+    /// Get a grayscale copy of the image for this tile.
+    pub fn luma(&self) -> GrayImage {
+        image::imageops::grayscale(&self.pixels())
+    }
+
+    /// Check whether every pixel of this tile is a shade of gray.
+    pub fn is_gray(&self) -> bool {
+        self.gray
     }
 
     /// Get the side length of this Tile.
     pub fn side_len(&self) -> u32 {
-        self.img.dimensions().0
+        self.side_len
+    }
+
+    /// Check whether this Tile was generated by tilr rather than
+    /// loaded from an image (see [`TileSet::fill_gaps`]).
+    pub fn is_synthetic(&self) -> bool {
+        self.synthetic
+    }
+
+    /// Get the weight of this Tile (`1.0` unless set with
+    /// [`TileSet::set_weight`]).
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Check whether this Tile may be placed in a cell with the given
+    /// label (where cells without a label may hold any Tile).
+    fn in_region(&self, label: Option<u8>) -> bool {
+        label.is_none() || self.label == label
+    }
+
+    /// Get the label of the region of the source image this Tile may be
+    /// placed in (see [`TileSet::with_labels`]), if any.
+    pub fn label(&self) -> Option<u8> {
+        self.label
+    }
+
+    /// Get the category of this Tile (see [`TileSet::with_categories`]),
+    /// if any.
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    /// Get the name of (or path to) the image this Tile was loaded from,
+    /// if it's known (see [`TileSet::from_report`]).
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Record the name of (or path to) the image this Tile was loaded
+    /// from, e.g., to tell which files were placed where in a mosaic (see
+    /// [`Mosaic::placements`](crate::Mosaic::placements)).
+    ///
+    /// The source doesn't change how the Tile is matched or placed.
+    pub fn with_source(mut self, source: impl Into<PathBuf>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Compute the score of this Tile for the given pixel (lower is better):
+    /// the distance between the pixel and the average color of this Tile,
+    /// divided by the Tile's weight.
+    pub fn score(&self, px: &Rgb<u8>, metric: Metric) -> f32 {
+        match metric {
+            Metric::Oklab => self.score_oklab(&Oklab::from(px)),
+            Metric::Lab | Metric::CieDe2000 => self.score_lab(&Lab::from(px), metric),
+            _ => metric.distance(px, &self.avg) / self.weight,
+        }
+    }
+
+    /// Compute the [score](Tile::score) of this Tile for a pixel which has
+    /// already been converted to the [`Oklab`] color space, using
+    /// [`Metric::Oklab`].
+    fn score_oklab(&self, px: &Oklab) -> f32 {
+        px.distance(&self.oklab) / self.weight
+    }
+
+    /// Compute the [score](Tile::score) of this Tile for a pixel which has
+    /// already been converted to the [`Lab`] color space, using
+    /// [`Metric::Lab`] or [`Metric::CieDe2000`].
+    fn score_lab(&self, px: &Lab, metric: Metric) -> f32 {
+        let distance = match metric {
+            Metric::CieDe2000 => px.ciede2000(&self.lab),
+            _ => px.distance(&self.lab),
+        };
+        distance / self.weight
+    }
+
+    /// Compute the score of this Tile for the given descriptor of a cell
+    /// (lower is better), like [`score`](Tile::score).
+    pub fn score_descriptor(
+        &self,
+        blocks: &[Rgb<u8>],
+        descriptor: Descriptor,
+        metric: Metric,
+    ) -> f32 {
+        if descriptor == Descriptor::Mean {
+            return self.score(&blocks[0], metric);
+        }
+        Descriptor::distance(metric, blocks, self.descriptor(descriptor)) / self.weight
+    }
+
+    /// Build a synthetic Tile of a single solid color.
+    fn solid(color: Rgb<u8>, side_len: u32) -> Self {
+        let mut tile = Self::from(RgbImage::from_pixel(side_len, side_len, color));
+        tile.synthetic = true;
+        tile
+    }
+
+    /// Build a synthetic Tile of a single color with subtle noise, seeded
+    /// by `seed` and the color (so tiles of different colors differ).
+    fn noisy(color: Rgb<u8>, side_len: u32, seed: u64) -> Self {
+        let seed = seed ^ fnv1a(&color.0);
+        let img = RgbImage::from_fn(side_len, side_len, |x, y| {
+            dither(&color, (x, y), seed, GapFill::NOISE_AMPLITUDE)
+        });
+        let mut tile = Self::from(img);
+        tile.synthetic = true;
+        tile
+    }
+
+    /// Build a [`Tile`] which keeps its transparency from its pixels
+    /// (whose transparent parts are already filled in) and their opacity.
+    fn with_alpha(img: RgbImage, alpha: GrayImage) -> Self {
+        let hash = fnv1a(&[img.as_raw().as_slice(), alpha.as_raw()].concat());
+        Self {
+            hash,
+            alpha: Some(alpha),
+            ..Self::from(img)
+        }
+    }
+}
+
+impl From<RgbaImage> for Tile {
+    /// Build a [`Tile`] from an [`RgbaImage`], keeping its transparency.
+    ///
+    /// The averages of the Tile are weighted by the opacity of its pixels,
+    /// so it's matched by the color of its opaque parts, and transparent
+    /// pixels are filled with its average color (see [`img`](Tile::img)).
+    /// Fully opaque images are built like an [`RgbImage`].
+    fn from(img: RgbaImage) -> Self {
+        let alpha = GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            Luma([img.get_pixel(x, y).0[3]])
+        });
+        let rgb = DynamicImage::ImageRgba8(img).into_rgb8();
+        if alpha.pixels().all(|a| a.0[0] == u8::MAX) {
+            return Self::from(rgb);
+        }
+
+        // the average color of the pixels, weighted by their opacity
+        let mut sums = [0u64; 3];
+        let mut total = 0u64;
+        for (px, a) in rgb.pixels().zip(alpha.pixels()) {
+            let a = a.0[0] as u64;
+            for (sum, &c) in sums.iter_mut().zip(&px.0) {
+                *sum += c as u64 * a;
+            }
+            total += a;
+        }
+        let avg = Rgb(sums.map(|sum| match total {
+            0 => 0,
+            total => ((sum + total / 2) / total) as u8,
+        }));
+        Self::with_alpha(composite(&rgb, &alpha, avg), alpha)
     }
 }
 
@@ -87,9 +426,56 @@ impl From<RgbImage> for Tile {
             ])
         };
 
+        let hash = fnv1a(img.as_raw());
+        let quadrants = block_averages(&img, 2);
+        let grid3 = block_averages(&img, 3);
+        let center = center_average(&img, TileSet::DEFAULT_CENTER_SIGMA);
+
         Self {
-            img,
+            side_len: img.width(),
+            gray: img.pixels().all(is_gray),
+            img: Pixels::Owned(img),
+            alpha: None,
             avg: avg_px_color,
+            oklab: Oklab::from(avg_px_color),
+            lab: Lab::from(avg_px_color),
+            quadrants,
+            grid3,
+            center,
+            hash,
+            synthetic: false,
+            weight: 1.0,
+            label: None,
+            category: None,
+            source: None,
+        }
+    }
+}
+
+/// How [`TileSet::fill_gaps_with`] and [`TileSet::fill_source_gaps`]
+/// make the synthetic [`Tile`]s they add.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GapFill {
+    /// Tiles of a single solid color.
+    #[default]
+    Solid,
+    /// Tiles of a single color with subtle noise (of up to
+    /// [`NOISE_AMPLITUDE`](GapFill::NOISE_AMPLITUDE) in each channel),
+    /// seeded by the given seed, so that they look less flat among tiles
+    /// loaded from photos.
+    Noise(u64),
+}
+
+impl GapFill {
+    /// The most each channel of a [`Noise`](GapFill::Noise) tile differs
+    /// from its color.
+    pub const NOISE_AMPLITUDE: u8 = 6;
+
+    /// Build a synthetic [`Tile`] of the given color.
+    fn tile(&self, color: Rgb<u8>, side_len: u32) -> Tile {
+        match *self {
+            Self::Solid => Tile::solid(color, side_len),
+            Self::Noise(seed) => Tile::noisy(color, side_len, seed),
         }
     }
 }
@@ -98,59 +484,964 @@ impl From<RgbImage> for Tile {
 ///
 /// This struct provides methods to map between the pixels in the original
 /// image to [`Tile`]s in order to build a [`Mosaic`](crate::Mosaic).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TileSet {
     /// The [`Tile`]s in this set.
     tiles: Vec<Tile>,
+    /// The largest share of the cells of a mosaic which the [`Tile`]s in
+    /// each category may fill; see [`TileSet::set_category_limit`].
+    limits: Vec<(String, f32)>,
+    /// The standard deviation of the Gaussian weighting the
+    /// [center](Descriptor::Center) of each [`Tile`], relative to its side
+    /// length; see [`TileSet::set_center_sigma`].
+    center_sigma: f32,
+    /// A k-d tree over the average colors of the [`Tile`]s, for
+    /// [`Metric::Rgb`], rebuilt whenever they change.
+    index: Option<KdTree>,
 }
 
 impl TileSet {
+    /// The number of divisions along each axis of the RGB cube used
+    /// when filling gaps with [`fill_gaps`](TileSet::fill_gaps).
+    pub const FILL_GAPS_DIVISIONS: u32 = 16;
+
+    /// The default standard deviation of the Gaussian used by
+    /// [`Descriptor::Center`], relative to the side length of the [`Tile`]s.
+    pub const DEFAULT_CENTER_SIGMA: f32 = 0.25;
+
+    /// Build a tile set from [`Tile`]s which have already been built (e.g.,
+    /// with `Tile::from` an [`RgbImage`], or taken from another set with
+    /// [`into_tiles`](TileSet::into_tiles)).
+    ///
+    /// Like [`TileSet::from`], the [`Tile`]s are sorted into a canonical
+    /// order, so the order of `tiles` does not matter.
+    ///
+    /// # Panics
+    /// This function panics if `tiles` is empty, or if the [`Tile`]s are
+    /// not all squares with the same side length.
+    pub fn new(tiles: Vec<Tile>) -> Self {
+        let side_len = tiles.first().expect(EMPTY).side_len();
+        let mut set = Self {
+            tiles: Vec::with_capacity(tiles.len()),
+            limits: Vec::new(),
+            center_sigma: Self::DEFAULT_CENTER_SIGMA,
+            index: None,
+        };
+        for tile in tiles {
+            set.push_unsorted(tile, side_len);
+        }
+        set.sort();
+        set
+    }
+
+    /// Add a [`Tile`] to this set.
+    ///
+    /// The set is kept in its canonical order, so the indices of the
+    /// [`Tile`]s after the new one change. The [center](Descriptor::Center)
+    /// of the new [`Tile`] is weighted with this set's
+    /// [`center_sigma`](TileSet::center_sigma).
+    ///
+    /// # Panics
+    /// This function panics if the [`Tile`] is not a square with the same
+    /// side length as the others in the set.
+    pub fn push(&mut self, tile: Tile) {
+        self.push_unsorted(tile, self.tile_side_len());
+        self.sort();
+    }
+
+    /// Take the [`Tile`]s out of this set, in order (e.g., to add them to
+    /// a new set with [`TileSet::new`]).
+    pub fn into_tiles(self) -> Vec<Tile> {
+        self.tiles
+    }
+
+    /// Add a [`Tile`] to the end of this set, checking that it's a square
+    /// with the given side length.
+    fn push_unsorted(&mut self, mut tile: Tile, side_len: u32) {
+        let (w, h) = match &tile.img {
+            Pixels::Owned(img) => img.dimensions(),
+            #[cfg(feature = "mmap")]
+            Pixels::Mapped(..) => (tile.side_len, tile.side_len),
+        };
+        if w != h || w != side_len {
+            panic!(
+                "Tile is {}x{} but the set requires {}px square tiles",
+                w, h, side_len
+            );
+        }
+        tile.center = center_average(&tile.pixels(), self.center_sigma);
+        self.tiles.push(tile);
+    }
+
     /// Get the side length of the [`Tile`]s (which are uniform squares)
     /// in this set.
     pub fn tile_side_len(&self) -> u32 {
         self.tiles[0].side_len()
     }
 
-    /// Create a mapping between pixels in the given image
-    /// and [`Tile`]s in the set.
-    pub fn map_to<'a>(&self, img: &'a RgbImage) -> HashMap<&'a Rgb<u8>, &Tile> {
-        let mut map = HashMap::new();
+    /// Get the number of [`Tile`]s in this set.
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Check whether this set contains no [`Tile`]s.
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// Get the [`Tile`] at the given index in this set.
+    pub fn get(&self, idx: usize) -> Option<&Tile> {
+        self.tiles.get(idx)
+    }
+
+    /// Iterate over the [`Tile`]s in this set.
+    pub fn iter(&self) -> std::slice::Iter<'_, Tile> {
+        self.tiles.iter()
+    }
+
+    /// Report how well the average colors of the [`Tile`]s in this
+    /// set cover the RGB color cube, using a grid with `divisions`
+    /// cells along each axis.
+    ///
+    /// # Panics
+    /// This function panics if `divisions` is zero or greater than 256.
+    pub fn coverage_report(&self, divisions: u32, metric: Metric) -> CoverageReport {
+        let avgs: Vec<Rgb<u8>> = self.tiles.iter().map(|t| t.avg).collect();
+        CoverageReport::new(&avgs, divisions, metric)
+    }
+
+    /// Summarize the average colors of the [`Tile`]s in this set (e.g.,
+    /// how much they vary, and which hues they cover).
+    pub fn summary(&self) -> TileSetSummary {
+        let side_len = self.tiles.first().map_or(0, Tile::side_len);
+        TileSetSummary::new(self.tiles.iter().map(|t| &t.avg), side_len)
+    }
+
+    /// Set the weight of the [`Tile`] at the given index in this set.
+    ///
+    /// When choosing a [`Tile`] for a pixel, each [`Tile`]'s
+    /// [score](Tile::score) is its distance to the pixel divided by its
+    /// weight, and the lowest score wins. So, a [`Tile`] with weight `2.0`
+    /// wins against an equally distant [`Tile`] with weight `1.0`, and can
+    /// win even when it is slightly further away. Weights default to `1.0`.
+    ///
+    /// Note that [`Tile`]s are kept in a canonical order (see
+    /// [`TileSet::from`]), so look up the index of a [`Tile`] with
+    /// [`iter`](TileSet::iter) rather than assuming the input order.
+    ///
+    /// # Panics
+    /// This function panics if `index` is out of bounds, or if `weight`
+    /// is not a positive, finite number.
+    pub fn set_weight(&mut self, index: usize, weight: f32) {
+        if !(weight.is_finite() && weight > 0.0) {
+            panic!("Tile weights must be positive and finite");
+        }
+        self.tiles[index].weight = weight;
+        if let Some(tree) = &mut self.index {
+            tree.reweigh(weight);
+        }
+    }
+
+    /// Set the standard deviation of the Gaussian weighting the colors of
+    /// each [`Tile`] in this set for [`Descriptor::Center`], as a fraction
+    /// of their side length (the default is
+    /// [`DEFAULT_CENTER_SIGMA`](TileSet::DEFAULT_CENTER_SIGMA)).
+    ///
+    /// Smaller values weight the middle of each [`Tile`] more heavily;
+    /// large values approach its plain average color.
+    ///
+    /// # Panics
+    /// This function panics if `sigma` is not a positive, finite number.
+    pub fn set_center_sigma(&mut self, sigma: f32) {
+        if !(sigma.is_finite() && sigma > 0.0) {
+            panic!("The center sigma must be positive and finite");
+        }
+        self.center_sigma = sigma;
+        for tile in self.tiles.iter_mut() {
+            tile.center = center_average(&tile.pixels(), sigma);
+        }
+    }
+
+    /// Get the standard deviation of the Gaussian used by
+    /// [`Descriptor::Center`]; see [`set_center_sigma`](TileSet::set_center_sigma).
+    pub fn center_sigma(&self) -> f32 {
+        self.center_sigma
+    }
+
+    /// Check whether every [`Tile`] in this set is grayscale, so that a
+    /// mosaic of a grayscale image can be rendered with
+    /// [`MosaicPlan::render_gray`](crate::MosaicPlan::render_gray).
+    pub fn is_grayscale(&self) -> bool {
+        self.tiles.iter().all(Tile::is_gray)
+    }
+
+    /// Check whether any [`Tile`] in this set has a weight other than `1.0`.
+    pub fn has_weights(&self) -> bool {
+        self.tiles.iter().any(|t| t.weight != 1.0)
+    }
+
+    /// Get the name of each category of the [`Tile`]s in this set (see
+    /// [`with_categories`](TileSet::with_categories)), in sorted order.
+    pub fn categories(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.tiles.iter().filter_map(Tile::category).collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Limit the [`Tile`]s in a category to at most the given share (in
+    /// `(0, 1]`) of the cells of a mosaic, replacing any previous limit.
+    ///
+    /// Cells are assigned [`Tile`]s one at a time (in row-major order), and
+    /// once the [`Tile`]s of a category fill their share of the cells, they
+    /// are only placed where no [`Tile`] outside of a full category comes
+    /// close (see [`category_overflow`](MosaicOptions::category_overflow)).
+    /// As with a [usage penalty](MosaicOptions::usage_penalty), each cell's
+    /// tile depends on those before it, and updating part of a mosaic only
+    /// limits the categories within that part. Limits do not apply to
+    /// [unique](MosaicOptions::unique) tiles.
+    ///
+    /// # Panics
+    /// This function panics if no [`Tile`] in this set has the category,
+    /// or if `share` is not in `(0, 1]`.
+    pub fn set_category_limit(&mut self, category: &str, share: f32) {
+        if !(share > 0.0 && share <= 1.0) {
+            panic!("Category limits must be in (0, 1]");
+        }
+        if !self.tiles.iter().any(|t| t.category() == Some(category)) {
+            panic!("No tiles in category {}", category);
+        }
+        self.limits.retain(|(name, _)| name != category);
+        self.limits.push((category.to_string(), share));
+    }
+
+    /// Get the limit on the share of cells for each category which has
+    /// one (see [`set_category_limit`](TileSet::set_category_limit)).
+    pub fn category_limits(&self) -> &[(String, f32)] {
+        &self.limits
+    }
+
+    /// Add synthetic solid-color [`Tile`]s to this set until every color
+    /// is within `max_distance` of some [`Tile`]'s average color.
+    ///
+    /// Coverage is measured at the centers of the cells of a
+    /// [`coverage_report`](TileSet::coverage_report) with
+    /// [`FILL_GAPS_DIVISIONS`](TileSet::FILL_GAPS_DIVISIONS) divisions;
+    /// new [`Tile`]s are added one at a time at the center of the least
+    /// covered cell. Returns the number of [`Tile`]s added, which are
+    /// marked as [synthetic](Tile::is_synthetic).
+    ///
+    /// # Panics
+    /// This function panics if `max_distance` is negative or not finite.
+    pub fn fill_gaps(&mut self, max_distance: f32, metric: Metric) -> usize {
+        self.fill_gaps_with(max_distance, metric, GapFill::Solid)
+    }
+
+    /// Add synthetic [`Tile`]s to this set until every color is within
+    /// `max_distance` of some [`Tile`]'s average color, like
+    /// [`fill_gaps`](TileSet::fill_gaps), made as given by `fill`.
+    ///
+    /// # Panics
+    /// This function panics if `max_distance` is negative or not finite.
+    pub fn fill_gaps_with(&mut self, max_distance: f32, metric: Metric, fill: GapFill) -> usize {
+        check_gap_distance(max_distance);
+        let side_len = self.tile_side_len();
+        let mut coverage = self.coverage_report(Self::FILL_GAPS_DIVISIONS, metric);
+
+        let mut added = 0;
+        while let Some(gap) = coverage.emptiest(1).pop() {
+            if gap.distance <= max_distance {
+                break;
+            }
+            let color = Rgb(gap.center);
+            coverage.add_tile(&color);
+            self.tiles.push(fill.tile(color, side_len));
+            added += 1;
+        }
+
+        self.sort();
+        added
+    }
+
+    /// Add synthetic [`Tile`]s (made as given by `fill`) to this set until
+    /// every color of `img` (e.g., the [source](crate::Mosaic::source) of a
+    /// mosaic) is within `max_distance` of some [`Tile`]'s average color,
+    /// so that even a sparse set can reproduce the image accurately.
+    ///
+    /// Unlike [`fill_gaps`](TileSet::fill_gaps), only the colors the image
+    /// needs are filled, each with a [`Tile`] of exactly that color; the
+    /// most common colors are filled first. Returns the number of
+    /// [`Tile`]s added, which are marked as [synthetic](Tile::is_synthetic).
+    ///
+    /// # Panics
+    /// This function panics if `max_distance` is negative or not finite.
+    pub fn fill_source_gaps(
+        &mut self,
+        img: &RgbImage,
+        max_distance: f32,
+        metric: Metric,
+        fill: GapFill,
+    ) -> usize {
+        check_gap_distance(max_distance);
+        let mut counts: HashMap<Rgb<u8>, usize> = HashMap::new();
         for px in img.pixels() {
-            if map.contains_key(px) {
-                continue; // don't duplicate closest tile calculations
+            *counts.entry(*px).or_default() += 1;
+        }
+        let mut colors: Vec<(Rgb<u8>, usize)> = counts.into_iter().collect();
+        colors.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.0.cmp(&b.0)));
+
+        let mut added: Vec<Rgb<u8>> = Vec::new();
+        for (color, _) in colors {
+            let closest = self.tiles[self.closest_tile(&color, metric)].avg();
+            let distance = added
+                .iter()
+                .chain([closest])
+                .map(|avg| metric.distance(&color, avg))
+                .fold(f32::INFINITY, f32::min);
+            if distance > max_distance {
+                added.push(color);
             }
-            map.insert(px, self.closest_tile(px));
         }
 
-        map
+        let side_len = self.tile_side_len();
+        for &color in &added {
+            self.tiles.push(fill.tile(color, side_len));
+        }
+        self.sort();
+        added.len()
+    }
+
+    /// Create a mapping between pixels in the given image
+    /// and the indices of [`Tile`]s in the set.
+    ///
+    /// With an `approx` factor greater than `1.0`, the [`Tile`]s are found
+    /// with an [`ApproxSearch`] rather than an exhaustive one. Otherwise,
+    /// they're found with a [`KdTree`] where possible (see
+    /// [`index`](TileSet::index)).
+    ///
+    /// With the `rayon` feature, the distinct colors in the image are
+    /// matched in parallel. If `cancel` is set while they're being matched,
+    /// this returns [`Error::Cancelled`].
+    pub(crate) fn map_to<'a>(
+        &self,
+        img: &'a RgbImage,
+        metric: Metric,
+        approx: f32,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<HashMap<&'a Rgb<u8>, usize>, Error> {
+        let search = (approx > 1.0).then(|| ApproxSearch::new(self, metric, approx));
+        let tree = search.is_none().then(|| self.index(metric)).flatten();
+        let closest = |px: &Rgb<u8>| match (&search, &tree) {
+            (Some(search), _) => search.closest(px),
+            (_, Some(tree)) => tree.closest(self, px),
+            _ => self.closest_linear(px, metric),
+        };
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            let colors: HashSet<&Rgb<u8>> = img.pixels().collect();
+            let colors: Vec<&Rgb<u8>> = colors.into_iter().collect();
+            colors
+                .into_par_iter()
+                .map(|px| check_cancelled(cancel).map(|_| (px, closest(px))))
+                .collect()
+        }
+
+        #[cfg(not(feature = "rayon"))]
+        {
+            let mut map = HashMap::new();
+            for px in img.pixels() {
+                if map.contains_key(px) {
+                    continue; // don't duplicate closest tile calculations
+                }
+                check_cancelled(cancel)?;
+                map.insert(px, closest(px));
+            }
+            Ok(map)
+        }
     }
 
     /// Scale the [`Tile`]s in this tileset to a new side length.
     pub fn scale_tiles(&mut self, s: u32) {
+        self.scale_tiles_with(s, &MosaicOptions::default());
+    }
+
+    /// Scale the [`Tile`]s in this tileset to a new side length, like
+    /// [`scale_tiles`](TileSet::scale_tiles), using the options'
+    /// [`tile_filter`](MosaicOptions::tile_filter). The tiles are then
+    /// sharpened with an unsharp mask of the options'
+    /// [`tile_sharpen`](MosaicOptions::tile_sharpen) strength (see
+    /// [`PostProcess::sharpen`]), to make up for the blurring of shrinking
+    /// them to a few pixels.
+    ///
+    /// The averages of the [`Tile`]s are computed after sharpening, so
+    /// they're matched by the colors they'll actually show. With the
+    /// default options, this is the same as
+    /// [`scale_tiles`](TileSet::scale_tiles).
+    ///
+    /// # Panics
+    /// This function panics if the `tile_sharpen` amount is negative or
+    /// not finite.
+    pub fn scale_tiles_with(&mut self, s: u32, options: &MosaicOptions) {
+        let amount = options.tile_sharpen;
+        if !(amount.is_finite() && amount >= 0.0) {
+            panic!("Sharpening amount must not be negative");
+        }
+        let sharpen = PostProcess {
+            sharpen: amount,
+            ..Default::default()
+        };
+        let filter = options.tile_filter.into();
         self.tiles = self
             .tiles
             .iter()
             .map(|t| {
-                let dyn_img = DynamicImage::ImageRgb8(t.img().clone());
-                Tile::from(dyn_img.resize_exact(s, s, FilterType::Triangle).to_rgb8())
+                let dyn_img = DynamicImage::ImageRgb8(t.img().into_owned());
+                let img = dyn_img.resize_exact(s, s, filter).to_rgb8();
+                let img = match sharpen.is_identity() {
+                    true => img,
+                    false => sharpen.apply(&img),
+                };
+                let mut scaled = match &t.alpha {
+                    Some(alpha) => Tile::with_alpha(img, imageops::resize(alpha, s, s, filter)),
+                    None => Tile::from(img),
+                };
+                scaled.synthetic = t.synthetic;
+                scaled.weight = t.weight;
+                scaled.label = t.label;
+                scaled.category = t.category.clone();
+                scaled.source = t.source.clone();
+                scaled.center = center_average(&scaled.pixels(), self.center_sigma);
+                scaled
             })
             .collect();
+        self.sort();
+    }
+
+    /// Read the pixels of every [`Tile`] in this set from `map` (the file
+    /// of a [`TileStore`](crate::TileStore)) rather than memory: the
+    /// pixels of the `i`th Tile are at `start(i)`.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn map_pixels(&mut self, map: &Arc<Mmap>, start: impl Fn(usize) -> usize) {
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            tile.img = Pixels::Mapped(Arc::clone(map), start(i));
+        }
+    }
+
+    /// Get a [`KdTree`] over the [`Tile`]s in this set for the given
+    /// [`Metric`]: the one kept with the set for [`Metric::Rgb`], or a new
+    /// one otherwise.
+    ///
+    /// Returns `None` when comparing a pixel to every [`Tile`] is the
+    /// better choice: for sets with fewer than
+    /// [`KdTree::MIN_TILES`] [`Tile`]s, and for [`Metric::CieDe2000`].
+    fn index(&self, metric: Metric) -> Option<Cow<'_, KdTree>> {
+        if self.tiles.len() < KdTree::MIN_TILES {
+            return None;
+        }
+        match &self.index {
+            Some(tree) if tree.metric() == metric => Some(Cow::Borrowed(tree)),
+            _ => KdTree::new(&self.tiles, metric).map(Cow::Owned),
+        }
+    }
+
+    /// Sort the [`Tile`]s in this set into a canonical order, and rebuild
+    /// the [`KdTree`] over them.
+    ///
+    /// The order depends only on the pixels in each [`Tile`], so a set
+    /// built from the same images always has the same order (and so
+    /// ties between equally close [`Tile`]s are always broken the same
+    /// way), regardless of the order in which the images were given.
+    fn sort(&mut self) {
+        self.tiles.sort_by(|a, b| {
+            a.hash
+                .cmp(&b.hash)
+                .then_with(|| a.pixels().as_raw().cmp(b.pixels().as_raw()))
+                .then_with(|| a.alpha.as_deref().cmp(&b.alpha.as_deref()))
+                .then_with(|| a.label.cmp(&b.label))
+                .then_with(|| a.category.cmp(&b.category))
+                .then_with(|| a.source.cmp(&b.source))
+        });
+        self.index = KdTree::new(&self.tiles, Metric::Rgb);
+    }
+
+    /// Create a mapping between the descriptors of the cells of an image
+    /// and the indices of [`Tile`]s in the set.
+    ///
+    /// Each pixel of `img` is one block of a cell; cells are `n` x `n`
+    /// blocks, where `n` is the [grid size](Descriptor::grid_size) of the
+    /// options' descriptor. Returns the index of the closest [`Tile`] to
+    /// each cell, in row-major order.
+    ///
+    /// When [dithering](MosaicOptions::dither), the blocks of each cell are
+    /// perturbed based on the position of the cell (offset by `origin`), so
+    /// matches can't be shared between cells with the same descriptor.
+    /// Likewise, with a [usage](MosaicOptions::usage_penalty) or
+    /// [repeat](MosaicOptions::repeat_penalty) penalty, a
+    /// [`top_k`](MosaicOptions::top_k) above `1`,
+    /// [category limits](TileSet::set_category_limit), or
+    /// [error diffusion](MosaicOptions::error_diffusion), the match for
+    /// each cell depends on the tiles used for the cells before it.
+    ///
+    /// With [unique](MosaicOptions::unique) tiles, the cells are matched
+    /// all at once instead (see [`map_unique`](TileSet::map_unique)).
+    ///
+    /// With `labels` (one per cell), each cell is only matched against the
+    /// [`Tile`]s with the same [label](Tile::label).
+    ///
+    /// If `cancel` is set before all of the rows of cells are matched, this
+    /// returns [`Error::Cancelled`].
+    ///
+    /// # Panics
+    /// This function panics if tiles are unique and there are fewer
+    /// [`Tile`]s than cells.
+    pub(crate) fn map_descriptors(
+        &self,
+        img: &RgbImage,
+        options: &MosaicOptions,
+        origin: (u32, u32),
+        labels: Option<&GrayImage>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<Vec<usize>, Error> {
+        let (descriptor, metric) = (options.descriptor, options.metric);
+        let mean = descriptor == Descriptor::Mean && labels.is_none();
+        let search =
+            (mean && options.approx > 1.0).then(|| ApproxSearch::new(self, metric, options.approx));
+        let tree = (mean && search.is_none())
+            .then(|| self.index(metric))
+            .flatten();
+        let closest = |blocks: &[Rgb<u8>], label| match (&search, &tree) {
+            (Some(search), _) => search.closest(&blocks[0]),
+            (_, Some(tree)) => tree.closest(self, &blocks[0]),
+            _ => self.closest_to(blocks, descriptor, metric, label),
+        };
+        let n = descriptor.grid_size();
+        let (columns, rows) = (img.width() / n, img.height() / n);
+        let mut map: HashMap<(Vec<Rgb<u8>>, Option<u8>), usize> = HashMap::new();
+        let mut uses = vec![0; self.tiles.len()];
+        let mut quotas = match options.unique {
+            true => None,
+            false => Quotas::new(self, (columns * rows) as usize),
+        };
+        let mut diffusion = (options.error_diffusion > 0.0)
+            .then(|| ErrorDiffusion::new(columns, options.error_diffusion));
+        let mut cells = Vec::with_capacity((columns * rows) as usize);
+        let mut unique = Vec::new();
+        for y in 0..rows {
+            check_cancelled(cancel)?;
+            for x in 0..columns {
+                let mut blocks = block_averages(&*img.view(x * n, y * n, n, n), n);
+                let pos = (origin.0 + x, origin.1 + y);
+                let label = labels.map(|labels| labels.get_pixel(x, y).0[0]);
+                if options.dither > 0 {
+                    for block in blocks.iter_mut() {
+                        *block = dither(block, pos, options.seed, options.dither);
+                    }
+                }
+                if options.unique {
+                    unique.push((blocks, label));
+                    continue;
+                }
+                let wanted = diffusion.as_ref().map(|d| d.adjust(x, &mut blocks));
+                let nearby = match options.repeat_penalty > 0.0 {
+                    true => nearby_tiles(&cells, columns, (x, y), options.repeat_radius),
+                    false => Vec::new(),
+                };
+                let penalties = Penalties {
+                    uses: &uses,
+                    nearby: &nearby,
+                };
+                let idx = if let Some(quotas) = &mut quotas {
+                    let idx = self.closest_limited(&blocks, options, penalties, pos, label, quotas);
+                    uses[idx] += 1;
+                    quotas.record(idx);
+                    idx
+                } else if options.usage_penalty > 0.0
+                    || options.repeat_penalty > 0.0
+                    || options.top_k > 1
+                {
+                    let idx = self.closest_penalized(&blocks, options, penalties, pos, label);
+                    uses[idx] += 1;
+                    idx
+                } else if options.dither > 0 || wanted.is_some() {
+                    closest(&blocks, label)
+                } else {
+                    *map.entry((blocks, label))
+                        .or_insert_with_key(|(blocks, label)| closest(blocks, *label))
+                };
+                if let (Some(diffusion), Some(wanted)) = (&mut diffusion, wanted) {
+                    diffusion.record(x, wanted, &self.tiles[idx].avg);
+                }
+                cells.push(idx);
+            }
+            if let Some(diffusion) = &mut diffusion {
+                diffusion.next_row();
+            }
+        }
+        if options.unique {
+            check_cancelled(cancel)?;
+            return Ok(self.map_unique(&unique, options));
+        }
+        Ok(cells)
+    }
+
+    /// Assign a distinct [`Tile`] to each cell, given the descriptor (and
+    /// label) of each cell, minimizing the total score over all cells.
+    ///
+    /// [`Tile`]s with a different label than a cell are only assigned to
+    /// it if there are no other [`Tile`]s left.
+    ///
+    /// # Panics
+    /// This function panics if there are fewer [`Tile`]s than cells.
+    fn map_unique(
+        &self,
+        cells: &[(Vec<Rgb<u8>>, Option<u8>)],
+        options: &MosaicOptions,
+    ) -> Vec<usize> {
+        if cells.len() > self.tiles.len() {
+            panic!(
+                "Unique tiles need at least as many tiles as cells ({} tiles, {} cells)",
+                self.tiles.len(),
+                cells.len()
+            );
+        }
+        // far worse than any real score, but finite so that the sums stay
+        // comparable
+        const MISMATCH: f32 = 1e9;
+        let mut costs = Vec::with_capacity(cells.len() * self.tiles.len());
+        for (blocks, label) in cells {
+            let score = scorer(blocks, options.descriptor, options.metric);
+            costs.extend(self.tiles.iter().map(|t| match t.in_region(*label) {
+                true => score(t),
+                false => MISMATCH,
+            }));
+        }
+        assign(&costs, cells.len(), self.tiles.len())
+    }
+
+    /// Given the descriptor of a cell, find the index of the [`Tile`]
+    /// in the set that most closely matches it, like
+    /// [`closest_tile`](TileSet::closest_tile).
+    ///
+    /// This is a linear scan over the set, comparing one color per block
+    /// of the descriptor (e.g., nine for [`Descriptor::Grid3`]). With a
+    /// `label`, only [`Tile`]s with that [label](Tile::label) are compared.
+    pub(crate) fn closest_to(
+        &self,
+        blocks: &[Rgb<u8>],
+        descriptor: Descriptor,
+        metric: Metric,
+        label: Option<u8>,
+    ) -> usize {
+        let score = scorer(blocks, descriptor, metric);
+        let mut min_idx = 0;
+        let mut min_score = f32::INFINITY;
+        for (i, t) in self.tiles.iter().enumerate() {
+            if !t.in_region(label) {
+                continue;
+            }
+            let score = score(t);
+            if score < min_score {
+                min_idx = i;
+                min_score = score;
+            }
+        }
+        min_idx
+    }
+
+    /// Given the descriptor of a cell, find the index of the [`Tile`] in
+    /// the set that most closely matches it, like
+    /// [`closest_to`](TileSet::closest_to), after adding the options'
+    /// [usage penalty](MosaicOptions::usage_penalty) to the score of each
+    /// [`Tile`] for each time it has been used, and the
+    /// [repeat penalty](MosaicOptions::repeat_penalty) for each time it has
+    /// been used nearby. With a [`top_k`](MosaicOptions::top_k) above `1`,
+    /// one of several close matches is picked (see
+    /// [`min_penalized`](TileSet::min_penalized)).
+    ///
+    /// Ties are broken pseudo-randomly (seeded by the options'
+    /// [`seed`](MosaicOptions::seed) and the position of the cell), so
+    /// that equally good [`Tile`]s don't repeat in a regular pattern.
+    fn closest_penalized(
+        &self,
+        blocks: &[Rgb<u8>],
+        options: &MosaicOptions,
+        penalties: Penalties<'_>,
+        pos: (u32, u32),
+        label: Option<u8>,
+    ) -> usize {
+        self.min_penalized(blocks, options, penalties, pos, label, |_| true)
+            .map_or(0, |(_, idx)| idx)
+    }
+
+    /// Given the descriptor of a cell, find the index of the [`Tile`] in
+    /// the set that most closely matches it, like
+    /// [`closest_penalized`](TileSet::closest_penalized), keeping
+    /// [`Tile`]s from categories which have filled their `quotas` out
+    /// unless no other [`Tile`] comes close (see
+    /// [`category_overflow`](MosaicOptions::category_overflow)).
+    fn closest_limited(
+        &self,
+        blocks: &[Rgb<u8>],
+        options: &MosaicOptions,
+        penalties: Penalties<'_>,
+        pos: (u32, u32),
+        label: Option<u8>,
+        quotas: &Quotas,
+    ) -> usize {
+        let best = self.min_penalized(blocks, options, penalties, pos, label, |_| true);
+        let Some((score, idx)) = best else {
+            return 0;
+        };
+        if !quotas.is_full(idx) {
+            return idx;
+        }
+        let open = self.min_penalized(blocks, options, penalties, pos, label, |i| {
+            !quotas.is_full(i)
+        });
+        match open {
+            Some((open_score, _))
+                if options.category_overflow > 0.0
+                    && open_score > score * options.category_overflow =>
+            {
+                idx
+            }
+            Some((_, open)) => open,
+            None => idx,
+        }
+    }
+
+    /// Find the lowest penalized score (see
+    /// [`closest_penalized`](TileSet::closest_penalized)) among the
+    /// [`Tile`]s which are `allowed` for a cell, and the index of the
+    /// [`Tile`] with it, if any are.
+    ///
+    /// With a [`top_k`](MosaicOptions::top_k) above `1`, one of the `k`
+    /// lowest scores (within the [tolerance](MosaicOptions::top_k_tolerance)
+    /// of the lowest) is picked instead, pseudo-randomly (seeded by the
+    /// options' [`seed`](MosaicOptions::seed) and the position of the cell).
+    fn min_penalized(
+        &self,
+        blocks: &[Rgb<u8>],
+        options: &MosaicOptions,
+        penalties: Penalties<'_>,
+        (x, y): (u32, u32),
+        label: Option<u8>,
+        allowed: impl Fn(usize) -> bool,
+    ) -> Option<(f32, usize)> {
+        let tie_key = |i: usize| {
+            let bytes = [options.seed, x as u64, y as u64, i as u64].map(u64::to_le_bytes);
+            fnv1a(&bytes.concat())
+        };
+
+        let score = scorer(blocks, options.descriptor, options.metric);
+        let scores = self.tiles.iter().enumerate().filter_map(|(i, t)| {
+            if !t.in_region(label) || !allowed(i) {
+                return None;
+            }
+            let score = score(t)
+                + options.usage_penalty * penalties.uses[i] as f32
+                + options.repeat_penalty * penalties.nearby_uses(i) as f32;
+            Some((score, tie_key(i), i))
+        });
+        let order =
+            |a: &(f32, u64, usize), b: &(f32, u64, usize)| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1));
+
+        if options.top_k <= 1 {
+            return scores.min_by(order).map(|(score, _, idx)| (score, idx));
+        }
+        let mut best: Vec<_> = scores.collect();
+        let k = (options.top_k as usize).min(best.len());
+        if k == 0 {
+            return None;
+        }
+        best.select_nth_unstable_by(k - 1, order);
+        best.truncate(k);
+        best.sort_unstable_by(order);
+        if let Some(tolerance) = options.top_k_tolerance {
+            let limit = best[0].0 + tolerance;
+            best.retain(|&(score, _, _)| score <= limit);
+        }
+        let bytes = [options.seed, x as u64, y as u64].map(u64::to_le_bytes);
+        let (score, _, idx) = best[(fnv1a(&bytes.concat()) % best.len() as u64) as usize];
+        Some((score, idx))
+    }
+
+    /// Given a pixel, find the index of the [`Tile`] in the set
+    /// that most closely matches it.
+    ///
+    /// [`Tile`]s are compared by their [score](Tile::score), so
+    /// weights are taken into account. Ties are broken in favor of the
+    /// [`Tile`] which comes first in the set.
+    ///
+    /// This searches the [`KdTree`] kept with the set for [`Metric::Rgb`]
+    /// (if there is one); use [`map_to`](TileSet::map_to) to match many
+    /// pixels with another [`Metric`].
+    pub(crate) fn closest_tile(&self, px: &Rgb<u8>, metric: Metric) -> usize {
+        match &self.index {
+            Some(tree) if tree.metric() == metric && self.tiles.len() >= KdTree::MIN_TILES => {
+                tree.closest(self, px)
+            }
+            _ => self.closest_linear(px, metric),
+        }
     }
 
-    /// Given a pixel, find the [`Tile`] in the set that most
-    /// closely matches it.
-    fn closest_tile(&self, px: &Rgb<u8>) -> &Tile {
+    /// Find the index of the [`Tile`] in the set that most closely matches
+    /// a pixel, like [`closest_tile`](TileSet::closest_tile), by comparing
+    /// it to every [`Tile`].
+    fn closest_linear(&self, px: &Rgb<u8>, metric: Metric) -> usize {
+        let score = scorer(std::slice::from_ref(px), Descriptor::Mean, metric);
         let mut min_idx = 0;
+        let mut min_score = f32::INFINITY;
         for (i, t) in self.tiles.iter().enumerate() {
-            if t.dist_to(px) < self.tiles[min_idx].dist_to(px) {
+            let score = score(t);
+            if score < min_score {
                 min_idx = i;
+                min_score = score;
             }
         }
-        &self.tiles[min_idx]
+        min_idx
+    }
+}
+
+/// How often each [`Tile`] has been used before a cell, for the penalties
+/// added to their scores (see [`TileSet::closest_penalized`]).
+#[derive(Debug, Clone, Copy)]
+struct Penalties<'a> {
+    /// The number of cells each [`Tile`] has been used in so far.
+    uses: &'a [u32],
+    /// The index of each [`Tile`] used in the cells near the cell, with the
+    /// number of them it's used in (see [`nearby_tiles`]).
+    nearby: &'a [(usize, u32)],
+}
+
+impl Penalties<'_> {
+    /// Get the number of cells near the cell which use the [`Tile`] at `idx`.
+    fn nearby_uses(&self, idx: usize) -> u32 {
+        self.nearby
+            .iter()
+            .find(|(i, _)| *i == idx)
+            .map_or(0, |(_, count)| *count)
+    }
+}
+
+/// Check the threshold for filling gaps in a [`TileSet`] (any other value
+/// would never be met, so gaps would be filled forever).
+fn check_gap_distance(max_distance: f32) {
+    if !(max_distance.is_finite() && max_distance >= 0.0) {
+        panic!("Gap distances must be finite and non-negative");
+    }
+}
+
+/// Count the [`Tile`]s used in the cells within `radius` of the cell at
+/// `(x, y)` which have been matched already, given the tiles of the cells
+/// matched so far (in row-major order, `columns` per row).
+fn nearby_tiles(
+    cells: &[usize],
+    columns: u32,
+    (x, y): (u32, u32),
+    radius: u32,
+) -> Vec<(usize, u32)> {
+    let mut counts: Vec<(usize, u32)> = Vec::new();
+    let (x0, x1) = (x.saturating_sub(radius), (x + radius).min(columns - 1));
+    for ny in y.saturating_sub(radius)..=y {
+        for nx in x0..=x1 {
+            let Some(&idx) = cells.get((ny * columns + nx) as usize) else {
+                break;
+            };
+            match counts.iter_mut().find(|(i, _)| *i == idx) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((idx, 1)),
+            }
+        }
+    }
+    counts
+}
+
+/// Build a function computing the [score](Tile::score_descriptor) of a
+/// [`Tile`] for the given descriptor of a cell.
+///
+/// With [`Metric::Oklab`], [`Metric::Lab`], or [`Metric::CieDe2000`] and
+/// [`Descriptor::Mean`], the cell is converted to [`Oklab`] (or [`Lab`])
+/// once here, rather than once for every [`Tile`].
+fn scorer(
+    blocks: &[Rgb<u8>],
+    descriptor: Descriptor,
+    metric: Metric,
+) -> impl Fn(&Tile) -> f32 + '_ {
+    let mean = descriptor == Descriptor::Mean;
+    let oklab = (mean && metric == Metric::Oklab).then(|| Oklab::from(&blocks[0]));
+    let lab =
+        (mean && matches!(metric, Metric::Lab | Metric::CieDe2000)).then(|| Lab::from(&blocks[0]));
+    move |t: &Tile| match (&oklab, &lab) {
+        (Some(oklab), _) => t.score_oklab(oklab),
+        (_, Some(lab)) => t.score_lab(lab, metric),
+        _ => t.score_descriptor(blocks, descriptor, metric),
     }
 }
 
 impl From<&Vec<DynamicImage>> for TileSet {
+    /// Build a tile set using the given images as [`Tile`]s; see
+    /// [`from_slice`](TileSet::from_slice).
+    fn from(imgs: &Vec<DynamicImage>) -> Self {
+        Self::from_slice(imgs)
+    }
+}
+
+impl<T: Borrow<DynamicImage>> FromIterator<T> for TileSet {
+    /// Build a tile set using the images (or references to images) from
+    /// an iterator as [`Tile`]s, like [`from_slice`](TileSet::from_slice).
+    ///
+    /// # Panics
+    /// This function panics if the iterator is empty; see
+    /// [`try_from_iter`](TileSet::try_from_iter).
+    fn from_iter<I: IntoIterator<Item = T>>(imgs: I) -> Self {
+        Self::try_from_iter(imgs).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Something a [`TileSet`] can be built from, or which already is one, as
+/// accepted by [`Mosaic::new`](crate::Mosaic::new) (and the other ways to
+/// build a [`Mosaic`](crate::Mosaic) from tiles).
+///
+/// This is implemented for [`TileSet`]s, which are used as they are, and
+/// for anything which yields images (e.g., a `&Vec<DynamicImage>`, a slice,
+/// or an iterator of owned or borrowed images), which are built into a
+/// [`TileSet`] like [`TileSet::try_from_iter`].
+pub trait IntoTileSet {
+    /// Get the [`TileSet`], building it if need be.
+    ///
+    /// # Errors
+    /// This function returns [`Error::NoTiles`] if there are no tiles.
+    fn into_tile_set(self) -> Result<TileSet, Error>;
+}
+
+impl IntoTileSet for TileSet {
+    fn into_tile_set(self) -> Result<TileSet, Error> {
+        match self.is_empty() {
+            true => Err(Error::NoTiles),
+            false => Ok(self),
+        }
+    }
+}
+
+impl<I> IntoTileSet for I
+where
+    I: IntoIterator,
+    I::Item: Borrow<DynamicImage>,
+{
+    fn into_tile_set(self) -> Result<TileSet, Error> {
+        TileSet::try_from_iter(self)
+    }
+}
+
+impl TileSet {
+    /// The color transparent parts of tile images are composited over
+    /// by default (white).
+    pub const DEFAULT_BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+
     /// Build a tile set using the given images as [`Tile`]s.
     ///
     /// The images will be scaled to be squares with a
@@ -160,13 +1451,145 @@ impl From<&Vec<DynamicImage>> for TileSet {
     /// NB: Aspect ratio will _not_ be preserved when the
     /// images are resized. Images are scaled using a
     /// triangular linear sampling filter.
+    ///
+    /// The resulting [`Tile`]s are sorted into a canonical order,
+    /// so the order of `imgs` does not matter.
+    ///
+    /// Images with transparency are composited over
+    /// [`DEFAULT_BACKGROUND`](TileSet::DEFAULT_BACKGROUND);
+    /// see [`with_background`](TileSet::with_background).
+    ///
+    /// # Panics
+    /// This function panics if `imgs` is empty.
+    pub fn from_slice(imgs: &[DynamicImage]) -> Self {
+        Self::with_background(imgs, Self::DEFAULT_BACKGROUND)
+    }
+
+    /// Build a tile set using the images (or references to images) from
+    /// an iterator as [`Tile`]s, like [`from_slice`](TileSet::from_slice).
+    ///
+    /// # Errors
+    /// This function returns [`Error::NoTiles`] if the iterator is empty.
+    pub fn try_from_iter<I>(imgs: I) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<DynamicImage>,
+    {
+        let imgs: Vec<I::Item> = imgs.into_iter().collect();
+        if imgs.is_empty() {
+            return Err(Error::NoTiles);
+        }
+        let imgs: Vec<_> = imgs
+            .iter()
+            .map(|img| (img.borrow(), None, None, None))
+            .collect();
+        Ok(Self::build(
+            &imgs,
+            Some(Self::DEFAULT_BACKGROUND),
+            TileFit::Stretch,
+        ))
+    }
+
+    /// Build a tile set using the given images as [`Tile`]s, like
+    /// [`TileSet::from`], compositing any transparent parts of the images
+    /// over a solid `background` color.
+    ///
+    /// Images are composited before they are scaled and their averages
+    /// computed, so tiles are matched by the colors they'll actually show.
+    pub fn with_background(imgs: &[DynamicImage], background: Rgb<u8>) -> Self {
+        Self::with_fit(imgs, background, TileFit::Stretch)
+    }
+
+    /// Build a tile set using the given images as [`Tile`]s, like
+    /// [`with_background`](TileSet::with_background), first cropping any
+    /// images which are not square as described by `fit` (rather than
+    /// stretching them to squares).
+    pub fn with_fit(imgs: &[DynamicImage], background: Rgb<u8>, fit: TileFit) -> Self {
+        let imgs: Vec<_> = imgs.iter().map(|img| (img, None, None, None)).collect();
+        Self::build(&imgs, Some(background), fit)
+    }
+
+    /// Build a tile set using the given images as [`Tile`]s, like
+    /// [`TileSet::from`], keeping the transparency of any images with
+    /// transparent pixels (e.g., to build a transparent mosaic with
+    /// [`MosaicPlan::render_rgba`](crate::MosaicPlan::render_rgba)).
+    ///
+    /// Since the colors behind such [`Tile`]s aren't known, they're matched
+    /// by the color of their opaque parts: their averages are weighted by
+    /// the opacity of each pixel, and the transparent parts of their
+    /// [images](Tile::img) are filled with that average color (which is
+    /// what they show when rendered without transparency).
+    pub fn with_alpha(imgs: &[DynamicImage]) -> Self {
+        let imgs: Vec<_> = imgs.iter().map(|img| (img, None, None, None)).collect();
+        Self::build(&imgs, None, TileFit::Stretch)
+    }
+
+    /// Build a tile set from the images loaded by [`load_tiles`](crate::load_tiles)
+    /// (or the other ways to load tiles), like [`TileSet::from`], recording
+    /// the path each image was loaded from as the [source](Tile::source)
+    /// of its [`Tile`].
+    ///
+    /// # Panics
+    /// This function panics if no images were loaded.
+    pub fn from_report(report: &LoadReport) -> Self {
+        let imgs: Vec<_> = report
+            .tiles
+            .iter()
+            .zip(&report.paths)
+            .map(|(img, path)| (img, None, None, Some(path.as_path())))
+            .collect();
+        Self::build(&imgs, Some(Self::DEFAULT_BACKGROUND), TileFit::Stretch)
+    }
+
+    /// Build a tile set from several groups of images, like
+    /// [`with_background`](TileSet::with_background), where each group
+    /// may only be placed in the cells of one region of the source image.
+    ///
+    /// Each group is given with the label of its region (see
+    /// [`Mosaic::set_label_mask`](crate::Mosaic::set_label_mask)), which
+    /// is recorded in each of its [`Tile`]s. All of the images are scaled
+    /// to the same side length, as with [`TileSet::from`].
+    pub fn with_labels(groups: &[(u8, Vec<DynamicImage>)], background: Rgb<u8>) -> Self {
+        let imgs: Vec<_> = groups
+            .iter()
+            .flat_map(|(label, imgs)| imgs.iter().map(|img| (img, Some(*label), None, None)))
+            .collect();
+        Self::build(&imgs, Some(background), TileFit::Stretch)
+    }
+
+    /// Build a tile set from several groups of images, like
+    /// [`with_background`](TileSet::with_background), where each group is
+    /// a category of [`Tile`]s (e.g., `sky`), or has no category.
+    ///
+    /// The category is recorded in each of the group's [`Tile`]s, so the
+    /// share of a mosaic each category fills can be limited (see
+    /// [`set_category_limit`](TileSet::set_category_limit)). All of the
+    /// images are scaled to the same side length, as with [`TileSet::from`].
+    pub fn with_categories(
+        groups: &[(Option<String>, Vec<DynamicImage>)],
+        background: Rgb<u8>,
+    ) -> Self {
+        let imgs: Vec<_> = groups
+            .iter()
+            .flat_map(|(category, imgs)| {
+                imgs.iter()
+                    .map(move |img| (img, None, category.as_deref(), None))
+            })
+            .collect();
+        Self::build(&imgs, Some(background), TileFit::Stretch)
+    }
+
+    /// Build a tile set from images with the given labels, categories and
+    /// sources (if any), cropped to squares as described by `fit`. Transparent
+    /// parts of the images are composited over `background`, or kept if
+    /// there is none (see [`with_alpha`](TileSet::with_alpha)).
     // TODO: look into reducing the memory footprint of this fn
-    fn from(imgs: &Vec<DynamicImage>) -> Self {
+    fn build(imgs: &[TileImage<'_>], background: Option<Rgb<u8>>, fit: TileFit) -> Self {
         // get the smallest dimension of any of the images
         // for the side length of the resulting image tiles
         let s = imgs
             .iter()
-            .map(|img| {
+            .map(|(img, _, _, _)| {
                 let (w, h) = img.dimensions();
                 if w < h {
                     w
@@ -175,17 +1598,39 @@ impl From<&Vec<DynamicImage>> for TileSet {
                 }
             })
             .min()
-            .unwrap();
+            .expect(EMPTY);
 
         // scale all of the images to be squares with that side length
-        let imgs: Vec<RgbImage> = imgs
+        let tiles = imgs
             .iter()
-            .map(|img| img.resize_exact(s, s, FilterType::Triangle).to_rgb8())
+            .map(|(img, label, category, source)| {
+                let tile = match background {
+                    Some(background) => {
+                        let img =
+                            fit.apply(DynamicImage::ImageRgb8(flatten_alpha(img, background)));
+                        Tile::from(img.resize_exact(s, s, FilterType::Triangle).to_rgb8())
+                    }
+                    None => {
+                        let img = fit.apply(DynamicImage::ImageRgba8(img.to_rgba8()));
+                        Tile::from(img.resize_exact(s, s, FilterType::Triangle).to_rgba8())
+                    }
+                };
+                Tile {
+                    label: *label,
+                    category: category.map(str::to_string),
+                    source: source.map(Path::to_path_buf),
+                    ..tile
+                }
+            })
             .collect();
 
-        // build tiles from the resulting images
-        Self {
-            tiles: imgs.iter().map(|img| Tile::from(img.clone())).collect(),
-        }
+        let mut set = Self {
+            tiles,
+            limits: Vec::new(),
+            center_sigma: Self::DEFAULT_CENTER_SIGMA,
+            index: None,
+        };
+        set.sort();
+        set
     }
 }
@@ -0,0 +1,102 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// The wall-clock time spent in each phase of building a mosaic.
+///
+/// Phases the library performs are recorded automatically (see
+/// [`Mosaic::timings`](crate::Mosaic::timings) and
+/// [`Mosaic::render_timed`](crate::Mosaic::render_timed)); phases it
+/// doesn't (e.g., loading tiles from disk) can be recorded by the caller
+/// with [`measure`](Timings::measure).
+///
+/// Each phase is timed as a whole, so time spent in parallel sections
+/// counts once rather than once per thread.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Timings {
+    /// Loading the source image and tiles.
+    pub load: Duration,
+    /// Resizing the [`Tile`](crate::Tile)s and computing their average colors.
+    pub averages: Duration,
+    /// Assigning a [`Tile`](crate::Tile) to each cell of the mosaic.
+    pub mapping: Duration,
+    /// Drawing the assigned [`Tile`](crate::Tile)s into the output image.
+    pub placement: Duration,
+    /// Sharpening, adjusting the contrast of, or gamma correcting the
+    /// output image (see [`PostProcess`](crate::PostProcess)).
+    pub post_process: Duration,
+    /// Encoding and saving the output image.
+    pub encoding: Duration,
+}
+
+impl Timings {
+    /// Run `f`, adding the time it takes to `phase`.
+    ///
+    /// # Example
+    /// ```
+    /// # use tilr::Timings;
+    /// let mut timings = Timings::default();
+    /// let sum: u64 = Timings::measure(&mut timings.load, || (0..100).sum());
+    /// assert_eq!(sum, 4950);
+    /// ```
+    pub fn measure<T>(phase: &mut Duration, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        *phase += start.elapsed();
+        result
+    }
+
+    /// Get the name and duration of each phase, in the order they happen.
+    pub fn phases(&self) -> [(&'static str, Duration); 6] {
+        [
+            ("load", self.load),
+            ("averages", self.averages),
+            ("mapping", self.mapping),
+            ("placement", self.placement),
+            ("post-process", self.post_process),
+            ("encoding", self.encoding),
+        ]
+    }
+
+    /// Get the total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.phases().iter().map(|(_, d)| *d).sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    /// Format the timings as a table, with the percentage of the
+    /// total time spent in each phase.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        for (name, duration) in self.phases() {
+            let percent = if total.is_zero() {
+                0.0
+            } else {
+                duration.as_secs_f64() / total.as_secs_f64() * 100.0
+            };
+            writeln!(
+                f,
+                "  {:<12} {:>10.3}s  {:5.1}%",
+                name,
+                duration.as_secs_f64(),
+                percent
+            )?;
+        }
+        write!(f, "  {:<12} {:>10.3}s", "total", total.as_secs_f64())
+    }
+}
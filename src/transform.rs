@@ -0,0 +1,165 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::Error;
+use image::{DynamicImage, ExtendedColorType, ImageDecoder, ImageFormat, ImageReader, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, Cursor, Seek};
+use std::path::Path;
+
+/// A clockwise rotation by a multiple of 90 degrees.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Rotation {
+    /// No rotation.
+    #[default]
+    None,
+    /// Rotate 90 degrees clockwise.
+    Cw90,
+    /// Rotate 180 degrees.
+    Cw180,
+    /// Rotate 270 degrees clockwise (i.e., 90 degrees counter-clockwise).
+    Cw270,
+}
+
+impl Rotation {
+    /// Get the rotation by the given number of degrees (clockwise).
+    ///
+    /// # Returns
+    /// The rotation, or `None` if `degrees` is not a multiple of 90.
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees % 360 {
+            0 => Some(Self::None),
+            90 => Some(Self::Cw90),
+            180 => Some(Self::Cw180),
+            270 => Some(Self::Cw270),
+            _ => None,
+        }
+    }
+}
+
+/// A rotation and/or flips applied to the source image of a
+/// [`Mosaic`](crate::Mosaic) before it is scaled.
+///
+/// The image is rotated first, then flipped horizontally, then flipped
+/// vertically.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    /// The rotation to apply.
+    pub rotate: Rotation,
+    /// Whether to mirror the image left-to-right.
+    pub flip_h: bool,
+    /// Whether to mirror the image top-to-bottom.
+    pub flip_v: bool,
+}
+
+impl Transform {
+    /// Check whether this transform leaves images unchanged.
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Get the dimensions of an image of the given dimensions after
+    /// applying this transform.
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self.rotate {
+            Rotation::Cw90 | Rotation::Cw270 => (height, width),
+            Rotation::None | Rotation::Cw180 => (width, height),
+        }
+    }
+
+    /// Apply this transform to an image.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let img = match self.rotate {
+            Rotation::None => img,
+            Rotation::Cw90 => img.rotate90(),
+            Rotation::Cw180 => img.rotate180(),
+            Rotation::Cw270 => img.rotate270(),
+        };
+        let img = if self.flip_h { img.fliph() } else { img };
+        if self.flip_v {
+            img.flipv()
+        } else {
+            img
+        }
+    }
+}
+
+/// Load an image, rotating and/or flipping it as described by its
+/// EXIF orientation (if it has one).
+///
+/// # Errors
+/// This function returns [`Error::Io`] if the image cannot be read, and
+/// [`Error::Decode`] if it cannot be decoded.
+pub fn load_oriented(path: &Path) -> Result<DynamicImage, Error> {
+    load_oriented_cmyk(path).map(|(img, _)| img)
+}
+
+/// Load an image as [`load_oriented`] does, and check whether it had to
+/// be converted from CMYK.
+///
+/// Images stored as CMYK (e.g., scans and files meant for printing) are
+/// always converted to RGB when they're loaded, with the naive transform
+/// (rather than an ICC profile), so their colors may be a little off.
+///
+/// # Returns
+/// The image, and whether it was converted from CMYK.
+///
+/// # Errors
+/// See [`load_oriented`].
+pub fn load_oriented_cmyk(path: &Path) -> Result<(DynamicImage, bool), Error> {
+    let reader = ImageReader::open(path)?;
+    let jpeg = match ImageFormat::from_path(path) {
+        Ok(ImageFormat::Jpeg) => crate::cmyk::decode_jpeg(&fs::read(path)?)?,
+        _ => None,
+    };
+    decode_oriented(reader, jpeg)
+}
+
+/// Decode an image already read into memory (e.g., from standard input),
+/// as [`load_oriented_cmyk`] loads one from a file: its format is guessed
+/// from its contents, since there's no extension to go by.
+///
+/// # Errors
+/// This function returns [`Error::Decode`] if the image cannot be decoded
+/// (or its format isn't recognized).
+pub fn decode_oriented_cmyk(bytes: &[u8]) -> Result<(DynamicImage, bool), Error> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let jpeg = match reader.format() {
+        Some(ImageFormat::Jpeg) => crate::cmyk::decode_jpeg(bytes)?,
+        _ => None,
+    };
+    decode_oriented(reader, jpeg)
+}
+
+/// Decode an image from `reader` (unless it's a CMYK JPEG, already decoded
+/// as `jpeg`), and orient it as described by its EXIF orientation
+fn decode_oriented<R: BufRead + Seek>(
+    reader: ImageReader<R>,
+    jpeg: Option<RgbImage>,
+) -> Result<(DynamicImage, bool), Error> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let (mut img, cmyk) = match jpeg {
+        Some(img) => (DynamicImage::ImageRgb8(img), true),
+        None => {
+            let cmyk = decoder.original_color_type() == ExtendedColorType::Cmyk8;
+            (DynamicImage::from_decoder(decoder)?, cmyk)
+        }
+    };
+    img.apply_orientation(orientation);
+    Ok((img, cmyk))
+}
@@ -13,34 +13,714 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use image::io::Reader as ImageReader;
-use image::DynamicImage;
-use std::error::Error;
+use crate::error::Error;
+use glob::{MatchOptions, Pattern};
+use image::{
+    DynamicImage, ExtendedColorType, GenericImageView, GrayImage, ImageDecoder, ImageError,
+    ImageFormat, ImageReader, Rgb, RgbImage,
+};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use xxhash_rust::xxh3::xxh3_128;
+
+/// The result of loading a directory of images to use as tiles.
+#[derive(Debug)]
+pub struct LoadReport {
+    /// The images which were loaded successfully.
+    pub tiles: Vec<DynamicImage>,
+    /// The path to each image in [`tiles`](LoadReport::tiles).
+    pub paths: Vec<PathBuf>,
+    /// The entries in the directory which were skipped, and why.
+    pub warnings: Vec<LoadWarning>,
+    /// The tiles (among [`paths`](LoadReport::paths)) which were stored as
+    /// CMYK, and so were converted to RGB when they were loaded.
+    pub cmyk: Vec<PathBuf>,
+}
+
+/// Options controlling how [`load_tiles_with`] finds images.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Whether to load images through symbolic links. When this is
+    /// `false`, symbolic links are skipped (with a warning).
+    ///
+    /// When following symbolic links, each file is only loaded once, even
+    /// if it can be reached through more than one path.
+    pub follow_symlinks: bool,
+    /// Whether to load files with exactly the same contents as one which
+    /// has already been loaded. When this is `false`, such duplicates are
+    /// skipped (with a warning), which saves decoding the same image more
+    /// than once; keep them to weight a tile set by duplicating tiles.
+    pub keep_duplicates: bool,
+    /// The smallest width or height of an image to load. Images whose
+    /// smaller dimension is below this (e.g., thumbnails and icons, which
+    /// look poor when scaled up to the tile size) are skipped (with a
+    /// warning). By default, images of any size are loaded.
+    pub min_dim: Option<u32>,
+    /// Whether to load the images in subdirectories (and in their
+    /// subdirectories, and so on). When this is `false`, subdirectories
+    /// are skipped (with a warning).
+    ///
+    /// Symbolic links to directories are only searched when following
+    /// symbolic links, and each directory is only searched once.
+    pub recursive: bool,
+    /// Glob patterns (e.g., `*.png`) selecting the files to load; when
+    /// this is empty, all files are loaded. Files which don't match any of
+    /// them are left out without a warning.
+    ///
+    /// A pattern containing a `/` (e.g., `beach/*.jpg`) is matched against
+    /// the path of the file within the directory being loaded, and any
+    /// other pattern against its name.
+    pub include: Vec<Pattern>,
+    /// Glob patterns (like [`include`](LoadOptions::include)) selecting
+    /// files to leave out, even if they match `include`.
+    pub exclude: Vec<Pattern>,
+}
+
+impl LoadOptions {
+    /// Check whether the file at `path` (within the directory being
+    /// loaded) is selected by the `include` and `exclude` patterns
+    fn selects(&self, path: &Path) -> bool {
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let matches = |pattern: &Pattern| match pattern.as_str().contains('/') {
+            true => pattern.matches_path_with(path, options),
+            false => pattern.matches_with(&name, options),
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
+/// Describes a directory entry skipped by [`load_tiles`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadWarning {
+    /// The path to the skipped entry.
+    pub path: PathBuf,
+    /// The reason the entry was skipped.
+    #[serde(flatten)]
+    pub reason: LoadWarningReason,
+}
+
+/// The reason an entry was skipped by [`load_tiles`].
+///
+/// This is serialized as a `reason` code (e.g., `"too_small"`), with any
+/// details of the reason as `detail`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", content = "detail", rename_all = "snake_case")]
+pub enum LoadWarningReason {
+    /// The entry is a directory.
+    IsDirectory,
+    /// The entry is a symbolic link, and symbolic links are not being
+    /// followed (see [`LoadOptions::follow_symlinks`]).
+    Symlink,
+    /// The entry is a symbolic link to a file which does not exist.
+    BrokenSymlink(String),
+    /// The entry refers to a file which has already been loaded
+    /// (through a symbolic link).
+    AlreadyLoaded,
+    /// The file has exactly the same contents as the given file, which
+    /// has already been loaded (see [`LoadOptions::keep_duplicates`]).
+    Duplicate(PathBuf),
+    /// The file is not in a supported image format.
+    UnsupportedFormat,
+    /// The file appears to be an image, but it could not be decoded.
+    UndecodableImage(String),
+    /// The file could not be read.
+    Unreadable(String),
+    /// The image (of the given width and height) is smaller than
+    /// [`LoadOptions::min_dim`].
+    TooSmall(u32, u32),
+    /// The image could not be downloaded (from a URL).
+    DownloadFailed(String),
+}
+
+impl fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+impl fmt::Display for LoadWarningReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IsDirectory => write!(f, "is a directory"),
+            Self::Symlink => write!(f, "is a symbolic link"),
+            Self::BrokenSymlink(e) => write!(f, "broken symbolic link ({})", e),
+            Self::AlreadyLoaded => write!(f, "already loaded through another path"),
+            Self::Duplicate(original) => {
+                write!(f, "exact duplicate of {}", original.display())
+            }
+            Self::UnsupportedFormat => write!(f, "unsupported image format"),
+            Self::UndecodableImage(e) => write!(f, "unable to decode image ({})", e),
+            Self::Unreadable(e) => write!(f, "unable to read file ({})", e),
+            Self::TooSmall(w, h) => write!(f, "image is too small ({}x{})", w, h),
+            Self::DownloadFailed(e) => write!(f, "unable to download ({})", e),
+        }
+    }
+}
 
 /// Load all images at the given `path` to use as tiles in the [`Mosaic`][crate::Mosaic]
-pub fn load_tiles(path: &Path) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+///
+/// Entries which cannot be used as tiles (subdirectories, files which are
+/// not images, etc.) are skipped and recorded in the
+/// [`warnings`](LoadReport::warnings) of the returned report.
+///
+/// Tiles (and warnings) are returned sorted by path.
+///
+/// This uses the default [`LoadOptions`]; see [`load_tiles_with`].
+pub fn load_tiles(path: &Path) -> Result<LoadReport, Error> {
+    load_tiles_with(path, &LoadOptions::default())
+}
+
+/// Load all images at the given `path` to use as tiles in the
+/// [`Mosaic`][crate::Mosaic], using the given [`LoadOptions`].
+///
+/// See [`load_tiles`].
+pub fn load_tiles_with(path: &Path, options: &LoadOptions) -> Result<LoadReport, Error> {
+    load_dir(path, options, &mut Seen::default())
+}
+
+/// The files already loaded by [`load_dir`].
+#[derive(Default)]
+struct Seen<'a> {
+    /// The canonical path to each file (when following symbolic links).
+    paths: HashSet<PathBuf>,
+    /// The path to the first file loaded with each hash of its contents.
+    contents: HashMap<(u64, u128), PathBuf>,
+    /// The images decoded by previous loads, if any.
+    cache: Option<&'a mut DecodeCache>,
+}
+
+/// Images decoded by [`load_tiles_cached`], by the size and hash of their
+/// contents, so that reloading a tile set (e.g., after some of its files
+/// change) only decodes the files which are new or have changed.
+///
+/// The cache keeps a copy of every image loaded by the most recent load
+/// (and only those), so it takes as much memory as the tiles themselves.
+#[derive(Debug, Default)]
+pub struct DecodeCache {
+    /// The images loaded by the current (or most recent) load (and whether
+    /// each was converted from CMYK).
+    images: HashMap<(u64, u128), (DynamicImage, bool)>,
+    /// The images loaded by the previous load, during a load.
+    previous: HashMap<(u64, u128), (DynamicImage, bool)>,
+    decoded: usize,
+}
+
+impl DecodeCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of images in the cache.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Check whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Get the number of images which were decoded by the most recent
+    /// load (rather than taken from the cache).
+    pub fn decoded(&self) -> usize {
+        self.decoded
+    }
+
+    /// Get the image with the contents `bytes` (read from `path`) and the
+    /// given `key`, decoding it only if it isn't already in the cache.
+    fn decode(
+        &mut self,
+        path: &Path,
+        bytes: Vec<u8>,
+        key: (u64, u128),
+    ) -> Result<(DynamicImage, bool), LoadWarningReason> {
+        if let Some(img) = self.images.get(&key) {
+            return Ok(img.clone());
+        }
+        let img = match self.previous.remove(&key) {
+            Some(img) => img,
+            None => {
+                self.decoded += 1;
+                decode(path, bytes)?
+            }
+        };
+        self.images.insert(key, img.clone());
+        Ok(img)
+    }
+}
+
+/// Load all images in a directory, skipping any files which have already
+/// been `seen` (through a symbolic link or as an exact duplicate) and
+/// adding those loaded to it.
+fn load_dir(path: &Path, options: &LoadOptions, seen: &mut Seen<'_>) -> Result<LoadReport, Error> {
     if !path.is_dir() {
-        return Err(format!("Path must be a directory: {}", path.display()).into());
+        return Err(Error::NotADirectory(path.to_path_buf()));
+    }
+
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut warnings = Vec::new();
+    if options.recursive {
+        let mut searched = HashSet::from([fs::canonicalize(path)?]);
+        paths = search(paths, options, &mut searched, &mut warnings);
+    }
+    paths.retain(|p| p.is_dir() || options.selects(p.strip_prefix(path).unwrap_or(p)));
+    // sort the entries so the results don't depend on the order
+    // in which the platform lists them
+    paths.sort();
+
+    let mut report = load_files(paths, options, seen);
+    if !warnings.is_empty() {
+        report.warnings.extend(warnings);
+        report.warnings.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+    Ok(report)
+}
+
+/// Replace each subdirectory among `paths` with the entries in it (and so
+/// on), skipping any directories which have already been `searched` and
+/// adding those searched to it.
+///
+/// Symbolic links to directories are left as they are unless following
+/// symbolic links, so they are skipped when loading. Directories which
+/// can't be read are skipped and recorded in `warnings`.
+fn search(
+    paths: Vec<PathBuf>,
+    options: &LoadOptions,
+    searched: &mut HashSet<PathBuf>,
+    warnings: &mut Vec<LoadWarning>,
+) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for path in paths {
+        let is_symlink = fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink());
+        if !path.is_dir() || (is_symlink && !options.follow_symlinks) {
+            found.push(path);
+            continue;
+        }
+        // only search each directory once (e.g., through symbolic links
+        // to a directory containing them)
+        if !searched.insert(fs::canonicalize(&path).unwrap_or(path.clone())) {
+            continue;
+        }
+        let entries = fs::read_dir(&path)
+            .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect());
+        match entries {
+            Ok(entries) => found.extend(search(entries, options, searched, warnings)),
+            Err(e) => {
+                let reason = LoadWarningReason::Unreadable(e.to_string());
+                warnings.push(LoadWarning { path, reason });
+            }
+        }
+    }
+    found
+}
+
+/// Load each of the given files, in order, skipping (and logging) any
+/// which have already been `seen` and adding those loaded to it.
+fn load_files(paths: Vec<PathBuf>, options: &LoadOptions, seen: &mut Seen<'_>) -> LoadReport {
+    let report = read_files(paths, options, seen);
+    log_skipped(&report);
+    report
+}
+
+/// Log the entries skipped by a load.
+pub(crate) fn log_skipped(report: &LoadReport) {
+    for warning in &report.warnings {
+        debug!("Skipped {}", warning);
     }
+}
 
+/// Load each of the given files, in order, like [`load_tile_files`],
+/// without logging those skipped.
+#[cfg(feature = "remote")]
+pub(crate) fn read_tile_files(paths: &[PathBuf], options: &LoadOptions) -> LoadReport {
+    read_files(paths.to_vec(), options, &mut Seen::default())
+}
+
+/// Load each of the given files, in order, skipping any which have
+/// already been `seen` and adding those loaded to it.
+fn read_files(paths: Vec<PathBuf>, options: &LoadOptions, seen: &mut Seen<'_>) -> LoadReport {
     let mut tiles = Vec::new();
+    let mut tile_paths = Vec::new();
+    let mut warnings = Vec::new();
+    let mut cmyk = Vec::new();
 
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let path = entry.path();
+    for path in paths {
+        let is_symlink = match fs::symlink_metadata(&path) {
+            Ok(meta) => meta.file_type().is_symlink(),
+            Err(e) => {
+                let reason = LoadWarningReason::Unreadable(e.to_string());
+                warnings.push(LoadWarning { path, reason });
+                continue;
+            }
+        };
+        if is_symlink && !options.follow_symlinks {
+            warnings.push(LoadWarning {
+                path,
+                reason: LoadWarningReason::Symlink,
+            });
+            continue;
+        }
+        let canonical = match fs::canonicalize(&path) {
+            Ok(canonical) => canonical,
+            Err(e) if is_symlink => {
+                let reason = LoadWarningReason::BrokenSymlink(e.to_string());
+                warnings.push(LoadWarning { path, reason });
+                continue;
+            }
+            Err(_) => path.clone(),
+        };
 
-        if path.is_file() {
-            let tile = load(&path)?;
-            tiles.push(tile);
+        if path.is_dir() {
+            warnings.push(LoadWarning {
+                path,
+                reason: LoadWarningReason::IsDirectory,
+            });
+            continue;
+        }
+
+        if options.follow_symlinks && !seen.paths.insert(canonical) {
+            warnings.push(LoadWarning {
+                path,
+                reason: LoadWarningReason::AlreadyLoaded,
+            });
+            continue;
+        }
+
+        let bytes = match read(&path) {
+            Ok(bytes) => bytes,
+            Err(reason) => {
+                warnings.push(LoadWarning { path, reason });
+                continue;
+            }
+        };
+        let key = (bytes.len() as u64, xxh3_128(&bytes));
+        if !options.keep_duplicates {
+            if let Some(original) = seen.contents.get(&key) {
+                let reason = LoadWarningReason::Duplicate(original.clone());
+                warnings.push(LoadWarning { path, reason });
+                continue;
+            }
+            seen.contents.insert(key, path.clone());
+        }
+
+        let decoded = match seen.cache.as_mut() {
+            Some(cache) => cache.decode(&path, bytes, key),
+            None => decode(&path, bytes),
+        };
+        match decoded {
+            Ok((tile, _))
+                if options
+                    .min_dim
+                    .is_some_and(|min| tile.width().min(tile.height()) < min) =>
+            {
+                let reason = LoadWarningReason::TooSmall(tile.width(), tile.height());
+                warnings.push(LoadWarning { path, reason });
+            }
+            Ok((tile, converted)) => {
+                if converted {
+                    cmyk.push(path.clone());
+                }
+                tiles.push(tile);
+                tile_paths.push(path);
+            }
+            Err(reason) => warnings.push(LoadWarning { path, reason }),
+        }
+    }
+    LoadReport {
+        tiles,
+        paths: tile_paths,
+        warnings,
+        cmyk,
+    }
+}
+
+/// Load exactly the given files to use as tiles in the
+/// [`Mosaic`][crate::Mosaic] (e.g., a list of paths from `find`), using
+/// the given [`LoadOptions`].
+///
+/// Files which cannot be used as tiles (because they don't exist, are not
+/// images, etc.) are skipped and recorded in the
+/// [`warnings`](LoadReport::warnings) of the returned report, as with
+/// [`load_tiles`]. Unlike `load_tiles`, tiles are returned in the order
+/// given, and directories are not searched for images.
+pub fn load_tile_files(paths: &[PathBuf], options: &LoadOptions) -> LoadReport {
+    load_files(paths.to_vec(), options, &mut Seen::default())
+}
+
+/// Load all images in each of the given directories, and merge them into
+/// a single [`LoadReport`].
+///
+/// Each directory is loaded as with [`load_tiles_with`], in the order given.
+/// Files which appear in more than one directory (e.g., because the same
+/// directory was given twice, or through a symbolic link) are only loaded
+/// once, from the first directory they appear in.
+pub fn load_tiles_multi(paths: &[PathBuf], options: &LoadOptions) -> Result<LoadReport, Error> {
+    // files are only loaded once across all of the directories
+    load_all(paths, options, Seen::default())
+}
+
+/// Load all images in each of the given directories, like
+/// [`load_tiles_multi`], taking any images whose contents have already
+/// been decoded from the `cache` (and adding the rest to it).
+///
+/// Afterwards, the cache holds exactly the images from this load, and
+/// [`decoded`](DecodeCache::decoded) gives the number of files which
+/// were decoded.
+pub fn load_tiles_cached(
+    paths: &[PathBuf],
+    options: &LoadOptions,
+    cache: &mut DecodeCache,
+) -> Result<LoadReport, Error> {
+    cache.previous = std::mem::take(&mut cache.images);
+    cache.decoded = 0;
+    let report = load_all(
+        paths,
+        options,
+        Seen {
+            cache: Some(&mut *cache),
+            ..Default::default()
+        },
+    );
+    // only keep the images from this load
+    cache.previous.clear();
+    debug!(
+        "Decoded {} tiles, and reused {} from the last load.",
+        cache.decoded,
+        cache.images.len() - cache.decoded
+    );
+    report
+}
+
+/// Load all images in each of the given directories into a single
+/// [`LoadReport`], skipping any files which have already been `seen`.
+fn load_all(
+    paths: &[PathBuf],
+    options: &LoadOptions,
+    mut loaded: Seen<'_>,
+) -> Result<LoadReport, Error> {
+    let mut merged = LoadReport {
+        tiles: Vec::new(),
+        paths: Vec::new(),
+        warnings: Vec::new(),
+        cmyk: Vec::new(),
+    };
+    let mut seen = HashSet::new();
+    for path in paths {
+        merged.merge(load_dir(path, options, &mut loaded)?, &mut seen);
+    }
+
+    Ok(merged)
+}
+
+impl LoadReport {
+    /// Add the tiles and warnings from `other` to this report, skipping
+    /// any files which have already been `seen`.
+    fn merge(&mut self, other: LoadReport, seen: &mut HashSet<PathBuf>) {
+        let mut first = |path: &Path| seen.insert(fs::canonicalize(path).unwrap_or(path.into()));
+        for (tile, path) in other.tiles.into_iter().zip(other.paths) {
+            if first(&path) {
+                if other.cmyk.contains(&path) {
+                    self.cmyk.push(path.clone());
+                }
+                self.tiles.push(tile);
+                self.paths.push(path);
+            }
+        }
+        for warning in other.warnings {
+            if first(&warning.path) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+}
+
+/// Read the contents of a single image to use as a tile in the
+/// [`Mosaic`][crate::Mosaic].
+///
+/// Files which are not in a supported image format (judging by their
+/// extension) are not read at all. Otherwise, the file is read in full
+/// exactly once, so it can be both hashed and decoded from memory.
+fn read(tile: &Path) -> Result<Vec<u8>, LoadWarningReason> {
+    if ImageFormat::from_path(tile).is_err() {
+        return Err(LoadWarningReason::UnsupportedFormat);
+    }
+    let mut bytes = Vec::new();
+    fs::File::open(tile)
+        .and_then(|mut f| f.read_to_end(&mut bytes))
+        .map_err(|e| LoadWarningReason::Unreadable(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Decode a single image (read from `tile`) to use as a tile in the
+/// [`Mosaic`][crate::Mosaic]
+///
+/// Images stored as CMYK are converted to RGB; the result says whether
+/// the image was converted.
+fn decode(tile: &Path, bytes: Vec<u8>) -> Result<(DynamicImage, bool), LoadWarningReason> {
+    let reason = |e| match e {
+        ImageError::Unsupported(_) => LoadWarningReason::UnsupportedFormat,
+        ImageError::IoError(e) => LoadWarningReason::Unreadable(e.to_string()),
+        e => LoadWarningReason::UndecodableImage(e.to_string()),
+    };
+    let format = ImageFormat::from_path(tile).ok();
+    if format == Some(ImageFormat::Jpeg) {
+        if let Some(img) = crate::cmyk::decode_jpeg(&bytes).map_err(reason)? {
+            return Ok((DynamicImage::ImageRgb8(img), true));
+        }
+    }
+
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    if let Some(format) = format {
+        reader.set_format(format);
+    }
+    let decoder = reader.into_decoder().map_err(reason)?;
+    // other formats (i.e., TIFF) convert CMYK themselves
+    let cmyk = decoder.original_color_type() == ExtendedColorType::Cmyk8;
+    let img = DynamicImage::from_decoder(decoder).map_err(reason)?;
+    Ok((img, cmyk))
+}
+
+/// Convert an image to RGB, compositing any transparent parts of it over
+/// a solid `background` color.
+///
+/// Images without an alpha channel are converted as-is.
+pub fn flatten_alpha(img: &DynamicImage, background: Rgb<u8>) -> RgbImage {
+    if !img.color().has_alpha() {
+        return img.to_rgb8();
+    }
+
+    let rgba = img.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        blend(&Rgb([r, g, b]), a, background)
+    })
+}
+
+/// Composite an image over a solid `background` color using a separate
+/// alpha channel of the same size.
+pub(crate) fn composite(img: &RgbImage, alpha: &GrayImage, background: Rgb<u8>) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        blend(img.get_pixel(x, y), alpha.get_pixel(x, y).0[0], background)
+    })
+}
+
+/// Get the alpha channel of an image, if it has one.
+pub(crate) fn alpha_channel(img: &DynamicImage) -> Option<GrayImage> {
+    if !img.color().has_alpha() {
+        return None;
+    }
+    let rgba = img.to_rgba8();
+    Some(GrayImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        image::Luma([rgba.get_pixel(x, y).0[3]])
+    }))
+}
+
+/// Check whether a pixel is a shade of gray (i.e., all of its channels
+/// are equal).
+pub(crate) fn is_gray(px: &Rgb<u8>) -> bool {
+    let [r, g, b] = px.0;
+    r == g && g == b
+}
+
+/// Composite a single pixel with the given alpha over a background color.
+fn blend(px: &Rgb<u8>, alpha: u8, background: Rgb<u8>) -> Rgb<u8> {
+    let a = alpha as f32 / 255.0;
+    let mix = |c: u8, bg: u8| (c as f32 * a + bg as f32 * (1.0 - a)).round() as u8;
+    Rgb([
+        mix(px.0[0], background.0[0]),
+        mix(px.0[1], background.0[1]),
+        mix(px.0[2], background.0[2]),
+    ])
+}
+
+/// Slice an image into a grid of `columns` x `rows` sub-images.
+///
+/// The sub-images are returned in row-major order. If the dimensions of
+/// the image are not multiples of the grid size, the sub-images differ
+/// in size by at most one pixel.
+///
+/// # Panics
+/// This function panics if the grid has more columns or rows than the
+/// image has pixels in that dimension, or if either is zero.
+pub fn slice_image(img: &DynamicImage, columns: u32, rows: u32) -> Vec<DynamicImage> {
+    let (w, h) = img.dimensions();
+    if columns == 0 || rows == 0 || columns > w || rows > h {
+        panic!(
+            "Unable to slice a {}x{} image into a {}x{} grid",
+            w, h, columns, rows
+        );
+    }
+
+    // the bounds of the i-th of n slices along a dimension of length len
+    let bounds = |i: u32, n: u32, len: u32| {
+        let start = (i as u64 * len as u64 / n as u64) as u32;
+        let end = ((i + 1) as u64 * len as u64 / n as u64) as u32;
+        (start, end - start)
+    };
+
+    let mut slices = Vec::with_capacity((columns * rows) as usize);
+    for y in 0..rows {
+        let (y0, sh) = bounds(y, rows, h);
+        for x in 0..columns {
+            let (x0, sw) = bounds(x, columns, w);
+            slices.push(img.crop_imm(x0, y0, sw, sh));
         }
     }
 
-    Ok(tiles)
+    slices
 }
 
-/// Load a single image to use as a tile in the [`Mosaic`][crate::Mosaic]
-fn load(tile: &Path) -> Result<DynamicImage, Box<dyn Error>> {
-    Ok(ImageReader::open(&tile)?.decode()?)
+/// Divide a label mask into a grid of `(columns, rows)` cells, and find
+/// the label (gray level) of most of the pixels in each cell.
+///
+/// Ties are broken in favor of the lowest label. If the mask is smaller
+/// than the grid, cells take the label of the nearest pixel.
+pub(crate) fn majority_labels(mask: &GrayImage, (columns, rows): (u32, u32)) -> GrayImage {
+    let (w, h) = mask.dimensions();
+    let span = |i: u32, n: u32, len: u32| {
+        let start = (i as u64 * len as u64 / n as u64) as u32;
+        let end = ((i + 1) as u64 * len as u64 / n as u64) as u32;
+        start..end.max(start + 1).min(len)
+    };
+
+    GrayImage::from_fn(columns, rows, |x, y| {
+        let mut counts = [0u32; 256];
+        for my in span(y, rows, h) {
+            for mx in span(x, columns, w) {
+                counts[mask.get_pixel(mx, my).0[0] as usize] += 1;
+            }
+        }
+        // max_by_key returns the last maximum, so search from the top
+        let label = (0..=u8::MAX)
+            .rev()
+            .max_by_key(|&l| counts[l as usize])
+            .unwrap();
+        image::Luma([label])
+    })
+}
+
+/// Compute the 64-bit FNV-1a hash of the given bytes.
+///
+/// Unlike [`std::hash::DefaultHasher`], this hash is stable across
+/// Rust releases and platforms.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
 }
@@ -0,0 +1,438 @@
+// tilr - A program to build an image from a set of image 'tiles'.
+// Copyright (C) 2023  Charles German <5donuts@pm.me>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Build mosaics of videos, frame by frame, and take tiles from the
+//! frames of videos.
+//!
+//! Frames are decoded and encoded by [ffmpeg](https://ffmpeg.org), which
+//! must be installed (along with `ffprobe`) and on the `PATH`. Raw RGB
+//! frames are passed to and from ffmpeg over pipes, so no intermediate
+//! files are written.
+
+use crate::cache::MapCache;
+use crate::cells::{cell_colors_rgb, cell_grid};
+use crate::mosaic::{build_tiles, scale_source_rgb};
+use crate::options::{check_scale, MosaicOptions, ScaleError};
+use crate::plan::MosaicPlan;
+use crate::progress::{Phase, Progress, ProgressHook};
+use crate::tiles::TileSet;
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+use std::error::Error;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Builds a mosaic of each frame of a video using the same [`TileSet`].
+///
+/// The closest [`Tile`](crate::Tile) to each color is remembered between
+/// frames (see [`MapCache`]), so later frames are much quicker to plan
+/// than the first.
+#[derive(Debug)]
+pub struct VideoMosaic {
+    /// The [`Tile`](crate::Tile)s used to build each frame.
+    tiles: TileSet,
+    /// The scaling factor to apply to each frame.
+    img_scaling: f32,
+    /// The options used to assign [`Tile`](crate::Tile)s to pixels.
+    options: MosaicOptions,
+    /// The closest tile to each color seen so far.
+    cache: MapCache,
+    /// The callback to report progress to while rendering, if any.
+    progress: ProgressHook,
+}
+
+/// Which frames of a video [`video_frames`] takes as tiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSampling {
+    /// Every Nth frame, starting with the first.
+    Every(u32),
+    /// N frames spread evenly through the video, starting with the first
+    /// (or every frame, if the video has no more than N).
+    Count(u32),
+}
+
+impl FrameSampling {
+    /// Get the indices of the frames taken from a video with `total` frames.
+    ///
+    /// # Panics
+    /// This function panics if N is `0`.
+    pub fn indices(&self, total: u64) -> Vec<u64> {
+        match *self {
+            Self::Every(0) | Self::Count(0) => panic!("Can't sample every 0 frames or 0 frames."),
+            Self::Every(n) => (0..total).step_by(n as usize).collect(),
+            Self::Count(n) => {
+                let n = (n as u64).min(total);
+                (0..n).map(|k| k * total / n).collect()
+            }
+        }
+    }
+}
+
+/// Take frames from the video at `input` to use as tiles, e.g., to build
+/// a mosaic of a movie's poster out of the movie's own frames.
+///
+/// The frames can be passed to [`Mosaic::new`](crate::Mosaic::new) (or
+/// made into a [`TileSet`] with [`TileSet::from`]) like any other tile
+/// images. They are usually not square, so consider how they are made
+/// into squares (e.g., with [`TileSet::with_fit`]).
+///
+/// If `max_side` is given, frames are scaled down (keeping their aspect
+/// ratio) so that their shorter side is at most that many pixels, which
+/// saves keeping full-size frames in memory when the tiles will be small.
+///
+/// # Errors
+/// This function returns an error if ffmpeg or ffprobe is not installed,
+/// or if they fail to decode the video.
+///
+/// # Panics
+/// This function panics if the `sampling` takes every `0` frames or `0`
+/// frames.
+pub fn video_frames(
+    input: &Path,
+    sampling: FrameSampling,
+    max_side: Option<u32>,
+) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+    let info = probe(input)?;
+    let mut indices = sampling
+        .indices(count_frames(input)?)
+        .into_iter()
+        .peekable();
+    let frame_len = info.width as usize * info.height as usize * 3;
+
+    let mut decoder = spawn(
+        Command::new("ffmpeg")
+            .args(["-v", "error", "-nostdin", "-i"])
+            .arg(input)
+            .args(["-map", "0:v:0", "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+            .stdout(Stdio::piped()),
+    )?;
+
+    let mut frames = Vec::new();
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        let mut reader = BufReader::new(decoder.stdout.take().expect("Decoder has no stdout"));
+        let mut buf = vec![0; frame_len];
+        // read every frame (even after the last one taken), so ffmpeg
+        // finishes decoding rather than failing to write to the pipe
+        let mut index = 0;
+        while read_frame(&mut reader, &mut buf)? {
+            if indices.next_if_eq(&index).is_some() {
+                let frame = RgbImage::from_raw(info.width, info.height, buf.clone())
+                    .expect("Frame buffer has the wrong size");
+                frames.push(shrink(DynamicImage::ImageRgb8(frame), max_side));
+            }
+            index += 1;
+        }
+        Ok(())
+    })();
+
+    let decoded = decoder.wait()?;
+    if !decoded.success() {
+        return Err(format!("ffmpeg failed to decode {} ({})", input.display(), decoded).into());
+    }
+    result?;
+
+    Ok(frames)
+}
+
+/// Scale `img` down so its shorter side is at most `max_side` pixels.
+fn shrink(img: DynamicImage, max_side: Option<u32>) -> DynamicImage {
+    let (width, height) = (img.width(), img.height());
+    match max_side {
+        Some(max_side) if width.min(height) > max_side => {
+            let factor = max_side as f64 / width.min(height) as f64;
+            let width = ((width as f64 * factor).round() as u32).max(1);
+            let height = ((height as f64 * factor).round() as u32).max(1);
+            img.resize_exact(width, height, FilterType::Triangle)
+        }
+        _ => img,
+    }
+}
+
+/// The dimensions and frame rate of a video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VideoInfo {
+    width: u32,
+    height: u32,
+    /// The frame rate, as a fraction (e.g., `30000/1001`).
+    frame_rate: String,
+}
+
+impl VideoMosaic {
+    /// Prepare to build mosaics of video frames.
+    ///
+    /// The arguments are the same as for [`Mosaic::new`](crate::Mosaic::new),
+    /// except that `img_scaling` is applied to each frame.
+    ///
+    /// # Panics
+    /// This function panics if `img_scaling` is not a positive, finite
+    /// number. (Whether it suits the size of the frames is checked when
+    /// they are [rendered](VideoMosaic::render).)
+    pub fn new(tiles: &Vec<DynamicImage>, img_scaling: f32, tile_size: u8) -> Self {
+        if !(img_scaling.is_finite() && img_scaling > 0.0) {
+            panic!("{}", ScaleError::Invalid(img_scaling));
+        }
+        let tiles = build_tiles(tiles, tile_size, &MosaicOptions::default());
+        let options = MosaicOptions::default();
+        let cache = MapCache::new(&tiles, options.metric);
+
+        Self {
+            tiles,
+            img_scaling,
+            options,
+            cache,
+            progress: ProgressHook::default(),
+        }
+    }
+
+    /// Get the [`TileSet`] used to build each frame.
+    pub fn tiles(&self) -> &TileSet {
+        &self.tiles
+    }
+
+    /// Get the options used to assign [`Tile`](crate::Tile)s to pixels.
+    pub fn options(&self) -> &MosaicOptions {
+        &self.options
+    }
+
+    /// Get a mutable reference to the options used to assign
+    /// [`Tile`](crate::Tile)s to pixels.
+    pub fn options_mut(&mut self) -> &mut MosaicOptions {
+        &mut self.options
+    }
+
+    /// Get the cache of the closest [`Tile`](crate::Tile) to each color
+    /// seen so far.
+    pub fn cache(&self) -> &MapCache {
+        &self.cache
+    }
+
+    /// Get a mutable reference to the cache of the closest
+    /// [`Tile`](crate::Tile) to each color (e.g., to replace it
+    /// with one [loaded](MapCache::load) from disk).
+    pub fn cache_mut(&mut self) -> &mut MapCache {
+        &mut self.cache
+    }
+
+    /// Report the progress of [rendering](VideoMosaic::render) a video to
+    /// `callback`, which is called after each frame is built (with
+    /// [`Phase::Frames`](crate::Phase::Frames)). This replaces any callback
+    /// set before.
+    pub fn on_progress(&mut self, callback: impl FnMut(Progress) + Send + 'static) {
+        self.progress = ProgressHook::new(callback);
+    }
+
+    /// Get the size (in pixels) of the mosaic of a frame of the given size.
+    ///
+    /// # Panics
+    /// This function panics if scaling the frame would result in an
+    /// image with zero pixels in any dimension.
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let scale = |n: u32| {
+            if self.img_scaling == 1.0 {
+                n
+            } else {
+                (n as f32 * self.img_scaling) as u32
+            }
+        };
+        let (x, y) = (scale(width), scale(height));
+        if x == 0 || y == 0 {
+            panic!("Scaling factor results in an image with at least one dimension with zero px");
+        }
+        let (x, y) = cell_grid((x, y), self.options.cell_size);
+        let tile_size = self.tiles.tile_side_len();
+        (x * tile_size, y * tile_size)
+    }
+
+    /// Build the mosaic of a single frame.
+    ///
+    /// The result is the same as building a [`Mosaic`](crate::Mosaic) of
+    /// the frame with the same tiles, scaling, and options.
+    pub fn render_frame(&mut self, frame: RgbImage) -> RgbImage {
+        let img = scale_source_rgb(frame, self.img_scaling, self.options.source_filter);
+        let img = cell_colors_rgb(img, self.options.cell_size, self.options.cell_color);
+        let plan = MosaicPlan::for_image_cached(&img, &self.tiles, self.options, &mut self.cache);
+
+        let (x, y) = plan.output_size();
+        let mut mosaic = RgbImage::new(x, y);
+        plan.render_at(&self.tiles, &mut mosaic, (0, 0));
+        mosaic
+    }
+
+    /// Build a mosaic of every frame of the video at `input`, and save the
+    /// result (at the same frame rate) as a video at `output`.
+    ///
+    /// The format of the output is chosen by ffmpeg based on the extension
+    /// of `output`; if it already exists, it is overwritten.
+    ///
+    /// # Returns
+    /// The number of frames in the output.
+    ///
+    /// # Errors
+    /// This function returns an error if ffmpeg or ffprobe is not installed,
+    /// or if either fails to decode the input or encode the output, or if
+    /// the scaling factor is invalid for the size of the frames (see
+    /// [`MosaicOptions::validate`]).
+    pub fn render(&mut self, input: &Path, output: &Path) -> Result<usize, Box<dyn Error>> {
+        let info = probe(input)?;
+        check_scale((info.width, info.height), self.img_scaling)?;
+        let frame_len = info.width as usize * info.height as usize * 3;
+        let (out_x, out_y) = self.output_size((info.width, info.height));
+
+        let mut decoder = spawn(
+            Command::new("ffmpeg")
+                .args(["-v", "error", "-nostdin", "-i"])
+                .arg(input)
+                .args(["-map", "0:v:0", "-f", "rawvideo", "-pix_fmt", "rgb24", "-"])
+                .stdout(Stdio::piped()),
+        )?;
+        let mut encoder = spawn(
+            Command::new("ffmpeg")
+                .args(["-v", "error", "-y", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+                .args(["-s", &format!("{}x{}", out_x, out_y)])
+                .args(["-r", &info.frame_rate, "-i", "-"])
+                // most encoders require even dimensions for yuv420p
+                .args([
+                    "-vf",
+                    "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+                    "-pix_fmt",
+                    "yuv420p",
+                ])
+                .arg(output)
+                .stdin(Stdio::piped()),
+        )?;
+
+        let mut frames = 0;
+        let result = (|| -> Result<(), Box<dyn Error>> {
+            let mut reader = BufReader::new(decoder.stdout.take().expect("Decoder has no stdout"));
+            let mut writer = BufWriter::new(encoder.stdin.take().expect("Encoder has no stdin"));
+            let mut buf = vec![0; frame_len];
+            while read_frame(&mut reader, &mut buf)? {
+                let frame = RgbImage::from_raw(info.width, info.height, buf.clone())
+                    .expect("Frame buffer has the wrong size");
+                writer.write_all(self.render_frame(frame).as_raw())?;
+                frames += 1;
+                self.progress.report(Progress {
+                    phase: Phase::Frames,
+                    done: frames as u64,
+                    total: None,
+                    rate: None,
+                    remaining: None,
+                });
+            }
+            writer.flush()?;
+            Ok(())
+        })();
+
+        // the pipes are closed by now, so both processes will exit; check
+        // them first, since their errors explain any broken pipe
+        let decoded = decoder.wait()?;
+        let encoded = encoder.wait()?;
+        if !decoded.success() {
+            return Err(
+                format!("ffmpeg failed to decode {} ({})", input.display(), decoded).into(),
+            );
+        }
+        if !encoded.success() {
+            return Err(
+                format!("ffmpeg failed to encode {} ({})", output.display(), encoded).into(),
+            );
+        }
+        result?;
+
+        Ok(frames)
+    }
+}
+
+/// Get the dimensions and frame rate of the first video stream in a file.
+fn probe(input: &Path) -> Result<VideoInfo, Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height,r_frame_rate"])
+        .args(["-of", "csv=p=0"])
+        .arg(input)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| not_found("ffprobe", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed to read {}", input.display()).into());
+    }
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let fields: Vec<&str> = stdout.trim().split(',').collect();
+    match fields[..] {
+        [width, height, frame_rate] => Ok(VideoInfo {
+            width: width.parse()?,
+            height: height.parse()?,
+            frame_rate: frame_rate.to_string(),
+        }),
+        _ => Err(format!("No video stream found in {}", input.display()).into()),
+    }
+}
+
+/// Count the frames in the first video stream in a file.
+///
+/// This counts the packets of the stream (each holding one frame), which
+/// needs no decoding, so it's much quicker than decoding the video.
+fn count_frames(input: &Path) -> Result<u64, Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-count_packets"])
+        .args(["-show_entries", "stream=nb_read_packets"])
+        .args(["-of", "csv=p=0"])
+        .arg(input)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| not_found("ffprobe", e))?;
+    if !output.status.success() {
+        return Err(format!("ffprobe failed to read {}", input.display()).into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().parse()?)
+}
+
+/// Spawn an ffmpeg process.
+fn spawn(command: &mut Command) -> Result<Child, Box<dyn Error>> {
+    command.spawn().map_err(|e| not_found("ffmpeg", e))
+}
+
+/// Describe an error running one of the ffmpeg tools.
+fn not_found(program: &str, e: io::Error) -> Box<dyn Error> {
+    if e.kind() == io::ErrorKind::NotFound {
+        format!(
+            "{} was not found; ffmpeg must be installed to use videos",
+            program
+        )
+        .into()
+    } else {
+        format!("Unable to run {}: {}", program, e).into()
+    }
+}
+
+/// Read a whole frame into `buf`.
+///
+/// # Returns
+/// `false` if there are no more frames to read.
+fn read_frame(reader: &mut impl Read, buf: &mut [u8]) -> Result<bool, Box<dyn Error>> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err("Video ended partway through a frame".into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(true)
+}
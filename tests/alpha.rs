@@ -0,0 +1,246 @@
+//! Test compositing transparent tiles over a backdrop color, and keeping
+//! their transparency
+
+use assert_cmd::Command;
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use std::fs;
+use tilr::{flatten_alpha, Mosaic, TileSet};
+
+mod utils;
+use utils::scratch_dir;
+
+/// A red circle on a transparent background
+fn red_circle(side: u32) -> DynamicImage {
+    let r = side as f32 / 2.0;
+    let img = RgbaImage::from_fn(side, side, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - r, y as f32 + 0.5 - r);
+        match dx * dx + dy * dy <= r * r {
+            true => Rgba([255, 0, 0, 255]),
+            false => Rgba([0, 0, 0, 0]),
+        }
+    });
+    DynamicImage::ImageRgba8(img)
+}
+
+#[test]
+fn composited_over_white_by_default() {
+    let tiles = TileSet::from(&vec![red_circle(32)]);
+    let avg = tiles.get(0).unwrap().avg().0;
+
+    // the corners outside the circle are white, not black, so the
+    // average is a pinkish red rather than a darker one
+    assert_eq!(avg[0], 255);
+    assert!(avg[1] > 40 && avg[1] < 80, "{:?}", avg);
+    assert_eq!(avg[1], avg[2]);
+}
+
+#[test]
+fn composited_over_custom_background() {
+    let blue = Rgb([0, 0, 255]);
+    let tiles = TileSet::with_background(&[red_circle(32)], blue);
+    let tile = tiles.get(0).unwrap();
+
+    assert_eq!(tile.img().get_pixel(0, 0), &blue);
+    assert_eq!(tile.img().get_pixel(16, 16), &Rgb([255, 0, 0]));
+    let avg = tile.avg().0;
+    assert_eq!(avg[1], 0);
+    assert!(avg[0] > avg[2] && avg[2] > 0, "{:?}", avg);
+}
+
+#[test]
+fn partial_alpha() {
+    let img = DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 128])));
+    let flat = flatten_alpha(&img, Rgb([255, 255, 255]));
+    assert_eq!(flat.get_pixel(0, 0), &Rgb([255, 127, 127]));
+
+    // opaque images are unchanged
+    let img = DynamicImage::ImageRgb8(flat.clone());
+    assert_eq!(flatten_alpha(&img, Rgb([0, 0, 0])), flat);
+}
+
+/// A red circle with a soft edge (about 3px wide) on a transparent background
+fn soft_circle(side: u32) -> DynamicImage {
+    let c = side as f32 / 2.0;
+    let r = c - 1.0;
+    let img = RgbaImage::from_fn(side, side, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - c, y as f32 + 0.5 - c);
+        let coverage = ((r - (dx * dx + dy * dy).sqrt()) / 3.0).clamp(0.0, 1.0);
+        Rgba([255, 0, 0, (coverage * 255.0).round() as u8])
+    });
+    DynamicImage::ImageRgba8(img)
+}
+
+#[test]
+fn alpha_threshold() {
+    let colors = [[255, 0, 0], [255, 128, 128], [255, 255, 255], [0, 0, 0]];
+    let tiles: Vec<DynamicImage> = colors
+        .iter()
+        .map(|&c| DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb(c))))
+        .collect();
+    let src = soft_circle(20);
+
+    let mut mosaic = Mosaic::new(src.clone(), &tiles, 1.0, 4);
+    mosaic.options_mut().alpha_threshold = 64;
+    let plan = mosaic.plan();
+    let alpha = mosaic.source_alpha().expect("source has an alpha channel");
+    let matte = Rgb(mosaic.options().matte);
+    let composited = flatten_alpha(&src, matte);
+
+    let mut edge_cells = 0;
+    for (x, y, a) in alpha.enumerate_pixels() {
+        let a = a.0[0];
+        assert_eq!(plan.is_skipped(x, y), a < 64, "cell ({}, {})", x, y);
+        if a < 64 {
+            continue;
+        }
+
+        // every other cell is matched by its color over the matte
+        let px = composited.get_pixel(x, y);
+        let tile = mosaic.tiles().get(plan.tile_at(x, y)).unwrap();
+        let best = mosaic
+            .tiles()
+            .iter()
+            .map(|t| t.dist_to(px))
+            .fold(f32::INFINITY, f32::min);
+        assert_eq!(tile.dist_to(px), best, "cell ({}, {})", x, y);
+        if a < 255 {
+            edge_cells += 1;
+        }
+    }
+    assert!(edge_cells > 0);
+
+    // semi-transparent edge cells get lighter tiles than the opaque center
+    let center = mosaic.tiles().get(plan.tile_at(10, 10)).unwrap();
+    assert_eq!(center.avg(), &Rgb([255, 0, 0]));
+    let edge = (0..20)
+        .map(|x| (x, 10))
+        .find(|&(x, y)| (96..160).contains(&alpha.get_pixel(x, y).0[0]))
+        .expect("an edge cell around half opacity");
+    let edge = mosaic.tiles().get(plan.tile_at(edge.0, edge.1)).unwrap();
+    assert_eq!(edge.avg(), &Rgb([255, 128, 128]));
+
+    // the transparent exterior is filled with the matte
+    let rendered = plan.render(mosaic.tiles());
+    assert!(plan.is_skipped(0, 0));
+    assert_eq!(rendered.get_pixel(0, 0), &matte);
+    assert_eq!(rendered.get_pixel(3, 3), &matte);
+    assert_eq!(rendered.get_pixel(42, 42), &Rgb([255, 0, 0]));
+    let averages = plan.render_averages(mosaic.tiles());
+    assert_eq!(averages.get_pixel(0, 0), &matte);
+}
+
+#[test]
+fn alpha_threshold_plan_round_trip() {
+    let tiles = vec![DynamicImage::ImageRgb8(RgbImage::from_pixel(
+        4,
+        4,
+        Rgb([255, 0, 0]),
+    ))];
+    let mut mosaic = Mosaic::new(soft_circle(12), &tiles, 1.0, 4);
+    mosaic.options_mut().alpha_threshold = 128;
+    mosaic.options_mut().matte = [0, 0, 255];
+    let plan = mosaic.plan();
+
+    let json = serde_json::to_string(&plan).unwrap();
+    let loaded: tilr::MosaicPlan = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, plan);
+    assert_eq!(
+        loaded.render(mosaic.tiles()).get_pixel(0, 0),
+        &Rgb([0, 0, 255])
+    );
+}
+
+#[test]
+fn kept_alpha() {
+    let tiles = TileSet::with_alpha(&[red_circle(32)]);
+    let tile = tiles.get(0).unwrap();
+
+    // matched by the color of the circle alone, which fills the corners
+    assert_eq!(tile.avg(), &Rgb([255, 0, 0]));
+    assert_eq!(tile.img().get_pixel(0, 0), &Rgb([255, 0, 0]));
+    let alpha = tile.alpha().expect("tile keeps its alpha channel");
+    assert_eq!(alpha.get_pixel(0, 0).0[0], 0);
+    assert_eq!(alpha.get_pixel(16, 16).0[0], 255);
+    assert_eq!(tile.rgba().get_pixel(0, 0), &Rgba([255, 0, 0, 0]));
+
+    // opaque images are built as usual
+    let opaque = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])));
+    let kept = TileSet::with_alpha(std::slice::from_ref(&opaque));
+    let flat = TileSet::from(&vec![opaque]);
+    let (kept, flat) = (kept.get(0).unwrap(), flat.get(0).unwrap());
+    assert!(kept.alpha().is_none());
+    assert_eq!(kept.content_hash(), flat.content_hash());
+}
+
+#[test]
+fn render_rgba() {
+    let blue = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, Rgb([0, 0, 255])));
+    let tiles = TileSet::with_alpha(&[red_circle(8), blue]);
+    let src = soft_circle(12);
+    let mosaic = Mosaic::builder(src)
+        .tile_set(tiles)
+        .tile_size(4)
+        .options(tilr::MosaicOptions {
+            alpha_threshold: 128,
+            ..Default::default()
+        })
+        .build()
+        .unwrap();
+    let plan = mosaic.plan();
+    let img = mosaic.render_rgba();
+    assert_eq!(img.dimensions(), (48, 48));
+
+    // background cells are transparent, not the matte
+    assert!(plan.is_skipped(0, 0));
+    assert_eq!(img.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+
+    // the red circle (scaled to 4px) keeps its transparent corners
+    let tile = mosaic.tiles().get(plan.tile_at(6, 6)).unwrap();
+    assert_eq!(tile.avg(), &Rgb([255, 0, 0]));
+    assert_eq!(
+        img.get_pixel(24, 24).0[3],
+        tile.alpha().unwrap().get_pixel(0, 0).0[0]
+    );
+    assert!(img.get_pixel(24, 24).0[3] < 255);
+    assert_eq!(img.get_pixel(25, 25), &Rgba([255, 0, 0, 255]));
+
+    // without transparency, the same plan shows the filled-in tiles
+    let rgb = plan.render(mosaic.tiles());
+    assert_eq!(rgb.get_pixel(24, 24), &Rgb([255, 0, 0]));
+}
+
+#[test]
+fn cli_transparent() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = scratch_dir("alpha_transparent")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    red_circle(8).save(tile_dir.join("circle.png"))?;
+    let input = dir.join("input.png");
+    soft_circle(12).save(&input)?;
+    let output = dir.join("out.png");
+
+    Command::cargo_bin("tilr")?
+        .arg(&input)
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--tile-size", "4", "--alpha-threshold", "128"])
+        .args(["--transparent", "--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .success();
+    let img = image::open(&output)?;
+    assert!(img.color().has_alpha());
+    let img = img.to_rgba8();
+    assert_eq!(img.get_pixel(0, 0).0[3], 0);
+    assert_eq!(img.get_pixel(25, 25), &Rgba([255, 0, 0, 255]));
+
+    Command::cargo_bin("tilr")?
+        .arg(&input)
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--transparent", "--format", "blocks", "--yes", "-o"])
+        .arg(dir.join("blocks.png"))
+        .assert()
+        .failure();
+    Ok(())
+}
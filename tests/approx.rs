@@ -0,0 +1,108 @@
+//! Test approximate nearest-tile search
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{ApproxSearch, Metric, MosaicOptions, MosaicPlan, TileSet};
+
+mod utils;
+use utils::{brute_force, Lcg};
+
+fn random_tiles(rng: &mut Lcg, n: usize) -> TileSet {
+    let imgs: Vec<DynamicImage> = (0..n)
+        .map(|_| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, rng.color())))
+        .collect();
+    TileSet::from(&imgs)
+}
+
+#[test]
+fn exact_with_factor_one() {
+    let mut rng = Lcg(1);
+    let mut tiles = random_tiles(&mut rng, 200);
+    for i in (0..tiles.len()).step_by(7) {
+        tiles.set_weight(i, 1.0 + rng.next_u8() as f32 / 64.0);
+    }
+
+    for metric in [
+        Metric::Rgb,
+        Metric::weighted_rgb([2.0, 1.0, 0.5]),
+        Metric::Oklab,
+        Metric::Lab,
+        Metric::CieDe2000,
+    ] {
+        let search = ApproxSearch::new(&tiles, metric, 1.0);
+        for _ in 0..2000 {
+            let px = rng.color();
+            assert_eq!(search.closest(&px), brute_force(&tiles, &px, metric).0);
+        }
+    }
+}
+
+#[test]
+fn within_factor() {
+    let mut rng = Lcg(2);
+    let tiles = random_tiles(&mut rng, 500);
+
+    for factor in [1.05, 1.5, 3.0] {
+        let search = ApproxSearch::new(&tiles, Metric::Rgb, factor);
+        for _ in 0..2000 {
+            let px = rng.color();
+            let score = tiles
+                .get(search.closest(&px))
+                .unwrap()
+                .score(&px, Metric::Rgb);
+            let (_, best) = brute_force(&tiles, &px, Metric::Rgb);
+            assert!(
+                score <= best * factor + 1e-3,
+                "{} > {} * {}",
+                score,
+                best,
+                factor
+            );
+        }
+    }
+}
+
+#[test]
+fn exact_with_ties() {
+    // every tile is the same distance from mid-gray
+    let imgs: Vec<DynamicImage> = [[118, 128, 128], [128, 138, 128], [128, 128, 118]]
+        .into_iter()
+        .map(|c| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb(c))))
+        .collect();
+    let tiles = TileSet::from(&imgs);
+    let search = ApproxSearch::new(&tiles, Metric::Rgb, 1.0);
+    assert_eq!(search.closest(&Rgb([128, 128, 128])), 0);
+}
+
+#[test]
+fn plan_matches_within_factor() {
+    let mut rng = Lcg(3);
+    let tiles = random_tiles(&mut rng, 300);
+    let img = RgbImage::from_fn(32, 32, |_, _| rng.color());
+
+    let exact = MosaicPlan::for_image(&img, &tiles, MosaicOptions::default());
+    let options = MosaicOptions {
+        approx: 1.0,
+        ..Default::default()
+    };
+    assert_eq!(
+        MosaicPlan::for_image(&img, &tiles, options).cells(),
+        exact.cells()
+    );
+
+    let options = MosaicOptions {
+        approx: 1.2,
+        ..Default::default()
+    };
+    let approx = MosaicPlan::for_image(&img, &tiles, options);
+    for (px, (&a, &e)) in img.pixels().zip(approx.cells().iter().zip(exact.cells())) {
+        let score = |i: usize| tiles.get(i).unwrap().score(px, Metric::Rgb);
+        assert!(score(a) <= score(e) * 1.2 + 1e-3);
+    }
+}
+
+#[test]
+#[should_panic]
+fn factor_below_one() {
+    let tiles = random_tiles(&mut Lcg(4), 4);
+    ApproxSearch::new(&tiles, Metric::Rgb, 0.9);
+}
@@ -0,0 +1,62 @@
+//! Test rendering mosaics as flat blocks of color, and listing their cells
+
+mod utils;
+
+use image::{DynamicImage, RgbaImage};
+use tilr::{Mosaic, MosaicOptions};
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn blocks() {
+    let mosaic = Mosaic::new(small_gradient(7, 5), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    let img = plan.render_blocks(mosaic.tiles(), 6);
+    assert_eq!(img.dimensions(), (42, 30));
+
+    // every pixel of each block is the average color of its tile
+    for (x, y) in [(0, 0), (3, 2), (6, 4), (6, 0)] {
+        let avg = mosaic.tiles().get(plan.tile_at(x, y)).unwrap().avg();
+        for (dx, dy) in [(0, 0), (5, 5), (2, 4)] {
+            assert_eq!(img.get_pixel(x * 6 + dx, y * 6 + dy), avg);
+        }
+    }
+    assert_eq!(
+        plan.render_blocks(mosaic.tiles(), 1),
+        plan.render_averages(mosaic.tiles())
+    );
+}
+
+#[test]
+fn csv() {
+    let mosaic = Mosaic::new(small_gradient(3, 2), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    let mut csv = Vec::new();
+    plan.write_csv(mosaic.tiles(), &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "column,row,tile,color");
+    let tile = plan.tile_at(2, 1);
+    let [r, g, b] = mosaic.tiles().get(tile).unwrap().avg().0;
+    let expected = format!("2,1,{},#{:02x}{:02x}{:02x}", tile, r, g, b);
+    assert_eq!(lines[6], expected);
+}
+
+#[test]
+fn background_csv() {
+    // a transparent source leaves every cell as background
+    let src = DynamicImage::ImageRgba8(RgbaImage::new(2, 1));
+    let options = MosaicOptions {
+        alpha_threshold: 1,
+        matte: [1, 2, 3],
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(src, solid_tiles(), 1.0, 4, options);
+    let mut csv = Vec::new();
+    mosaic.plan().write_csv(mosaic.tiles(), &mut csv).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "column,row,tile,color\n0,0,,#010203\n1,0,,#010203\n"
+    );
+}
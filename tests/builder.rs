@@ -0,0 +1,86 @@
+//! Test configuring mosaics with a builder
+
+use image::{DynamicImage, RgbImage};
+use tilr::{Metric, Mosaic, MosaicOptions, TileSet};
+
+mod utils;
+use utils::{small_gradient, solid, solid_tiles};
+
+#[test]
+fn same_as_new() {
+    let img = small_gradient(12, 8);
+    let built = Mosaic::builder(img.clone())
+        .tiles(solid_tiles())
+        .scale(0.5)
+        .tile_size(4)
+        .build()
+        .unwrap();
+    let mosaic = Mosaic::new(img, solid_tiles(), 0.5, 4);
+    assert_eq!(built.output_size(), (24, 16));
+    assert_eq!(built.to_image(), mosaic.to_image());
+}
+
+#[test]
+fn options_and_tile_set() {
+    let img = small_gradient(6, 6);
+    let options = MosaicOptions {
+        metric: Metric::Oklab,
+        ..Default::default()
+    };
+    let tiles = TileSet::from(&solid_tiles());
+    let built = Mosaic::builder(img.clone())
+        .tile_set(tiles)
+        .tile_size(5)
+        .options(options)
+        .build()
+        .unwrap();
+    assert_eq!(built.options(), &options);
+    let mosaic = Mosaic::with_options(img, solid_tiles(), 1.0, 5, options);
+    assert_eq!(built.to_image(), mosaic.to_image());
+}
+
+#[test]
+fn defaults() {
+    // no scaling, and tiles keep their size
+    let built = Mosaic::builder(small_gradient(3, 2))
+        .tiles(solid_tiles())
+        .build()
+        .unwrap();
+    assert_eq!(built.output_size(), (75, 50));
+}
+
+#[test]
+fn reports_errors() {
+    let build = |tiles: Vec<DynamicImage>, scale, tile_size| {
+        Mosaic::builder(small_gradient(10, 10))
+            .tiles(tiles)
+            .scale(scale)
+            .tile_size(tile_size)
+            .build()
+    };
+    assert!(build(Vec::new(), 1.0, 4).is_err());
+    assert!(build(solid_tiles(), 1.0, 0).is_err());
+    assert!(build(solid_tiles(), 0.0, 4).is_err());
+    assert!(build(solid_tiles(), 0.01, 4).is_err());
+    assert!(build(solid_tiles(), 0.5, 4).is_ok());
+
+    let no_tiles = Mosaic::builder(small_gradient(10, 10)).build();
+    assert!(no_tiles.is_err());
+
+    // tiles which are too large for a u8 need a tile size
+    let large = vec![solid(&(1, 2, 3), 300, 300)];
+    let built = Mosaic::builder(small_gradient(2, 2)).tiles(&large).build();
+    assert!(built.is_err());
+    let built = Mosaic::builder(small_gradient(2, 2))
+        .tiles(&large)
+        .tile_size(10)
+        .build();
+    assert_eq!(built.unwrap().output_size(), (20, 20));
+}
+
+#[test]
+#[should_panic(expected = "at least 1")]
+fn new_panics() {
+    let img = DynamicImage::ImageRgb8(RgbImage::new(4, 4));
+    Mosaic::new(img, solid_tiles(), 1.0, 0);
+}
@@ -0,0 +1,65 @@
+//! Test cancelling mosaics before they're finished
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tilr::{Error, Mosaic, MosaicOptions};
+
+mod utils;
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn not_cancelled() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let cancel = AtomicBool::new(false);
+    let img = mosaic.to_image_cancellable(&cancel).unwrap();
+    assert_eq!(img, mosaic.to_image());
+}
+
+#[test]
+fn cancelled_while_mapping() {
+    let cancel = AtomicBool::new(true);
+    let penalized = MosaicOptions {
+        usage_penalty: 10.0,
+        ..MosaicOptions::default()
+    };
+    let unique = MosaicOptions {
+        unique: true,
+        ..MosaicOptions::default()
+    };
+    for options in [MosaicOptions::default(), penalized, unique] {
+        let mosaic = Mosaic::with_options(small_gradient(3, 3), solid_tiles(), 1.0, 4, options);
+        assert!(matches!(
+            mosaic.plan_cancellable(&cancel),
+            Err(Error::Cancelled)
+        ));
+        assert!(matches!(
+            mosaic.to_image_cancellable(&cancel),
+            Err(Error::Cancelled)
+        ));
+    }
+}
+
+#[test]
+fn cancelled_while_rendering() {
+    let mut mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let placed = Arc::new(Mutex::new(0));
+    let (flag, counter) = (Arc::clone(&cancel), Arc::clone(&placed));
+    mosaic.on_progress(move |progress| {
+        *counter.lock().unwrap() = progress.done;
+        if progress.done == 5 {
+            flag.store(true, Ordering::Relaxed);
+        }
+    });
+
+    let plan = mosaic.plan_cancellable(&cancel).unwrap();
+    let err = plan
+        .render_cancellable(mosaic.tiles(), &cancel)
+        .unwrap_err();
+    assert!(matches!(err, Error::Cancelled));
+    assert_eq!(
+        err.to_string(),
+        "The mosaic was cancelled before it was finished"
+    );
+    assert_eq!(*placed.lock().unwrap(), 5);
+}
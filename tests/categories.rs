@@ -0,0 +1,114 @@
+//! Test limiting the share of a mosaic filled by each category of tiles
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{CategoryUsage, Mosaic, MosaicOptions, MosaicPlan, TileSet};
+use utils::solid;
+
+/// A tile set of blue `sky` tiles and brown `ground` tiles
+fn tiles() -> TileSet {
+    let groups = vec![
+        (
+            Some("sky".to_string()),
+            vec![solid(&(40, 90, 220), 4, 4), solid(&(60, 120, 240), 4, 4)],
+        ),
+        (
+            Some("ground".to_string()),
+            vec![solid(&(120, 80, 40), 4, 4), solid(&(150, 110, 60), 4, 4)],
+        ),
+    ];
+    TileSet::with_categories(&groups, TileSet::DEFAULT_BACKGROUND)
+}
+
+/// A source which is blue but for its bottom row
+fn source() -> RgbImage {
+    RgbImage::from_fn(10, 10, |_, y| match y {
+        9 => Rgb([130, 90, 50]),
+        _ => Rgb([50, 100, 230]),
+    })
+}
+
+/// The number of cells of a plan using a tile in each category
+fn usage(plan: &MosaicPlan) -> Vec<CategoryUsage> {
+    plan.stats(0).categories
+}
+
+#[test]
+fn categories() {
+    let tiles = tiles();
+    assert_eq!(tiles.categories(), ["ground", "sky"]);
+    let sky = tiles.iter().filter(|t| t.category() == Some("sky")).count();
+    assert_eq!(sky, 2);
+}
+
+#[test]
+fn unlimited() {
+    let plan = MosaicPlan::for_image(&source(), &tiles(), MosaicOptions::default());
+    let usage = usage(&plan);
+    assert_eq!(usage[0].name, "ground");
+    assert_eq!(usage[0].count, 10);
+    assert_eq!(usage[1].name, "sky");
+    assert_eq!(usage[1].count, 90);
+}
+
+#[test]
+fn limit() {
+    let mut tiles = tiles();
+    tiles.set_category_limit("sky", 0.5);
+    let plan = MosaicPlan::for_image(&source(), &tiles, MosaicOptions::default());
+    let usage = usage(&plan);
+    assert!(usage[1].count.abs_diff(50) <= 1, "{:?}", usage);
+    assert_eq!(usage[0].count + usage[1].count, 100);
+    // the first cells get their best match
+    let first = tiles.get(plan.tile_at(0, 0)).unwrap();
+    assert_eq!(first.category(), Some("sky"));
+}
+
+#[test]
+fn overflow() {
+    let mut tiles = tiles();
+    tiles.set_category_limit("sky", 0.5);
+    // the ground tiles are about ten times further from the sky than the
+    // sky tiles, so the sky goes over its limit unless the factor is larger
+    let options = MosaicOptions {
+        category_overflow: 1.5,
+        ..Default::default()
+    };
+    let plan = MosaicPlan::for_image(&source(), &tiles, options);
+    assert_eq!(usage(&plan)[1].count, 90);
+
+    let options = MosaicOptions {
+        category_overflow: 100.0,
+        ..Default::default()
+    };
+    let plan = MosaicPlan::for_image(&source(), &tiles, options);
+    assert!(usage(&plan)[1].count.abs_diff(50) <= 1);
+}
+
+#[test]
+fn mosaic() {
+    let groups = vec![
+        (Some("sky".to_string()), vec![solid(&(40, 90, 220), 4, 4)]),
+        (None, vec![solid(&(120, 80, 40), 4, 4)]),
+    ];
+    let tiles = TileSet::with_categories(&groups, TileSet::DEFAULT_BACKGROUND);
+    let img = DynamicImage::ImageRgb8(source());
+    let mut mosaic = Mosaic::with_tile_set(img, tiles, 1.0, 4, MosaicOptions::default());
+    mosaic.tiles_mut().set_category_limit("sky", 0.2);
+    let plan = mosaic.plan();
+    assert_eq!(
+        usage(&plan),
+        [CategoryUsage {
+            name: "sky".into(),
+            count: 20
+        }]
+    );
+    assert!(plan.stats(0).to_string().contains("sky"));
+}
+
+#[test]
+#[should_panic(expected = "No tiles in category")]
+fn unknown_category() {
+    tiles().set_category_limit("water", 0.5);
+}
@@ -0,0 +1,184 @@
+//! Test dividing the source image into cells of several pixels
+
+mod utils;
+
+use assert_cmd::Command;
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use std::error::Error;
+use std::fs;
+use tilr::{CellColor, Mosaic, MosaicOptions};
+use utils::{scratch_dir, solid};
+
+fn options(cell_size: u32, cell_color: CellColor) -> MosaicOptions {
+    MosaicOptions {
+        cell_size,
+        cell_color,
+        ..Default::default()
+    }
+}
+
+/// The mosaic of a source with the given options, with a few solid tiles
+fn mosaic(src: DynamicImage, options: MosaicOptions) -> Mosaic {
+    let tiles = vec![
+        solid(&(255, 0, 0), 2, 2),
+        solid(&(0, 0, 255), 2, 2),
+        solid(&(128, 0, 128), 2, 2),
+    ];
+    Mosaic::with_options(src, &tiles, 1.0, 2, options)
+}
+
+#[test]
+fn averages_each_cell() {
+    // 8x8 quadrants of red, green, blue, and a gradient
+    let src = RgbImage::from_fn(16, 16, |x, y| match (x / 8, y / 8) {
+        (0, 0) => Rgb([255, 0, 0]),
+        (1, 0) => Rgb([0, 255, 0]),
+        (0, 1) => Rgb([0, 0, 255]),
+        _ => Rgb([(x - 8) as u8 * 10, (y - 8) as u8 * 10, 0]),
+    });
+    let mosaic = mosaic(src.into(), options(8, CellColor::Average));
+
+    let cells = mosaic.source();
+    assert_eq!(cells.dimensions(), (2, 2));
+    assert_eq!(*cells.get_pixel(0, 0), Rgb([255, 0, 0]));
+    assert_eq!(*cells.get_pixel(1, 0), Rgb([0, 255, 0]));
+    assert_eq!(*cells.get_pixel(0, 1), Rgb([0, 0, 255]));
+    assert_eq!(*cells.get_pixel(1, 1), Rgb([35, 35, 0]));
+    assert_eq!(mosaic.output_size(), (4, 4));
+    assert_eq!(mosaic.plan().grid_size(), (2, 2));
+}
+
+#[test]
+fn partial_cells_at_edges() {
+    let src = RgbImage::from_fn(10, 6, |x, _| match x < 8 {
+        true => Rgb([0, 0, 0]),
+        false => Rgb([200, 100, 50]),
+    });
+    let options = options(4, CellColor::Average);
+    assert_eq!(options.validate((10, 6), 1.0), Ok((3, 2)));
+
+    let mosaic = mosaic(src.into(), options);
+    let cells = mosaic.source();
+    assert_eq!(cells.dimensions(), (3, 2));
+    // the last column covers only the two columns of pixels left over
+    assert_eq!(*cells.get_pixel(2, 1), Rgb([200, 100, 50]));
+}
+
+#[test]
+fn after_scaling() {
+    let options = options(4, CellColor::Average);
+    assert_eq!(options.validate((32, 20), 0.5), Ok((4, 3)));
+
+    let src = RgbImage::from_pixel(32, 20, Rgb([10, 20, 30]));
+    let mosaic = Mosaic::with_options(src.into(), &[solid(&(0, 0, 0), 2, 2)], 0.5, 2, options);
+    assert_eq!(mosaic.source().dimensions(), (4, 3));
+}
+
+#[test]
+fn one_pixel_cells_by_default() {
+    let src = RgbImage::from_fn(5, 3, |x, y| Rgb([x as u8 * 40, y as u8 * 80, 7]));
+    let default = mosaic(src.clone().into(), MosaicOptions::default());
+    assert_eq!(default.source(), &src);
+    let zero = mosaic(src.clone().into(), options(0, CellColor::Dominant));
+    assert_eq!(zero.source(), &src);
+}
+
+#[test]
+fn dominant_color() {
+    // a cell of mostly red, with a quarter of it blue
+    let src = RgbImage::from_fn(4, 4, |x, y| match x < 2 && y < 2 {
+        true => Rgb([0, 0, 255]),
+        false => Rgb([255, 0, 0]),
+    });
+    let choose = |color| {
+        let mosaic = mosaic(src.clone().into(), options(4, color));
+        let idx = mosaic.plan().tile_at(0, 0);
+        mosaic.tiles().get(idx).unwrap().avg().0
+    };
+    assert_eq!(choose(CellColor::Average), [128, 0, 128]);
+    assert_eq!(choose(CellColor::Dominant), [255, 0, 0]);
+}
+
+#[test]
+fn dominant_averages_similar_colors() {
+    // slightly noisy red, which still groups together against a blue corner
+    let src = RgbImage::from_fn(4, 4, |x, y| match x == 0 && y == 0 {
+        true => Rgb([0, 0, 255]),
+        false => Rgb([240 + (x + y) as u8 % 2, 0, 0]),
+    });
+    let mosaic = mosaic(src.into(), options(4, CellColor::Dominant));
+    let [r, g, b] = mosaic.source().get_pixel(0, 0).0;
+    assert!((240..=241).contains(&r) && g == 0 && b == 0);
+}
+
+#[test]
+fn averages_alpha() {
+    // the left cell is fully transparent, the right one mostly opaque
+    let src = RgbaImage::from_fn(8, 4, |x, y| match (x / 4, x % 4 == 0 && y == 0) {
+        (0, _) => Rgba([0, 0, 0, 0]),
+        (_, true) => Rgba([255, 0, 0, 0]),
+        _ => Rgba([255, 0, 0, 255]),
+    });
+    let options = MosaicOptions {
+        alpha_threshold: 128,
+        ..options(4, CellColor::Average)
+    };
+    let mosaic = mosaic(src.into(), options);
+    let alpha = mosaic.source_alpha().unwrap();
+    assert_eq!(alpha.dimensions(), (2, 1));
+    assert_eq!(alpha.get_pixel(0, 0).0, [0]);
+    assert_eq!(alpha.get_pixel(1, 0).0, [239]);
+    let plan = mosaic.plan();
+    assert!(plan.is_skipped(0, 0));
+    assert!(!plan.is_skipped(1, 0));
+}
+
+#[test]
+fn cli() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("cells")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(tile_dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(tile_dir.join("blue.png"))?;
+    RgbImage::from_fn(30, 20, |x, _| Rgb([255 - x as u8, 0, x as u8])).save(dir.join("src.png"))?;
+
+    let output = dir.join("out.png");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args([
+            "--tile-size",
+            "4",
+            "--cell-size",
+            "10",
+            "--cell-color",
+            "dominant",
+        ])
+        .args(["--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(image::open(&output)?.to_rgb8().dimensions(), (12, 8));
+
+    // the cell size is counted when fitting the mosaic
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args([
+            "--tile-size",
+            "4",
+            "--cell-size",
+            "5",
+            "--fit-within",
+            "9x9",
+        ])
+        .args(["--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .success();
+    assert_eq!(image::open(&output)?.to_rgb8().dimensions(), (8, 8));
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Test the CIEDE2000 color difference metric
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Lab, Metric, Mosaic};
+use utils::solid;
+
+/// Pairs of colors in CIELAB, with their CIEDE2000 color difference, from
+/// the test data of Sharma, Wu, and Dalal (2005)
+const REFERENCE_PAIRS: [([f32; 3], [f32; 3], f32); 34] = [
+    ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+    ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+    ([50.0, 2.8361, -74.0200], [50.0, 0.0, -82.7485], 3.4412),
+    ([50.0, -1.3802, -84.2814], [50.0, 0.0, -82.7485], 1.0000),
+    ([50.0, -1.1848, -84.8006], [50.0, 0.0, -82.7485], 1.0000),
+    ([50.0, -0.9009, -85.5211], [50.0, 0.0, -82.7485], 1.0000),
+    ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+    ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+    ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0009], 7.1792),
+    ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0010], 7.1792),
+    ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0011], 7.2195),
+    ([50.0, 2.49, -0.001], [50.0, -2.49, 0.0012], 7.2195),
+    ([50.0, -0.001, 2.49], [50.0, 0.0009, -2.49], 4.8045),
+    ([50.0, -0.001, 2.49], [50.0, 0.0010, -2.49], 4.8045),
+    ([50.0, -0.001, 2.49], [50.0, 0.0011, -2.49], 4.7461),
+    ([50.0, 2.5, 0.0], [50.0, 0.0, -2.5], 4.3065),
+    ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+    ([50.0, 2.5, 0.0], [61.0, -5.0, 29.0], 22.8977),
+    ([50.0, 2.5, 0.0], [56.0, -27.0, -3.0], 31.9030),
+    ([50.0, 2.5, 0.0], [58.0, 24.0, 15.0], 19.4535),
+    ([50.0, 2.5, 0.0], [50.0, 3.1736, 0.5854], 1.0000),
+    ([50.0, 2.5, 0.0], [50.0, 3.2972, 0.0], 1.0000),
+    ([50.0, 2.5, 0.0], [50.0, 1.8634, 0.5757], 1.0000),
+    ([50.0, 2.5, 0.0], [50.0, 3.2592, 0.3350], 1.0000),
+    (
+        [60.2574, -34.0099, 36.2677],
+        [60.4626, -34.1751, 39.4387],
+        1.2644,
+    ),
+    (
+        [63.0109, -31.0961, -5.8663],
+        [62.8187, -29.7946, -4.0864],
+        1.2630,
+    ),
+    (
+        [61.2901, 3.7196, -5.3901],
+        [61.4292, 2.2480, -4.9620],
+        1.8731,
+    ),
+    (
+        [35.0831, -44.1164, 3.7933],
+        [35.0232, -40.0716, 1.5901],
+        1.8645,
+    ),
+    (
+        [22.7233, 20.0904, -46.6940],
+        [23.0331, 14.9730, -42.5619],
+        2.0373,
+    ),
+    (
+        [36.4612, 47.8580, 18.3852],
+        [36.2715, 50.5065, 21.2231],
+        1.4146,
+    ),
+    (
+        [90.8027, -2.0831, 1.4410],
+        [91.1528, -1.6435, 0.0447],
+        1.4441,
+    ),
+    (
+        [90.9257, -0.5406, -0.9208],
+        [88.6381, -0.8985, -0.7239],
+        1.5381,
+    ),
+    (
+        [6.7747, -0.2908, -2.4247],
+        [5.8714, -0.0985, -2.2286],
+        0.6377,
+    ),
+    (
+        [2.0776, 0.0795, -1.1350],
+        [0.9033, -0.0636, -0.5514],
+        0.9082,
+    ),
+];
+
+#[test]
+fn reference_pairs() {
+    let lab = |[l, a, b]: [f32; 3]| Lab { l, a, b };
+    for (p, q, expected) in REFERENCE_PAIRS {
+        let (p, q) = (lab(p), lab(q));
+        for actual in [p.ciede2000(&q), q.ciede2000(&p)] {
+            assert!(
+                (actual - expected).abs() < 1e-3,
+                "ΔE00({:?}, {:?}) = {}, expected {}",
+                p,
+                q,
+                actual,
+                expected
+            );
+        }
+    }
+}
+
+#[test]
+fn distance() {
+    let (black, white) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+    assert!((Metric::CieDe2000.distance(&black, &white) - 100.0).abs() < 1e-3);
+    assert_eq!(Metric::CieDe2000.distance(&white, &white), 0.0);
+
+    let (p, q) = (Rgb([12, 200, 99]), Rgb([250, 3, 100]));
+    assert_eq!(
+        Metric::CieDe2000.distance(&p, &q),
+        Lab::from(p).ciede2000(&Lab::from(q))
+    );
+}
+
+#[test]
+fn tile_score() {
+    let tiles = vec![solid(&(200, 30, 90), 2, 2)];
+    let mosaic = Mosaic::new(solid(&(0, 0, 0), 1, 1), &tiles, 1.0, 2);
+    let tile = mosaic.tiles().get(0).unwrap();
+
+    let px = Rgb([10, 20, 30]);
+    assert_eq!(
+        tile.score(&px, Metric::CieDe2000),
+        Metric::CieDe2000.distance(&px, tile.avg())
+    );
+}
+
+#[test]
+fn differs_from_lab() {
+    // for a mid gray, a faintly green tile of the same lightness is closer
+    // in CIELAB, while a lighter gray is closer by CIEDE2000, which weighs
+    // differences in lightness less
+    let choose = |metric| {
+        let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128, 128, 128])));
+        let tiles = vec![solid(&(155, 155, 155), 2, 2), solid(&(128, 140, 128), 2, 2)];
+        let mut mosaic = Mosaic::new(src, &tiles, 1.0, 2);
+        mosaic.options_mut().metric = metric;
+        let idx = mosaic.plan().tile_at(0, 0);
+        mosaic.tiles().get(idx).unwrap().avg().0
+    };
+    assert_eq!(choose(Metric::Lab), [128, 140, 128]);
+    assert_eq!(choose(Metric::CieDe2000), [155, 155, 155]);
+}
@@ -0,0 +1,197 @@
+//! Test converting images stored as CMYK to RGB when they're loaded
+
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+
+mod utils;
+use utils::scratch_dir;
+
+/// Cyan, magenta, yellow, and 50% black (as amounts of ink)
+const PATCHES: [[u8; 4]; 4] = [
+    [255, 0, 0, 0],
+    [0, 255, 0, 0],
+    [0, 0, 255, 0],
+    [0, 0, 0, 128],
+];
+/// The RGB colors of `PATCHES`
+const EXPECTED: [[u8; 3]; 4] = [[0, 255, 255], [255, 0, 255], [255, 255, 0], [127, 127, 127]];
+
+/// Write a bit string, stuffing a zero byte after each `0xFF` (as JPEG
+/// requires within image data)
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl Bits {
+    fn push(&mut self, bits: u32, len: u32) {
+        for i in (0..len).rev() {
+            self.acc = self.acc << 1 | (bits >> i & 1);
+            self.len += 1;
+            if self.len == 8 {
+                self.byte();
+            }
+        }
+    }
+
+    fn byte(&mut self) {
+        self.bytes.push(self.acc as u8);
+        if self.acc == 0xFF {
+            self.bytes.push(0);
+        }
+        (self.acc, self.len) = (0, 0);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            // pad with ones
+            let pad = 8 - self.len;
+            self.push((1 << pad) - 1, pad);
+        }
+        self.bytes
+    }
+}
+
+/// Add a marker segment to a JPEG
+fn segment(jpeg: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    jpeg.extend([0xFF, marker]);
+    jpeg.extend((data.len() as u16 + 2).to_be_bytes());
+    jpeg.extend(data);
+}
+
+/// Encode a baseline JPEG with four channels, made of a row of solid 8x8
+/// blocks with the given (stored) values
+///
+/// Only the average of each block is stored (the rest of its DCT is
+/// zero), which is all a solid block needs. If `adobe` is given, an Adobe
+/// segment is added with it as the color transform.
+fn jpeg(blocks: &[[u8; 4]], adobe: Option<u8>) -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8];
+    if let Some(transform) = adobe {
+        let mut data = b"Adobe".to_vec();
+        data.extend([0, 100, 0, 0, 0, 0, transform]);
+        segment(&mut jpeg, 0xEE, &data);
+    }
+    // no quantization
+    let mut dqt = vec![0];
+    dqt.extend([1; 64]);
+    segment(&mut jpeg, 0xDB, &dqt);
+    let width = 8 * blocks.len() as u16;
+    let mut sof = vec![8, 0, 8];
+    sof.extend(width.to_be_bytes());
+    sof.push(4);
+    for id in 1..=4 {
+        sof.extend([id, 0x11, 0]);
+    }
+    segment(&mut jpeg, 0xC0, &sof);
+    // the DC difference categories 0-11 all have 4-bit codes, and the
+    // only AC symbol (the end of the block) has a 1-bit code
+    let mut dc = vec![0x00, 0, 0, 0, 12];
+    dc.extend([0; 12]);
+    dc.extend(0..12);
+    segment(&mut jpeg, 0xC4, &dc);
+    let mut ac = vec![0x10, 1];
+    ac.extend([0; 15]);
+    ac.push(0);
+    segment(&mut jpeg, 0xC4, &ac);
+    let mut sos = vec![4];
+    for id in 1..=4 {
+        sos.extend([id, 0x00]);
+    }
+    sos.extend([0, 63, 0]);
+    segment(&mut jpeg, 0xDA, &sos);
+
+    let mut bits = Bits::default();
+    let mut previous = [0i32; 4];
+    for block in blocks {
+        for (c, &v) in block.iter().enumerate() {
+            let dc = 8 * (v as i32 - 128);
+            let diff = dc - previous[c];
+            previous[c] = dc;
+            let category = 32 - diff.unsigned_abs().leading_zeros();
+            bits.push(category, 4);
+            let value = if diff < 0 { diff - 1 } else { diff };
+            bits.push(value as u32 & ((1 << category) - 1), category);
+            // end of block
+            bits.push(0, 1);
+        }
+    }
+    jpeg.extend(bits.finish());
+    jpeg.extend([0xFF, 0xD9]);
+    jpeg
+}
+
+/// Convert an RGB color to YCbCr (as in JFIF)
+fn ycc([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b,
+    ]
+    .map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+/// Check that each 8x8 block of `img` has the expected color
+fn assert_patches(img: &RgbImage, tolerance: u8) {
+    assert_eq!(img.dimensions(), (8 * EXPECTED.len() as u32, 8));
+    for (i, expected) in EXPECTED.iter().enumerate() {
+        let actual = img.get_pixel(8 * i as u32 + 4, 4).0;
+        let close = actual
+            .iter()
+            .zip(expected)
+            .all(|(a, e)| a.abs_diff(*e) <= tolerance);
+        assert!(close, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn jpeg_source() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("cmyk-source")?;
+    let inverted = PATCHES.map(|px| px.map(|v| 255 - v));
+    // YCCK stores the inverted CMY values as an RGB color (inverted again)
+    let ycck = inverted.map(|[c, m, y, k]| {
+        let [y, cb, cr] = ycc([255 - c, 255 - m, 255 - y]);
+        [y, cb, cr, k]
+    });
+    for (name, jpeg, tolerance) in [
+        ("plain.jpg", jpeg(&PATCHES, None), 1),
+        ("adobe.jpg", jpeg(&inverted, Some(0)), 1),
+        ("ycck.jpg", jpeg(&ycck, Some(2)), 3),
+    ] {
+        let path = dir.join(name);
+        fs::write(&path, jpeg)?;
+        let (img, cmyk) = tilr::load_oriented_cmyk(&path)?;
+        assert!(cmyk, "{}", name);
+        assert_patches(&img.to_rgb8(), tolerance);
+    }
+
+    // RGB images aren't converted
+    let path = dir.join("rgb.jpg");
+    RgbImage::from_pixel(8, 8, Rgb([0, 255, 255])).save(&path)?;
+    assert!(!tilr::load_oriented_cmyk(&path)?.1);
+    Ok(())
+}
+
+#[test]
+fn tiles() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("cmyk-tiles")?;
+    fs::write(dir.join("cmyk.jpg"), jpeg(&PATCHES, None))?;
+    let mut tiff = tiff::encoder::TiffEncoder::new(fs::File::create(dir.join("cmyk.tiff"))?)?;
+    let pixels: Vec<u8> = (0..8)
+        .flat_map(|_| PATCHES.iter().flat_map(|px| [*px; 8]).flatten())
+        .collect();
+    tiff.write_image::<tiff::encoder::colortype::CMYK8>(32, 8, &pixels)?;
+    RgbImage::from_pixel(8, 8, Rgb([0, 255, 255])).save(dir.join("rgb.png"))?;
+
+    let report = tilr::load_tiles(&dir)?;
+    assert_eq!(report.tiles.len(), 3);
+    assert_eq!(report.cmyk, [dir.join("cmyk.jpg"), dir.join("cmyk.tiff")]);
+    for tile in &report.tiles[..2] {
+        assert_patches(&tile.to_rgb8(), 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Test shifting the colors of tiles towards their cells as they're placed
+
+use assert_cmd::Command;
+use image::{DynamicImage, Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use tilr::{Mosaic, MosaicOptions, MosaicPlan, TileSet};
+
+mod utils;
+use utils::scratch_dir;
+
+/// A gray tile with a lighter left half and a darker right half (averaging
+/// to 100), and a solid white tile
+fn tiles() -> TileSet {
+    let striped = RgbImage::from_fn(4, 4, |x, _| match x < 2 {
+        true => Rgb([120, 120, 120]),
+        false => Rgb([80, 80, 80]),
+    });
+    let white = RgbImage::from_pixel(4, 4, Rgb([255, 255, 255]));
+    TileSet::from(&vec![
+        DynamicImage::from(striped),
+        DynamicImage::from(white),
+    ])
+}
+
+fn options(color_adjust: f32) -> MosaicOptions {
+    MosaicOptions {
+        color_adjust,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn unchanged_by_default() {
+    let tiles = tiles();
+    let src = RgbImage::from_pixel(1, 1, Rgb([140, 60, 100]));
+    let plan = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default());
+    let rendered = plan.render(&tiles);
+    assert_eq!(rendered, *tiles.get(plan.tile_at(0, 0)).unwrap().img());
+}
+
+#[test]
+fn shifts_towards_cell() {
+    let tiles = tiles();
+    let src = RgbImage::from_pixel(1, 1, Rgb([140, 60, 100]));
+
+    let rendered = MosaicPlan::for_image(&src, &tiles, options(1.0)).render(&tiles);
+    // the texture of the tile is kept, around the color of the cell
+    assert_eq!(rendered.get_pixel(0, 0), &Rgb([160, 80, 120]));
+    assert_eq!(rendered.get_pixel(3, 3), &Rgb([120, 40, 80]));
+
+    let rendered = MosaicPlan::for_image(&src, &tiles, options(0.5)).render(&tiles);
+    assert_eq!(rendered.get_pixel(0, 0), &Rgb([140, 100, 120]));
+    assert_eq!(rendered.get_pixel(3, 3), &Rgb([100, 60, 80]));
+}
+
+#[test]
+fn clips_channels() {
+    let tiles = tiles();
+    let src = RgbImage::from_pixel(1, 1, Rgb([250, 0, 100]));
+    let plan = MosaicPlan::for_image(&src, &tiles, options(1.0));
+    let rendered = plan.render(&tiles);
+    assert_eq!(rendered.get_pixel(0, 0), &Rgb([255, 20, 120]));
+    assert_eq!(rendered.get_pixel(3, 0), &Rgb([230, 0, 80]));
+}
+
+#[test]
+fn every_render_adjusts() -> Result<(), Box<dyn Error>> {
+    let tiles = tiles();
+    let src = RgbImage::from_fn(3, 2, |x, y| {
+        Rgb([60 + 40 * x as u8, 100, 80 + 30 * y as u8])
+    });
+    let plan = MosaicPlan::for_image(&src, &tiles, options(0.75));
+    let rendered = plan.render(&tiles);
+    let cell = image::imageops::crop_imm(&rendered, 8, 4, 4, 4).to_image();
+    assert_eq!(plan.render_cell(&tiles, 2, 1), cell);
+    assert_eq!(plan.render_by_hash(&tiles)?, rendered);
+    let plain = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default());
+    assert_ne!(plan.render_gray(&tiles), plain.render_gray(&tiles));
+
+    // the colors of the cells are saved with the plan
+    let json = serde_json::to_string(&plan)?;
+    let loaded: MosaicPlan = serde_json::from_str(&json)?;
+    assert_eq!(loaded.render(&tiles), rendered);
+    Ok(())
+}
+
+#[test]
+fn through_mosaic() {
+    let src = DynamicImage::from(RgbImage::from_pixel(2, 2, Rgb([200, 40, 40])));
+    let tiles = [DynamicImage::from(RgbImage::from_pixel(
+        4,
+        4,
+        Rgb([90, 90, 90]),
+    ))];
+    let mosaic = Mosaic::with_options(src, &tiles, 1.0, 4, options(1.0));
+    let rendered = mosaic.to_image();
+    assert!(rendered.pixels().all(|px| px == &Rgb([200, 40, 40])));
+}
+
+#[test]
+fn cli() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("color_adjust")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    RgbImage::from_pixel(4, 4, Rgb([90, 90, 90])).save(tile_dir.join("gray.png"))?;
+    RgbImage::from_pixel(2, 2, Rgb([200, 40, 40])).save(dir.join("src.png"))?;
+
+    let output = dir.join("out.png");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--tile-size", "4", "--color-adjust", "0.5", "--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .success();
+    let rendered = image::open(&output)?.to_rgb8();
+    assert!(rendered.pixels().all(|px| px == &Rgb([145, 65, 65])));
+
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--color-adjust", "1.5", "--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .failure();
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Test comparing mosaic plans and images
+
+mod utils;
+
+use image::{Rgb, RgbImage};
+use tilr::{MosaicOptions, MosaicPlan, TileSet};
+use utils::{scratch_dir, small_gradient, solid_tiles};
+
+fn plan(img: &RgbImage) -> MosaicPlan {
+    let tiles = TileSet::from(&solid_tiles());
+    MosaicPlan::for_image(img, &tiles, MosaicOptions::default())
+}
+
+#[test]
+fn identical_plans() {
+    let img = small_gradient(16, 12).to_rgb8();
+    let diff = plan(&img).compare(&plan(&img)).unwrap();
+    assert_eq!((diff.cells, diff.changed), (16 * 12, 0));
+    assert_eq!(diff.changed_fraction(), 0.0);
+}
+
+#[test]
+fn one_changed_cell() {
+    let img = small_gradient(16, 12).to_rgb8();
+    let mut changed = img.clone();
+    changed.put_pixel(5, 7, Rgb([255, 255, 255]));
+
+    let diff = plan(&img).compare(&plan(&changed)).unwrap();
+    assert_eq!(diff.changed, 1);
+    assert!(diff.is_changed(5, 7));
+    assert!(!diff.is_changed(4, 7));
+    assert_eq!(diff.to_string(), "1 of 192 cells changed (0.52%)");
+
+    let diff_img = diff.to_image(2);
+    assert_eq!(diff_img.dimensions(), (32, 24));
+    assert_ne!(diff_img.get_pixel(10, 14), diff_img.get_pixel(0, 0));
+}
+
+#[test]
+fn saved_plans() {
+    // plans compare the same after a round trip through a sidecar
+    let img = small_gradient(16, 12).to_rgb8();
+    let path = scratch_dir("compare").unwrap().join("plan.json");
+    plan(&img).save(&path).unwrap();
+    let loaded = MosaicPlan::load(&path).unwrap();
+    assert_eq!(loaded.compare(&plan(&img)).unwrap().changed, 0);
+}
+
+#[test]
+fn different_grids() {
+    let a = plan(&small_gradient(16, 12).to_rgb8());
+    let b = plan(&small_gradient(12, 16).to_rgb8());
+    assert!(a.compare(&b).is_err());
+}
+
+#[test]
+fn images() {
+    let a = small_gradient(16, 12).to_rgb8();
+    let diff = tilr::compare_images(&a, &a).unwrap();
+    assert_eq!(diff.changed, 0);
+    assert!(diff.psnr.is_infinite());
+
+    let mut b = a.clone();
+    b.put_pixel(3, 3, Rgb([0, 255, 0]));
+    let diff = tilr::compare_images(&a, &b).unwrap();
+    assert_eq!((diff.pixels, diff.changed), (16 * 12, 1));
+    assert!(diff.is_changed(3, 3));
+    assert!(diff.psnr.is_finite());
+
+    assert!(tilr::compare_images(&a, &RgbImage::new(2, 2)).is_err());
+}
@@ -0,0 +1,95 @@
+//! Test reading default options for the CLI from a config file
+
+use assert_cmd::Command;
+use std::fs;
+use std::path::PathBuf;
+
+mod utils;
+use utils::{scratch_dir, small_gradient, solid};
+
+/// Set up a tile directory, source image and config file in a fresh
+/// directory.
+fn setup(name: &str, config: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = scratch_dir(name)?;
+    for tiles in ["red", "blue"] {
+        fs::create_dir_all(dir.join(tiles))?;
+    }
+    solid(&(255, 0, 0), 8, 8).save(dir.join("red/red.png"))?;
+    solid(&(0, 0, 255), 8, 8).save(dir.join("blue/blue.png"))?;
+    small_gradient(6, 4).save(dir.join("input.png"))?;
+    fs::write(dir.join("tilr.toml"), config)?;
+    Ok(dir)
+}
+
+const CONFIG: &str = r#"
+tile_dir = ["red", "blue"]
+tile-size = 5
+output = "from_config.png"
+yes = true
+metric = "oklab"
+"#;
+
+#[test]
+fn default_config() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("config_default", CONFIG)?;
+    let output = Command::cargo_bin("tilr")?
+        .current_dir(&dir)
+        .arg("input.png")
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let mosaic = image::open(dir.join("from_config.png"))?;
+    assert_eq!((mosaic.width(), mosaic.height()), (30, 20));
+    Ok(())
+}
+
+#[test]
+fn command_line_overrides() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("config_overrides", CONFIG)?;
+    fs::rename(dir.join("tilr.toml"), dir.join("settings.toml"))?;
+    let output = Command::cargo_bin("tilr")?
+        .current_dir(&dir)
+        .arg("input.png")
+        .args(["--config", "settings.toml", "--tile-size", "3", "-t", "red"])
+        .args(["-o", "from_cli.png"])
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!dir.join("from_config.png").exists());
+
+    // only the red tiles are used, since the tile directories were given
+    let mosaic = image::open(dir.join("from_cli.png"))?.to_rgb8();
+    assert_eq!(mosaic.dimensions(), (18, 12));
+    assert!(mosaic.pixels().all(|px| px.0 == [255, 0, 0]));
+    Ok(())
+}
+
+#[test]
+fn invalid_config() -> Result<(), Box<dyn std::error::Error>> {
+    for (name, config, message) in [
+        (
+            "config_unknown",
+            "tile_sise = 4",
+            "unknown option 'tile_sise'",
+        ),
+        (
+            "config_bad_flag",
+            "yes = \"please\"",
+            "'yes' must be true or false",
+        ),
+        ("config_bad_toml", "scale = ", "invalid TOML"),
+    ] {
+        let dir = setup(name, config)?;
+        let output = Command::cargo_bin("tilr")?
+            .current_dir(&dir)
+            .arg("input.png")
+            .output()?;
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr)?;
+        assert!(
+            stderr.contains("Error reading config tilr.toml"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+    Ok(())
+}
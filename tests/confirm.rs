@@ -0,0 +1,43 @@
+//! Test running the CLI unattended, without a terminal to confirm on
+
+use assert_cmd::Command;
+use std::path::Path;
+
+mod utils;
+use utils::tiles_and_gradient;
+
+/// Build a mosaic in `dir` which is large enough to need confirmation.
+fn tilr(dir: &Path) -> Result<Command, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("tilr")?;
+    cmd.arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(dir.join("tiles"))
+        .args(["--confirm-above", "0", "-o"])
+        .arg(dir.join("out.png"));
+    Ok(cmd)
+}
+
+#[test]
+fn refused_without_terminal() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("confirm_refused", 6, 4)?;
+    let output = tilr(&dir)?.write_stdin("").output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("use --yes"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(!dir.join("out.png").exists());
+    Ok(())
+}
+
+#[test]
+fn yes() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("confirm_yes", 6, 4)?;
+    let output = tilr(&dir)?.arg("--yes").write_stdin("").output()?;
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Resulting mosaic will be a 48px x 32px image"));
+    assert!(dir.join("out.png").exists());
+    Ok(())
+}
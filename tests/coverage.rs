@@ -0,0 +1,46 @@
+//! Test measuring how well a tile set covers the color space
+
+mod utils;
+
+use image::Rgb;
+use tilr::{Metric, TileSet};
+use utils::solid_tiles;
+
+#[test]
+fn solid_colors() {
+    let tiles = TileSet::from(&solid_tiles());
+    let report = tiles.coverage_report(16, Metric::Rgb);
+    assert_eq!(report.distances().len(), 16 * 16 * 16);
+
+    // the cells containing the tile colors are within half a cell
+    // diagonal (~13.9) of a tile
+    for tile in tiles.iter() {
+        assert!(report.distance_at(tile.avg()) < 14.0);
+    }
+
+    // dark, saturated purples are far from every tile
+    assert!(report.distance_at(&Rgb([64, 0, 96])) > 100.0);
+
+    // the emptiest regions are sorted and consistent with the percentiles
+    let gaps = report.emptiest(5);
+    assert_eq!(gaps.len(), 5);
+    assert!(gaps.windows(2).all(|w| w[0].distance >= w[1].distance));
+    assert_eq!(gaps[0].distance, report.percentile(100.0));
+    assert!(report.percentile(50.0) <= report.percentile(90.0));
+    assert!(report.percentile(0.0) < 14.0);
+}
+
+#[test]
+fn false_color_image() {
+    let tiles = TileSet::from(&solid_tiles());
+    let report = tiles.coverage_report(4, Metric::Rgb);
+    let img = report.to_image(2);
+
+    // 4 slices of 8x8px, separated by 1px lines
+    assert_eq!(img.dimensions(), (4 * 9 - 1, 8));
+    // the black tile covers the first cell well (drawn blue-ish)...
+    let px = img.get_pixel(0, 0);
+    assert!(px.0[2] > px.0[0]);
+    // ...and the separators are black
+    assert_eq!(*img.get_pixel(8, 0), Rgb([0, 0, 0]));
+}
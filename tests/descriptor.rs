@@ -0,0 +1,239 @@
+//! Test matching cells to tiles by block descriptors
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Descriptor, Mosaic, MosaicOptions, MosaicPlan, TileSet};
+
+const DARK: Rgb<u8> = Rgb([0, 0, 0]);
+const LIGHT: Rgb<u8> = Rgb([200, 200, 200]);
+const GRAY: Rgb<u8> = Rgb([100, 100, 100]);
+
+/// An image which is dark on top and light on the bottom
+fn split(side: u32) -> RgbImage {
+    RgbImage::from_fn(side, side, |_, y| if y < side / 2 { DARK } else { LIGHT })
+}
+
+fn tiles() -> Vec<DynamicImage> {
+    vec![
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, GRAY)),
+        DynamicImage::ImageRgb8(split(4)),
+    ]
+}
+
+fn options(descriptor: Descriptor) -> MosaicOptions {
+    MosaicOptions {
+        descriptor,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn tile_quadrants() {
+    let set = TileSet::from(&tiles());
+    let split_tile = set.iter().find(|t| *t.img() == split(4)).unwrap();
+    assert_eq!(split_tile.avg(), &GRAY);
+    assert_eq!(split_tile.descriptor(Descriptor::Mean), &[GRAY]);
+    assert_eq!(
+        split_tile.descriptor(Descriptor::Quadrants),
+        &[DARK, DARK, LIGHT, LIGHT]
+    );
+}
+
+#[test]
+fn quadrants_prefer_split_tile() {
+    // each cell covers an 8x8 block of the source image
+    let mut src = RgbImage::from_pixel(16, 8, GRAY);
+    for (x, y, px) in src.enumerate_pixels_mut() {
+        if x < 8 {
+            *px = if y < 4 { DARK } else { LIGHT };
+        }
+    }
+    let src = DynamicImage::ImageRgb8(src);
+
+    let mosaic = Mosaic::with_options(src, tiles(), 0.125, 4, options(Descriptor::Quadrants));
+    assert_eq!(mosaic.source().dimensions(), (2, 1));
+    let plan = mosaic.plan();
+    let tile_at = |x| mosaic.tiles().get(plan.tile_at(x, 0)).unwrap();
+
+    // both tiles have the same average color, so only the
+    // quadrants can tell them apart
+    assert_eq!(*tile_at(0).img(), split(4));
+    assert_eq!(tile_at(1).avg(), &GRAY);
+    assert_ne!(*tile_at(1).img(), split(4));
+}
+
+#[test]
+fn descriptor_recorded_in_plan() {
+    let src = DynamicImage::ImageRgb8(split(8));
+    let mosaic = Mosaic::with_options(src, tiles(), 1.0, 4, options(Descriptor::Quadrants));
+    let plan = mosaic.plan();
+    assert_eq!(plan.grid_size(), (8, 8));
+    assert_eq!(plan.options().descriptor, Descriptor::Quadrants);
+
+    let json = serde_json::to_string(&plan).unwrap();
+    assert!(json.contains("\"descriptor\":\"quadrants\""));
+    let loaded: MosaicPlan = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, plan);
+}
+
+#[test]
+fn neighborhood_without_detail() {
+    // without an original to take blocks from, each pixel's
+    // neighborhood is used instead
+    let set = TileSet::from(&tiles());
+    let plan = MosaicPlan::for_image(&split(8), &set, options(Descriptor::Quadrants));
+    assert_eq!(plan.grid_size(), (8, 8));
+    assert_eq!(plan.cells().len(), 64);
+}
+
+#[test]
+fn parse() {
+    assert_eq!("mean".parse(), Ok(Descriptor::Mean));
+    assert_eq!("quadrants".parse(), Ok(Descriptor::Quadrants));
+    assert!("median".parse::<Descriptor>().is_err());
+    assert_eq!("grid3".parse(), Ok(Descriptor::Grid3));
+    assert_eq!("3x3".parse(), Ok(Descriptor::Grid3));
+    assert_eq!(Descriptor::Quadrants.to_string(), "quadrants");
+    assert_eq!(Descriptor::Grid3.to_string(), "grid3");
+    assert_eq!("center".parse(), Ok(Descriptor::Center));
+    assert_eq!(Descriptor::Center.to_string(), "center");
+}
+
+/// A 16x16 tile which is red in the middle and gray around the edges
+fn red_center() -> RgbImage {
+    RgbImage::from_fn(16, 16, |x, y| {
+        match (4..12).contains(&x) && (4..12).contains(&y) {
+            true => Rgb([220, 20, 20]),
+            false => GRAY,
+        }
+    })
+}
+
+#[test]
+fn center_is_redder() {
+    let set = TileSet::from(&vec![DynamicImage::ImageRgb8(red_center())]);
+    let tile = set.get(0).unwrap();
+    let [mean] = tile.descriptor(Descriptor::Mean) else {
+        panic!("mean is one color");
+    };
+    let [center] = tile.descriptor(Descriptor::Center) else {
+        panic!("center is one color");
+    };
+    // a quarter of the pixels are red, but most of the weight is on them
+    assert!(center.0[0] > mean.0[0] + 25, "{:?} vs {:?}", center, mean);
+    assert!(center.0[1] < mean.0[1]);
+    assert_eq!(tile.avg(), mean);
+}
+
+#[test]
+fn center_sigma() {
+    let mut set = TileSet::from(&vec![DynamicImage::ImageRgb8(red_center())]);
+    assert_eq!(set.center_sigma(), TileSet::DEFAULT_CENTER_SIGMA);
+    let red = |set: &TileSet| set.get(0).unwrap().descriptor(Descriptor::Center)[0].0[0];
+    let default = red(&set);
+
+    set.set_center_sigma(0.1);
+    let narrow = red(&set);
+    assert!(narrow > default);
+    set.set_center_sigma(100.0);
+    assert!(red(&set).abs_diff(set.get(0).unwrap().avg().0[0]) <= 1);
+
+    // the sigma is kept when the tiles are scaled
+    set.set_center_sigma(0.1);
+    set.scale_tiles(8);
+    assert!(red(&set).abs_diff(narrow) <= 10);
+}
+
+#[test]
+fn center_prefers_red_center() {
+    // a tile with a red center, and a uniform tile of the same average
+    let set = TileSet::from(&vec![DynamicImage::ImageRgb8(red_center())]);
+    let avg = *set.get(0).unwrap().avg();
+    let imgs = vec![
+        DynamicImage::ImageRgb8(red_center()),
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(16, 16, avg)),
+    ];
+    let set = TileSet::from(&imgs);
+
+    // a cell which is nearly as red as the middle of the first tile
+    let src = RgbImage::from_pixel(1, 1, Rgb([200, 40, 40]));
+    let tile_at = |descriptor| {
+        let plan = MosaicPlan::for_image(&src, &set, options(descriptor));
+        set.get(plan.tile_at(0, 0)).unwrap().img().into_owned()
+    };
+    // both tiles have the same average color, so only the center
+    // weighting can tell them apart
+    assert_eq!(tile_at(Descriptor::Center), red_center());
+}
+
+/// An 8x8 image with each quadrant split along its own diagonal
+/// (`anti = false`) or anti-diagonal (`anti = true`)
+fn quadrant_diagonals(anti: bool) -> RgbImage {
+    RgbImage::from_fn(8, 8, |x, y| {
+        let (x, y) = (x % 4, y % 4);
+        let dark = match anti {
+            false => x > y,
+            true => x + y < 3,
+        };
+        if dark {
+            DARK
+        } else {
+            LIGHT
+        }
+    })
+}
+
+#[test]
+fn grid3_tells_diagonals_apart() {
+    let diagonal = quadrant_diagonals(false);
+    let anti = quadrant_diagonals(true);
+    let imgs = vec![
+        DynamicImage::ImageRgb8(diagonal.clone()),
+        DynamicImage::ImageRgb8(anti.clone()),
+    ];
+    let set = TileSet::from(&imgs);
+    let find = |img: &RgbImage| set.iter().find(|t| *t.img() == *img).unwrap();
+    let (d, a) = (find(&diagonal), find(&anti));
+
+    // every quadrant of both tiles has the same average color
+    assert_eq!(
+        d.descriptor(Descriptor::Quadrants),
+        a.descriptor(Descriptor::Quadrants)
+    );
+    assert_ne!(
+        d.descriptor(Descriptor::Grid3),
+        a.descriptor(Descriptor::Grid3)
+    );
+
+    // so only the 3x3 grid matches each cell to the right tile
+    let mut src = RgbImage::new(16, 8);
+    image::imageops::replace(&mut src, &diagonal, 0, 0);
+    image::imageops::replace(&mut src, &anti, 8, 0);
+    let src = DynamicImage::ImageRgb8(src);
+    let mosaic = Mosaic::with_options(src, &imgs, 0.125, 8, options(Descriptor::Grid3));
+    let plan = mosaic.plan();
+    let tile_at = |x| mosaic.tiles().get(plan.tile_at(x, 0)).unwrap().img();
+    assert_eq!(plan.grid_size(), (2, 1));
+    assert_eq!(*tile_at(0), diagonal);
+    assert_eq!(*tile_at(1), anti);
+}
+
+#[test]
+fn grid3_prefers_diagonal_tile() {
+    // a tile split along its diagonal, and a uniform tile of the same average
+    let diagonal = RgbImage::from_fn(8, 8, |x, y| if x > y { DARK } else { LIGHT });
+    let set = TileSet::from(&vec![DynamicImage::ImageRgb8(diagonal.clone())]);
+    let avg = *set.get(0).unwrap().avg();
+    let imgs = vec![
+        DynamicImage::ImageRgb8(diagonal.clone()),
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, avg)),
+    ];
+
+    let src = DynamicImage::ImageRgb8(diagonal.clone());
+    let mosaic = Mosaic::with_options(src, &imgs, 0.125, 8, options(Descriptor::Grid3));
+    let plan = mosaic.plan();
+    assert_eq!(plan.grid_size(), (1, 1));
+    assert_eq!(
+        *mosaic.tiles().get(plan.tile_at(0, 0)).unwrap().img(),
+        diagonal
+    );
+}
@@ -0,0 +1,53 @@
+//! Test that identical inputs always produce identical mosaics
+
+mod utils;
+
+use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+use std::error::Error;
+use std::io::Cursor;
+use tilr::Mosaic;
+use utils::{small_gradient, solid, solid_tiles};
+
+/// Build a mosaic and encode it as a PNG
+fn build_png(src: &DynamicImage, tiles: &Vec<DynamicImage>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let img = Mosaic::new(src.clone(), tiles, 1.0, 4).to_image();
+    let mut buf = Cursor::new(Vec::new());
+    img.write_to(&mut buf, ImageFormat::Png)?;
+    Ok(buf.into_inner())
+}
+
+#[test]
+fn identical_output() -> Result<(), Box<dyn Error>> {
+    // red & blue are equally close to the purple pixels in the top row
+    let mut src = small_gradient(16, 16).to_rgb8();
+    for x in 0..16 {
+        src.put_pixel(x, 0, Rgb([128, 0, 128]));
+    }
+    let src = DynamicImage::ImageRgb8(src);
+    let mut tiles = solid_tiles();
+    tiles.push(solid(&(255, 0, 0), 8, 8));
+    tiles.push(solid(&(0, 0, 255), 8, 8));
+
+    let first = build_png(&src, &tiles)?;
+    let second = build_png(&src, &tiles)?;
+    assert_eq!(first, second);
+
+    // the order in which tiles are given must not matter
+    let mut shuffled = tiles.clone();
+    shuffled.reverse();
+    shuffled.rotate_left(5);
+    assert_eq!(first, build_png(&src, &shuffled)?);
+
+    Ok(())
+}
+
+#[test]
+fn ties_are_order_independent() {
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128, 0, 128])));
+    let red = solid(&(255, 0, 0), 2, 2);
+    let blue = solid(&(0, 0, 255), 2, 2);
+
+    let a = Mosaic::new(src.clone(), vec![red.clone(), blue.clone()], 1.0, 2).to_image();
+    let b = Mosaic::new(src, vec![blue, red], 1.0, 2).to_image();
+    assert_eq!(a, b);
+}
@@ -0,0 +1,76 @@
+//! Test perturbing cell colors to break up banding
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions, MosaicPlan, Rect, TileSet};
+
+const DARK: Rgb<u8> = Rgb([100, 100, 100]);
+const LIGHT: Rgb<u8> = Rgb([110, 110, 110]);
+
+/// A horizontal ramp from `DARK` to `LIGHT`
+fn ramp() -> RgbImage {
+    RgbImage::from_fn(64, 4, |x, _| {
+        let v = 100 + (x * 10 / 63) as u8;
+        Rgb([v, v, v])
+    })
+}
+
+fn tiles() -> Vec<DynamicImage> {
+    [DARK, LIGHT]
+        .into_iter()
+        .map(|c| DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, c)))
+        .collect()
+}
+
+fn options(dither: u8, seed: u64) -> MosaicOptions {
+    MosaicOptions {
+        dither,
+        seed,
+        ..Default::default()
+    }
+}
+
+/// Whether the cells of each row which use the dark tile
+/// are all to the left of those which use the light tile
+fn is_banded(plan: &MosaicPlan, set: &TileSet) -> bool {
+    let (columns, rows) = plan.grid_size();
+    (0..rows).all(|y| {
+        let dark: Vec<bool> = (0..columns)
+            .map(|x| set.get(plan.tile_at(x, y)).unwrap().avg() == &DARK)
+            .collect();
+        dark.windows(2).all(|w| w[0] || !w[1])
+    })
+}
+
+#[test]
+fn breaks_up_bands() {
+    let set = TileSet::from(&tiles());
+
+    let plan = MosaicPlan::for_image(&ramp(), &set, options(0, 0));
+    assert!(is_banded(&plan, &set));
+
+    let plan = MosaicPlan::for_image(&ramp(), &set, options(4, 0));
+    assert!(!is_banded(&plan, &set));
+}
+
+#[test]
+fn reproducible_with_seed() {
+    let set = TileSet::from(&tiles());
+    let plan = |seed| MosaicPlan::for_image(&ramp(), &set, options(4, seed));
+
+    assert_eq!(plan(7).cells(), plan(7).cells());
+    assert_ne!(plan(7).cells(), plan(8).cells());
+}
+
+#[test]
+fn update_region_matches_full_render() {
+    let src = DynamicImage::ImageRgb8(ramp());
+    let mosaic = Mosaic::with_options(src, tiles(), 1.0, 4, options(4, 3));
+    let expected = mosaic.plan().render(mosaic.tiles());
+
+    let mut output = RgbImage::new(expected.width(), expected.height());
+    for x in (0..64).step_by(16) {
+        mosaic.update_region(&mut output, Rect::new(x, 1, 16, 3));
+        mosaic.update_region(&mut output, Rect::new(x, 0, 16, 1));
+    }
+    assert_eq!(output, expected);
+}
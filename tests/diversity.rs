@@ -0,0 +1,101 @@
+//! Test warning about tile sets whose colors are too alike
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{ColorHistogram, DiversityCheck, DiversityWarning, TileSet};
+use utils::{solid, solid_tiles};
+
+/// A set of dark, bluish tiles (e.g., night photos)
+fn narrow_tiles() -> Vec<DynamicImage> {
+    (0..8)
+        .map(|i| solid(&(10 + i * 2, 12 + i, 30 + i * 3), 2, 2))
+        .collect()
+}
+
+/// An image covering every value of each channel
+fn full_range() -> RgbImage {
+    RgbImage::from_fn(256, 4, |x, y| {
+        let v = x as u8;
+        Rgb(match y {
+            0 => [v, v, v],
+            1 => [v, 0, 255 - v],
+            _ => [255 - v, v, 128],
+        })
+    })
+}
+
+#[test]
+fn narrow_set() {
+    let summary = TileSet::from(&narrow_tiles()).summary();
+    let histogram = ColorHistogram::new(&full_range());
+    let warnings = DiversityCheck::default().check(&summary, Some(&histogram));
+
+    assert_eq!(warnings.len(), 4, "{:?}", warnings);
+    match &warnings[0] {
+        DiversityWarning::LowSpread { std_dev, min } => {
+            assert!(*std_dev < 10.0);
+            assert_eq!(*min, DiversityCheck::default().min_std_dev);
+        }
+        w => panic!("unexpected warning {:?}", w),
+    }
+    assert_eq!(
+        warnings[1],
+        DiversityWarning::NarrowRange {
+            channel: 0,
+            tiles: (10, 24),
+            source: (2, 253),
+        }
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        "tiles cover red 10-24, but the source spans 2-253"
+    );
+
+    // without a source, only the spread is checked
+    assert_eq!(DiversityCheck::default().check(&summary, None).len(), 1);
+}
+
+#[test]
+fn wide_set() {
+    let summary = TileSet::from(&solid_tiles()).summary();
+    let histogram = ColorHistogram::new(&full_range());
+    assert_eq!(
+        DiversityCheck::default().check(&summary, Some(&histogram)),
+        []
+    );
+}
+
+#[test]
+fn narrow_source() {
+    // a dark source is covered by dark tiles, so only the spread is a problem
+    let source = RgbImage::from_fn(16, 16, |x, y| Rgb([12 + x as u8, 14, 32 + y as u8]));
+    let summary = TileSet::from(&narrow_tiles()).summary();
+    let warnings = DiversityCheck::default().check(&summary, Some(&ColorHistogram::new(&source)));
+    assert!(matches!(
+        warnings.as_slice(),
+        [DiversityWarning::LowSpread { .. }]
+    ));
+
+    // a tighter margin finds the gaps at the ends of the channels
+    let check = DiversityCheck {
+        min_std_dev: 0.0,
+        margin: 0,
+    };
+    let warnings = check.check(&summary, Some(&ColorHistogram::new(&source)));
+    assert!(!warnings.is_empty());
+    assert!(warnings
+        .iter()
+        .all(|w| matches!(w, DiversityWarning::NarrowRange { .. })));
+}
+
+#[test]
+fn histogram_ignores_outliers() {
+    let mut img = RgbImage::from_pixel(20, 20, Rgb([100, 100, 100]));
+    img.put_pixel(0, 0, Rgb([255, 0, 255]));
+    let histogram = ColorHistogram::new(&img);
+    assert_eq!(histogram.counts(0)[100], 399);
+    for c in 0..3 {
+        assert_eq!(histogram.range(c), (100, 100));
+    }
+}
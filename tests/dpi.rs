@@ -0,0 +1,79 @@
+//! Test recording the print resolution of mosaics
+
+use image::{GenericImageView, Rgb, RgbImage};
+use tilr::PrintSize;
+
+mod utils;
+use utils::scratch_dir;
+
+fn img() -> RgbImage {
+    RgbImage::from_fn(6, 4, |x, y| Rgb([x as u8 * 40, y as u8 * 60, 0]))
+}
+
+/// Find the data of the first chunk of the given type in a PNG file
+fn png_chunk<'a>(png: &'a [u8], chunk: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut pos = 8; // skip the signature
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        if &png[pos + 4..pos + 8] == chunk {
+            return Some(&png[pos + 8..pos + 8 + len]);
+        }
+        pos += 12 + len; // length, type, data, CRC
+    }
+    None
+}
+
+#[test]
+fn png_phys() {
+    let dir = scratch_dir("dpi-png").unwrap();
+    let path = dir.join("300dpi.png");
+    tilr::save_image(&img(), &path, Some(300.0)).unwrap();
+
+    let png = std::fs::read(&path).unwrap();
+    let phys = png_chunk(&png, b"pHYs").expect("no pHYs chunk");
+    // 300 dots per inch is 11,811 dots per meter
+    assert_eq!(&phys[0..4], &11811u32.to_be_bytes());
+    assert_eq!(&phys[4..8], &11811u32.to_be_bytes());
+    assert_eq!(phys[8], 1); // meters
+
+    // the pixels are unchanged
+    assert_eq!(image::open(&path).unwrap().to_rgb8(), img());
+
+    // no metadata unless asked for
+    let path = dir.join("no-dpi.png");
+    tilr::save_image(&img(), &path, None).unwrap();
+    assert!(png_chunk(&std::fs::read(&path).unwrap(), b"pHYs").is_none());
+}
+
+#[test]
+fn jpeg_density() {
+    let dir = scratch_dir("dpi-jpeg").unwrap();
+    let path = dir.join("150dpi.jpg");
+    tilr::save_image(&img(), &path, Some(150.0)).unwrap();
+
+    let jpeg = std::fs::read(&path).unwrap();
+    let jfif = jpeg
+        .windows(5)
+        .position(|w| w == b"JFIF\0")
+        .expect("no JFIF header");
+    // version (2 bytes), units (1 = dots per inch), then X and Y density
+    let density = &jpeg[jfif + 7..jfif + 12];
+    assert_eq!(density, [1, 0, 150, 0, 150]);
+    assert_eq!(image::open(&path).unwrap().dimensions(), (6, 4));
+    assert!(tilr::records_dpi(&path));
+    assert!(!tilr::records_dpi(&dir.join("mosaic.bmp")));
+}
+
+#[test]
+fn print_size() {
+    let size = PrintSize::new((3000, 1500), 300.0);
+    assert_eq!(size.width_in, 10.0);
+    assert_eq!(size.height_in, 5.0);
+    assert!((size.width_cm() - 25.4).abs() < 1e-4);
+    assert_eq!(size.to_string(), "10.0in x 5.0in (25.4cm x 12.7cm)");
+
+    // 100cm at 300 DPI is 11,811px
+    assert_eq!(tilr::print_width_px(100.0, 300.0), 11811);
+    let scale = tilr::scale_for_print(1000, 8, 100.0, 300.0);
+    assert!((scale - 11811.0 / 8000.0).abs() < 1e-6);
+}
@@ -0,0 +1,62 @@
+//! Test reporting what a mosaic would take to build, without building it
+
+use assert_cmd::Command;
+use image::{ImageFormat, Rgb, RgbImage};
+use tilr::OutputOptions;
+
+mod utils;
+use utils::{solid, tiles_and_gradient};
+
+#[test]
+fn dry_run() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("dry_run", 6, 4)?;
+    let tile_dir = dir.join("tiles");
+    solid(&(0, 255, 0), 8, 8).save(tile_dir.join("green.png"))?;
+
+    let output = Command::cargo_bin("tilr")?
+        .arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--tile-size", "4", "--dry-run", "--map-cache"])
+        .arg(dir.join("cache.json"))
+        .arg("-o")
+        .arg(dir.join("out.png"))
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(
+        stderr.contains("would be a 24px x 16px image"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Cells: 24 (0 background)"), "{}", stderr);
+    assert!(stderr.contains("unique tiles used, of 3"), "{}", stderr);
+    // 24px x 16px of RGB, and three 4px x 4px tiles
+    assert!(
+        stderr.contains("Estimated memory: 1.3 KB (1.2 KB for the mosaic, 144 B for the tiles)"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("Estimated file size: "), "{}", stderr);
+
+    // nothing is written
+    assert!(!dir.join("out.png").exists());
+    assert!(!dir.join("cache.json").exists());
+    Ok(())
+}
+
+#[test]
+fn encoded_size() -> Result<(), Box<dyn std::error::Error>> {
+    let img = RgbImage::from_fn(32, 32, |x, y| Rgb([x as u8 * 8, y as u8 * 8, 0]));
+    let options = OutputOptions::default();
+    for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::Bmp] {
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        img.write_to(&mut bytes, format)?;
+        let size = tilr::encoded_size(&img, format, &options)?;
+        if format == ImageFormat::Bmp {
+            assert_eq!(size, bytes.get_ref().len() as u64);
+        }
+        assert!(size > 0);
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+//! Test reporting why mosaics can't be built or loaded
+
+use image::DynamicImage;
+use std::error::Error as _;
+use std::fs;
+use tilr::{Error, Mosaic, ScaleError};
+
+mod utils;
+use utils::{scratch_dir, small_gradient, solid_tiles};
+
+#[test]
+fn constructors() {
+    let no_tiles: Vec<DynamicImage> = Vec::new();
+    let err = Mosaic::try_new(small_gradient(4, 4), no_tiles, 1.0, 4).err();
+    assert!(matches!(err, Some(Error::NoTiles)));
+
+    let err = Mosaic::try_new(small_gradient(4, 4), solid_tiles(), 1.0, 0).err();
+    assert!(matches!(err, Some(Error::TileSize(0))));
+
+    let err = Mosaic::try_new(small_gradient(4, 4), solid_tiles(), 0.0, 4).err();
+    assert!(matches!(err, Some(Error::Scale(ScaleError::Invalid(_)))));
+    assert!(err.unwrap().source().is_some());
+
+    let err = Mosaic::try_new(small_gradient(100, 10), solid_tiles(), 0.05, 4).err();
+    assert!(matches!(
+        err,
+        Some(Error::Scale(ScaleError::Collapsed { .. }))
+    ));
+
+    let mosaic = Mosaic::try_new(small_gradient(4, 4), solid_tiles(), 1.0, 4).unwrap();
+    assert_eq!(mosaic.output_size(), (16, 16));
+}
+
+#[test]
+fn builder() {
+    let err = Mosaic::builder(small_gradient(4, 4)).build().err();
+    assert!(matches!(err, Some(Error::NoTiles)));
+}
+
+#[test]
+fn loading() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = scratch_dir("error")?;
+
+    let missing = dir.join("missing");
+    let err = tilr::load_tiles(&missing).unwrap_err();
+    assert!(matches!(&err, Error::NotADirectory(path) if path == &missing));
+    assert!(err.to_string().contains("must be a directory"));
+
+    let err = tilr::load_oriented(&dir.join("missing.png")).unwrap_err();
+    assert!(matches!(err, Error::Io(_)));
+
+    let garbage = dir.join("garbage.png");
+    fs::write(&garbage, b"not a png")?;
+    let err = tilr::load_oriented(&garbage).unwrap_err();
+    assert!(matches!(err, Error::Decode(_)));
+    Ok(())
+}
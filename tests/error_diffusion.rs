@@ -0,0 +1,115 @@
+//! Test diffusing the error of each cell's tile to the cells after it
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Descriptor, MosaicOptions, MosaicPlan, TileSet};
+
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+fn tiles() -> TileSet {
+    let imgs: Vec<DynamicImage> = [BLACK, WHITE]
+        .into_iter()
+        .map(|c| DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, c)))
+        .collect();
+    TileSet::from(&imgs)
+}
+
+/// A horizontal ramp from black to white
+fn ramp() -> RgbImage {
+    RgbImage::from_fn(64, 16, |x, _| {
+        let v = (x * 255 / 63) as u8;
+        Rgb([v, v, v])
+    })
+}
+
+fn options(error_diffusion: f32) -> MosaicOptions {
+    MosaicOptions {
+        error_diffusion,
+        ..Default::default()
+    }
+}
+
+/// The mean brightness of the tiles in each band of eight columns
+fn band_means(plan: &MosaicPlan, set: &TileSet) -> Vec<f32> {
+    let (columns, rows) = plan.grid_size();
+    (0..columns / 8)
+        .map(|band| {
+            let mut total = 0.0;
+            for y in 0..rows {
+                for x in band * 8..(band + 1) * 8 {
+                    total += set.get(plan.tile_at(x, y)).unwrap().avg().0[0] as f32;
+                }
+            }
+            total / (8 * rows) as f32
+        })
+        .collect()
+}
+
+#[test]
+fn follows_gradients() {
+    let set = tiles();
+    let src = ramp();
+
+    // without diffusion, the ramp is split into a black and a white band
+    let plan = MosaicPlan::for_image(&src, &set, options(0.0));
+    assert_eq!(
+        band_means(&plan, &set),
+        [0.0, 0.0, 0.0, 0.0, 255.0, 255.0, 255.0, 255.0]
+    );
+
+    // with it, each band is as bright as the ramp on average
+    let plan = MosaicPlan::for_image(&src, &set, options(1.0));
+    for (band, mean) in band_means(&plan, &set).into_iter().enumerate() {
+        let expected = (band * 8..(band + 1) * 8)
+            .map(|x| src.get_pixel(x as u32, 0).0[0] as f32)
+            .sum::<f32>()
+            / 8.0;
+        assert!(
+            (mean - expected).abs() < 20.0,
+            "band {}: {} != {}",
+            band,
+            mean,
+            expected
+        );
+    }
+}
+
+#[test]
+fn strength() {
+    let set = tiles();
+    let src = RgbImage::from_pixel(32, 32, Rgb([64, 64, 64]));
+    let white = |strength| {
+        let plan = MosaicPlan::for_image(&src, &set, options(strength));
+        plan.cells()
+            .iter()
+            .filter(|&&i| set.get(i).unwrap().avg() == &WHITE)
+            .count()
+    };
+
+    // a quarter of the way from black to white
+    let full = white(1.0);
+    assert!((230..=282).contains(&full), "{}", full);
+    // with less of the error carried on, it never builds up far enough
+    assert_eq!(white(0.2), 0);
+    assert_eq!(white(0.0), 0);
+}
+
+#[test]
+fn deterministic() {
+    let set = tiles();
+    let plan = || MosaicPlan::for_image(&ramp(), &set, options(0.75));
+    assert_eq!(plan().cells(), plan().cells());
+}
+
+#[test]
+fn with_block_descriptors() {
+    let set = tiles();
+    let options = MosaicOptions {
+        descriptor: Descriptor::Quadrants,
+        ..options(1.0)
+    };
+    let plan = MosaicPlan::for_image(&ramp(), &set, options);
+    let means = band_means(&plan, &set);
+    assert!(means.windows(2).all(|w| w[0] <= w[1] + 32.0), "{:?}", means);
+    assert!(means[0] < 64.0 && means[7] > 192.0, "{:?}", means);
+}
@@ -0,0 +1,76 @@
+//! Test estimating the rate of progress and the time left
+
+use std::time::{Duration, Instant};
+use tilr::Eta;
+
+/// Record progress at `rate` units per second for `secs` seconds, every
+/// 10ms, starting from `done` units at `start`
+fn feed(eta: &mut Eta, start: Instant, done: u64, rate: u64, secs: u64) -> (Instant, u64) {
+    let steps = secs * 100;
+    for step in 1..=steps {
+        let at = start + Duration::from_millis(10 * step);
+        eta.record_at(done + rate * step / 100, at);
+    }
+    (start + Duration::from_secs(secs), done + rate * secs)
+}
+
+fn assert_near(actual: Duration, expected: Duration) {
+    let diff = actual.abs_diff(expected);
+    assert!(diff < expected / 50, "{:?} != {:?}", actual, expected);
+}
+
+#[test]
+fn nothing_to_go_on() {
+    let start = Instant::now();
+    let mut eta = Eta::new(100);
+    assert_eq!(eta.rate(), None);
+    assert_eq!(eta.remaining(), None);
+    eta.record_at(0, start);
+    assert_eq!(eta.rate(), None);
+    assert_eq!(eta.to_string(), "estimating time left");
+
+    // or no progress being made
+    eta.record_at(0, start + Duration::from_secs(1));
+    assert_eq!(eta.rate(), Some(0.0));
+    assert_eq!(eta.remaining(), None);
+}
+
+#[test]
+fn steady_rate() {
+    let start = Instant::now();
+    let mut eta = Eta::new(10_000);
+    eta.record_at(0, start);
+    let (_, done) = feed(&mut eta, start, 0, 100, 2);
+    assert_eq!(eta.done(), done);
+    assert!((eta.rate().unwrap() - 100.0).abs() < 1.0);
+    assert_near(eta.remaining().unwrap(), Duration::from_secs(98));
+    assert_eq!(eta.to_string(), "100/s, 1m 38s left");
+}
+
+#[test]
+fn follows_changes() {
+    let start = Instant::now();
+    let mut eta = Eta::with_window(100_000, Duration::from_secs(2));
+    eta.record_at(0, start);
+    let (now, done) = feed(&mut eta, start, 0, 100, 10);
+    assert_near(eta.remaining().unwrap(), Duration::from_secs(990));
+
+    // speeding up; once the slow part is out of the window, the estimate
+    // only reflects the new rate
+    let (_, done) = feed(&mut eta, now, done, 2000, 3);
+    let rate = eta.rate().unwrap();
+    assert!((rate - 2000.0).abs() < 20.0, "{}", rate);
+    let left = (100_000 - done) as f64 / 2000.0;
+    assert_near(eta.remaining().unwrap(), Duration::from_secs_f64(left));
+    assert!(eta.to_string().starts_with("2.0k/s, 47s"), "{}", eta);
+}
+
+#[test]
+fn finished() {
+    let start = Instant::now();
+    let mut eta = Eta::new(500);
+    eta.record_at(0, start);
+    feed(&mut eta, start, 0, 250, 2);
+    assert_eq!(eta.remaining(), Some(Duration::ZERO));
+    assert_eq!(eta.to_string(), "250/s, <1s left");
+}
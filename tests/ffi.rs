@@ -0,0 +1,80 @@
+//! Test the C API by compiling and running a small C program against it
+
+#![cfg(all(feature = "ffi", unix))]
+
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+mod utils;
+use utils::scratch_dir;
+
+#[test]
+fn c_program() -> Result<(), Box<dyn Error>> {
+    // the shared library is only built on request (see the README), in a
+    // separate target directory so as not to wait on the lock on this one
+    let target_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ffi-target");
+    let output = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "cdylib",
+        ])
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "Unable to build the shared library: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lib_dir = target_dir.join("debug");
+
+    let work_dir = scratch_dir("ffi")?;
+    let tile_dir = work_dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+
+    for (i, c) in [[255, 0, 0], [0, 255, 0], [0, 0, 255]].iter().enumerate() {
+        RgbImage::from_pixel(4, 4, Rgb(*c)).save(tile_dir.join(format!("tile-{}.png", i)))?;
+    }
+
+    let compiler = cc::Build::new()
+        .target(env!("TILR_TARGET"))
+        .host(env!("TILR_TARGET"))
+        .opt_level(0)
+        .cargo_metadata(false)
+        .get_compiler();
+    let exe = work_dir.join("ffi-test");
+    let status = compiler
+        .to_command()
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/ffi/main.c"))
+        .arg("-I")
+        .arg(env!("OUT_DIR"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("-ltilr")
+        .arg("-o")
+        .arg(&exe)
+        .status()?;
+    assert!(status.success(), "Unable to compile the C test program");
+
+    let output = Command::new(&exe)
+        .arg(&tile_dir)
+        .env("LD_LIBRARY_PATH", &lib_dir)
+        .env("DYLD_LIBRARY_PATH", &lib_dir)
+        .output()?;
+    assert!(
+        output.status.success(),
+        "C test program failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+/* Exercise the tilr C API; see tests/ffi.rs. */
+
+#include <stdio.h>
+#include <stdint.h>
+#include "tilr.h"
+
+#define CHECK(cond)                                            \
+    do {                                                       \
+        if (!(cond)) {                                         \
+            fprintf(stderr, "check failed: %s\n", #cond);      \
+            return 1;                                          \
+        }                                                      \
+    } while (0)
+
+int main(int argc, char **argv) {
+    CHECK(argc == 2);
+
+    /* Errors are reported via status codes and the last error message */
+    TilrTileSet *missing = NULL;
+    CHECK(tilr_tileset_from_dir("/does/not/exist", &missing) == TILR_STATUS_FAILED);
+    CHECK(missing == NULL);
+    CHECK(tilr_last_error_message() != NULL);
+
+    TilrTileSet *tiles = NULL;
+    CHECK(tilr_tileset_from_dir(argv[1], &tiles) == TILR_STATUS_OK);
+
+    /* An 8x8 red-to-blue gradient */
+    uint8_t rgb[8 * 8 * 3];
+    for (int y = 0; y < 8; y++) {
+        for (int x = 0; x < 8; x++) {
+            uint8_t *px = &rgb[(y * 8 + x) * 3];
+            px[0] = (uint8_t)(255 - x * 32);
+            px[1] = 0;
+            px[2] = (uint8_t)(x * 32);
+        }
+    }
+
+    /* An invalid scaling factor is reported rather than panicking */
+    TilrMosaic *bad = NULL;
+    CHECK(tilr_mosaic_new(rgb, 8, 8, tiles, 0.0f, 4, &bad) == TILR_STATUS_INVALID_ARGUMENT);
+    CHECK(bad == NULL);
+
+    TilrMosaic *mosaic = NULL;
+    CHECK(tilr_mosaic_new(rgb, 8, 8, tiles, 1.0f, 4, &mosaic) == TILR_STATUS_OK);
+
+    uint8_t *out = NULL;
+    uint32_t width = 0, height = 0;
+    CHECK(tilr_mosaic_render(mosaic, &out, &width, &height) == TILR_STATUS_OK);
+    CHECK(out != NULL);
+    CHECK(width == 32 && height == 32);
+
+    /* The top-left tile is red, the top-right tile is blue */
+    CHECK(out[0] == 255 && out[1] == 0 && out[2] == 0);
+    uint8_t *last = &out[(width - 1) * 3];
+    CHECK(last[0] == 0 && last[1] == 0 && last[2] == 255);
+
+    tilr_buffer_free(out);
+    tilr_mosaic_free(mosaic);
+    tilr_tileset_free(tiles);
+
+    printf("ok\n");
+    return 0;
+}
@@ -0,0 +1,114 @@
+//! Test filling gaps in the colors covered by a tile set
+
+mod utils;
+
+use image::{Rgb, RgbImage};
+use tilr::{GapFill, Metric, TileSet};
+use utils::{small_gradient, solid};
+
+#[test]
+fn primaries() {
+    let primaries = vec![
+        solid(&(255, 0, 0), 4, 4),
+        solid(&(0, 255, 0), 4, 4),
+        solid(&(0, 0, 255), 4, 4),
+    ];
+    let mut tiles = TileSet::from(&primaries);
+    let max_distance = 100.0;
+
+    let added = tiles.fill_gaps(max_distance, Metric::Rgb);
+    assert!(added > 0);
+    assert_eq!(tiles.len(), 3 + added);
+    assert_eq!(tiles.iter().filter(|t| t.is_synthetic()).count(), added);
+    assert_eq!(tiles.tile_side_len(), 4);
+
+    // the worst-case coverage is now below the threshold
+    let report = tiles.coverage_report(TileSet::FILL_GAPS_DIVISIONS, Metric::Rgb);
+    assert!(report.percentile(100.0) <= max_distance);
+
+    // there are synthetic tiles near gray and the secondary colors
+    // (within the threshold plus half the diagonal of a coverage cell)
+    for color in [[128, 128, 128], [0, 255, 255], [255, 0, 255], [255, 255, 0]] {
+        let nearest = tiles
+            .iter()
+            .filter(|t| t.is_synthetic())
+            .map(|t| Metric::Rgb.distance(&Rgb(color), t.avg()))
+            .fold(f32::INFINITY, f32::min);
+        assert!(nearest < max_distance + 14.0, "{:?}: {}", color, nearest);
+    }
+
+    // the set is already covered well enough, so nothing more is added
+    assert_eq!(tiles.fill_gaps(max_distance, Metric::Rgb), 0);
+}
+
+#[test]
+fn source_colors() {
+    let mut tiles = TileSet::from(&vec![solid(&(255, 0, 0), 4, 4), solid(&(0, 0, 255), 4, 4)]);
+    let img = small_gradient(16, 16).to_rgb8();
+    let max_distance = 30.0;
+
+    let added = tiles.fill_source_gaps(&img, max_distance, Metric::Rgb, GapFill::Solid);
+    assert!(added > 0);
+    assert_eq!(tiles.len(), 2 + added);
+    assert_eq!(tiles.iter().filter(|t| t.is_synthetic()).count(), added);
+
+    // every color of the image is now close to some tile
+    for px in img.pixels() {
+        let nearest = tiles
+            .iter()
+            .map(|t| Metric::Rgb.distance(px, t.avg()))
+            .fold(f32::INFINITY, f32::min);
+        assert!(nearest <= max_distance, "{:?}: {}", px, nearest);
+    }
+    // with far fewer tiles than filling every color
+    let mut all = TileSet::from(&vec![solid(&(255, 0, 0), 4, 4), solid(&(0, 0, 255), 4, 4)]);
+    assert!(added < all.fill_gaps(max_distance, Metric::Rgb));
+
+    // the image is already covered, so nothing more is added
+    assert_eq!(
+        tiles.fill_source_gaps(&img, max_distance, Metric::Rgb, GapFill::Solid),
+        0
+    );
+}
+
+#[test]
+fn noise() {
+    let tiles = |seed| {
+        let mut tiles = TileSet::from(&vec![solid(&(255, 0, 0), 8, 8)]);
+        let img = RgbImage::from_pixel(2, 2, Rgb([0, 128, 64]));
+        tiles.fill_source_gaps(&img, 10.0, Metric::Rgb, GapFill::Noise(seed));
+        tiles
+            .iter()
+            .find(|t| t.is_synthetic())
+            .unwrap()
+            .img()
+            .into_owned()
+    };
+
+    let tile = tiles(1);
+    // the pixels vary, but only subtly
+    assert!(tile.pixels().any(|px| px != tile.get_pixel(0, 0)));
+    for px in tile.pixels() {
+        for (c, v) in px.0.iter().zip([0, 128, 64]) {
+            assert!(c.abs_diff(v) <= GapFill::NOISE_AMPLITUDE, "{:?}", px);
+        }
+    }
+    // and depend only on the seed
+    assert_eq!(tiles(1), tile);
+    assert_ne!(tiles(2), tile);
+}
+
+#[test]
+#[should_panic(expected = "finite and non-negative")]
+fn negative_distance() {
+    let mut tiles = TileSet::from(&vec![solid(&(255, 0, 0), 4, 4)]);
+    tiles.fill_gaps(-1.0, Metric::Rgb);
+}
+
+#[test]
+#[should_panic(expected = "finite and non-negative")]
+fn nan_source_distance() {
+    let mut tiles = TileSet::from(&vec![solid(&(255, 0, 0), 4, 4)]);
+    let img = small_gradient(4, 4).to_rgb8();
+    tiles.fill_source_gaps(&img, f32::NAN, Metric::Rgb, GapFill::Solid);
+}
@@ -0,0 +1,85 @@
+//! Test choosing the filters used to scale the source image and the tiles
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions, ResizeFilter};
+
+/// A black and white checkerboard of `square` x `square` pixel squares
+fn checkerboard(size: u32, square: u32) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(size, size, |x, y| {
+        match (x / square + y / square) % 2 {
+            0 => Rgb([0, 0, 0]),
+            _ => Rgb([255, 255, 255]),
+        }
+    }))
+}
+
+/// A smooth horizontal gradient with a bright band across the middle
+fn gradient(w: u32, h: u32) -> RgbImage {
+    RgbImage::from_fn(w, h, |x, y| {
+        let v = (x * 255 / (w - 1)) as u8;
+        match y % 8 < 4 {
+            true => Rgb([v, 128, 255 - v]),
+            false => Rgb([v / 2, 200, 40]),
+        }
+    })
+}
+
+#[test]
+fn independent_filters() {
+    let options = MosaicOptions {
+        source_filter: ResizeFilter::Lanczos3,
+        tile_filter: ResizeFilter::Nearest,
+        ..Default::default()
+    };
+    let src = gradient(64, 32);
+    let tiles = vec![checkerboard(64, 8)];
+    let mosaic = Mosaic::with_options(
+        DynamicImage::ImageRgb8(src.clone()),
+        &tiles,
+        0.5,
+        8,
+        options,
+    );
+
+    // the tile keeps its hard edges
+    let tile = mosaic.tiles().get(0).unwrap();
+    assert_eq!(tile.side_len(), 8);
+    assert!(tile.img().as_raw().iter().all(|&v| v == 0 || v == 255));
+    assert_eq!(*tile.img(), checkerboard(8, 1).to_rgb8());
+
+    // while the source is resampled smoothly
+    let expected = imageops::resize(&src, 32, 16, FilterType::Lanczos3);
+    assert_eq!(mosaic.source(), &expected);
+    assert_ne!(
+        mosaic.source(),
+        &imageops::resize(&src, 32, 16, FilterType::Nearest)
+    );
+}
+
+#[test]
+fn default_filters() {
+    let tiles = vec![checkerboard(64, 8)];
+    let mosaic = Mosaic::new(checkerboard(32, 4), &tiles, 0.5, 8);
+    // the default (triangle) filter blurs the checkerboard
+    let tile = mosaic.tiles().get(0).unwrap();
+    assert!(tile.img().as_raw().iter().any(|&v| v != 0 && v != 255));
+    assert_eq!(
+        mosaic.source(),
+        &imageops::resize(&checkerboard(32, 4).to_rgb8(), 16, 16, FilterType::Triangle)
+    );
+}
+
+#[test]
+fn parse_filters() {
+    for filter in [
+        ResizeFilter::Nearest,
+        ResizeFilter::Triangle,
+        ResizeFilter::CatmullRom,
+        ResizeFilter::Gaussian,
+        ResizeFilter::Lanczos3,
+    ] {
+        assert_eq!(filter.to_string().parse::<ResizeFilter>(), Ok(filter));
+    }
+    assert!("bicubic".parse::<ResizeFilter>().is_err());
+}
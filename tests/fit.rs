@@ -0,0 +1,93 @@
+//! Test cropping tiles to squares
+
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use tilr::{TileFit, TileSet};
+
+const GRAY: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// A flat gray image with a detailed `patch`x`patch` square starting
+/// `start` pixels along its long side
+fn with_patch(w: u32, h: u32, start: u32, patch: u32) -> DynamicImage {
+    let mut state = 12345u32;
+    let mut next = || {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 16) as u8
+    };
+    let blocks: Vec<Rgb<u8>> = (0..patch * patch)
+        .map(|_| Rgb([next(), next(), next()]))
+        .collect();
+    let img = RgbImage::from_fn(w, h, |x, y| {
+        let (u, v) = if w > h { (y, x) } else { (x, y) };
+        if u < patch && (start..start + patch).contains(&v) {
+            // 4px blocks of random colors
+            blocks[((v - start) / 4 * patch + u / 4) as usize]
+        } else {
+            GRAY
+        }
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Count the pixels of an image which aren't flat gray
+fn detail(img: &DynamicImage) -> usize {
+    img.to_rgb8().pixels().filter(|px| **px != GRAY).count()
+}
+
+#[test]
+fn smart_portrait() {
+    let img = with_patch(40, 120, 6, 20);
+    let full = detail(&img);
+    assert!(full > 300);
+
+    let center = TileFit::Center.apply(img.clone());
+    assert_eq!(center.dimensions(), (40, 40));
+    assert_eq!(detail(&center), 0);
+
+    let smart = TileFit::Smart.apply(img);
+    assert_eq!(smart.dimensions(), (40, 40));
+    assert_eq!(detail(&smart), full);
+}
+
+#[test]
+fn smart_landscape() {
+    let img = with_patch(300, 50, 260, 24);
+    let full = detail(&img);
+
+    let smart = TileFit::Smart.apply(img);
+    assert_eq!(smart.dimensions(), (50, 50));
+    assert_eq!(detail(&smart), full);
+}
+
+#[test]
+fn unchanged_or_centered() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(30, 90, GRAY));
+    assert_eq!(
+        TileFit::Smart.apply(img.clone()),
+        TileFit::Center.apply(img.clone())
+    );
+
+    let square = with_patch(32, 32, 4, 8);
+    assert_eq!(TileFit::Smart.apply(square.clone()), square);
+    assert_eq!(TileFit::Stretch.apply(img.clone()), img);
+}
+
+#[test]
+fn tile_set() {
+    let img = with_patch(40, 120, 6, 20);
+    let stretched = TileSet::with_fit(
+        std::slice::from_ref(&img),
+        TileSet::DEFAULT_BACKGROUND,
+        TileFit::Stretch,
+    );
+    let smart = TileSet::with_fit(
+        std::slice::from_ref(&img),
+        TileSet::DEFAULT_BACKGROUND,
+        TileFit::Smart,
+    );
+    assert_eq!(stretched.tile_side_len(), 40);
+    assert_eq!(smart.tile_side_len(), 40);
+    assert_eq!(
+        *smart.get(0).unwrap().img(),
+        TileFit::Smart.apply(img).to_rgb8()
+    );
+}
@@ -0,0 +1,105 @@
+//! Test spreading repeated tiles evenly over flat regions with blue noise
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{MosaicOptions, MosaicPlan, TileSet};
+
+const GRAY: [u8; 3] = [128, 128, 128];
+const SIDE: u32 = 16;
+
+/// Four tiles, each exactly 9 away from `GRAY`
+fn tiles() -> TileSet {
+    let offsets: [[i32; 3]; 4] = [[9, 0, 0], [-9, 0, 0], [0, 9, 0], [0, -9, 0]];
+    let imgs: Vec<DynamicImage> = offsets
+        .iter()
+        .map(|d| {
+            let px = Rgb([0, 1, 2].map(|c| (GRAY[c] as i32 + d[c]) as u8));
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, px))
+        })
+        .collect();
+    TileSet::from(&imgs)
+}
+
+fn options(flat_shuffle: f32, seed: u64) -> MosaicOptions {
+    MosaicOptions {
+        usage_penalty: 0.5,
+        flat_shuffle,
+        seed,
+        ..Default::default()
+    }
+}
+
+fn plan(options: MosaicOptions) -> MosaicPlan {
+    let src = RgbImage::from_pixel(SIDE, SIDE, Rgb(GRAY));
+    MosaicPlan::for_image(&src, &tiles(), options)
+}
+
+/// The most cells any one tile takes in any row or column
+fn most_in_line(plan: &MosaicPlan) -> u32 {
+    let mut most = 0;
+    for i in 0..SIDE {
+        let mut rows = [0; 4];
+        let mut columns = [0; 4];
+        for j in 0..SIDE {
+            rows[plan.tile_at(j, i)] += 1;
+            columns[plan.tile_at(i, j)] += 1;
+        }
+        most = most.max(rows.into_iter().chain(columns).max().unwrap());
+    }
+    most
+}
+
+fn usage(plan: &MosaicPlan) -> Vec<usize> {
+    let mut counts = vec![0; 4];
+    for &i in plan.cells() {
+        counts[i] += 1;
+    }
+    counts
+}
+
+#[test]
+fn no_stripes() {
+    let unshuffled = plan(options(0.0, 0));
+
+    // shuffled at random, each tile takes 8 to 10 cells of its worst line
+    // (a quarter of each line is expected, but some line is bound to be off)
+    let mut total = 0;
+    for seed in 0..8 {
+        let shuffled = plan(options(1.0, seed));
+        let most = most_in_line(&shuffled);
+        assert!(most <= SIDE / 2 + 1, "seed {}: {}", seed, most);
+        assert_eq!(usage(&shuffled), usage(&unshuffled));
+        total += most;
+    }
+    assert!(total <= 8 * SIDE / 2, "{}", total);
+}
+
+#[test]
+fn deterministic() {
+    assert_eq!(plan(options(1.0, 7)), plan(options(1.0, 7)));
+    assert_ne!(plan(options(1.0, 7)).cells(), plan(options(1.0, 8)).cells());
+}
+
+#[test]
+fn stays_within_regions() {
+    // the left half is gray and the right half red, each with its own tiles
+    let src = RgbImage::from_fn(8, 8, |x, _| match x < 4 {
+        true => Rgb(GRAY),
+        false => Rgb([200, 20, 20]),
+    });
+    let mut imgs: Vec<DynamicImage> = (0..2)
+        .map(|i| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128 + i * 2, 128, 128]))))
+        .collect();
+    imgs.extend(
+        (0..2).map(|i| {
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([200 + i * 2, 20, 20])))
+        }),
+    );
+    let tiles = TileSet::from(&imgs);
+    let plan = MosaicPlan::for_image(&src, &tiles, options(1.0, 0));
+    for y in 0..8 {
+        for x in 0..8 {
+            let red = tiles.get(plan.tile_at(x, y)).unwrap().avg().0[0] >= 200;
+            assert_eq!(red, x >= 4, "({}, {})", x, y);
+        }
+    }
+}
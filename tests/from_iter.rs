@@ -0,0 +1,55 @@
+//! Test building tile sets from slices and iterators
+
+mod utils;
+
+use image::DynamicImage;
+use tilr::{Mosaic, MosaicOptions, TileSet};
+use utils::{small_gradient, solid_tiles};
+
+/// Get the average color of every tile in a set
+fn avgs(set: &TileSet) -> Vec<[u8; 3]> {
+    set.iter().map(|t| t.avg().0).collect()
+}
+
+#[test]
+fn same_tiles() {
+    let imgs = solid_tiles();
+    let expected = avgs(&TileSet::from(&imgs));
+
+    assert_eq!(avgs(&TileSet::from_slice(&imgs)), expected);
+    assert_eq!(avgs(&TileSet::from_slice(&imgs[..])), expected);
+    assert_eq!(avgs(&imgs.iter().collect()), expected);
+    assert_eq!(avgs(&imgs.clone().into_iter().collect()), expected);
+    assert_eq!(avgs(&TileSet::try_from_iter(&imgs).unwrap()), expected);
+    // from an iterator adapter, in reverse (the order doesn't matter)
+    let reversed: TileSet = imgs.iter().rev().cloned().collect();
+    assert_eq!(avgs(&reversed), expected);
+}
+
+#[test]
+fn same_mosaic() {
+    let imgs = solid_tiles();
+    let src = small_gradient(12, 10);
+    let expected = Mosaic::new(src.clone(), &imgs, 1.0, 4).plan();
+
+    let from_slice = Mosaic::new(src.clone(), &imgs[..], 1.0, 4).plan();
+    assert_eq!(from_slice, expected);
+    let from_iter = Mosaic::new(src.clone(), imgs.iter().rev(), 1.0, 4).plan();
+    assert_eq!(from_iter, expected);
+    let options = MosaicOptions::default();
+    let owned = Mosaic::with_options(src, imgs, 1.0, 4, options).plan();
+    assert_eq!(owned, expected);
+}
+
+#[test]
+fn empty() {
+    let none: Vec<DynamicImage> = Vec::new();
+    let err = TileSet::try_from_iter(none).unwrap_err();
+    assert!(err.to_string().contains("no images"));
+}
+
+#[test]
+#[should_panic(expected = "no images")]
+fn empty_collect() {
+    let _: TileSet = std::iter::empty::<DynamicImage>().collect();
+}
@@ -0,0 +1,88 @@
+//! Test building mosaics of sources which are already RGB images
+
+mod utils;
+
+use image::{DynamicImage, RgbImage};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use tilr::{Descriptor, Mosaic, MosaicOptions, Rotation, Transform};
+use utils::{small_gradient, solid_tiles};
+
+/// Counts the allocations (on each thread) at least as large as `LARGE`
+struct CountLarge;
+
+const LARGE: usize = 64 * 64 * 3;
+
+thread_local! {
+    static LARGE_ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountLarge {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() >= LARGE {
+            LARGE_ALLOCS.with(|n| n.set(n.get() + 1));
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountLarge = CountLarge;
+
+fn large_allocs<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LARGE_ALLOCS.with(Cell::get);
+    let result = f();
+    (result, LARGE_ALLOCS.with(Cell::get) - before)
+}
+
+#[test]
+fn same_as_dynamic_image() {
+    let img = small_gradient(64, 48).to_rgb8();
+    let tiles = solid_tiles();
+    let rotate = Transform {
+        rotate: Rotation::Cw90,
+        flip_h: true,
+        ..Default::default()
+    };
+    for (scaling, options) in [
+        (1.0, MosaicOptions::default()),
+        (0.5, MosaicOptions::default()),
+        (
+            0.25,
+            MosaicOptions {
+                transform: rotate,
+                ..Default::default()
+            },
+        ),
+        (
+            0.25,
+            MosaicOptions {
+                descriptor: Descriptor::Quadrants,
+                ..Default::default()
+            },
+        ),
+    ] {
+        let rgb = Mosaic::from_rgb(img.clone(), &tiles, scaling, 4, options);
+        // an opaque RGBA image goes through the general path, converting
+        // it to RGB after it's scaled
+        let rgba = DynamicImage::ImageRgba8(DynamicImage::ImageRgb8(img.clone()).into_rgba8());
+        let general = Mosaic::with_options(rgba, &tiles, scaling, 4, options);
+        assert_eq!(rgb.source(), general.source(), "{} {:?}", scaling, options);
+        assert_eq!(rgb.to_image(), general.to_image());
+    }
+}
+
+#[test]
+fn no_copies() {
+    let img = RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8 * 4, 128]));
+    let tiles = solid_tiles();
+    // the source is kept as it is, rather than converted (i.e., copied)
+    let (mosaic, allocs) =
+        large_allocs(|| Mosaic::from_rgb(img.clone(), &tiles, 1.0, 4, MosaicOptions::default()));
+    assert_eq!(allocs, 1, "only the clone of the source");
+    assert_eq!(mosaic.source(), &img);
+}
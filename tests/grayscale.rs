@@ -0,0 +1,79 @@
+//! Test rendering grayscale mosaics
+
+use image::{ColorType, DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use tilr::Mosaic;
+
+mod utils;
+use utils::scratch_dir;
+
+/// Gray tiles, some of which are not a single solid shade
+fn gray_tiles() -> Vec<DynamicImage> {
+    (0..8u8)
+        .map(|i| {
+            let img = GrayImage::from_fn(4, 4, |x, y| Luma([i * 32 + (x + y) as u8 * 2]));
+            DynamicImage::ImageLuma8(img)
+        })
+        .collect()
+}
+
+fn gray_source() -> DynamicImage {
+    let img = GrayImage::from_fn(24, 16, |x, y| Luma([(x * 10 + y * 3) as u8]));
+    DynamicImage::ImageLuma8(img)
+}
+
+#[test]
+fn same_tiles_as_rgb() {
+    let gray = Mosaic::new(gray_source(), gray_tiles(), 1.0, 4);
+    let rgb_tiles: Vec<DynamicImage> = gray_tiles()
+        .iter()
+        .map(|t| DynamicImage::ImageRgb8(t.to_rgb8()))
+        .collect();
+    let rgb_source = DynamicImage::ImageRgb8(gray_source().to_rgb8());
+    let rgb = Mosaic::new(rgb_source, &rgb_tiles, 1.0, 4);
+
+    assert!(gray.is_grayscale());
+    assert!(rgb.is_grayscale());
+    let plan = gray.plan();
+    assert_eq!(plan, rgb.plan());
+
+    let rendered = plan.render(gray.tiles());
+    let rendered_gray = plan.render_gray(gray.tiles());
+    assert_eq!(rendered_gray.dimensions(), rendered.dimensions());
+    assert_eq!(rendered_gray.as_raw().len() * 3, rendered.as_raw().len());
+    for (g, px) in rendered_gray.pixels().zip(rendered.pixels()) {
+        assert_eq!(px, &Rgb([g.0[0]; 3]));
+    }
+}
+
+#[test]
+fn color_is_not_grayscale() {
+    let mut tiles = gray_tiles();
+    tiles.push(DynamicImage::ImageRgb8(RgbImage::from_pixel(
+        4,
+        4,
+        Rgb([255, 0, 0]),
+    )));
+    let mosaic = Mosaic::new(gray_source(), &tiles, 1.0, 4);
+    assert!(!mosaic.tiles().is_grayscale());
+    assert!(!mosaic.is_grayscale());
+
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])));
+    let mosaic = Mosaic::new(src, gray_tiles(), 1.0, 4);
+    assert!(mosaic.tiles().is_grayscale());
+    assert!(!mosaic.is_grayscale());
+}
+
+#[test]
+fn saved_as_luma8() {
+    let dir = scratch_dir("grayscale").unwrap();
+
+    let mosaic = Mosaic::new(gray_source(), gray_tiles(), 1.0, 4);
+    let img = mosaic.plan().render_gray(mosaic.tiles());
+    for (name, dpi) in [("plain.png", None), ("300dpi.png", Some(300.0))] {
+        let path = dir.join(name);
+        tilr::save_image(&img, &path, dpi).unwrap();
+        let saved = image::open(&path).unwrap();
+        assert_eq!(saved.color(), ColorType::L8);
+        assert_eq!(saved.to_luma8(), img);
+    }
+}
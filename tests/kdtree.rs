@@ -0,0 +1,80 @@
+//! Test that matching with a k-d tree over large tile sets is exact
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Metric, MosaicOptions, MosaicPlan, TileSet};
+
+mod utils;
+use utils::{brute_force, Lcg};
+
+fn tiles_of(colors: impl IntoIterator<Item = Rgb<u8>>) -> TileSet {
+    let imgs: Vec<DynamicImage> = colors
+        .into_iter()
+        .map(|c| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, c)))
+        .collect();
+    TileSet::from(&imgs)
+}
+
+/// Check that the plan for `img` matches every pixel to the best tile
+fn assert_exact(img: &RgbImage, tiles: &TileSet, metric: Metric) {
+    let options = MosaicOptions {
+        metric,
+        ..Default::default()
+    };
+    let plan = MosaicPlan::for_image(img, tiles, options);
+    for (px, &idx) in img.pixels().zip(plan.cells()) {
+        assert_eq!(
+            idx,
+            brute_force(tiles, px, metric).0,
+            "{:?} {:?}",
+            metric,
+            px
+        );
+    }
+}
+
+#[test]
+fn matches_brute_force() {
+    let mut rng = Lcg(1);
+    let mut tiles = tiles_of((0..500).map(|_| rng.color()));
+    for i in (0..tiles.len()).step_by(5) {
+        tiles.set_weight(i, 0.5 + rng.next_u8() as f32 / 64.0);
+    }
+    let img = RgbImage::from_fn(48, 48, |_, _| rng.color());
+
+    for metric in [
+        Metric::Rgb,
+        Metric::weighted_rgb([2.0, 1.0, 0.5]),
+        Metric::Oklab,
+        Metric::Lab,
+    ] {
+        assert_exact(&img, &tiles, metric);
+    }
+}
+
+#[test]
+fn after_lowering_weights() {
+    let mut rng = Lcg(2);
+    let mut tiles = tiles_of((0..200).map(|_| rng.color()));
+    tiles.set_weight(0, 8.0);
+    tiles.set_weight(0, 0.25);
+    let img = RgbImage::from_fn(32, 32, |_, _| rng.color());
+    assert_exact(&img, &tiles, Metric::Rgb);
+}
+
+#[test]
+fn after_filling_gaps() {
+    let mut rng = Lcg(3);
+    let mut tiles = tiles_of((0..100).map(|_| Rgb([rng.next_u8() / 2, 0, rng.next_u8() / 2])));
+    assert!(tiles.fill_gaps(60.0, Metric::Rgb) > 0);
+    let img = RgbImage::from_fn(32, 32, |_, _| rng.color());
+    assert_exact(&img, &tiles, Metric::Rgb);
+}
+
+#[test]
+fn ties() {
+    // many tiles of each of a few colors, so most lookups are ties
+    let colors = [[118, 128, 128], [128, 138, 128], [128, 128, 118]];
+    let tiles = tiles_of((0..40).flat_map(|_| colors.map(Rgb)));
+    let img = RgbImage::from_fn(16, 16, |x, y| Rgb([120 + x as u8, 128, 120 + y as u8]));
+    assert_exact(&img, &tiles, Metric::Rgb);
+}
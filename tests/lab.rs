@@ -0,0 +1,67 @@
+//! Test the CIELAB color space and distance metric
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Lab, Metric, Mosaic};
+use utils::solid;
+
+/// Check that a color is within rounding of the expected CIELAB values
+fn assert_close(lab: Lab, expected: [f32; 3]) {
+    let actual = [lab.l, lab.a, lab.b];
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() <= 0.01, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn srgb_primaries() {
+    assert_close(Lab::from(Rgb([255, 255, 255])), [100.0, 0.0, 0.0]);
+    assert_close(Lab::from(Rgb([0, 0, 0])), [0.0, 0.0, 0.0]);
+    assert_close(Lab::from(Rgb([255, 0, 0])), [53.24, 80.09, 67.2]);
+    assert_close(Lab::from(Rgb([0, 255, 0])), [87.73, -86.18, 83.18]);
+    assert_close(Lab::from(Rgb([0, 0, 255])), [32.3, 79.19, -107.86]);
+}
+
+#[test]
+fn distance() {
+    let (black, white) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+    assert!((Metric::Lab.distance(&black, &white) - 100.0).abs() < 1e-3);
+    assert_eq!(Metric::Lab.distance(&white, &white), 0.0);
+
+    let (p, q) = (Rgb([12, 200, 99]), Rgb([250, 3, 100]));
+    assert_eq!(
+        Metric::Lab.distance(&p, &q),
+        Lab::from(p).distance(&Lab::from(q))
+    );
+}
+
+#[test]
+fn cached_on_tile() {
+    let tiles = vec![solid(&(200, 30, 90), 2, 2)];
+    let mosaic = Mosaic::new(solid(&(0, 0, 0), 1, 1), &tiles, 1.0, 2);
+    let tile = mosaic.tiles().get(0).unwrap();
+    assert_eq!(tile.lab(), Lab::from(tile.avg()));
+
+    let px = Rgb([10, 20, 30]);
+    assert_eq!(
+        tile.score(&px, Metric::Lab),
+        Metric::Lab.distance(&px, tile.avg())
+    );
+}
+
+#[test]
+fn differs_from_rgb() {
+    // for a skin tone, a pinker tile is closer in RGB, while a lighter tile
+    // of the same hue is closer in CIELAB
+    let choose = |metric| {
+        let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([224, 172, 140])));
+        let tiles = vec![solid(&(228, 132, 168), 2, 2), solid(&(253, 211, 178), 2, 2)];
+        let mut mosaic = Mosaic::new(src, &tiles, 1.0, 2);
+        mosaic.options_mut().metric = metric;
+        let idx = mosaic.plan().tile_at(0, 0);
+        mosaic.tiles().get(idx).unwrap().avg().0
+    };
+    assert_eq!(choose(Metric::Rgb), [228, 132, 168]);
+    assert_eq!(choose(Metric::Lab), [253, 211, 178]);
+}
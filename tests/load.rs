@@ -0,0 +1,361 @@
+//! Test loading tile directories
+
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use tilr::{DecodeCache, LoadOptions, LoadWarning, LoadWarningReason};
+
+mod utils;
+use utils::scratch_dir;
+
+#[test]
+fn mixed_directory() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-mixed")?;
+    fs::create_dir_all(dir.join("subdir"))?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("blue.bmp"))?;
+    fs::write(dir.join("notes.txt"), "not an image")?;
+    fs::write(dir.join("corrupt.png"), "not a png either")?;
+
+    let report = tilr::load_tiles(&dir)?;
+    assert_eq!(report.tiles.len(), 2);
+
+    let mut warnings: Vec<(PathBuf, LoadWarningReason)> = report
+        .warnings
+        .into_iter()
+        .map(|LoadWarning { path, reason }| (path, reason))
+        .collect();
+    warnings.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[0].0, dir.join("corrupt.png"));
+    assert!(matches!(
+        warnings[0].1,
+        LoadWarningReason::UndecodableImage(_)
+    ));
+    assert_eq!(
+        warnings[1],
+        (dir.join("notes.txt"), LoadWarningReason::UnsupportedFormat)
+    );
+    assert_eq!(
+        warnings[2],
+        (dir.join("subdir"), LoadWarningReason::IsDirectory)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn missing_directory() {
+    assert!(tilr::load_tiles(&PathBuf::from("does/not/exist")).is_err());
+}
+
+#[test]
+fn multiple_directories() -> Result<(), Box<dyn Error>> {
+    let root = scratch_dir("load-multi")?;
+    let (first, second) = (root.join("first"), root.join("second"));
+    fs::create_dir_all(&first)?;
+    fs::create_dir_all(&second)?;
+
+    for (i, dir) in [&first, &first, &second, &second, &second]
+        .iter()
+        .enumerate()
+    {
+        let px = Rgb([i as u8 * 50, 0, 0]);
+        RgbImage::from_pixel(4, 4, px).save(dir.join(format!("{}.png", i)))?;
+    }
+    fs::write(second.join("notes.txt"), "not an image")?;
+
+    let options = LoadOptions::default();
+    let report = tilr::load_tiles_multi(&[first.clone(), second.clone()], &options)?;
+    assert_eq!(report.tiles.len(), 5);
+    assert_eq!(report.paths.len(), 5);
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.paths[..2].iter().all(|p| p.starts_with(&first)));
+    assert!(report.paths[2..].iter().all(|p| p.starts_with(&second)));
+
+    // the same directory (by another name) is only loaded once
+    let alias = first.join("..").join("first");
+    let report = tilr::load_tiles_multi(&[first.clone(), alias, first.clone()], &options)?;
+    assert_eq!(report.tiles.len(), 2);
+
+    assert!(tilr::load_tiles_multi(&[first, root.join("missing")], &options).is_err());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::symlink;
+
+    let root = scratch_dir("load-symlinks")?;
+    let (dir, archive) = (root.join("tiles"), root.join("archive"));
+    fs::create_dir_all(&dir)?;
+    fs::create_dir_all(&archive)?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(archive.join("blue.png"))?;
+    symlink(archive.join("blue.png"), dir.join("good.png"))?;
+    symlink(dir.join("red.png"), dir.join("same.png"))?;
+    symlink(archive.join("missing.png"), dir.join("broken.png"))?;
+    symlink(&dir, dir.join("loop"))?;
+
+    let reasons = |warnings: Vec<LoadWarning>| -> Vec<(String, LoadWarningReason)> {
+        warnings
+            .into_iter()
+            .map(|w| {
+                (
+                    w.path.file_name().unwrap().to_string_lossy().into(),
+                    w.reason,
+                )
+            })
+            .collect()
+    };
+
+    // symlinks are skipped by default
+    let report = tilr::load_tiles(&dir)?;
+    assert_eq!(report.tiles.len(), 1);
+    assert_eq!(
+        reasons(report.warnings),
+        [
+            ("broken.png".into(), LoadWarningReason::Symlink),
+            ("good.png".into(), LoadWarningReason::Symlink),
+            ("loop".into(), LoadWarningReason::Symlink),
+            ("same.png".into(), LoadWarningReason::Symlink),
+        ]
+    );
+
+    // when following them, files reached twice are only loaded once
+    let options = LoadOptions {
+        follow_symlinks: true,
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(report.tiles.len(), 2);
+    assert_eq!(report.paths, [dir.join("good.png"), dir.join("red.png")]);
+    let warnings = reasons(report.warnings);
+    assert_eq!(warnings.len(), 3);
+    assert_eq!(warnings[0].0, "broken.png");
+    assert!(matches!(warnings[0].1, LoadWarningReason::BrokenSymlink(_)));
+    assert_eq!(warnings[1], ("loop".into(), LoadWarningReason::IsDirectory));
+    assert_eq!(
+        warnings[2],
+        ("same.png".into(), LoadWarningReason::AlreadyLoaded)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn exact_duplicates() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-duplicates")?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    fs::copy(dir.join("red.png"), dir.join("red-copy.png"))?;
+
+    // copies are skipped by default, keeping the first by path
+    let report = tilr::load_tiles(&dir)?;
+    assert_eq!(report.tiles.len(), 1);
+    assert_eq!(report.paths, [dir.join("red-copy.png")]);
+    assert_eq!(
+        report.warnings,
+        [LoadWarning {
+            path: dir.join("red.png"),
+            reason: LoadWarningReason::Duplicate(dir.join("red-copy.png")),
+        }]
+    );
+
+    let options = LoadOptions {
+        keep_duplicates: true,
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(report.tiles.len(), 2);
+    assert!(report.warnings.is_empty());
+
+    // duplicates are also found across directories
+    let other = dir.join("other");
+    fs::create_dir_all(&other)?;
+    fs::copy(dir.join("red.png"), other.join("also-red.png"))?;
+    let report = tilr::load_tiles_multi(&[other.clone(), dir.clone()], &LoadOptions::default())?;
+    assert_eq!(report.paths, [other.join("also-red.png")]);
+
+    Ok(())
+}
+
+#[test]
+fn decode_cache() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-cached")?;
+    let dirs = [dir.clone()];
+    let options = LoadOptions::default();
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("blue.png"))?;
+    let mut cache = DecodeCache::new();
+    let first = tilr::load_tiles_cached(&dirs, &options, &mut cache)?;
+    assert_eq!(first.tiles.len(), 2);
+    assert_eq!((cache.decoded(), cache.len()), (2, 2));
+
+    // nothing changed
+    let again = tilr::load_tiles_cached(&dirs, &options, &mut cache)?;
+    assert_eq!(again.tiles, first.tiles);
+    assert_eq!(again.paths, first.paths);
+    assert_eq!((cache.decoded(), cache.len()), (0, 2));
+
+    // one tile changed and one renamed
+    RgbImage::from_pixel(4, 4, Rgb([0, 255, 0])).save(dir.join("red.png"))?;
+    fs::rename(dir.join("blue.png"), dir.join("azure.png"))?;
+    let changed = tilr::load_tiles_cached(&dirs, &options, &mut cache)?;
+    assert_eq!(changed.tiles, tilr::load_tiles(&dir)?.tiles);
+    assert_eq!((cache.decoded(), cache.len()), (1, 2));
+
+    fs::remove_file(dir.join("azure.png"))?;
+    tilr::load_tiles_cached(&dirs, &options, &mut cache)?;
+    assert_eq!((cache.decoded(), cache.len()), (0, 1));
+
+    Ok(())
+}
+
+#[test]
+fn min_dim() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-min-dim")?;
+
+    RgbImage::from_pixel(512, 384, Rgb([255, 0, 0])).save(dir.join("photo.png"))?;
+    RgbImage::from_pixel(32, 32, Rgb([0, 0, 255])).save(dir.join("icon.png"))?;
+
+    // everything is loaded by default
+    assert_eq!(tilr::load_tiles(&dir)?.tiles.len(), 2);
+
+    let options = LoadOptions {
+        min_dim: Some(64),
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(report.paths, [dir.join("photo.png")]);
+    assert_eq!(
+        report.warnings,
+        [LoadWarning {
+            path: dir.join("icon.png"),
+            reason: LoadWarningReason::TooSmall(32, 32),
+        }]
+    );
+
+    // the threshold applies to the smaller dimension
+    let options = LoadOptions {
+        min_dim: Some(400),
+        ..Default::default()
+    };
+    assert!(tilr::load_tiles_with(&dir, &options)?.tiles.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn recursive() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-recursive")?;
+    fs::create_dir_all(dir.join("beach/sunset"))?;
+    fs::create_dir_all(dir.join("forest"))?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("beach/blue.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([255, 128, 0])).save(dir.join("beach/sunset/orange.jpg"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 255, 0])).save(dir.join("forest/green.png"))?;
+
+    // subdirectories are skipped by default
+    let report = tilr::load_tiles(&dir)?;
+    assert_eq!(report.paths, [dir.join("red.png")]);
+    assert_eq!(report.warnings.len(), 2);
+
+    let options = LoadOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(
+        report.paths,
+        [
+            dir.join("beach/blue.png"),
+            dir.join("beach/sunset/orange.jpg"),
+            dir.join("forest/green.png"),
+            dir.join("red.png"),
+        ]
+    );
+    assert!(report.warnings.is_empty());
+
+    // a directory linking back to one being loaded is only searched once
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&dir, dir.join("forest/loop"))?;
+        let report = tilr::load_tiles_with(&dir, &options)?;
+        assert_eq!(report.tiles.len(), 4);
+        assert_eq!(report.warnings[0].reason, LoadWarningReason::Symlink);
+
+        let options = LoadOptions {
+            follow_symlinks: true,
+            ..options
+        };
+        let report = tilr::load_tiles_with(&dir, &options)?;
+        assert_eq!(report.tiles.len(), 4);
+        assert!(report.warnings.is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn include_exclude() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("load-include-exclude")?;
+    fs::create_dir_all(dir.join("beach"))?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 255, 0])).save(dir.join("green.jpg"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("beach/blue.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 0])).save(dir.join("beach/draft.png"))?;
+    fs::write(dir.join("notes.txt"), "not an image")?;
+
+    let pattern = |s| glob::Pattern::new(s).unwrap();
+    // patterns without a `/` match the name, in any subdirectory
+    let options = LoadOptions {
+        recursive: true,
+        include: vec![pattern("*.png")],
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(
+        report.paths,
+        [
+            dir.join("beach/blue.png"),
+            dir.join("beach/draft.png"),
+            dir.join("red.png")
+        ]
+    );
+    // files which aren't included are left out without warnings
+    assert!(report.warnings.is_empty());
+
+    let options = LoadOptions {
+        exclude: vec![pattern("draft*")],
+        ..options
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(
+        report.paths,
+        [dir.join("beach/blue.png"), dir.join("red.png")]
+    );
+
+    // patterns with a `/` match the path within the directory
+    let options = LoadOptions {
+        recursive: true,
+        include: vec![pattern("beach/*")],
+        ..Default::default()
+    };
+    let report = tilr::load_tiles_with(&dir, &options)?;
+    assert_eq!(
+        report.paths,
+        [dir.join("beach/blue.png"), dir.join("beach/draft.png")]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Test persisting the color -> tile map between runs
+
+mod utils;
+
+use tilr::{Descriptor, MapCache, Metric, Mosaic};
+use utils::{scratch_dir, small_gradient, solid_tiles};
+
+fn mosaic() -> Mosaic {
+    Mosaic::new(small_gradient(20, 16), solid_tiles(), 1.0, 4)
+}
+
+#[test]
+fn second_run_skips_searches() {
+    let path = scratch_dir("map_cache-second_run")
+        .unwrap()
+        .join("cache.json");
+    let mosaic = mosaic();
+    let metric = mosaic.options().metric;
+
+    // first run: every distinct color is searched for
+    let mut cache = MapCache::new(mosaic.tiles(), metric);
+    let first = mosaic.plan_cached(&mut cache);
+    assert!(cache.searches() > 0);
+    assert_eq!(cache.searches(), cache.len());
+    assert_eq!(first, mosaic.plan());
+    cache.save(&path).unwrap();
+
+    // second run: every color is a cache hit
+    let mut cache = MapCache::load(&path, mosaic.tiles(), metric).unwrap();
+    let second = mosaic.plan_cached(&mut cache);
+    assert_eq!(cache.searches(), 0);
+    assert_eq!(second, first);
+    assert_eq!(second.render(mosaic.tiles()), first.render(mosaic.tiles()));
+}
+
+#[test]
+fn mismatched_cache_is_rejected() {
+    let path = scratch_dir("map_cache-mismatched")
+        .unwrap()
+        .join("cache.json");
+    let mosaic = mosaic();
+    let mut cache = MapCache::new(mosaic.tiles(), Metric::Rgb);
+    mosaic.plan_cached(&mut cache);
+    cache.save(&path).unwrap();
+
+    // different metric
+    let weighted = Metric::weighted_rgb([2.0, 1.0, 1.0]);
+    assert!(MapCache::load(&path, mosaic.tiles(), weighted).is_err());
+
+    // different tiles
+    let other = Mosaic::new(small_gradient(20, 16), solid_tiles()[1..].to_vec(), 1.0, 4);
+    assert!(MapCache::load(&path, other.tiles(), Metric::Rgb).is_err());
+
+    // a stale cache is cleared rather than used
+    let mut other_mosaic = mosaic;
+    other_mosaic.options_mut().metric = weighted;
+    let plan = other_mosaic.plan_cached(&mut cache);
+    assert_eq!(plan, other_mosaic.plan());
+    assert!(cache.matches(other_mosaic.tiles(), weighted));
+}
+
+#[test]
+fn corrupt_cache_is_rejected() {
+    let path = scratch_dir("map_cache-corrupt").unwrap().join("cache.json");
+    let mosaic = mosaic();
+    let metric = mosaic.options().metric;
+    let mut cache = MapCache::new(mosaic.tiles(), metric);
+    mosaic.plan_cached(&mut cache);
+    cache.save(&path).unwrap();
+    let json = std::fs::read_to_string(&path).unwrap();
+
+    // truncated
+    std::fs::write(&path, &json[..json.len() / 2]).unwrap();
+    assert!(MapCache::load(&path, mosaic.tiles(), metric).is_err());
+
+    // an entry changed to point at a different tile
+    let (idx, _) = json.match_indices(":0").next().unwrap();
+    let mut tampered = json.clone();
+    tampered.replace_range(idx..idx + 2, ":1");
+    std::fs::write(&path, tampered).unwrap();
+    assert!(MapCache::load(&path, mosaic.tiles(), metric).is_err());
+
+    // not JSON at all
+    std::fs::write(&path, b"\x00\x01\x02").unwrap();
+    assert!(MapCache::load(&path, mosaic.tiles(), metric).is_err());
+}
+
+#[test]
+fn descriptor_recorded() {
+    let path = scratch_dir("map_cache-descriptor")
+        .unwrap()
+        .join("cache.json");
+    let mut mosaic = mosaic();
+    mosaic.options_mut().descriptor = Descriptor::Center;
+    let metric = mosaic.options().metric;
+
+    // a cache for the mean is replaced by one for the center
+    let mut cache = MapCache::new(mosaic.tiles(), metric);
+    let plan = mosaic.plan_cached(&mut cache);
+    assert_eq!(cache.descriptor(), Descriptor::Center);
+    assert_eq!(plan, mosaic.plan());
+    cache.save(&path).unwrap();
+
+    let cache = MapCache::load(&path, mosaic.tiles(), metric).unwrap();
+    assert_eq!(cache.descriptor(), Descriptor::Center);
+    assert!(!cache.is_empty());
+    assert!(cache.matches(mosaic.tiles(), metric));
+}
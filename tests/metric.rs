@@ -0,0 +1,51 @@
+//! Test the color distance metrics
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Metric, Mosaic};
+use utils::solid;
+
+/// Build a single-pixel mosaic choosing between a tile matching the red
+/// channel exactly (but off in blue) and one with the opposite errors
+fn choose(metric: Metric) -> [u8; 3] {
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([100, 100, 100])));
+    let tiles = vec![solid(&(100, 100, 160), 2, 2), solid(&(160, 100, 100), 2, 2)];
+    let mut mosaic = Mosaic::new(src, &tiles, 1.0, 2);
+    mosaic.options_mut().metric = metric;
+
+    let idx = mosaic.plan().tile_at(0, 0);
+    mosaic.tiles().get(idx).unwrap().avg().0
+}
+
+#[test]
+fn channel_weights() {
+    assert_eq!(
+        choose(Metric::weighted_rgb([10.0, 1.0, 1.0])),
+        [100, 100, 160]
+    );
+    assert_eq!(
+        choose(Metric::weighted_rgb([1.0, 1.0, 10.0])),
+        [160, 100, 100]
+    );
+}
+
+#[test]
+fn uniform_weights_match_rgb() {
+    let weighted = Metric::weighted_rgb([1.0, 1.0, 1.0]);
+    let (p, q) = (Rgb([12, 200, 99]), Rgb([250, 3, 100]));
+    assert_eq!(weighted.distance(&p, &q), Metric::Rgb.distance(&p, &q));
+}
+
+#[test]
+fn weights_apply_before_squaring() {
+    let weighted = Metric::weighted_rgb([2.0, 1.0, 0.5]);
+    let d = weighted.distance(&Rgb([10, 10, 10]), &Rgb([13, 14, 22]));
+    assert_eq!(d, (36.0f32 + 16.0 + 36.0).sqrt());
+}
+
+#[test]
+#[should_panic]
+fn rejects_non_positive_weights() {
+    Metric::weighted_rgb([1.0, 0.0, 1.0]);
+}
@@ -0,0 +1,110 @@
+//! Test combining several source images into one mosaic
+
+mod utils;
+
+use image::DynamicImage;
+use tilr::{Montage, Mosaic, MosaicOptions, ResizeFilter};
+use utils::solid;
+
+const RED: (u8, u8, u8) = (255, 0, 0);
+const BLUE: (u8, u8, u8) = (0, 0, 255);
+
+fn sources() -> Vec<DynamicImage> {
+    // a wide red image, and a square blue one
+    vec![solid(&RED, 4, 2), solid(&BLUE, 2, 2)]
+}
+
+#[test]
+fn layout() {
+    let montage = Montage {
+        columns: 2,
+        rows: 1,
+        gap: 1,
+    };
+    let canvas = montage.compose(&sources(), 1.0, ResizeFilter::Nearest);
+    // two 4x2 slots, and the gap between them
+    assert_eq!(canvas.dimensions(), (9, 2));
+    // the blue image is centered in the second slot
+    let row: Vec<[u8; 4]> = (0..9).map(|x| canvas.get_pixel(x, 1).0).collect();
+    let (red, blue, none) = ([255, 0, 0, 255], [0, 0, 255, 255], [0; 4]);
+    assert_eq!(
+        row,
+        [red, red, red, red, none, none, blue, blue, none],
+        "{:?}",
+        row
+    );
+}
+
+#[test]
+fn fits_slots() {
+    let montage = Montage {
+        columns: 2,
+        rows: 2,
+        gap: 2,
+    };
+    // the slots are as large as the largest image (once scaled), and
+    // smaller images are scaled up to fit them
+    let sources = vec![solid(&RED, 8, 4), solid(&BLUE, 2, 4), solid(&RED, 4, 4)];
+    assert_eq!(montage.slot_size(&[(8, 4), (2, 4)], 0.5), (4, 2));
+    let canvas = montage.compose(&sources, 0.5, ResizeFilter::Nearest);
+    assert_eq!(canvas.dimensions(), (2 * 4 + 2, 2 * 2 + 2));
+    assert_eq!(montage.slot_position(2, (4, 2)), (0, 4));
+
+    let opaque = |x0: u32, y0: u32| {
+        (0..4)
+            .map(|x| {
+                (y0..y0 + 2)
+                    .filter(|&y| canvas.get_pixel(x0 + x, y).0[3] > 0)
+                    .count()
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(opaque(0, 0), [2, 2, 2, 2]);
+    // 1x2 in a 4x2 slot
+    assert_eq!(opaque(6, 0), [0, 2, 0, 0]);
+    // 2x2 in a 4x2 slot
+    assert_eq!(opaque(0, 4), [0, 2, 2, 0]);
+    // the last slot is empty
+    assert_eq!(opaque(6, 4), [0; 4]);
+}
+
+#[test]
+fn gaps_are_background() {
+    let montage = Montage {
+        columns: 2,
+        rows: 1,
+        gap: 2,
+    };
+    let canvas = montage.compose(&sources(), 1.0, ResizeFilter::Nearest);
+    let options = MosaicOptions {
+        alpha_threshold: 1,
+        ..Default::default()
+    };
+    let tiles = vec![solid(&RED, 2, 2), solid(&BLUE, 2, 2)];
+    let mosaic = Mosaic::with_options(DynamicImage::ImageRgba8(canvas), &tiles, 1.0, 2, options);
+    let plan = mosaic.plan();
+    assert_eq!(plan.grid_size(), (10, 2));
+    for y in 0..2 {
+        let cells: String = (0..10)
+            .map(|x| match plan.is_skipped(x, y) {
+                true => '.',
+                false => match mosaic.tiles().get(plan.tile_at(x, y)).unwrap().avg().0 {
+                    [255, 0, 0] => 'r',
+                    _ => 'b',
+                },
+            })
+            .collect();
+        assert_eq!(cells, "rrrr...bb.");
+    }
+}
+
+#[test]
+#[should_panic]
+fn too_many_images() {
+    let montage = Montage {
+        columns: 1,
+        rows: 1,
+        gap: 0,
+    };
+    montage.compose(&sources(), 1.0, ResizeFilter::Nearest);
+}
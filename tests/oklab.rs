@@ -0,0 +1,114 @@
+//! Test the OKLab color space and distance metric
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Metric, Mosaic, Oklab};
+use utils::solid;
+
+/// Check that a color is within rounding of the expected OKLab values
+fn assert_close(lab: Oklab, expected: [f32; 3], tolerance: f32) {
+    let actual = [lab.l, lab.a, lab.b];
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+    }
+}
+
+/// Convert a color from CIE XYZ (D65) to linear sRGB
+fn xyz_to_linear_srgb([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ]
+}
+
+#[test]
+fn reference_values() {
+    // the table of XYZ colors in Björn Ottosson's post introducing OKLab
+    let table = [
+        ([0.950, 1.000, 1.089], [1.000, 0.000, 0.000]),
+        ([1.000, 0.000, 0.000], [0.450, 1.236, -0.019]),
+        ([0.000, 1.000, 0.000], [0.922, -0.671, 0.263]),
+        ([0.000, 0.000, 1.000], [0.153, -1.415, -0.449]),
+    ];
+    for (xyz, lab) in table {
+        assert_close(Oklab::from_linear_srgb(xyz_to_linear_srgb(xyz)), lab, 1e-3);
+    }
+}
+
+#[test]
+fn srgb_primaries() {
+    assert_close(Oklab::from(Rgb([255, 255, 255])), [1.0, 0.0, 0.0], 1e-4);
+    assert_close(Oklab::from(Rgb([0, 0, 0])), [0.0, 0.0, 0.0], 1e-4);
+    assert_close(
+        Oklab::from(Rgb([255, 0, 0])),
+        [0.627_96, 0.224_86, 0.125_85],
+        1e-4,
+    );
+    assert_close(
+        Oklab::from(Rgb([0, 255, 0])),
+        [0.866_44, -0.233_89, 0.179_5],
+        1e-4,
+    );
+    assert_close(
+        Oklab::from(Rgb([0, 0, 255])),
+        [0.452_01, -0.032_46, -0.311_53],
+        1e-4,
+    );
+}
+
+#[test]
+fn round_trip() {
+    for r in (0..=255).step_by(15) {
+        for g in (0..=255).step_by(15) {
+            for b in (0..=255).step_by(15) {
+                let px = Rgb([r as u8, g as u8, b as u8]);
+                assert_eq!(Oklab::from(px).to_rgb(), px);
+            }
+        }
+    }
+}
+
+#[test]
+fn distance() {
+    let (black, white) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+    assert!((Metric::Oklab.distance(&black, &white) - 1.0).abs() < 1e-4);
+    assert_eq!(Metric::Oklab.distance(&white, &white), 0.0);
+
+    let (p, q) = (Rgb([12, 200, 99]), Rgb([250, 3, 100]));
+    assert_eq!(
+        Metric::Oklab.distance(&p, &q),
+        Oklab::from(p).distance(&Oklab::from(q))
+    );
+}
+
+#[test]
+fn cached_on_tile() {
+    let tiles = vec![solid(&(200, 30, 90), 2, 2)];
+    let mosaic = Mosaic::new(solid(&(0, 0, 0), 1, 1), &tiles, 1.0, 2);
+    let tile = mosaic.tiles().get(0).unwrap();
+    assert_eq!(tile.oklab(), Oklab::from(tile.avg()));
+
+    let px = Rgb([10, 20, 30]);
+    assert_eq!(
+        tile.score(&px, Metric::Oklab),
+        Metric::Oklab.distance(&px, tile.avg())
+    );
+}
+
+#[test]
+fn differs_from_rgb() {
+    // a darker blue is closer in RGB, while a lighter, grayer blue is
+    // closer in OKLab
+    let choose = |metric| {
+        let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([0, 0, 160])));
+        let tiles = vec![solid(&(0, 0, 100), 2, 2), solid(&(40, 40, 200), 2, 2)];
+        let mut mosaic = Mosaic::new(src, &tiles, 1.0, 2);
+        mosaic.options_mut().metric = metric;
+        let idx = mosaic.plan().tile_at(0, 0);
+        mosaic.tiles().get(idx).unwrap().avg().0
+    };
+    assert_eq!(choose(Metric::Rgb), [0, 0, 100]);
+    assert_eq!(choose(Metric::Oklab), [40, 40, 200]);
+}
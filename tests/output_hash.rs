@@ -0,0 +1,88 @@
+//! Test hashing mosaics as they are saved
+
+use assert_cmd::Command;
+use image::{Rgb, RgbImage};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use tilr::MosaicPlan;
+
+mod utils;
+use utils::scratch_dir;
+
+fn sha256(path: &Path) -> Result<String, Box<dyn Error>> {
+    let hash = Sha256::digest(&fs::read(path)?);
+    Ok(hash.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[test]
+fn save_image_hashed() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("output-hash")?;
+
+    let img = RgbImage::from_fn(16, 8, |x, y| Rgb([x as u8 * 16, y as u8 * 32, 128]));
+    // streamed (with and without a resolution) and buffered formats
+    for (name, dpi) in [
+        ("a.png", None),
+        ("b.png", Some(300.0)),
+        ("c.jpg", None),
+        ("d.tiff", None),
+    ] {
+        let path = dir.join(name);
+        let hash = tilr::save_image_hashed(&img, &path, dpi)?;
+        assert_eq!(hash, sha256(&path)?, "{}", name);
+        assert_eq!(hash.len(), 64);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reproducible_builds() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("output-hash-cli")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(tile_dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(tile_dir.join("blue.png"))?;
+    let src = RgbImage::from_fn(6, 4, |x, _| Rgb([x as u8 * 40, 0, 255 - x as u8 * 40]));
+    src.save(dir.join("src.png"))?;
+
+    let build = |n: usize| -> Result<String, Box<dyn Error>> {
+        let output = dir.join(format!("out-{}.png", n));
+        let sidecar = dir.join(format!("plan-{}.json", n));
+        let report = dir.join(format!("report-{}.json", n));
+        let assert = Command::cargo_bin("tilr")?
+            .arg(dir.join("src.png"))
+            .arg("-t")
+            .arg(&tile_dir)
+            .args(["--tile-size", "4", "--yes", "-o"])
+            .arg(&output)
+            .arg("--sidecar")
+            .arg(&sidecar)
+            .arg("--report")
+            .arg(&report)
+            .assert()
+            .success();
+        let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+        let line = stderr
+            .lines()
+            .find(|l| l.starts_with("sha256("))
+            .expect("no hash printed")
+            .to_string();
+
+        // the hash is the same everywhere it is reported
+        let hash = sha256(&output)?;
+        assert_eq!(line, format!("sha256({}) = {}", output.display(), hash));
+        let plan = MosaicPlan::load(&sidecar)?;
+        assert_eq!(plan.output_sha256(), Some(hash.as_str()));
+        let report: Value = serde_json::from_str(&fs::read_to_string(&report)?)?;
+        assert_eq!(report["output_sha256"], hash.as_str());
+        Ok(hash)
+    };
+
+    assert_eq!(build(1)?, build(2)?);
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! Test the encoder options used when saving mosaics
+
+use assert_cmd::Command;
+use image::ImageFormat;
+use std::fs;
+use std::path::PathBuf;
+use tilr::{save_image_with, OutputOptions, PngCompression};
+
+mod utils;
+use utils::{scratch_dir, small_gradient, solid};
+
+/// A fresh directory to save images in.
+fn setup(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = scratch_dir(name)?;
+    Ok(dir)
+}
+
+#[test]
+fn jpeg_quality() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("jpeg_quality")?;
+    let img = small_gradient(64, 64).to_rgb8();
+    for quality in [10, 95] {
+        let options = OutputOptions {
+            jpeg_quality: quality,
+            ..Default::default()
+        };
+        save_image_with(&img, &dir.join(format!("{}.jpg", quality)), &options)?;
+    }
+    let low = fs::metadata(dir.join("10.jpg"))?.len();
+    let high = fs::metadata(dir.join("95.jpg"))?.len();
+    assert!(low < high, "{} >= {}", low, high);
+    Ok(())
+}
+
+#[test]
+fn png_compression() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("png_compression")?;
+    let img = small_gradient(64, 64).to_rgb8();
+    for (name, compression) in [
+        ("fast", PngCompression::Fast),
+        ("best", PngCompression::Best),
+    ] {
+        let options = OutputOptions {
+            png_compression: compression,
+            ..Default::default()
+        };
+        save_image_with(&img, &dir.join(format!("{}.png", name)), &options)?;
+        assert_eq!(
+            image::open(dir.join(format!("{}.png", name)))?.to_rgb8(),
+            img
+        );
+    }
+    let fast = fs::metadata(dir.join("fast.png"))?.len();
+    let best = fs::metadata(dir.join("best.png"))?.len();
+    assert!(best <= fast, "{} > {}", best, fast);
+    Ok(())
+}
+
+#[test]
+fn explicit_format() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("explicit_format")?;
+    let options = OutputOptions {
+        format: Some(ImageFormat::Jpeg),
+        ..Default::default()
+    };
+    save_image_with(
+        &solid(&(255, 0, 0), 8, 8).to_rgb8(),
+        &dir.join("out.png"),
+        &options,
+    )?;
+    let bytes = fs::read(dir.join("out.png"))?;
+    assert_eq!(image::guess_format(&bytes)?, ImageFormat::Jpeg);
+    Ok(())
+}
+
+#[test]
+fn cli_format() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = setup("cli_format")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    solid(&(255, 0, 0), 8, 8).save(tile_dir.join("red.png"))?;
+    small_gradient(6, 4).save(dir.join("input.png"))?;
+
+    let output = Command::cargo_bin("tilr")?
+        .arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args([
+            "--tile-size",
+            "4",
+            "--format",
+            "jpeg",
+            "--jpeg-quality",
+            "50",
+        ])
+        .args(["--yes", "-o"])
+        .arg(dir.join("mosaic.out"))
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    let bytes = fs::read(dir.join("mosaic.out"))?;
+    assert_eq!(image::guess_format(&bytes)?, ImageFormat::Jpeg);
+    Ok(())
+}
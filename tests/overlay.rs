@@ -0,0 +1,123 @@
+//! Test blending the source image over a mosaic
+
+use assert_cmd::Command;
+use image::{DynamicImage, Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use tilr::{Mosaic, MosaicOptions, MosaicPlan, Rect, TileSet};
+
+mod utils;
+use utils::scratch_dir;
+
+const TILE: Rgb<u8> = Rgb([90, 90, 90]);
+
+fn tiles() -> TileSet {
+    TileSet::from(&vec![DynamicImage::from(RgbImage::from_pixel(4, 4, TILE))])
+}
+
+fn options(overlay_strength: f32) -> MosaicOptions {
+    MosaicOptions {
+        overlay_strength,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn blends_source() {
+    let tiles = tiles();
+    let src = RgbImage::from_pixel(2, 2, Rgb([200, 40, 40]));
+
+    let rendered = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default()).render(&tiles);
+    assert!(rendered.pixels().all(|px| px == &TILE));
+
+    let rendered = MosaicPlan::for_image(&src, &tiles, options(0.5)).render(&tiles);
+    assert!(rendered.pixels().all(|px| px == &Rgb([145, 65, 65])));
+
+    let rendered = MosaicPlan::for_image(&src, &tiles, options(1.0)).render(&tiles);
+    assert!(rendered.pixels().all(|px| px == &Rgb([200, 40, 40])));
+}
+
+#[test]
+fn scales_up_smoothly() {
+    let tiles = tiles();
+    let src = RgbImage::from_fn(2, 1, |x, _| Rgb([255 * x as u8; 3]));
+    let rendered = MosaicPlan::for_image(&src, &tiles, options(1.0)).render(&tiles);
+    let row: Vec<u8> = (0..8).map(|x| rendered.get_pixel(x, 2).0[0]).collect();
+
+    // the edges keep the colors of their cells, with a gradient between
+    // the centers of the cells
+    assert_eq!(row[..2], [0, 0]);
+    assert_eq!(row[6..], [255, 255]);
+    assert!(row.windows(2).all(|w| w[0] <= w[1]));
+    assert!(row[2] > 0 && row[5] < 255);
+    assert!((row[3] as i32 + row[4] as i32 - 255).abs() <= 1);
+}
+
+#[test]
+fn every_render_blends() -> Result<(), Box<dyn Error>> {
+    let tiles = tiles();
+    let src = RgbImage::from_fn(3, 3, |x, y| Rgb([80 * x as u8, 100, 60 * y as u8]));
+    let plan = MosaicPlan::for_image(&src, &tiles, options(0.6));
+    let rendered = plan.render(&tiles);
+
+    let cell = image::imageops::crop_imm(&rendered, 4, 4, 4, 4).to_image();
+    assert_eq!(plan.render_cell(&tiles, 1, 1), cell);
+    let window = Rect::new(2, 3, 7, 6);
+    let part = image::imageops::crop_imm(&rendered, 2, 3, 7, 6).to_image();
+    assert_eq!(plan.render_window(&tiles, window), part);
+
+    let json = serde_json::to_string(&plan)?;
+    let loaded: MosaicPlan = serde_json::from_str(&json)?;
+    assert_eq!(loaded.render(&tiles), rendered);
+    Ok(())
+}
+
+#[test]
+fn through_mosaic() {
+    let src = DynamicImage::from(RgbImage::from_pixel(2, 2, Rgb([200, 40, 40])));
+    let tiles = [DynamicImage::from(RgbImage::from_pixel(4, 4, TILE))];
+    let mosaic = Mosaic::with_options(src, &tiles, 1.0, 4, options(1.0));
+    assert!(mosaic
+        .to_image()
+        .pixels()
+        .all(|px| px == &Rgb([200, 40, 40])));
+}
+
+#[test]
+fn cli() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("overlay")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    RgbImage::from_pixel(4, 4, TILE).save(tile_dir.join("gray.png"))?;
+    RgbImage::from_pixel(2, 2, Rgb([200, 40, 40])).save(dir.join("src.png"))?;
+
+    let output = dir.join("out.png");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args([
+            "--tile-size",
+            "4",
+            "--overlay-strength",
+            "0.5",
+            "--yes",
+            "-o",
+        ])
+        .arg(&output)
+        .assert()
+        .success();
+    let rendered = image::open(&output)?.to_rgb8();
+    assert!(rendered.pixels().all(|px| px == &Rgb([145, 65, 65])));
+
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--overlay-strength", "2", "--yes", "-o"])
+        .arg(&output)
+        .assert()
+        .failure();
+
+    Ok(())
+}
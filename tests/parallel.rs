@@ -0,0 +1,43 @@
+//! Test matching the colors of a source image to tiles in parallel
+#![cfg(feature = "rayon")]
+
+use image::{DynamicImage, RgbImage};
+use tilr::{Metric, MosaicOptions, MosaicPlan, TileSet};
+
+mod utils;
+use utils::{brute_force, Lcg};
+
+#[test]
+fn matches_serial() {
+    let mut rng = Lcg(1);
+    let imgs: Vec<DynamicImage> = (0..300)
+        .map(|_| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, rng.color())))
+        .collect();
+    let tiles = TileSet::from(&imgs);
+    let img = RgbImage::from_fn(64, 64, |_, _| rng.color());
+
+    for (metric, approx) in [
+        (Metric::Rgb, 1.0),
+        (Metric::Oklab, 1.0),
+        (Metric::CieDe2000, 1.0),
+        (Metric::Rgb, 1.5),
+    ] {
+        let options = MosaicOptions {
+            metric,
+            approx,
+            ..Default::default()
+        };
+        let plan = MosaicPlan::for_image(&img, &tiles, options);
+        for (px, &idx) in img.pixels().zip(plan.cells()) {
+            let expected = match approx > 1.0 {
+                true => tilr::ApproxSearch::new(&tiles, metric, approx).closest(px),
+                false => brute_force(&tiles, px, metric).0,
+            };
+            assert_eq!(idx, expected, "{:?} {:?}", metric, px);
+        }
+
+        // and the same plan every time
+        let again = MosaicPlan::for_image(&img, &tiles, options);
+        assert_eq!(again.cells(), plan.cells());
+    }
+}
@@ -0,0 +1,83 @@
+//! Test exporting mosaics as PDFs
+#![cfg(feature = "pdf")]
+
+mod utils;
+
+use lopdf::{Document, Object};
+use std::collections::HashSet;
+use tilr::Mosaic;
+use utils::{small_gradient, solid_tiles};
+
+fn pdf(mosaic: &Mosaic, width_mm: f32) -> Document {
+    let mut bytes = Vec::new();
+    let plan = mosaic.plan();
+    plan.write_pdf(mosaic.tiles(), width_mm, &mut bytes)
+        .unwrap();
+    Document::load_mem(&bytes).expect("PDF should parse")
+}
+
+fn as_f32(obj: &Object) -> f32 {
+    match obj {
+        Object::Integer(i) => *i as f32,
+        Object::Real(r) => *r,
+        _ => panic!("Not a number: {:?}", obj),
+    }
+}
+
+#[test]
+fn page_size() {
+    let mosaic = Mosaic::new(small_gradient(20, 10), solid_tiles(), 1.0, 4);
+    let doc = pdf(&mosaic, 800.0);
+
+    let pages = doc.get_pages();
+    assert_eq!(pages.len(), 1);
+    let page = doc.get_dictionary(pages[&1]).unwrap();
+    let media_box = page.get(b"MediaBox").unwrap().as_array().unwrap();
+    let size: Vec<f32> = media_box.iter().map(as_f32).collect();
+
+    // 800mm x 400mm, in points
+    let expected = [0.0, 0.0, 800.0 * 72.0 / 25.4, 400.0 * 72.0 / 25.4];
+    for (actual, expected) in size.iter().zip(expected) {
+        assert!((actual - expected).abs() < 0.01, "{:?}", size);
+    }
+    assert_eq!(mosaic.plan().pdf_page_size_mm(800.0), (800.0, 400.0));
+}
+
+#[test]
+fn embeds_each_tile_once() {
+    let mosaic = Mosaic::new(small_gradient(24, 16), solid_tiles(), 1.0, 4);
+    let doc = pdf(&mosaic, 100.0);
+
+    let images = doc
+        .objects
+        .values()
+        .filter_map(|obj| obj.as_stream().ok())
+        .filter(|stream| {
+            stream
+                .dict
+                .get(b"Subtype")
+                .and_then(|s| s.as_name())
+                .is_ok_and(|s| s == b"Image")
+        })
+        .count();
+
+    let used: HashSet<_> = mosaic.plan().cells().iter().copied().collect();
+    assert!(used.len() > 1);
+    assert_eq!(images, used.len());
+
+    // every cell is drawn
+    let pages = doc.get_pages();
+    let content = doc.get_page_content(pages[&1]).unwrap();
+    let draws = String::from_utf8(content).unwrap().matches(" Do").count();
+    assert_eq!(draws, mosaic.plan().cells().len());
+}
+
+#[test]
+fn invalid_width() {
+    let mosaic = Mosaic::new(small_gradient(4, 4), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    assert!(plan.write_pdf(mosaic.tiles(), 0.0, Vec::new()).is_err());
+    assert!(plan
+        .write_pdf(mosaic.tiles(), f32::NAN, Vec::new())
+        .is_err());
+}
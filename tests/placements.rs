@@ -0,0 +1,76 @@
+//! Test recording where tiles were loaded from and where they're placed
+
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use std::path::Path;
+use tilr::tiles::{Tile, TileSet};
+use tilr::Mosaic;
+
+mod utils;
+use utils::{scratch_dir, small_gradient, solid, solid_tiles};
+
+#[test]
+fn sources_from_report() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = scratch_dir("sources")?;
+    solid(&(255, 0, 0), 8, 8).save(dir.join("red.png"))?;
+    solid(&(0, 0, 255), 12, 12).save(dir.join("blue.png"))?;
+
+    let report = tilr::load_tiles(&dir)?;
+    let tiles = TileSet::from_report(&report);
+    for tile in tiles.iter() {
+        let name = match tile.avg().0 {
+            [255, 0, 0] => "red.png",
+            _ => "blue.png",
+        };
+        assert_eq!(tile.source(), Some(dir.join(name).as_path()));
+    }
+
+    // tiles built from bare images have no source
+    assert!(TileSet::from(&report.tiles)
+        .iter()
+        .all(|t| t.source().is_none()));
+    Ok(())
+}
+
+#[test]
+fn sources_survive_scaling() {
+    let tiles = TileSet::new(vec![
+        Tile::from(RgbImage::from_pixel(8, 8, Rgb([255, 0, 0]))).with_source("red.png"),
+        Tile::from(RgbImage::from_pixel(8, 8, Rgb([0, 0, 255]))).with_source("blue.png"),
+    ]);
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([250, 10, 10])));
+    let mosaic = Mosaic::new(img, tiles, 1.0, 4);
+    assert_eq!(mosaic.tiles().tile_side_len(), 4);
+
+    let placed: Vec<_> = mosaic
+        .placements()
+        .map(|(_, _, tile)| mosaic.tiles().get(tile).unwrap().source().unwrap())
+        .collect();
+    assert_eq!(placed, [Path::new("red.png"); 4]);
+}
+
+#[test]
+fn placements() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    let placements: Vec<_> = mosaic.placements().collect();
+    assert_eq!(placements.len(), 24);
+    assert_eq!(placements, plan.placements().collect::<Vec<_>>());
+    for (i, &(x, y, tile)) in placements.iter().enumerate() {
+        assert_eq!((x, y), (i as u32 % 6, i as u32 / 6));
+        assert_eq!(tile, plan.tile_at(x, y));
+    }
+}
+
+#[test]
+fn placements_skip_background() {
+    // the left column of the source is transparent
+    let src = RgbaImage::from_fn(3, 2, |x, _| match x {
+        0 => Rgba([0, 0, 0, 0]),
+        _ => Rgba([255, 0, 0, 255]),
+    });
+    let mut mosaic = Mosaic::new(DynamicImage::ImageRgba8(src), solid_tiles(), 1.0, 4);
+    mosaic.options_mut().alpha_threshold = 128;
+
+    let cells: Vec<_> = mosaic.placements().map(|(x, y, _)| (x, y)).collect();
+    assert_eq!(cells, [(1, 0), (2, 0), (1, 1), (2, 1)]);
+}
@@ -0,0 +1,158 @@
+//! Test building, serializing, and rendering mosaic plans
+
+mod utils;
+
+use std::error::Error;
+use std::path::PathBuf;
+use tilr::{Mosaic, MosaicPlan, Tile, TileSet};
+use utils::{scratch_dir, small_gradient, solid_tiles};
+
+fn mosaic() -> Mosaic {
+    Mosaic::new(small_gradient(20, 16), solid_tiles(), 1.0, 4)
+}
+
+#[test]
+fn round_trip() -> Result<(), Box<dyn Error>> {
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    assert_eq!(plan.grid_size(), (20, 16));
+    assert_eq!(plan.tile_size(), 4);
+    assert!(plan.matches(mosaic.tiles()));
+
+    let json = serde_json::to_string(&plan)?;
+    let parsed: MosaicPlan = serde_json::from_str(&json)?;
+    assert_eq!(plan, parsed);
+
+    let expected = plan.render(mosaic.tiles());
+    assert_eq!(expected, parsed.render(mosaic.tiles()));
+    assert_eq!(expected, mosaic.to_image());
+
+    Ok(())
+}
+
+#[test]
+fn rejects_out_of_range_tile() -> Result<(), Box<dyn Error>> {
+    let plan = mosaic().plan();
+    let mut json: serde_json::Value = serde_json::to_value(&plan)?;
+    json["cells"][0] = plan.tiles().len().into();
+
+    assert!(serde_json::from_value::<MosaicPlan>(json).is_err());
+    Ok(())
+}
+
+#[test]
+fn rejects_inconsistent_dimensions() -> Result<(), Box<dyn Error>> {
+    let plan = mosaic().plan();
+    let mut json: serde_json::Value = serde_json::to_value(&plan)?;
+    json["columns"] = 21.into();
+
+    assert!(serde_json::from_value::<MosaicPlan>(json).is_err());
+    Ok(())
+}
+
+#[test]
+fn records_distances() -> Result<(), Box<dyn Error>> {
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    assert_eq!(plan.distances().len(), plan.cells().len());
+    let metric = plan.options().metric;
+    for (x, y, px) in mosaic.source().enumerate_pixels() {
+        let tile = mosaic.tiles().get(plan.tile_at(x, y)).unwrap();
+        let i = (y * plan.grid_size().0 + x) as usize;
+        assert_eq!(plan.distances()[i], metric.distance(px, tile.avg()));
+    }
+
+    // plans saved before distances were recorded still load
+    let mut json: serde_json::Value = serde_json::to_value(&plan)?;
+    json.as_object_mut().unwrap().remove("distances");
+    let parsed: MosaicPlan = serde_json::from_value(json.clone())?;
+    assert!(parsed.distances().is_empty());
+    assert_eq!(parsed.cells(), plan.cells());
+
+    json["distances"] = vec![1.0; 3].into();
+    assert!(serde_json::from_value::<MosaicPlan>(json).is_err());
+    Ok(())
+}
+
+#[test]
+fn render_averages() {
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    let preview = plan.render_averages(mosaic.tiles());
+    assert_eq!(preview.dimensions(), plan.grid_size());
+    for (x, y, px) in preview.enumerate_pixels() {
+        let tile = mosaic.tiles().get(plan.tile_at(x, y)).unwrap();
+        assert_eq!(px, tile.avg());
+    }
+}
+
+#[test]
+fn save_preview() -> Result<(), Box<dyn Error>> {
+    let path = scratch_dir("plan")?.join("preview.png");
+
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    plan.save_preview(mosaic.tiles(), &path)?;
+
+    let preview = image::open(&path)?.to_rgb8();
+    assert_eq!(preview.dimensions(), plan.grid_size());
+    for (x, y, px) in preview.enumerate_pixels() {
+        let tile = mosaic.tiles().get(plan.tile_at(x, y)).unwrap();
+        assert_eq!(px, tile.avg());
+    }
+    Ok(())
+}
+
+#[test]
+fn render_by_hash() {
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    let expected = plan.render(mosaic.tiles());
+    assert_eq!(plan.render_by_hash(mosaic.tiles()).unwrap(), expected);
+
+    // extra tiles don't get in the way
+    let mut tiles = solid_tiles();
+    tiles.push(utils::solid(&(1, 2, 3), 25, 25));
+    let more = Mosaic::new(small_gradient(20, 16), tiles, 1.0, 4);
+    assert!(!plan.matches(more.tiles()));
+    assert!(plan.find_tiles(more.tiles()).iter().all(Option::is_some));
+    assert_eq!(plan.render_by_hash(more.tiles()).unwrap(), expected);
+
+    // but missing tiles are reported
+    let fewer = Mosaic::new(small_gradient(20, 16), &solid_tiles()[..1], 1.0, 4);
+    let used = plan.find_tiles(fewer.tiles());
+    assert_eq!(used.iter().filter(|t| t.is_some()).count(), 1);
+    assert!(plan.render_by_hash(fewer.tiles()).is_err());
+}
+
+#[test]
+fn tile_sources() -> Result<(), Box<dyn Error>> {
+    let imgs = solid_tiles();
+    let tiles = TileSet::new(
+        imgs.iter()
+            .enumerate()
+            .map(|(i, img)| match i {
+                0 => Tile::from(img.to_rgb8()),
+                _ => Tile::from(img.to_rgb8()).with_source(format!("tiles/tile-{}.png", i)),
+            })
+            .collect(),
+    );
+    let plan = Mosaic::new(small_gradient(20, 16), tiles, 1.0, 4).plan();
+
+    // each tile is traced back to the file it was loaded from, if any
+    let json = serde_json::to_value(&plan)?;
+    let refs = json["tiles"].as_array().unwrap();
+    assert_eq!(
+        refs.iter().filter(|r| r.get("source").is_some()).count(),
+        11
+    );
+    let parsed: MosaicPlan = serde_json::from_value(json)?;
+    let sources: Vec<_> = parsed.tiles().iter().map(|r| r.source.clone()).collect();
+    assert_eq!(sources.iter().filter(|s| s.is_none()).count(), 1);
+    assert!(sources.contains(&Some(PathBuf::from("tiles/tile-3.png"))));
+
+    // but the same tiles still match without their sources
+    assert!(plan.matches(mosaic().tiles()));
+    assert_eq!(plan.compare(&mosaic().plan())?.changed, 0);
+    Ok(())
+}
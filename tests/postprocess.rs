@@ -0,0 +1,124 @@
+//! Test post-processing rendered mosaics
+
+use image::{Rgb, RgbImage};
+use tilr::PostProcess;
+
+fn gray(values: &[u8], w: u32) -> RgbImage {
+    let h = values.len() as u32 / w;
+    RgbImage::from_fn(w, h, |x, y| {
+        let v = values[(y * w + x) as usize];
+        Rgb([v, v, v])
+    })
+}
+
+fn values(img: &RgbImage) -> Vec<u8> {
+    img.pixels().map(|px| px.0[0]).collect()
+}
+
+#[test]
+fn identity() {
+    let img = gray(&[0, 50, 100, 150, 200, 255], 3);
+    let post = PostProcess::default();
+    assert!(post.is_identity());
+    assert_eq!(post.apply(&img), img);
+}
+
+#[test]
+fn contrast() {
+    let img = gray(&[0, 100, 128, 200, 255, 60], 3);
+    let post = PostProcess {
+        contrast: 50.0,
+        ..Default::default()
+    };
+    // (v - 128) * 1.5 + 128, clamped
+    assert_eq!(values(&post.apply(&img)), [0, 86, 128, 236, 255, 26]);
+
+    let flat = PostProcess {
+        contrast: -100.0,
+        ..Default::default()
+    };
+    assert_eq!(values(&flat.apply(&img)), [128; 6]);
+}
+
+#[test]
+fn gamma() {
+    let img = gray(&[0, 64, 255], 3);
+    let post = PostProcess {
+        gamma: 2.0,
+        ..Default::default()
+    };
+    // 255 * (64 / 255)^(1 / 2) = 127.75
+    assert_eq!(values(&post.apply(&img)), [0, 128, 255]);
+}
+
+#[test]
+fn sharpen() {
+    #[rustfmt::skip]
+    let img = gray(&[
+        0, 0, 0,
+        0, 90, 0,
+        0, 0, 0,
+    ], 3);
+    let post = PostProcess {
+        sharpen: 1.0,
+        ..Default::default()
+    };
+    // the 3x3 average around every pixel includes the center once (90 / 9 =
+    // 10), so the center becomes 90 + (90 - 10) and the rest 0 - 10 -> 0
+    #[rustfmt::skip]
+    assert_eq!(values(&post.apply(&img)), [
+        0, 0, 0,
+        0, 170, 0,
+        0, 0, 0,
+    ]);
+
+    // uniform regions are unchanged
+    let flat = gray(&[77; 12], 4);
+    assert_eq!(post.apply(&flat), flat);
+
+    // sharpening happens before the contrast adjustment
+    let both = PostProcess {
+        sharpen: 1.0,
+        contrast: 50.0,
+        gamma: 1.0,
+    };
+    assert_eq!(values(&both.apply(&img))[4], 191);
+}
+
+#[test]
+fn bands_match_whole_image() {
+    let img = RgbImage::from_fn(7, 10, |x, y| {
+        Rgb([(x * 37 + y * 11) as u8, (x * y * 13) as u8, (y * 29) as u8])
+    });
+    let post = PostProcess {
+        sharpen: 0.8,
+        contrast: 20.0,
+        gamma: 1.2,
+    };
+    let whole = post.apply(&img);
+
+    let overlap = PostProcess::OVERLAP;
+    let mut banded = RgbImage::new(7, 10);
+    for start in (0..10).step_by(3) {
+        let end = (start + 3).min(10);
+        let top = start.min(overlap);
+        let bottom = (10 - end).min(overlap);
+        let band = image::imageops::crop_imm(&img, 0, start - top, 7, end - start + top + bottom)
+            .to_image();
+        let out = post.apply_band(&band, top, bottom);
+        assert_eq!(out.height(), end - start);
+        image::imageops::replace(&mut banded, &out, 0, start as i64);
+    }
+
+    assert_eq!(banded, whole);
+}
+
+#[test]
+#[should_panic]
+fn invalid_contrast() {
+    let post = PostProcess {
+        contrast: 150.0,
+        ..Default::default()
+    };
+    post.apply(&gray(&[0], 1));
+}
@@ -0,0 +1,293 @@
+//! Test preprocessing source images before matching them to tiles
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{saturate, Mosaic, MosaicOptions, Oklab, Posterize, Preprocess};
+use utils::solid;
+
+/// A horizontal gray gradient covering only the values `lo..=hi`
+fn narrow_gradient(lo: u8, hi: u8) -> RgbImage {
+    let w = (hi - lo) as u32 + 1;
+    RgbImage::from_fn(w, 4, |x, _| {
+        let v = lo + x as u8;
+        Rgb([v, v, v])
+    })
+}
+
+fn range(img: &RgbImage) -> (u8, u8) {
+    let values = img.as_raw();
+    (*values.iter().min().unwrap(), *values.iter().max().unwrap())
+}
+
+fn row_range(img: &RgbImage, y: u32) -> (u8, u8) {
+    let row = (0..img.width()).map(|x| img.get_pixel(x, y).0[0]);
+    (row.clone().min().unwrap(), row.max().unwrap())
+}
+
+#[test]
+fn identity() {
+    let img = narrow_gradient(100, 140);
+    assert!(Preprocess::None.is_identity());
+    assert_eq!(Preprocess::None.apply(&img), img);
+}
+
+#[test]
+fn stretch_spans_full_range() {
+    let img = narrow_gradient(100, 140);
+    for clip in [0.0, Preprocess::DEFAULT_CLIP, 10.0] {
+        let out = Preprocess::Stretch { clip }.apply(&img);
+        assert_eq!(range(&out), (0, 255), "clip {}", clip);
+        // the order of values is kept
+        let row: Vec<u8> = (0..out.width()).map(|x| out.get_pixel(x, 0).0[0]).collect();
+        assert!(row.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    // without clipping, the ends map exactly to black and white
+    let out = Preprocess::Stretch { clip: 0.0 }.apply(&img);
+    assert_eq!(out.get_pixel(0, 0).0, [0; 3]);
+    assert_eq!(out.get_pixel(20, 0).0, [128; 3]);
+    assert_eq!(out.get_pixel(40, 0).0, [255; 3]);
+}
+
+#[test]
+fn stretch_clips_outliers() {
+    // a single bright pixel would otherwise stop the stretch
+    let mut img = narrow_gradient(100, 140);
+    img.put_pixel(0, 0, Rgb([255, 255, 255]));
+    let out = Preprocess::Stretch { clip: 0.0 }.apply(&img);
+    assert!(row_range(&out, 1).1 < 100);
+
+    let out = Preprocess::Stretch { clip: 1.0 }.apply(&img);
+    assert_eq!(row_range(&out, 1), (0, 255));
+}
+
+#[test]
+fn stretch_keeps_color_balance() {
+    let img = RgbImage::from_fn(41, 1, |x, _| {
+        let v = 100 + x as u8;
+        Rgb([v, v + 10, v + 20])
+    });
+    let out = Preprocess::Stretch { clip: 0.0 }.apply(&img);
+    // every channel is stretched by the same amount, so blue stays
+    // brighter than green, which stays brighter than red
+    for px in out.pixels() {
+        let [r, g, b] = px.0;
+        assert!(r <= g && g <= b, "{:?}", px);
+    }
+    assert_eq!(range(&out), (0, 255));
+}
+
+#[test]
+#[should_panic]
+fn rejects_clipping_half() {
+    Preprocess::Stretch { clip: 50.0 }.apply(&narrow_gradient(0, 10));
+}
+
+#[test]
+fn equalize_spans_full_range() {
+    let img = narrow_gradient(60, 90);
+    for preprocess in [Preprocess::Equalize, Preprocess::EqualizeLuminance] {
+        let out = preprocess.apply(&img);
+        assert_eq!(range(&out), (0, 255), "{:?}", preprocess);
+    }
+    // for grays, equalizing the luminance equalizes each channel
+    assert_eq!(
+        Preprocess::Equalize.apply(&img),
+        Preprocess::EqualizeLuminance.apply(&img)
+    );
+}
+
+#[test]
+fn flat_images_unchanged() {
+    let img = RgbImage::from_pixel(4, 4, Rgb([90, 120, 30]));
+    for preprocess in [
+        Preprocess::Stretch { clip: 1.0 },
+        Preprocess::Equalize,
+        Preprocess::EqualizeLuminance,
+    ] {
+        assert_eq!(preprocess.apply(&img), img, "{:?}", preprocess);
+    }
+}
+
+#[test]
+fn mosaic_uses_more_tiles() {
+    let src = DynamicImage::ImageRgb8(narrow_gradient(110, 140));
+    let tiles = vec![
+        solid(&(0, 0, 0), 2, 2),
+        solid(&(128, 128, 128), 2, 2),
+        solid(&(255, 255, 255), 2, 2),
+    ];
+    let used = |preprocess| {
+        let options = MosaicOptions {
+            preprocess,
+            ..Default::default()
+        };
+        let mosaic = Mosaic::with_options(src.clone(), &tiles, 1.0, 2, options);
+        let mut cells = mosaic.plan().cells().to_vec();
+        cells.sort();
+        cells.dedup();
+        cells.len()
+    };
+    assert_eq!(used(Preprocess::None), 1);
+    assert_eq!(used(Preprocess::Stretch { clip: 1.0 }), 3);
+    assert_eq!(used(Preprocess::Equalize), 3);
+}
+
+/// Saturate a single color
+fn saturate_px(c: [u8; 3], factor: f32) -> [u8; 3] {
+    saturate(&RgbImage::from_pixel(1, 1, Rgb(c)), factor)
+        .get_pixel(0, 0)
+        .0
+}
+
+fn chroma(c: [u8; 3]) -> f32 {
+    let lab = Oklab::from(Rgb(c));
+    lab.a.hypot(lab.b)
+}
+
+#[test]
+fn saturation_scales_chroma() {
+    let c = [150, 110, 100];
+    assert_eq!(saturate_px(c, 1.0), c);
+    for factor in [0.5, 1.3, 2.0] {
+        let out = saturate_px(c, factor);
+        let ratio = chroma(out) / chroma(c);
+        assert!((ratio - factor).abs() < 0.05, "{} != {}", ratio, factor);
+        // lightness and hue are kept
+        let (a, b) = (Oklab::from(Rgb(c)), Oklab::from(Rgb(out)));
+        assert!((a.l - b.l).abs() < 0.01);
+        assert!((a.b.atan2(a.a) - b.b.atan2(b.a)).abs() < 0.05);
+    }
+}
+
+#[test]
+fn saturation_keeps_grays() {
+    for v in [0, 77, 255] {
+        assert_eq!(saturate_px([v; 3], 3.0), [v; 3]);
+    }
+    // and desaturating fully makes grays
+    let [r, g, b] = saturate_px([200, 40, 90], 0.0);
+    assert!(r.abs_diff(g) <= 1 && g.abs_diff(b) <= 1);
+}
+
+#[test]
+fn saturation_clamps_to_gamut() {
+    // pure primaries are already at the edge of the gamut
+    assert_eq!(saturate_px([255, 0, 0], 2.0), [255, 0, 0]);
+    assert_eq!(saturate_px([0, 0, 255], 1.5), [0, 0, 255]);
+    // other colors stop at the edge of the gamut, with the same hue
+    let c = [240, 60, 50];
+    let out = saturate_px(c, 3.0);
+    assert!(out.contains(&0) || out.contains(&255), "{:?}", out);
+    assert!(chroma(out) > chroma(c));
+    let (a, b) = (Oklab::from(Rgb(c)), Oklab::from(Rgb(out)));
+    assert!((a.b.atan2(a.a) - b.b.atan2(b.a)).abs() < 0.05);
+}
+
+#[test]
+#[should_panic]
+fn rejects_negative_saturation() {
+    saturate(&RgbImage::new(1, 1), -0.5);
+}
+
+#[test]
+fn mosaic_saturation() {
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([150, 110, 100])));
+    let tiles = vec![solid(&(150, 110, 100), 2, 2), solid(&(190, 90, 60), 2, 2)];
+    let choose = |saturation| {
+        let options = MosaicOptions {
+            saturation,
+            ..Default::default()
+        };
+        let mosaic = Mosaic::with_options(src.clone(), &tiles, 1.0, 2, options);
+        let idx = mosaic.plan().tile_at(0, 0);
+        mosaic.tiles().get(idx).unwrap().avg().0
+    };
+    assert_eq!(choose(1.0), [150, 110, 100]);
+    assert_eq!(choose(2.5), [190, 90, 60]);
+}
+
+/// A smooth, colorful image with many distinct colors
+fn colorful(w: u32, h: u32) -> RgbImage {
+    RgbImage::from_fn(w, h, |x, y| {
+        Rgb([
+            (x * 255 / (w - 1)) as u8,
+            (y * 255 / (h - 1)) as u8,
+            ((x + y) * 255 / (w + h - 2)) as u8,
+        ])
+    })
+}
+
+fn unique_colors(img: &RgbImage) -> usize {
+    let mut colors: Vec<[u8; 3]> = img.pixels().map(|px| px.0).collect();
+    colors.sort_unstable();
+    colors.dedup();
+    colors.len()
+}
+
+#[test]
+fn posterize_levels() {
+    let img = colorful(64, 48);
+    assert!(unique_colors(&img) > 1000);
+    for levels in [2, 3, 4, 8] {
+        let out = Posterize::Levels(levels).apply(&img);
+        assert!(unique_colors(&out) <= (levels as usize).pow(3));
+        // every channel takes one of the levels, spread evenly
+        let mut values = out.as_raw().clone();
+        values.sort_unstable();
+        values.dedup();
+        assert_eq!(values.len(), levels as usize);
+        assert_eq!((values[0], values[values.len() - 1]), (0, 255));
+    }
+    assert_eq!(
+        Posterize::Levels(2)
+            .apply(&RgbImage::from_pixel(1, 1, Rgb([20, 127, 128])))
+            .as_raw(),
+        &[0, 0, 255]
+    );
+}
+
+#[test]
+fn posterize_colors() {
+    let img = colorful(64, 48);
+    for k in [1, 2, 5, 8, 16] {
+        let out = Posterize::Colors(k).apply(&img);
+        assert!(unique_colors(&out) <= k as usize, "{} colors", k);
+    }
+    // with more colors than the image has, it's unchanged
+    let few = RgbImage::from_fn(4, 1, |x, _| Rgb([x as u8 * 60, 10, 200]));
+    assert_eq!(Posterize::Colors(16).apply(&few), few);
+    // and it's deterministic
+    assert_eq!(
+        Posterize::Colors(6).apply(&img),
+        Posterize::Colors(6).apply(&img)
+    );
+}
+
+#[test]
+#[should_panic]
+fn rejects_one_level() {
+    Posterize::Levels(1).apply(&RgbImage::new(1, 1));
+}
+
+#[test]
+fn mosaic_posterize() {
+    let src = DynamicImage::ImageRgb8(colorful(32, 24));
+    let tiles: Vec<DynamicImage> = (0..64)
+        .map(|i| solid(&((i % 4) * 80, (i / 4 % 4) * 80, (i / 16) * 80), 2, 2))
+        .collect();
+    let used = |posterize| {
+        let options = MosaicOptions {
+            posterize,
+            ..Default::default()
+        };
+        let mosaic = Mosaic::with_options(src.clone(), &tiles, 1.0, 2, options);
+        assert!(unique_colors(mosaic.source()) <= 4);
+        let mut cells = mosaic.plan().cells().to_vec();
+        cells.sort();
+        cells.dedup();
+        cells.len()
+    };
+    assert!(used(Posterize::Colors(4)) <= 4);
+}
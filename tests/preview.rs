@@ -0,0 +1,36 @@
+//! Test rendering flat-color previews of mosaics
+
+mod utils;
+
+use tilr::Mosaic;
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn cell_averages() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    let preview = mosaic.preview(100);
+    assert_eq!(preview.dimensions(), (6, 4));
+    for (x, y, px) in preview.enumerate_pixels() {
+        let tile = mosaic.tiles().get(plan.tile_at(x, y)).unwrap();
+        assert_eq!(px, tile.avg(), "cell ({}, {})", x, y);
+    }
+    assert_eq!(plan.preview(mosaic.tiles(), 100), preview);
+}
+
+#[test]
+fn downscaled() {
+    let mosaic = Mosaic::new(small_gradient(40, 10), solid_tiles(), 1.0, 4);
+    let preview = mosaic.preview(8);
+    assert_eq!(preview.dimensions(), (8, 2));
+
+    // every pixel is still the average of some tile
+    let avgs: Vec<_> = mosaic.tiles().iter().map(|t| *t.avg()).collect();
+    assert!(preview.pixels().all(|px| avgs.contains(px)));
+}
+
+#[test]
+#[should_panic(expected = "at least one pixel")]
+fn empty() {
+    Mosaic::new(small_gradient(4, 4), solid_tiles(), 1.0, 4).preview(0);
+}
@@ -0,0 +1,134 @@
+//! Test reporting the progress of rendering mosaics
+
+use assert_cmd::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tilr::{Mosaic, MosaicPlan, Phase, Progress};
+
+mod utils;
+use utils::{small_gradient, solid_tiles, tiles_and_gradient};
+
+/// Record every update passed to the callback of `mosaic`.
+fn record(mosaic: &mut Mosaic) -> Arc<Mutex<Vec<Progress>>> {
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&updates);
+    mosaic.on_progress(move |progress| recorded.lock().unwrap().push(progress));
+    updates
+}
+
+#[test]
+fn reports_each_cell() {
+    let mut mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let updates = record(&mut mosaic);
+    mosaic.to_image();
+
+    let updates = updates.lock().unwrap();
+    assert_eq!(updates.len(), 24);
+    for (i, progress) in updates.iter().enumerate() {
+        assert_eq!(progress.phase, Phase::Placement);
+        assert_eq!(progress.done, i as u64 + 1);
+        assert_eq!(progress.total, Some(24));
+    }
+    assert!(updates.last().unwrap().is_done());
+    assert!(!updates[0].is_done());
+}
+
+#[test]
+fn silent_by_default() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    assert_eq!(plan.render(mosaic.tiles()), mosaic.to_image());
+}
+
+#[test]
+fn plans_share_the_callback() {
+    let mut mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let updates = record(&mut mosaic);
+
+    let mut plan = mosaic.plan();
+    plan.render(mosaic.tiles());
+    assert_eq!(updates.lock().unwrap().len(), 24);
+
+    // single cells aren't reported; windows report the cells they cover
+    plan.render_cell(mosaic.tiles(), 0, 0);
+    assert_eq!(updates.lock().unwrap().len(), 24);
+    plan.render_window(mosaic.tiles(), tilr::Rect::new(0, 0, 8, 8));
+    assert_eq!(updates.lock().unwrap().len(), 28);
+
+    // a callback set on the plan replaces the mosaic's
+    let replaced = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&replaced);
+    plan.on_progress(move |_| *counter.lock().unwrap() += 1);
+    plan.render(mosaic.tiles());
+    assert_eq!(updates.lock().unwrap().len(), 28);
+    assert_eq!(*replaced.lock().unwrap(), 24);
+}
+
+#[test]
+fn callback_is_not_saved() {
+    let mut mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let silent = mosaic.plan();
+    record(&mut mosaic);
+    let plan = mosaic.plan();
+    assert_eq!(plan, silent);
+
+    let json = serde_json::to_string(&plan).unwrap();
+    let loaded: MosaicPlan = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, plan);
+}
+
+#[test]
+fn recursive() {
+    let mut mosaic = Mosaic::new(small_gradient(3, 2), solid_tiles(), 1.0, 4);
+    let updates = record(&mut mosaic);
+    mosaic.render_recursive(2, 2).unwrap();
+
+    let updates = updates.lock().unwrap();
+    let done: Vec<u64> = updates.iter().map(|p| p.done).collect();
+    assert_eq!(done, [1, 2, 3, 4, 5, 6]);
+    assert!(updates.iter().all(|p| p.total == Some(6)));
+}
+
+#[test]
+fn display() {
+    let mut progress = Progress {
+        phase: Phase::Placement,
+        done: 42,
+        total: Some(1000),
+        rate: None,
+        remaining: None,
+    };
+    assert_eq!(
+        progress.to_string(),
+        "Placing tile 0042/1000 (estimating time left)"
+    );
+    progress.rate = Some(1200.0);
+    progress.remaining = Some(Duration::from_secs(185));
+    assert_eq!(
+        progress.to_string(),
+        "Placing tile 0042/1000 (1.2k/s, 3m 05s left)"
+    );
+
+    progress.phase = Phase::Frames;
+    progress.total = None;
+    assert_eq!(progress.to_string(), "Processing frame 00042");
+}
+
+#[test]
+fn cli_prints_progress() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("progress", 3, 2)?;
+    let tile_dir = dir.join("tiles");
+    let input = dir.join("input.png");
+
+    let output = Command::cargo_bin("tilr")?
+        .arg(&input)
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--yes", "-o"])
+        .arg(dir.join("out.png"))
+        .output()?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Placing tile 0006/0006"));
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Test measuring the quality of a mosaic
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::Mosaic;
+use utils::solid;
+
+const COLORS: [(u8, u8, u8); 4] = [(200, 30, 30), (30, 200, 30), (30, 30, 200), (240, 240, 240)];
+
+/// A small source image made up of each of the test colors
+fn source() -> DynamicImage {
+    let img = RgbImage::from_fn(8, 8, |x, y| {
+        let c = COLORS[((x / 2 + y / 2) % 4) as usize];
+        Rgb([c.0, c.1, c.2])
+    });
+    DynamicImage::ImageRgb8(img)
+}
+
+#[test]
+fn ordering() {
+    let exact: Vec<_> = COLORS.iter().map(|c| solid(c, 4, 4)).collect();
+    let mosaic = Mosaic::new(source(), &exact, 1.0, 4);
+    let img = mosaic.plan().render(mosaic.tiles());
+    let good = mosaic.quality(&img);
+
+    assert!(good.psnr > 60.0, "{}", good);
+    assert!(good.ssim > 0.999, "{}", good);
+    assert_eq!(good.mean_distance, 0.0);
+
+    let gray = vec![solid(&(128, 128, 128), 4, 4)];
+    let mosaic = Mosaic::new(source(), &gray, 1.0, 4);
+    let img = mosaic.plan().render(mosaic.tiles());
+    let bad = mosaic.quality(&img);
+
+    assert!(bad.psnr < 15.0, "{}", bad);
+    assert!(bad.ssim < 0.1, "{}", bad);
+    assert!(bad.mean_distance > 100.0, "{}", bad);
+
+    assert!(good.psnr > bad.psnr);
+    assert!(good.ssim > bad.ssim);
+    assert!(good.mean_distance < bad.mean_distance);
+}
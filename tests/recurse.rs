@@ -0,0 +1,56 @@
+//! Test building recursive mosaics
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::Mosaic;
+use utils::{small_gradient, solid, solid_tiles};
+
+#[test]
+fn depth_one_is_a_normal_mosaic() {
+    let mosaic = Mosaic::new(small_gradient(12, 10), solid_tiles(), 1.0, 4);
+    let recursive = mosaic.render_recursive(1, 2).unwrap();
+    assert_eq!(
+        mosaic.recursive_output_size(1, 2),
+        Some(mosaic.output_size())
+    );
+    assert_eq!(recursive, mosaic.to_image());
+}
+
+#[test]
+fn depth_two() {
+    // a tile whose left column is red and right column is blue; its
+    // average is (roughly) purple, so it's picked for a purple source
+    let mut split = RgbImage::from_pixel(2, 2, Rgb([0, 0, 255]));
+    split.put_pixel(0, 0, Rgb([255, 0, 0]));
+    split.put_pixel(0, 1, Rgb([255, 0, 0]));
+    let tiles = vec![
+        solid(&(255, 0, 0), 2, 2),
+        solid(&(0, 0, 255), 2, 2),
+        DynamicImage::ImageRgb8(split),
+    ];
+
+    let src = solid(&(128, 0, 128), 1, 1);
+    let mosaic = Mosaic::new(src, &tiles, 1.0, 2);
+    assert_eq!(mosaic.recursive_output_size(2, 2), Some((4, 4)));
+
+    // each pixel of the split tile is replaced by a solid red or blue tile
+    let img = mosaic.render_recursive(2, 2).unwrap();
+    assert_eq!(img.dimensions(), (4, 4));
+    for (x, _, px) in img.enumerate_pixels() {
+        let expected = if x < 2 { [255, 0, 0] } else { [0, 0, 255] };
+        assert_eq!(px.0, expected, "wrong color at column {}", x);
+    }
+}
+
+#[test]
+fn invalid_arguments() {
+    let mosaic = Mosaic::new(small_gradient(12, 10), solid_tiles(), 1.0, 4);
+    assert!(mosaic.render_recursive(0, 4).is_err());
+    assert!(mosaic.render_recursive(2, 0).is_err());
+    assert_eq!(mosaic.recursive_output_size(0, 4), None);
+
+    // far too large to build
+    assert!(mosaic.render_recursive(5, 64).is_err());
+    assert_eq!(mosaic.recursive_output_size(10, 1024), None);
+}
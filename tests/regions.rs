@@ -0,0 +1,100 @@
+//! Test building regions of a mosaic from separate tile sets
+
+use image::{DynamicImage, GrayImage, Luma, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions, Rect, TileSet, Transform};
+
+const RED: Rgb<u8> = Rgb([200, 0, 0]);
+const BLUE: Rgb<u8> = Rgb([0, 0, 200]);
+
+fn solid(color: Rgb<u8>, side: u32) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(side, side, color))
+}
+
+/// Red tiles for region 0 and (larger) blue tiles for region 1
+fn tiles() -> TileSet {
+    TileSet::with_labels(
+        &[(0, vec![solid(RED, 4)]), (1, vec![solid(BLUE, 8)])],
+        TileSet::DEFAULT_BACKGROUND,
+    )
+}
+
+/// A mask which is label 0 on the left and 1 on the right
+fn half_mask(w: u32, h: u32) -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(w, h, |x, _| Luma([(x >= w / 2) as u8])))
+}
+
+/// The color of the tile placed in each cell of a mosaic
+fn cell_colors(mosaic: &Mosaic) -> Vec<Vec<Rgb<u8>>> {
+    let plan = mosaic.plan();
+    let (columns, rows) = plan.grid_size();
+    (0..rows)
+        .map(|y| {
+            (0..columns)
+                .map(|x| *mosaic.tiles().get(plan.tile_at(x, y)).unwrap().avg())
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn tiles_stay_in_their_region() {
+    let tiles = tiles();
+    assert_eq!(tiles.tile_side_len(), 4);
+
+    // the source is all red, so the blue tile is only used where it must be
+    let src = solid(RED, 16);
+    let mut mosaic = Mosaic::with_tile_set(src, tiles, 0.5, 4, MosaicOptions::default());
+    mosaic.set_label_mask(&half_mask(16, 16)).unwrap();
+
+    for row in cell_colors(&mosaic) {
+        assert_eq!(row, [RED, RED, RED, RED, BLUE, BLUE, BLUE, BLUE]);
+    }
+
+    // re-planning part of the mosaic respects the regions too
+    let mut output = mosaic.plan().render(mosaic.tiles());
+    let expected = output.clone();
+    mosaic.update_region(&mut output, Rect::new(2, 2, 4, 4));
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn mask_is_transformed() {
+    let options = MosaicOptions {
+        transform: Transform {
+            flip_h: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut mosaic = Mosaic::with_tile_set(solid(RED, 8), tiles(), 0.5, 4, options);
+    mosaic.set_label_mask(&half_mask(8, 8)).unwrap();
+
+    for row in cell_colors(&mosaic) {
+        assert_eq!(row, [BLUE, BLUE, RED, RED]);
+    }
+}
+
+#[test]
+fn majority_label() {
+    // the mask is larger than the grid; each cell covers 4x4 mask pixels,
+    // of which the first column of each cell (a quarter) is label 1
+    let mask = GrayImage::from_fn(8, 8, |x, _| Luma([(x % 4 == 0) as u8]));
+    let mut mosaic = Mosaic::with_tile_set(solid(BLUE, 2), tiles(), 1.0, 4, Default::default());
+    mosaic
+        .set_label_mask(&DynamicImage::ImageLuma8(mask))
+        .unwrap();
+    assert_eq!(mosaic.labels().unwrap().as_raw(), &[0, 0, 0, 0]);
+    assert_eq!(cell_colors(&mosaic), [[RED, RED], [RED, RED]]);
+}
+
+#[test]
+fn missing_label() {
+    let mask = GrayImage::from_fn(8, 8, |x, _| Luma([if x < 4 { 0 } else { 7 }]));
+    let mut mosaic = Mosaic::with_tile_set(solid(RED, 8), tiles(), 0.5, 4, Default::default());
+
+    let err = mosaic
+        .set_label_mask(&DynamicImage::ImageLuma8(mask))
+        .unwrap_err();
+    assert!(err.to_string().contains("7 (8 cells)"), "{}", err);
+    assert!(mosaic.labels().is_none());
+}
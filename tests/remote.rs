@@ -0,0 +1,116 @@
+//! Test downloading tiles from a list of URLs
+#![cfg(feature = "remote")]
+
+mod utils;
+
+use image::ImageFormat;
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tilr::{LoadOptions, LoadWarningReason, RemoteOptions};
+use utils::{scratch_dir, solid};
+
+/// Serve a red PNG at `/red.png` and a blue one at `/blue` (with no
+/// extension) on a local port, and nothing else.
+///
+/// # Returns
+/// The URL of the server, and the number of requests it has answered.
+fn serve() -> (String, Arc<AtomicUsize>) {
+    let png = |color| {
+        let mut bytes = Cursor::new(Vec::new());
+        solid(color, 4, 4)
+            .write_to(&mut bytes, ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    };
+    let (red, blue) = (png(&(255, 0, 0)), png(&(0, 0, 255)));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(AtomicUsize::new(0));
+    let count = Arc::clone(&requests);
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut line = String::new();
+            let mut reader = BufReader::new(&stream);
+            reader.read_line(&mut line).unwrap();
+            // skip the headers
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+            count.fetch_add(1, Ordering::SeqCst);
+            let body = match line.split(' ').nth(1) {
+                Some("/red.png") => Some(&red),
+                Some("/blue") => Some(&blue),
+                _ => None,
+            };
+            let _ = match body {
+                Some(body) => write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .and_then(|_| stream.write_all(body)),
+                None => write!(
+                    stream,
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                ),
+            };
+        }
+    });
+    (url, requests)
+}
+
+#[test]
+fn url_list() {
+    let list = "# tiles\nhttps://example.com/a.png\n\n  http://example.com/b?size=4  \n";
+    assert_eq!(
+        tilr::parse_url_list(list),
+        ["https://example.com/a.png", "http://example.com/b?size=4"]
+    );
+}
+
+#[test]
+fn download() -> Result<(), Box<dyn std::error::Error>> {
+    let cache = scratch_dir("remote-cache")?;
+    let (url, requests) = serve();
+    let urls = vec![
+        format!("{}/red.png", url),
+        format!("{}/missing.png", url),
+        format!("{}/blue", url),
+    ];
+    let remote = RemoteOptions::new(&cache);
+
+    let report = tilr::load_tile_urls(&urls, &LoadOptions::default(), &remote)?;
+    assert_eq!(report.tiles.len(), 2);
+    // tiles are given in order, by their URLs
+    assert_eq!(
+        report.paths,
+        [PathBuf::from(&urls[0]), PathBuf::from(&urls[2])]
+    );
+    assert_eq!(report.warnings.len(), 1);
+    assert_eq!(report.warnings[0].path, PathBuf::from(&urls[1]));
+    assert!(
+        matches!(&report.warnings[0].reason, LoadWarningReason::DownloadFailed(e) if e.contains("404")),
+        "{:?}",
+        report.warnings
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    // the images are cached with the extension of their format
+    let mut cached: Vec<_> = fs::read_dir(&cache)?
+        .map(|e| e.map(|e| e.path().extension().map(|e| e.to_owned())))
+        .collect::<Result<_, _>>()?;
+    cached.sort();
+    assert_eq!(cached, [Some("png".into()), Some("png".into())]);
+
+    // only the missing image is requested again
+    let again = tilr::load_tile_urls(&urls, &LoadOptions::default(), &remote)?;
+    assert_eq!(again.tiles, report.tiles);
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+    Ok(())
+}
@@ -0,0 +1,46 @@
+//! Test rendering a mosaic without consuming it
+
+use image::{Rgb, RgbImage};
+use tilr::Mosaic;
+
+mod utils;
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn render_twice() {
+    let mut mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let rendered = mosaic.render();
+    assert_eq!(rendered, mosaic.render());
+
+    // the same mosaic renders with new options, without being rebuilt
+    mosaic.options_mut().overlay_strength = 0.5;
+    let overlaid = mosaic.render();
+    assert_ne!(overlaid, rendered);
+
+    let rebuilt = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    assert_eq!(rendered, rebuilt.to_image());
+}
+
+#[test]
+fn render_into() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let (w, h) = mosaic.output_size();
+
+    // every pixel of the buffer is overwritten
+    let mut output = RgbImage::from_pixel(w, h, Rgb([1, 2, 3]));
+    mosaic.render_into(&mut output);
+    assert_eq!(output, mosaic.render());
+
+    let plan = mosaic.plan();
+    let mut reused = RgbImage::from_pixel(w, h, Rgb([1, 2, 3]));
+    plan.render_into(mosaic.tiles(), &mut reused);
+    assert_eq!(reused, output);
+}
+
+#[test]
+#[should_panic(expected = "does not match the size of the mosaic")]
+fn render_into_wrong_size() {
+    let mosaic = Mosaic::new(small_gradient(6, 4), solid_tiles(), 1.0, 4);
+    let mut output = RgbImage::new(10, 10);
+    mosaic.render_into(&mut output);
+}
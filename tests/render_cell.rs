@@ -0,0 +1,44 @@
+//! Test rendering single cells of a mosaic
+
+mod utils;
+
+use image::GenericImageView;
+use tilr::{Mosaic, MosaicOptions};
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn matches_to_image() {
+    let mosaic = Mosaic::new(small_gradient(2, 2), solid_tiles(), 1.0, 4);
+    let cells: Vec<_> = (0..2)
+        .flat_map(|y| (0..2).map(move |x| (x, y)))
+        .map(|(x, y)| ((x, y), mosaic.render_cell(x, y)))
+        .collect();
+
+    let full = mosaic.to_image();
+    assert_eq!(full.dimensions(), (8, 8));
+    for ((x, y), cell) in cells {
+        assert_eq!(cell.dimensions(), (4, 4));
+        assert_eq!(cell, full.view(x * 4, y * 4, 4, 4).to_image());
+    }
+}
+
+#[test]
+fn background_cell() {
+    let mut src = small_gradient(2, 2).to_rgba8();
+    src.get_pixel_mut(1, 0).0[3] = 0;
+    let options = MosaicOptions {
+        alpha_threshold: 128,
+        matte: [10, 20, 30],
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(src.into(), solid_tiles(), 1.0, 4, options);
+
+    let cell = mosaic.render_cell(1, 0);
+    assert!(cell.pixels().all(|px| px.0 == [10, 20, 30]));
+}
+
+#[test]
+#[should_panic(expected = "lies outside of the 2x2 grid")]
+fn outside() {
+    Mosaic::new(small_gradient(2, 2), solid_tiles(), 1.0, 4).render_cell(2, 0);
+}
@@ -0,0 +1,61 @@
+//! Test rendering a mosaic with a per-cell hook
+
+mod utils;
+
+use image::RgbImage;
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tilr::Mosaic;
+use utils::{small_gradient, solid_tiles};
+
+fn mosaic() -> Mosaic {
+    Mosaic::new(small_gradient(20, 16), solid_tiles(), 1.0, 4)
+}
+
+#[test]
+fn pass_through() {
+    let mosaic = mosaic();
+    let calls = AtomicUsize::new(0);
+    let img = mosaic.render_with(|_, px| {
+        calls.fetch_add(1, Ordering::Relaxed);
+        Cow::Borrowed(px)
+    });
+
+    assert_eq!(img, mosaic.plan().render(mosaic.tiles()));
+    assert_eq!(calls.into_inner(), 20 * 16);
+}
+
+#[test]
+fn invert() {
+    let mosaic = mosaic();
+    let mut expected = mosaic.plan().render(mosaic.tiles());
+    image::imageops::invert(&mut expected);
+
+    let img = mosaic.render_with(|_, px| {
+        let mut px = px.clone();
+        image::imageops::invert(&mut px);
+        Cow::Owned(px)
+    });
+    assert_eq!(img, expected);
+}
+
+#[test]
+fn cells() {
+    let mosaic = mosaic();
+    let plan = mosaic.plan();
+    let source = mosaic.source().clone();
+
+    // Fill each cell with its target color
+    let img = mosaic.render_with(|cell, px| {
+        assert_eq!(cell.tile, plan.tile_at(cell.x, cell.y));
+        assert_eq!(cell.target, *source.get_pixel(cell.x, cell.y));
+        Cow::Owned(RgbImage::from_pixel(px.width(), px.height(), cell.target))
+    });
+    assert_eq!(img.get_pixel(13, 9), source.get_pixel(3, 2));
+}
+
+#[test]
+#[should_panic(expected = "Hook returned a 2x2 image for a 4px tile")]
+fn wrong_size() {
+    mosaic().render_with(|cell, _| Cow::Owned(RgbImage::from_pixel(2, 2, cell.target)));
+}
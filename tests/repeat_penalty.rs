@@ -0,0 +1,97 @@
+//! Test rotating between similar tiles with a repeat penalty
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{MosaicOptions, MosaicPlan, TileSet};
+
+const GRAY: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// An exact match for `GRAY`, and eight tiles slightly further from it
+fn tiles() -> TileSet {
+    let imgs: Vec<DynamicImage> = (0..9u8)
+        .map(|i| {
+            let px = Rgb([128 + i, 128, 128 - i]);
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, px))
+        })
+        .collect();
+    TileSet::from(&imgs)
+}
+
+fn options(repeat_penalty: f32, repeat_radius: u32) -> MosaicOptions {
+    MosaicOptions {
+        repeat_penalty,
+        repeat_radius,
+        ..Default::default()
+    }
+}
+
+fn usage(plan: &MosaicPlan, tiles: &TileSet) -> Vec<usize> {
+    let mut counts = vec![0; tiles.len()];
+    for &i in plan.cells() {
+        counts[i] += 1;
+    }
+    counts
+}
+
+/// The index of the exact match for `GRAY`
+fn exact(tiles: &TileSet) -> usize {
+    tiles.iter().position(|t| t.avg() == &GRAY).unwrap()
+}
+
+#[test]
+fn no_repeats_among_neighbors() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(20, 10, GRAY);
+
+    // without a penalty, every cell gets the exact match
+    let plan = MosaicPlan::for_image(&img, &tiles, MosaicOptions::default());
+    assert_eq!(usage(&plan, &tiles)[exact(&tiles)], 200);
+
+    let plan = MosaicPlan::for_image(&img, &tiles, options(20.0, 1));
+    for y in 0..10 {
+        for x in 0..20 {
+            let idx = plan.tile_at(x, y);
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0)] {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if (0..20).contains(&nx) && (0..10).contains(&ny) {
+                    assert_ne!(idx, plan.tile_at(nx as u32, ny as u32), "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    // the exact match is still used as often as its neighbors allow
+    let counts = usage(&plan, &tiles);
+    assert_eq!(counts.iter().max(), Some(&counts[exact(&tiles)]));
+    assert!(counts[exact(&tiles)] >= 40, "{:?}", counts);
+}
+
+#[test]
+fn only_counts_nearby_uses() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(40, 1, GRAY);
+
+    // a small penalty only moves a cell off the exact match once it has
+    // been used in several cells around it
+    let plan = MosaicPlan::for_image(&img, &tiles, options(1.5, 2));
+    let counts = usage(&plan, &tiles);
+    assert!(counts[exact(&tiles)] >= 20, "{:?}", counts);
+    assert!(counts[exact(&tiles)] < 40, "{:?}", counts);
+}
+
+#[test]
+fn zero_radius_has_no_effect() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(8, 8, GRAY);
+    let plan = MosaicPlan::for_image(&img, &tiles, options(20.0, 0));
+    assert_eq!(usage(&plan, &tiles)[exact(&tiles)], 64);
+}
+
+#[test]
+fn deterministic() {
+    let tiles = tiles();
+    let img = RgbImage::from_fn(16, 16, |x, y| {
+        Rgb([120 + (x % 4) as u8, 128, 120 + y as u8])
+    });
+    let plan = || MosaicPlan::for_image(&img, &tiles, options(5.0, 2));
+    assert_eq!(plan().cells(), plan().cells());
+}
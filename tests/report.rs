@@ -0,0 +1,64 @@
+//! Test reporting skipped inputs and statistics of a run
+
+use image::{DynamicImage, Rgb, RgbImage};
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+use tilr::{LoadOptions, Mosaic, RunReport};
+
+mod utils;
+use utils::scratch_dir;
+
+#[test]
+fn skipped_inputs() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("report")?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    fs::copy(dir.join("red.png"), dir.join("red-copy.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("blue.png"))?;
+    RgbImage::from_pixel(2, 2, Rgb([0, 255, 0])).save(dir.join("tiny.png"))?;
+    fs::write(dir.join("notes.txt"), "not an image")?;
+    fs::write(dir.join("corrupt.png"), "not a png either")?;
+
+    let options = LoadOptions {
+        min_dim: Some(3),
+        ..Default::default()
+    };
+    let load = tilr::load_tiles_with(&dir, &options)?;
+    let mut report = RunReport::default();
+    report.add_load(&load);
+    assert_eq!(report.loaded, 2);
+
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([250, 0, 0])));
+    let mosaic = Mosaic::new(src, load.tiles, 1.0, 4);
+    report.stats = Some(mosaic.plan().stats(10));
+    report.notes.push("a note".to_string());
+
+    let path = dir.join("report.json");
+    report.save(&path)?;
+    let json: Value = serde_json::from_str(&fs::read_to_string(&path)?)?;
+    let reason = |name: &str| {
+        let skipped = json["skipped"].as_array().unwrap();
+        let entry = skipped
+            .iter()
+            .find(|w| w["path"] == dir.join(name).to_str().unwrap())
+            .unwrap_or_else(|| panic!("{} is not in the report", name));
+        (entry["reason"].clone(), entry["detail"].clone())
+    };
+    assert_eq!(json["skipped"].as_array().unwrap().len(), 4);
+    assert_eq!(reason("notes.txt").0, "unsupported_format");
+    assert_eq!(reason("corrupt.png").0, "undecodable_image");
+    assert_eq!(
+        reason("tiny.png"),
+        ("too_small".into(), serde_json::json!([2, 2]))
+    );
+    let (code, detail) = reason("red.png");
+    assert_eq!(code, "duplicate");
+    assert_eq!(detail, dir.join("red-copy.png").to_str().unwrap());
+    assert_eq!(json["stats"]["cells"], 16);
+
+    // the report can be read back
+    assert_eq!(RunReport::load(&path)?, report);
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! Test validating the scaling factor applied to the source image
+
+mod utils;
+
+use image::{DynamicImage, RgbImage};
+use tilr::{Mosaic, MosaicOptions, Rotation, ScaleError, Transform};
+use utils::solid_tiles;
+
+#[test]
+fn small_scale() {
+    // e.g., a panorama
+    let img = DynamicImage::ImageRgb8(RgbImage::new(20_000, 400));
+    let options = MosaicOptions::default();
+    assert_eq!(options.validate((20_000, 400), 0.01), Ok((200, 4)));
+
+    let mosaic = Mosaic::with_options(img, solid_tiles(), 0.01, 4, options);
+    assert_eq!(mosaic.source().dimensions(), (200, 4));
+    assert_eq!(mosaic.output_size(), (800, 16));
+}
+
+#[test]
+fn invalid() {
+    let options = MosaicOptions::default();
+    for scale in [f32::NAN, 0.0, -1.0, f32::INFINITY] {
+        let err = options.validate((100, 100), scale).unwrap_err();
+        assert!(matches!(err, ScaleError::Invalid(_)), "{}", scale);
+    }
+    let err = options.validate((100, 100), f32::NAN).unwrap_err();
+    assert!(err.to_string().contains("NaN"));
+}
+
+#[test]
+fn collapsed() {
+    let options = MosaicOptions::default();
+    let err = options.validate((20_000, 400), 0.002).unwrap_err();
+    assert_eq!(
+        err,
+        ScaleError::Collapsed {
+            src: (20_000, 400),
+            scale: 0.002,
+            scaled: (40, 0),
+        }
+    );
+    assert!(err.to_string().contains("40x0"), "{}", err);
+}
+
+#[test]
+fn transformed() {
+    // the source is rotated before it's scaled
+    let options = MosaicOptions {
+        transform: Transform {
+            rotate: Rotation::Cw90,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert_eq!(options.validate((400, 100), 0.5), Ok((50, 200)));
+}
+
+#[test]
+fn too_many_cells() {
+    let err = MosaicOptions::default()
+        .validate((100_000, 100_000), 1.0)
+        .unwrap_err();
+    assert!(matches!(err, ScaleError::TooManyCells { .. }));
+}
+
+#[test]
+#[should_panic(expected = "no pixels")]
+fn collapsed_mosaic() {
+    let img = DynamicImage::ImageRgb8(RgbImage::new(100, 10));
+    Mosaic::new(img, solid_tiles(), 0.05, 4);
+}
+
+#[test]
+fn estimated_output_size() {
+    for (dims, scale, tile_size) in [
+        ((20_000, 400), 0.01, 4),
+        ((37, 23), 0.7, 9),
+        ((64, 48), 1.0, 8),
+    ] {
+        let img = DynamicImage::ImageRgb8(RgbImage::new(dims.0, dims.1));
+        let mosaic = Mosaic::new(img, solid_tiles(), scale, tile_size);
+        assert_eq!(
+            tilr::estimate_output_size(dims, scale, tile_size as u32),
+            mosaic.output_size()
+        );
+    }
+
+    // larger cells are estimated from the grid of cells
+    let options = MosaicOptions {
+        cell_size: 4,
+        ..Default::default()
+    };
+    let img = DynamicImage::ImageRgb8(RgbImage::new(37, 23));
+    let grid = options.validate((37, 23), 0.7).unwrap();
+    let mosaic = Mosaic::with_options(img, solid_tiles(), 0.7, 9, options);
+    assert_eq!(
+        tilr::estimate_output_size(grid, 1.0, 9),
+        mosaic.output_size()
+    );
+
+    // the estimate saturates rather than overflowing
+    assert_eq!(
+        tilr::estimate_output_size((100_000, 10), 1.0, 100_000),
+        (u32::MAX, 1_000_000)
+    );
+}
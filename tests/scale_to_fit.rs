@@ -0,0 +1,61 @@
+//! Test choosing the scaling factor for a mosaic to fit within a size
+
+mod utils;
+
+use image::{DynamicImage, RgbImage};
+use tilr::{grid_size, Mosaic};
+use utils::solid;
+
+/// Source dimensions, tile size, and the size to fit within
+type Case = ((u32, u32), u32, (u32, u32));
+
+const CASES: [Case; 14] = [
+    ((1000, 1000), 32, (16384, 16384)),
+    ((4032, 3024), 32, (16384, 16384)),
+    ((3024, 4032), 32, (16384, 16384)),
+    ((1920, 1080), 8, (3840, 2160)),
+    ((1919, 1081), 7, (3841, 2161)),
+    ((333, 777), 13, (1000, 1000)),
+    ((7, 3), 8, (1000, 1000)),
+    ((1, 1), 16, (100, 100)),
+    ((6000, 4000), 64, (10000, 200000)),
+    ((997, 991), 9, (8191, 8191)),
+    ((12345, 678), 3, (4096, 4096)),
+    ((100, 100), 10, (95, 1000)),
+    ((640, 480), 17, (1023, 767)),
+    ((20000, 15000), 255, (65535, 65535)),
+];
+
+#[test]
+fn within_bound() {
+    for (src, tile, (max_x, max_y)) in CASES {
+        let scale = Mosaic::scale_to_fit(src, tile, (max_x, max_y));
+        let (x, y) = grid_size(src, scale);
+        let (out_x, out_y) = (x * tile, y * tile);
+        let case = format!("{:?} with {}px tiles in {}x{}", src, tile, max_x, max_y);
+        assert!(out_x <= max_x && out_y <= max_y, "{} is too big", case);
+        // less than one tile short of the bound on the limiting side
+        assert!(
+            max_x - out_x < tile || max_y - out_y < tile,
+            "{} is {}x{}",
+            case,
+            out_x,
+            out_y
+        );
+    }
+}
+
+#[test]
+fn too_small() {
+    assert_eq!(Mosaic::scale_to_fit((100, 100), 32, (31, 1000)), 0.0);
+    assert_eq!(Mosaic::scale_to_fit((100, 100), 32, (1000, 0)), 0.0);
+}
+
+#[test]
+fn mosaic_size() {
+    let img = DynamicImage::ImageRgb8(RgbImage::new(97, 61));
+    let tiles = vec![solid(&(10, 20, 30), 4, 4)];
+    let scale = Mosaic::scale_to_fit((97, 61), 4, (301, 301));
+    let mosaic = Mosaic::new(img, &tiles, scale, 4);
+    assert_eq!(mosaic.output_size(), (300, 188));
+}
@@ -0,0 +1,52 @@
+//! Test slicing an image into tiles (e.g., to build a mosaic of itself)
+
+mod utils;
+
+use image::{GenericImageView, Rgb};
+use tilr::TileSet;
+use utils::small_gradient;
+
+/// Compute the (truncated) average color of a region of an image
+fn block_avg(img: &image::RgbImage, x0: u32, y0: u32, w: u32, h: u32) -> Rgb<u8> {
+    let mut tot = [0u32; 3];
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            for (t, c) in tot.iter_mut().zip(img.get_pixel(x, y).0) {
+                *t += c as u32;
+            }
+        }
+    }
+    Rgb(tot.map(|t| (t / (w * h)) as u8))
+}
+
+#[test]
+fn slice_grid() {
+    let src = small_gradient(100, 100);
+    let slices = tilr::slice_image(&src, 10, 10);
+    assert_eq!(slices.len(), 100);
+    assert!(slices.iter().all(|s| s.dimensions() == (10, 10)));
+
+    // each tile's average matches the average of the corresponding block
+    let rgb = src.to_rgb8();
+    let mut expected: Vec<[u8; 3]> = (0..100)
+        .map(|i| block_avg(&rgb, (i % 10) * 10, (i / 10) * 10, 10, 10).0)
+        .collect();
+    let tiles = TileSet::from(&slices);
+    let mut actual: Vec<[u8; 3]> = tiles.iter().map(|t| t.avg().0).collect();
+
+    expected.sort();
+    actual.sort();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn uneven_slices() {
+    let src = small_gradient(10, 7);
+    let slices = tilr::slice_image(&src, 3, 2);
+    assert_eq!(slices.len(), 6);
+
+    let widths: Vec<u32> = slices[..3].iter().map(|s| s.width()).collect();
+    let heights: Vec<u32> = slices.iter().step_by(3).map(|s| s.height()).collect();
+    assert_eq!(widths, [3, 3, 4]);
+    assert_eq!(heights, [3, 4]);
+}
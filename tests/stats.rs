@@ -0,0 +1,110 @@
+//! Test summarizing how a saved mosaic plan uses its tiles
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use std::error::Error;
+use tilr::{Mosaic, MosaicPlan, PlanStats};
+use utils::{scratch_dir, solid};
+
+/// A 4x4 source: a red block in the top left, green elsewhere, and a
+/// single blue cell in the bottom right
+fn source() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(4, 4, |x, y| match (x, y) {
+        (0..=1, 0..=1) => Rgb([250, 0, 0]),
+        (3, 3) => Rgb([0, 0, 240]),
+        _ => Rgb([0, 250, 0]),
+    }))
+}
+
+fn tiles() -> Vec<DynamicImage> {
+    vec![
+        solid(&(255, 0, 0), 2, 2),
+        solid(&(0, 255, 0), 2, 2),
+        solid(&(0, 0, 255), 2, 2),
+        solid(&(255, 255, 255), 2, 2),
+    ]
+}
+
+/// Build the mosaic and save its plan, as with --sidecar
+fn saved_plan(name: &str) -> Result<(Mosaic, MosaicPlan), Box<dyn Error>> {
+    let mosaic = Mosaic::new(source(), tiles(), 1.0, 2);
+    let path = scratch_dir(name)?.join("plan.json");
+    mosaic.plan().save(&path)?;
+    let plan = MosaicPlan::load(&path)?;
+    Ok((mosaic, plan))
+}
+
+/// The index of the tile with the given average color
+fn index_of(mosaic: &Mosaic, color: [u8; 3]) -> usize {
+    (mosaic.tiles().iter())
+        .position(|t| t.avg().0 == color)
+        .unwrap()
+}
+
+#[test]
+fn usage_counts() -> Result<(), Box<dyn Error>> {
+    let (mosaic, plan) = saved_plan("stats-usage")?;
+    let stats = plan.stats(10);
+
+    assert_eq!((stats.cells, stats.background, stats.tiles), (16, 0, 4));
+    assert_eq!(stats.unused, 1);
+    assert_eq!(stats.top.iter().map(|u| u.count).sum::<usize>(), 16);
+    let top: Vec<(usize, usize)> = stats.top.iter().map(|u| (u.index, u.count)).collect();
+    assert_eq!(
+        top,
+        [
+            (index_of(&mosaic, [0, 255, 0]), 11),
+            (index_of(&mosaic, [255, 0, 0]), 4),
+            (index_of(&mosaic, [0, 0, 255]), 1),
+        ]
+    );
+    assert_eq!(stats.top[0].hash, plan.tiles()[stats.top[0].index].hash);
+
+    // only the requested number of tiles are listed
+    assert_eq!(plan.stats(1).top.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn clustering() -> Result<(), Box<dyn Error>> {
+    let (mosaic, plan) = saved_plan("stats-clustering")?;
+    let clustering = plan.stats(3).clustering.unwrap();
+    assert_eq!(clustering.tile, index_of(&mosaic, [0, 255, 0]));
+    // the green cells have 34 neighbors, 28 of which are green
+    assert!((clustering.same_neighbors - 28.0 / 34.0).abs() < 1e-9);
+    assert!((clustering.expected - 10.0 / 15.0).abs() < 1e-9);
+    Ok(())
+}
+
+#[test]
+fn distances() -> Result<(), Box<dyn Error>> {
+    let (mosaic, plan) = saved_plan("stats-distances")?;
+    let d = plan.stats(3).distances.unwrap();
+    // red and green cells are 5 away from their tiles, and blue 15
+    assert_eq!((d.min, d.p50, d.p90, d.max), (5.0, 5.0, 5.0, 15.0));
+    assert!((d.mean - 90.0 / 16.0).abs() < 1e-4);
+    let quality = mosaic.quality(&plan.render(mosaic.tiles()));
+    assert!((d.mean as f64 - quality.mean_distance).abs() < 1e-4);
+
+    // plans saved without distances report none
+    let mut json = serde_json::to_value(&plan)?;
+    json.as_object_mut().unwrap().remove("distances");
+    let plan: MosaicPlan = serde_json::from_value(json)?;
+    assert_eq!(plan.stats(3).distances, None);
+    Ok(())
+}
+
+#[test]
+fn json_and_display() -> Result<(), Box<dyn Error>> {
+    let (_, plan) = saved_plan("stats-display")?;
+    let stats = plan.stats(2);
+    let parsed: PlanStats = serde_json::from_str(&serde_json::to_string(&stats)?)?;
+    assert_eq!(parsed, stats);
+
+    let text = stats.to_string();
+    assert!(text.starts_with("16 cells, 4 tiles, 1 never used\n"));
+    assert!(text.contains("(68.8%)"));
+    assert!(text.ends_with("max 15.00"));
+    Ok(())
+}
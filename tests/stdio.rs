@@ -0,0 +1,51 @@
+//! Test reading the source image from standard input and writing the
+//! mosaic to standard output
+
+use assert_cmd::Command;
+use image::ImageFormat;
+use std::fs;
+
+mod utils;
+use utils::tiles_and_gradient;
+
+#[test]
+fn pipe() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("stdio_pipe", 6, 4)?;
+    let output = Command::cargo_bin("tilr")?
+        .arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(dir.join("tiles"))
+        .args(["--tile-size", "4", "--yes", "-o"])
+        .arg(dir.join("expected.png"))
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = Command::cargo_bin("tilr")?
+        .arg("-")
+        .arg("-t")
+        .arg(dir.join("tiles"))
+        .args(["--tile-size", "4", "--yes", "--format", "png", "-o", "-"])
+        .write_stdin(fs::read(dir.join("input.png"))?)
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(image::guess_format(&output.stdout)?, ImageFormat::Png);
+    let mosaic = image::load_from_memory(&output.stdout)?;
+    assert_eq!(mosaic, image::open(dir.join("expected.png"))?);
+    Ok(())
+}
+
+#[test]
+fn stdout_needs_format() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient("stdio_format", 6, 4)?;
+    let output = Command::cargo_bin("tilr")?
+        .arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(dir.join("tiles"))
+        .args(["--tile-size", "4", "--yes", "-o", "-"])
+        .output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("Use --format"), "{}", stderr);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Test summarizing the colors of a tile set
+
+mod utils;
+
+use image::Rgb;
+use tilr::{HueBucket, TileSet, TileSetSummary};
+use utils::solid_tiles;
+
+fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+    for (a, e) in actual.iter().zip(expected) {
+        assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+    }
+}
+
+#[test]
+fn solid_colors() {
+    let summary = TileSet::from(&solid_tiles()).summary();
+    assert_eq!(summary.count, 12);
+    assert_eq!(summary.side_len, 25);
+    assert_eq!(summary.min, [0, 0, 0]);
+    assert_eq!(summary.max, [255, 255, 255]);
+    assert_close(summary.mean, [128.33333, 133.91667, 115.75]);
+    assert_close(summary.std_dev, [86.44105, 84.44866, 89.93528]);
+
+    let hues: Vec<usize> = summary.hues.values().copied().collect();
+    assert_eq!(
+        summary.hues.keys().copied().collect::<Vec<_>>(),
+        HueBucket::ALL
+    );
+    assert_eq!(hues, [2, 2, 2, 1, 2, 1, 2]);
+    assert_eq!(hues.iter().sum::<usize>(), summary.count);
+}
+
+#[test]
+fn hue_buckets() {
+    assert_eq!(HueBucket::of(&Rgb([128, 128, 128])), HueBucket::Neutral);
+    assert_eq!(HueBucket::of(&Rgb([208, 35, 35])), HueBucket::Red);
+    assert_eq!(HueBucket::of(&Rgb([209, 108, 36])), HueBucket::Red);
+    assert_eq!(HueBucket::of(&Rgb([209, 207, 36])), HueBucket::Yellow);
+    assert_eq!(HueBucket::of(&Rgb([42, 209, 36])), HueBucket::Green);
+    assert_eq!(HueBucket::of(&Rgb([36, 167, 209])), HueBucket::Cyan);
+    assert_eq!(HueBucket::of(&Rgb([52, 36, 209])), HueBucket::Blue);
+    assert_eq!(HueBucket::of(&Rgb([209, 136, 192])), HueBucket::Magenta);
+}
+
+#[test]
+fn json_round_trip() {
+    let summary = TileSet::from(&solid_tiles()).summary();
+    let json = serde_json::to_string(&summary).unwrap();
+    assert!(json.contains(r#""neutral":2"#));
+    let parsed: TileSetSummary = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, summary);
+}
+
+#[test]
+fn display() {
+    let text = TileSet::from(&solid_tiles()).summary().to_string();
+    assert!(text.starts_with("12 tiles, 25x25px\n"));
+    assert!(text.contains("  red       0  255   128.33    86.44\n"));
+    assert!(text.ends_with("Hues: neutral 2 red 2 yellow 2 green 1 cyan 2 blue 1 magenta 2"));
+}
@@ -0,0 +1,71 @@
+//! Test reading the tiles to use from a list
+
+use assert_cmd::Command;
+use image::{Rgb, RgbImage};
+use serde_json::Value;
+use std::error::Error;
+use std::fs;
+
+mod utils;
+use utils::scratch_dir;
+
+#[test]
+fn list_from_stdin() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("tile-list")?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(dir.join("blue.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 255, 0])).save(dir.join("green.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([250, 0, 0])).save(dir.join("src.png"))?;
+
+    // only the listed tiles are loaded, not everything in the directory
+    let list = format!(
+        "{}\n{}\n",
+        dir.join("red.png").display(),
+        dir.join("blue.png").display()
+    );
+    let output = dir.join("out.png");
+    let report = dir.join("report.json");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .args(["--tile-list", "-", "--tile-size", "4", "--yes", "-o"])
+        .arg(&output)
+        .arg("--report")
+        .arg(&report)
+        .write_stdin(list)
+        .assert()
+        .success();
+    assert_eq!(image::open(&output)?.to_rgb8().dimensions(), (16, 16));
+
+    let report: Value = serde_json::from_str(&fs::read_to_string(&report)?)?;
+    assert_eq!(report["loaded"], 2);
+    assert_eq!(report["stats"]["tiles"], 2);
+
+    // NUL-separated paths work the same way
+    let list = format!(
+        "{}\0{}\0",
+        dir.join("red.png").display(),
+        dir.join("green.png").display()
+    );
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .args(["--tile-list", "-", "-0", "--tile-size", "4", "--yes", "-o"])
+        .arg(&output)
+        .write_stdin(list)
+        .assert()
+        .success();
+
+    Ok(())
+}
+
+#[test]
+fn source_and_list_from_stdin() {
+    let assert = Command::cargo_bin("tilr")
+        .unwrap()
+        .args(["-", "--tile-list", "-", "--yes"])
+        .write_stdin("tiles/a.png\n")
+        .assert()
+        .failure();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert!(stderr.contains("standard input"), "{}", stderr);
+}
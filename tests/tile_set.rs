@@ -0,0 +1,67 @@
+//! Test building tile sets from tiles and reusing them across mosaics
+
+use image::{Rgb, RgbImage};
+use tilr::tiles::{Tile, TileSet};
+use tilr::Mosaic;
+
+mod utils;
+use utils::{small_gradient, solid_tiles};
+
+fn solid(c: [u8; 3], side: u32) -> Tile {
+    Tile::from(RgbImage::from_pixel(side, side, Rgb(c)))
+}
+
+#[test]
+fn from_tiles() {
+    let red = solid([255, 0, 0], 4);
+    let blue = solid([0, 0, 255], 4);
+    let a = TileSet::new(vec![red.clone(), blue.clone()]);
+    let b = TileSet::new(vec![blue, red]);
+    assert_eq!(a.len(), 2);
+    assert_eq!(a.tile_side_len(), 4);
+
+    // the order of the tiles doesn't matter
+    let hashes = |set: &TileSet| set.iter().map(Tile::content_hash).collect::<Vec<_>>();
+    assert_eq!(hashes(&a), hashes(&b));
+
+    let tiles = a.into_tiles();
+    assert_eq!(tiles.len(), 2);
+    assert_eq!(TileSet::new(tiles).len(), 2);
+}
+
+#[test]
+fn push() {
+    let mut set = TileSet::new(vec![solid([255, 0, 0], 4)]);
+    set.push(solid([0, 255, 0], 4));
+    assert_eq!(set.len(), 2);
+    assert!(set.iter().any(|t| t.avg() == &Rgb([0, 255, 0])));
+
+    // the new tile is matched like any other
+    let img = image::DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([10, 240, 10])));
+    let mosaic = Mosaic::new(img, set, 1.0, 4);
+    assert_eq!(mosaic.to_image().get_pixel(0, 0), &Rgb([0, 255, 0]));
+}
+
+#[test]
+#[should_panic(expected = "requires 4px square tiles")]
+fn push_mismatched_size() {
+    let mut set = TileSet::new(vec![solid([255, 0, 0], 4)]);
+    set.push(solid([0, 255, 0], 8));
+}
+
+#[test]
+#[should_panic(expected = "Cannot build a tile set from no images")]
+fn new_empty() {
+    TileSet::new(Vec::new());
+}
+
+#[test]
+fn reused_across_mosaics() {
+    let tiles = TileSet::from(&solid_tiles());
+    for (w, h) in [(4, 4), (6, 3)] {
+        let reused = Mosaic::new(small_gradient(w, h), tiles.clone(), 1.0, 8);
+        let built = Mosaic::new(small_gradient(w, h), solid_tiles(), 1.0, 8);
+        assert_eq!(reused.plan(), built.plan());
+        assert_eq!(reused.to_image(), built.to_image());
+    }
+}
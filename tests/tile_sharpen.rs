@@ -0,0 +1,92 @@
+//! Test sharpening tiles after scaling them
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions, TileSet};
+use utils::solid;
+
+fn sharpen(tile_sharpen: f32) -> MosaicOptions {
+    MosaicOptions {
+        tile_sharpen,
+        ..Default::default()
+    }
+}
+
+/// A tile with a soft vertical edge between dark and light halves
+fn edge_tile(size: u32) -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(size, size, |x, _| {
+        let t = (x as f32 / (size - 1) as f32 - 0.5) * 4.0;
+        let v = (128.0 + t.clamp(-1.0, 1.0) * 100.0) as u8;
+        Rgb([v, v, v])
+    }))
+}
+
+/// The total difference between horizontally adjacent pixels
+fn local_contrast(img: &RgbImage) -> u32 {
+    let (w, h) = img.dimensions();
+    (0..h)
+        .flat_map(|y| (1..w).map(move |x| (x, y)))
+        .map(|(x, y)| img.get_pixel(x, y).0[0].abs_diff(img.get_pixel(x - 1, y).0[0]) as u32)
+        .sum()
+}
+
+#[test]
+fn sharper_edges() {
+    let set = TileSet::from(&vec![edge_tile(64)]);
+    let mut plain = set.clone();
+    plain.scale_tiles(8);
+    let mut sharp = set.clone();
+    sharp.scale_tiles_with(8, &sharpen(1.0));
+
+    let (plain, sharp) = (plain.get(0).unwrap(), sharp.get(0).unwrap());
+    assert_eq!(sharp.side_len(), 8);
+    assert!(
+        local_contrast(&sharp.img()) > local_contrast(&plain.img()),
+        "{} <= {}",
+        local_contrast(&sharp.img()),
+        local_contrast(&plain.img())
+    );
+}
+
+#[test]
+fn zero_is_a_no_op() {
+    let imgs = vec![edge_tile(64), solid(&(30, 160, 90), 40, 40)];
+    let set = TileSet::from(&imgs);
+    let mut plain = set.clone();
+    plain.scale_tiles(8);
+    let mut sharp = set.clone();
+    sharp.scale_tiles_with(8, &sharpen(0.0));
+
+    for (p, s) in plain.iter().zip(sharp.iter()) {
+        assert_eq!(p.img(), s.img());
+        assert_eq!(p.avg(), s.avg());
+        assert_eq!(p.content_hash(), s.content_hash());
+    }
+}
+
+#[test]
+fn averages_follow_sharpening() {
+    let options = MosaicOptions {
+        tile_sharpen: 2.0,
+        ..Default::default()
+    };
+    let src = solid(&(128, 128, 128), 2, 2);
+    let mosaic = Mosaic::with_options(src, vec![edge_tile(64)], 1.0, 8, options);
+    let tile = mosaic.tiles().get(0).unwrap();
+
+    let mut plain = TileSet::from(&vec![edge_tile(64)]);
+    plain.scale_tiles(8);
+    assert_ne!(tile.img(), plain.get(0).unwrap().img());
+
+    // the average is of the sharpened pixels
+    let n = tile.img().pixels().len() as u32;
+    let total: u32 = tile.img().pixels().map(|px| px.0[0] as u32).sum();
+    assert_eq!(tile.avg().0[0] as u32, total / n);
+}
+
+#[test]
+#[should_panic]
+fn rejects_negative_amount() {
+    TileSet::from(&vec![edge_tile(16)]).scale_tiles_with(4, &sharpen(-1.0));
+}
@@ -0,0 +1,116 @@
+//! Test keeping the pixels of tiles in a memory-mapped file
+#![cfg(feature = "mmap")]
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{MosaicOptions, MosaicPlan, TileSet, TileStore};
+use utils::{scratch_dir, small_gradient};
+
+/// Tiles with a pattern (so a tile in the wrong place, or out of order,
+/// changes the render) in a range of colors
+fn tiles() -> TileSet {
+    let imgs: Vec<DynamicImage> = (0..12u32)
+        .map(|i| {
+            let img = RgbImage::from_fn(6, 6, |x, y| {
+                let v = ((x * 7 + y * 13 + i * 31) % 64) as u8;
+                Rgb([(i * 20) as u8 + v / 2, 200 - (i * 15) as u8 + v / 2, v * 3])
+            });
+            DynamicImage::ImageRgb8(img)
+        })
+        .collect();
+    TileSet::from(&imgs)
+}
+
+#[test]
+fn render_matches() {
+    let src = small_gradient(23, 17).to_rgb8();
+    let mut tiles = tiles();
+    let plan = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default());
+    let expected = plan.render(&tiles);
+
+    let store = tiles
+        .store(
+            &scratch_dir("tile_store-render")
+                .unwrap()
+                .join("render.tiles"),
+        )
+        .unwrap();
+    assert_eq!(store.len(), 12);
+    assert_eq!(store.tile_side_len(), 6);
+    assert!(tiles.iter().all(|t| t.is_stored()));
+    assert_eq!(plan.render_stored(&store), expected);
+
+    // the averages are still there to build new plans
+    let again = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default());
+    assert_eq!(again.render_stored(&store), expected);
+}
+
+#[test]
+fn reopen() {
+    let path = scratch_dir("tile_store-reopen")
+        .unwrap()
+        .join("reopen.tiles");
+    let tiles = tiles();
+    TileStore::create(&path, &tiles).unwrap();
+
+    let store = TileStore::open(&path).unwrap();
+    assert_eq!(store.len(), tiles.len());
+    for (i, tile) in tiles.iter().enumerate() {
+        assert_eq!(
+            *store.get(i).unwrap().as_raw(),
+            tile.img().as_raw().as_slice()
+        );
+    }
+    assert!(store.get(tiles.len()).is_none());
+}
+
+#[test]
+fn not_a_store() {
+    let dir = scratch_dir("tile_store-not_a_store").unwrap();
+    let path = dir.join("truncated.tiles");
+    TileStore::create(&path, &tiles()).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    assert!(TileStore::open(&path).is_err());
+
+    let path = dir.join("garbage.tiles");
+    std::fs::write(&path, b"not a tile store at all").unwrap();
+    assert!(TileStore::open(&path).is_err());
+}
+
+#[test]
+fn stored_pixels() {
+    let plain = tiles();
+    let mut tiles = tiles();
+    tiles
+        .store(
+            &scratch_dir("tile_store-stored")
+                .unwrap()
+                .join("stored.tiles"),
+        )
+        .unwrap();
+    for (stored, plain) in tiles.iter().zip(plain.iter()) {
+        assert_eq!(stored.pixels().as_raw(), plain.pixels().as_raw());
+        assert_eq!(stored.img(), plain.img());
+    }
+
+    // the set can still be rendered from directly
+    let src = small_gradient(9, 7).to_rgb8();
+    let plan = MosaicPlan::for_image(&src, &tiles, MosaicOptions::default());
+    assert_eq!(plan.render(&tiles), plan.render(&plain));
+}
+
+#[test]
+fn oversized_header() {
+    let path = scratch_dir("tile_store-oversized")
+        .unwrap()
+        .join("oversized.tiles");
+    for (side_len, len) in [(u32::MAX, u32::MAX), (1 << 16, 1 << 31), (6, u32::MAX)] {
+        let mut bytes = b"TILRTS01".to_vec();
+        bytes.extend(side_len.to_le_bytes());
+        bytes.extend(len.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(TileStore::open(&path).is_err(), "{}x{}", side_len, len);
+    }
+}
@@ -0,0 +1,45 @@
+//! Test timing the phases of building a mosaic
+
+mod utils;
+
+use std::time::{Duration, Instant};
+use tilr::{Mosaic, Timings};
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn phases_sum_to_total() {
+    let start = Instant::now();
+    let mosaic = Mosaic::new(small_gradient(64, 48), solid_tiles(), 1.0, 8);
+    let (img, timings) = mosaic.render_timed();
+    let elapsed = start.elapsed();
+
+    assert_eq!(timings.averages, mosaic.timings().averages);
+    assert_eq!(img, mosaic.to_image());
+    assert!(timings.mapping > Duration::ZERO);
+    assert!(timings.placement > Duration::ZERO);
+    assert_eq!(timings.load, Duration::ZERO);
+    assert_eq!(timings.encoding, Duration::ZERO);
+    assert_eq!(timings.post_process, Duration::ZERO);
+
+    let sum: Duration = timings.phases().iter().map(|(_, d)| *d).sum();
+    assert_eq!(sum, timings.total());
+    assert!(timings.total() <= elapsed);
+}
+
+#[test]
+fn measure_accumulates() {
+    let mut timings = Timings::default();
+    Timings::measure(&mut timings.load, || {
+        std::thread::sleep(Duration::from_millis(5))
+    });
+    Timings::measure(&mut timings.load, || {
+        std::thread::sleep(Duration::from_millis(5))
+    });
+    assert!(timings.load >= Duration::from_millis(10));
+    assert_eq!(timings.total(), timings.load);
+
+    let table = timings.to_string();
+    assert!(table.contains("load"));
+    assert!(table.contains("100.0%"));
+    assert!(table.lines().last().unwrap().contains("total"));
+}
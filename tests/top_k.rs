@@ -0,0 +1,140 @@
+//! Test picking randomly among the closest tiles to each cell
+
+use assert_cmd::Command;
+use image::{DynamicImage, Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use tilr::{Mosaic, MosaicOptions, MosaicPlan, TileSet};
+
+mod utils;
+use utils::scratch_dir;
+
+const GRAY: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// Tiles at increasing distances from `GRAY`
+fn tiles() -> TileSet {
+    TileSet::from(&images())
+}
+
+fn images() -> Vec<DynamicImage> {
+    [0u8, 1, 2, 3, 40, 80]
+        .into_iter()
+        .map(|d| DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([128 + d, 128, 128]))))
+        .collect()
+}
+
+fn options(top_k: u32, top_k_tolerance: Option<f32>, seed: u64) -> MosaicOptions {
+    MosaicOptions {
+        top_k,
+        top_k_tolerance,
+        seed,
+        ..Default::default()
+    }
+}
+
+/// The red channels of the tiles used in a plan, without repeats
+fn used(plan: &MosaicPlan, tiles: &TileSet) -> Vec<u8> {
+    let mut used: Vec<u8> = plan
+        .cells()
+        .iter()
+        .map(|&i| tiles.get(i).unwrap().avg().0[0])
+        .collect();
+    used.sort_unstable();
+    used.dedup();
+    used
+}
+
+#[test]
+fn picks_among_k_closest() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(16, 16, GRAY);
+
+    let plan = MosaicPlan::for_image(&img, &tiles, options(1, None, 0));
+    assert_eq!(used(&plan, &tiles), [128]);
+
+    let plan = MosaicPlan::for_image(&img, &tiles, options(3, None, 0));
+    assert_eq!(used(&plan, &tiles), [128, 129, 130]);
+
+    // k beyond the size of the set picks among every tile
+    let plan = MosaicPlan::for_image(&img, &tiles, options(100, None, 0));
+    assert_eq!(used(&plan, &tiles), [128, 129, 130, 131, 168, 208]);
+}
+
+#[test]
+fn tolerance_excludes_poor_matches() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(16, 16, GRAY);
+    let plan = MosaicPlan::for_image(&img, &tiles, options(6, Some(5.0), 0));
+    assert_eq!(used(&plan, &tiles), [128, 129, 130, 131]);
+
+    let plan = MosaicPlan::for_image(&img, &tiles, options(6, Some(0.0), 0));
+    assert_eq!(used(&plan, &tiles), [128]);
+}
+
+#[test]
+fn seeded() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(16, 16, GRAY);
+    let plan = |seed| MosaicPlan::for_image(&img, &tiles, options(4, None, seed));
+    assert_eq!(plan(1).cells(), plan(1).cells());
+    assert_ne!(plan(1).cells(), plan(2).cells());
+}
+
+#[test]
+fn through_mosaic() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, GRAY));
+    let mosaic = Mosaic::with_options(img, images(), 1.0, 2, options(2, None, 7));
+    assert_eq!(mosaic.options().top_k, 2);
+    assert_eq!(used(&mosaic.plan(), mosaic.tiles()), [128, 129]);
+}
+
+#[test]
+fn cli() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("top_k")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    for (i, img) in images().into_iter().enumerate() {
+        img.save(tile_dir.join(format!("{}.png", i)))?;
+    }
+    RgbImage::from_pixel(8, 8, GRAY).save(dir.join("src.png"))?;
+
+    let run = |seed: &str, output: &PathBuf| -> Result<RgbImage, Box<dyn Error>> {
+        Command::cargo_bin("tilr")?
+            .arg(dir.join("src.png"))
+            .arg("-t")
+            .arg(&tile_dir)
+            .args([
+                "--tile-size",
+                "2",
+                "--top-k",
+                "3",
+                "--top-k-tolerance",
+                "10",
+            ])
+            .args(["--seed", seed, "--yes", "-o"])
+            .arg(output)
+            .assert()
+            .success();
+        Ok(image::open(output)?.to_rgb8())
+    };
+    let a = run("1", &dir.join("a.png"))?;
+    assert_eq!(a, run("1", &dir.join("b.png"))?);
+    assert_ne!(a, run("2", &dir.join("c.png"))?);
+    let mut reds: Vec<u8> = a.pixels().map(|px| px.0[0]).collect();
+    reds.sort_unstable();
+    reds.dedup();
+    assert_eq!(reds, [128, 129, 130]);
+
+    // the tolerance only applies to --top-k
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--top-k-tolerance", "10", "--yes", "-o"])
+        .arg(dir.join("d.png"))
+        .assert()
+        .failure();
+
+    Ok(())
+}
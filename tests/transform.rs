@@ -0,0 +1,84 @@
+//! Test rotating and flipping the source image
+
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions, Rotation, Transform};
+
+const MARKER: Rgb<u8> = Rgb([255, 0, 0]);
+
+/// A 4x2 black image with a red marker pixel in the top left corner
+fn marked() -> DynamicImage {
+    let mut img = RgbImage::new(4, 2);
+    img.put_pixel(0, 0, MARKER);
+    DynamicImage::ImageRgb8(img)
+}
+
+/// Find the marker pixel in an image
+fn marker(img: &RgbImage) -> (u32, u32) {
+    let (x, y, _) = img
+        .enumerate_pixels()
+        .find(|(_, _, px)| **px == MARKER)
+        .expect("marker pixel");
+    (x, y)
+}
+
+fn transform(rotate: Rotation, flip_h: bool, flip_v: bool) -> Transform {
+    Transform {
+        rotate,
+        flip_h,
+        flip_v,
+    }
+}
+
+#[test]
+fn marker_corners() {
+    let cases = [
+        (transform(Rotation::None, false, false), (4, 2), (0, 0)),
+        (transform(Rotation::Cw90, false, false), (2, 4), (1, 0)),
+        (transform(Rotation::Cw180, false, false), (4, 2), (3, 1)),
+        (transform(Rotation::Cw270, false, false), (2, 4), (0, 3)),
+        (transform(Rotation::None, true, false), (4, 2), (3, 0)),
+        (transform(Rotation::None, false, true), (4, 2), (0, 1)),
+        (transform(Rotation::None, true, true), (4, 2), (3, 1)),
+        // rotation is applied before flips
+        (transform(Rotation::Cw90, true, false), (2, 4), (0, 0)),
+        (transform(Rotation::Cw90, false, true), (2, 4), (1, 3)),
+    ];
+
+    for (t, size, corner) in cases {
+        let img = t.apply(marked());
+        assert_eq!(img.dimensions(), size, "{:?}", t);
+        assert_eq!(t.output_size((4, 2)), size, "{:?}", t);
+        assert_eq!(marker(&img.to_rgb8()), corner, "{:?}", t);
+    }
+}
+
+#[test]
+fn applied_before_scaling() {
+    let tiles = vec![
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, MARKER)),
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([0, 0, 0]))),
+    ];
+    let options = MosaicOptions {
+        transform: transform(Rotation::Cw90, false, false),
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(marked(), &tiles, 1.0, 2, options);
+
+    assert_eq!(mosaic.source().dimensions(), (2, 4));
+    assert_eq!(mosaic.output_size(), (4, 8));
+    assert_eq!(marker(mosaic.source()), (1, 0));
+
+    let plan = mosaic.plan();
+    assert_eq!(plan.options().transform, options.transform);
+    let rendered = plan.render(mosaic.tiles());
+    assert_eq!(rendered.get_pixel(3, 0), &MARKER);
+    assert_eq!(rendered.get_pixel(0, 0), &Rgb([0, 0, 0]));
+}
+
+#[test]
+fn rotation_from_degrees() {
+    assert_eq!(Rotation::from_degrees(0), Some(Rotation::None));
+    assert_eq!(Rotation::from_degrees(90), Some(Rotation::Cw90));
+    assert_eq!(Rotation::from_degrees(450), Some(Rotation::Cw90));
+    assert_eq!(Rotation::from_degrees(45), None);
+}
@@ -0,0 +1,91 @@
+//! Test using each tile at most once
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Mosaic, MosaicOptions};
+use utils::solid;
+
+fn unique() -> MosaicOptions {
+    MosaicOptions {
+        unique: true,
+        ..Default::default()
+    }
+}
+
+fn gray(v: u8) -> DynamicImage {
+    solid(&(v, v, v), 2, 2)
+}
+
+#[test]
+fn every_tile_once() {
+    // a 3x3 source of nearly the same color, which would otherwise use
+    // the same tile for every cell
+    let src = DynamicImage::ImageRgb8(RgbImage::from_fn(3, 3, |x, y| {
+        let v = 120 + (x + y) as u8;
+        Rgb([v, v, v])
+    }));
+    let tiles: Vec<DynamicImage> = (0..9).map(|i| gray(i * 30)).collect();
+
+    let repeated = Mosaic::new(src.clone(), &tiles, 1.0, 2).plan();
+    let mut used = repeated.cells().to_vec();
+    used.sort();
+    used.dedup();
+    assert!(used.len() < 9);
+
+    let mosaic = Mosaic::with_options(src, &tiles, 1.0, 2, unique());
+    let plan = mosaic.plan();
+    let mut cells = plan.cells().to_vec();
+    cells.sort();
+    assert_eq!(cells, (0..9).collect::<Vec<_>>());
+
+    // every tile appears in the rendered mosaic
+    let img = plan.render(mosaic.tiles());
+    let mut colors: Vec<u8> = img.pixels().map(|px| px.0[0]).collect();
+    colors.sort();
+    colors.dedup();
+    assert_eq!(colors, (0..9).map(|i| i * 30).collect::<Vec<u8>>());
+}
+
+#[test]
+fn global_assignment() {
+    // the first cell would take the tile the second one matches exactly
+    let src = DynamicImage::ImageRgb8(RgbImage::from_fn(2, 1, |x, _| {
+        let v = 100 + x as u8;
+        Rgb([v, v, v])
+    }));
+    let tiles = vec![gray(101), gray(60)];
+    let mosaic = Mosaic::with_options(src, &tiles, 1.0, 2, unique());
+    let plan = mosaic.plan();
+    let avgs: Vec<u8> = (0..2)
+        .map(|x| mosaic.tiles().get(plan.tile_at(x, 0)).unwrap().avg().0[0])
+        .collect();
+    assert_eq!(avgs, [60, 101]);
+
+    let total: f32 = plan.distances().iter().sum();
+    assert!((total - 40.0 * 3f32.sqrt()).abs() < 1e-3);
+}
+
+#[test]
+fn more_tiles_than_cells() {
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([200, 10, 10])));
+    let tiles: Vec<DynamicImage> = (0..6).map(|i| solid(&(i * 50, 10, 10), 2, 2)).collect();
+    let mosaic = Mosaic::with_options(src, &tiles, 1.0, 2, unique());
+    let plan = mosaic.plan();
+    let mut cells = plan.cells().to_vec();
+    cells.sort();
+    cells.dedup();
+    assert_eq!(cells.len(), 4);
+    // the four reddest tiles are used
+    for &idx in plan.cells() {
+        assert!(mosaic.tiles().get(idx).unwrap().avg().0[0] >= 100);
+    }
+}
+
+#[test]
+#[should_panic(expected = "4 tiles, 9 cells")]
+fn too_few_tiles() {
+    let src = DynamicImage::ImageRgb8(RgbImage::new(3, 3));
+    let tiles: Vec<DynamicImage> = (0..4).map(|i| gray(i * 60)).collect();
+    Mosaic::with_options(src, &tiles, 1.0, 2, unique()).plan();
+}
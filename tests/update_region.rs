@@ -0,0 +1,63 @@
+//! Test re-rendering part of a mosaic
+
+mod utils;
+
+use image::Rgb;
+use tilr::{Mosaic, Rect};
+use utils::{small_gradient, solid_tiles};
+
+fn mosaic() -> Mosaic {
+    Mosaic::new(small_gradient(16, 12), solid_tiles(), 1.0, 4)
+}
+
+#[test]
+fn matches_full_render() {
+    let mut mosaic = mosaic();
+    let mut output = mosaic.plan().render(mosaic.tiles());
+
+    // paint over a few pixels of the source
+    for (x, y) in [(3, 4), (4, 4), (5, 6), (6, 7)] {
+        mosaic.source_mut().put_pixel(x, y, Rgb([255, 0, 255]));
+    }
+    let before = output.clone();
+    mosaic.update_region(&mut output, Rect::new(3, 4, 4, 4));
+
+    assert_ne!(output, before);
+    assert_eq!(output, mosaic.plan().render(mosaic.tiles()));
+}
+
+#[test]
+fn leaves_other_cells_untouched() {
+    let mut mosaic = mosaic();
+    let mut output = mosaic.plan().render(mosaic.tiles());
+
+    // the painted pixel lies outside the updated region
+    mosaic.source_mut().put_pixel(0, 0, Rgb([255, 0, 255]));
+    mosaic.source_mut().put_pixel(10, 10, Rgb([255, 0, 255]));
+    let before = output.clone();
+    mosaic.update_region(&mut output, Rect::new(8, 8, 4, 4));
+
+    assert_eq!(&output.get_pixel(0, 0), &before.get_pixel(0, 0));
+    assert_ne!(output, before);
+    for (x, y, px) in output.enumerate_pixels() {
+        let in_region = (32..48).contains(&x) && (32..48).contains(&y);
+        if !in_region {
+            assert_eq!(px, before.get_pixel(x, y), "pixel ({}, {}) changed", x, y);
+        }
+    }
+}
+
+#[test]
+fn clips_to_source() {
+    let mut mosaic = mosaic();
+    let mut output = mosaic.plan().render(mosaic.tiles());
+
+    mosaic.source_mut().put_pixel(15, 11, Rgb([255, 0, 255]));
+    mosaic.update_region(&mut output, Rect::new(14, 10, 100, 100));
+    assert_eq!(output, mosaic.plan().render(mosaic.tiles()));
+
+    // entirely outside the source; nothing to do
+    let before = output.clone();
+    mosaic.update_region(&mut output, Rect::new(100, 100, 5, 5));
+    assert_eq!(output, before);
+}
@@ -0,0 +1,92 @@
+//! Test drawing which tile each cell of a mosaic uses
+
+mod utils;
+
+use image::{DynamicImage, Rgb, Rgba, RgbaImage};
+use tilr::{Mosaic, MosaicOptions, MosaicPlan};
+use utils::solid;
+
+const COLORS: [[u8; 3]; 3] = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+
+/// A plan for a 3x2 source whose top left pixel is transparent, and whose
+/// other pixels each match one of three tiles exactly:
+///
+/// ```text
+/// . 1 2
+/// 2 0 2
+/// ```
+///
+/// Returns the plan, and the index of each tile in it.
+fn plan() -> (MosaicPlan, [usize; 3]) {
+    let layout = [[None, Some(1), Some(2)], [Some(2), Some(0), Some(2)]];
+    let src = RgbaImage::from_fn(3, 2, |x, y| match layout[y as usize][x as usize] {
+        Some(i) => {
+            let [r, g, b] = COLORS[i];
+            Rgba([r, g, b, 255])
+        }
+        None => Rgba([0; 4]),
+    });
+    let tiles: Vec<DynamicImage> = COLORS
+        .iter()
+        .map(|&[r, g, b]| solid(&(r, g, b), 2, 2))
+        .collect();
+    let options = MosaicOptions {
+        alpha_threshold: 1,
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(DynamicImage::ImageRgba8(src), &tiles, 1.0, 2, options);
+    let index = COLORS.map(|color| {
+        mosaic
+            .tiles()
+            .iter()
+            .position(|tile| tile.avg().0 == color)
+            .unwrap()
+    });
+    (mosaic.plan(), index)
+}
+
+/// The cells set in a mask, as `(x, y)`
+fn set_cells(mask: &image::GrayImage) -> Vec<(u32, u32)> {
+    mask.enumerate_pixels()
+        .filter(|(_, _, px)| px.0[0] == 255)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+#[test]
+fn mask() {
+    let (plan, index) = plan();
+    for tile in 0..3 {
+        let mask = plan.usage_mask(tile);
+        assert_eq!(mask.dimensions(), (3, 2));
+        // every pixel is either set or not
+        assert!(mask.pixels().all(|px| px.0[0] == 0 || px.0[0] == 255));
+    }
+    assert_eq!(set_cells(&plan.usage_mask(index[0])), [(1, 1)]);
+    assert_eq!(set_cells(&plan.usage_mask(index[1])), [(1, 0)]);
+    assert_eq!(
+        set_cells(&plan.usage_mask(index[2])),
+        [(2, 0), (0, 1), (2, 1)]
+    );
+    assert!(set_cells(&plan.usage_mask(3)).is_empty());
+}
+
+#[test]
+fn map() {
+    let (plan, index) = plan();
+    let map = plan.usage_map();
+    assert_eq!(map.dimensions(), (3, 2));
+    // the background is black, and the tiles run from blue (the first) to
+    // red (the last)
+    assert_eq!(*map.get_pixel(0, 0), Rgb([0, 0, 0]));
+    let heat = |i: usize| match index[i] {
+        0 => Rgb([0, 0, 255]),
+        2 => Rgb([255, 0, 0]),
+        _ => Rgb([0, 255, 0]),
+    };
+    assert_eq!(*map.get_pixel(1, 1), heat(0));
+    assert_eq!(*map.get_pixel(1, 0), heat(1));
+    for (x, y) in [(2, 0), (0, 1), (2, 1)] {
+        assert_eq!(*map.get_pixel(x, y), heat(2));
+    }
+}
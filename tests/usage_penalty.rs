@@ -0,0 +1,89 @@
+//! Test spreading cells between similar tiles with a usage penalty
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{MosaicOptions, MosaicPlan, TileSet};
+
+const GRAY: Rgb<u8> = Rgb([128, 128, 128]);
+
+/// Ten tiles, each exactly 9 away from `GRAY`
+fn tiles() -> TileSet {
+    let offsets: [[i32; 3]; 10] = [
+        [9, 0, 0],
+        [-9, 0, 0],
+        [0, 9, 0],
+        [0, -9, 0],
+        [0, 0, 9],
+        [0, 0, -9],
+        [6, 6, 3],
+        [6, -6, 3],
+        [-6, 6, -3],
+        [-6, -6, -3],
+    ];
+    let imgs: Vec<DynamicImage> = offsets
+        .iter()
+        .map(|d| {
+            let px = Rgb([0, 1, 2].map(|c| (GRAY.0[c] as i32 + d[c]) as u8));
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, px))
+        })
+        .collect();
+    TileSet::from(&imgs)
+}
+
+fn usage(plan: &MosaicPlan, tiles: &TileSet) -> Vec<usize> {
+    let mut counts = vec![0; tiles.len()];
+    for &i in plan.cells() {
+        counts[i] += 1;
+    }
+    counts
+}
+
+fn options(usage_penalty: f32, seed: u64) -> MosaicOptions {
+    MosaicOptions {
+        usage_penalty,
+        seed,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn spreads_usage() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(20, 10, GRAY);
+
+    // without a penalty, every cell gets the first of the tied tiles
+    let plan = MosaicPlan::for_image(&img, &tiles, MosaicOptions::default());
+    assert_eq!(usage(&plan, &tiles).iter().max(), Some(&200));
+
+    let plan = MosaicPlan::for_image(&img, &tiles, options(0.5, 0));
+    for count in usage(&plan, &tiles) {
+        assert!((15..=25).contains(&count), "{:?}", usage(&plan, &tiles));
+    }
+}
+
+#[test]
+fn prefers_closer_tiles() {
+    // a small penalty only breaks ties; a far tile is never used
+    let imgs = vec![
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, GRAY)),
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(2, 2, Rgb([0, 0, 0]))),
+    ];
+    let img = RgbImage::from_pixel(8, 8, GRAY);
+    let tiles = TileSet::from(&imgs);
+    let plan = MosaicPlan::for_image(&img, &tiles, options(0.5, 0));
+    let gray = (0..tiles.len()).find(|&i| tiles.get(i).unwrap().avg() == &GRAY);
+    assert!(plan.cells().iter().all(|&i| Some(i) == gray));
+
+    // ... but a strong one spreads cells between them
+    let plan = MosaicPlan::for_image(&img, &tiles, options(50.0, 0));
+    assert!(usage(&plan, &tiles).iter().all(|&n| n > 0));
+}
+
+#[test]
+fn reproducible_with_seed() {
+    let tiles = tiles();
+    let img = RgbImage::from_pixel(20, 10, GRAY);
+    let plan = |seed| MosaicPlan::for_image(&img, &tiles, options(0.5, seed));
+
+    assert_eq!(plan(1).cells(), plan(1).cells());
+    assert_ne!(plan(1).cells(), plan(2).cells());
+}
@@ -13,18 +13,21 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use image::io::Reader as ImageReader;
-use image::{DynamicImage, GenericImage, Pixel, Rgb};
+// Not every test uses every helper in this module
+#![allow(dead_code)]
+
+use image::{DynamicImage, GenericImage, ImageReader, Pixel, Rgb};
 use std::error::Error;
 use std::ops::{Add, Div, Sub};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Once;
 use std::{fs, io};
+use tilr::{Metric, TileSet};
 
 // Directory constants
-pub const TILE_DIR: &'static str = "images/tiles";
-pub const INPUT_DIR: &'static str = "images/input";
-pub const OUTPUT_DIR: &'static str = "images/output";
+pub const TILE_DIR: &str = "images/tiles";
+pub const INPUT_DIR: &str = "images/input";
+pub const OUTPUT_DIR: &str = "images/output";
 
 // Tile constants
 const TILE_WIDTH: u32 = 25;
@@ -87,20 +90,88 @@ fn make_dirs() -> io::Result<()> {
 
 /// Create the solid-color tile images
 fn make_tiles() -> Result<(), Box<dyn Error>> {
-    for (i, c) in COLORS.iter().enumerate() {
-        let mut img = DynamicImage::new_rgb8(TILE_WIDTH, TILE_HEIGHT);
-        for x in 0..TILE_WIDTH {
-            for y in 0..TILE_HEIGHT {
-                let px = Rgb([c.0, c.1, c.2]);
-                img.put_pixel(x, y, px.to_rgba());
-            }
+    for (i, img) in solid_tiles().iter().enumerate() {
+        img.to_rgb8().save(format!("{}/tile-{}.png", TILE_DIR, i))?;
+    }
+
+    Ok(())
+}
+
+/// Create an image of a single solid color
+pub fn solid(c: &(u8, u8, u8), w: u32, h: u32) -> DynamicImage {
+    let mut img = DynamicImage::new_rgb8(w, h);
+    for x in 0..w {
+        for y in 0..h {
+            let px = Rgb([c.0, c.1, c.2]);
+            img.put_pixel(x, y, px.to_rgba());
         }
+    }
 
-        let img = img.to_rgb8();
-        img.save(format!("{}/tile-{}.png", TILE_DIR, i))?;
+    img
+}
+
+/// Create the solid-color tiles (in memory) for each of the test colors
+pub fn solid_tiles() -> Vec<DynamicImage> {
+    COLORS
+        .iter()
+        .map(|c| solid(c, TILE_WIDTH, TILE_HEIGHT))
+        .collect()
+}
+
+/// Create a small purple-to-yellow gradient to use as a source image
+pub fn small_gradient(w: u32, h: u32) -> DynamicImage {
+    gradient(&PURPLE, &YELLOW, w, h)
+}
+
+/// A simple, seeded pseudo-random number generator (for test cases)
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    pub fn next_u8(&mut self) -> u8 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 56) as u8
     }
 
-    Ok(())
+    pub fn color(&mut self) -> Rgb<u8> {
+        Rgb([self.next_u8(), self.next_u8(), self.next_u8()])
+    }
+}
+
+/// The index and score of the best tile for a color, found by brute force
+pub fn brute_force(tiles: &TileSet, px: &Rgb<u8>, metric: Metric) -> (usize, f32) {
+    let mut best = (0, f32::INFINITY);
+    for (i, t) in tiles.iter().enumerate() {
+        let score = t.score(px, metric);
+        if score < best.1 {
+            best = (i, score);
+        }
+    }
+    best
+}
+
+/// Create a fresh, empty directory for a test to work in, named `name`
+/// (removing anything left there by a previous run)
+pub fn scratch_dir(name: &str) -> io::Result<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Create a scratch directory (see [`scratch_dir`]) with red and blue 8px
+/// tiles in `tiles/`, and a `w`x`h` gradient at `input.png` to use as a
+/// source image
+pub fn tiles_and_gradient(name: &str, w: u32, h: u32) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = scratch_dir(name)?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+    solid(&(255, 0, 0), 8, 8).save(tile_dir.join("red.png"))?;
+    solid(&(0, 0, 255), 8, 8).save(tile_dir.join("blue.png"))?;
+    small_gradient(w, h).save(dir.join("input.png"))?;
+    Ok(dir)
 }
 
 /// The core logic of these tests
@@ -124,7 +195,7 @@ pub fn make_mosaic(extension: &str) -> Result<(), Box<dyn Error>> {
 
     // create the mosaic
     let img = ImageReader::open(&img_path)?.decode()?.into_rgb8();
-    let tiles = tilr::load_tiles(Path::new(TILE_DIR))?;
+    let tiles = tilr::load_tiles(Path::new(TILE_DIR))?.tiles;
     let mosaic = tilr::Mosaic::new(
         DynamicImage::ImageRgb8(img),
         &tiles,
@@ -201,7 +272,7 @@ impl<T> Color<T>
 where
     T: Into<f32> + Copy,
 {
-    fn to_u8_array(&self) -> [u8; 3] {
+    fn to_u8_array(self) -> [u8; 3] {
         let r: f32 = (self.0).0.into();
         let g: f32 = (self.0).1.into();
         let b: f32 = (self.0).2.into();
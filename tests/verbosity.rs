@@ -0,0 +1,67 @@
+//! Test choosing how much the CLI prints with -v and -q
+
+use assert_cmd::Command;
+use std::fs;
+
+mod utils;
+use utils::tiles_and_gradient;
+
+/// Build a mosaic from tiles which include a file that can't be decoded,
+/// with the given verbosity flags, and get what was printed.
+fn tilr(name: &str, flags: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+    let dir = tiles_and_gradient(name, 40, 40)?;
+    let tile_dir = dir.join("tiles");
+    fs::write(tile_dir.join("broken.png"), b"not a png")?;
+
+    let output = Command::cargo_bin("tilr")?
+        .arg(dir.join("input.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--tile-size", "4", "--yes", "--scale", "0.05"])
+        .args(flags)
+        .arg("-o")
+        .arg(dir.join("out.png"))
+        .output()?;
+    assert!(output.status.success(), "{:?}", output);
+    assert!(dir.join("out.png").exists());
+    Ok(String::from_utf8(output.stderr)?)
+}
+
+#[test]
+fn default() -> Result<(), Box<dyn std::error::Error>> {
+    let stderr = tilr("verbosity_default", &[])?;
+    assert!(stderr.contains("Loading tiles..."), "{}", stderr);
+    assert!(stderr.contains("skipped 1 — run with -v"), "{}", stderr);
+    assert!(!stderr.contains("broken.png"), "{}", stderr);
+    Ok(())
+}
+
+#[test]
+fn verbose() -> Result<(), Box<dyn std::error::Error>> {
+    // the skipped file is logged by the library
+    let stderr = tilr("verbosity_verbose", &["-v"])?;
+    assert!(stderr.contains("Skipped "), "{}", stderr);
+    assert!(
+        stderr.contains("broken.png: unable to decode"),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
+
+#[test]
+fn quiet() -> Result<(), Box<dyn std::error::Error>> {
+    let stderr = tilr("verbosity_quiet", &["-q"])?;
+    assert!(!stderr.contains("Loading tiles"), "{}", stderr);
+    assert!(!stderr.contains("Placing tile"), "{}", stderr);
+    // warnings are still printed
+    assert!(
+        stderr.contains("Warning: scaling by 0.05 leaves only 2x2 cells."),
+        "{}",
+        stderr
+    );
+
+    let stderr = tilr("verbosity_silent", &["-qq"])?;
+    assert!(stderr.is_empty(), "{}", stderr);
+    Ok(())
+}
@@ -0,0 +1,144 @@
+//! Test checking mosaics against the plans they were built from
+
+use assert_cmd::Command;
+use image::{Rgb, RgbImage};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+mod utils;
+use utils::scratch_dir;
+
+/// Build a small mosaic in `dir` (with its plan next to it), returning
+/// the path to the mosaic and the tile directory
+fn build(dir: &Path) -> Result<(PathBuf, PathBuf), Box<dyn Error>> {
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(&tile_dir)?;
+
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(tile_dir.join("red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(tile_dir.join("blue.png"))?;
+    let src = RgbImage::from_fn(6, 4, |x, _| Rgb([x as u8 * 40, 0, 255 - x as u8 * 40]));
+    src.save(dir.join("src.png"))?;
+
+    let mosaic = dir.join("mosaic.png");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--tile-size", "4", "--yes", "-o"])
+        .arg(&mosaic)
+        .arg("--sidecar")
+        .arg(dir.join("mosaic.json"))
+        .assert()
+        .success();
+    Ok((mosaic, tile_dir))
+}
+
+fn verify(mosaic: &Path, tile_dir: &Path, deep: bool) -> Command {
+    let mut cmd = Command::cargo_bin("tilr").unwrap();
+    cmd.arg("verify").arg(mosaic).arg("-t").arg(tile_dir);
+    if deep {
+        cmd.arg("--deep");
+    }
+    cmd
+}
+
+#[test]
+fn pristine() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("verify-pristine")?;
+    let (mosaic, tile_dir) = build(&dir)?;
+    verify(&mosaic, &tile_dir, false).assert().code(0);
+    verify(&mosaic, &tile_dir, true).assert().code(0);
+    Ok(())
+}
+
+#[test]
+fn modified_pixel() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("verify-modified")?;
+    let (mosaic, tile_dir) = build(&dir)?;
+
+    let mut img = image::open(&mosaic)?.to_rgb8();
+    let px = img.get_pixel_mut(5, 5);
+    px.0[1] = px.0[1].wrapping_add(64);
+    img.save(&mosaic)?;
+
+    verify(&mosaic, &tile_dir, false).assert().code(1);
+    let assert = verify(&mosaic, &tile_dir, true).assert().code(1);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("1 of 24 cells"), "{}", stdout);
+
+    // a generous enough tolerance lets it through
+    verify(&mosaic, &tile_dir, true)
+        .args(["--tolerance", "10"])
+        .assert()
+        .code(0);
+    Ok(())
+}
+
+#[test]
+fn missing_tile() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("verify-missing-tile")?;
+    let (mosaic, tile_dir) = build(&dir)?;
+    fs::remove_file(tile_dir.join("blue.png"))?;
+
+    let assert = verify(&mosaic, &tile_dir, true).assert().code(1);
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout);
+    assert!(stdout.contains("missing"), "{}", stdout);
+    Ok(())
+}
+
+#[test]
+fn no_metadata() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("verify-no-metadata")?;
+    let (mosaic, tile_dir) = build(&dir)?;
+    let other = dir.join("other.png");
+    fs::copy(&mosaic, &other)?;
+    verify(&other, &tile_dir, false).assert().code(2);
+    verify(&other, &tile_dir, true).assert().code(2);
+
+    // plans saved without a hash can only be checked with --deep
+    let plan = tilr::MosaicPlan::load(&dir.join("mosaic.json"))?;
+    let mut json: serde_json::Value = serde_json::to_value(&plan)?;
+    json.as_object_mut().unwrap().remove("output_sha256");
+    fs::write(dir.join("other.json"), json.to_string())?;
+    verify(&other, &tile_dir, false).assert().code(2);
+    verify(&other, &tile_dir, true).assert().code(0);
+    Ok(())
+}
+
+#[test]
+fn recursive() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("verify-recursive")?;
+    let tile_dir = dir.join("tiles");
+    fs::create_dir_all(tile_dir.join("warm"))?;
+    fs::create_dir_all(tile_dir.join("cool"))?;
+    RgbImage::from_pixel(4, 4, Rgb([255, 0, 0])).save(tile_dir.join("warm/red.png"))?;
+    RgbImage::from_pixel(4, 4, Rgb([0, 0, 255])).save(tile_dir.join("cool/blue.png"))?;
+    let src = RgbImage::from_fn(6, 4, |x, _| Rgb([x as u8 * 40, 0, 255 - x as u8 * 40]));
+    src.save(dir.join("src.png"))?;
+
+    let mosaic = dir.join("mosaic.png");
+    Command::cargo_bin("tilr")?
+        .arg(dir.join("src.png"))
+        .arg("-t")
+        .arg(&tile_dir)
+        .args(["--recursive", "--tile-size", "4", "--yes", "-o"])
+        .arg(&mosaic)
+        .arg("--sidecar")
+        .arg(dir.join("mosaic.json"))
+        .assert()
+        .success();
+
+    // the tiles are only found in the subdirectories with --recursive
+    verify(&mosaic, &tile_dir, true).assert().code(1);
+    verify(&mosaic, &tile_dir, true)
+        .arg("--recursive")
+        .assert()
+        .code(0);
+    // and only the selected ones are used
+    verify(&mosaic, &tile_dir, true)
+        .args(["--recursive", "--tile-exclude", "cool/*"])
+        .assert()
+        .code(1);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Test building mosaics of videos
+#![cfg(feature = "video")]
+
+mod utils;
+
+use image::GenericImageView;
+use std::process::Command;
+use tilr::{FrameSampling, Mosaic, VideoMosaic};
+use utils::{scratch_dir, small_gradient, solid_tiles};
+
+/// Check whether ffmpeg (and ffprobe) can be run
+fn ffmpeg_available() -> bool {
+    ["ffmpeg", "ffprobe"]
+        .iter()
+        .all(|p| Command::new(p).arg("-version").output().is_ok())
+}
+
+#[test]
+fn frames_match_mosaics() {
+    let mut video = VideoMosaic::new(&solid_tiles(), 0.5, 4);
+    for frame in [small_gradient(20, 16), small_gradient(16, 20)] {
+        let expected = Mosaic::new(frame.clone(), solid_tiles(), 0.5, 4).to_image();
+        let (w, h) = (frame.width(), frame.height());
+        assert_eq!(video.output_size((w, h)), expected.dimensions());
+        assert_eq!(video.render_frame(frame.into_rgb8()), expected);
+    }
+
+    // repeating a frame needs no new searches
+    let searches = video.cache().searches();
+    video.render_frame(small_gradient(20, 16).into_rgb8());
+    assert_eq!(video.cache().searches(), searches);
+}
+
+#[test]
+fn end_to_end() {
+    let dir = scratch_dir("video").unwrap();
+    let (input, output) = (dir.join("input.mp4"), dir.join("output.mp4"));
+    let mut video = VideoMosaic::new(&solid_tiles(), 1.0, 4);
+
+    if !ffmpeg_available() {
+        // without ffmpeg, there should be a clear error rather than a panic
+        let err = video.render(&input, &output).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+        eprintln!("ffmpeg is not installed; skipping end-to-end test");
+        return;
+    }
+
+    // a 5-frame, 24x16 test clip
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-f", "lavfi"])
+        .args(["-i", "testsrc=size=24x16:rate=5", "-frames:v", "5"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(video.render(&input, &output).unwrap(), 5);
+
+    let probe = Command::new("ffprobe")
+        .args(["-v", "error", "-count_frames", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=nb_read_frames,width,height"])
+        .args(["-of", "csv=p=0"])
+        .arg(&output)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(probe.stdout).unwrap();
+    assert_eq!(stdout.trim(), "96,64,5");
+
+    // a missing input is an error
+    assert!(video.render(&dir.join("missing.mp4"), &output).is_err());
+}
+
+#[test]
+fn frame_sampling() {
+    assert_eq!(FrameSampling::Every(3).indices(10), [0, 3, 6, 9]);
+    assert_eq!(FrameSampling::Every(1).indices(3), [0, 1, 2]);
+    assert_eq!(FrameSampling::Count(4).indices(10), [0, 2, 5, 7]);
+    // there may be fewer frames than asked for
+    assert_eq!(FrameSampling::Count(5).indices(3), [0, 1, 2]);
+    assert!(FrameSampling::Count(5).indices(0).is_empty());
+}
+
+#[test]
+fn frames_as_tiles() {
+    let dir = scratch_dir("video-frames").unwrap();
+    let input = dir.join("input.mp4");
+
+    if !ffmpeg_available() {
+        let err = tilr::video_frames(&input, FrameSampling::Count(2), None).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+        eprintln!("ffmpeg is not installed; skipping end-to-end test");
+        return;
+    }
+
+    // a 10-frame, 48x32 test clip
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-f", "lavfi"])
+        .args(["-i", "testsrc=size=48x32:rate=10", "-frames:v", "10"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let frames = tilr::video_frames(&input, FrameSampling::Every(4), None).unwrap();
+    assert_eq!(frames.len(), 3);
+    assert_eq!(frames[0].dimensions(), (48, 32));
+
+    // frames are scaled down to the size asked for
+    let frames = tilr::video_frames(&input, FrameSampling::Count(2), Some(8)).unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].dimensions(), (12, 8));
+
+    // and can be used as tiles
+    let mosaic = Mosaic::new(small_gradient(6, 4), frames, 1.0, 4).to_image();
+    assert_eq!(mosaic.dimensions(), (24, 16));
+}
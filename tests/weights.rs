@@ -0,0 +1,86 @@
+//! Test weighting tiles to influence which tiles are chosen
+
+mod utils;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use tilr::{Metric, Mosaic};
+use utils::solid;
+
+/// Find the index of the tile with the given average color
+fn index_of(mosaic: &Mosaic, color: [u8; 3]) -> usize {
+    mosaic
+        .tiles()
+        .iter()
+        .position(|t| t.avg().0 == color)
+        .expect("No tile with that color")
+}
+
+/// Build a single-pixel mosaic from red & blue tiles
+fn red_blue(px: [u8; 3]) -> Mosaic {
+    let src = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(px)));
+    let tiles = vec![solid(&(200, 0, 0), 2, 2), solid(&(0, 0, 200), 2, 2)];
+    Mosaic::new(src, &tiles, 1.0, 2)
+}
+
+#[test]
+fn weight_breaks_ties() {
+    let mut mosaic = red_blue([100, 0, 100]);
+    let red = index_of(&mosaic, [200, 0, 0]);
+    let blue = index_of(&mosaic, [0, 0, 200]);
+
+    mosaic.tiles_mut().set_weight(red, 2.0);
+    assert_eq!(mosaic.plan().tile_at(0, 0), red);
+
+    mosaic.tiles_mut().set_weight(red, 1.0);
+    mosaic.tiles_mut().set_weight(blue, 2.0);
+    assert_eq!(mosaic.plan().tile_at(0, 0), blue);
+}
+
+#[test]
+fn weight_beats_distance() {
+    let mut mosaic = red_blue([110, 0, 90]);
+    let red = index_of(&mosaic, [200, 0, 0]);
+    let blue = index_of(&mosaic, [0, 0, 200]);
+    assert_eq!(mosaic.plan().tile_at(0, 0), red);
+
+    mosaic.tiles_mut().set_weight(blue, 2.0);
+    assert_eq!(mosaic.plan().tile_at(0, 0), blue);
+}
+
+/// A larger set (where any search acceleration would be used) must choose
+/// exactly the same tiles as a brute-force search over the weighted scores.
+#[test]
+fn matches_brute_force() {
+    let mut tiles = Vec::new();
+    for r in (0..=255).step_by(51) {
+        for g in (0..=255).step_by(51) {
+            for b in (0..=255).step_by(85) {
+                tiles.push(solid(&(r as u8, g as u8, b as u8), 2, 2));
+            }
+        }
+    }
+    let src = RgbImage::from_fn(32, 32, |x, y| Rgb([(x * 8) as u8, (y * 8) as u8, 128]));
+    let mut mosaic = Mosaic::new(DynamicImage::ImageRgb8(src.clone()), &tiles, 1.0, 2);
+    let n = mosaic.tiles().len();
+    for i in (0..n).step_by(7) {
+        mosaic.tiles_mut().set_weight(i, 1.0 + (i % 5) as f32);
+    }
+    assert!(mosaic.tiles().has_weights());
+
+    let plan = mosaic.plan();
+    for (px, &idx) in src.pixels().zip(plan.cells()) {
+        let score = |t: &tilr::Tile| Metric::Rgb.distance(px, t.avg()) / t.weight();
+        let best = mosaic
+            .tiles()
+            .iter()
+            .map(score)
+            .fold(f32::INFINITY, f32::min);
+        assert_eq!(score(mosaic.tiles().get(idx).unwrap()), best);
+    }
+}
+
+#[test]
+#[should_panic]
+fn rejects_non_positive_weight() {
+    red_blue([0, 0, 0]).tiles_mut().set_weight(0, 0.0);
+}
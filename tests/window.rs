@@ -0,0 +1,59 @@
+//! Test rendering only a window of a mosaic
+
+mod utils;
+
+use image::GenericImageView;
+use tilr::{Mosaic, MosaicOptions, Rect};
+use utils::{small_gradient, solid_tiles};
+
+#[test]
+fn matches_crop() {
+    let mosaic = Mosaic::new(small_gradient(20, 16), solid_tiles(), 1.0, 4);
+    let plan = mosaic.plan();
+    let full = plan.render(mosaic.tiles());
+
+    // aligned to cells, straddling cells, a single pixel, and everything
+    for window in [
+        Rect::new(8, 4, 16, 12),
+        Rect::new(5, 3, 27, 10),
+        Rect::new(79, 63, 1, 1),
+        Rect::new(0, 0, 80, 64),
+    ] {
+        let img = plan.render_window(mosaic.tiles(), window);
+        assert_eq!(img.dimensions(), (window.width, window.height));
+        let crop = full.view(window.x, window.y, window.width, window.height);
+        assert_eq!(img, crop.to_image(), "{:?}", window);
+    }
+}
+
+#[test]
+fn background_cells() {
+    let mut src = small_gradient(12, 12).to_rgba8();
+    for y in 0..12 {
+        for x in 0..6 {
+            src.get_pixel_mut(x, y).0[3] = 0;
+        }
+    }
+    let options = MosaicOptions {
+        alpha_threshold: 128,
+        matte: [10, 20, 30],
+        ..Default::default()
+    };
+    let mosaic = Mosaic::with_options(src.into(), solid_tiles(), 1.0, 4, options);
+    let plan = mosaic.plan();
+    let full = plan.render(mosaic.tiles());
+
+    let window = Rect::new(18, 6, 12, 30);
+    let img = plan.render_window(mosaic.tiles(), window);
+    let crop = full.view(window.x, window.y, window.width, window.height);
+    assert_eq!(img, crop.to_image());
+}
+
+#[test]
+#[should_panic(expected = "does not lie within")]
+fn outside() {
+    let mosaic = Mosaic::new(small_gradient(20, 16), solid_tiles(), 1.0, 4);
+    mosaic
+        .plan()
+        .render_window(mosaic.tiles(), Rect::new(60, 60, 30, 4));
+}